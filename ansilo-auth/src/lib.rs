@@ -1,8 +1,8 @@
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use ansilo_core::{
     config::{AuthConfig, UserConfig},
-    err::{bail, Context, Result},
+    err::{bail, Context, Error, Result},
 };
 use ansilo_logging::info;
 use provider::{password::PasswordAuthProvider, AuthProvider};
@@ -16,7 +16,8 @@ pub mod service_user;
 #[derive(Clone)]
 pub struct Authenticator {
     /// The authentication config
-    conf: &'static AuthConfig,
+    /// This is swapped out when the users are reloaded
+    conf: Arc<RwLock<&'static AuthConfig>>,
     /// The authentication providers
     providers: Arc<Vec<(String, AuthProvider)>>,
 }
@@ -40,6 +41,42 @@ impl Authenticator {
             AuthProvider::Password(PasswordAuthProvider::default()),
         ));
 
+        Self::validate_users(conf, &providers)?;
+
+        Ok(Self {
+            conf: Arc::new(RwLock::new(conf)),
+            providers: Arc::new(providers),
+        })
+    }
+
+    /// Replaces the configured users with the supplied users.
+    ///
+    /// The auth providers cannot be changed after initialisation so any
+    /// users must reference the existing providers.
+    pub fn reload_users(&self, users: Vec<UserConfig>) -> Result<()> {
+        let current = self.conf();
+        let conf: &'static _ = Box::leak(Box::new(AuthConfig {
+            providers: current.providers.clone(),
+            users,
+            service_users: current.service_users.clone(),
+            rules: current.rules.clone(),
+            limits: current.limits.clone(),
+            masks: current.masks.clone(),
+            grants: current.grants.clone(),
+            workloads: current.workloads.clone(),
+        }));
+
+        Self::validate_users(conf, &self.providers)?;
+
+        *self
+            .conf
+            .write()
+            .map_err(|_| Error::msg("Failed to lock auth config"))? = conf;
+
+        Ok(())
+    }
+
+    fn validate_users(conf: &AuthConfig, providers: &[(String, AuthProvider)]) -> Result<()> {
         if let Some(invalid) = conf.users.iter().find(|u| {
             u.provider.is_some()
                 && !providers
@@ -65,20 +102,18 @@ impl Authenticator {
             );
         }
 
-        Ok(Self {
-            conf,
-            providers: Arc::new(providers),
-        })
+        Ok(())
     }
 
     /// Gets the auth config
-    pub fn conf(&self) -> &AuthConfig {
-        &self.conf
+    pub fn conf(&self) -> &'static AuthConfig {
+        // A poisoned lock still holds a valid reference
+        *self.conf.read().unwrap_or_else(|e| e.into_inner())
     }
 
     /// Gets the requested user from the auth configuration
     pub fn get_user(&self, username: &str) -> Result<&UserConfig> {
-        self.conf
+        self.conf()
             .users
             .iter()
             .find(|i| i.username == username)
@@ -119,6 +154,11 @@ mod tests {
             providers: vec![],
             users: vec![],
             service_users: vec![],
+            rules: vec![],
+            limits: vec![],
+            masks: vec![],
+            grants: vec![],
+            workloads: vec![],
         }));
         let authenticator = Authenticator::init(conf).unwrap();
 
@@ -137,17 +177,69 @@ mod tests {
                 username: "mary".into(),
                 description: None,
                 provider: None,
+                roles: vec![],
                 r#type: UserTypeOptions::Password(PasswordUserConfig {
                     password: "foo".into(),
                 }),
             }],
             service_users: vec![],
+            rules: vec![],
+            limits: vec![],
+            masks: vec![],
+            grants: vec![],
+            workloads: vec![],
         }));
         let authenticator = Authenticator::init(conf).unwrap();
 
         assert_eq!(authenticator.get_user("mary").unwrap(), &conf.users[0]);
     }
 
+    #[test]
+    fn test_reload_users() {
+        let conf = Box::leak(Box::new(AuthConfig {
+            providers: vec![],
+            users: vec![UserConfig {
+                username: "mary".into(),
+                description: None,
+                provider: None,
+                roles: vec![],
+                r#type: UserTypeOptions::Password(PasswordUserConfig {
+                    password: "foo".into(),
+                }),
+            }],
+            service_users: vec![],
+            rules: vec![],
+            limits: vec![],
+            masks: vec![],
+            grants: vec![],
+            workloads: vec![],
+        }));
+        let authenticator = Authenticator::init(conf).unwrap();
+        let clone = authenticator.clone();
+
+        let user = UserConfig {
+            username: "mary".into(),
+            description: None,
+            provider: None,
+            roles: vec![],
+            r#type: UserTypeOptions::Password(PasswordUserConfig {
+                password: "bar".into(),
+            }),
+        };
+        authenticator.reload_users(vec![user.clone()]).unwrap();
+
+        assert_eq!(clone.get_user("mary").unwrap(), &user);
+
+        // Reloaded users must reference existing providers
+        authenticator
+            .reload_users(vec![UserConfig {
+                provider: Some("unknown".into()),
+                ..user.clone()
+            }])
+            .unwrap_err();
+        assert_eq!(clone.get_user("mary").unwrap(), &user);
+    }
+
     #[test]
     fn test_empty_password_disallowed() {
         let conf = Box::leak(Box::new(AuthConfig {
@@ -156,11 +248,17 @@ mod tests {
                 username: "test".into(),
                 description: None,
                 provider: None,
+                roles: vec![],
                 r#type: UserTypeOptions::Password(PasswordUserConfig {
                     password: "".into(),
                 }),
             }],
             service_users: vec![],
+            rules: vec![],
+            limits: vec![],
+            masks: vec![],
+            grants: vec![],
+            workloads: vec![],
         }));
 
         let res = Authenticator::init(conf);
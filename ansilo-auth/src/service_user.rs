@@ -101,6 +101,11 @@ mod tests {
                     password: "pass123".into(),
                 }),
             )],
+            rules: vec![],
+            limits: vec![],
+            masks: vec![],
+            grants: vec![],
+            workloads: vec![],
         }));
         let authenticator = Authenticator::init(conf).unwrap();
 
@@ -128,6 +133,11 @@ mod tests {
                     shell: r#"echo '{"password": "some_secret_pass"}'"#.into(),
                 }),
             )],
+            rules: vec![],
+            limits: vec![],
+            masks: vec![],
+            grants: vec![],
+            workloads: vec![],
         }));
         let authenticator = Authenticator::init(conf).unwrap();
 
@@ -155,6 +165,11 @@ mod tests {
                     shell: r#"exit 1"#.into(),
                 }),
             )],
+            rules: vec![],
+            limits: vec![],
+            masks: vec![],
+            grants: vec![],
+            workloads: vec![],
         }));
         let authenticator = Authenticator::init(conf).unwrap();
 
@@ -169,6 +184,11 @@ mod tests {
             providers: vec![],
             users: vec![],
             service_users: vec![],
+            rules: vec![],
+            limits: vec![],
+            masks: vec![],
+            grants: vec![],
+            workloads: vec![],
         }));
         let authenticator = Authenticator::init(conf).unwrap();
 
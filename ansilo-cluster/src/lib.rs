@@ -0,0 +1,491 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex, RwLock,
+    },
+    thread,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use ansilo_core::{
+    config::ClusterConfig,
+    err::{Context, Error, Result},
+    web::cluster::{ClusterMember, ClusterStatus},
+};
+use ansilo_logging::{debug, info, warn};
+
+use crate::store::ClusterStore;
+
+pub mod store;
+
+/// The prefix of the shared keys used to invalidate the metadata cache
+const CACHE_INVALIDATION_PREFIX: &str = "metadata_cache/";
+
+/// The key suffix used to invalidate the metadata cache of all data sources
+const ALL_SOURCES: &str = "*";
+
+/// The entrypoint to the clustering subsystem.
+///
+/// Each member periodically records a heartbeat in the shared store and
+/// attempts to acquire or renew leadership, so exactly one live member
+/// is the leader at any time. Subsystems which must only run once across
+/// the cluster, such as the job scheduler, check `is_leader` before running.
+///
+/// Cache invalidations requested on any member are published through the
+/// shared store and applied by every other member.
+pub struct Cluster {
+    /// The shared view of the cluster
+    state: ClusterState,
+    /// The shared coordination state
+    store: Arc<dyn ClusterStore>,
+    /// Whether the last exchange with the store succeeded
+    healthy: Arc<AtomicBool>,
+    /// Dropping this sender signals the cluster thread to stop
+    stop: Mutex<Option<Sender<()>>>,
+}
+
+/// The current view of the cluster, shared across subsystems
+#[derive(Debug, Clone)]
+pub struct ClusterState {
+    status: Arc<RwLock<ClusterStatus>>,
+    /// Cache invalidations waiting to be published to the other members
+    invalidations: Arc<Mutex<Vec<Option<String>>>>,
+}
+
+impl Cluster {
+    /// Joins the cluster, invoking `on_invalidate` when another member
+    /// invalidates the metadata cache of a data source
+    pub fn start(
+        conf: &'static ClusterConfig,
+        store: Box<dyn ClusterStore>,
+        on_invalidate: impl Fn(Option<&str>) + Send + 'static,
+    ) -> Result<Self> {
+        let state = ClusterState::new(conf.node_id.clone());
+        let store: Arc<dyn ClusterStore> = Arc::from(store);
+        let healthy = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel::<()>();
+
+        {
+            let state = state.clone();
+            let store = Arc::clone(&store);
+            let healthy = Arc::clone(&healthy);
+            let mut seen = None;
+            let mut last_success = Instant::now();
+
+            thread::Builder::new()
+                .name("ansilo-cluster".into())
+                .spawn(move || loop {
+                    match Self::tick(conf, store.as_ref(), &state, &mut seen, &on_invalidate) {
+                        Ok(()) => {
+                            healthy.store(true, Ordering::SeqCst);
+                            last_success = Instant::now();
+                        }
+                        Err(err) => {
+                            warn!("Failed to update cluster state: {:?}", err);
+                            healthy.store(false, Ordering::SeqCst);
+
+                            // Leadership held by this node may have expired
+                            if last_success.elapsed() >= conf.member_ttl() {
+                                state.update(None, vec![]);
+                            }
+                        }
+                    }
+
+                    match rx.recv_timeout(conf.heartbeat_interval()) {
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        _ => break,
+                    }
+                })
+                .context("Failed to spawn cluster thread")?;
+        }
+
+        Ok(Self {
+            state,
+            store,
+            healthy,
+            stop: Mutex::new(Some(tx)),
+        })
+    }
+
+    /// Gets the current view of the cluster
+    pub fn state(&self) -> &ClusterState {
+        &self.state
+    }
+
+    /// Whether this node is currently the leader of the cluster
+    pub fn is_leader(&self) -> bool {
+        self.state.is_leader()
+    }
+
+    /// Whether this node is able to reach the shared store
+    pub fn healthy(&self) -> bool {
+        self.healthy.load(Ordering::SeqCst)
+    }
+
+    /// Leaves the cluster, releasing leadership so another member can take over
+    pub fn terminate(&self) -> Result<()> {
+        let stop = self
+            .stop
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock cluster thread"))?
+            .take();
+
+        if stop.is_some() && self.state.is_leader() {
+            info!("Releasing leadership of the cluster...");
+            self.store.resign(&self.state.status().node_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Exchanges the state of this node with the other members
+    fn tick(
+        conf: &ClusterConfig,
+        store: &dyn ClusterStore,
+        state: &ClusterState,
+        seen: &mut Option<HashMap<String, String>>,
+        on_invalidate: &dyn Fn(Option<&str>),
+    ) -> Result<()> {
+        let ttl = conf.member_ttl();
+
+        store.heartbeat(
+            &ClusterMember {
+                node_id: conf.node_id.clone(),
+                url: conf.url.clone(),
+                last_seen: now(),
+            },
+            ttl,
+        )?;
+
+        let leader = store.elect(&conf.node_id, ttl)?;
+        let members = store.members(ttl)?;
+
+        Self::sync_invalidations(conf, store, state, seen, on_invalidate)?;
+
+        if state.status().leader.as_deref() != Some(leader.as_str()) {
+            if leader == conf.node_id {
+                info!("This node is now the leader of the cluster");
+            } else {
+                info!("Node '{leader}' is the leader of the cluster");
+            }
+        }
+
+        state.update(Some(leader), members);
+        Ok(())
+    }
+
+    /// Publishes the cache invalidations requested on this node and applies
+    /// those published by other members since the last tick
+    fn sync_invalidations(
+        conf: &ClusterConfig,
+        store: &dyn ClusterStore,
+        state: &ClusterState,
+        seen: &mut Option<HashMap<String, String>>,
+        on_invalidate: &dyn Fn(Option<&str>),
+    ) -> Result<()> {
+        let mut pending = state.take_invalidations().into_iter();
+        let mut published = vec![];
+
+        while let Some(source) = pending.next() {
+            let key = cache_key(source.as_deref());
+            let value = format!("{}:{}", now(), conf.node_id);
+
+            if let Err(err) = store.set(&key, &value) {
+                state.requeue_invalidations(std::iter::once(source).chain(pending));
+                return Err(err);
+            }
+
+            published.push((key, value));
+        }
+
+        let current = store
+            .list(CACHE_INVALIDATION_PREFIX)?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+        // Invalidations which occurred before this node joined are ignored
+        if let Some(seen) = seen.as_ref() {
+            for (key, value) in current.iter() {
+                if seen.get(key) == Some(value) || published.contains(&(key.clone(), value.clone()))
+                {
+                    continue;
+                }
+
+                let source = &key[CACHE_INVALIDATION_PREFIX.len()..];
+                debug!("Applying cache invalidation published by another member: {key}={value}");
+                on_invalidate(if source == ALL_SOURCES {
+                    None
+                } else {
+                    Some(source)
+                });
+            }
+        }
+
+        *seen = Some(current);
+        Ok(())
+    }
+}
+
+impl Drop for Cluster {
+    fn drop(&mut self) {
+        if let Err(err) = self.terminate() {
+            warn!("Failed to leave cluster: {:?}", err);
+        }
+    }
+}
+
+impl ClusterState {
+    pub fn new(node_id: impl Into<String>) -> Self {
+        Self {
+            status: Arc::new(RwLock::new(ClusterStatus {
+                node_id: node_id.into(),
+                leader: None,
+                members: vec![],
+            })),
+            invalidations: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Gets the current status of the cluster
+    pub fn status(&self) -> ClusterStatus {
+        self.status
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Whether this node is currently the leader of the cluster
+    pub fn is_leader(&self) -> bool {
+        let status = self.status.read().unwrap_or_else(|e| e.into_inner());
+
+        status.leader.as_deref() == Some(status.node_id.as_str())
+    }
+
+    /// Publishes the invalidation of the metadata cache of the supplied data source,
+    /// or of all data sources if none is supplied, to the other members.
+    ///
+    /// This does not block, the invalidation is published during the next heartbeat.
+    pub fn invalidate_cache(&self, data_source_id: Option<&str>) {
+        self.invalidations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(data_source_id.map(|s| s.to_string()));
+    }
+
+    fn take_invalidations(&self) -> Vec<Option<String>> {
+        std::mem::take(&mut *self.invalidations.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn requeue_invalidations(&self, sources: impl Iterator<Item = Option<String>>) {
+        self.invalidations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(sources);
+    }
+
+    fn update(&self, leader: Option<String>, members: Vec<ClusterMember>) {
+        let mut status = self.status.write().unwrap_or_else(|e| e.into_inner());
+
+        status.leader = leader;
+        status.members = members;
+    }
+}
+
+/// Gets the shared key used to invalidate the metadata cache of the data source
+fn cache_key(data_source_id: Option<&str>) -> String {
+    format!(
+        "{CACHE_INVALIDATION_PREFIX}{}",
+        data_source_id.unwrap_or(ALL_SOURCES)
+    )
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, time::Duration};
+
+    use ansilo_core::config::{ClusterStoreConfig, EtcdClusterStoreConfig};
+
+    use super::*;
+
+    /// An in-memory store shared by the members of the test cluster
+    #[derive(Default)]
+    struct MockStore {
+        members: Mutex<HashMap<String, ClusterMember>>,
+        leader: Mutex<Option<String>>,
+        state: Mutex<HashMap<String, String>>,
+    }
+
+    impl ClusterStore for MockStore {
+        fn heartbeat(&self, member: &ClusterMember, _ttl: Duration) -> Result<()> {
+            self.members
+                .lock()
+                .unwrap()
+                .insert(member.node_id.clone(), member.clone());
+            Ok(())
+        }
+
+        fn members(&self, _ttl: Duration) -> Result<Vec<ClusterMember>> {
+            let mut members = self
+                .members
+                .lock()
+                .unwrap()
+                .values()
+                .cloned()
+                .collect::<Vec<_>>();
+            members.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+            Ok(members)
+        }
+
+        fn elect(&self, node_id: &str, _ttl: Duration) -> Result<String> {
+            Ok(self
+                .leader
+                .lock()
+                .unwrap()
+                .get_or_insert_with(|| node_id.to_string())
+                .clone())
+        }
+
+        fn resign(&self, node_id: &str) -> Result<()> {
+            let mut leader = self.leader.lock().unwrap();
+            if leader.as_deref() == Some(node_id) {
+                *leader = None;
+            }
+            Ok(())
+        }
+
+        fn get(&self, key: &str) -> Result<Option<String>> {
+            Ok(self.state.lock().unwrap().get(key).cloned())
+        }
+
+        fn set(&self, key: &str, value: &str) -> Result<()> {
+            self.state
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), value.to_string());
+            Ok(())
+        }
+
+        fn list(&self, prefix: &str) -> Result<Vec<(String, String)>> {
+            Ok(self
+                .state
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(k, _)| k.starts_with(prefix))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect())
+        }
+    }
+
+    fn mock_conf(node_id: &str) -> ClusterConfig {
+        ClusterConfig {
+            node_id: node_id.into(),
+            url: format!("https://{node_id}.internal:65432"),
+            store: ClusterStoreConfig::Etcd(EtcdClusterStoreConfig {
+                endpoints: vec![],
+                prefix: None,
+            }),
+            heartbeat_interval_secs: Some(1),
+            member_ttl_secs: None,
+        }
+    }
+
+    /// A member of the test cluster which records the invalidations applied to it
+    struct MockMember {
+        conf: ClusterConfig,
+        state: ClusterState,
+        seen: Option<HashMap<String, String>>,
+        invalidated: RefCell<Vec<Option<String>>>,
+    }
+
+    impl MockMember {
+        fn new(node_id: &str) -> Self {
+            Self {
+                conf: mock_conf(node_id),
+                state: ClusterState::new(node_id),
+                seen: None,
+                invalidated: RefCell::new(vec![]),
+            }
+        }
+
+        fn tick(&mut self, store: &MockStore) {
+            let invalidated = &self.invalidated;
+
+            Cluster::tick(&self.conf, store, &self.state, &mut self.seen, &|source| {
+                invalidated.borrow_mut().push(source.map(|s| s.to_string()))
+            })
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_cluster_membership_and_leader() {
+        let store = MockStore::default();
+        let mut a = MockMember::new("a");
+        let mut b = MockMember::new("b");
+
+        a.tick(&store);
+        b.tick(&store);
+        a.tick(&store);
+
+        assert!(a.state.is_leader());
+        assert!(!b.state.is_leader());
+
+        let status = a.state.status();
+        assert_eq!(status.leader, Some("a".into()));
+        assert_eq!(
+            status
+                .members
+                .iter()
+                .map(|m| m.node_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+
+        // Once the leader resigns another member takes over
+        store.resign("a").unwrap();
+        b.tick(&store);
+        a.tick(&store);
+
+        assert!(b.state.is_leader());
+        assert!(!a.state.is_leader());
+    }
+
+    #[test]
+    fn test_cluster_cache_invalidation() {
+        let store = MockStore::default();
+        let mut a = MockMember::new("a");
+        let mut b = MockMember::new("b");
+
+        // Invalidations which occurred before joining are not applied
+        store.set(&cache_key(Some("old")), "1:c").unwrap();
+
+        a.tick(&store);
+        b.tick(&store);
+
+        a.state.invalidate_cache(Some("mysql"));
+        a.state.invalidate_cache(None);
+        a.tick(&store);
+        b.tick(&store);
+
+        // Invalidations are only applied by the other members
+        assert_eq!(*a.invalidated.borrow(), vec![]);
+
+        let mut invalidated = b.invalidated.borrow().clone();
+        invalidated.sort();
+        assert_eq!(invalidated, vec![None, Some("mysql".into())]);
+
+        // Each invalidation is only applied once
+        b.tick(&store);
+        assert_eq!(b.invalidated.borrow().len(), 2);
+    }
+}
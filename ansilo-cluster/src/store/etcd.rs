@@ -0,0 +1,309 @@
+use std::{sync::Mutex, time::Duration};
+
+use ansilo_core::{
+    config::EtcdClusterStoreConfig,
+    err::{bail, Context, Error, Result},
+    web::cluster::ClusterMember,
+};
+use ansilo_logging::debug;
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+
+use super::ClusterStore;
+
+/// The timeout of requests to etcd
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Stores the coordination state in etcd using its json gateway.
+///
+/// The member and leader keys are attached to a lease of this member
+/// which is kept alive by each heartbeat, so they are removed by etcd
+/// once the member stops sending heartbeats.
+pub struct EtcdClusterStore {
+    /// The urls of the etcd members, tried in order
+    endpoints: Vec<String>,
+    /// The prefix of all keys
+    prefix: String,
+    client: Client,
+    /// The id of the lease of this member
+    lease: Mutex<Option<i64>>,
+}
+
+impl EtcdClusterStore {
+    pub fn new(conf: &EtcdClusterStoreConfig, node_name: &str) -> Result<Self> {
+        if conf.endpoints.is_empty() {
+            bail!("At least one etcd endpoint must be configured");
+        }
+
+        Ok(Self {
+            endpoints: conf.endpoints.clone(),
+            prefix: conf
+                .prefix
+                .clone()
+                .unwrap_or_else(|| format!("/ansilo/{node_name}"))
+                .trim_end_matches('/')
+                .to_string(),
+            client: Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .context("Failed to initialise http client")?,
+            lease: Mutex::new(None),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}/{key}", self.prefix)
+    }
+
+    /// Sends the request to the first etcd endpoint which responds
+    fn post(&self, path: &str, body: Value) -> Result<Value> {
+        let mut err = Error::msg("No etcd endpoints configured");
+
+        for endpoint in self.endpoints.iter() {
+            let res = self
+                .client
+                .post(format!("{}{path}", endpoint.trim_end_matches('/')))
+                .json(&body)
+                .send()
+                .and_then(|r| r.error_for_status())
+                .and_then(|r| r.json::<Value>());
+
+            match res {
+                Ok(res) => return Ok(res),
+                Err(e) => {
+                    debug!("Request to etcd endpoint {endpoint} failed: {:?}", e);
+                    err = Error::new(e);
+                }
+            }
+        }
+
+        Err(err).with_context(|| format!("Failed to send request {path} to etcd"))
+    }
+
+    /// Gets the lease of this member, granting a new lease if it has expired
+    fn lease(&self, ttl: Duration) -> Result<i64> {
+        let mut lease = self
+            .lease
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock etcd lease"))?;
+
+        if let Some(id) = *lease {
+            let res = self.post("/v3/lease/keepalive", json!({ "ID": id }))?;
+
+            // An expired lease is reported without a ttl
+            if int(&res["result"]["TTL"]).unwrap_or(0) > 0 {
+                return Ok(id);
+            }
+        }
+
+        let res = self.post("/v3/lease/grant", json!({ "TTL": ttl.as_secs().max(1) }))?;
+        let id = int(&res["ID"]).context("Failed to parse etcd lease")?;
+
+        *lease = Some(id);
+        Ok(id)
+    }
+
+    fn range(&self, key: &str, range_end: Option<Vec<u8>>) -> Result<Vec<(String, String)>> {
+        let mut body = json!({ "key": base64::encode(key) });
+
+        if let Some(end) = range_end {
+            body["range_end"] = Value::String(base64::encode(end));
+        }
+
+        let res = self.post("/v3/kv/range", body)?;
+
+        kvs(&res)
+    }
+}
+
+impl ClusterStore for EtcdClusterStore {
+    fn heartbeat(&self, member: &ClusterMember, ttl: Duration) -> Result<()> {
+        let lease = self.lease(ttl)?;
+        let value = serde_json::to_string(member).context("Failed to serialize member")?;
+
+        self.post(
+            "/v3/kv/put",
+            json!({
+                "key": base64::encode(self.key(&format!("members/{}", member.node_id))),
+                "value": base64::encode(value),
+                "lease": lease,
+            }),
+        )?;
+
+        Ok(())
+    }
+
+    fn members(&self, _ttl: Duration) -> Result<Vec<ClusterMember>> {
+        let prefix = self.key("members/");
+
+        self.range(&prefix, Some(range_end(prefix.as_bytes())))?
+            .into_iter()
+            .map(|(_, value)| {
+                serde_json::from_str::<ClusterMember>(&value).context("Failed to parse member")
+            })
+            .collect()
+    }
+
+    fn elect(&self, node_id: &str, ttl: Duration) -> Result<String> {
+        let lease = self.lease(ttl)?;
+        let key = base64::encode(self.key("leader"));
+
+        // Leadership is renewed by keeping the lease alive
+        let res = self.post(
+            "/v3/kv/txn",
+            json!({
+                "compare": [{
+                    "key": key,
+                    "result": "EQUAL",
+                    "target": "CREATE",
+                    "create_revision": 0,
+                }],
+                "success": [{
+                    "request_put": { "key": key, "value": base64::encode(node_id), "lease": lease }
+                }],
+                "failure": [{
+                    "request_range": { "key": key }
+                }],
+            }),
+        )?;
+
+        if res["succeeded"].as_bool() == Some(true) {
+            return Ok(node_id.to_string());
+        }
+
+        kvs(&res["responses"][0]["response_range"])?
+            .into_iter()
+            .next()
+            .map(|(_, leader)| leader)
+            .context("Failed to find cluster leader")
+    }
+
+    fn resign(&self, node_id: &str) -> Result<()> {
+        let key = base64::encode(self.key("leader"));
+
+        self.post(
+            "/v3/kv/txn",
+            json!({
+                "compare": [{
+                    "key": key,
+                    "result": "EQUAL",
+                    "target": "VALUE",
+                    "value": base64::encode(node_id),
+                }],
+                "success": [{
+                    "request_delete_range": { "key": key }
+                }],
+            }),
+        )?;
+
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .range(&self.key(&format!("state/{key}")), None)?
+            .into_iter()
+            .next()
+            .map(|(_, value)| value))
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.post(
+            "/v3/kv/put",
+            json!({
+                "key": base64::encode(self.key(&format!("state/{key}"))),
+                "value": base64::encode(value),
+            }),
+        )?;
+
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<(String, String)>> {
+        let state = self.key("state/");
+        let prefix = format!("{state}{prefix}");
+
+        Ok(self
+            .range(&prefix, Some(range_end(prefix.as_bytes())))?
+            .into_iter()
+            .map(|(key, value)| (key[state.len()..].to_string(), value))
+            .collect())
+    }
+}
+
+/// The json gateway encodes 64-bit integers as strings
+fn int(value: &Value) -> Option<i64> {
+    value
+        .as_i64()
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
+
+/// Decodes the key-value pairs of a range response
+fn kvs(res: &Value) -> Result<Vec<(String, String)>> {
+    let decode = |value: &Value| -> Result<String> {
+        let bytes = base64::decode(value.as_str().unwrap_or_default())
+            .context("Failed to decode etcd value")?;
+
+        String::from_utf8(bytes).context("Failed to decode etcd value")
+    };
+
+    res["kvs"]
+        .as_array()
+        .map(|kvs| kvs.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .map(|kv| Ok((decode(&kv["key"])?, decode(&kv["value"])?)))
+        .collect()
+}
+
+/// Gets the end of the key range which covers all keys starting with the prefix
+fn range_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return end;
+        }
+    }
+
+    // A range end of "\0" covers all keys
+    vec![0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_end() {
+        assert_eq!(range_end(b"/ansilo/members/"), b"/ansilo/members0".to_vec());
+        assert_eq!(range_end(&[b'a', 0xff]), b"b".to_vec());
+        assert_eq!(range_end(&[0xff]), vec![0]);
+    }
+
+    #[test]
+    fn test_int() {
+        assert_eq!(
+            int(&json!("7587866534580826114")),
+            Some(7587866534580826114)
+        );
+        assert_eq!(int(&json!(15)), Some(15));
+        assert_eq!(int(&Value::Null), None);
+    }
+
+    #[test]
+    fn test_kvs() {
+        let res = json!({
+            "kvs": [
+                { "key": base64::encode("/ansilo/leader"), "value": base64::encode("node-a") }
+            ]
+        });
+
+        assert_eq!(
+            kvs(&res).unwrap(),
+            vec![("/ansilo/leader".to_string(), "node-a".to_string())]
+        );
+        assert_eq!(kvs(&json!({})).unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use ansilo_core::{
+    config::{ClusterConfig, ClusterStoreConfig},
+    err::Result,
+    web::cluster::ClusterMember,
+};
+use tokio::runtime::Handle;
+
+pub mod etcd;
+pub mod postgres;
+
+/// A backend storing the coordination state shared by the members of the cluster.
+///
+/// The methods are blocking and must not be called from within the async runtime.
+pub trait ClusterStore: Send + Sync {
+    /// Records a heartbeat of the member, which is considered live for the ttl
+    fn heartbeat(&self, member: &ClusterMember, ttl: Duration) -> Result<()>;
+
+    /// Gets the members which are currently live
+    fn members(&self, ttl: Duration) -> Result<Vec<ClusterMember>>;
+
+    /// Acquires or renews leadership for the ttl, unless it is held by another member.
+    ///
+    /// Returns the id of the current leader.
+    fn elect(&self, node_id: &str, ttl: Duration) -> Result<String>;
+
+    /// Releases leadership, if it is held by the node
+    fn resign(&self, node_id: &str) -> Result<()>;
+
+    /// Gets the shared value of the key
+    fn get(&self, key: &str) -> Result<Option<String>>;
+
+    /// Sets the shared value of the key
+    fn set(&self, key: &str, value: &str) -> Result<()>;
+
+    /// Gets the shared entries of which the key starts with the prefix
+    fn list(&self, prefix: &str) -> Result<Vec<(String, String)>>;
+}
+
+/// Creates the store configured for the cluster
+pub fn connect(
+    conf: &ClusterConfig,
+    node_name: &str,
+    runtime: Handle,
+) -> Result<Box<dyn ClusterStore>> {
+    Ok(match &conf.store {
+        ClusterStoreConfig::Postgres(conf) => {
+            Box::new(postgres::PostgresClusterStore::new(conf, runtime)?)
+        }
+        ClusterStoreConfig::Etcd(conf) => Box::new(etcd::EtcdClusterStore::new(conf, node_name)?),
+    })
+}
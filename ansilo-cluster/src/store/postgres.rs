@@ -0,0 +1,328 @@
+use std::{sync::Mutex, time::Duration};
+
+use ansilo_connectors_base::common::tls::TlsVerifyConfig;
+use ansilo_connectors_native_postgres::PostgresTlsConnector;
+use ansilo_core::{
+    config::PostgresClusterStoreConfig,
+    err::{bail, Context, Error, Result},
+    web::cluster::ClusterMember,
+};
+use ansilo_logging::warn;
+use ansilo_util_pg::query::pg_quote_identifier;
+use tokio::runtime::Handle;
+use tokio_postgres::{config::SslMode, types::ToSql, Client, Config, Row};
+
+use super::ClusterStore;
+
+/// The default schema of the coordination tables
+const DEFAULT_SCHEMA: &str = "ansilo_cluster";
+
+/// The sslmode used if the connection string does not specify one
+const DEFAULT_SSLMODE: &str = "require";
+
+/// Stores the coordination state in tables of a shared postgres database.
+///
+/// Leadership is held by a single row which expires unless it is renewed,
+/// using the database's clock so members do not rely on synchronised clocks.
+pub struct PostgresClusterStore {
+    /// The connection options of the database
+    config: Config,
+    /// The quoted schema of the coordination tables
+    schema: String,
+    /// Establishes TLS connections, verifying the certificate as per the sslmode of the connection string
+    tls: PostgresTlsConnector,
+    /// The runtime to run the queries on
+    runtime: Handle,
+    /// The current connection, re-established after any error
+    client: Mutex<Option<Client>>,
+}
+
+impl PostgresClusterStore {
+    pub fn new(conf: &PostgresClusterStoreConfig, runtime: Handle) -> Result<Self> {
+        let verify = match conf.ca_bundle.as_ref() {
+            Some(ca_bundle) => TlsVerifyConfig::CaBundle {
+                ca_bundle: ca_bundle.clone(),
+            },
+            None => TlsVerifyConfig::System,
+        };
+
+        let (config, verify_hostname) = parse_url(&conf.url)?;
+        let tls = if verify_hostname {
+            PostgresTlsConnector::new(verify)
+        } else {
+            PostgresTlsConnector::without_hostname_verification(verify)
+        };
+
+        Ok(Self {
+            config,
+            schema: pg_quote_identifier(conf.schema.as_deref().unwrap_or(DEFAULT_SCHEMA)),
+            tls: tls.context("Failed to initialise cluster store tls")?,
+            runtime,
+            client: Mutex::new(None),
+        })
+    }
+
+    /// Connects to the database and creates the coordination tables
+    fn connect(&self) -> Result<Client> {
+        self.runtime.block_on(async {
+            let (client, con) = self
+                .config
+                .connect(self.tls.clone())
+                .await
+                .context("Failed to connect to cluster store")?;
+
+            tokio::spawn(async move {
+                if let Err(err) = con.await {
+                    warn!("Cluster store connection error: {:?}", err);
+                }
+            });
+
+            client
+                .batch_execute(&init_sql(&self.schema))
+                .await
+                .context("Failed to create cluster store tables")?;
+
+            Ok(client)
+        })
+    }
+
+    fn query(&self, sql: &str, params: &[&(dyn ToSql + Sync)]) -> Result<Vec<Row>> {
+        let mut client = self
+            .client
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock cluster store connection"))?;
+
+        let con = match client.take() {
+            Some(con) if !con.is_closed() => con,
+            _ => self.connect()?,
+        };
+
+        let rows = self
+            .runtime
+            .block_on(con.query(sql, params))
+            .context("Failed to query cluster store")?;
+
+        *client = Some(con);
+        Ok(rows)
+    }
+}
+
+impl ClusterStore for PostgresClusterStore {
+    fn heartbeat(&self, member: &ClusterMember, _ttl: Duration) -> Result<()> {
+        self.query(
+            &format!(
+                r#"
+                INSERT INTO {}.members (node_id, url, heartbeat_at)
+                VALUES ($1, $2, NOW())
+                ON CONFLICT (node_id) DO UPDATE
+                SET url = EXCLUDED.url, heartbeat_at = EXCLUDED.heartbeat_at
+                "#,
+                self.schema
+            ),
+            &[&member.node_id, &member.url],
+        )?;
+
+        Ok(())
+    }
+
+    fn members(&self, ttl: Duration) -> Result<Vec<ClusterMember>> {
+        let rows = self.query(
+            &format!(
+                r#"
+                SELECT node_id, url, (EXTRACT(EPOCH FROM heartbeat_at) * 1000)::BIGINT
+                FROM {}.members
+                WHERE heartbeat_at >= NOW() - make_interval(secs => $1)
+                ORDER BY node_id
+                "#,
+                self.schema
+            ),
+            &[&ttl.as_secs_f64()],
+        )?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| ClusterMember {
+                node_id: row.get(0),
+                url: row.get(1),
+                last_seen: row.get::<_, i64>(2) as u64,
+            })
+            .collect())
+    }
+
+    fn elect(&self, node_id: &str, ttl: Duration) -> Result<String> {
+        self.query(
+            &format!(
+                r#"
+                INSERT INTO {}.leader AS l (id, node_id, expires_at)
+                VALUES (TRUE, $1, NOW() + make_interval(secs => $2))
+                ON CONFLICT (id) DO UPDATE
+                SET node_id = EXCLUDED.node_id, expires_at = EXCLUDED.expires_at
+                WHERE l.node_id = EXCLUDED.node_id OR l.expires_at < NOW()
+                "#,
+                self.schema
+            ),
+            &[&node_id, &ttl.as_secs_f64()],
+        )?;
+
+        let rows = self.query(&format!("SELECT node_id FROM {}.leader", self.schema), &[])?;
+
+        rows.first()
+            .map(|row| row.get(0))
+            .context("Failed to find cluster leader")
+    }
+
+    fn resign(&self, node_id: &str) -> Result<()> {
+        self.query(
+            &format!("DELETE FROM {}.leader WHERE node_id = $1", self.schema),
+            &[&node_id],
+        )?;
+
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        let rows = self.query(
+            &format!("SELECT value FROM {}.state WHERE key = $1", self.schema),
+            &[&key],
+        )?;
+
+        Ok(rows.first().map(|row| row.get(0)))
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.query(
+            &format!(
+                r#"
+                INSERT INTO {}.state (key, value, updated_at)
+                VALUES ($1, $2, NOW())
+                ON CONFLICT (key) DO UPDATE
+                SET value = EXCLUDED.value, updated_at = EXCLUDED.updated_at
+                "#,
+                self.schema
+            ),
+            &[&key, &value],
+        )?;
+
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<(String, String)>> {
+        let rows = self.query(
+            &format!(
+                "SELECT key, value FROM {}.state WHERE starts_with(key, $1) ORDER BY key",
+                self.schema
+            ),
+            &[&prefix],
+        )?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect())
+    }
+}
+
+/// Returns the sql which creates the coordination tables.
+///
+/// Members may start at once so the tables are created while holding a lock.
+fn init_sql(schema: &str) -> String {
+    format!(
+        r#"
+        BEGIN;
+        SELECT pg_advisory_xact_lock(hashtext('ansilo_cluster'));
+        CREATE SCHEMA IF NOT EXISTS {schema};
+        CREATE TABLE IF NOT EXISTS {schema}.members (
+            node_id TEXT PRIMARY KEY,
+            url TEXT NOT NULL,
+            heartbeat_at TIMESTAMPTZ NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS {schema}.leader (
+            id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+            node_id TEXT NOT NULL,
+            expires_at TIMESTAMPTZ NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS {schema}.state (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at TIMESTAMPTZ NOT NULL
+        );
+        COMMIT;
+        "#
+    )
+}
+
+/// Parses the connection string, returning the connection options and whether
+/// the hostname of the database is verified.
+///
+/// The verifying sslmodes of libpq are not understood by tokio-postgres, so these
+/// require TLS with the certificate verified by the connector. The certificate
+/// chain is verified whenever TLS is used and the hostname unless "verify-ca".
+fn parse_url(url: &str) -> Result<(Config, bool)> {
+    let sslmode = url
+        .split(|c: char| c.is_whitespace() || c == '?' || c == '&')
+        .find_map(|p| p.strip_prefix("sslmode="))
+        .unwrap_or(DEFAULT_SSLMODE);
+
+    let (ssl_mode, verify_hostname) = match sslmode {
+        "disable" => (SslMode::Disable, true),
+        "allow" | "prefer" => (SslMode::Prefer, true),
+        "require" | "verify-full" => (SslMode::Require, true),
+        "verify-ca" => (SslMode::Require, false),
+        _ => bail!("Unsupported sslmode \"{sslmode}\" in cluster store connection string"),
+    };
+
+    if ssl_mode != SslMode::Require {
+        warn!(
+            "The connection to the cluster store may not be encrypted due to sslmode \"{sslmode}\""
+        );
+    }
+
+    let mut config = url
+        .replace(&format!("sslmode={sslmode}"), "sslmode=require")
+        .parse::<Config>()
+        .context("Failed to parse cluster store connection string")?;
+    config.ssl_mode(ssl_mode);
+
+    Ok((config, verify_hostname))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        let parse = |url: &str| {
+            let (config, verify_hostname) = parse_url(url).unwrap();
+            (config.get_ssl_mode(), verify_hostname)
+        };
+
+        assert_eq!(parse("host=db"), (SslMode::Require, true));
+        assert_eq!(
+            parse("host=db sslmode=verify-full"),
+            (SslMode::Require, true)
+        );
+        assert_eq!(
+            parse("postgres://db/ansilo?sslmode=verify-ca"),
+            (SslMode::Require, false)
+        );
+        assert_eq!(
+            parse("postgres://db/ansilo?connect_timeout=5&sslmode=prefer"),
+            (SslMode::Prefer, true)
+        );
+        assert_eq!(parse("host=db sslmode=disable"), (SslMode::Disable, true));
+
+        let (config, _) = parse_url("host=db user=ansilo sslmode=verify-full").unwrap();
+        assert_eq!(config.get_user(), Some("ansilo"));
+
+        parse_url("host=db sslmode=invalid").unwrap_err();
+    }
+
+    #[test]
+    fn test_init_sql_quotes_schema() {
+        let sql = init_sql(&pg_quote_identifier("my \"cluster\""));
+
+        assert!(sql.contains(r#"CREATE SCHEMA IF NOT EXISTS "my ""cluster""";"#));
+        assert!(sql.contains(r#"CREATE TABLE IF NOT EXISTS "my ""cluster""".leader ("#));
+    }
+}
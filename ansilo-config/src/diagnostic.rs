@@ -1,6 +1,21 @@
 use std::fmt::{self, Display};
 
-use miette::{Diagnostic, SourceSpan};
+use miette::{Diagnostic, NamedSource, SourceSpan};
+
+use crate::{locate::Location, validate::ConfigValidationIssue};
+
+/// Installs the handler used to render the diagnostics, showing the
+/// surrounding lines of the config. This is called once on startup.
+pub fn install_report_hook() {
+    let _ = miette::set_hook(Box::new(|_| {
+        Box::new(miette::MietteHandlerOpts::new().context_lines(3).build())
+    }));
+}
+
+/// Prints the diagnostic, with its location in the config, to stderr
+fn print_diagnostic(diagnostic: impl Diagnostic + Send + Sync + 'static) {
+    eprintln!("Error: {:?}", miette::Report::new(diagnostic));
+}
 
 #[derive(Debug, Diagnostic)]
 #[diagnostic(code(config_parse_error))]
@@ -9,13 +24,12 @@ pub struct ConfigParseError {
     src: String,
     #[label("Error occurred here")]
     loc: Option<SourceSpan>,
-    #[help]
     error: String,
 }
 
 impl Display for ConfigParseError {
-    fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        Ok(())
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)
     }
 }
 
@@ -36,9 +50,58 @@ impl ConfigParseError {
     }
 
     pub fn print(self) {
-        let _ = miette::set_hook(Box::new(|_| {
-            Box::new(miette::MietteHandlerOpts::new().context_lines(3).build())
-        }));
-        eprintln!("Error: {:?}", miette::Report::new(self));
+        print_diagnostic(self)
+    }
+}
+
+#[derive(Debug, Diagnostic)]
+#[diagnostic(code(config_validation_error))]
+pub struct ConfigValidationError {
+    #[source_code]
+    src: NamedSource,
+    #[label("Error occurred here")]
+    loc: Option<SourceSpan>,
+    message: String,
+    #[help]
+    suggestion: Option<String>,
+}
+
+impl Display for ConfigValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ConfigValidationError {}
+
+impl ConfigValidationError {
+    pub(crate) fn new(
+        name: String,
+        src: String,
+        loc: Option<Location>,
+        issue: &ConfigValidationIssue,
+    ) -> Self {
+        let loc = loc.map(|loc| {
+            SourceSpan::new(
+                loc.offset.into(),
+                src[loc.offset..].find('\n').unwrap_or(1).into(),
+            )
+        });
+        let message = if issue.path.is_empty() {
+            issue.message.clone()
+        } else {
+            format!("{}: {}", issue.path, issue.message)
+        };
+
+        Self {
+            src: NamedSource::new(name, src),
+            loc,
+            message,
+            suggestion: issue.suggestion.clone(),
+        }
+    }
+
+    pub fn print(self) {
+        print_diagnostic(self)
     }
 }
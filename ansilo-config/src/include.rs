@@ -0,0 +1,225 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ansilo_core::err::{bail, Context, Result};
+use serde_yaml::Value;
+
+/// The top-level key used to include other configuration files
+pub(crate) const INCLUDE_KEY: &str = "include";
+
+/// Removes the include directive from the supplied (processed) config and
+/// returns the list of files to be included, in the order they should be merged.
+///
+/// Each entry may be a file, a directory (all *.yml and *.yaml files in the directory)
+/// or a glob pattern. Relative paths are resolved against the directory of the
+/// including config file. Directories and globs are expanded in lexicographic order.
+pub(crate) fn take_includes(config: &mut Value, path: Option<&Path>) -> Result<Vec<PathBuf>> {
+    let include = match config.as_mapping_mut() {
+        Some(map) => map.remove(&Value::String(INCLUDE_KEY.into())),
+        None => None,
+    };
+
+    let entries = match include {
+        None | Some(Value::Null) => return Ok(vec![]),
+        Some(Value::String(s)) => vec![s],
+        Some(Value::Sequence(seq)) => seq
+            .into_iter()
+            .map(|i| match i {
+                Value::String(s) => Ok(s),
+                _ => bail!("Expected '{INCLUDE_KEY}' entries to be strings"),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        Some(_) => bail!("Expected '{INCLUDE_KEY}' to be a string or list of strings"),
+    };
+
+    let base = path
+        .and_then(|p| p.parent())
+        .map(|p| p.to_path_buf())
+        .unwrap_or_default();
+
+    let mut files = vec![];
+
+    for entry in entries {
+        let entry = base.join(entry);
+
+        if is_glob(&entry) {
+            let pattern = entry.to_string_lossy();
+            let mut matches = glob::glob(&pattern)
+                .with_context(|| format!("Invalid include pattern {pattern}"))?
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .with_context(|| format!("Failed to expand include pattern {pattern}"))?;
+            matches.sort();
+            files.extend(matches.into_iter().filter(|p| p.is_file()));
+        } else if entry.is_dir() {
+            let mut matches = fs::read_dir(&entry)
+                .with_context(|| format!("Failed to read directory {}", entry.display()))?
+                .map(|e| Ok(e?.path()))
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .filter(|p| p.is_file() && is_yaml(p))
+                .collect::<Vec<_>>();
+            matches.sort();
+            files.extend(matches);
+        } else if entry.is_file() {
+            files.push(entry);
+        } else {
+            bail!("Included config file {} does not exist", entry.display());
+        }
+    }
+
+    Ok(files)
+}
+
+/// Merges the overlay config into the base config.
+///
+/// Mappings are merged recursively, sequences are appended and
+/// any other value in the overlay replaces the value in the base.
+/// This allows sources, entities, users and jobs to be split across files.
+pub(crate) fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, val) in overlay.into_iter() {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, val),
+                    None => {
+                        base.insert(key, val);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(overlay)) => {
+            base.extend(overlay);
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+fn is_yaml(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("yml" | "yaml")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut base = yaml(
+            r#"
+name: main
+networking:
+  port: 1234
+sources:
+  - id: a
+"#,
+        );
+
+        merge(
+            &mut base,
+            yaml(
+                r#"
+networking:
+  bind: 0.0.0.0
+sources:
+  - id: b
+jobs:
+  - id: job
+"#,
+            ),
+        );
+
+        assert_eq!(
+            base,
+            yaml(
+                r#"
+name: main
+networking:
+  port: 1234
+  bind: 0.0.0.0
+sources:
+  - id: a
+  - id: b
+jobs:
+  - id: job
+"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_merge_replaces_scalars() {
+        let mut base = yaml("name: main");
+        merge(&mut base, yaml("name: overlay"));
+
+        assert_eq!(base, yaml("name: overlay"));
+    }
+
+    #[test]
+    fn test_take_includes_none() {
+        let mut config = yaml("name: main");
+
+        assert_eq!(
+            take_includes(&mut config, None).unwrap(),
+            Vec::<PathBuf>::new()
+        );
+        assert_eq!(config, yaml("name: main"));
+    }
+
+    #[test]
+    fn test_take_includes() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("conf.d")).unwrap();
+        fs::write(dir.path().join("conf.d/b.yml"), "").unwrap();
+        fs::write(dir.path().join("conf.d/a.yaml"), "").unwrap();
+        fs::write(dir.path().join("conf.d/ignored.txt"), "").unwrap();
+        fs::write(dir.path().join("users.yml"), "").unwrap();
+        fs::write(dir.path().join("jobs-2.yml"), "").unwrap();
+        fs::write(dir.path().join("jobs-1.yml"), "").unwrap();
+
+        let mut config = yaml(
+            r#"
+name: main
+include:
+  - users.yml
+  - conf.d
+  - jobs-*.yml
+"#,
+        );
+        let files = take_includes(&mut config, Some(&dir.path().join("main.yml"))).unwrap();
+
+        assert_eq!(
+            files,
+            vec![
+                dir.path().join("users.yml"),
+                dir.path().join("conf.d/a.yaml"),
+                dir.path().join("conf.d/b.yml"),
+                dir.path().join("jobs-1.yml"),
+                dir.path().join("jobs-2.yml"),
+            ]
+        );
+        assert_eq!(config, yaml("name: main"));
+    }
+
+    #[test]
+    fn test_take_includes_missing_file() {
+        let dir = TempDir::new().unwrap();
+        let mut config = yaml("include: missing.yml");
+
+        take_includes(&mut config, Some(&dir.path().join("main.yml"))).unwrap_err();
+    }
+}
@@ -1,4 +1,8 @@
 pub mod ctx;
+mod include;
 pub mod loader;
+mod locate;
 pub mod processor;
+mod redact;
 pub mod diagnostic;
+pub mod validate;
@@ -1,27 +1,44 @@
-use std::{any::type_name, collections::HashMap, fs, path::Path};
+use std::{
+    any::type_name,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
 
-use ansilo_core::err::{bail, Context, Result};
+use ansilo_core::err::{bail, ensure, Context, Error, Result};
 use ansilo_logging::{debug, info};
 use serde::de::DeserializeOwned;
 
 use crate::{
     ctx::Ctx,
-    diagnostic::ConfigParseError,
+    diagnostic::{ConfigParseError, ConfigValidationError},
+    include::{merge, take_includes},
+    locate::ConfigLocator,
     processor::{
         arg::ArgConfigProcessor,
         dir::DirConfigProcessor,
         embed::EmbedConfigProcessor,
+        encrypted::{resolve_encrypted_tags, ConfigKey},
         env::EnvConfigProcessor,
-        fetch::FetchConfigProcessor,
+        fetch::{resolve_fetch_tags, FetchConfigProcessor},
         util::{expression_to_string, parse_expression, process_expression, process_strings},
         vault::VaultConfigProcessor,
         ConfigExprProcessor, ConfigExprResult,
     },
+    redact::redact_secrets,
+    validate::ConfigValidator,
 };
 
 /// Parses and loads the configuration
 pub struct ConfigLoader {
     processors: Vec<Box<dyn ConfigExprProcessor>>,
+    /// Whether secrets are masked and secret references (!fetch and !encrypted tags) are left unresolved
+    redact: bool,
+    /// Cache of the values retrieved for !fetch tags, keyed by url
+    pub(crate) fetch_cache: Mutex<HashMap<String, String>>,
+    /// The key used to decrypt !encrypted values, retrieved when first required
+    config_key: Mutex<Option<ConfigKey>>,
 }
 
 impl ConfigLoader {
@@ -29,7 +46,38 @@ impl ConfigLoader {
     pub fn new() -> Self {
         Self {
             processors: Self::default_processors(),
+            redact: false,
+            fetch_cache: Mutex::new(HashMap::new()),
+            config_key: Mutex::new(None),
+        }
+    }
+
+    /// Masks any secrets and leaves secret references (!fetch and !encrypted tags) unresolved
+    /// so the processed config can be safely displayed
+    pub fn redacted(mut self) -> Self {
+        self.redact = true;
+        self
+    }
+
+    /// Uses the supplied key to decrypt !encrypted values, rather than
+    /// retrieving it from the environment
+    pub fn with_config_key(self, key: ConfigKey) -> Self {
+        *self.config_key.lock().unwrap() = Some(key);
+        self
+    }
+
+    /// Gets the key used to decrypt !encrypted values
+    pub(crate) fn config_key(&self) -> Result<ConfigKey> {
+        let mut key = self
+            .config_key
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock config key"))?;
+
+        if key.is_none() {
+            *key = Some(ConfigKey::from_env()?);
         }
+
+        Ok(key.clone().unwrap())
     }
 
     fn default_processors() -> Vec<Box<dyn ConfigExprProcessor>> {
@@ -50,6 +98,58 @@ impl ConfigLoader {
         args: HashMap<String, String>,
     ) -> Result<T> {
         let processed = self.load_as_string(path, args)?;
+        self.parse(processed)
+    }
+
+    /// Loads the configuration from the supplied file, validating it before parsing.
+    ///
+    /// All issues found by the validator are reported along with their location
+    /// in the config file before failing.
+    pub fn load_and_validate<T: DeserializeOwned>(
+        &self,
+        path: &Path,
+        args: HashMap<String, String>,
+        validator: &ConfigValidator,
+    ) -> Result<T> {
+        let processed = self.load_as_string(path, args)?;
+        let config: serde_yaml::Value =
+            serde_yaml::from_str(&processed).context("Failed to parse processed config")?;
+
+        let issues = validator.validate(&config);
+        if issues.is_empty() {
+            return self.parse(processed);
+        }
+
+        // We report the location in the original file where possible, falling back
+        // to the processed config for values which originate from included files
+        let original = fs::read_to_string(path).unwrap_or_default();
+        let original_locator = ConfigLocator::new(&original);
+        let processed_locator = ConfigLocator::new(&processed);
+
+        for issue in issues.iter() {
+            let err = match original_locator.find(&issue.path) {
+                Some(loc) => ConfigValidationError::new(
+                    path.display().to_string(),
+                    original.clone(),
+                    Some(loc),
+                    issue,
+                ),
+                None => ConfigValidationError::new(
+                    format!("{} (processed)", path.display()),
+                    processed.clone(),
+                    processed_locator.find(&issue.path),
+                    issue,
+                ),
+            };
+
+            err.print();
+        }
+
+        bail!("Configuration is invalid, found {} error(s)", issues.len());
+    }
+
+    /// Parses the processed yaml into the config type
+    fn parse<T: DeserializeOwned>(&self, processed: String) -> Result<T> {
         debug!("Parsing into {}", type_name::<T>());
         let config: T = match serde_yaml::from_str(&processed) {
             Ok(c) => c,
@@ -78,23 +178,58 @@ impl ConfigLoader {
     }
 
     /// Loads processed yaml from the supplied file
+    ///
+    /// Any files referenced by the `include` directive are loaded
+    /// and merged into the config in the order they are defined.
     pub(crate) fn load_yaml(
         &self,
         path: &Path,
         args: HashMap<String, String>,
+    ) -> Result<serde_yaml::Value> {
+        self.load_yaml_with_includes(path, &args, &mut vec![])
+    }
+
+    fn load_yaml_with_includes(
+        &self,
+        path: &Path,
+        args: &HashMap<String, String>,
+        stack: &mut Vec<PathBuf>,
     ) -> Result<serde_yaml::Value> {
         debug!("Loading yaml from file {}", path.display());
 
-        let file_data = fs::read(path).context(format!(
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("Failed to get real path of {}", path.display()))?;
+        ensure!(
+            !stack.contains(&path),
+            "Config file {} is included recursively",
+            path.display()
+        );
+
+        let file_data = fs::read(&path).context(format!(
             "Failed to read config from file {}",
             path.display()
         ))?;
 
-        let config: serde_yaml::Value =
-            serde_yaml::from_slice(file_data.as_slice()).context("Failed to parse yaml")?;
-        let mut ctx = Ctx::new(self, config.clone(), Some(path.to_path_buf()), args);
+        let config: serde_yaml::Value = serde_yaml::from_slice(file_data.as_slice())
+            .with_context(|| format!("Failed to parse yaml from file {}", path.display()))?;
+        let mut ctx = Ctx::new(self, config.clone(), Some(path.clone()), args.clone());
+
+        let mut config = self.process_config(&mut ctx, config)?;
+        if self.redact {
+            redact_secrets(Some(&ctx.config), &mut config);
+        }
+        let includes = take_includes(&mut config, Some(&path))?;
+
+        stack.push(path);
+        for include in includes {
+            info!("Including config from path {}", include.display());
+            let included = self.load_yaml_with_includes(&include, args, stack)?;
+            merge(&mut config, included);
+        }
+        stack.pop();
 
-        self.process_config(&mut ctx, config)
+        Ok(config)
     }
 
     /// Loads a subsection of config
@@ -152,6 +287,11 @@ impl ConfigLoader {
         }
 
         let config = process_config(ctx, config)?;
+        let config = if self.redact {
+            config
+        } else {
+            resolve_encrypted_tags(self, resolve_fetch_tags(self, config)?)?
+        };
 
         debug!("Finished processing yaml from file");
         Ok(config)
@@ -199,6 +339,20 @@ mod tests {
         assert_eq!(result.unwrap(), r#"a: ${unknown}"#);
     }
 
+    #[test]
+    fn test_config_loader_lower_case_interpolation() {
+        // Templates such as ${origin} are not shell-style env var references
+        let input = r#"redirect_uri: ${origin}"#;
+        let result = process_yaml(input, None, None);
+
+        assert_eq!(result.unwrap(), r#"redirect_uri: ${origin}"#);
+
+        let input = r#"redirect_uri: \${origin}"#;
+        let result = process_yaml(input, None, None);
+
+        assert_eq!(result.unwrap(), r#"redirect_uri: ${origin}"#);
+    }
+
     #[test]
     fn test_config_loader_env_interpolation() {
         env::set_var("ANSILO_CONFIG_LOADER_TEST1", "FROM_ENV_VAR");
@@ -221,6 +375,99 @@ mod tests {
         assert_eq!(result.unwrap(), r#"a: RESOLVED_OUTER_VALUE"#);
     }
 
+    #[test]
+    fn test_config_loader_shell_style_env_interpolation() {
+        env::set_var("ANSILO_CONFIG_LOADER_TEST3", "FROM_ENV_VAR");
+        let input = r#"a: "${ANSILO_CONFIG_LOADER_TEST3} ${ANSILO_CONFIG_LOADER_TEST4:-fallback}""#;
+        let result = process_yaml(input, None, None);
+
+        assert_eq!(result.unwrap(), r#"a: FROM_ENV_VAR fallback"#);
+    }
+
+    #[test]
+    fn test_config_loader_shell_style_env_interpolation_unset() {
+        let input = r#"a: "${ANSILO_CONFIG_LOADER_TEST5}""#;
+        process_yaml(input, None, None).unwrap_err();
+
+        let input = r#"a: '\${ANSILO_CONFIG_LOADER_TEST5}'"#;
+        let result = process_yaml(input, None, None).unwrap();
+        assert!(result.contains("${ANSILO_CONFIG_LOADER_TEST5}"));
+        assert!(!result.contains('\\'));
+    }
+
+    #[test]
+    fn test_config_loader_fetch_tag() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join("secret"), "password123\n").unwrap();
+        fs::write(
+            dir.path().join("main.yml"),
+            "password: !fetch file://${dir}/secret",
+        )
+        .unwrap();
+
+        let result = ConfigLoader::new()
+            .load_as_string(&dir.path().join("main.yml"), HashMap::new())
+            .unwrap();
+        assert_eq!(result, "password: password123\n");
+
+        let result = ConfigLoader::new()
+            .redacted()
+            .load_as_string(&dir.path().join("main.yml"), HashMap::new())
+            .unwrap();
+        assert_eq!(
+            result,
+            format!(
+                "password: !fetch file://{}/secret\n",
+                dir.path().canonicalize().unwrap().display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_config_loader_encrypted_tag() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let key = ConfigKey::new(&[3u8; 32]).unwrap();
+        let encrypted = key.encrypt("password123").unwrap();
+        fs::write(
+            dir.path().join("main.yml"),
+            format!("password: !encrypted {encrypted}"),
+        )
+        .unwrap();
+
+        let result = ConfigLoader::new()
+            .with_config_key(key.clone())
+            .load_as_string(&dir.path().join("main.yml"), HashMap::new())
+            .unwrap();
+        assert_eq!(result, "password: password123\n");
+
+        let result = ConfigLoader::new()
+            .with_config_key(key)
+            .redacted()
+            .load_as_string(&dir.path().join("main.yml"), HashMap::new())
+            .unwrap();
+        assert_eq!(result, format!("password: !encrypted {encrypted}\n"));
+    }
+
+    #[test]
+    fn test_config_loader_redacted() {
+        let dir = tempfile::TempDir::new().unwrap();
+        env::set_var("ANSILO_TEST_REDACTED_PASSWORD", "password123");
+        fs::write(
+            dir.path().join("main.yml"),
+            "username: mary\npassword: ${env:ANSILO_TEST_REDACTED_PASSWORD}",
+        )
+        .unwrap();
+
+        let result = ConfigLoader::new()
+            .redacted()
+            .load_as_string(&dir.path().join("main.yml"), HashMap::new())
+            .unwrap();
+        assert_eq!(
+            result,
+            "username: mary\npassword: <redacted from ${env:ANSILO_TEST_REDACTED_PASSWORD}>\n"
+        );
+    }
+
     #[test]
     fn test_config_loader_dir_interpolation() {
         let input = r#"a: "${dir}/bar/baz""#;
@@ -229,6 +476,49 @@ mod tests {
         assert_eq!(result.unwrap(), r#"a: /foo/bar/baz"#);
     }
 
+    #[test]
+    fn test_config_loader_includes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("conf.d")).unwrap();
+        fs::write(
+            dir.path().join("main.yml"),
+            "name: main\ninclude: ${dir}/conf.d\nsources:\n  - id: a",
+        )
+        .unwrap();
+        fs::write(dir.path().join("conf.d/1.yml"), "sources:\n  - id: b").unwrap();
+        fs::write(
+            dir.path().join("conf.d/2.yml"),
+            "include: ../jobs.yml\nsources:\n  - id: c",
+        )
+        .unwrap();
+        fs::write(dir.path().join("jobs.yml"), "jobs:\n  - id: job").unwrap();
+
+        let loader = ConfigLoader::new();
+        let result = loader
+            .load_yaml(&dir.path().join("main.yml"), HashMap::new())
+            .unwrap();
+
+        assert_eq!(
+            result,
+            serde_yaml::from_str::<serde_yaml::Value>(
+                "name: main\nsources:\n  - id: a\n  - id: b\n  - id: c\njobs:\n  - id: job"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_config_loader_recursive_include() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join("a.yml"), "include: b.yml").unwrap();
+        fs::write(dir.path().join("b.yml"), "include: a.yml").unwrap();
+
+        let loader = ConfigLoader::new();
+        loader
+            .load_yaml(&dir.path().join("a.yml"), HashMap::new())
+            .unwrap_err();
+    }
+
     #[test]
     fn test_config_loader_arg_interpolation() {
         let input = r#"a: "${arg:TEST_ARG} bar""#;
@@ -244,4 +534,50 @@ mod tests {
 
         assert_eq!(result.unwrap(), r#"a: foo bar"#);
     }
+
+    #[test]
+    fn test_config_loader_load_and_validate() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("main.yml"),
+            "name: main\nsources:\n  - id: a\n    type: jdbc.mysl\n    options: {}\n  - id: a",
+        )
+        .unwrap();
+
+        let loader = ConfigLoader::new();
+        let validator = ConfigValidator::new().with_source_types(["jdbc.mysql"]);
+        let err = loader
+            .load_and_validate::<serde_yaml::Value>(
+                &dir.path().join("main.yml"),
+                HashMap::new(),
+                &validator,
+            )
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Configuration is invalid, found 5 error(s)"
+        );
+    }
+
+    #[test]
+    fn test_config_loader_load_and_validate_valid() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("main.yml"),
+            "name: main\nnetworking:\n  port: 1234\nauth:\n  users: []\nbuild:\n  stages: []",
+        )
+        .unwrap();
+
+        let loader = ConfigLoader::new();
+        let config = loader
+            .load_and_validate::<serde_yaml::Value>(
+                &dir.path().join("main.yml"),
+                HashMap::new(),
+                &ConfigValidator::new(),
+            )
+            .unwrap();
+
+        assert_eq!(config["name"], serde_yaml::Value::String("main".into()));
+    }
 }
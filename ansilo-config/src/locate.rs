@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use yaml_rust::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::Marker,
+};
+
+/// Maps field paths (eg "sources[1].type") to their location in a yaml document.
+///
+/// serde_yaml does not expose the location of values once parsed so we
+/// re-parse the document using yaml-rust which reports markers for each event.
+#[derive(Debug, Default)]
+pub(crate) struct ConfigLocator {
+    locations: HashMap<String, Location>,
+}
+
+/// A location within a yaml document
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Location {
+    /// The byte offset into the document
+    pub offset: usize,
+    /// The line number (1-based)
+    pub line: usize,
+    /// The column number (1-based)
+    pub col: usize,
+}
+
+impl ConfigLocator {
+    /// Parses the supplied yaml, if the yaml is invalid no locations are returned
+    pub(crate) fn new(src: &str) -> Self {
+        let mut builder = Builder {
+            src,
+            stack: vec![],
+            locations: HashMap::new(),
+        };
+
+        if Parser::new(src.chars()).load(&mut builder, false).is_err() {
+            return Self::default();
+        }
+
+        Self {
+            locations: builder.locations,
+        }
+    }
+
+    /// Finds the location of the field at the supplied path.
+    ///
+    /// If the field is not present (eg a missing field) the location
+    /// of the closest parent is returned.
+    pub(crate) fn find(&self, path: &str) -> Option<Location> {
+        let mut path = path;
+
+        loop {
+            if let Some(loc) = self.locations.get(path) {
+                return Some(*loc);
+            }
+
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
+}
+
+enum Frame {
+    Mapping { path: String, key: Option<String> },
+    Sequence { path: String, idx: usize },
+}
+
+struct Builder<'a> {
+    src: &'a str,
+    stack: Vec<Frame>,
+    locations: HashMap<String, Location>,
+}
+
+impl<'a> Builder<'a> {
+    fn is_key(&self) -> bool {
+        matches!(self.stack.last(), Some(Frame::Mapping { key: None, .. }))
+    }
+
+    fn key(&mut self, key: String, mark: Marker) {
+        if let Some(Frame::Mapping { path, key: k }) = self.stack.last_mut() {
+            let path = join(path, &key);
+            *k = Some(key);
+            self.record(path, mark);
+        }
+    }
+
+    /// Returns the path of the next value in the current mapping or sequence
+    fn value(&mut self, mark: Marker) -> String {
+        let path = match self.stack.last_mut() {
+            None => return String::new(),
+            Some(Frame::Mapping { path, key }) => {
+                return join(path, &key.take().unwrap_or_default())
+            }
+            Some(Frame::Sequence { path, idx }) => {
+                let path = format!("{path}[{idx}]");
+                *idx += 1;
+                path
+            }
+        };
+
+        self.record(path.clone(), mark);
+        path
+    }
+
+    /// Returns the path of a nested mapping or sequence.
+    ///
+    /// Complex keys are not supported, they are treated as an empty key.
+    fn nested(&mut self, mark: Marker) -> String {
+        if self.is_key() {
+            self.key(String::new(), mark);
+        }
+
+        self.value(mark)
+    }
+
+    fn record(&mut self, path: String, mark: Marker) {
+        // yaml-rust reports character offsets so we convert to byte offsets
+        let offset = self
+            .src
+            .char_indices()
+            .nth(mark.index())
+            .map(|(i, _)| i)
+            .unwrap_or(self.src.len());
+
+        self.locations.entry(path).or_insert(Location {
+            offset,
+            line: mark.line(),
+            col: mark.col() + 1,
+        });
+    }
+}
+
+impl<'a> MarkedEventReceiver for Builder<'a> {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Scalar(val, ..) if self.is_key() => self.key(val, mark),
+            Event::Alias(_) if self.is_key() => self.key(String::new(), mark),
+            Event::Scalar(..) | Event::Alias(_) => {
+                self.value(mark);
+            }
+            Event::MappingStart(_) => {
+                let path = self.nested(mark);
+                self.stack.push(Frame::Mapping { path, key: None });
+            }
+            Event::SequenceStart(_) => {
+                let path = self.nested(mark);
+                self.stack.push(Frame::Sequence { path, idx: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = r#"name: test
+networking:
+  port: 1234
+sources:
+  - id: a
+    type: jdbc.mysql
+  - id: b
+    options:
+      tags: [x, y]
+"#;
+
+    #[test]
+    fn test_locate() {
+        let locator = ConfigLocator::new(YAML);
+
+        assert_eq!(
+            locator.find("name"),
+            Some(Location {
+                offset: 0,
+                line: 1,
+                col: 1
+            })
+        );
+        assert_eq!(
+            locator.find("networking.port"),
+            Some(Location {
+                offset: 25,
+                line: 3,
+                col: 3
+            })
+        );
+        assert_eq!(locator.find("sources[0].type").unwrap().line, 6);
+        assert_eq!(locator.find("sources[1]").unwrap().line, 7);
+        assert_eq!(locator.find("sources[1].options.tags[1]").unwrap().line, 9);
+    }
+
+    #[test]
+    fn test_locate_missing_field_uses_parent() {
+        let locator = ConfigLocator::new(YAML);
+
+        assert_eq!(locator.find("sources[1].type").unwrap().line, 7);
+        assert_eq!(locator.find("unknown"), None);
+    }
+
+    #[test]
+    fn test_locate_invalid_yaml() {
+        let locator = ConfigLocator::new("a: [");
+
+        assert_eq!(locator.find("a"), None);
+    }
+}
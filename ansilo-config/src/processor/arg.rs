@@ -7,6 +7,9 @@ use super::{
     util::match_interpolation, ConfigExprProcessor, ConfigExprResult, ConfigStringExpr as X,
 };
 
+/// The prefix of the expressions of this processor, eg ${arg:PORT}
+pub(crate) const PREFIX: &str = "arg";
+
 /// Interpolates confugration arguments from the command line
 #[derive(Default)]
 pub struct ArgConfigProcessor {}
@@ -18,7 +21,7 @@ impl ConfigExprProcessor for ArgConfigProcessor {
 
     fn process(&self, ctx: &mut Ctx, expr: X) -> Result<ConfigExprResult> {
         Ok(ConfigExprResult::Expr(
-            match match_interpolation(&expr, &["arg"]) {
+            match match_interpolation(&expr, &[PREFIX]) {
                 Some(p) => {
                     ensure!(p.len() > 1, "${{arg:...}} expression cannot be empty");
 
@@ -30,9 +33,8 @@ impl ConfigExprProcessor for ArgConfigProcessor {
                     };
 
                     trace!(
-                        "Replaced configuration expression '{}' with '{}'",
-                        expression_to_string(&expr),
-                        replacement
+                        "Resolved configuration expression '{}'",
+                        expression_to_string(&expr)
                     );
 
                     X::Constant(replacement.clone())
@@ -7,6 +7,9 @@ use super::{
     util::match_interpolation, ConfigExprProcessor, ConfigExprResult, ConfigStringExpr as X,
 };
 
+/// The prefix of the expressions of this processor, eg ${dir}
+pub(crate) const PREFIX: &str = "dir";
+
 /// Interpolates configuration referncing the current directory
 #[derive(Default)]
 pub struct DirConfigProcessor {}
@@ -18,7 +21,7 @@ impl ConfigExprProcessor for DirConfigProcessor {
 
     fn process(&self, ctx: &mut Ctx, expr: X) -> Result<ConfigExprResult> {
         Ok(ConfigExprResult::Expr(
-            match (match_interpolation(&expr, &["dir"]), &ctx.path) {
+            match (match_interpolation(&expr, &[PREFIX]), &ctx.path) {
                 (Some(_), Some(path)) if path.parent().is_some() => {
                     let replacement = path.parent().unwrap().to_string_lossy().to_string();
 
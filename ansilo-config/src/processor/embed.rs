@@ -7,6 +7,9 @@ use super::{
     util::match_interpolation, ConfigExprProcessor, ConfigExprResult, ConfigStringExpr as X,
 };
 
+/// The prefix of the expressions of this processor, eg ${embed:example.yml}
+pub(crate) const PREFIX: &str = "embed";
+
 /// Interpolates configuration that embeds the output of the supplied url
 /// This expects the output to parse as valid YAML.
 /// This feature allows for a form of easy code splitting.
@@ -19,7 +22,7 @@ impl ConfigExprProcessor for EmbedConfigProcessor {
     }
 
     fn process(&self, _ctx: &mut Ctx, expr: X) -> Result<ConfigExprResult> {
-        Ok(match match_interpolation(&expr, &["embed"]) {
+        Ok(match match_interpolation(&expr, &[PREFIX]) {
             Some(p) => {
                 ensure!(p.len() > 1, "${{embed:...}} expression must have arguments");
 
@@ -33,9 +36,8 @@ impl ConfigExprProcessor for EmbedConfigProcessor {
                     .with_context(|| format!("Failed to parse output from {url} as yaml"))?;
 
                 trace!(
-                    "Replaced configuration expression '{}' with '{}'",
-                    expression_to_string(&expr),
-                    serde_yaml::to_string(&yaml).unwrap_or("invalid".into())
+                    "Resolved configuration expression '{}'",
+                    expression_to_string(&expr)
                 );
 
                 ConfigExprResult::Yaml(yaml)
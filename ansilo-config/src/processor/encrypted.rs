@@ -0,0 +1,206 @@
+use std::env;
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, OsRng},
+    AeadCore, Aes256Gcm, Nonce,
+};
+use ansilo_core::err::{bail, ensure, Context, Error, Result};
+use ansilo_logging::debug;
+use serde_yaml::{value::TaggedValue, Mapping, Value};
+
+use crate::loader::ConfigLoader;
+
+/// The yaml tag used for values encrypted with the config key,
+/// eg `password: !encrypted <base64>`
+pub(crate) const ENCRYPTED_TAG: &str = "encrypted";
+
+/// The env var containing the base64-encoded key used to decrypt `!encrypted` values
+pub const CONFIG_KEY_ENV: &str = "ANSILO_CONFIG_KEY";
+
+/// The env var containing the url from which the base64-encoded key is retrieved,
+/// eg using `sh://` to decrypt a wrapped key using a KMS
+pub const CONFIG_KEY_URL_ENV: &str = "ANSILO_CONFIG_KEY_URL";
+
+/// The length of the AES-GCM nonce prepended to the encrypted values
+const NONCE_LEN: usize = 12;
+
+/// The key used to encrypt and decrypt secrets within the configuration.
+///
+/// Values are encrypted using AES-256-GCM with a random nonce and
+/// encoded as base64, so they can be safely committed alongside the config.
+#[derive(Clone)]
+pub struct ConfigKey {
+    cipher: Aes256Gcm,
+}
+
+impl ConfigKey {
+    /// Creates a key from the supplied 256-bit key material
+    pub fn new(key: &[u8]) -> Result<Self> {
+        ensure!(
+            key.len() == 32,
+            "Config key must be 256 bits, found {} bits",
+            key.len() * 8
+        );
+
+        Ok(Self {
+            cipher: Aes256Gcm::new_from_slice(key).map_err(|_| Error::msg("Invalid config key"))?,
+        })
+    }
+
+    /// Creates a key from the base64-encoded key material
+    pub fn parse(key: &str) -> Result<Self> {
+        let key =
+            base64::decode(key.trim()).context("Failed to decode config key, expected base64")?;
+
+        Self::new(&key)
+    }
+
+    /// Retrieves the key from the environment, either directly from
+    /// `ANSILO_CONFIG_KEY` or from the url in `ANSILO_CONFIG_KEY_URL`
+    pub fn from_env() -> Result<Self> {
+        if let Ok(key) = env::var(CONFIG_KEY_ENV) {
+            return Self::parse(&key);
+        }
+
+        if let Ok(url) = env::var(CONFIG_KEY_URL_ENV) {
+            debug!("Retrieving config key from {url}");
+            let key = ansilo_util_url::get(url.clone())
+                .with_context(|| format!("Failed to retrieve config key from {url}"))?;
+
+            return Self::parse(&String::from_utf8_lossy(&key));
+        }
+
+        bail!("A config key is required to decrypt !{ENCRYPTED_TAG} values, set the {CONFIG_KEY_ENV} or {CONFIG_KEY_URL_ENV} env var")
+    }
+
+    /// Encrypts the value, returning the base64-encoded ciphertext
+    pub fn encrypt(&self, value: &str) -> Result<String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let encrypted = self
+            .cipher
+            .encrypt(&nonce, value.as_bytes())
+            .map_err(|_| Error::msg("Failed to encrypt value"))?;
+
+        Ok(base64::encode(
+            [nonce.as_slice(), encrypted.as_slice()].concat(),
+        ))
+    }
+
+    /// Decrypts the base64-encoded ciphertext
+    pub fn decrypt(&self, value: &str) -> Result<String> {
+        let data =
+            base64::decode(value.trim()).context("Failed to decode encrypted value as base64")?;
+        ensure!(data.len() > NONCE_LEN, "Encrypted value is too short");
+
+        let (nonce, encrypted) = data.split_at(NONCE_LEN);
+        let decrypted = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), encrypted)
+            .map_err(|_| Error::msg("Failed to decrypt value, the config key may be incorrect"))?;
+
+        String::from_utf8(decrypted).context("Decrypted value is not valid UTF8")
+    }
+}
+
+/// Resolves any values tagged with `!encrypted <base64>`, replacing
+/// them with the value decrypted using the config key.
+///
+/// The key is only retrieved if the config contains encrypted values.
+/// As these typically contain secrets, the decrypted values are never logged.
+pub(crate) fn resolve_encrypted_tags(loader: &ConfigLoader, node: Value) -> Result<Value> {
+    Ok(match node {
+        Value::Tagged(tagged) if tagged.tag == ENCRYPTED_TAG => {
+            let value = match tagged.value {
+                Value::String(value) => value,
+                other => bail!(
+                    "Expected base64 string for !{ENCRYPTED_TAG} tag, found: {}",
+                    serde_yaml::to_string(&other).unwrap_or_default().trim_end()
+                ),
+            };
+
+            Value::String(loader.config_key()?.decrypt(&value)?)
+        }
+        Value::Tagged(tagged) => Value::Tagged(Box::new(TaggedValue {
+            tag: tagged.tag,
+            value: resolve_encrypted_tags(loader, tagged.value)?,
+        })),
+        Value::Sequence(seq) => Value::Sequence(
+            seq.into_iter()
+                .map(|n| resolve_encrypted_tags(loader, n))
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        Value::Mapping(map) => Value::Mapping(
+            map.into_iter()
+                .map(|(k, v)| Ok((k, resolve_encrypted_tags(loader, v)?)))
+                .collect::<Result<Mapping>>()?,
+        ),
+        n => n,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_key() -> ConfigKey {
+        ConfigKey::new(&[1u8; 32]).unwrap()
+    }
+
+    #[test]
+    fn test_config_key_encrypt_decrypt() {
+        let key = mock_key();
+
+        let encrypted = key.encrypt("password123").unwrap();
+        assert!(!encrypted.contains("password123"));
+        assert_ne!(encrypted, key.encrypt("password123").unwrap());
+        assert_eq!(key.decrypt(&encrypted).unwrap(), "password123");
+
+        assert_eq!(
+            ConfigKey::new(&[2u8; 32])
+                .unwrap()
+                .decrypt(&encrypted)
+                .unwrap_err()
+                .to_string(),
+            "Failed to decrypt value, the config key may be incorrect"
+        );
+    }
+
+    #[test]
+    fn test_config_key_invalid() {
+        assert_eq!(
+            ConfigKey::parse("YWJj").err().unwrap().to_string(),
+            "Config key must be 256 bits, found 24 bits"
+        );
+        ConfigKey::parse("not base64!").err().unwrap();
+    }
+
+    #[test]
+    fn test_resolve_encrypted_tags() {
+        let key = mock_key();
+        let loader = ConfigLoader::new().with_config_key(key.clone());
+
+        let input = serde_yaml::from_str::<Value>(&format!(
+            "a: !encrypted {0}\nb:\n  - !encrypted {0}\nc: !other value",
+            key.encrypt("secret").unwrap()
+        ))
+        .unwrap();
+
+        let result = resolve_encrypted_tags(&loader, input).unwrap();
+
+        assert_eq!(
+            result,
+            serde_yaml::from_str::<Value>("a: secret\nb:\n  - secret\nc: !other value").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_resolve_encrypted_tags_invalid() {
+        let loader = ConfigLoader::new().with_config_key(mock_key());
+
+        let input = serde_yaml::from_str::<Value>("a: !encrypted [1, 2]").unwrap();
+        resolve_encrypted_tags(&loader, input).unwrap_err();
+
+        let input = serde_yaml::from_str::<Value>("a: !encrypted YWJj").unwrap();
+        resolve_encrypted_tags(&loader, input).unwrap_err();
+    }
+}
@@ -1,15 +1,24 @@
 use std::env;
 
-use ansilo_core::err::Result;
+use ansilo_core::err::{bail, Result};
 use ansilo_logging::{trace, warn};
 
 use crate::{ctx::Ctx, processor::util::expression_to_string};
 
 use super::{
     util::match_interpolation, ConfigExprProcessor, ConfigExprResult, ConfigStringExpr as X,
+    PREFIXES,
 };
 
+/// The prefix of the expressions of this processor, eg ${env:VAR:default}
+pub(crate) const PREFIX: &str = "env";
+
 /// Interpolates configuration using environment variables
+///
+/// Supports the explicit form ${env:VAR:default} as well as the
+/// shell-style forms ${VAR} and ${VAR:-default}. Shell-style variable
+/// names must be upper case so other expressions, such as ${origin},
+/// are left as is.
 #[derive(Default)]
 pub struct EnvConfigProcessor {}
 
@@ -20,40 +29,91 @@ impl ConfigExprProcessor for EnvConfigProcessor {
 
     fn process(&self, _ctx: &mut Ctx, expr: X) -> Result<ConfigExprResult> {
         Ok(ConfigExprResult::Expr(
-            match match_interpolation(&expr, &["env"]) {
+            match match_interpolation(&expr, &[PREFIX]) {
                 Some(p) => {
                     let name = p
                         .get(1)
                         .map(|i| i.to_string())
                         .unwrap_or_else(|| "".to_owned());
-                    let default = p
-                        .get(2)
-                        .map(|i| i.to_string())
-                        .unwrap_or_else(|| "".to_owned());
+                    let default = p.get(2).map(|i| i.to_string());
 
-                    let var = match env::var(name.clone()) {
-                        Err(err) => {
-                            warn!("Failed to get env var \"{}\": {:?}", name, err);
-                            None
+                    let replacement = match (get_var(&name), default) {
+                        (Some(var), _) | (None, Some(var)) => var,
+                        (None, None) => {
+                            warn!(
+                                "Environment variable \"{}\" is not set, using an empty string",
+                                name
+                            );
+                            "".to_owned()
                         }
-                        Ok(var) if var.is_empty() => None,
-                        Ok(var) => Some(var),
                     };
-
-                    let replacement = var.unwrap_or(default);
                     trace!(
-                        "Replaced configuration expression '{}' with '{}'",
-                        expression_to_string(&expr),
-                        replacement
+                        "Resolved configuration expression '{}'",
+                        expression_to_string(&expr)
                     );
                     X::Constant(replacement)
                 }
-                _ => expr,
+                _ => match match_shell_style(&expr) {
+                    Some((name, default)) => match (get_var(&name), default) {
+                        (Some(var), _) | (None, Some(var)) => {
+                            trace!(
+                                "Resolved configuration expression '{}'",
+                                expression_to_string(&expr)
+                            );
+                            X::Constant(var)
+                        }
+                        (None, None) => bail!(
+                            "Environment variable \"{}\" is not set and has no default",
+                            name
+                        ),
+                    },
+                    None => expr,
+                },
             },
         ))
     }
 }
 
+/// Matches expressions in the form ${VAR} or ${VAR:-default}, where the
+/// name is upper case, returning the variable name and the default, if any
+fn match_shell_style(expr: &X) -> Option<(String, Option<String>)> {
+    let parts = match_interpolation(expr, &[])?;
+    let name = parts.first()?;
+
+    let valid_name = name
+        .chars()
+        .next()
+        .map(|c| c.is_ascii_uppercase() || c == '_')
+        .unwrap_or(false)
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+
+    if !valid_name || PREFIXES.contains(&name.as_str()) {
+        return None;
+    }
+
+    match parts.len() {
+        1 => Some((name.clone(), None)),
+        _ if parts[1].starts_with('-') => {
+            Some((name.clone(), Some(parts[1..].join(":")[1..].to_string())))
+        }
+        _ => None,
+    }
+}
+
+/// Gets the value of the env var, returning None if it is unset or empty
+fn get_var(name: &str) -> Option<String> {
+    match env::var(name) {
+        Err(err) => {
+            trace!("Failed to get env var \"{}\": {:?}", name, err);
+            None
+        }
+        Ok(var) if var.is_empty() => None,
+        Ok(var) => Some(var),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -134,4 +194,92 @@ mod tests {
             ConfigExprResult::Expr(X::Constant("DEFAULT_VAL".to_string()))
         );
     }
+
+    #[test]
+    fn test_env_processor_shell_style() {
+        let mut ctx = Ctx::mock();
+        let processor = EnvConfigProcessor::default();
+
+        env::set_var("ANSILO_TEST_VAR4", "FROM_ENV");
+        let input = X::Interpolation(vec![X::Constant("ANSILO_TEST_VAR4".to_owned())]);
+        let result = processor.process(&mut ctx, input.clone());
+
+        assert_eq!(
+            result.unwrap(),
+            ConfigExprResult::Expr(X::Constant("FROM_ENV".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_env_processor_shell_style_default() {
+        let mut ctx = Ctx::mock();
+        let processor = EnvConfigProcessor::default();
+
+        let input = X::Interpolation(vec![
+            X::Constant("ANSILO_TEST_VAR5".to_owned()),
+            X::Constant("-http".to_owned()),
+            X::Constant("//fallback".to_owned()),
+        ]);
+        let result = processor.process(&mut ctx, input.clone());
+
+        assert_eq!(
+            result.unwrap(),
+            ConfigExprResult::Expr(X::Constant("http://fallback".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_env_processor_shell_style_unset_without_default() {
+        let mut ctx = Ctx::mock();
+        let processor = EnvConfigProcessor::default();
+
+        let input = X::Interpolation(vec![X::Constant("ANSILO_TEST_VAR6".to_owned())]);
+        let err = processor.process(&mut ctx, input).unwrap_err();
+
+        assert!(err.to_string().contains("\"ANSILO_TEST_VAR6\" is not set"));
+    }
+
+    #[test]
+    fn test_env_processor_unset_without_default() {
+        let mut ctx = Ctx::mock();
+        let processor = EnvConfigProcessor::default();
+
+        let input = X::Interpolation(vec![
+            X::Constant("env".to_owned()),
+            X::Constant("ANSILO_TEST_VAR7".to_owned()),
+        ]);
+        let result = processor.process(&mut ctx, input);
+
+        assert_eq!(
+            result.unwrap(),
+            ConfigExprResult::Expr(X::Constant("".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_env_processor_shell_style_ignores_reserved_and_invalid_names() {
+        let mut ctx = Ctx::mock();
+        let processor = EnvConfigProcessor::default();
+
+        for input in [
+            X::Interpolation(vec![X::Constant("dir".to_owned())]),
+            X::Interpolation(vec![
+                X::Constant("arg".to_owned()),
+                X::Constant("-foo".to_owned()),
+            ]),
+            X::Interpolation(vec![X::Constant("not-a-var".to_owned())]),
+            X::Interpolation(vec![X::Constant("origin".to_owned())]),
+            X::Interpolation(vec![
+                X::Constant("lower_case".to_owned()),
+                X::Constant("-default".to_owned()),
+            ]),
+            X::Interpolation(vec![
+                X::Constant("VAR".to_owned()),
+                X::Constant("foo".to_owned()),
+            ]),
+        ] {
+            let result = processor.process(&mut ctx, input.clone());
+            assert_eq!(result.unwrap(), ConfigExprResult::Expr(input));
+        }
+    }
 }
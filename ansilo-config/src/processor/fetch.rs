@@ -1,12 +1,16 @@
-use ansilo_core::err::{ensure, Context, Result};
-use ansilo_logging::trace;
+use ansilo_core::err::{bail, ensure, Context, Error, Result};
+use ansilo_logging::{debug, trace};
+use serde_yaml::{value::TaggedValue, Mapping, Value};
 
-use crate::{ctx::Ctx, processor::util::expression_to_string};
+use crate::{ctx::Ctx, loader::ConfigLoader, processor::util::expression_to_string};
 
 use super::{
     util::match_interpolation, ConfigExprProcessor, ConfigExprResult, ConfigStringExpr as X,
 };
 
+/// The prefix of the expressions of this processor, eg ${fetch:file://secret}
+pub(crate) const PREFIX: &str = "fetch";
+
 /// Interpolates configuration that fetchs the output of the supplied url
 /// This will return the output as UTF8 string
 #[derive(Default)]
@@ -18,7 +22,7 @@ impl ConfigExprProcessor for FetchConfigProcessor {
     }
 
     fn process(&self, _ctx: &mut Ctx, expr: X) -> Result<ConfigExprResult> {
-        Ok(match match_interpolation(&expr, &["fetch"]) {
+        Ok(match match_interpolation(&expr, &[PREFIX]) {
             Some(p) => {
                 ensure!(p.len() > 1, "${{fetch:...}} expression must have arguments");
 
@@ -32,9 +36,8 @@ impl ConfigExprProcessor for FetchConfigProcessor {
                     .with_context(|| format!("Failed to parse output from url as UTF8: {url}"))?;
 
                 trace!(
-                    "Replaced configuration expression '{}' with '{}'",
-                    expression_to_string(&expr),
-                    output
+                    "Resolved configuration expression '{}'",
+                    expression_to_string(&expr)
                 );
 
                 ConfigExprResult::Expr(X::Constant(output))
@@ -44,6 +47,71 @@ impl ConfigExprProcessor for FetchConfigProcessor {
     }
 }
 
+/// The yaml tag used to reference a secret or other external value
+/// for any string config field, eg `password: !fetch file:///run/secrets/db`
+pub(crate) const FETCH_TAG: &str = "fetch";
+
+/// Resolves any values tagged with `!fetch <url>`, replacing them with the
+/// contents retrieved from the url as a string (without any trailing newline).
+///
+/// Retrieved values are cached by the loader so each url is only fetched once.
+/// As these typically contain secrets, the retrieved values are never logged.
+pub(crate) fn resolve_fetch_tags(loader: &ConfigLoader, node: Value) -> Result<Value> {
+    Ok(match node {
+        Value::Tagged(tagged) if tagged.tag == FETCH_TAG => {
+            let url = match tagged.value {
+                Value::String(url) => url,
+                other => bail!(
+                    "Expected url string for !{FETCH_TAG} tag, found: {}",
+                    serde_yaml::to_string(&other).unwrap_or_default().trim_end()
+                ),
+            };
+
+            Value::String(fetch_cached(loader, &url)?)
+        }
+        Value::Tagged(tagged) => Value::Tagged(Box::new(TaggedValue {
+            tag: tagged.tag,
+            value: resolve_fetch_tags(loader, tagged.value)?,
+        })),
+        Value::Sequence(seq) => Value::Sequence(
+            seq.into_iter()
+                .map(|n| resolve_fetch_tags(loader, n))
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        Value::Mapping(map) => Value::Mapping(
+            map.into_iter()
+                .map(|(k, v)| Ok((k, resolve_fetch_tags(loader, v)?)))
+                .collect::<Result<Mapping>>()?,
+        ),
+        n => n,
+    })
+}
+
+fn fetch_cached(loader: &ConfigLoader, url: &str) -> Result<String> {
+    let mut cache = loader
+        .fetch_cache
+        .lock()
+        .map_err(|_| Error::msg("Failed to lock fetch cache"))?;
+
+    if let Some(cached) = cache.get(url) {
+        debug!("Using cached value for !{FETCH_TAG} {url} (<redacted>)");
+        return Ok(cached.clone());
+    }
+
+    debug!("Resolving !{FETCH_TAG} {url}");
+    let output = ansilo_util_url::get(url).with_context(|| format!("Failed to retrieve {url}"))?;
+    let output = String::from_utf8(output)
+        .with_context(|| format!("Failed to parse output from url as UTF8: {url}"))?;
+    let output = output
+        .strip_suffix('\n')
+        .map(|o| o.strip_suffix('\r').unwrap_or(o))
+        .unwrap_or(&output)
+        .to_string();
+
+    cache.insert(url.to_string(), output.clone());
+    Ok(output)
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Write;
@@ -96,4 +164,52 @@ mod tests {
             ConfigExprResult::Expr(X::Constant("hello world".into()))
         );
     }
+
+    #[test]
+    fn test_resolve_fetch_tags() {
+        let loader = ConfigLoader::new();
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"secret\n").unwrap();
+
+        let input = serde_yaml::from_str::<Value>(&format!(
+            "a: !fetch file://{0}\nb:\n  - !fetch file://{0}\nc: !other value",
+            file.path().display()
+        ))
+        .unwrap();
+
+        let result = resolve_fetch_tags(&loader, input).unwrap();
+
+        assert_eq!(
+            result,
+            serde_yaml::from_str::<Value>("a: secret\nb:\n  - secret\nc: !other value").unwrap()
+        );
+        assert_eq!(loader.fetch_cache.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_fetch_tags_uses_cache() {
+        let loader = ConfigLoader::new();
+        loader
+            .fetch_cache
+            .lock()
+            .unwrap()
+            .insert("file:///cached/secret".into(), "from_cache".into());
+
+        let input = serde_yaml::from_str::<Value>("a: !fetch file:///cached/secret").unwrap();
+        let result = resolve_fetch_tags(&loader, input).unwrap();
+
+        assert_eq!(
+            result,
+            serde_yaml::from_str::<Value>("a: from_cache").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_resolve_fetch_tags_invalid() {
+        let loader = ConfigLoader::new();
+
+        let input = serde_yaml::from_str::<Value>("a: !fetch [1, 2]").unwrap();
+        resolve_fetch_tags(&loader, input).unwrap_err();
+    }
 }
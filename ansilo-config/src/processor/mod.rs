@@ -4,12 +4,23 @@ use crate::ctx::Ctx;
 
 pub(crate) mod dir;
 pub(crate) mod embed;
+pub mod encrypted;
 pub(crate) mod env;
 pub(crate) mod fetch;
 pub(crate) mod util;
 pub(crate) mod arg;
 pub(crate) mod vault;
 
+/// The prefixes of the interpolation expressions of each processor, eg "dir" in ${dir}
+pub(crate) const PREFIXES: [&str; 6] = [
+    dir::PREFIX,
+    embed::PREFIX,
+    fetch::PREFIX,
+    env::PREFIX,
+    arg::PREFIX,
+    vault::PREFIX,
+];
+
 /// A config processor applies transformations to the yaml config
 /// This is used for interpolating config items from various sources
 pub(crate) trait ConfigExprProcessor {
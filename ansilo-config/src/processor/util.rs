@@ -2,7 +2,7 @@ use core::borrow::Borrow;
 use std::mem;
 
 use ansilo_core::err::{bail, Context, Result};
-use serde_yaml::{Mapping, Value};
+use serde_yaml::{value::TaggedValue, Mapping, Value};
 
 use super::{ConfigExprResult, ConfigStringExpr as X};
 
@@ -28,6 +28,10 @@ pub(crate) fn process_strings(
                 })
                 .collect::<Result<Mapping>>()?,
         ),
+        Value::Tagged(tagged) => Value::Tagged(Box::new(TaggedValue {
+            tag: tagged.tag,
+            value: process_strings(tagged.value, cb)?,
+        })),
         n @ _ => n,
     })
 }
@@ -13,6 +13,9 @@ use super::{
     util::match_interpolation, ConfigExprProcessor, ConfigExprResult, ConfigStringExpr as X,
 };
 
+/// The prefix of the expressions of this processor, eg ${vault:mnt:/secret/path:key}
+pub(crate) const PREFIX: &str = "vault";
+
 /// Interpolates configuration using secrets retrieved from HashiCorp Vault
 #[derive(Default)]
 pub struct VaultConfigProcessor {}
@@ -28,7 +31,7 @@ impl ConfigExprProcessor for VaultConfigProcessor {
     }
 
     fn process(&self, ctx: &mut Ctx, expr: X) -> Result<ConfigExprResult> {
-        Ok(match match_interpolation(&expr, &["vault"]) {
+        Ok(match match_interpolation(&expr, &[PREFIX]) {
             Some(p) => {
                 ensure!(
                     p.len() == 4,
@@ -62,9 +65,8 @@ impl ConfigExprProcessor for VaultConfigProcessor {
                 };
 
                 trace!(
-                    "Replaced configuration expression '{}' with '{}'",
-                    expression_to_string(&expr),
-                    output
+                    "Resolved configuration expression '{}'",
+                    expression_to_string(&expr)
                 );
 
                 ConfigExprResult::Expr(X::Constant(output))
@@ -91,7 +93,8 @@ impl VaultConfigProcessor {
                 .load_part(ctx, config.clone())
                 .context("Failed to load vault config")?;
 
-            trace!("Vault configuration: {:?}", config);
+            // The config contains the auth credentials so only the address is logged
+            trace!("Vault address: {}", config.address);
 
             let settings = VaultClientSettingsBuilder::default()
                 .address(config.address)
@@ -0,0 +1,2099 @@
+use std::collections::HashSet;
+
+use ansilo_core::{
+    auth::RowFilter,
+    config::{
+        AttributeMaskType, AuthConfig, AuthProviderConfig, BuildConfig, CatalogConfig,
+        ClassificationMaskConfig, ClusterConfig, ClusterStoreConfig, DataSourceConfig, DevConfig,
+        DriftConfig, EncryptionConfig, EntityConfig, EntityDriftAction, GrantConfig, HaConfig,
+        JobConfig, JobStepConfig, LoggingConfig, MaterializeMode, NetworkingConfig, PostgresConfig,
+        PublishConfig, QueryRuleConfig, ResourceConfig, ServiceUserConfig, SyncConfig,
+        SyncConflictStrategy, UserConfig, UserLimitConfig, WorkloadConfig,
+    },
+};
+use serde::de::DeserializeOwned;
+use serde_yaml::{Mapping, Value};
+
+/// The top-level sections of the node configuration
+const SECTIONS: [&str; 19] = [
+    "name",
+    "description",
+    "networking",
+    "resources",
+    "auth",
+    "build",
+    "sources",
+    "entities",
+    "jobs",
+    "syncs",
+    "catalogs",
+    "postgres",
+    "logging",
+    "encryption",
+    "ha",
+    "cluster",
+    "publish",
+    "drift",
+    "dev",
+];
+
+/// The types of data sources which support change data capture.
+/// Capturing changes from the MySQL binlog, MongoDB change streams or
+/// Oracle LogMiner is not implemented.
+const CDC_SOURCE_TYPES: [&str; 1] = ["native.postgres"];
+
+/// The TLS verification modes which are not supported by the driver of each data source type
+const UNSUPPORTED_TLS_MODES: [(&str, &[&str]); 4] = [
+    ("native.mongodb", &["fingerprint"]),
+    ("jdbc.mysql", &["fingerprint"]),
+    ("jdbc.oracle", &["fingerprint", "disabled"]),
+    ("jdbc.teradata", &["fingerprint"]),
+];
+
+/// The database names which cannot be used as the name of a catalog
+const RESERVED_CATALOG_NAMES: [&str; 3] = ["postgres", "template0", "template1"];
+
+/// The sections which must be defined
+const REQUIRED_SECTIONS: [&str; 4] = ["name", "networking", "auth", "build"];
+
+/// A single problem found while validating the configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigValidationIssue {
+    /// The path of the offending field, eg "sources[1].type"
+    pub path: String,
+    /// Description of the problem
+    pub message: String,
+    /// A suggested fix, if any
+    pub suggestion: Option<String>,
+}
+
+/// Validates the processed node configuration.
+///
+/// Unlike deserialising the config directly, this collects every issue
+/// found in the config rather than failing on the first.
+#[derive(Debug, Default)]
+pub struct ConfigValidator {
+    /// The known data source types, if empty the types are not validated
+    source_types: Vec<String>,
+}
+
+impl ConfigValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the known data source types used to validate each source
+    pub fn with_source_types(mut self, types: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.source_types = types.into_iter().map(|t| t.into()).collect();
+        self
+    }
+
+    /// Validates the supplied config, returning all issues found
+    pub fn validate(&self, config: &Value) -> Vec<ConfigValidationIssue> {
+        let mut issues = Issues::default();
+
+        let map = match config.as_mapping() {
+            Some(map) => map,
+            None => {
+                issues.push("", "Expected the configuration to be a mapping", None);
+                return issues.0;
+            }
+        };
+
+        self.validate_sections(map, &mut issues);
+
+        issues.check::<String>(map.get("name"), "name");
+        issues.check::<Option<String>>(map.get("description"), "description");
+        issues.check::<NetworkingConfig>(map.get("networking"), "networking");
+        issues.check::<ResourceConfig>(map.get("resources"), "resources");
+        issues.check::<BuildConfig>(map.get("build"), "build");
+        issues.check::<Option<PostgresConfig>>(map.get("postgres"), "postgres");
+        issues.check::<LoggingConfig>(map.get("logging"), "logging");
+        issues.check::<Option<EncryptionConfig>>(map.get("encryption"), "encryption");
+        let ha = issues
+            .check::<Option<HaConfig>>(map.get("ha"), "ha")
+            .flatten();
+        let cluster = issues
+            .check::<Option<ClusterConfig>>(map.get("cluster"), "cluster")
+            .flatten();
+        let publish = issues
+            .check::<Option<PublishConfig>>(map.get("publish"), "publish")
+            .flatten();
+        let drift = issues
+            .check::<Option<DriftConfig>>(map.get("drift"), "drift")
+            .flatten();
+        let dev = issues.check::<DevConfig>(map.get("dev"), "dev");
+
+        let auth = map.get("auth").and_then(|a| a.as_mapping());
+        let errors = issues.0.len();
+        let providers = issues.check_list::<AuthProviderConfig>(
+            auth.and_then(|a| a.get("providers")),
+            "auth.providers",
+        );
+        let users =
+            issues.check_list::<UserConfig>(auth.and_then(|a| a.get("users")), "auth.users");
+        let service_users = issues.check_list::<ServiceUserConfig>(
+            auth.and_then(|a| a.get("service_users")),
+            "auth.service_users",
+        );
+        let rules =
+            issues.check_list::<QueryRuleConfig>(auth.and_then(|a| a.get("rules")), "auth.rules");
+        let limits =
+            issues.check_list::<UserLimitConfig>(auth.and_then(|a| a.get("limits")), "auth.limits");
+        let masks = issues.check_list::<ClassificationMaskConfig>(
+            auth.and_then(|a| a.get("masks")),
+            "auth.masks",
+        );
+        let grants =
+            issues.check_list::<GrantConfig>(auth.and_then(|a| a.get("grants")), "auth.grants");
+        let workloads = issues
+            .check_list::<WorkloadConfig>(auth.and_then(|a| a.get("workloads")), "auth.workloads");
+        // Report any remaining errors in the auth section
+        if issues.0.len() == errors {
+            issues.check::<AuthConfig>(map.get("auth"), "auth");
+        }
+
+        let sources = issues.check_list::<DataSourceConfig>(map.get("sources"), "sources");
+        let entities = issues.check_list::<EntityConfig>(map.get("entities"), "entities");
+        let jobs = issues.check_list::<JobConfig>(map.get("jobs"), "jobs");
+        let syncs = issues.check_list::<SyncConfig>(map.get("syncs"), "syncs");
+        let catalogs = issues.check_list::<CatalogConfig>(map.get("catalogs"), "catalogs");
+
+        issues.unique(&providers, "auth.providers", "id", |p| p.id.as_str());
+        issues.unique(&users, "auth.users", "username", |u| u.username.as_str());
+        issues.unique(&service_users, "auth.service_users", "id", |u| u.id());
+        issues.unique(&sources, "sources", "id", |s| s.id.as_str());
+        issues.unique(&entities, "entities", "id", |e| e.id.as_str());
+        issues.unique(&jobs, "jobs", "id", |j| j.id.as_str());
+        issues.unique(&syncs, "syncs", "id", |s| s.id.as_str());
+        issues.unique(&catalogs, "catalogs", "name", |c| c.name.as_str());
+
+        // Validate references between sections
+        let provider_ids = providers
+            .iter()
+            .map(|(_, p)| p.id.as_str())
+            .chain(["password"])
+            .collect::<Vec<_>>();
+        for (idx, user) in users.iter() {
+            if let Some(provider) = user.provider.as_deref() {
+                issues.reference(
+                    format!("auth.users[{idx}].provider"),
+                    "auth provider",
+                    provider,
+                    &provider_ids,
+                );
+            }
+        }
+
+        if !self.source_types.is_empty() {
+            let types = self
+                .source_types
+                .iter()
+                .map(|t| t.as_str())
+                .collect::<Vec<_>>();
+            for (idx, source) in sources.iter() {
+                issues.reference(
+                    format!("sources[{idx}].type"),
+                    "data source type",
+                    &source.r#type,
+                    &types,
+                );
+            }
+        }
+
+        for (idx, source) in sources.iter() {
+            if let Some(fetch_size) = source.fetch_size.as_ref() {
+                if let (Some(min), Some(max)) = (fetch_size.min_rows, fetch_size.max_rows) {
+                    if min > max {
+                        issues.push(
+                            format!("sources[{idx}].fetch_size"),
+                            format!("The min_rows ({min}) must not exceed the max_rows ({max})"),
+                            None,
+                        );
+                    }
+                }
+            }
+
+            let tls_mode = source
+                .options
+                .get("tls")
+                .and_then(|t| t.get("verify"))
+                .and_then(|v| v.as_str());
+            let unsupported = UNSUPPORTED_TLS_MODES
+                .iter()
+                .find(|(t, _)| *t == source.r#type)
+                .map(|(_, modes)| *modes)
+                .unwrap_or_default();
+            if let Some(mode) = tls_mode.filter(|m| unsupported.contains(m)) {
+                issues.push(
+                    format!("sources[{idx}].options.tls.verify"),
+                    format!(
+                        "The '{mode}' verification mode is not supported by data sources of type '{}'",
+                        source.r#type
+                    ),
+                    Some("Use the 'system' or 'ca_bundle' mode".into()),
+                );
+            }
+
+            let admission = source.admission.as_ref();
+            if admission.and_then(|a| a.max_concurrent_scans) == Some(0) {
+                issues.push(
+                    format!("sources[{idx}].admission.max_concurrent_scans"),
+                    "At least one concurrent scan must be allowed",
+                    None,
+                );
+            }
+        }
+
+        // The internal data source is always available
+        let source_ids = sources
+            .iter()
+            .map(|(_, s)| s.id.as_str())
+            .chain(["internal"])
+            .collect::<Vec<_>>();
+        for (idx, entity) in entities.iter() {
+            issues.reference(
+                format!("entities[{idx}].source.data_source"),
+                "data source",
+                &entity.source.data_source,
+                &source_ids,
+            );
+
+            let attrs = entity
+                .attributes
+                .iter()
+                .map(|a| a.id.as_str())
+                .collect::<Vec<_>>();
+
+            for (fidx, filter) in entity.row_filters.iter().enumerate() {
+                match RowFilter::parse(filter) {
+                    Ok(filter) => issues.reference(
+                        format!("entities[{idx}].row_filters[{fidx}]"),
+                        "attribute",
+                        &filter.attribute,
+                        &attrs,
+                    ),
+                    Err(err) => issues.push(
+                        format!("entities[{idx}].row_filters[{fidx}]"),
+                        err.to_string(),
+                        None,
+                    ),
+                }
+            }
+
+            for (aidx, attr) in entity.attributes.iter().enumerate() {
+                let mask = match attr.mask.as_ref() {
+                    Some(mask) => mask,
+                    None => continue,
+                };
+
+                if mask.r#type != AttributeMaskType::Null && !attr.r#type.is_utf8_string() {
+                    issues.push(
+                        format!("entities[{idx}].attributes[{aidx}].mask.type"),
+                        "Only string attributes can be hashed or partially masked",
+                        Some("Use the 'null' mask type".into()),
+                    );
+                }
+            }
+
+            // Materialized entities are served from a local snapshot
+            if !entity.row_filters.is_empty() && entity.materialize.is_some() {
+                issues.push(
+                    format!("entities[{idx}].row_filters"),
+                    "Row filters cannot be applied to a materialized entity",
+                    None,
+                );
+            }
+
+            // The snapshot is refreshed with the unmasked values
+            if entity.materialize.is_some() {
+                for (aidx, attr) in entity.attributes.iter().enumerate() {
+                    let classified = attr
+                        .classifications
+                        .iter()
+                        .chain(entity.classifications.iter())
+                        .any(|c| masks.iter().any(|(_, m)| &m.classification == c));
+
+                    if attr.mask.is_some() || classified {
+                        issues.push(
+                            format!("entities[{idx}].attributes[{aidx}]"),
+                            "Masks cannot be applied to a materialized entity",
+                            Some("Remove either the mask or the materialize option".into()),
+                        );
+                    }
+                }
+            }
+
+            if let Some(materialize) = entity.materialize.as_ref() {
+                match materialize.watermark.as_deref() {
+                    Some(watermark) => issues.reference(
+                        format!("entities[{idx}].materialize.watermark"),
+                        "attribute",
+                        watermark,
+                        &attrs,
+                    ),
+                    None if materialize.mode == MaterializeMode::Incremental => issues.push(
+                        format!("entities[{idx}].materialize.watermark"),
+                        "A watermark is required to materialize an entity incrementally",
+                        Some("Set the watermark to an increasing attribute".into()),
+                    ),
+                    None => {}
+                }
+
+                match materialize.mode {
+                    MaterializeMode::Cdc => {
+                        if !entity.attributes.iter().any(|a| a.primary_key) {
+                            issues.push(
+                                format!("entities[{idx}].materialize.mode"),
+                                "A primary key is required to capture the changes to an entity",
+                                Some("Mark the key attributes with 'primary_key: true'".into()),
+                            );
+                        }
+
+                        let unsupported = sources
+                            .iter()
+                            .find(|(_, s)| s.id == entity.source.data_source)
+                            .map(|(_, s)| s.r#type.as_str())
+                            .filter(|t| !CDC_SOURCE_TYPES.contains(t));
+
+                        if let Some(source_type) = unsupported {
+                            issues.push(
+                                format!("entities[{idx}].materialize.mode"),
+                                format!("Data sources of type '{source_type}' do not support change data capture, it is only supported for {}", CDC_SOURCE_TYPES.join(", ")),
+                                Some("Use the 'incremental' mode with a watermark, or the 'full' mode".into()),
+                            );
+                        }
+                    }
+                    _ if materialize.refresh.trim().is_empty() => issues.push(
+                        format!("entities[{idx}].materialize.refresh"),
+                        "A refresh schedule is required to materialize an entity",
+                        Some("Set the refresh to a cron expression".into()),
+                    ),
+                    _ => {}
+                }
+            }
+
+            if let Some(cache) = entity.cache.as_ref() {
+                // The snapshot would otherwise be refreshed from the cached scans
+                if entity.materialize.is_some() {
+                    issues.push(
+                        format!("entities[{idx}].cache"),
+                        "A materialized entity cannot be cached",
+                        Some("Remove either the cache or materialize option".into()),
+                    );
+                }
+
+                if cache.ttl == 0 {
+                    issues.push(
+                        format!("entities[{idx}].cache.ttl"),
+                        "The cache ttl must be at least 1 second",
+                        None,
+                    );
+                }
+            }
+
+            if entity.on_drift == EntityDriftAction::Regenerate {
+                // The snapshot of the entity would not match the regenerated attributes
+                if entity.materialize.is_some() {
+                    issues.push(
+                        format!("entities[{idx}].on_drift"),
+                        "A materialized entity cannot be regenerated on drift",
+                        Some("Use the 'report' action".into()),
+                    );
+                } else if drift.is_none() {
+                    issues.push(
+                        format!("entities[{idx}].on_drift"),
+                        "Drift detection is not enabled",
+                        Some("Add the 'drift' section to enable drift detection".into()),
+                    );
+                }
+            }
+        }
+
+        for (idx, limit) in limits.iter() {
+            if limit.max_concurrent_statements == Some(0) {
+                issues.push(
+                    format!("auth.limits[{idx}].max_concurrent_statements"),
+                    "At least one concurrent statement must be allowed",
+                    None,
+                );
+            }
+        }
+
+        for (idx, workload) in workloads.iter() {
+            if workload.max_concurrent_statements == Some(0) {
+                issues.push(
+                    format!("auth.workloads[{idx}].max_concurrent_statements"),
+                    "At least one concurrent statement must be allowed",
+                    None,
+                );
+            }
+            if workload.max_concurrent_scans == Some(0) {
+                issues.push(
+                    format!("auth.workloads[{idx}].max_concurrent_scans"),
+                    "At least one concurrent scan must be allowed",
+                    None,
+                );
+            }
+            if let Some(fetch_size) = workload.fetch_size.as_ref() {
+                if let (Some(min), Some(max)) = (fetch_size.min_rows, fetch_size.max_rows) {
+                    if min > max {
+                        issues.push(
+                            format!("auth.workloads[{idx}].fetch_size"),
+                            format!("The min_rows ({min}) must not exceed the max_rows ({max})"),
+                            None,
+                        );
+                    }
+                }
+            }
+        }
+
+        let entity_ids = entities
+            .iter()
+            .map(|(_, e)| e.id.as_str())
+            .collect::<Vec<_>>();
+        for (idx, rule) in rules.iter() {
+            for (eidx, entity) in rule.require_where.iter().enumerate() {
+                issues.reference(
+                    format!("auth.rules[{idx}].require_where[{eidx}]"),
+                    "entity",
+                    entity,
+                    &entity_ids,
+                );
+            }
+        }
+
+        for (idx, grant) in grants.iter() {
+            for (sidx, source) in grant.sources.iter().enumerate() {
+                issues.reference(
+                    format!("auth.grants[{idx}].sources[{sidx}]"),
+                    "data source",
+                    source,
+                    &source_ids,
+                );
+            }
+            for (eidx, entity) in grant.entities.iter().enumerate() {
+                issues.reference(
+                    format!("auth.grants[{idx}].entities[{eidx}]"),
+                    "entity",
+                    entity,
+                    &entity_ids,
+                );
+            }
+        }
+
+        let classifications = entities
+            .iter()
+            .flat_map(|(_, e)| {
+                e.classifications
+                    .iter()
+                    .chain(e.attributes.iter().flat_map(|a| a.classifications.iter()))
+            })
+            .map(|c| c.as_str())
+            .collect::<Vec<_>>();
+        for (idx, mask) in masks.iter() {
+            issues.reference(
+                format!("auth.masks[{idx}].classification"),
+                "classification",
+                &mask.classification,
+                &classifications,
+            );
+        }
+
+        for (idx, catalog) in catalogs.iter() {
+            if RESERVED_CATALOG_NAMES.contains(&catalog.name.as_str()) {
+                issues.push(
+                    format!("catalogs[{idx}].name"),
+                    format!("The name '{}' is reserved", catalog.name),
+                    Some("Choose a different name for the catalog".into()),
+                );
+            }
+
+            for (sidx, source) in catalog.sources.iter().enumerate() {
+                issues.reference(
+                    format!("catalogs[{idx}].sources[{sidx}]"),
+                    "data source",
+                    source,
+                    &source_ids,
+                );
+            }
+        }
+
+        let catalog_names = catalogs
+            .iter()
+            .map(|(_, c)| c.name.as_str())
+            .collect::<Vec<_>>();
+        let service_user_ids = service_users
+            .iter()
+            .map(|(_, u)| u.id())
+            .collect::<Vec<_>>();
+        let peer_source_ids = sources
+            .iter()
+            .filter(|(_, s)| s.r#type == "peer")
+            .map(|(_, s)| s.id.as_str())
+            .collect::<Vec<_>>();
+        for (idx, job) in jobs.iter() {
+            if let Some(user) = job.service_user.as_deref() {
+                issues.reference(
+                    format!("jobs[{idx}].service_user"),
+                    "service user",
+                    user,
+                    &service_user_ids,
+                );
+            }
+
+            if let Some(catalog) = job.catalog.as_deref() {
+                issues.reference(
+                    format!("jobs[{idx}].catalog"),
+                    "catalog",
+                    catalog,
+                    &catalog_names,
+                );
+            }
+
+            let has_sql = !job.sql.trim().is_empty() || !job.steps.is_empty();
+            match job.peer_import.as_ref() {
+                Some(_) if has_sql => issues.push(
+                    format!("jobs[{idx}]"),
+                    "A job must define either sql or peer_import, not both",
+                    None,
+                ),
+                Some(import) => issues.reference(
+                    format!("jobs[{idx}].peer_import.data_source"),
+                    "peer data source",
+                    &import.data_source,
+                    &peer_source_ids,
+                ),
+                None if !has_sql => issues.push(
+                    format!("jobs[{idx}].sql"),
+                    "A job must define the sql to execute",
+                    Some("Set 'sql', 'steps' or use a built-in job such as 'peer_import'".into()),
+                ),
+                None => {}
+            }
+
+            for (step_idx, step) in job.steps.iter().enumerate() {
+                if let JobStepConfig::Script(step) = step {
+                    if step.script.command.trim().is_empty() {
+                        issues.push(
+                            format!("jobs[{idx}].steps[{step_idx}].script.command"),
+                            "A script must define the command to execute",
+                            None,
+                        );
+                    }
+                }
+            }
+        }
+
+        for (idx, sync) in syncs.iter() {
+            let path = format!("syncs[{idx}]");
+            issues.reference(
+                format!("{path}.source"),
+                "entity",
+                &sync.source,
+                &entity_ids,
+            );
+            issues.reference(
+                format!("{path}.target"),
+                "entity",
+                &sync.target,
+                &entity_ids,
+            );
+
+            if sync.schedule.trim().is_empty() {
+                issues.push(
+                    format!("{path}.schedule"),
+                    "A schedule is required to run a sync",
+                    Some("Set the schedule to a cron expression".into()),
+                );
+            }
+
+            if sync.watermark.is_some() && sync.conflict == SyncConflictStrategy::Replace {
+                issues.push(
+                    format!("{path}.watermark"),
+                    "A watermark cannot be used with the 'replace' conflict strategy",
+                    Some("Use the 'upsert' conflict strategy".into()),
+                );
+            }
+
+            let attrs = |id: &str| {
+                entities
+                    .iter()
+                    .find(|(_, e)| e.id == id)
+                    .map(|(_, e)| e.attributes.iter().collect::<Vec<_>>())
+            };
+            let (source, target) = match (attrs(&sync.source), attrs(&sync.target)) {
+                (Some(source), Some(target)) => (source, target),
+                _ => continue,
+            };
+            let source_attrs = source.iter().map(|a| a.id.as_str()).collect::<Vec<_>>();
+            let target_attrs = target.iter().map(|a| a.id.as_str()).collect::<Vec<_>>();
+
+            for (cidx, col) in sync.columns.iter().enumerate() {
+                issues.reference(
+                    format!("{path}.columns[{cidx}].source"),
+                    "attribute",
+                    &col.source,
+                    &source_attrs,
+                );
+                issues.reference(
+                    format!("{path}.columns[{cidx}].target"),
+                    "attribute",
+                    &col.target,
+                    &target_attrs,
+                );
+            }
+
+            if let Some(watermark) = sync.watermark.as_deref() {
+                issues.reference(
+                    format!("{path}.watermark"),
+                    "attribute",
+                    watermark,
+                    &source_attrs,
+                );
+            }
+
+            for (kidx, key) in sync.key.iter().enumerate() {
+                issues.reference(
+                    format!("{path}.key[{kidx}]"),
+                    "attribute",
+                    key,
+                    &target_attrs,
+                );
+            }
+
+            if sync.conflict.requires_key()
+                && sync.key.is_empty()
+                && !target.iter().any(|a| a.primary_key)
+            {
+                issues.push(
+                    format!("{path}.key"),
+                    "A key is required to find the existing rows of the target",
+                    Some("Set the key or mark the key attributes of the target with 'primary_key: true'".into()),
+                );
+            }
+        }
+
+        let memory_source_ids = sources
+            .iter()
+            .filter(|(_, s)| s.r#type == "test.memory")
+            .map(|(_, s)| s.id.as_str())
+            .collect::<Vec<_>>();
+        for (idx, fixture) in dev.iter().flat_map(|d| d.fixtures.iter().enumerate()) {
+            let path = format!("dev.fixtures[{idx}]");
+
+            match (fixture.sql.as_ref(), fixture.csv.as_ref()) {
+                (Some(_), Some(_)) => {
+                    issues.push(path, "A fixture must define either sql or csv, not both", None)
+                }
+                (None, None) => issues.push(
+                    path,
+                    "A fixture must define the sql or csv file to load",
+                    None,
+                ),
+                (Some(_), None) => {}
+                (None, Some(_)) => match (fixture.table.as_ref(), fixture.data_source.as_deref()) {
+                    (Some(_), Some(_)) => issues.push(
+                        path,
+                        "A csv fixture must be loaded into either a table or a data source, not both",
+                        None,
+                    ),
+                    (None, None) => issues.push(
+                        path,
+                        "A csv fixture must define the table or data source to load into",
+                        Some("Set 'table' to load into postgres or 'data_source' and 'entity' to load into a memory data source".into()),
+                    ),
+                    (Some(_), None) => {}
+                    (None, Some(source)) => {
+                        issues.reference(
+                            format!("{path}.data_source"),
+                            "memory data source",
+                            source,
+                            &memory_source_ids,
+                        );
+
+                        match fixture.entity.as_deref() {
+                            Some(entity) => issues.reference(
+                                format!("{path}.entity"),
+                                "entity",
+                                entity,
+                                &entity_ids,
+                            ),
+                            None => issues.push(
+                                format!("{path}.entity"),
+                                "The entity to load the csv file into must be defined",
+                                None,
+                            ),
+                        }
+                    }
+                },
+            }
+        }
+
+        if let Some(ha) = ha.as_ref() {
+            if ha.nodes.len() != 2 {
+                issues.push(
+                    "ha.nodes",
+                    format!(
+                        "A high-availability pair must have exactly 2 nodes, found {}",
+                        ha.nodes.len()
+                    ),
+                    None,
+                );
+            }
+
+            let nodes = ha.nodes.iter().enumerate().collect::<Vec<_>>();
+            issues.unique(&nodes, "ha.nodes", "id", |n| n.id.as_str());
+
+            let node_ids = ha.nodes.iter().map(|n| n.id.as_str()).collect::<Vec<_>>();
+            issues.reference("ha.node_id".into(), "ha node", &ha.node_id, &node_ids);
+        }
+
+        if let Some(ClusterStoreConfig::Etcd(etcd)) = cluster.as_ref().map(|c| &c.store) {
+            if etcd.endpoints.is_empty() {
+                issues.push(
+                    "cluster.store.endpoints",
+                    "At least one etcd endpoint must be configured",
+                    None,
+                );
+            }
+        }
+
+        if let Some(publish) = publish.as_ref() {
+            if publish.kafka.brokers.is_empty() {
+                issues.push(
+                    "publish.kafka.brokers",
+                    "At least one kafka broker must be configured",
+                    None,
+                );
+            }
+
+            let tables = publish.tables.iter().enumerate().collect::<Vec<_>>();
+            issues.unique(&tables, "publish.tables", "table", |t| t.table.as_str());
+
+            for (idx, table) in tables {
+                if table.topic.as_deref() == Some("") {
+                    issues.push(
+                        format!("publish.tables[{idx}].topic"),
+                        "The topic cannot be empty",
+                        None,
+                    );
+                }
+            }
+        }
+
+        if drift.and_then(|d| d.interval_secs) == Some(0) {
+            issues.push(
+                "drift.interval_secs",
+                "The interval must be at least one second",
+                None,
+            );
+        }
+
+        issues.0
+    }
+
+    fn validate_sections(&self, map: &Mapping, issues: &mut Issues) {
+        for key in map.keys() {
+            let key = match key.as_str() {
+                Some(k) => k,
+                None => {
+                    issues.push("", "Expected configuration keys to be strings", None);
+                    continue;
+                }
+            };
+
+            // We only flag unknown sections which look like a typo
+            // so custom keys (eg for yaml anchors) are still allowed
+            if !SECTIONS.contains(&key) {
+                if let Some(nearest) = nearest_match(key, &SECTIONS) {
+                    issues.push(
+                        key,
+                        format!("Unknown configuration section '{key}'"),
+                        Some(format!("Did you mean '{nearest}'?")),
+                    );
+                }
+            }
+        }
+
+        for section in REQUIRED_SECTIONS {
+            if !map.contains_key(section) {
+                issues.push(
+                    "",
+                    format!("Missing required configuration section '{section}'"),
+                    None,
+                );
+            }
+        }
+    }
+}
+
+/// Collects the issues found during validation
+#[derive(Default)]
+struct Issues(Vec<ConfigValidationIssue>);
+
+impl Issues {
+    fn push(
+        &mut self,
+        path: impl Into<String>,
+        message: impl Into<String>,
+        suggestion: Option<String>,
+    ) {
+        self.0.push(ConfigValidationIssue {
+            path: path.into(),
+            message: message.into(),
+            suggestion,
+        });
+    }
+
+    /// Checks the value deserialises into the supplied type
+    fn check<T: DeserializeOwned>(&mut self, value: Option<&Value>, path: &str) -> Option<T> {
+        let value = value?;
+
+        match serde_yaml::from_value::<T>(value.clone()) {
+            Ok(v) => Some(v),
+            Err(err) => {
+                self.push(path, err.to_string(), None);
+                None
+            }
+        }
+    }
+
+    /// Checks each item in the list deserialises into the supplied type,
+    /// returning the valid items along with their index
+    fn check_list<T: DeserializeOwned>(
+        &mut self,
+        value: Option<&Value>,
+        path: &str,
+    ) -> Vec<(usize, T)> {
+        let seq = match value {
+            None | Some(Value::Null) => return vec![],
+            Some(Value::Sequence(seq)) => seq,
+            Some(_) => {
+                self.push(path, "Expected a list", None);
+                return vec![];
+            }
+        };
+
+        seq.iter()
+            .enumerate()
+            .filter_map(|(idx, item)| {
+                self.check::<T>(Some(item), &format!("{path}[{idx}]"))
+                    .map(|i| (idx, i))
+            })
+            .collect()
+    }
+
+    /// Checks the key of each item is unique
+    fn unique<'a, T>(
+        &mut self,
+        items: &'a [(usize, T)],
+        path: &str,
+        field: &str,
+        key: impl Fn(&'a T) -> &'a str,
+    ) {
+        let mut seen = HashSet::new();
+
+        for (idx, item) in items.iter() {
+            let key = key(item);
+
+            if !seen.insert(key) {
+                self.push(
+                    format!("{path}[{idx}].{field}"),
+                    format!("Duplicate {field} '{key}'"),
+                    None,
+                );
+            }
+        }
+    }
+
+    /// Checks the supplied value references one of the valid options
+    fn reference(&mut self, path: String, kind: &str, value: &str, options: &[&str]) {
+        if options.contains(&value) {
+            return;
+        }
+
+        let mut seen = HashSet::new();
+        let options = options
+            .iter()
+            .filter(|o| seen.insert(**o))
+            .copied()
+            .collect::<Vec<_>>();
+
+        self.push(
+            path,
+            format!("Unknown {kind} '{value}'"),
+            match nearest_match(value, &options) {
+                Some(nearest) => Some(format!("Did you mean '{nearest}'?")),
+                None if !options.is_empty() => Some(format!(
+                    "Expected one of: {}",
+                    options
+                        .iter()
+                        .map(|o| format!("'{o}'"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+                None => None,
+            },
+        );
+    }
+}
+
+/// Finds the closest option to the supplied value, if it is similar enough
+fn nearest_match<'a>(value: &str, options: &[&'a str]) -> Option<&'a str> {
+    let max_distance = (value.len() / 3).max(2);
+
+    options
+        .iter()
+        .map(|o| (*o, edit_distance(&value.to_lowercase(), &o.to_lowercase())))
+        .filter(|(_, d)| *d <= max_distance)
+        .min_by_key(|(_, d)| *d)
+        .map(|(o, _)| o)
+}
+
+/// Computes the levenshtein distance between the two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut cur = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j + 1] + 1).min(cur[j] + 1).min(prev[j] + cost);
+        }
+
+        std::mem::swap(&mut prev, &mut cur);
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(yaml: &str) -> Vec<ConfigValidationIssue> {
+        ConfigValidator::new()
+            .with_source_types(["jdbc.mysql", "native.postgres", "peer", "test.memory"])
+            .validate(&serde_yaml::from_str(yaml).unwrap())
+    }
+
+    const MINIMAL: &str = r#"
+name: test
+networking:
+  port: 1234
+auth:
+  users: []
+build:
+  stages: []
+"#;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", "abc"), 0);
+        assert_eq!(edit_distance("abc", "abd"), 1);
+        assert_eq!(edit_distance("sources", "soruces"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_nearest_match() {
+        assert_eq!(nearest_match("soruces", &SECTIONS), Some("sources"));
+        assert_eq!(
+            nearest_match("jdbc.mysl", &["jdbc.mysql", "jdbc.oracle"]),
+            Some("jdbc.mysql")
+        );
+        assert_eq!(nearest_match("completely-different", &SECTIONS), None);
+    }
+
+    #[test]
+    fn test_validate_minimal() {
+        assert_eq!(validate(MINIMAL), vec![]);
+    }
+
+    #[test]
+    fn test_validate_auth() {
+        let issues = validate("name: test\nnetworking:\n  port: 1\nauth: {}\nbuild:\n  stages: []");
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].path, "auth");
+        assert!(issues[0].message.contains("missing field `users`"));
+    }
+
+    #[test]
+    fn test_validate_not_a_mapping() {
+        assert_eq!(validate("[]").len(), 1);
+    }
+
+    #[test]
+    fn test_validate_sections() {
+        let issues = validate("soruces: []\nx-custom: {}");
+
+        assert_eq!(
+            issues[0],
+            ConfigValidationIssue {
+                path: "soruces".into(),
+                message: "Unknown configuration section 'soruces'".into(),
+                suggestion: Some("Did you mean 'sources'?".into())
+            }
+        );
+        assert_eq!(
+            issues[1..]
+                .iter()
+                .map(|i| i.message.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Missing required configuration section 'name'",
+                "Missing required configuration section 'networking'",
+                "Missing required configuration section 'auth'",
+                "Missing required configuration section 'build'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_collects_multiple_errors() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+sources:
+  - id: a
+    type: jdbc.mysl
+    options: {{}}
+  - id: a
+    type: native.postgres
+    options: {{}}
+  - type: native.postgres
+entities:
+  - id: people
+    attributes: []
+    source:
+      data_source: unknown_source
+      options: {{}}
+"#
+        ));
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| (i.path.as_str(), i.suggestion.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("sources[2]", None),
+                ("sources[1].id", None),
+                ("sources[0].type", Some("Did you mean 'jdbc.mysql'?")),
+                (
+                    "entities[0].source.data_source",
+                    Some("Expected one of: 'a', 'internal'")
+                ),
+            ]
+        );
+        assert!(issues[0].message.contains("missing field `id`"));
+    }
+
+    #[test]
+    fn test_validate_references() {
+        let issues = validate(
+            r#"
+name: test
+networking:
+  port: 1234
+auth:
+  users:
+    - username: mary
+      provider: jwtt
+      password: foo
+  service_users:
+    - username: svc
+      password: foo
+build:
+  stages: []
+jobs:
+  - id: job
+    service_user: svcc
+    sql: SELECT 1
+"#,
+        );
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| (i.path.as_str(), i.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("auth.users[0].provider", "Unknown auth provider 'jwtt'"),
+                ("jobs[0].service_user", "Unknown service user 'svcc'"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_materialize() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+sources:
+  - id: mysql
+    type: jdbc.mysql
+    options: {{}}
+entities:
+  - id: orders
+    attributes:
+      - id: updated_at
+        type: DateTime
+    source:
+      data_source: mysql
+      options: {{}}
+    materialize:
+      refresh: 0 * * * * *
+      mode: incremental
+  - id: customers
+    attributes:
+      - id: updated_at
+        type: DateTime
+    source:
+      data_source: mysql
+      options: {{}}
+    materialize:
+      refresh: 0 * * * * *
+      mode: incremental
+      watermark: updatd_at
+"#
+        ));
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| (i.path.as_str(), i.suggestion.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "entities[0].materialize.watermark",
+                    Some("Set the watermark to an increasing attribute")
+                ),
+                (
+                    "entities[1].materialize.watermark",
+                    Some("Did you mean 'updated_at'?")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_materialize_masked() {
+        let issues = validate(&format!(
+            r#"
+name: test
+networking:
+  port: 1234
+auth:
+  users: []
+  masks:
+    - classification: pii
+      type: hash
+build:
+  stages: []
+sources:
+  - id: mysql
+    type: jdbc.mysql
+    options: {{}}
+entities:
+  - id: orders
+    attributes:
+      - id: id
+        type: Int32
+      - id: email
+        type: !Utf8String {{}}
+        mask:
+          type: hash
+      - id: phone
+        type: !Utf8String {{}}
+        classifications: [pii]
+    source:
+      data_source: mysql
+      options: {{}}
+    materialize:
+      refresh: 0 * * * * *
+"#
+        ));
+
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec!["entities[0].attributes[1]", "entities[0].attributes[2]"]
+        );
+    }
+
+    #[test]
+    fn test_validate_entity_cache() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+sources:
+  - id: mysql
+    type: jdbc.mysql
+    options: {{}}
+entities:
+  - id: orders
+    attributes:
+      - id: id
+        type: Int32
+    source:
+      data_source: mysql
+      options: {{}}
+    cache:
+      ttl: 60
+  - id: customers
+    attributes:
+      - id: id
+        type: Int32
+    source:
+      data_source: mysql
+      options: {{}}
+    cache:
+      ttl: 0
+  - id: products
+    attributes:
+      - id: id
+        type: Int32
+    source:
+      data_source: mysql
+      options: {{}}
+    materialize:
+      refresh: 0 * * * * *
+    cache:
+      ttl: 60
+"#
+        ));
+
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec!["entities[1].cache.ttl", "entities[2].cache"]
+        );
+    }
+
+    #[test]
+    fn test_validate_entity_on_drift() {
+        let entities = r#"
+sources:
+  - id: mysql
+    type: jdbc.mysql
+    options: {}
+entities:
+  - id: orders
+    attributes:
+      - id: id
+        type: Int32
+    source:
+      data_source: mysql
+      options: {}
+    on_drift: regenerate
+  - id: products
+    attributes:
+      - id: id
+        type: Int32
+    source:
+      data_source: mysql
+      options: {}
+    materialize:
+      refresh: 0 * * * * *
+    on_drift: regenerate
+"#;
+
+        let issues = validate(&format!("{MINIMAL}{entities}"));
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec!["entities[0].on_drift", "entities[1].on_drift"]
+        );
+
+        let issues = validate(&format!("{MINIMAL}{entities}drift: {{}}\n"));
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec!["entities[1].on_drift"]
+        );
+    }
+
+    #[test]
+    fn test_validate_materialize_cdc() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+sources:
+  - id: mysql
+    type: jdbc.mysql
+    options: {{}}
+  - id: postgres
+    type: native.postgres
+    options: {{}}
+entities:
+  - id: orders
+    attributes:
+      - id: id
+        type: Int32
+        primary_key: true
+    source:
+      data_source: postgres
+      options: {{}}
+    materialize:
+      mode: cdc
+  - id: customers
+    attributes:
+      - id: id
+        type: Int32
+    source:
+      data_source: mysql
+      options: {{}}
+    materialize:
+      mode: cdc
+  - id: invoices
+    attributes:
+      - id: id
+        type: Int32
+    source:
+      data_source: mysql
+      options: {{}}
+    materialize:
+      mode: full
+"#
+        ));
+
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec![
+                "entities[1].materialize.mode",
+                "entities[1].materialize.mode",
+                "entities[2].materialize.refresh",
+            ]
+        );
+        assert_eq!(
+            issues[1].message,
+            "Data sources of type 'jdbc.mysql' do not support change data capture, it is only supported for native.postgres"
+        );
+    }
+
+    #[test]
+    fn test_validate_unsupported_tls_modes() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+sources:
+  - id: mysql
+    type: jdbc.mysql
+    options:
+      tls:
+        verify: fingerprint
+        fingerprint: AB:CD
+  - id: mongo
+    type: native.mongodb
+    options:
+      tls:
+        verify: ca_bundle
+        ca_bundle: /etc/ca.pem
+  - id: oracle
+    type: jdbc.oracle
+    options:
+      tls:
+        verify: disabled
+  - id: postgres
+    type: native.postgres
+    options:
+      tls:
+        verify: fingerprint
+        fingerprint: AB:CD
+"#
+        ));
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| (i.path.as_str(), i.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "sources[0].options.tls.verify",
+                    "The 'fingerprint' verification mode is not supported by data sources of type 'jdbc.mysql'"
+                ),
+                (
+                    "sources[2].options.tls.verify",
+                    "The 'disabled' verification mode is not supported by data sources of type 'jdbc.oracle'"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_row_filters() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+sources:
+  - id: mysql
+    type: jdbc.mysql
+    options: {{}}
+entities:
+  - id: orders
+    attributes:
+      - id: region
+        type: !Utf8String {{}}
+    source:
+      data_source: mysql
+      options: {{}}
+    row_filters:
+      - region = ${{auth.claims.region}}
+      - regoin = ${{auth.claims.region}}
+      - region ~ eu
+"#
+        ));
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| (i.path.as_str(), i.suggestion.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("entities[0].row_filters[1]", Some("Did you mean 'region'?")),
+                ("entities[0].row_filters[2]", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_attribute_masks() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+sources:
+  - id: mysql
+    type: jdbc.mysql
+    options: {{}}
+entities:
+  - id: customers
+    attributes:
+      - id: email
+        type: !Utf8String {{}}
+        mask:
+          type: hash
+      - id: phone
+        type: !Utf8String {{}}
+        mask:
+          type: partial
+          visible: 4
+          unmasked_for:
+            roles: [support]
+      - id: salary
+        type: Int32
+        mask:
+          type: hash
+      - id: age
+        type: Int32
+        mask:
+          type: "null"
+    source:
+      data_source: mysql
+      options: {{}}
+"#
+        ));
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| (i.path.as_str(), i.suggestion.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![(
+                "entities[0].attributes[2].mask.type",
+                Some("Use the 'null' mask type")
+            ),]
+        );
+    }
+
+    #[test]
+    fn test_validate_query_rules() {
+        let issues = validate(
+            r#"
+name: test
+networking:
+  port: 1234
+auth:
+  users: []
+  rules:
+    - roles: [analyst]
+      deny_writes: true
+      require_where: [orders, ordres]
+    - users: [mary]
+      deny_cross_source_joins: yes please
+build:
+  stages: []
+sources:
+  - id: mysql
+    type: jdbc.mysql
+    options: {}
+entities:
+  - id: orders
+    attributes: []
+    source:
+      data_source: mysql
+      options: {}
+"#,
+        );
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| (i.path.as_str(), i.suggestion.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("auth.rules[1]", None),
+                (
+                    "auth.rules[0].require_where[1]",
+                    Some("Did you mean 'orders'?")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_grants() {
+        let issues = validate(
+            r#"
+name: test
+networking:
+  port: 1234
+auth:
+  users: []
+  grants:
+    - roles: [analyst]
+      sources: [mysql, mysq]
+    - users: [mary]
+      entities: [ordres]
+      write: true
+    - users: [john]
+      remote_queries: sometimes
+build:
+  stages: []
+sources:
+  - id: mysql
+    type: jdbc.mysql
+    options: {}
+entities:
+  - id: orders
+    attributes: []
+    source:
+      data_source: mysql
+      options: {}
+"#,
+        );
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| (i.path.as_str(), i.suggestion.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("auth.grants[2]", None),
+                ("auth.grants[0].sources[1]", Some("Did you mean 'mysql'?")),
+                ("auth.grants[1].entities[0]", Some("Did you mean 'orders'?")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_ha() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+ha:
+  node_id: node-c
+  replication_password: pass
+  nodes:
+    - id: node-a
+      host: node-a.internal
+      url: https://node-a.internal:65432
+    - id: node-a
+      host: node-b.internal
+      url: https://node-b.internal:65432
+    - id: node-b
+      host: node-b.internal
+      url: https://node-b.internal:65432
+"#
+        ));
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| (i.path.as_str(), i.suggestion.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("ha.nodes", None),
+                ("ha.nodes[1].id", None),
+                ("ha.node_id", Some("Did you mean 'node-a'?")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_cluster() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+cluster:
+  node_id: node-a
+  url: https://node-a.internal:65432
+  store:
+    type: etcd
+    endpoints: []
+"#
+        ));
+
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec!["cluster.store.endpoints"]
+        );
+
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+cluster:
+  node_id: node-a
+  store:
+    type: zookeeper
+"#
+        ));
+
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec!["cluster"]
+        );
+    }
+
+    #[test]
+    fn test_validate_publish() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+publish:
+  kafka:
+    brokers: []
+  tables:
+    - table: orders
+    - table: orders
+      topic: ""
+"#
+        ));
+
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec![
+                "publish.kafka.brokers",
+                "publish.tables[1].table",
+                "publish.tables[1].topic"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_drift() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+drift:
+  interval_secs: 0
+"#
+        ));
+
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec!["drift.interval_secs"]
+        );
+    }
+
+    #[test]
+    fn test_validate_syncs() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+sources:
+  - id: crm
+    type: native.postgres
+    options: {{}}
+entities:
+  - id: customers
+    attributes:
+      - id: id
+        type: Int32
+      - id: updated_at
+        type: DateTime
+    source:
+      data_source: crm
+  - id: contacts
+    attributes:
+      - id: customer_id
+        type: Int32
+    source:
+      data_source: crm
+syncs:
+  - id: contacts
+    source: customers
+    target: contacts
+    schedule: "0 0 * * * *"
+    columns:
+      - source: id
+        target: customer_id
+      - source: name
+        target: customer_id
+    watermark: updated_at
+    conflict: upsert
+  - id: missing
+    source: customer
+    target: contacts
+    schedule: ""
+    watermark: updated_at
+    conflict: replace
+"#
+        ));
+
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec![
+                "syncs[0].columns[1].source",
+                "syncs[0].key",
+                "syncs[1].source",
+                "syncs[1].schedule",
+                "syncs[1].watermark",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_peer_import_jobs() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+sources:
+  - id: sales
+    type: peer
+    options: {{}}
+  - id: mysql
+    type: jdbc.mysql
+    options: {{}}
+jobs:
+  - id: import_sales
+    peer_import:
+      data_source: sales
+      schema: sales
+  - id: import_mysql
+    peer_import:
+      data_source: mysql
+      schema: mysql
+  - id: both
+    sql: SELECT 1
+    peer_import:
+      data_source: sales
+      schema: sales
+  - id: neither
+"#
+        ));
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| (i.path.as_str(), i.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "jobs[1].peer_import.data_source",
+                    "Unknown peer data source 'mysql'"
+                ),
+                (
+                    "jobs[2]",
+                    "A job must define either sql or peer_import, not both"
+                ),
+                ("jobs[3].sql", "A job must define the sql to execute"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_job_steps() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+jobs:
+  - id: steps_only
+    steps:
+      - sql: SELECT 1
+      - script:
+          command: /usr/bin/curl
+  - id: empty_command
+    sql: SELECT 1
+    steps:
+      - script:
+          command: ""
+"#
+        ));
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| (i.path.as_str(), i.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(
+                "jobs[1].steps[0].script.command",
+                "A script must define the command to execute"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_validate_dev_fixtures() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+sources:
+  - id: memory
+    type: test.memory
+    options: {{}}
+  - id: mysql
+    type: jdbc.mysql
+    options: {{}}
+entities:
+  - id: people
+    attributes: []
+    source:
+      data_source: memory
+      options: {{}}
+dev:
+  fixtures:
+    - sql: /fixtures/*.sql
+    - csv: /fixtures/people.csv
+      table: public.people
+    - csv: /fixtures/people.csv
+      data_source: memory
+      entity: people
+    - sql: /fixtures/*.sql
+      csv: /fixtures/people.csv
+    - csv: /fixtures/people.csv
+    - csv: /fixtures/people.csv
+      data_source: mysql
+      entity: orders
+"#
+        ));
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| (i.path.as_str(), i.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "dev.fixtures[3]",
+                    "A fixture must define either sql or csv, not both"
+                ),
+                (
+                    "dev.fixtures[4]",
+                    "A csv fixture must define the table or data source to load into"
+                ),
+                (
+                    "dev.fixtures[5].data_source",
+                    "Unknown memory data source 'mysql'"
+                ),
+                ("dev.fixtures[5].entity", "Unknown entity 'orders'"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_classification_masks() {
+        let issues = validate(
+            r#"
+name: test
+networking:
+  port: 1234
+auth:
+  users: []
+  masks:
+    - classification: pii
+      type: hash
+    - classification: confidental
+      type: "null"
+    - type: "null"
+build:
+  stages: []
+sources:
+  - id: mysql
+    type: jdbc.mysql
+    options: {}
+entities:
+  - id: orders
+    classifications: [confidential]
+    attributes:
+      - id: email
+        type: !Utf8String {}
+        classifications: [pii]
+    source:
+      data_source: mysql
+      options: {}
+"#,
+        );
+
+        assert_eq!(
+            issues
+                .iter()
+                .map(|i| (i.path.as_str(), i.suggestion.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("auth.masks[2]", None),
+                (
+                    "auth.masks[1].classification",
+                    Some("Did you mean 'confidential'?")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_user_limits() {
+        let issues = validate(
+            r#"
+name: test
+networking:
+  port: 1234
+auth:
+  users: []
+  limits:
+    - roles: [analyst]
+      max_result_rows: 10000
+      max_statement_runtime: 60
+    - users: [mary]
+      max_concurrent_statements: 0
+    - users: [john]
+      max_result_rows: -1
+build:
+  stages: []
+"#,
+        );
+
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec!["auth.limits[2]", "auth.limits[1].max_concurrent_statements"]
+        );
+    }
+
+    #[test]
+    fn test_validate_fetch_size() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+sources:
+  - id: mysql
+    type: jdbc.mysql
+    options: {{}}
+    fetch_size:
+      min_rows: 100
+      max_rows: 1000
+  - id: postgres
+    type: native.postgres
+    options: {{}}
+    fetch_size:
+      min_rows: 1000
+      max_rows: 100
+"#
+        ));
+
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec!["sources[1].fetch_size"]
+        );
+    }
+
+    #[test]
+    fn test_validate_admission() {
+        let issues = validate(&format!(
+            r#"{MINIMAL}
+sources:
+  - id: mysql
+    type: jdbc.mysql
+    options: {{}}
+    admission:
+      max_concurrent_scans: 10
+      queue_timeout: 60
+  - id: postgres
+    type: native.postgres
+    options: {{}}
+    admission:
+      max_concurrent_scans: 0
+"#
+        ));
+
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec!["sources[1].admission.max_concurrent_scans"]
+        );
+    }
+
+    #[test]
+    fn test_validate_workloads() {
+        let issues = validate(
+            r#"
+name: test
+networking:
+  port: 1234
+auth:
+  users: []
+  workloads:
+    - roles: [etl]
+      class: batch
+      max_concurrent_scans: 2
+      fetch_size:
+        min_rows: 1000
+    - users: [mary]
+      class: interactive
+      max_concurrent_scans: 0
+    - users: [john]
+      class: nightly
+    - users: [bob]
+      fetch_size:
+        min_rows: 1000
+        max_rows: 100
+build:
+  stages: []
+"#,
+        );
+
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec![
+                "auth.workloads[2]",
+                "auth.workloads[1].max_concurrent_scans",
+                "auth.workloads[3].fetch_size"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_catalogs() {
+        let issues = validate(
+            r#"
+name: test
+networking:
+  port: 1234
+auth:
+  users: []
+build:
+  stages: []
+sources:
+  - id: crm
+    type: test.memory
+    options: {}
+catalogs:
+  - name: sales
+    roles: [sales]
+    sources: [crm, crn]
+  - name: sales
+  - name: postgres
+jobs:
+  - id: refresh
+    catalog: sales
+    sql: SELECT 1
+  - id: report
+    catalog: finance
+    sql: SELECT 1
+"#,
+        );
+
+        assert_eq!(
+            issues.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            vec![
+                "catalogs[1].name",
+                "catalogs[0].sources[1]",
+                "catalogs[2].name",
+                "jobs[1].catalog"
+            ]
+        );
+    }
+}
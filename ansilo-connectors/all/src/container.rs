@@ -2,6 +2,7 @@ use std::str::FromStr;
 
 use ansilo_connectors_file_avro::{AvroConfig, AvroIO};
 use ansilo_connectors_file_base::{FileConnection, FileConnectionUnpool};
+use ansilo_connectors_file_wasm::{WasmConfig, WasmIO};
 use ansilo_connectors_jdbc_mssql::{MssqlJdbcConnectionConfig, MssqlJdbcEntitySourceConfig};
 use ansilo_connectors_jdbc_mysql::{MysqlJdbcConnectionConfig, MysqlJdbcEntitySourceConfig};
 use ansilo_connectors_jdbc_teradata::{
@@ -11,15 +12,19 @@ use ansilo_connectors_native_mongodb::{
     MongodbConnection, MongodbConnectionConfig, MongodbConnectionUnpool, MongodbEntitySourceConfig,
 };
 use ansilo_connectors_native_postgres::{
-    PooledClient, PostgresConnection, PostgresConnectionConfig, PostgresConnectionPool,
-    PostgresEntitySourceConfig, UnpooledClient,
+    PooledClient, PostgresChangeStream, PostgresConnection, PostgresConnectionConfig,
+    PostgresConnectionPool, PostgresEntitySourceConfig, UnpooledClient,
 };
 use ansilo_connectors_native_sqlite::{
     SqliteConnection, SqliteConnectionConfig, SqliteConnectionUnpool, SqliteEntitySourceConfig,
 };
 use ansilo_connectors_peer::{conf::PeerConfig, pool::PeerConnectionUnpool};
+use ansilo_connectors_plugin::{
+    ConnectorPlugin, PluginConnection, PluginConnectionConfig, PluginConnectionPool,
+    PluginEntitySourceConfig,
+};
 use ansilo_core::{
-    config::{self, NodeConfig},
+    config::{self, EntityConfig, NodeConfig},
     err::{bail, Context, Result},
 };
 
@@ -33,10 +38,13 @@ use ansilo_connectors_jdbc_base::{JdbcConnection, JdbcConnectionPool};
 
 use ansilo_connectors_jdbc_oracle::{OracleJdbcConnectionConfig, OracleJdbcEntitySourceConfig};
 
-use ansilo_connectors_base::interface::Connector;
+use ansilo_connectors_base::interface::{
+    ChangeStream, ConnectionPool, Connector, EntityDiscoverOptions, EntitySearcher, EntityValidator,
+};
 
 pub use ansilo_connectors_file_avro::AvroConnector;
 pub use ansilo_connectors_file_base::FileSourceConfig;
+pub use ansilo_connectors_file_wasm::WasmConnector;
 pub use ansilo_connectors_internal::{InternalConnection, InternalConnector};
 pub use ansilo_connectors_jdbc_mssql::MssqlJdbcConnector;
 pub use ansilo_connectors_jdbc_mysql::MysqlJdbcConnector;
@@ -47,6 +55,7 @@ pub use ansilo_connectors_native_mongodb::MongodbConnector;
 pub use ansilo_connectors_native_postgres::PostgresConnector;
 pub use ansilo_connectors_native_sqlite::SqliteConnector;
 pub use ansilo_connectors_peer::PeerConnector;
+pub use ansilo_connectors_plugin::PluginConnector;
 
 #[derive(Debug, PartialEq)]
 pub enum Connectors {
@@ -58,9 +67,11 @@ pub enum Connectors {
     NativeSqlite,
     NativeMongodb,
     FileAvro,
+    FileWasm,
     Peer,
     Internal,
     Memory,
+    Plugin(&'static ConnectorPlugin),
 }
 
 #[derive(Debug)]
@@ -73,9 +84,11 @@ pub enum ConnectionConfigs {
     NativeSqlite(SqliteConnectionConfig),
     NativeMongodb(MongodbConnectionConfig),
     FileAvro(AvroConfig),
+    FileWasm(WasmConfig),
     Peer(PeerConfig),
     Internal,
     Memory(MemoryDatabase),
+    Plugin(PluginConnectionConfig),
 }
 
 #[derive(Debug)]
@@ -91,6 +104,7 @@ pub enum EntitySourceConfigs {
     Peer(PostgresEntitySourceConfig),
     Internal,
     Memory(MemoryConnectorEntitySourceConfig),
+    Plugin(PluginEntitySourceConfig),
 }
 
 #[derive(Clone)]
@@ -106,6 +120,7 @@ pub enum ConnectorEntityConfigs {
     Peer(ConnectorEntityConfig<PostgresEntitySourceConfig>),
     Internal,
     Memory(ConnectorEntityConfig<MemoryConnectorEntitySourceConfig>),
+    Plugin(ConnectorEntityConfig<PluginEntitySourceConfig>),
 }
 
 #[derive(Clone)]
@@ -115,9 +130,11 @@ pub enum ConnectionPools {
     NativeSqlite(SqliteConnectionUnpool),
     NativeMongodb(MongodbConnectionUnpool),
     FileAvro(FileConnectionUnpool<AvroIO>),
+    FileWasm(FileConnectionUnpool<WasmIO>),
     Peer(PeerConnectionUnpool),
     Internal(InternalConnection),
     Memory(MemoryConnectionPool),
+    Plugin(PluginConnectionPool),
 }
 
 pub enum Connections {
@@ -126,12 +143,17 @@ pub enum Connections {
     NativeSqlite(SqliteConnection),
     NativeMongodb(MongodbConnection),
     FileAvro(FileConnection<AvroIO>),
+    FileWasm(FileConnection<WasmIO>),
     Peer(PostgresConnection<UnpooledClient>),
     Internal(InternalConnection),
     Memory(MemoryConnection),
+    Plugin(PluginConnection),
 }
 
 impl Connectors {
+    /// Gets the connector of the supplied type.
+    ///
+    /// The built-in connectors take precedence over the connectors of loaded plugins.
     pub fn from_type(r#type: &str) -> Option<Self> {
         Some(match r#type {
             OracleJdbcConnector::TYPE => Connectors::OracleJdbc,
@@ -142,13 +164,44 @@ impl Connectors {
             SqliteConnector::TYPE => Connectors::NativeSqlite,
             MongodbConnector::TYPE => Connectors::NativeMongodb,
             AvroConnector::TYPE => Connectors::FileAvro,
+            WasmConnector::TYPE => Connectors::FileWasm,
             PeerConnector::TYPE => Connectors::Peer,
             InternalConnector::TYPE => Connectors::Internal,
             MemoryConnector::TYPE => Connectors::Memory,
-            _ => return None,
+            _ => return ansilo_connectors_plugin::find(r#type).map(Connectors::Plugin),
         })
     }
 
+    /// Returns all the supported connectors, including those of loaded plugins
+    pub fn all() -> Vec<Self> {
+        let builtin = vec![
+            Connectors::OracleJdbc,
+            Connectors::MysqlJdbc,
+            Connectors::TeradataJdbc,
+            Connectors::MssqlJdbc,
+            Connectors::NativePostgres,
+            Connectors::NativeSqlite,
+            Connectors::NativeMongodb,
+            Connectors::FileAvro,
+            Connectors::FileWasm,
+            Connectors::Peer,
+            Connectors::Internal,
+            Connectors::Memory,
+        ];
+
+        let plugins = ansilo_connectors_plugin::all()
+            .into_iter()
+            .filter(|p| !builtin.iter().any(|c| c.r#type() == p.r#type()))
+            .map(Connectors::Plugin);
+
+        builtin.into_iter().chain(plugins).collect()
+    }
+
+    /// Returns the types of all the supported connectors
+    pub fn types() -> Vec<&'static str> {
+        Self::all().iter().map(|c| c.r#type()).collect()
+    }
+
     pub fn r#type(&self) -> &'static str {
         match self {
             Connectors::OracleJdbc => OracleJdbcConnector::TYPE,
@@ -159,9 +212,11 @@ impl Connectors {
             Connectors::NativeSqlite => SqliteConnector::TYPE,
             Connectors::NativeMongodb => MongodbConnector::TYPE,
             Connectors::FileAvro => AvroConnector::TYPE,
+            Connectors::FileWasm => WasmConnector::TYPE,
             Connectors::Peer => PeerConnector::TYPE,
             Connectors::Internal => InternalConnector::TYPE,
             Connectors::Memory => MemoryConnector::TYPE,
+            Connectors::Plugin(plugin) => plugin.r#type(),
         }
     }
 
@@ -191,11 +246,15 @@ impl Connectors {
             Connectors::FileAvro => {
                 ConnectionConfigs::FileAvro(AvroConnector::parse_options(options)?)
             }
+            Connectors::FileWasm => {
+                ConnectionConfigs::FileWasm(WasmConnector::parse_options(options)?)
+            }
             Connectors::Peer => ConnectionConfigs::Peer(PeerConnector::parse_options(options)?),
             Connectors::Internal => ConnectionConfigs::Internal,
             Connectors::Memory => {
                 ConnectionConfigs::Memory(MemoryConnector::parse_options(options)?)
             }
+            Connectors::Plugin(plugin) => ConnectionConfigs::Plugin(plugin.parse_options(options)?),
         })
     }
 
@@ -228,6 +287,9 @@ impl Connectors {
             Connectors::FileAvro => {
                 EntitySourceConfigs::File(AvroConnector::parse_entity_source_options(options)?)
             }
+            Connectors::FileWasm => {
+                EntitySourceConfigs::File(WasmConnector::parse_entity_source_options(options)?)
+            }
             Connectors::Peer => {
                 EntitySourceConfigs::Peer(PeerConnector::parse_entity_source_options(options)?)
             }
@@ -235,6 +297,9 @@ impl Connectors {
             Connectors::Memory => {
                 EntitySourceConfigs::Memory(MemoryConnector::parse_entity_source_options(options)?)
             }
+            Connectors::Plugin(plugin) => {
+                EntitySourceConfigs::Plugin(plugin.parse_entity_source_options(options)?)
+            }
         })
     }
 
@@ -309,6 +374,14 @@ impl Connectors {
                     ConnectorEntityConfigs::File(entities),
                 )
             }
+            (Connectors::FileWasm, ConnectionConfigs::FileWasm(options)) => {
+                let (pool, entities) =
+                    Self::create_pool::<WasmConnector>(options, nc, data_source_id)?;
+                (
+                    ConnectionPools::FileWasm(pool),
+                    ConnectorEntityConfigs::File(entities),
+                )
+            }
             (Connectors::Peer, ConnectionConfigs::Peer(options)) => {
                 let (pool, entities) =
                     Self::create_pool::<PeerConnector>(options, nc, data_source_id)?;
@@ -332,6 +405,16 @@ impl Connectors {
                     ConnectorEntityConfigs::Memory(entities),
                 )
             }
+            (Connectors::Plugin(plugin), ConnectionConfigs::Plugin(options))
+                if options.plugin == *plugin =>
+            {
+                let (pool, entities) =
+                    Self::create_pool::<PluginConnector>(options, nc, data_source_id)?;
+                (
+                    ConnectionPools::Plugin(pool),
+                    ConnectorEntityConfigs::Plugin(entities),
+                )
+            }
             (this, options) => bail!(
                 "Type mismatch between connector {:?} and config {:?}",
                 this,
@@ -340,6 +423,83 @@ impl Connectors {
         })
     }
 
+    /// Re-validates the entity against its data source and retrieves the
+    /// current schema of the entity from the data source.
+    ///
+    /// The remote entity is found by discovering the entities of the data source
+    /// and matching their source options, returns None if it no longer exists.
+    pub fn describe_entity(
+        &self,
+        pool: &mut ConnectionPools,
+        nc: &NodeConfig,
+        entity: &EntityConfig,
+    ) -> Result<Option<EntityConfig>> {
+        match (self, pool) {
+            (Connectors::OracleJdbc, ConnectionPools::Jdbc(pool)) => {
+                Self::describe::<OracleJdbcConnector>(pool, nc, entity)
+            }
+            (Connectors::MysqlJdbc, ConnectionPools::Jdbc(pool)) => {
+                Self::describe::<MysqlJdbcConnector>(pool, nc, entity)
+            }
+            (Connectors::TeradataJdbc, ConnectionPools::Jdbc(pool)) => {
+                Self::describe::<TeradataJdbcConnector>(pool, nc, entity)
+            }
+            (Connectors::MssqlJdbc, ConnectionPools::Jdbc(pool)) => {
+                Self::describe::<MssqlJdbcConnector>(pool, nc, entity)
+            }
+            (Connectors::NativePostgres, ConnectionPools::NativePostgres(pool)) => {
+                Self::describe::<PostgresConnector>(pool, nc, entity)
+            }
+            (Connectors::NativeSqlite, ConnectionPools::NativeSqlite(pool)) => {
+                Self::describe::<SqliteConnector>(pool, nc, entity)
+            }
+            (Connectors::NativeMongodb, ConnectionPools::NativeMongodb(pool)) => {
+                Self::describe::<MongodbConnector>(pool, nc, entity)
+            }
+            (Connectors::FileAvro, ConnectionPools::FileAvro(pool)) => {
+                Self::describe::<AvroConnector>(pool, nc, entity)
+            }
+            (Connectors::FileWasm, ConnectionPools::FileWasm(pool)) => {
+                Self::describe::<WasmConnector>(pool, nc, entity)
+            }
+            (Connectors::Peer, ConnectionPools::Peer(pool)) => {
+                Self::describe::<PeerConnector>(pool, nc, entity)
+            }
+            (Connectors::Memory, ConnectionPools::Memory(pool)) => {
+                Self::describe::<MemoryConnector>(pool, nc, entity)
+            }
+            (Connectors::Plugin(_), ConnectionPools::Plugin(pool)) => {
+                Self::describe::<PluginConnector>(pool, nc, entity)
+            }
+            (Connectors::Internal, _) => {
+                bail!("Entities of the internal data source cannot be described")
+            }
+            (this, _) => bail!("Type mismatch between connector {:?} and pool", this),
+        }
+    }
+
+    fn describe<TConnector: Connector>(
+        pool: &mut TConnector::TConnectionPool,
+        nc: &NodeConfig,
+        entity: &EntityConfig,
+    ) -> Result<Option<EntityConfig>> {
+        let mut con = pool
+            .acquire(None)
+            .context("Failed to connect to data source")?;
+
+        TConnector::TEntityValidator::validate(&mut con, entity, nc)
+            .context("Failed to validate entity config")?;
+
+        let options = normalize_options(&entity.source.options);
+        let remote =
+            TConnector::TEntitySearcher::discover(&mut con, nc, EntityDiscoverOptions::default())
+                .context("Failed to discover entities")?;
+
+        Ok(remote
+            .into_iter()
+            .find(|e| normalize_options(&e.source.options) == options))
+    }
+
     fn create_pool<TConnector: Connector>(
         options: TConnector::TConnectionConfig,
         nc: &NodeConfig,
@@ -359,6 +519,106 @@ impl Connectors {
     }
 }
 
+impl ConnectionConfigs {
+    /// Whether the changes to the entities of the data source can be captured
+    pub fn supports_change_capture(&self) -> bool {
+        matches!(self, ConnectionConfigs::NativePostgres(_))
+    }
+
+    /// Opens the stream of changes made to the entity in the data source.
+    ///
+    /// The `consumer` uniquely identifies the reader of the stream, the position
+    /// of the stream is retained by the data source for each consumer.
+    pub fn open_change_stream(
+        &self,
+        entity: &EntityConfig,
+        consumer: &str,
+    ) -> Result<Box<dyn ChangeStream>> {
+        Ok(match self {
+            ConnectionConfigs::NativePostgres(conf) => {
+                Box::new(PostgresChangeStream::open(conf.clone(), entity, consumer)?)
+            }
+            _ => bail!("Change data capture is not supported by this data source"),
+        })
+    }
+}
+
+impl ConnectionPools {
+    /// Whether the data source can be pinged outside of a user session
+    pub fn supports_ping(&self) -> bool {
+        match self {
+            ConnectionPools::Peer(pool) => pool.has_credentials(),
+            ConnectionPools::Internal(_) => false,
+            _ => true,
+        }
+    }
+
+    /// Checks the data source is reachable by acquiring a connection.
+    ///
+    /// JDBC connections are validated when they are checked out of the pool,
+    /// for postgres-based sources a cheap validation query is executed.
+    pub fn ping(&mut self) -> Result<()> {
+        match self {
+            ConnectionPools::Jdbc(pool) => {
+                pool.acquire(None)?;
+            }
+            ConnectionPools::NativePostgres(pool) => {
+                pool.acquire(None)?.execute_modify("SELECT 1", vec![])?;
+            }
+            ConnectionPools::NativeSqlite(pool) => {
+                pool.acquire(None)?;
+            }
+            ConnectionPools::NativeMongodb(pool) => {
+                pool.acquire(None)?;
+            }
+            ConnectionPools::FileAvro(pool) => {
+                pool.acquire(None)?;
+            }
+            ConnectionPools::FileWasm(pool) => {
+                pool.acquire(None)?;
+            }
+            ConnectionPools::Peer(pool) => {
+                pool.acquire(None)?.execute_modify("SELECT 1", vec![])?;
+            }
+            ConnectionPools::Internal(pool) => {
+                pool.acquire(None)?;
+            }
+            ConnectionPools::Memory(pool) => {
+                pool.acquire(None)?;
+            }
+            ConnectionPools::Plugin(pool) => {
+                pool.acquire(None)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes the null and empty values from the entity source options so the
+/// options of configured and discovered entities can be compared
+fn normalize_options(options: &config::Value) -> config::Value {
+    let is_empty = |v: &config::Value| match v {
+        config::Value::Null => true,
+        config::Value::Mapping(m) => m.is_empty(),
+        config::Value::Sequence(s) => s.is_empty(),
+        _ => false,
+    };
+
+    match options {
+        config::Value::Mapping(map) => config::Value::Mapping(
+            map.iter()
+                .map(|(k, v)| (k.clone(), normalize_options(v)))
+                .filter(|(_, v)| !is_empty(v))
+                .collect(),
+        ),
+        config::Value::Sequence(seq) => {
+            config::Value::Sequence(seq.iter().map(normalize_options).collect())
+        }
+        other => other.clone(),
+    }
+}
+
 impl FromStr for Connectors {
     type Err = ansilo_core::err::Error;
 
@@ -1,3 +1,4 @@
 pub mod data;
 pub mod entity;
-pub mod query;
\ No newline at end of file
+pub mod query;
+pub mod tls;
\ No newline at end of file
@@ -0,0 +1,217 @@
+use std::{fs, path::PathBuf};
+
+use ansilo_core::err::{bail, ensure, Context, Result};
+use ansilo_logging::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Options for verifying the server certificate when connecting to a
+/// data source over TLS.
+///
+/// The options are shared across connectors but not every driver supports
+/// every mode, unsupported modes are rejected by the connector.
+///
+/// Examples:
+///   tls:
+///     verify: system
+///
+///   tls:
+///     verify: ca_bundle
+///     ca_bundle: /etc/ssl/certs/internal-ca.pem
+///
+///   tls:
+///     verify: fingerprint
+///     fingerprint: "AB:CD:..."
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(tag = "verify", rename_all = "snake_case")]
+pub enum TlsVerifyConfig {
+    /// Verify the certificate against the system trust store
+    #[default]
+    System,
+    /// Verify the certificate against the CA certificates in the PEM bundle
+    CaBundle { ca_bundle: PathBuf },
+    /// Only accept the certificate with the pinned SHA-256 fingerprint
+    Fingerprint { fingerprint: String },
+    /// Disable certificate verification, the connection is encrypted
+    /// but vulnerable to interception
+    Disabled,
+}
+
+impl TlsVerifyConfig {
+    /// Logs a warning if certificate verification is disabled
+    pub fn warn_if_disabled(&self, target: &str) {
+        if self == &Self::Disabled {
+            warn!("TLS certificate verification is disabled when connecting to {target}, the connection is vulnerable to interception");
+        }
+    }
+
+    /// Reads the certificates from the configured CA bundle, returning each
+    /// certificate in PEM format
+    pub fn read_ca_bundle(&self) -> Result<Vec<Vec<u8>>> {
+        let path = match self {
+            Self::CaBundle { ca_bundle } => ca_bundle,
+            _ => return Ok(vec![]),
+        };
+
+        let pem = fs::read(path)
+            .with_context(|| format!("Failed to read CA bundle from {}", path.display()))?;
+        let certs = split_pem_bundle(&pem);
+        ensure!(
+            !certs.is_empty(),
+            "No certificates found in CA bundle {}",
+            path.display()
+        );
+
+        Ok(certs)
+    }
+
+    /// Checks the DER-encoded server certificate against the pinned fingerprint
+    pub fn verify_fingerprint(&self, der: &[u8]) -> Result<()> {
+        let expected = match self {
+            Self::Fingerprint { fingerprint } => normalise_fingerprint(fingerprint)?,
+            _ => return Ok(()),
+        };
+
+        let actual = sha256_fingerprint(der);
+        if normalise_fingerprint(&actual)? != expected {
+            bail!("Server certificate fingerprint {actual} does not match the pinned fingerprint");
+        }
+
+        Ok(())
+    }
+
+    /// Validates the configured options
+    pub fn validate(&self) -> Result<()> {
+        if let Self::Fingerprint { fingerprint } = self {
+            normalise_fingerprint(fingerprint)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the SHA-256 fingerprint of the DER-encoded certificate
+/// formatted as colon-separated hex, eg "AB:CD:..."
+pub fn sha256_fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Parses the fingerprint, allowing for colon-separated or plain hex in either case
+fn normalise_fingerprint(fingerprint: &str) -> Result<Vec<u8>> {
+    let digest = hex::decode(fingerprint.trim().replace(':', ""))
+        .with_context(|| format!("Invalid certificate fingerprint \"{fingerprint}\""))?;
+    ensure!(
+        digest.len() == 32,
+        "Certificate fingerprint \"{fingerprint}\" must be a SHA-256 digest"
+    );
+
+    Ok(digest)
+}
+
+/// Splits the PEM bundle into the individual certificates
+fn split_pem_bundle(pem: &[u8]) -> Vec<Vec<u8>> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+
+    let pem = String::from_utf8_lossy(pem);
+    let mut certs = vec![];
+    let mut rest = pem.as_ref();
+
+    while let Some(start) = rest.find(BEGIN) {
+        let end = match rest[start..].find(END) {
+            Some(end) => start + end + END.len(),
+            None => break,
+        };
+
+        certs.push(rest[start..end].as_bytes().to_vec());
+        rest = &rest[end..];
+    }
+
+    certs
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::config;
+
+    use super::*;
+
+    #[test]
+    fn test_tls_verify_config_parse() {
+        let parse = |yaml: &str| {
+            config::from_value::<TlsVerifyConfig>(config::parse_config(yaml).unwrap()).unwrap()
+        };
+
+        assert_eq!(parse("verify: system"), TlsVerifyConfig::System);
+        assert_eq!(
+            parse("verify: ca_bundle\nca_bundle: /etc/ca.pem"),
+            TlsVerifyConfig::CaBundle {
+                ca_bundle: "/etc/ca.pem".into()
+            }
+        );
+        assert_eq!(
+            parse("verify: fingerprint\nfingerprint: AB:CD"),
+            TlsVerifyConfig::Fingerprint {
+                fingerprint: "AB:CD".into()
+            }
+        );
+        assert_eq!(parse("verify: disabled"), TlsVerifyConfig::Disabled);
+    }
+
+    #[test]
+    fn test_tls_verify_fingerprint() {
+        let der = b"mock certificate";
+        let fingerprint = sha256_fingerprint(der);
+        assert_eq!(fingerprint.len(), 32 * 3 - 1);
+
+        let conf = TlsVerifyConfig::Fingerprint {
+            fingerprint: fingerprint.clone(),
+        };
+        conf.validate().unwrap();
+        conf.verify_fingerprint(der).unwrap();
+        conf.verify_fingerprint(b"other certificate").unwrap_err();
+
+        // plain lowercase hex is also accepted
+        let conf = TlsVerifyConfig::Fingerprint {
+            fingerprint: fingerprint.replace(':', "").to_lowercase(),
+        };
+        conf.verify_fingerprint(der).unwrap();
+
+        let conf = TlsVerifyConfig::Fingerprint {
+            fingerprint: "AB:CD".into(),
+        };
+        conf.validate().unwrap_err();
+        conf.verify_fingerprint(der).unwrap_err();
+
+        TlsVerifyConfig::System
+            .verify_fingerprint(b"other certificate")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_split_pem_bundle() {
+        let pem = "\
+# Root CA
+-----BEGIN CERTIFICATE-----
+AAAA
+-----END CERTIFICATE-----
+# Intermediate CA
+-----BEGIN CERTIFICATE-----
+BBBB
+-----END CERTIFICATE-----
+";
+
+        assert_eq!(
+            split_pem_bundle(pem.as_bytes()),
+            vec![
+                b"-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----".to_vec(),
+                b"-----BEGIN CERTIFICATE-----\nBBBB\n-----END CERTIFICATE-----".to_vec(),
+            ]
+        );
+        assert!(split_pem_bundle(b"invalid").is_empty());
+    }
+}
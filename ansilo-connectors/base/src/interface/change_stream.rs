@@ -0,0 +1,51 @@
+use ansilo_core::{data::DataValue, err::Result};
+
+/// A stream of the changes made to an entity in the data source.
+///
+/// Connectors which support change data capture implement this to
+/// allow the local copy of an entity to be kept up to date as the
+/// data source changes, rather than being replaced periodically.
+pub trait ChangeStream: Send {
+    /// Reads up to `max` changes which have not yet been acknowledged.
+    ///
+    /// Returns immediately with an empty batch if there are no pending changes.
+    /// Changes are read again until they are acknowledged.
+    fn read(&mut self, max: usize) -> Result<ChangeBatch>;
+
+    /// Acknowledges the changes up to and including the supplied position
+    /// have been applied, so they are not read again
+    fn ack(&mut self, position: &str) -> Result<()>;
+}
+
+/// A batch of changes read from a change stream
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ChangeBatch {
+    /// The changes, in the order they were made
+    pub changes: Vec<RowChange>,
+    /// The position of the stream following the changes, if any were read
+    pub position: Option<String>,
+}
+
+/// A change to the rows of an entity.
+///
+/// The values are keyed by the attribute ids of the entity.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowChange {
+    /// A row was inserted or updated.
+    /// Attributes which were not changed may be omitted.
+    Upsert(Vec<(String, DataValue)>),
+    /// The row with the supplied primary key was deleted
+    Delete(Vec<(String, DataValue)>),
+    /// All rows were deleted
+    Truncate,
+}
+
+impl ChangeBatch {
+    pub fn new(changes: Vec<RowChange>, position: Option<String>) -> Self {
+        Self { changes, position }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
@@ -1,3 +1,4 @@
+mod change_stream;
 mod connection;
 mod entity_searcher;
 mod entity_validator;
@@ -6,6 +7,7 @@ mod query_compiler;
 mod query_planner;
 mod result_set;
 
+pub use change_stream::*;
 pub use connection::*;
 pub use entity_searcher::*;
 pub use entity_validator::*;
@@ -24,4 +24,11 @@ pub trait QueryCompiler {
         query: String,
         params: Vec<sql::Parameter>,
     ) -> Result<Self::TQuery>;
+
+    /// Gets a key which identifies the compiled query and its parameter types.
+    /// Prepared queries with equal keys are interchangeable and can be reused
+    /// across sessions. Connectors return `None` if this is not supported.
+    fn query_key(_query: &Self::TQuery) -> Option<String> {
+        None
+    }
 }
@@ -0,0 +1,142 @@
+use std::path::{Path, PathBuf};
+
+use ansilo_connectors_file_base::FileConfig;
+use ansilo_core::{
+    config,
+    err::{Context, Result},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::WasmModule;
+
+/// The default amount of fuel available to each call into a module
+const DEFAULT_FUEL: u64 = 1_000_000_000;
+
+/// The default maximum size of the memory of a module, in megabytes
+const DEFAULT_MEMORY_MB: u32 = 256;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WasmConfig {
+    /// The path in which the files are stored
+    pub path: PathBuf,
+    /// The path of the module which parses the files
+    pub parser: PathBuf,
+    /// The path of the module which transforms the parsed rows, if any
+    pub transform: Option<PathBuf>,
+    /// The limits of the sandbox in which the modules are executed
+    #[serde(default)]
+    pub limits: WasmLimits,
+}
+
+impl WasmConfig {
+    pub fn new(path: PathBuf, parser: PathBuf) -> Self {
+        Self {
+            path,
+            parser,
+            transform: None,
+            limits: WasmLimits::default(),
+        }
+    }
+
+    pub fn parse(options: config::Value) -> Result<Self> {
+        config::from_value::<Self>(options)
+            .context("Failed to parse connection configuration options")
+    }
+}
+
+/// The resources available to a module
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct WasmLimits {
+    /// The fuel available to each call into the module, this is
+    /// roughly the number of instructions executed, defaults to 1 billion
+    pub fuel: Option<u64>,
+    /// The maximum size of the memory of the module in megabytes, defaults to 256
+    pub memory_mb: Option<u32>,
+}
+
+impl WasmLimits {
+    pub fn fuel(&self) -> u64 {
+        self.fuel.unwrap_or(DEFAULT_FUEL)
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.memory_mb.unwrap_or(DEFAULT_MEMORY_MB) as usize * 1024 * 1024
+    }
+}
+
+/// The config of a data source along with its loaded modules
+#[derive(Clone)]
+pub struct WasmFileConfig {
+    pub conf: WasmConfig,
+    pub parser: WasmModule,
+    pub transform: Option<WasmModule>,
+}
+
+impl WasmFileConfig {
+    pub fn new(conf: WasmConfig, parser: WasmModule, transform: Option<WasmModule>) -> Self {
+        Self {
+            conf,
+            parser,
+            transform,
+        }
+    }
+
+    /// Loads the modules of the data source
+    pub fn load(conf: WasmConfig) -> Result<Self> {
+        let parser = WasmModule::load(&conf.parser, conf.limits)?;
+        let transform = match &conf.transform {
+            Some(path) => Some(WasmModule::load(path, conf.limits)?),
+            None => None,
+        };
+
+        Ok(Self::new(conf, parser, transform))
+    }
+}
+
+impl FileConfig for WasmFileConfig {
+    fn get_path(&self) -> &Path {
+        self.conf.path.as_path()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wasm_config_parse() {
+        let conf = WasmConfig::parse(
+            serde_yaml::from_str(
+                r#"
+path: /data
+parser: /plugins/parser.wasm
+transform: /plugins/transform.wasm
+limits:
+  memory_mb: 16
+"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            conf,
+            WasmConfig {
+                path: "/data".into(),
+                parser: "/plugins/parser.wasm".into(),
+                transform: Some("/plugins/transform.wasm".into()),
+                limits: WasmLimits {
+                    fuel: None,
+                    memory_mb: Some(16)
+                }
+            }
+        );
+        assert_eq!(conf.limits.fuel(), DEFAULT_FUEL);
+        assert_eq!(conf.limits.memory_bytes(), 16 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_wasm_config_parse_requires_parser() {
+        WasmConfig::parse(serde_yaml::from_str("path: /data").unwrap()).unwrap_err();
+    }
+}
@@ -0,0 +1,153 @@
+use ansilo_connectors_file_base::{FileColumn, FileStructure};
+use ansilo_core::{
+    data::{DataType, DataValue},
+    err::{Context, Result},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+/// A column of the rows returned by a module
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WasmColumn {
+    pub name: String,
+    /// The type of the column, using the same names as the entity config
+    pub r#type: JsonValue,
+    #[serde(default = "default_nullable")]
+    pub nullable: bool,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+fn default_nullable() -> bool {
+    true
+}
+
+impl WasmColumn {
+    /// Parses the type of the column.
+    ///
+    /// Types with options can be specified by name only, eg "Utf8String"
+    /// rather than `{"Utf8String": {}}`.
+    pub fn data_type(&self) -> Result<DataType> {
+        if let Ok(r#type) = serde_json::from_value(self.r#type.clone()) {
+            return Ok(r#type);
+        }
+
+        let r#type = match &self.r#type {
+            JsonValue::String(name) => serde_json::from_value(serde_json::json!({ name: {} })).ok(),
+            _ => None,
+        };
+
+        r#type.with_context(|| {
+            format!(
+                "Unknown type {} of column '{}' returned from wasm module",
+                self.r#type, self.name
+            )
+        })
+    }
+}
+
+impl From<&FileColumn> for WasmColumn {
+    fn from(col: &FileColumn) -> Self {
+        Self {
+            name: col.name.clone(),
+            r#type: serde_json::to_value(&col.r#type).unwrap_or(JsonValue::Null),
+            nullable: col.nullable,
+            description: col.desc.clone(),
+        }
+    }
+}
+
+/// Converts the columns returned by a module into the structure of the file
+pub fn into_file_structure(cols: &[WasmColumn]) -> Result<FileStructure> {
+    let cols = cols
+        .iter()
+        .map(|c| {
+            Ok(FileColumn::new(
+                c.name.clone(),
+                c.data_type()?,
+                c.nullable,
+                c.description.clone(),
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(FileStructure::new(cols, None))
+}
+
+/// Converts a JSON value returned from a module into the data type of the column
+pub fn from_json_value(val: JsonValue, r#type: &DataType) -> Result<DataValue> {
+    let val = match val {
+        JsonValue::Null => DataValue::Null,
+        JsonValue::Bool(b) => DataValue::Boolean(b),
+        JsonValue::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                DataValue::Int64(i)
+            } else if let Some(u) = n.as_u64() {
+                DataValue::UInt64(u)
+            } else {
+                DataValue::Float64(n.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        JsonValue::String(s) => DataValue::Utf8String(s),
+        val @ (JsonValue::Array(_) | JsonValue::Object(_)) => DataValue::JSON(val.to_string()),
+    };
+
+    val.try_coerce_into(r#type)
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::data::StringOptions;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_wasm_column_data_type() {
+        let col = |r#type: JsonValue| WasmColumn {
+            name: "col".into(),
+            r#type,
+            nullable: true,
+            description: None,
+        };
+
+        assert_eq!(col(json!("Int32")).data_type().unwrap(), DataType::Int32);
+        assert_eq!(
+            col(json!("Utf8String")).data_type().unwrap(),
+            DataType::Utf8String(StringOptions::default())
+        );
+        assert_eq!(
+            col(json!({"Utf8String": {"length": 10}}))
+                .data_type()
+                .unwrap(),
+            DataType::Utf8String(StringOptions::new(Some(10)))
+        );
+        col(json!("Unknown")).data_type().unwrap_err();
+        col(json!(1)).data_type().unwrap_err();
+    }
+
+    #[test]
+    fn test_wasm_from_json_value() {
+        assert_eq!(
+            from_json_value(json!(null), &DataType::Int32).unwrap(),
+            DataValue::Null
+        );
+        assert_eq!(
+            from_json_value(json!(123), &DataType::Int32).unwrap(),
+            DataValue::Int32(123)
+        );
+        assert_eq!(
+            from_json_value(json!("abc"), &DataType::rust_string()).unwrap(),
+            DataValue::Utf8String("abc".into())
+        );
+        assert_eq!(
+            from_json_value(json!(true), &DataType::Boolean).unwrap(),
+            DataValue::Boolean(true)
+        );
+        assert_eq!(
+            from_json_value(json!({"a": 1}), &DataType::JSON).unwrap(),
+            DataValue::JSON(r#"{"a":1}"#.into())
+        );
+        from_json_value(json!("abc"), &DataType::Int32).unwrap_err();
+    }
+}
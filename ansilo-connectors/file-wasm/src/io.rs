@@ -0,0 +1,309 @@
+use std::{collections::VecDeque, fs, path::Path};
+
+use ansilo_connectors_file_base::{FileIO, FileReader, FileStructure, NullWriter};
+use ansilo_core::{
+    data::DataValue,
+    err::{bail, Context, Result},
+};
+use serde::Deserialize;
+use serde_json::{Map, Value as JsonValue};
+
+use crate::{
+    data::{from_json_value, into_file_structure, WasmColumn},
+    WasmFileConfig, WasmInstance,
+};
+
+/// Parses the contents of a file into its columns and rows
+pub const PARSE_EXPORT: &str = "ansilo_parse";
+
+/// Transforms a row, returning the new row or null to skip it
+pub const TRANSFORM_EXPORT: &str = "ansilo_transform";
+
+/// Gets the columns of the transformed rows, if not exported the columns are unchanged
+pub const TRANSFORM_COLUMNS_EXPORT: &str = "ansilo_transform_columns";
+
+/// The output of the parser module
+#[derive(Debug, Deserialize)]
+struct ParseOutput {
+    columns: Vec<WasmColumn>,
+    #[serde(default)]
+    rows: Vec<Vec<JsonValue>>,
+}
+
+#[derive(Clone)]
+pub struct WasmIO;
+
+impl FileIO for WasmIO {
+    type Conf = WasmFileConfig;
+    type Reader = WasmReader;
+    type Writer = NullWriter;
+
+    fn get_structure(conf: &Self::Conf, path: &Path) -> Result<FileStructure> {
+        let parsed = parse(conf, path)?;
+        let cols = match &conf.transform {
+            Some(transform) if transform.exports(TRANSFORM_COLUMNS_EXPORT) => {
+                let output = transform.instantiate()?.call(
+                    TRANSFORM_COLUMNS_EXPORT,
+                    &serde_json::to_vec(&parsed.columns)?,
+                )?;
+
+                serde_json::from_slice(&output)
+                    .context("Failed to parse columns returned from transform module")?
+            }
+            _ => parsed.columns,
+        };
+
+        into_file_structure(&cols)
+    }
+
+    fn estimate_row_count(_conf: &Self::Conf, _path: &Path) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    fn get_extension(_conf: &Self::Conf) -> Option<&'static str> {
+        None
+    }
+
+    fn reader(conf: &Self::Conf, structure: &FileStructure, path: &Path) -> Result<Self::Reader> {
+        WasmReader::new(conf, structure, path)
+    }
+
+    fn supports_writing(_conf: &Self::Conf, _path: &Path) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn writer(_conf: &Self::Conf, _structure: &FileStructure, _path: &Path) -> Result<NullWriter> {
+        bail!("Writing is not supported by wasm modules")
+    }
+
+    fn supports_truncating(_conf: &Self::Conf, _path: &Path) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn truncate(_conf: &Self::Conf, _structure: &FileStructure, _path: &Path) -> Result<()> {
+        bail!("Truncating is not supported by wasm modules")
+    }
+}
+
+/// Parses the file at the supplied path using the parser module
+fn parse(conf: &WasmFileConfig, path: &Path) -> Result<ParseOutput> {
+    let data = fs::read(path).with_context(|| format!("Failed to read file {}", path.display()))?;
+
+    let output = conf
+        .parser
+        .instantiate()?
+        .call(PARSE_EXPORT, &data)
+        .with_context(|| format!("Failed to parse file {}", path.display()))?;
+
+    serde_json::from_slice(&output).context("Failed to parse output of parser module")
+}
+
+/// Reads the rows of a file parsed by a wasm module
+pub struct WasmReader {
+    structure: FileStructure,
+    /// The columns of the parsed rows
+    cols: Vec<String>,
+    /// The parsed rows which have not been read
+    rows: VecDeque<Vec<JsonValue>>,
+    /// The instance of the transform module, if any
+    transform: Option<WasmInstance>,
+}
+
+impl WasmReader {
+    fn new(conf: &WasmFileConfig, structure: &FileStructure, path: &Path) -> Result<Self> {
+        let parsed = parse(conf, path)?;
+        let transform = match &conf.transform {
+            Some(transform) => Some(transform.instantiate()?),
+            None => None,
+        };
+
+        Ok(Self {
+            structure: structure.clone(),
+            cols: parsed.columns.into_iter().map(|c| c.name).collect(),
+            rows: parsed.rows.into(),
+            transform,
+        })
+    }
+
+    /// Applies the transform module to the row
+    fn transform(&mut self, row: Map<String, JsonValue>) -> Result<Option<Map<String, JsonValue>>> {
+        let transform = match self.transform.as_mut() {
+            Some(t) => t,
+            None => return Ok(Some(row)),
+        };
+
+        let output = transform.call(TRANSFORM_EXPORT, &serde_json::to_vec(&row)?)?;
+
+        match serde_json::from_slice(&output)
+            .context("Failed to parse row returned from transform module")?
+        {
+            JsonValue::Object(row) => Ok(Some(row)),
+            JsonValue::Null => Ok(None),
+            other => bail!("Transform module returned unexpected row: {}", other),
+        }
+    }
+}
+
+impl FileReader for WasmReader {
+    fn read_row(&mut self) -> Result<Option<Vec<DataValue>>> {
+        while let Some(values) = self.rows.pop_front() {
+            let row = self
+                .cols
+                .iter()
+                .cloned()
+                .zip(values.into_iter())
+                .collect::<Map<_, _>>();
+
+            let mut row = match self.transform(row)? {
+                Some(row) => row,
+                None => continue,
+            };
+
+            let mut output = vec![];
+            for col in self.structure.cols.iter() {
+                let val = row.remove(&col.name).unwrap_or(JsonValue::Null);
+                let val = from_json_value(val, &col.r#type)
+                    .with_context(|| format!("Parsing column '{}'", col.name))?;
+
+                output.push(val);
+            }
+
+            return Ok(Some(output));
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use ansilo_connectors_file_base::FileColumn;
+    use ansilo_core::data::{DataType, StringOptions};
+    use pretty_assertions::assert_eq;
+
+    use crate::{test::*, WasmConfig, WasmModule};
+
+    use super::*;
+
+    const PARSED: &str = r#"{
+        "columns": [
+            {"name": "id", "type": "Int32", "nullable": false},
+            {"name": "name", "type": "Utf8String"}
+        ],
+        "rows": [[1, "John"], [2, null]]
+    }"#;
+
+    fn mock_conf(transform: Option<WasmModule>) -> (WasmFileConfig, tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("people.dat");
+        fs::write(&path, b"proprietary data").unwrap();
+
+        let conf = WasmFileConfig::new(
+            WasmConfig::new(dir.path().into(), "parser.wasm".into()),
+            const_module(PARSE_EXPORT, PARSED),
+            transform,
+        );
+
+        (conf, dir, path)
+    }
+
+    fn read_all(conf: &WasmFileConfig, path: &Path) -> Vec<Vec<DataValue>> {
+        let structure = WasmIO::get_structure(conf, path).unwrap();
+        let mut reader = WasmIO::reader(conf, &structure, path).unwrap();
+        let mut rows = vec![];
+
+        while let Some(row) = reader.read_row().unwrap() {
+            rows.push(row);
+        }
+
+        rows
+    }
+
+    #[test]
+    fn test_wasm_io_get_structure() {
+        let (conf, _dir, path) = mock_conf(None);
+
+        assert_eq!(
+            WasmIO::get_structure(&conf, &path).unwrap(),
+            FileStructure::new(
+                vec![
+                    FileColumn::new("id".into(), DataType::Int32, false, None),
+                    FileColumn::new(
+                        "name".into(),
+                        DataType::Utf8String(StringOptions::default()),
+                        true,
+                        None
+                    ),
+                ],
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn test_wasm_io_read_rows() {
+        let (conf, _dir, path) = mock_conf(None);
+
+        assert_eq!(
+            read_all(&conf, &path),
+            vec![
+                vec![DataValue::Int32(1), DataValue::Utf8String("John".into())],
+                vec![DataValue::Int32(2), DataValue::Null],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wasm_io_read_rows_with_transform() {
+        let (conf, _dir, path) = mock_conf(Some(echo_module()));
+
+        assert_eq!(
+            read_all(&conf, &path),
+            vec![
+                vec![DataValue::Int32(1), DataValue::Utf8String("John".into())],
+                vec![DataValue::Int32(2), DataValue::Null],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wasm_io_transform_skips_rows() {
+        let (conf, _dir, path) = mock_conf(Some(const_module(TRANSFORM_EXPORT, "null")));
+
+        assert_eq!(read_all(&conf, &path), Vec::<Vec<DataValue>>::new());
+    }
+
+    #[test]
+    fn test_wasm_io_transform_columns() {
+        let (conf, _dir, path) = mock_conf(Some(const_module(
+            TRANSFORM_COLUMNS_EXPORT,
+            r#"[{"name": "id", "type": "Int64"}]"#,
+        )));
+
+        assert_eq!(
+            WasmIO::get_structure(&conf, &path).unwrap(),
+            FileStructure::new(
+                vec![FileColumn::new("id".into(), DataType::Int64, true, None)],
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn test_wasm_io_invalid_parser_output() {
+        let (mut conf, _dir, path) = mock_conf(None);
+        conf.parser = const_module(PARSE_EXPORT, "not json");
+
+        WasmIO::get_structure(&conf, &path).unwrap_err();
+    }
+
+    #[test]
+    fn test_wasm_io_writing_not_supported() {
+        let (conf, _dir, path) = mock_conf(None);
+
+        assert!(!WasmIO::supports_writing(&conf, &path).unwrap());
+        assert!(!WasmIO::supports_truncating(&conf, &path).unwrap());
+    }
+}
@@ -0,0 +1,57 @@
+use ansilo_connectors_base::{common::entity::ConnectorEntityConfig, interface::Connector};
+use ansilo_connectors_file_base::{
+    FileConnection, FileConnectionUnpool, FileEntitySearcher, FileEntityValidator, FileQuery,
+    FileQueryCompiler, FileQueryHandle, FileQueryPlanner, FileResultSet, FileSourceConfig,
+};
+use ansilo_core::{
+    config::{self, NodeConfig},
+    err::Result,
+};
+
+mod conf;
+pub mod data;
+pub use conf::*;
+mod io;
+pub use io::*;
+mod sandbox;
+pub use sandbox::*;
+
+#[cfg(test)]
+pub(crate) mod test;
+
+/// The connector for files parsed by a sandboxed WebAssembly module
+#[derive(Default)]
+pub struct WasmConnector;
+
+impl Connector for WasmConnector {
+    type TConnectionPool = FileConnectionUnpool<WasmIO>;
+    type TConnection = FileConnection<WasmIO>;
+    type TConnectionConfig = WasmConfig;
+    type TEntitySearcher = FileEntitySearcher<WasmIO>;
+    type TEntityValidator = FileEntityValidator<WasmIO>;
+    type TEntitySourceConfig = FileSourceConfig;
+    type TQueryPlanner = FileQueryPlanner<WasmIO>;
+    type TQueryCompiler = FileQueryCompiler<WasmIO>;
+    type TQueryHandle = FileQueryHandle<WasmIO>;
+    type TQuery = FileQuery;
+    type TResultSet = FileResultSet<WasmReader>;
+    type TTransactionManager = ();
+
+    const TYPE: &'static str = "file.wasm";
+
+    fn parse_options(options: config::Value) -> Result<Self::TConnectionConfig> {
+        WasmConfig::parse(options)
+    }
+
+    fn parse_entity_source_options(options: config::Value) -> Result<Self::TEntitySourceConfig> {
+        FileSourceConfig::parse(options)
+    }
+
+    fn create_connection_pool(
+        conf: WasmConfig,
+        _nc: &NodeConfig,
+        _entities: &ConnectorEntityConfig<Self::TEntitySourceConfig>,
+    ) -> Result<Self::TConnectionPool> {
+        Ok(FileConnectionUnpool::new(WasmFileConfig::load(conf)?))
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use ansilo_core::err::{bail, Context, Error, Result};
+use wasmi::{
+    Config, Engine, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+    TypedFunc,
+};
+
+use crate::WasmLimits;
+
+/// The memory of the module, used to pass the input and output of calls
+const MEMORY_EXPORT: &str = "memory";
+
+/// Allocates a buffer in the memory of the module for the input of a call
+const ALLOC_EXPORT: &str = "ansilo_alloc";
+
+/// Frees a buffer returned from a call, if exported by the module
+const FREE_EXPORT: &str = "ansilo_free";
+
+/// A compiled WebAssembly module.
+///
+/// Modules are executed in a sandbox: they are not able to import any host functions
+/// so have no access to the filesystem, network or environment of ansilo and each
+/// call into the module is limited in the fuel and memory it can consume.
+#[derive(Clone)]
+pub struct WasmModule {
+    path: PathBuf,
+    engine: Engine,
+    module: Module,
+    limits: WasmLimits,
+}
+
+impl WasmModule {
+    /// Loads the module from the supplied path
+    pub fn load(path: &Path, limits: WasmLimits) -> Result<Self> {
+        let wasm = fs::read(path)
+            .with_context(|| format!("Failed to read wasm module {}", path.display()))?;
+
+        Self::new(path, &wasm, limits)
+    }
+
+    /// Compiles the supplied module
+    pub fn new(path: &Path, wasm: &[u8], limits: WasmLimits) -> Result<Self> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+
+        let module = Module::new(&engine, wasm)
+            .map_err(wasm_err)
+            .with_context(|| format!("Failed to compile wasm module {}", path.display()))?;
+
+        if let Some(import) = module.imports().next() {
+            bail!(
+                "Wasm module {} imports '{}.{}' but modules cannot import host functions",
+                path.display(),
+                import.module(),
+                import.name()
+            );
+        }
+
+        Ok(Self {
+            path: path.into(),
+            engine,
+            module,
+            limits,
+        })
+    }
+
+    /// Gets the path of the module
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Whether the module exports the supplied function
+    pub fn exports(&self, name: &str) -> bool {
+        self.module.exports().any(|e| e.name() == name)
+    }
+
+    /// Creates a new instance of the module, the state of each instance is isolated
+    pub fn instantiate(&self) -> Result<WasmInstance> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.limits.memory_bytes())
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.add_fuel(self.limits.fuel()).map_err(wasm_err)?;
+
+        let instance = Linker::<StoreLimits>::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .map_err(wasm_err)?
+            .start(&mut store)
+            .map_err(wasm_err)
+            .with_context(|| {
+                format!("Failed to instantiate wasm module {}", self.path.display())
+            })?;
+
+        let memory = instance
+            .get_memory(&store, MEMORY_EXPORT)
+            .with_context(|| format!("Wasm module must export '{}'", MEMORY_EXPORT))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&store, ALLOC_EXPORT)
+            .map_err(wasm_err)
+            .with_context(|| format!("Wasm module must export '{}'", ALLOC_EXPORT))?;
+        let free = instance
+            .get_typed_func::<(i32, i32), ()>(&store, FREE_EXPORT)
+            .ok();
+
+        Ok(WasmInstance {
+            fuel: self.limits.fuel(),
+            store,
+            instance,
+            memory,
+            alloc,
+            free,
+        })
+    }
+}
+
+/// An instance of a module
+pub struct WasmInstance {
+    fuel: u64,
+    store: Store<StoreLimits>,
+    instance: Instance,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    free: Option<TypedFunc<(i32, i32), ()>>,
+}
+
+impl WasmInstance {
+    /// Calls the exported function with the supplied input, returning its output.
+    ///
+    /// Functions take the pointer and length of the input buffer, allocated using
+    /// `ansilo_alloc`, and return the pointer and length of the output buffer packed
+    /// into an i64 as `(ptr << 32) | len`.
+    pub fn call(&mut self, export: &str, input: &[u8]) -> Result<Vec<u8>> {
+        self.refuel()?;
+
+        let func = self
+            .instance
+            .get_typed_func::<(i32, i32), i64>(&self.store, export)
+            .map_err(wasm_err)
+            .with_context(|| format!("Wasm module does not export function '{}'", export))?;
+
+        let len = i32::try_from(input.len()).context("Input is too large for wasm module")?;
+        let ptr = self
+            .alloc
+            .call(&mut self.store, len)
+            .map_err(wasm_err)
+            .context("Failed to allocate memory in wasm module")?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, input)
+            .map_err(wasm_err)
+            .context("Wasm module allocated an invalid input buffer")?;
+
+        let res = func
+            .call(&mut self.store, (ptr, len))
+            .map_err(wasm_err)
+            .with_context(|| format!("Call to wasm function '{}' failed", export))?;
+
+        let (ptr, len) = (
+            (res as u64 >> 32) as usize,
+            (res as u64 & 0xffff_ffff) as usize,
+        );
+        let output = self
+            .memory
+            .data(&self.store)
+            .get(ptr..(ptr + len))
+            .context("Wasm module returned an invalid output buffer")?
+            .to_vec();
+
+        if let Some(free) = &self.free {
+            free.call(&mut self.store, (ptr as i32, len as i32))
+                .map_err(wasm_err)
+                .context("Failed to free memory in wasm module")?;
+        }
+
+        Ok(output)
+    }
+
+    /// Resets the fuel available to the instance so each call is limited independently
+    fn refuel(&mut self) -> Result<()> {
+        let remaining = self.store.consume_fuel(0).map_err(wasm_err)?;
+        self.store
+            .add_fuel(self.fuel.saturating_sub(remaining))
+            .map_err(wasm_err)
+    }
+}
+
+fn wasm_err(err: impl fmt::Display) -> Error {
+    Error::msg(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::*;
+
+    use super::*;
+
+    #[test]
+    fn test_wasm_module_call() {
+        let mut instance = echo_module().instantiate().unwrap();
+
+        assert_eq!(instance.call("echo", b"hello").unwrap(), b"hello".to_vec());
+        assert_eq!(instance.call("echo", b"world").unwrap(), b"world".to_vec());
+        assert_eq!(instance.call("echo", b"").unwrap(), b"".to_vec());
+    }
+
+    #[test]
+    fn test_wasm_module_exports() {
+        let module = echo_module();
+
+        assert!(module.exports("echo"));
+        assert!(!module.exports("unknown"));
+    }
+
+    #[test]
+    fn test_wasm_module_call_unknown_export() {
+        let mut instance = echo_module().instantiate().unwrap();
+
+        instance.call("unknown", b"hello").unwrap_err();
+    }
+
+    #[test]
+    fn test_wasm_module_trap_is_returned_as_error() {
+        let mut instance = echo_module().instantiate().unwrap();
+
+        instance.call("trap", b"hello").unwrap_err();
+        // The instance can still be used after a trap
+        assert_eq!(instance.call("echo", b"hello").unwrap(), b"hello".to_vec());
+    }
+
+    #[test]
+    fn test_wasm_module_fuel_is_limited() {
+        let module = WasmModule::new(
+            Path::new("echo.wasm"),
+            &wat::parse_str(ECHO_WAT).unwrap(),
+            WasmLimits {
+                fuel: Some(10_000),
+                memory_mb: None,
+            },
+        )
+        .unwrap();
+        let mut instance = module.instantiate().unwrap();
+
+        instance.call("spin", b"hello").unwrap_err();
+        // Fuel is reset between calls
+        assert_eq!(instance.call("echo", b"hello").unwrap(), b"hello".to_vec());
+    }
+
+    #[test]
+    fn test_wasm_module_invalid_output() {
+        let mut instance = echo_module().instantiate().unwrap();
+
+        instance.call("invalid", b"hello").unwrap_err();
+    }
+
+    #[test]
+    fn test_wasm_module_imports_are_rejected() {
+        WasmModule::new(
+            Path::new("imports.wasm"),
+            &wat::parse_str(r#"(module (import "env" "read_file" (func)))"#).unwrap(),
+            WasmLimits::default(),
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn test_wasm_module_invalid_module() {
+        WasmModule::new(
+            Path::new("invalid.wasm"),
+            b"not a wasm module",
+            WasmLimits::default(),
+        )
+        .unwrap_err();
+    }
+}
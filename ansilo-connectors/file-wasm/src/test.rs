@@ -0,0 +1,63 @@
+use std::path::Path;
+
+use crate::{WasmLimits, WasmModule};
+
+/// A module which echoes its input
+pub const ECHO_WAT: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (func (export "ansilo_alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  (func $echo (export "echo") (param $ptr i32) (param $len i32) (result i64)
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+      (i64.extend_i32_u (local.get $len))))
+  (func (export "ansilo_transform") (param $ptr i32) (param $len i32) (result i64)
+    (call $echo (local.get $ptr) (local.get $len)))
+  (func (export "trap") (param i32 i32) (result i64)
+    unreachable)
+  (func (export "spin") (param i32 i32) (result i64)
+    (loop $l (br $l))
+    (i64.const 0))
+  (func (export "invalid") (param i32 i32) (result i64)
+    (i64.const -1))
+)
+"#;
+
+pub fn echo_module() -> WasmModule {
+    WasmModule::new(
+        Path::new("echo.wasm"),
+        &wat::parse_str(ECHO_WAT).unwrap(),
+        WasmLimits::default(),
+    )
+    .unwrap()
+}
+
+/// Creates a module where the supplied export returns a constant output
+pub fn const_module(export: &str, output: &str) -> WasmModule {
+    let wat = format!(
+        r#"
+(module
+  (memory (export "memory") 1)
+  (data (i32.const 16) "{data}")
+  (func (export "ansilo_alloc") (param i32) (result i32)
+    (i32.const 4096))
+  (func (export "{export}") (param i32 i32) (result i64)
+    (i64.const {res}))
+)
+"#,
+        data = output.replace('\\', "\\\\").replace('"', "\\\""),
+        res = (16i64 << 32) | output.len() as i64
+    );
+
+    WasmModule::new(
+        Path::new("const.wasm"),
+        &wat::parse_str(wat).unwrap(),
+        WasmLimits::default(),
+    )
+    .unwrap()
+}
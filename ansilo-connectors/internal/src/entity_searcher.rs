@@ -60,6 +60,19 @@ impl EntitySearcher for InternalEntitySearcher {
                 vec![],
                 EntitySourceConfig::minimal(""),
             ),
+            EntityConfig::new(
+                "classifications".into(),
+                Some("Classifications".into()),
+                Some("The data classifications of the entities and their attributes".into()),
+                vec![],
+                vec![
+                    EntityAttributeConfig::nullable("entity_id", DataType::rust_string()),
+                    EntityAttributeConfig::nullable("attribute_id", DataType::rust_string()),
+                    EntityAttributeConfig::nullable("classification", DataType::rust_string()),
+                ],
+                vec![],
+                EntitySourceConfig::minimal(""),
+            ),
         ])
     }
 }
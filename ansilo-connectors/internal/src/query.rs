@@ -22,6 +22,7 @@ pub enum InternalQueryType {
     Job(Vec<(String, JobColumn)>),
     JobTrigger(Vec<(String, JobTriggerColumn)>),
     ServiceUser(Vec<(String, ServiceUserColumn)>),
+    Classification(Vec<(String, ClassificationColumn)>),
 }
 
 #[derive(Clone, Copy, Debug, Serialize)]
@@ -86,6 +87,26 @@ impl FromStr for ServiceUserColumn {
     }
 }
 
+#[derive(Clone, Copy, Debug, Serialize)]
+pub enum ClassificationColumn {
+    EntityId,
+    AttributeId,
+    Classification,
+}
+
+impl FromStr for ClassificationColumn {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "entity_id" => Self::EntityId,
+            "attribute_id" => Self::AttributeId,
+            "classification" => Self::Classification,
+            _ => bail!("Unsupported"),
+        })
+    }
+}
+
 impl QueryHandle for InternalQuery {
     type TResultSet = InternalResultSet;
 
@@ -147,6 +168,34 @@ impl QueryHandle for InternalQuery {
                     })
                 })
                 .collect(),
+            InternalQueryType::Classification(cols) => self
+                .nc
+                .entities
+                .iter()
+                .flat_map(|entity| {
+                    let entity_classifications = entity
+                        .classifications
+                        .iter()
+                        .map(move |c| (entity, None, c));
+                    let attribute_classifications =
+                        entity.attributes.iter().flat_map(move |attr| {
+                            attr.classifications
+                                .iter()
+                                .map(move |c| (entity, Some(attr), c))
+                        });
+
+                    entity_classifications.chain(attribute_classifications)
+                })
+                .flat_map(|(entity, attr, classification)| {
+                    cols.iter()
+                        .map(|(_, c)| match c {
+                            ClassificationColumn::EntityId => Some(entity.id.clone()),
+                            ClassificationColumn::AttributeId => attr.map(|a| a.id.clone()),
+                            ClassificationColumn::Classification => Some(classification.clone()),
+                        })
+                        .collect_vec()
+                })
+                .collect(),
         };
 
         let cols: Vec<_> = match &self.query {
@@ -162,6 +211,10 @@ impl QueryHandle for InternalQuery {
                 .iter()
                 .map(|(a, _)| (a.clone(), DataType::rust_string()))
                 .collect(),
+            InternalQueryType::Classification(cols) => cols
+                .iter()
+                .map(|(a, _)| (a.clone(), DataType::rust_string()))
+                .collect(),
         };
 
         let data = data
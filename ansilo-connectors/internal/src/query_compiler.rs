@@ -30,6 +30,7 @@ impl QueryCompiler for InternalQueryCompiler {
             "jobs" => InternalQueryType::Job(parse_cols(select.cols)?),
             "job_triggers" => InternalQueryType::JobTrigger(parse_cols(select.cols)?),
             "service_users" => InternalQueryType::ServiceUser(parse_cols(select.cols)?),
+            "classifications" => InternalQueryType::Classification(parse_cols(select.cols)?),
             _ => bail!("Unsupported"),
         };
 
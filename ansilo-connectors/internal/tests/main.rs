@@ -23,6 +23,9 @@ fn test_internal_select_jobs() {
         description: None,
         service_user: None,
         sql: "SQL".into(),
+        catalog: None,
+        peer_import: None,
+        steps: vec![],
         triggers: vec![],
     });
 
@@ -32,6 +35,9 @@ fn test_internal_select_jobs() {
         description: None,
         service_user: None,
         sql: "SQL".into(),
+        catalog: None,
+        peer_import: None,
+        steps: vec![],
         triggers: vec![],
     });
 
@@ -92,6 +98,9 @@ fn test_internal_select_job_triggers() {
         description: None,
         service_user: None,
         sql: "SQL".into(),
+        catalog: None,
+        peer_import: None,
+        steps: vec![],
         triggers: vec![
             JobTriggerConfig::Cron(CronTriggerConfig {
                 cron: "cron 1".into(),
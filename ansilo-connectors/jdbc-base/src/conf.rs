@@ -1,5 +1,6 @@
 use std::{collections::HashMap, time::Duration};
 
+use ansilo_core::err::Result;
 use serde::{Deserialize, Serialize};
 
 /// JDBC connection config
@@ -8,7 +9,7 @@ pub trait JdbcConnectionConfig: Send + Sync + Clone {
     fn get_jdbc_url(&self) -> String;
 
     /// Gets the connection props
-    fn get_jdbc_props(&self) -> HashMap<String, String>;
+    fn get_jdbc_props(&self) -> Result<HashMap<String, String>>;
 
     /// Gets the connection pool config
     fn get_pool_config(&self) -> Option<JdbcConnectionPoolConfig>;
@@ -37,7 +38,8 @@ pub trait JdbcConnectionConfig: Send + Sync + Clone {
 /// Options for pooling the JDBC connections
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct JdbcConnectionPoolConfig {
-    /// Minimum number of connections
+    /// Minimum number of connections, these are established when
+    /// the pool is created and replaced as they are closed
     pub min_cons: u32,
     /// Maximum number of connections
     pub max_cons: u32,
@@ -47,4 +49,6 @@ pub struct JdbcConnectionPoolConfig {
     pub idle_timeout: Option<Duration>,
     /// Maximum connection timeout
     pub connect_timeout: Option<Duration>,
+    /// How often idle connections are validated to keep them alive
+    pub keepalive_interval: Option<Duration>,
 }
@@ -1,6 +1,7 @@
 use std::{
     collections::HashMap,
     sync::{Arc, Mutex, MutexGuard},
+    thread,
     time::Duration,
 };
 
@@ -28,6 +29,8 @@ use super::{JdbcConnectionConfig, JdbcPreparedQuery, JdbcQuery, Jvm};
 #[derive(Clone)]
 pub struct JdbcConnectionPool {
     pool: r2d2::Pool<R2d2Adaptor<Manager>>,
+    /// Once all clones are dropped the keepalive thread will stop
+    _keepalive: Option<Arc<()>>,
 }
 
 struct Manager {
@@ -49,7 +52,7 @@ impl JdbcConnectionPool {
         let manager = Manager {
             jvm: Arc::new(jvm),
             jdbc_url: options.get_jdbc_url(),
-            jdbc_props: options.get_jdbc_props(),
+            jdbc_props: options.get_jdbc_props()?,
             init_queries: options.get_initialisation_queries(),
             connection_class: options.get_java_connection().replace('.', "/"),
             data_mapping_class: options.get_java_jdbc_data_mapping().replace('.', "/"),
@@ -57,7 +60,10 @@ impl JdbcConnectionPool {
         }
         .adaptor();
 
-        let pool = if let Some(conf) = options.get_pool_config().as_ref() {
+        let pool_conf = options.get_pool_config();
+        let pool = if let Some(conf) = pool_conf.as_ref() {
+            // The pool establishes the minimum number of connections upfront
+            // so the first queries do not pay the connection cost
             r2d2::Builder::new()
                 .min_idle(Some(conf.min_cons))
                 .max_size(conf.max_cons)
@@ -76,7 +82,37 @@ impl JdbcConnectionPool {
                 .context("Failed to build connection pool")?
         };
 
-        Ok(Self { pool })
+        let keepalive = pool_conf
+            .and_then(|conf| conf.keepalive_interval)
+            .map(|interval| Self::keep_alive(pool.clone(), interval));
+
+        Ok(Self {
+            pool,
+            _keepalive: keepalive,
+        })
+    }
+
+    /// Periodically validates the idle connections in the pool so they are not
+    /// closed by the remote server or intermediate firewalls while unused.
+    /// Connections which fail validation are discarded and replaced by the pool.
+    fn keep_alive(pool: r2d2::Pool<R2d2Adaptor<Manager>>, interval: Duration) -> Arc<()> {
+        let token = Arc::new(());
+        let weak = Arc::downgrade(&token);
+
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+
+            if weak.upgrade().is_none() {
+                return;
+            }
+
+            // Connections are validated as they are checked out of the pool
+            let idle = pool.state().idle_connections;
+            let validated = (0..idle).filter_map(|_| pool.try_get()).collect::<Vec<_>>();
+            debug!("Validated {} idle JDBC connections", validated.len());
+        });
+
+        token
     }
 }
 
@@ -419,19 +455,23 @@ mod tests {
     use super::*;
 
     #[derive(Clone)]
-    struct MockSqliteJdbcConnectionConfig(String, HashMap<String, String>);
+    struct MockSqliteJdbcConnectionConfig(
+        String,
+        HashMap<String, String>,
+        Option<JdbcConnectionPoolConfig>,
+    );
 
     impl JdbcConnectionConfig for MockSqliteJdbcConnectionConfig {
         fn get_jdbc_url(&self) -> String {
             self.0.clone()
         }
 
-        fn get_jdbc_props(&self) -> HashMap<String, String> {
-            self.1.clone()
+        fn get_jdbc_props(&self) -> Result<HashMap<String, String>> {
+            Ok(self.1.clone())
         }
 
         fn get_pool_config(&self) -> Option<JdbcConnectionPoolConfig> {
-            None
+            self.2.clone()
         }
 
         fn get_java_jdbc_data_mapping(&self) -> String {
@@ -442,7 +482,7 @@ mod tests {
     fn init_sqlite_connection() -> JdbcConnection {
         JdbcConnectionPool::new(
             &ResourceConfig::default(),
-            MockSqliteJdbcConnectionConfig("jdbc:sqlite::memory:".to_owned(), HashMap::new()),
+            MockSqliteJdbcConnectionConfig("jdbc:sqlite::memory:".to_owned(), HashMap::new(), None),
         )
         .unwrap()
         .acquire(None)
@@ -458,7 +498,7 @@ mod tests {
     fn test_jdbc_connection_init_invalid() {
         let res = JdbcConnectionPool::new(
             &ResourceConfig::default(),
-            MockSqliteJdbcConnectionConfig("invalid".to_owned(), HashMap::new()),
+            MockSqliteJdbcConnectionConfig("invalid".to_owned(), HashMap::new(), None),
         )
         .unwrap()
         .acquire(None);
@@ -466,6 +506,33 @@ mod tests {
         assert!(res.is_err());
     }
 
+    #[test]
+    fn test_jdbc_connection_pool_min_cons_and_keepalive() {
+        let pool = JdbcConnectionPool::new(
+            &ResourceConfig::default(),
+            MockSqliteJdbcConnectionConfig(
+                "jdbc:sqlite::memory:".to_owned(),
+                HashMap::new(),
+                Some(JdbcConnectionPoolConfig {
+                    min_cons: 2,
+                    max_cons: 5,
+                    max_lifetime: None,
+                    idle_timeout: None,
+                    connect_timeout: None,
+                    keepalive_interval: Some(Duration::from_millis(10)),
+                }),
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(pool.pool.state().connections, 2);
+
+        std::thread::sleep(Duration::from_millis(50));
+
+        assert_eq!(pool.pool.state().connections, 2);
+        assert_eq!(pool.pool.state().idle_connections, 2);
+    }
+
     #[test]
     fn test_jdbc_connection_prepare_statement() {
         let mut con = init_sqlite_connection();
@@ -9,6 +9,7 @@ import java.util.Properties;
 import com.ansilo.connectors.mapping.JdbcDataMapping;
 import com.ansilo.connectors.query.JdbcParameter;
 import com.ansilo.connectors.query.JdbcPreparedQuery;
+import com.ansilo.connectors.tls.PemTrustStore;
 
 /**
  * The JDBC Connection wrapper class.
@@ -36,7 +37,7 @@ public class JdbcConnection {
     public JdbcConnection(String jdbcUrl, Properties jdbcProps, JdbcDataMapping mapping)
             throws SQLException {
         // TODO: logging
-        this(DriverManager.getConnection(jdbcUrl, jdbcProps), mapping);
+        this(DriverManager.getConnection(jdbcUrl, PemTrustStore.apply(jdbcProps)), mapping);
     }
 
     /**
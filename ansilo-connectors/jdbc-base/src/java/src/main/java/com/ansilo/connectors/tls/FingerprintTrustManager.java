@@ -0,0 +1,63 @@
+package com.ansilo.connectors.tls;
+
+import java.security.MessageDigest;
+import java.security.NoSuchAlgorithmException;
+import java.security.cert.CertificateException;
+import java.security.cert.X509Certificate;
+import java.util.HexFormat;
+import javax.net.ssl.X509TrustManager;
+
+/**
+ * Trust manager which only accepts the server certificate with the pinned SHA-256 fingerprint.
+ *
+ * The certificate chain is not otherwise verified, so this supports self-signed certificates.
+ */
+public class FingerprintTrustManager implements X509TrustManager {
+    /**
+     * The pinned SHA-256 digest of the DER-encoded certificate
+     */
+    private final byte[] fingerprint;
+
+    /**
+     * @param fingerprint The SHA-256 fingerprint as hex, optionally colon-separated
+     */
+    public FingerprintTrustManager(String fingerprint) {
+        this.fingerprint = HexFormat.of().parseHex(fingerprint.trim().replace(":", ""));
+
+        if (this.fingerprint.length != 32) {
+            throw new IllegalArgumentException(
+                    "Certificate fingerprint must be a SHA-256 digest");
+        }
+    }
+
+    @Override
+    public void checkClientTrusted(X509Certificate[] chain, String authType)
+            throws CertificateException {
+        throw new CertificateException("Client certificates are not trusted");
+    }
+
+    @Override
+    public void checkServerTrusted(X509Certificate[] chain, String authType)
+            throws CertificateException {
+        if (chain == null || chain.length == 0) {
+            throw new CertificateException("Server did not present a certificate");
+        }
+
+        byte[] actual;
+        try {
+            actual = MessageDigest.getInstance("SHA-256").digest(chain[0].getEncoded());
+        } catch (NoSuchAlgorithmException e) {
+            throw new CertificateException(e);
+        }
+
+        if (!MessageDigest.isEqual(actual, this.fingerprint)) {
+            throw new CertificateException(
+                    "Server certificate fingerprint does not match the pinned fingerprint");
+        }
+    }
+
+    @Override
+    public X509Certificate[] getAcceptedIssuers() {
+        return new X509Certificate[0];
+    }
+}
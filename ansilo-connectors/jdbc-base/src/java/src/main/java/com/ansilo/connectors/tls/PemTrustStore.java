@@ -0,0 +1,113 @@
+package com.ansilo.connectors.tls;
+
+import java.io.IOException;
+import java.nio.file.Files;
+import java.nio.file.Path;
+import java.security.GeneralSecurityException;
+import java.security.KeyStore;
+import java.security.cert.CertificateFactory;
+import java.sql.SQLException;
+import java.util.Map;
+import java.util.Properties;
+import java.util.UUID;
+import java.util.concurrent.ConcurrentHashMap;
+
+/**
+ * Converts a PEM CA bundle into a PKCS12 trust store.
+ *
+ * Most JDBC drivers only accept trust stores in JKS or PKCS12 format, so we generate a trust
+ * store from the configured bundle and substitute its path and password into the driver-specific
+ * connection properties.
+ */
+public class PemTrustStore {
+    public static final String CA_BUNDLE_PROP = "ansilo.tls.caBundle";
+    public static final String TRUST_STORE_PATH = "${ansilo.tls.trustStore}";
+    public static final String TRUST_STORE_PASSWORD = "${ansilo.tls.trustStorePassword}";
+
+    /**
+     * Generated trust stores, keyed by the path of the CA bundle
+     */
+    private static final Map<String, PemTrustStore> stores = new ConcurrentHashMap<>();
+
+    /**
+     * The path of the generated trust store
+     */
+    private final Path path;
+
+    /**
+     * The random password of the generated trust store
+     */
+    private final String password;
+
+    private PemTrustStore(Path path, String password) {
+        this.path = path;
+        this.password = password;
+    }
+
+    /**
+     * Returns a copy of the properties with the CA bundle converted into a trust store, if
+     * configured.
+     */
+    public static Properties apply(Properties props) throws SQLException {
+        var caBundle = props.getProperty(CA_BUNDLE_PROP);
+
+        if (caBundle == null) {
+            return props;
+        }
+
+        PemTrustStore store;
+        try {
+            store = stores.computeIfAbsent(caBundle, path -> {
+                try {
+                    return PemTrustStore.create(Path.of(path));
+                } catch (IOException | GeneralSecurityException e) {
+                    throw new RuntimeException(e);
+                }
+            });
+        } catch (RuntimeException e) {
+            throw new SQLException("Failed to load CA bundle from " + caBundle, e.getCause());
+        }
+
+        var result = new Properties();
+        for (var key : props.stringPropertyNames()) {
+            if (key.equals(CA_BUNDLE_PROP)) {
+                continue;
+            }
+
+            result.setProperty(key,
+                    props.getProperty(key).replace(TRUST_STORE_PATH, store.path.toString())
+                            .replace(TRUST_STORE_PASSWORD, store.password));
+        }
+
+        return result;
+    }
+
+    /**
+     * Creates a PKCS12 trust store containing each certificate in the PEM bundle
+     */
+    static PemTrustStore create(Path caBundle) throws IOException, GeneralSecurityException {
+        var password = UUID.randomUUID().toString();
+        var keyStore = KeyStore.getInstance("PKCS12");
+        keyStore.load(null, password.toCharArray());
+
+        try (var in = Files.newInputStream(caBundle)) {
+            var i = 0;
+            for (var cert : CertificateFactory.getInstance("X.509").generateCertificates(in)) {
+                keyStore.setCertificateEntry("ca-" + i++, cert);
+            }
+        }
+
+        if (keyStore.size() == 0) {
+            throw new GeneralSecurityException("No certificates found in CA bundle");
+        }
+
+        var path = Files.createTempFile("ansilo-truststore-", ".p12");
+        path.toFile().deleteOnExit();
+
+        try (var out = Files.newOutputStream(path)) {
+            keyStore.store(out, password.toCharArray());
+        }
+
+        return new PemTrustStore(path, password);
+    }
+}
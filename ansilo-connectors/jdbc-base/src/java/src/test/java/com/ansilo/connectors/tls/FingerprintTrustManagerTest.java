@@ -0,0 +1,43 @@
+package com.ansilo.connectors.tls;
+
+import static org.junit.jupiter.api.Assertions.assertThrows;
+import java.io.ByteArrayInputStream;
+import java.nio.charset.StandardCharsets;
+import java.security.cert.CertificateException;
+import java.security.cert.CertificateFactory;
+import java.security.cert.X509Certificate;
+import org.junit.jupiter.api.Test;
+
+public class FingerprintTrustManagerTest {
+    static final String FINGERPRINT =
+            "19:74:AD:25:7F:32:C7:39:B8:E7:74:50:65:DF:05:BB:7E:33:41:20:82:E9:52:E0:5C:EB:84:A1:D0:06:4E:10";
+
+    private X509Certificate[] chain() throws Exception {
+        var cert = CertificateFactory.getInstance("X.509").generateCertificate(
+                new ByteArrayInputStream(PemTrustStoreTest.CA_CERT.getBytes(StandardCharsets.UTF_8)));
+
+        return new X509Certificate[] {(X509Certificate) cert};
+    }
+
+    @Test
+    void testMatchingFingerprint() throws Exception {
+        new FingerprintTrustManager(FINGERPRINT).checkServerTrusted(chain(), "RSA");
+        new FingerprintTrustManager(FINGERPRINT.replace(":", "").toLowerCase())
+                .checkServerTrusted(chain(), "RSA");
+    }
+
+    @Test
+    void testMismatchedFingerprint() throws Exception {
+        var manager = new FingerprintTrustManager(FINGERPRINT.replace("19:74", "00:00"));
+
+        assertThrows(CertificateException.class, () -> manager.checkServerTrusted(chain(), "RSA"));
+        assertThrows(CertificateException.class,
+                () -> manager.checkServerTrusted(new X509Certificate[0], "RSA"));
+    }
+
+    @Test
+    void testInvalidFingerprint() throws Exception {
+        assertThrows(IllegalArgumentException.class, () -> new FingerprintTrustManager("AB:CD"));
+        assertThrows(IllegalArgumentException.class, () -> new FingerprintTrustManager("invalid"));
+    }
+}
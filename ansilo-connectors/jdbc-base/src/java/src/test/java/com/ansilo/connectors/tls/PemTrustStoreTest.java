@@ -0,0 +1,87 @@
+package com.ansilo.connectors.tls;
+
+import static org.junit.jupiter.api.Assertions.assertEquals;
+import static org.junit.jupiter.api.Assertions.assertFalse;
+import static org.junit.jupiter.api.Assertions.assertNotEquals;
+import static org.junit.jupiter.api.Assertions.assertSame;
+import static org.junit.jupiter.api.Assertions.assertThrows;
+import static org.junit.jupiter.api.Assertions.assertTrue;
+import java.io.FileInputStream;
+import java.nio.file.Files;
+import java.nio.file.Path;
+import java.security.KeyStore;
+import java.sql.SQLException;
+import java.util.Properties;
+import org.junit.jupiter.api.Test;
+
+public class PemTrustStoreTest {
+    static final String CA_CERT = "-----BEGIN CERTIFICATE-----\n"
+            + "MIIDFTCCAf2gAwIBAgIUfX/OMAIEqOxxC7wug6SFwkULitgwDQYJKoZIhvcNAQEL\n"
+            + "BQAwGTEXMBUGA1UEAwwOYW5zaWxvLXRlc3QtY2EwIBcNMjYxMDE2MDkzMjA0WhgP\n"
+            + "MjEyNjA5MjIwOTMyMDRaMBkxFzAVBgNVBAMMDmFuc2lsby10ZXN0LWNhMIIBIjAN\n"
+            + "BgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAhlf8XjLkZWDnW61K/ry6hItQP/eh\n"
+            + "0CWHqOlsWgkdOv5L16LaY8e1SJfZQc0iRHnkbmpE0LQrneNm73daP2J/jjIz0F0D\n"
+            + "je1F3BRhgxF5HoY/Ou4gBLRqH4U0Iq2UNwtlIFcJDMTsZBE3GSX6g+C9zrw9Sxuk\n"
+            + "av470jZI6f77e5vJtqSU/3/bRquySoDbIap7uzcMznAIfCPyYJ0Be/1cJ4okCEPw\n"
+            + "1WlH36KxMbiVAOpVASXKRsS4FvmR8V3yvnrq6CPb6nwOVYmjDkFCLC6jteDPM0P6\n"
+            + "bOkXkm1R2itbzkghZZcGNTxBw7ZG5tDpsrKXEHzyBBQzIXPfGrghxMxR1wIDAQAB\n"
+            + "o1MwUTAdBgNVHQ4EFgQUQc53kWtyxB8kog4RpH+hDTRYiVIwHwYDVR0jBBgwFoAU\n"
+            + "Qc53kWtyxB8kog4RpH+hDTRYiVIwDwYDVR0TAQH/BAUwAwEB/zANBgkqhkiG9w0B\n"
+            + "AQsFAAOCAQEAY3D+1MyfZz0AS2C7ZoY1/UMW4eTQHLsv1Cz7AuMUVFG1xop+I9M5\n"
+            + "HTxcTUrYNv+STdDgW4O3UlREOibSleCQ/mbygUWjZ+7WdeCnLbqO0ZgYv/nlOgQ6\n"
+            + "unepPkouoNVmzqg6MkttOzlkOmpnslh03RwXrTVOZo2b03AXdhBRkFn82qikDEx6\n"
+            + "Sdnh058mA9TJ2+9T4Yc7oELlM7q+p1tNk98kmkTiP4znHDgQMUs5SJz3mg9VValq\n"
+            + "iROXieHuiMwSUPrbpy+Qw8/LeRulXmj2c/CVghOd2SX/qaCcqQuLQ94kt76KJWOp\n"
+            + "uZODgzGUQ3VBknuZHHiVpQaMbA+IP+vmfQ==\n"
+            + "-----END CERTIFICATE-----\n";
+
+    @Test
+    void testApplyWithoutCaBundle() throws Exception {
+        var props = new Properties();
+        props.setProperty("key", "value");
+
+        assertSame(props, PemTrustStore.apply(props));
+    }
+
+    @Test
+    void testApplyWithCaBundle() throws Exception {
+        var caBundle = Files.createTempFile("ca-", ".pem");
+        Files.writeString(caBundle, CA_CERT);
+
+        var props = new Properties();
+        props.setProperty(PemTrustStore.CA_BUNDLE_PROP, caBundle.toString());
+        props.setProperty("trustStore", "file:" + PemTrustStore.TRUST_STORE_PATH);
+        props.setProperty("trustStorePassword", PemTrustStore.TRUST_STORE_PASSWORD);
+        props.setProperty("other", "value");
+
+        var result = PemTrustStore.apply(props);
+
+        assertFalse(result.containsKey(PemTrustStore.CA_BUNDLE_PROP));
+        assertEquals("value", result.getProperty("other"));
+        assertTrue(result.getProperty("trustStore").startsWith("file:"));
+        assertNotEquals(PemTrustStore.TRUST_STORE_PASSWORD,
+                result.getProperty("trustStorePassword"));
+
+        var keyStore = KeyStore.getInstance("PKCS12");
+        try (var in = new FileInputStream(result.getProperty("trustStore").substring(5))) {
+            keyStore.load(in, result.getProperty("trustStorePassword").toCharArray());
+        }
+        assertEquals(1, keyStore.size());
+        assertTrue(keyStore.isCertificateEntry("ca-0"));
+    }
+
+    @Test
+    void testApplyWithInvalidCaBundle() throws Exception {
+        var caBundle = Files.createTempFile("ca-", ".pem");
+        Files.writeString(caBundle, "invalid");
+
+        var props = new Properties();
+        props.setProperty(PemTrustStore.CA_BUNDLE_PROP, caBundle.toString());
+
+        assertThrows(SQLException.class, () -> PemTrustStore.apply(props));
+
+        props.setProperty(PemTrustStore.CA_BUNDLE_PROP, Path.of("/non/existant.pem").toString());
+
+        assertThrows(SQLException.class, () -> PemTrustStore.apply(props));
+    }
+}
@@ -14,6 +14,8 @@ mod query;
 pub use query::*;
 mod jvm;
 pub use jvm::*;
+mod tls;
+pub use tls::*;
 
 #[cfg(test)]
 mod tests;
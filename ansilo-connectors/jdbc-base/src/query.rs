@@ -32,6 +32,11 @@ impl JdbcQuery {
             params,
         }
     }
+
+    /// Gets a key which identifies the query and its parameters
+    pub fn cache_key(&self) -> String {
+        format!("{}\n{:?}", self.query, self.params)
+    }
 }
 
 /// JDBC prepared query
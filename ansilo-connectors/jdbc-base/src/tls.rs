@@ -0,0 +1,45 @@
+use std::{collections::HashMap, path::Path};
+
+/// JDBC property containing the path of a PEM CA bundle.
+///
+/// Most JDBC drivers only accept trust stores in JKS or PKCS12 format, so the
+/// bundle is converted into a PKCS12 trust store before connecting and the
+/// placeholders below are substituted in the remaining properties.
+/// @see com.ansilo.connectors.tls.PemTrustStore
+pub const JDBC_TLS_CA_BUNDLE_PROP: &str = "ansilo.tls.caBundle";
+
+/// Placeholder for the path of the generated PKCS12 trust store
+pub const JDBC_TLS_TRUST_STORE_PATH: &str = "${ansilo.tls.trustStore}";
+
+/// Placeholder for the password of the generated PKCS12 trust store
+pub const JDBC_TLS_TRUST_STORE_PASSWORD: &str = "${ansilo.tls.trustStorePassword}";
+
+/// Trust manager which only accepts the server certificate with the pinned
+/// SHA-256 fingerprint, taking the fingerprint as its constructor argument
+pub const JDBC_TLS_FINGERPRINT_TRUST_MANAGER: &str =
+    "com.ansilo.connectors.tls.FingerprintTrustManager";
+
+/// Gets the properties to verify the server certificate against the CA bundle
+/// using the supplied driver-specific trust store properties
+pub fn jdbc_trust_store_props(
+    ca_bundle: &Path,
+    path_prop: &str,
+    path_prefix: &str,
+    type_prop: &str,
+    password_prop: &str,
+) -> HashMap<String, String> {
+    [
+        (
+            JDBC_TLS_CA_BUNDLE_PROP.into(),
+            ca_bundle.to_string_lossy().to_string(),
+        ),
+        (
+            path_prop.into(),
+            format!("{path_prefix}{JDBC_TLS_TRUST_STORE_PATH}"),
+        ),
+        (type_prop.into(), "PKCS12".into()),
+        (password_prop.into(), JDBC_TLS_TRUST_STORE_PASSWORD.into()),
+    ]
+    .into_iter()
+    .collect()
+}
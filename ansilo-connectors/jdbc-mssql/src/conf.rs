@@ -6,8 +6,11 @@ use ansilo_core::{
 };
 use serde::{Deserialize, Serialize};
 
-use ansilo_connectors_base::common::entity::ConnectorEntityConfig;
-use ansilo_connectors_jdbc_base::{JdbcConnectionConfig, JdbcConnectionPoolConfig};
+use ansilo_connectors_base::common::{entity::ConnectorEntityConfig, tls::TlsVerifyConfig};
+use ansilo_connectors_jdbc_base::{
+    jdbc_trust_store_props, JdbcConnectionConfig, JdbcConnectionPoolConfig,
+    JDBC_TLS_FINGERPRINT_TRUST_MANAGER,
+};
 
 /// The connection config for the Mssql JDBC driver
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -17,6 +20,8 @@ pub struct MssqlJdbcConnectionConfig {
     #[serde(default)]
     pub properties: HashMap<String, String>,
     pub pool: Option<JdbcConnectionPoolConfig>,
+    /// Server certificate verification, mapped to the equivalent driver properties
+    pub tls: Option<TlsVerifyConfig>,
 }
 
 impl JdbcConnectionConfig for MssqlJdbcConnectionConfig {
@@ -24,10 +29,11 @@ impl JdbcConnectionConfig for MssqlJdbcConnectionConfig {
         self.jdbc_url.clone()
     }
 
-    fn get_jdbc_props(&self) -> HashMap<String, String> {
-        let props = self.properties.clone();
+    fn get_jdbc_props(&self) -> Result<HashMap<String, String>> {
+        let mut props = self.properties.clone();
+        props.extend(self.tls_props()?);
 
-        props
+        Ok(props)
     }
 
     fn get_pool_config(&self) -> Option<JdbcConnectionPoolConfig> {
@@ -49,6 +55,7 @@ impl MssqlJdbcConnectionConfig {
             jdbc_url,
             properties,
             pool,
+            tls: None,
         }
     }
 
@@ -56,6 +63,49 @@ impl MssqlJdbcConnectionConfig {
         config::from_value::<Self>(options)
             .context("Failed to parse connection configuration options")
     }
+
+    /// Gets the mssql-jdbc properties for the tls options
+    /// @see https://learn.microsoft.com/en-us/sql/connect/jdbc/setting-the-connection-properties
+    fn tls_props(&self) -> Result<HashMap<String, String>> {
+        let tls = match self.tls.as_ref() {
+            Some(tls) => tls,
+            None => return Ok(HashMap::new()),
+        };
+
+        tls.validate()?;
+        let mut props = HashMap::new();
+        props.insert("encrypt".into(), "true".into());
+
+        match tls {
+            TlsVerifyConfig::System => {
+                props.insert("trustServerCertificate".into(), "false".into());
+            }
+            TlsVerifyConfig::CaBundle { ca_bundle } => {
+                props.insert("trustServerCertificate".into(), "false".into());
+                props.extend(jdbc_trust_store_props(
+                    ca_bundle,
+                    "trustStore",
+                    "",
+                    "trustStoreType",
+                    "trustStorePassword",
+                ));
+            }
+            TlsVerifyConfig::Fingerprint { fingerprint } => {
+                props.insert("trustServerCertificate".into(), "false".into());
+                props.insert(
+                    "trustManagerClass".into(),
+                    JDBC_TLS_FINGERPRINT_TRUST_MANAGER.into(),
+                );
+                props.insert("trustManagerConstructorArg".into(), fingerprint.clone());
+            }
+            TlsVerifyConfig::Disabled => {
+                tls.warn_if_disabled("mssql");
+                props.insert("trustServerCertificate".into(), "true".into());
+            }
+        }
+
+        Ok(props)
+    }
 }
 
 /// Entity source config for Mssql JDBC driver
@@ -125,11 +175,55 @@ properties:
                     map.insert("TEST_PROP".to_string(), "TEST_PROP_VAL".to_string());
                     map
                 },
-                pool: None
+                pool: None,
+                tls: None
             }
         );
     }
 
+    #[test]
+    fn test_mssql_jdbc_tls_props() {
+        let conf = config::parse_config(
+            r#"
+jdbc_url: "JDBC_URL"
+properties:
+  TEST_PROP: "TEST_PROP_VAL"
+tls:
+  verify: fingerprint
+  fingerprint: "19:74:AD:25:7F:32:C7:39:B8:E7:74:50:65:DF:05:BB:7E:33:41:20:82:E9:52:E0:5C:EB:84:A1:D0:06:4E:10"
+"#,
+        )
+        .unwrap();
+
+        let mut parsed = MssqlJdbcConnectionConfig::parse(conf).unwrap();
+        let props = parsed.get_jdbc_props().unwrap();
+
+        assert_eq!(props["TEST_PROP"], "TEST_PROP_VAL");
+        assert_eq!(props["encrypt"], "true");
+        assert_eq!(props["trustServerCertificate"], "false");
+        assert_eq!(
+            props["trustManagerClass"],
+            "com.ansilo.connectors.tls.FingerprintTrustManager"
+        );
+        assert_eq!(
+            props["trustManagerConstructorArg"],
+            "19:74:AD:25:7F:32:C7:39:B8:E7:74:50:65:DF:05:BB:7E:33:41:20:82:E9:52:E0:5C:EB:84:A1:D0:06:4E:10"
+        );
+
+        parsed.tls = Some(TlsVerifyConfig::CaBundle {
+            ca_bundle: "/etc/ca.pem".into(),
+        });
+        let props = parsed.get_jdbc_props().unwrap();
+
+        assert_eq!(props["ansilo.tls.caBundle"], "/etc/ca.pem");
+        assert_eq!(props["trustStore"], "${ansilo.tls.trustStore}");
+        assert_eq!(props["trustStoreType"], "PKCS12");
+        assert_eq!(
+            props["trustStorePassword"],
+            "${ansilo.tls.trustStorePassword}"
+        );
+    }
+
     #[test]
     fn test_mssql_jdbc_parse_entity_table_options() {
         let conf = config::parse_config(
@@ -41,6 +41,10 @@ impl QueryCompiler for MssqlJdbcQueryCompiler {
             params.into_iter().map(|p| QueryParam::dynamic(p)).collect(),
         ))
     }
+
+    fn query_key(query: &Self::TQuery) -> Option<String> {
+        Some(query.cache_key())
+    }
 }
 
 impl MssqlJdbcQueryCompiler {
@@ -2,12 +2,14 @@ use std::collections::HashMap;
 
 use ansilo_core::{
     config,
-    err::{Context, Result},
+    err::{bail, Context, Result},
 };
 use serde::{Deserialize, Serialize};
 
-use ansilo_connectors_base::common::entity::ConnectorEntityConfig;
-use ansilo_connectors_jdbc_base::{JdbcConnectionConfig, JdbcConnectionPoolConfig};
+use ansilo_connectors_base::common::{entity::ConnectorEntityConfig, tls::TlsVerifyConfig};
+use ansilo_connectors_jdbc_base::{
+    jdbc_trust_store_props, JdbcConnectionConfig, JdbcConnectionPoolConfig,
+};
 
 /// The connection config for the Mysql JDBC driver
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -16,6 +18,8 @@ pub struct MysqlJdbcConnectionConfig {
     /// @see https://dev.mysql.com/doc/connector-j/8.0/en/connector-j-reference-configuration-properties.html
     pub properties: HashMap<String, String>,
     pub pool: Option<JdbcConnectionPoolConfig>,
+    /// Server certificate verification, mapped to the equivalent driver properties
+    pub tls: Option<TlsVerifyConfig>,
 }
 
 impl JdbcConnectionConfig for MysqlJdbcConnectionConfig {
@@ -23,12 +27,13 @@ impl JdbcConnectionConfig for MysqlJdbcConnectionConfig {
         self.jdbc_url.clone()
     }
 
-    fn get_jdbc_props(&self) -> HashMap<String, String> {
+    fn get_jdbc_props(&self) -> Result<HashMap<String, String>> {
         let mut props = self.properties.clone();
         props.insert("characterEncoding".into(), "utf8".into());
         props.insert("characterSetResults".into(), "utf8mb4".into());
+        props.extend(self.tls_props()?);
 
-        props
+        Ok(props)
     }
 
     fn get_pool_config(&self) -> Option<JdbcConnectionPoolConfig> {
@@ -50,6 +55,7 @@ impl MysqlJdbcConnectionConfig {
             jdbc_url,
             properties,
             pool,
+            tls: None,
         }
     }
 
@@ -57,6 +63,43 @@ impl MysqlJdbcConnectionConfig {
         config::from_value::<Self>(options)
             .context("Failed to parse connection configuration options")
     }
+
+    /// Gets the Connector/J properties for the tls options
+    /// @see https://dev.mysql.com/doc/connector-j/8.0/en/connector-j-connp-props-security.html
+    fn tls_props(&self) -> Result<HashMap<String, String>> {
+        let tls = match self.tls.as_ref() {
+            Some(tls) => tls,
+            None => return Ok(HashMap::new()),
+        };
+
+        let mut props = HashMap::new();
+
+        match tls {
+            TlsVerifyConfig::System => {
+                props.insert("sslMode".into(), "VERIFY_IDENTITY".into());
+            }
+            TlsVerifyConfig::CaBundle { ca_bundle } => {
+                props.insert("sslMode".into(), "VERIFY_IDENTITY".into());
+                props.insert("fallbackToSystemTrustStore".into(), "false".into());
+                props.extend(jdbc_trust_store_props(
+                    ca_bundle,
+                    "trustCertificateKeyStoreUrl",
+                    "file:",
+                    "trustCertificateKeyStoreType",
+                    "trustCertificateKeyStorePassword",
+                ));
+            }
+            TlsVerifyConfig::Fingerprint { .. } => {
+                bail!("Pinned certificate fingerprints are not supported by the mysql driver, use verify: ca_bundle with the certificate of the server instead")
+            }
+            TlsVerifyConfig::Disabled => {
+                tls.warn_if_disabled("mysql");
+                props.insert("sslMode".into(), "REQUIRED".into());
+            }
+        }
+
+        Ok(props)
+    }
 }
 
 /// Entity source config for Mysql JDBC driver
@@ -126,11 +169,29 @@ properties:
                     map.insert("TEST_PROP".to_string(), "TEST_PROP_VAL".to_string());
                     map
                 },
-                pool: None
+                pool: None,
+                tls: None
             }
         );
     }
 
+    #[test]
+    fn test_mysql_jdbc_tls_props() {
+        let mut conf = MysqlJdbcConnectionConfig::new("JDBC_URL".into(), HashMap::new(), None);
+        assert_eq!(conf.get_jdbc_props().unwrap().get("sslMode"), None);
+
+        conf.tls = Some(TlsVerifyConfig::System);
+        assert_eq!(conf.get_jdbc_props().unwrap()["sslMode"], "VERIFY_IDENTITY");
+
+        conf.tls = Some(TlsVerifyConfig::Disabled);
+        assert_eq!(conf.get_jdbc_props().unwrap()["sslMode"], "REQUIRED");
+
+        conf.tls = Some(TlsVerifyConfig::Fingerprint {
+            fingerprint: "AB:CD".into(),
+        });
+        conf.get_jdbc_props().unwrap_err();
+    }
+
     #[test]
     fn test_mysql_jdbc_parse_entity_table_options() {
         let conf = config::parse_config(
@@ -2,12 +2,14 @@ use std::collections::HashMap;
 
 use ansilo_core::{
     config,
-    err::{Context, Result},
+    err::{bail, Context, Result},
 };
 use serde::{Deserialize, Serialize};
 
-use ansilo_connectors_base::common::entity::ConnectorEntityConfig;
-use ansilo_connectors_jdbc_base::{JdbcConnectionConfig, JdbcConnectionPoolConfig};
+use ansilo_connectors_base::common::{entity::ConnectorEntityConfig, tls::TlsVerifyConfig};
+use ansilo_connectors_jdbc_base::{
+    jdbc_trust_store_props, JdbcConnectionConfig, JdbcConnectionPoolConfig,
+};
 
 /// The connection config for the Oracle JDBC driver
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -16,6 +18,8 @@ pub struct OracleJdbcConnectionConfig {
     /// @see https://docs.oracle.com/en/database/oracle/oracle-database/21/jajdb/oracle/jdbc/OracleConnection.html
     pub properties: HashMap<String, String>,
     pub pool: Option<JdbcConnectionPoolConfig>,
+    /// Server certificate verification, mapped to the equivalent driver properties
+    pub tls: Option<TlsVerifyConfig>,
 }
 
 impl JdbcConnectionConfig for OracleJdbcConnectionConfig {
@@ -23,8 +27,11 @@ impl JdbcConnectionConfig for OracleJdbcConnectionConfig {
         self.jdbc_url.clone()
     }
 
-    fn get_jdbc_props(&self) -> HashMap<String, String> {
-        self.properties.clone()
+    fn get_jdbc_props(&self) -> Result<HashMap<String, String>> {
+        let mut props = self.properties.clone();
+        props.extend(self.tls_props()?);
+
+        Ok(props)
     }
 
     fn get_pool_config(&self) -> Option<JdbcConnectionPoolConfig> {
@@ -46,6 +53,7 @@ impl OracleJdbcConnectionConfig {
             jdbc_url,
             properties,
             pool,
+            tls: None,
         }
     }
 
@@ -53,6 +61,42 @@ impl OracleJdbcConnectionConfig {
         config::from_value::<Self>(options)
             .context("Failed to parse connection configuration options")
     }
+
+    /// Gets the Oracle thin driver properties for the tls options,
+    /// these apply when connecting using the TCPS protocol
+    /// @see https://docs.oracle.com/en/database/oracle/oracle-database/21/jjdbc/client-side-security.html
+    fn tls_props(&self) -> Result<HashMap<String, String>> {
+        let tls = match self.tls.as_ref() {
+            Some(tls) => tls,
+            None => return Ok(HashMap::new()),
+        };
+
+        let mut props = HashMap::new();
+
+        match tls {
+            TlsVerifyConfig::System => {
+                props.insert("oracle.net.ssl_server_dn_match".into(), "true".into());
+            }
+            TlsVerifyConfig::CaBundle { ca_bundle } => {
+                props.insert("oracle.net.ssl_server_dn_match".into(), "true".into());
+                props.extend(jdbc_trust_store_props(
+                    ca_bundle,
+                    "javax.net.ssl.trustStore",
+                    "",
+                    "javax.net.ssl.trustStoreType",
+                    "javax.net.ssl.trustStorePassword",
+                ));
+            }
+            TlsVerifyConfig::Fingerprint { .. } => {
+                bail!("Pinned certificate fingerprints are not supported by the oracle driver, use verify: ca_bundle with the certificate of the server instead")
+            }
+            TlsVerifyConfig::Disabled => {
+                bail!("Disabling certificate verification is not supported by the oracle driver, use verify: ca_bundle with the certificate of the server instead")
+            }
+        }
+
+        Ok(props)
+    }
 }
 
 /// Entity source config for Oracle JDBC driver
@@ -122,7 +166,8 @@ properties:
                     map.insert("TEST_PROP".to_string(), "TEST_PROP_VAL".to_string());
                     map
                 },
-                pool: None
+                pool: None,
+                tls: None
             }
         );
     }
@@ -2,11 +2,11 @@ use std::collections::HashMap;
 
 use ansilo_core::{
     config,
-    err::{Context, Result},
+    err::{bail, Context, Result},
 };
 use serde::{Deserialize, Serialize};
 
-use ansilo_connectors_base::common::entity::ConnectorEntityConfig;
+use ansilo_connectors_base::common::{entity::ConnectorEntityConfig, tls::TlsVerifyConfig};
 use ansilo_connectors_jdbc_base::{JdbcConnectionConfig, JdbcConnectionPoolConfig};
 
 /// The connection config for the Teradata JDBC driver
@@ -23,6 +23,8 @@ pub struct TeradataJdbcConnectionConfig {
     #[serde(default)]
     pub startup: Vec<String>,
     pub pool: Option<JdbcConnectionPoolConfig>,
+    /// Server certificate verification, mapped to the equivalent driver properties
+    pub tls: Option<TlsVerifyConfig>,
 }
 
 impl JdbcConnectionConfig for TeradataJdbcConnectionConfig {
@@ -30,8 +32,11 @@ impl JdbcConnectionConfig for TeradataJdbcConnectionConfig {
         self.jdbc_url.clone()
     }
 
-    fn get_jdbc_props(&self) -> HashMap<String, String> {
-        self.properties.clone()
+    fn get_jdbc_props(&self) -> Result<HashMap<String, String>> {
+        let mut props = self.properties.clone();
+        props.extend(self.tls_props()?);
+
+        Ok(props)
     }
 
     fn get_pool_config(&self) -> Option<JdbcConnectionPoolConfig> {
@@ -63,6 +68,7 @@ impl TeradataJdbcConnectionConfig {
             properties,
             startup,
             pool,
+            tls: None,
         }
     }
 
@@ -70,6 +76,37 @@ impl TeradataJdbcConnectionConfig {
         config::from_value::<Self>(options)
             .context("Failed to parse connection configuration options")
     }
+
+    /// Gets the Teradata driver properties for the tls options,
+    /// the driver accepts PEM CA bundles directly
+    /// @see https://teradata-docs.s3.amazonaws.com/doc/connectivity/jdbc/reference/current/jdbcug_chapter_2.html#URL_SSLMODE
+    fn tls_props(&self) -> Result<HashMap<String, String>> {
+        let tls = match self.tls.as_ref() {
+            Some(tls) => tls,
+            None => return Ok(HashMap::new()),
+        };
+
+        let mut props = HashMap::new();
+
+        match tls {
+            TlsVerifyConfig::System => {
+                props.insert("SSLMODE".into(), "VERIFY-FULL".into());
+            }
+            TlsVerifyConfig::CaBundle { ca_bundle } => {
+                props.insert("SSLMODE".into(), "VERIFY-FULL".into());
+                props.insert("SSLCA".into(), ca_bundle.to_string_lossy().to_string());
+            }
+            TlsVerifyConfig::Fingerprint { .. } => {
+                bail!("Pinned certificate fingerprints are not supported by the teradata driver, use verify: ca_bundle with the certificate of the server instead")
+            }
+            TlsVerifyConfig::Disabled => {
+                tls.warn_if_disabled("teradata");
+                props.insert("SSLMODE".into(), "REQUIRE".into());
+            }
+        }
+
+        Ok(props)
+    }
 }
 
 /// Entity source config for Teradata JDBC driver
@@ -174,7 +211,8 @@ properties:
                     map
                 },
                 startup: vec![],
-                pool: None
+                pool: None,
+                tls: None
             }
         );
     }
@@ -43,6 +43,10 @@ impl QueryCompiler for TeradataJdbcQueryCompiler {
             params.into_iter().map(|p| QueryParam::dynamic(p)).collect(),
         ))
     }
+
+    fn query_key(query: &Self::TQuery) -> Option<String> {
+        Some(query.cache_key())
+    }
 }
 
 impl TeradataJdbcQueryCompiler {
@@ -6,6 +6,8 @@ use std::{
 use ansilo_core::data::DataValue;
 use serde::{Deserialize, Serialize};
 
+use crate::MemoryGenerateConfig;
+
 /// The in-memory data store config, all data is stored in the data structure
 /// below
 #[derive(Debug, Serialize, Deserialize)]
@@ -25,6 +27,8 @@ pub struct MemoryDatabaseConf {
     pub transactions_enabled: bool,
     /// Whether to preten row-level locking is supported
     pub row_locks_pretend: bool,
+    /// The options to generate the data of entities, keyed by the entity id
+    pub generate: HashMap<String, MemoryGenerateConfig>,
 }
 
 impl Default for MemoryDatabaseConf {
@@ -32,6 +36,7 @@ impl Default for MemoryDatabaseConf {
         Self {
             transactions_enabled: true,
             row_locks_pretend: true,
+            generate: HashMap::new(),
         }
     }
 }
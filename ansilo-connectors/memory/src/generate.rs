@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+
+use ansilo_core::{
+    config::{self, EntityAttributeConfig, EntityConfig},
+    data::{
+        chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime},
+        chrono_tz,
+        rust_decimal::Decimal,
+        uuid, DataType, DataValue, DateTimeWithTZ,
+    },
+    err::{bail, Context, Result},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::parse_value;
+
+/// Options for generating synthetic rows of an entity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryGenerateConfig {
+    /// The number of rows to generate
+    pub rows: usize,
+    /// The seed of the random generator, the same seed generates the same rows
+    #[serde(default)]
+    pub seed: u64,
+    /// The generators of the attributes, keyed by the attribute id.
+    /// Attributes without a generator use the defaults of their type.
+    #[serde(default)]
+    pub attributes: HashMap<String, MemoryAttributeGenerator>,
+}
+
+/// Options for generating the values of an attribute
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MemoryAttributeGenerator {
+    /// The lower bound of generated numbers, dates or times
+    #[serde(default)]
+    pub min: Option<config::Value>,
+    /// The upper bound of generated numbers, dates or times
+    #[serde(default)]
+    pub max: Option<config::Value>,
+    /// The pattern of generated strings, see [`generate_pattern`]
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// The values to choose from
+    #[serde(default)]
+    pub values: Option<Vec<config::Value>>,
+    /// The proportion of null values, between 0 and 1
+    #[serde(default)]
+    pub null_ratio: f64,
+}
+
+/// The default bounds of generated numbers
+const DEFAULT_NUMBER_RANGE: (f64, f64) = (0.0, 1000.0);
+
+/// The default bounds of generated dates
+const DEFAULT_DATE_RANGE: (&str, &str) = ("2000-01-01", "2030-01-01");
+
+/// Generates the rows of the entity, ordered by its attributes
+pub fn generate_rows(
+    entity: &EntityConfig,
+    conf: &MemoryGenerateConfig,
+) -> Result<Vec<Vec<DataValue>>> {
+    if let Some(id) = conf
+        .attributes
+        .keys()
+        .find(|id| !entity.attributes.iter().any(|a| &a.id == *id))
+    {
+        bail!(
+            "Cannot generate data for unknown attribute '{id}' of entity '{}'",
+            entity.id
+        );
+    }
+
+    let default = MemoryAttributeGenerator::default();
+    let mut rng = Rng::new(conf.seed);
+
+    (1..=conf.rows)
+        .map(|n| {
+            entity
+                .attributes
+                .iter()
+                .map(|attr| {
+                    let gen = conf.attributes.get(&attr.id).unwrap_or(&default);
+
+                    generate_value(&mut rng, attr, gen, n).with_context(|| {
+                        format!(
+                            "Failed to generate value of attribute '{}' of entity '{}'",
+                            attr.id, entity.id
+                        )
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Generates the value of the attribute for the n-th row
+fn generate_value(
+    rng: &mut Rng,
+    attr: &EntityAttributeConfig,
+    gen: &MemoryAttributeGenerator,
+    n: usize,
+) -> Result<DataValue> {
+    if gen.null_ratio > 0.0 && rng.next_f64() < gen.null_ratio {
+        return Ok(DataValue::Null);
+    }
+
+    if let Some(values) = gen.values.as_ref() {
+        if values.is_empty() {
+            bail!("At least one value must be specified");
+        }
+
+        let idx = rng.range(0, values.len() as i64 - 1) as usize;
+        return parse_value(&values[idx]).try_coerce_into(&attr.r#type);
+    }
+
+    let r#type = &attr.r#type;
+
+    Ok(match r#type {
+        DataType::Null => DataValue::Null,
+        DataType::Boolean => DataValue::Boolean(rng.next_u64() % 2 == 0),
+        DataType::Utf8String(_) => {
+            let pattern = gen
+                .pattern
+                .clone()
+                .unwrap_or_else(|| format!("{}-{{n}}", attr.id));
+
+            DataValue::Utf8String(generate_pattern(rng, &pattern, n))
+        }
+        DataType::Int8
+        | DataType::UInt8
+        | DataType::Int16
+        | DataType::UInt16
+        | DataType::Int32
+        | DataType::UInt32
+        | DataType::Int64
+        | DataType::UInt64 => {
+            // Primary keys default to a sequence so they are unique
+            let val = if attr.primary_key && gen.min.is_none() && gen.max.is_none() {
+                n as i64
+            } else {
+                let (min, max) = number_range(gen)?;
+                rng.range(min.ceil() as i64, max.floor() as i64)
+            };
+
+            DataValue::Int64(val).try_coerce_into(r#type)?
+        }
+        DataType::Float32 => {
+            let (min, max) = number_range(gen)?;
+            DataValue::Float32((min + rng.next_f64() * (max - min)) as f32)
+        }
+        DataType::Float64 => {
+            let (min, max) = number_range(gen)?;
+            DataValue::Float64(min + rng.next_f64() * (max - min))
+        }
+        DataType::Decimal(_) => {
+            let (min, max) = number_range(gen)?;
+            let cents = rng.range((min * 100.0).ceil() as i64, (max * 100.0).floor() as i64);
+            DataValue::Decimal(Decimal::new(cents, 2))
+        }
+        DataType::Date | DataType::DateTime | DataType::DateTimeWithTZ => {
+            let min = parse_datetime(gen.min.as_ref(), DEFAULT_DATE_RANGE.0)?;
+            let max = parse_datetime(gen.max.as_ref(), DEFAULT_DATE_RANGE.1)?;
+            let dt = min + Duration::seconds(rng.range(0, (max - min).num_seconds()));
+
+            match r#type {
+                DataType::Date => DataValue::Date(dt.date()),
+                DataType::DateTime => DataValue::DateTime(dt),
+                _ => DataValue::DateTimeWithTZ(DateTimeWithTZ::new(dt, chrono_tz::UTC)),
+            }
+        }
+        DataType::Time => {
+            let min = parse_time(gen.min.as_ref(), NaiveTime::from_hms_opt(0, 0, 0).unwrap())?;
+            let max = parse_time(
+                gen.max.as_ref(),
+                NaiveTime::from_hms_opt(23, 59, 59).unwrap(),
+            )?;
+
+            DataValue::Time(min + Duration::seconds(rng.range(0, (max - min).num_seconds())))
+        }
+        DataType::Uuid => {
+            let mut bytes = [0u8; 16];
+            bytes[..8].copy_from_slice(&rng.next_u64().to_le_bytes());
+            bytes[8..].copy_from_slice(&rng.next_u64().to_le_bytes());
+
+            DataValue::Uuid(uuid::Builder::from_random_bytes(bytes).into_uuid())
+        }
+        DataType::Binary => DataValue::Binary(rng.next_u64().to_le_bytes().to_vec()),
+        DataType::JSON => DataValue::JSON(format!(r#"{{"{}":{n}}}"#, attr.id)),
+    })
+}
+
+/// Generates a string from the pattern, replacing:
+///  - `#` with a random digit
+///  - `?` with a random lowercase letter
+///  - `{n}` with the row number, starting at 1
+///
+/// Any character can be escaped with a backslash.
+pub fn generate_pattern(rng: &mut Rng, pattern: &str, n: usize) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            '#' => out.push((b'0' + rng.range(0, 9) as u8) as char),
+            '?' => out.push((b'a' + rng.range(0, 25) as u8) as char),
+            '{' if chars.peek() == Some(&'n') => {
+                let mut lookahead = chars.clone();
+                lookahead.next();
+
+                if lookahead.next() == Some('}') {
+                    out.push_str(&n.to_string());
+                    chars = lookahead;
+                } else {
+                    out.push(c);
+                }
+            }
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+fn number_range(gen: &MemoryAttributeGenerator) -> Result<(f64, f64)> {
+    let bound = |val: Option<&config::Value>, default: f64| match val {
+        None => Ok(default),
+        Some(val) => val
+            .as_f64()
+            .with_context(|| format!("Expected a number but found {:?}", val)),
+    };
+
+    let min = bound(gen.min.as_ref(), DEFAULT_NUMBER_RANGE.0)?;
+    let max = bound(gen.max.as_ref(), DEFAULT_NUMBER_RANGE.1)?;
+
+    if min > max {
+        bail!("The min ({min}) must not be greater than the max ({max})");
+    }
+
+    Ok((min, max))
+}
+
+/// Parses a date (YYYY-MM-DD) or date time (YYYY-MM-DDTHH:MM:SS) bound
+fn parse_datetime(val: Option<&config::Value>, default: &str) -> Result<NaiveDateTime> {
+    let val = match val {
+        Some(config::Value::String(s)) => s.as_str(),
+        Some(val) => bail!("Expected a date but found {:?}", val),
+        None => default,
+    };
+
+    NaiveDateTime::parse_from_str(val, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(val, "%Y-%m-%d").map(|d| d.and_hms_opt(0, 0, 0).unwrap())
+        })
+        .with_context(|| format!("Failed to parse date '{val}'"))
+}
+
+/// Parses a time (HH:MM:SS) bound
+fn parse_time(val: Option<&config::Value>, default: NaiveTime) -> Result<NaiveTime> {
+    match val {
+        Some(config::Value::String(s)) => NaiveTime::parse_from_str(s, "%H:%M:%S")
+            .with_context(|| format!("Failed to parse time '{s}'")),
+        Some(val) => bail!("Expected a time but found {:?}", val),
+        None => Ok(default),
+    }
+}
+
+/// A small, seedable pseudo-random generator (splitmix64).
+///
+/// Generated data only needs to look plausible and be reproducible,
+/// so a cryptographically secure generator is not required.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in the range [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns an integer in the inclusive range [min, max]
+    pub fn range(&mut self, min: i64, max: i64) -> i64 {
+        if max <= min {
+            return min;
+        }
+
+        let span = (max as i128 - min as i128 + 1) as u128;
+        (min as i128 + (self.next_u64() as u128 % span) as i128) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::config::EntitySourceConfig;
+
+    use super::*;
+
+    fn mock_entity() -> EntityConfig {
+        EntityConfig::minimal(
+            "orders",
+            vec![
+                EntityAttributeConfig::new("id".into(), None, DataType::Int32, true, false),
+                EntityAttributeConfig::minimal("ref", DataType::rust_string()),
+                EntityAttributeConfig::minimal("amount", DataType::Int32),
+                EntityAttributeConfig::minimal("placed", DataType::Date),
+                EntityAttributeConfig::nullable("status", DataType::rust_string()),
+            ],
+            EntitySourceConfig::minimal("memory"),
+        )
+    }
+
+    fn mock_conf() -> MemoryGenerateConfig {
+        serde_yaml::from_str(
+            r#"
+rows: 100
+seed: 123
+attributes:
+  ref:
+    pattern: "ORD-####-{n}"
+  amount:
+    min: 10
+    max: 20
+  placed:
+    min: 2022-01-01
+    max: 2022-12-31
+  status:
+    values: [pending, shipped]
+    null_ratio: 0.5
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_generate_rows() {
+        let rows = generate_rows(&mock_entity(), &mock_conf()).unwrap();
+
+        assert_eq!(rows.len(), 100);
+
+        for (idx, row) in rows.iter().enumerate() {
+            assert_eq!(row.len(), 5);
+            assert_eq!(row[0], DataValue::Int32(idx as i32 + 1));
+
+            match &row[1] {
+                DataValue::Utf8String(s) => {
+                    assert!(s.starts_with("ORD-"));
+                    assert!(s.ends_with(&format!("-{}", idx + 1)));
+                    assert!(s[4..8].chars().all(|c| c.is_ascii_digit()));
+                }
+                v => panic!("Unexpected value {:?}", v),
+            }
+
+            match &row[2] {
+                DataValue::Int32(n) => assert!((10..=20).contains(n)),
+                v => panic!("Unexpected value {:?}", v),
+            }
+
+            match &row[3] {
+                DataValue::Date(d) => {
+                    assert!(*d >= NaiveDate::from_ymd_opt(2022, 1, 1).unwrap());
+                    assert!(*d <= NaiveDate::from_ymd_opt(2022, 12, 31).unwrap());
+                }
+                v => panic!("Unexpected value {:?}", v),
+            }
+
+            assert!([
+                DataValue::Null,
+                DataValue::Utf8String("pending".into()),
+                DataValue::Utf8String("shipped".into())
+            ]
+            .contains(&row[4]));
+        }
+
+        assert!(rows.iter().any(|r| r[4] == DataValue::Null));
+        assert!(rows.iter().any(|r| r[4] != DataValue::Null));
+    }
+
+    #[test]
+    fn test_generate_rows_is_deterministic() {
+        let entity = mock_entity();
+        let conf = mock_conf();
+
+        assert_eq!(
+            generate_rows(&entity, &conf).unwrap(),
+            generate_rows(&entity, &conf).unwrap()
+        );
+
+        let other = MemoryGenerateConfig {
+            seed: 456,
+            ..conf.clone()
+        };
+
+        assert_ne!(
+            generate_rows(&entity, &conf).unwrap(),
+            generate_rows(&entity, &other).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_generate_rows_unknown_attribute() {
+        let mut conf = mock_conf();
+        conf.attributes
+            .insert("invalid".into(), MemoryAttributeGenerator::default());
+
+        generate_rows(&mock_entity(), &conf).unwrap_err();
+    }
+
+    #[test]
+    fn test_generate_rows_invalid_range() {
+        let mut conf = mock_conf();
+        conf.attributes.get_mut("amount").unwrap().min = Some(config::Value::from(100));
+
+        generate_rows(&mock_entity(), &conf).unwrap_err();
+    }
+
+    #[test]
+    fn test_generate_pattern() {
+        let mut rng = Rng::new(0);
+
+        assert_eq!(generate_pattern(&mut rng, "user-{n}", 5), "user-5");
+        assert_eq!(generate_pattern(&mut rng, r"\#\?{n", 1), "#?{n");
+
+        let s = generate_pattern(&mut rng, "??-##", 1);
+        assert_eq!(s.len(), 5);
+        assert!(s[..2].chars().all(|c| c.is_ascii_lowercase()));
+        assert!(s[3..].chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn test_rng_range() {
+        let mut rng = Rng::new(0);
+
+        for _ in 0..1000 {
+            assert!((-5..=5).contains(&rng.range(-5, 5)));
+        }
+
+        assert_eq!(rng.range(3, 3), 3);
+    }
+}
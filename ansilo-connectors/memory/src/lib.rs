@@ -1,5 +1,6 @@
 mod conf;
 pub mod executor;
+mod generate;
 use ansilo_connectors_base::{
     common::entity::ConnectorEntityConfig,
     interface::{Connector, OperationCost},
@@ -10,6 +11,7 @@ use ansilo_core::{
     err::{Context, Result},
 };
 pub use conf::*;
+pub use generate::*;
 mod connection;
 pub use connection::*;
 mod query;
@@ -52,29 +54,30 @@ impl Connector for MemoryConnector {
 
         if let Some(data) = options.as_mapping() {
             for (id, data) in data.iter() {
-                if let (Some(id), Some(rows)) = (id.as_str(), data.as_sequence()) {
+                let id = match id.as_str() {
+                    Some(id) => id,
+                    None => continue,
+                };
+
+                // Entities are either configured with their rows or the options
+                // to generate them, which are generated once the entity configs are known
+                if let Some(rows) = data.as_sequence() {
                     let rows = rows
                         .iter()
                         .filter_map(|r| r.as_sequence())
-                        .map(|r| {
-                            r.iter()
-                                .map(|d| match d {
-                                    config::Value::Null => DataValue::Null,
-                                    config::Value::Bool(b) => DataValue::Boolean(*b),
-                                    config::Value::Number(n) if n.is_i64() => {
-                                        DataValue::Int64(n.as_i64().unwrap())
-                                    }
-                                    config::Value::Number(n) if n.is_f64() => {
-                                        DataValue::Float64(n.as_f64().unwrap())
-                                    }
-                                    config::Value::String(s) => DataValue::Utf8String(s.clone()),
-                                    v => DataValue::Utf8String(serde_json::to_string(v).unwrap()),
-                                })
-                                .collect::<Vec<_>>()
-                        })
+                        .map(|r| r.iter().map(parse_value).collect::<Vec<_>>())
                         .collect::<Vec<_>>();
 
                     db.set_data(id, rows);
+                } else if data.is_mapping() {
+                    let gen: MemoryGenerateConfig = serde_yaml::from_value(data.clone())
+                        .with_context(|| {
+                            format!("Failed to parse data generation options of entity '{id}'")
+                        })?;
+
+                    db.update_conf(|conf| {
+                        conf.generate.insert(id.to_string(), gen);
+                    });
                 }
             }
         }
@@ -91,10 +94,31 @@ impl Connector for MemoryConnector {
         _nc: &NodeConfig,
         entities: &ConnectorEntityConfig<MemoryConnectorEntitySourceConfig>,
     ) -> Result<Self::TConnectionPool> {
+        for (id, gen) in conf.conf().generate.iter() {
+            let entity = entities
+                .entities()
+                .find(|e| &e.conf.id == id)
+                .with_context(|| format!("Failed to find entity '{id}' to generate data for"))?;
+
+            conf.set_data(id, generate_rows(&entity.conf, gen)?);
+        }
+
         MemoryConnectionPool::new(conf, entities.clone())
     }
 }
 
+/// Converts the yaml value from the data source options to a data value
+pub(crate) fn parse_value(val: &config::Value) -> DataValue {
+    match val {
+        config::Value::Null => DataValue::Null,
+        config::Value::Bool(b) => DataValue::Boolean(*b),
+        config::Value::Number(n) if n.is_i64() => DataValue::Int64(n.as_i64().unwrap()),
+        config::Value::Number(n) if n.is_f64() => DataValue::Float64(n.as_f64().unwrap()),
+        config::Value::String(s) => DataValue::Utf8String(s.clone()),
+        v => DataValue::Utf8String(serde_json::to_string(v).unwrap()),
+    }
+}
+
 #[derive(Clone, Default, PartialEq, Debug, Deserialize, Serialize)]
 pub struct MemoryConnectorEntitySourceConfig {
     pub mock_entity_size: Option<OperationCost>,
@@ -111,4 +135,91 @@ impl MemoryConnectorEntitySourceConfig {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use ansilo_connectors_base::common::entity::EntitySource;
+    use ansilo_core::{
+        config::{EntityAttributeConfig, EntityConfig, EntitySourceConfig},
+        data::DataType,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_memory_connector_parse_options() {
+        let db = MemoryConnector::parse_options(
+            serde_yaml::from_str(
+                r#"
+people:
+  - [1, "John"]
+orders:
+  rows: 10
+  attributes:
+    amount:
+      min: 1
+      max: 5
+"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            db.get_data("people"),
+            Some(vec![vec![
+                DataValue::Int64(1),
+                DataValue::Utf8String("John".into())
+            ]])
+        );
+        assert_eq!(db.get_data("orders"), None);
+        assert_eq!(db.conf().generate["orders"].rows, 10);
+    }
+
+    #[test]
+    fn test_memory_connector_generates_data() {
+        let db =
+            MemoryConnector::parse_options(serde_yaml::from_str("orders: { rows: 3 }").unwrap())
+                .unwrap();
+
+        let mut entities = ConnectorEntityConfig::new();
+        entities.add(EntitySource::new(
+            EntityConfig::minimal(
+                "orders",
+                vec![EntityAttributeConfig::new(
+                    "id".into(),
+                    None,
+                    DataType::Int32,
+                    true,
+                    false,
+                )],
+                EntitySourceConfig::minimal("memory"),
+            ),
+            MemoryConnectorEntitySourceConfig::default(),
+        ));
+
+        let pool =
+            MemoryConnector::create_connection_pool(db, &NodeConfig::default(), &entities).unwrap();
+
+        assert_eq!(
+            pool.conf().get_data("orders"),
+            Some(vec![
+                vec![DataValue::Int32(1)],
+                vec![DataValue::Int32(2)],
+                vec![DataValue::Int32(3)],
+            ])
+        );
+    }
+
+    #[test]
+    fn test_memory_connector_generate_unknown_entity() {
+        let db =
+            MemoryConnector::parse_options(serde_yaml::from_str("orders: { rows: 3 }").unwrap())
+                .unwrap();
+
+        MemoryConnector::create_connection_pool(
+            db,
+            &NodeConfig::default(),
+            &ConnectorEntityConfig::new(),
+        )
+        .unwrap_err();
+    }
+}
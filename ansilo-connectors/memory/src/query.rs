@@ -27,6 +27,11 @@ impl MemoryQuery {
     pub fn new(query: sqlil::Query, params: Vec<(u32, DataType)>) -> Self {
         Self { query, params }
     }
+
+    /// Gets a key which identifies the query and its parameters
+    pub fn cache_key(&self) -> String {
+        format!("{:?}\n{:?}", self.query, self.params)
+    }
 }
 
 pub struct MemoryQueryHandle {
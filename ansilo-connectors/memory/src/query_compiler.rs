@@ -36,4 +36,8 @@ impl QueryCompiler for MemoryQueryCompiler {
     ) -> Result<Self::TQuery> {
         bail!("Unsupported")
     }
+
+    fn query_key(query: &Self::TQuery) -> Option<String> {
+        Some(query.cache_key())
+    }
 }
@@ -1,4 +1,4 @@
-use ansilo_connectors_base::common::entity::ConnectorEntityConfig;
+use ansilo_connectors_base::common::{entity::ConnectorEntityConfig, tls::TlsVerifyConfig};
 use ansilo_core::{
     config,
     err::{Context, Result},
@@ -14,6 +14,8 @@ pub struct MongodbConnectionConfig {
     /// Disables transactions (which aren't supported in standalone deployments)
     #[serde(default)]
     pub disable_transactions: bool,
+    /// Server certificate verification, enables TLS when set
+    pub tls: Option<TlsVerifyConfig>,
 }
 
 impl MongodbConnectionConfig {
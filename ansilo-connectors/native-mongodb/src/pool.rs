@@ -1,9 +1,9 @@
-use ansilo_connectors_base::interface::ConnectionPool;
+use ansilo_connectors_base::{common::tls::TlsVerifyConfig, interface::ConnectionPool};
 use ansilo_core::{
     auth::AuthContext,
-    err::{Context, Result},
+    err::{bail, Context, Result},
 };
-use mongodb::options::ClientOptions;
+use mongodb::options::{ClientOptions, Tls, TlsOptions};
 
 use crate::{conf::MongodbConnectionConfig, MongodbConnection};
 
@@ -24,8 +24,13 @@ impl ConnectionPool for MongodbConnectionUnpool {
     type TConnection = MongodbConnection;
 
     fn acquire(&mut self, _auth: Option<&AuthContext>) -> Result<Self::TConnection> {
-        let opts =
+        let mut opts =
             ClientOptions::parse(&self.conf.url).context("Failed to parse connection string")?;
+
+        if let Some(tls) = self.conf.tls.as_ref() {
+            opts.tls = Some(Tls::Enabled(Self::tls_options(tls)?));
+        }
+
         let con =
             mongodb::sync::Client::with_options(opts).context("Failed to connect to mongodb")?;
 
@@ -34,3 +39,24 @@ impl ConnectionPool for MongodbConnectionUnpool {
         Ok(MongodbConnection::new(self.conf.clone(), con, sess))
     }
 }
+
+impl MongodbConnectionUnpool {
+    fn tls_options(tls: &TlsVerifyConfig) -> Result<TlsOptions> {
+        Ok(match tls {
+            TlsVerifyConfig::System => TlsOptions::default(),
+            TlsVerifyConfig::CaBundle { ca_bundle } => TlsOptions::builder()
+                .ca_file_path(ca_bundle.clone())
+                .build(),
+            TlsVerifyConfig::Fingerprint { .. } => {
+                bail!("Pinned certificate fingerprints are not supported by the mongodb driver, use verify: ca_bundle with the certificate of the server instead")
+            }
+            TlsVerifyConfig::Disabled => {
+                tls.warn_if_disabled("mongodb");
+                TlsOptions::builder()
+                    .allow_invalid_certificates(true)
+                    .allow_invalid_hostnames(true)
+                    .build()
+            }
+        })
+    }
+}
@@ -42,6 +42,7 @@ pub fn connect_to_mongo(containers: &ContainerInstances) -> MongodbConnection {
             containers.get("mongo").unwrap().ip
         ),
         disable_transactions: false,
+        tls: None,
     };
 
     MongodbConnector::connect(config).unwrap()
@@ -0,0 +1,772 @@
+use std::collections::HashMap;
+
+use ansilo_connectors_base::interface::{ChangeBatch, ChangeStream, ConnectionPool, RowChange};
+use ansilo_core::{
+    config::{EntityAttributeConfig, EntityConfig},
+    data::{
+        chrono::{DateTime, NaiveDateTime, NaiveTime},
+        chrono_tz, DataType, DataValue, DateTimeWithTZ,
+    },
+    err::{bail, Context, Result},
+};
+use ansilo_logging::info;
+use ansilo_util_pg::query::pg_quote_identifier;
+use tokio_postgres::{types::ToSql, Row};
+
+use crate::{
+    runtime, PostgresConnectionConfig, PostgresConnectionPool, PostgresConnectionPoolConfig,
+    PostgresEntitySourceConfig, PostgresTableOptions,
+};
+
+/// The maximum length of a replication slot name
+const MAX_SLOT_NAME_LEN: usize = 63;
+
+/// Captures the changes made to the table of an entity using logical replication.
+///
+/// The changes are decoded from a replication slot by the built-in pgoutput plugin.
+/// They are peeked from the slot, which is only advanced once the changes are
+/// acknowledged, so no changes are lost if they fail to be applied.
+pub struct PostgresChangeStream {
+    /// The connection to the data source
+    pool: PostgresConnectionPool,
+    /// The replication slot the changes are decoded from
+    slot: String,
+    /// The publication defining which tables are replicated
+    publication: String,
+    /// The table of the entity
+    table: PostgresTableOptions,
+    /// The attributes of the entity, keyed by their column name
+    columns: HashMap<String, EntityAttributeConfig>,
+    /// The relations described by the decoded messages
+    relations: HashMap<u32, Relation>,
+}
+
+impl PostgresChangeStream {
+    /// Opens the change stream of the entity, creating the
+    /// replication slot and publication if they do not exist.
+    ///
+    /// The `consumer` uniquely identifies the reader of the changes and
+    /// is used to name the replication slot.
+    pub fn open(
+        conf: PostgresConnectionConfig,
+        entity: &EntityConfig,
+        consumer: &str,
+    ) -> Result<Self> {
+        let table = match PostgresEntitySourceConfig::parse(entity.source.options.clone())? {
+            PostgresEntitySourceConfig::Table(table) => table,
+        };
+
+        let slot = slot_name(consumer);
+        let publication = conf.cdc.as_ref().and_then(|c| c.publication.clone());
+        let columns = entity
+            .attributes
+            .iter()
+            .map(|a| {
+                let col = table.attribute_column_map.get(&a.id).unwrap_or(&a.id);
+                (col.clone(), a.clone())
+            })
+            .collect();
+
+        // Changes are read from a single connection
+        let pool = PostgresConnectionPool::new(PostgresConnectionConfig {
+            pool: Some(PostgresConnectionPoolConfig {
+                max_size: Some(1),
+                ..conf.pool.clone().unwrap_or_default()
+            }),
+            ..conf
+        })?;
+
+        let stream = Self {
+            pool,
+            publication: publication.clone().unwrap_or_else(|| slot.clone()),
+            slot,
+            table,
+            columns,
+            relations: HashMap::new(),
+        };
+
+        if publication.is_none() {
+            stream.create_publication()?;
+        }
+
+        stream.create_slot()?;
+
+        Ok(stream)
+    }
+
+    /// The name of the replication slot the changes are decoded from
+    pub fn slot(&self) -> &str {
+        &self.slot
+    }
+
+    fn create_publication(&self) -> Result<()> {
+        let exists = !self
+            .query(
+                "SELECT 1 FROM pg_publication WHERE pubname = $1",
+                &[&self.publication],
+            )?
+            .is_empty();
+
+        if exists {
+            return Ok(());
+        }
+
+        info!("Creating publication {}", self.publication);
+        self.query(
+            &format!(
+                "CREATE PUBLICATION {} FOR TABLE {}",
+                pg_quote_identifier(&self.publication),
+                self.qualified_table()
+            ),
+            &[],
+        )
+        .with_context(|| format!("Failed to create publication '{}'", self.publication))?;
+
+        Ok(())
+    }
+
+    fn create_slot(&self) -> Result<()> {
+        let exists = !self
+            .query(
+                "SELECT 1 FROM pg_replication_slots WHERE slot_name = $1",
+                &[&self.slot],
+            )?
+            .is_empty();
+
+        if exists {
+            return Ok(());
+        }
+
+        info!("Creating replication slot {}", self.slot);
+        self.query(
+            "SELECT pg_create_logical_replication_slot($1, 'pgoutput')",
+            &[&self.slot],
+        )
+        .with_context(|| format!("Failed to create replication slot '{}'", self.slot))?;
+
+        Ok(())
+    }
+
+    fn qualified_table(&self) -> String {
+        match self.table.schema_name.as_ref() {
+            Some(schema) => format!(
+                "{}.{}",
+                pg_quote_identifier(schema),
+                pg_quote_identifier(&self.table.table_name)
+            ),
+            None => pg_quote_identifier(&self.table.table_name),
+        }
+    }
+
+    fn query(&self, sql: &str, params: &[&(dyn ToSql + Sync)]) -> Result<Vec<Row>> {
+        let con = self.pool.clone().acquire(None)?;
+        let client = con.client();
+
+        runtime()
+            .block_on(client.query(sql, params))
+            .context("Failed to execute query")
+    }
+
+    /// Whether the relation is the table of the entity
+    fn is_captured(&self, relation: &Relation) -> bool {
+        relation.name == self.table.table_name
+            && self
+                .table
+                .schema_name
+                .as_ref()
+                .map_or(true, |s| s == &relation.namespace)
+    }
+
+    /// Converts the decoded message into the changes of the entity's rows
+    fn to_changes(&mut self, message: Message) -> Result<Vec<RowChange>> {
+        let (relation, change) = match message {
+            Message::Relation(relation) => {
+                self.relations.insert(relation.id, relation);
+                return Ok(vec![]);
+            }
+            Message::Insert { relation, new } => (relation, (None, Some(new))),
+            Message::Update { relation, key, new } => (relation, (key, Some(new))),
+            Message::Delete { relation, key } => (relation, (Some(key), None)),
+            Message::Truncate { relations } => {
+                let truncated = relations
+                    .iter()
+                    .filter_map(|id| self.relations.get(id))
+                    .any(|r| self.is_captured(r));
+
+                return Ok(if truncated {
+                    vec![RowChange::Truncate]
+                } else {
+                    vec![]
+                });
+            }
+            Message::Other => return Ok(vec![]),
+        };
+
+        let relation = self
+            .relations
+            .get(&relation)
+            .with_context(|| format!("Received change for unknown relation {relation}"))?;
+
+        if !self.is_captured(relation) {
+            return Ok(vec![]);
+        }
+
+        let key = change
+            .0
+            .map(|tuple| self.to_values(relation, tuple, true))
+            .transpose()?;
+        let new = change
+            .1
+            .map(|tuple| self.to_values(relation, tuple, false))
+            .transpose()?;
+
+        Ok(match (key, new) {
+            (Some(key), None) => vec![RowChange::Delete(key)],
+            (None, Some(new)) => vec![RowChange::Upsert(new)],
+            // The old key is only sent if it was changed by the update,
+            // in which case the row is moved to its new key
+            (Some(key), Some(new)) => {
+                let moved = key.iter().any(|(attr, val)| {
+                    new.iter()
+                        .find(|(a, _)| a == attr)
+                        .map_or(false, |(_, v)| v != val)
+                });
+
+                if moved {
+                    vec![RowChange::Delete(key), RowChange::Upsert(new)]
+                } else {
+                    vec![RowChange::Upsert(new)]
+                }
+            }
+            (None, None) => vec![],
+        })
+    }
+
+    /// Converts the tuple into the values of the entity's attributes.
+    ///
+    /// Unchanged values and columns which are not mapped to an attribute are omitted.
+    fn to_values(
+        &self,
+        relation: &Relation,
+        tuple: Tuple,
+        key: bool,
+    ) -> Result<Vec<(String, DataValue)>> {
+        relation
+            .columns
+            .iter()
+            .zip(tuple.into_iter())
+            .filter_map(|(col, val)| {
+                let attr = self.columns.get(col)?;
+
+                if key && !attr.primary_key {
+                    return None;
+                }
+
+                let val = match val {
+                    TupleValue::Null => Ok(DataValue::Null),
+                    TupleValue::Unchanged => return None,
+                    TupleValue::Text(text) => parse_pg_text(&text, &attr.r#type)
+                        .with_context(|| format!("Failed to parse value of column '{col}'")),
+                };
+
+                Some(val.map(|val| (attr.id.clone(), val)))
+            })
+            .collect()
+    }
+}
+
+impl ChangeStream for PostgresChangeStream {
+    fn read(&mut self, max: usize) -> Result<ChangeBatch> {
+        let rows = self
+            .query(
+                r#"
+                SELECT lsn::text, data
+                FROM pg_logical_slot_peek_binary_changes(
+                    $1, NULL, $2,
+                    'proto_version', '1',
+                    'publication_names', $3
+                )
+            "#,
+                &[
+                    &self.slot,
+                    &(max.min(i32::MAX as usize) as i32),
+                    &self.publication,
+                ],
+            )
+            .with_context(|| format!("Failed to read changes from slot '{}'", self.slot))?;
+
+        let mut batch = ChangeBatch::default();
+
+        for row in rows {
+            let lsn: String = row.get(0);
+            let data: Vec<u8> = row.get(1);
+            let message = Message::decode(&data)
+                .with_context(|| format!("Failed to decode message at {lsn}"))?;
+
+            batch.changes.extend(self.to_changes(message)?);
+            batch.position = Some(lsn);
+        }
+
+        Ok(batch)
+    }
+
+    fn ack(&mut self, position: &str) -> Result<()> {
+        self.query(
+            "SELECT pg_replication_slot_advance($1, $2::text::pg_lsn)",
+            &[&self.slot, &position],
+        )
+        .with_context(|| format!("Failed to advance slot '{}'", self.slot))?;
+
+        Ok(())
+    }
+}
+
+/// Gets the name of the replication slot of the consumer.
+///
+/// Slot names may only contain lower case letters, numbers and underscores.
+pub fn slot_name(consumer: &str) -> String {
+    consumer
+        .chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            c @ ('a'..='z' | '0'..='9' | '_') => c,
+            _ => '_',
+        })
+        .take(MAX_SLOT_NAME_LEN)
+        .collect()
+}
+
+/// Parses a value in the text format of postgres
+fn parse_pg_text(text: &str, r#type: &DataType) -> Result<DataValue> {
+    Ok(match r#type {
+        DataType::Boolean => DataValue::Boolean(match text {
+            "t" => true,
+            "f" => false,
+            _ => bail!("Invalid boolean '{text}'"),
+        }),
+        DataType::Binary => DataValue::Binary(parse_bytea(text)?),
+        DataType::Time => {
+            DataValue::Time(NaiveTime::parse_from_str(text, "%H:%M:%S%.f").context("Invalid time")?)
+        }
+        DataType::DateTime => DataValue::DateTime(
+            NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+                .context("Invalid timestamp")?,
+        ),
+        DataType::DateTimeWithTZ => {
+            let dt = DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z")
+                .context("Invalid timestamp with time zone")?;
+
+            DataValue::DateTimeWithTZ(DateTimeWithTZ::new(dt.naive_utc(), chrono_tz::UTC))
+        }
+        _ => DataValue::Utf8String(text.into()).try_coerce_into(r#type)?,
+    })
+}
+
+/// Parses a bytea in the hex format, eg "\x01ff"
+fn parse_bytea(text: &str) -> Result<Vec<u8>> {
+    let hex = text
+        .strip_prefix("\\x")
+        .context("Only the hex format is supported for bytea")?;
+
+    if hex.len() % 2 != 0 {
+        bail!("Invalid hex length");
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .context("Invalid hex")
+        })
+        .collect()
+}
+
+/// A message of the pgoutput logical replication protocol
+/// @see https://www.postgresql.org/docs/current/protocol-logicalrep-message-formats.html
+#[derive(Debug, Clone, PartialEq)]
+enum Message {
+    Relation(Relation),
+    Insert {
+        relation: u32,
+        new: Tuple,
+    },
+    Update {
+        relation: u32,
+        key: Option<Tuple>,
+        new: Tuple,
+    },
+    Delete {
+        relation: u32,
+        key: Tuple,
+    },
+    Truncate {
+        relations: Vec<u32>,
+    },
+    /// Messages which do not change any rows, such as begin and commit
+    Other,
+}
+
+/// Describes the columns of a replicated table
+#[derive(Debug, Clone, PartialEq)]
+struct Relation {
+    id: u32,
+    namespace: String,
+    name: String,
+    columns: Vec<String>,
+}
+
+type Tuple = Vec<TupleValue>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum TupleValue {
+    Null,
+    /// An unchanged TOASTed value, the actual value is not sent
+    Unchanged,
+    Text(String),
+}
+
+impl Message {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut buf = Reader(data);
+
+        Ok(match buf.u8()? {
+            b'R' => {
+                let id = buf.u32()?;
+                let namespace = buf.string()?;
+                let name = buf.string()?;
+                let _replica_identity = buf.u8()?;
+                let columns = (0..buf.u16()?)
+                    .map(|_| {
+                        let _flags = buf.u8()?;
+                        let name = buf.string()?;
+                        let _type = buf.u32()?;
+                        let _modifier = buf.u32()?;
+                        Ok(name)
+                    })
+                    .collect::<Result<_>>()?;
+
+                Self::Relation(Relation {
+                    id,
+                    namespace,
+                    name,
+                    columns,
+                })
+            }
+            b'I' => {
+                let relation = buf.u32()?;
+                buf.expect(b'N')?;
+
+                Self::Insert {
+                    relation,
+                    new: buf.tuple()?,
+                }
+            }
+            b'U' => {
+                let relation = buf.u32()?;
+                let key = match buf.u8()? {
+                    b'K' | b'O' => {
+                        let key = buf.tuple()?;
+                        buf.expect(b'N')?;
+                        Some(key)
+                    }
+                    b'N' => None,
+                    b => bail!("Unexpected byte '{}' in update message", b as char),
+                };
+
+                Self::Update {
+                    relation,
+                    key,
+                    new: buf.tuple()?,
+                }
+            }
+            b'D' => {
+                let relation = buf.u32()?;
+                match buf.u8()? {
+                    b'K' | b'O' => {}
+                    b => bail!("Unexpected byte '{}' in delete message", b as char),
+                }
+
+                Self::Delete {
+                    relation,
+                    key: buf.tuple()?,
+                }
+            }
+            b'T' => {
+                let count = buf.u32()?;
+                let _options = buf.u8()?;
+
+                Self::Truncate {
+                    relations: (0..count).map(|_| buf.u32()).collect::<Result<_>>()?,
+                }
+            }
+            _ => Self::Other,
+        })
+    }
+}
+
+/// Reads the fields of a pgoutput message
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            bail!("Unexpected end of message");
+        }
+
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into()?))
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<()> {
+        let b = self.u8()?;
+
+        if b != expected {
+            bail!(
+                "Expected byte '{}' but found '{}'",
+                expected as char,
+                b as char
+            );
+        }
+
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self
+            .0
+            .iter()
+            .position(|b| *b == 0)
+            .context("Unterminated string")?;
+        let string = String::from_utf8(self.bytes(len)?.to_vec()).context("Invalid string")?;
+        self.bytes(1)?;
+
+        Ok(string)
+    }
+
+    fn tuple(&mut self) -> Result<Tuple> {
+        (0..self.u16()?)
+            .map(|_| {
+                Ok(match self.u8()? {
+                    b'n' => TupleValue::Null,
+                    b'u' => TupleValue::Unchanged,
+                    b't' => {
+                        let len = self.u32()? as usize;
+                        TupleValue::Text(
+                            String::from_utf8(self.bytes(len)?.to_vec())
+                                .context("Invalid text value")?,
+                        )
+                    }
+                    b => bail!("Unexpected tuple value type '{}'", b as char),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::data::chrono::NaiveDate;
+
+    use super::*;
+
+    /// Encodes the fields of a message as per the pgoutput protocol
+    #[derive(Default)]
+    struct Writer(Vec<u8>);
+
+    impl Writer {
+        fn u8(mut self, b: u8) -> Self {
+            self.0.push(b);
+            self
+        }
+
+        fn u16(mut self, n: u16) -> Self {
+            self.0.extend(n.to_be_bytes());
+            self
+        }
+
+        fn u32(mut self, n: u32) -> Self {
+            self.0.extend(n.to_be_bytes());
+            self
+        }
+
+        fn string(mut self, s: &str) -> Self {
+            self.0.extend(s.as_bytes());
+            self.0.push(0);
+            self
+        }
+
+        fn tuple(self, values: &[Option<&str>]) -> Self {
+            values
+                .iter()
+                .fold(self.u16(values.len() as _), |w, v| match v {
+                    Some(v) => {
+                        let mut w = w.u8(b't').u32(v.len() as _);
+                        w.0.extend(v.as_bytes());
+                        w
+                    }
+                    None => w.u8(b'n'),
+                })
+        }
+    }
+
+    fn relation_message() -> Vec<u8> {
+        Writer::default()
+            .u8(b'R')
+            .u32(16384)
+            .string("public")
+            .string("orders")
+            .u8(b'd')
+            .u16(2)
+            .u8(1)
+            .string("id")
+            .u32(23)
+            .u32(u32::MAX)
+            .u8(0)
+            .string("status")
+            .u32(25)
+            .u32(u32::MAX)
+            .0
+    }
+
+    #[test]
+    fn test_slot_name() {
+        assert_eq!(slot_name("ansilo_node-1_Orders"), "ansilo_node_1_orders");
+        assert_eq!(slot_name(&"a".repeat(100)).len(), MAX_SLOT_NAME_LEN);
+    }
+
+    #[test]
+    fn test_decode_relation() {
+        assert_eq!(
+            Message::decode(&relation_message()).unwrap(),
+            Message::Relation(Relation {
+                id: 16384,
+                namespace: "public".into(),
+                name: "orders".into(),
+                columns: vec!["id".into(), "status".into()],
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_insert() {
+        let data = Writer::default()
+            .u8(b'I')
+            .u32(16384)
+            .u8(b'N')
+            .tuple(&[Some("1"), None])
+            .0;
+
+        assert_eq!(
+            Message::decode(&data).unwrap(),
+            Message::Insert {
+                relation: 16384,
+                new: vec![TupleValue::Text("1".into()), TupleValue::Null],
+            }
+        );
+    }
+
+    #[test]
+    fn test_decode_update_with_key() {
+        let data = Writer::default()
+            .u8(b'U')
+            .u32(16384)
+            .u8(b'K')
+            .tuple(&[Some("1"), None])
+            .u8(b'N')
+            .tuple(&[Some("2"), Some("new")])
+            .0;
+
+        assert_eq!(
+            Message::decode(&data).unwrap(),
+            Message::Update {
+                relation: 16384,
+                key: Some(vec![TupleValue::Text("1".into()), TupleValue::Null]),
+                new: vec![TupleValue::Text("2".into()), TupleValue::Text("new".into())],
+            }
+        );
+    }
+
+    #[test]
+    fn test_decode_delete_and_truncate() {
+        let data = Writer::default()
+            .u8(b'D')
+            .u32(16384)
+            .u8(b'K')
+            .tuple(&[Some("1"), None])
+            .0;
+
+        assert_eq!(
+            Message::decode(&data).unwrap(),
+            Message::Delete {
+                relation: 16384,
+                key: vec![TupleValue::Text("1".into()), TupleValue::Null],
+            }
+        );
+
+        let data = Writer::default().u8(b'T').u32(1).u8(0).u32(16384).0;
+
+        assert_eq!(
+            Message::decode(&data).unwrap(),
+            Message::Truncate {
+                relations: vec![16384]
+            }
+        );
+    }
+
+    #[test]
+    fn test_decode_other_and_invalid() {
+        assert_eq!(Message::decode(b"B").unwrap(), Message::Other);
+        Message::decode(b"I\x00\x00").unwrap_err();
+        Message::decode(&relation_message()[..10]).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_pg_text() {
+        assert_eq!(
+            parse_pg_text("t", &DataType::Boolean).unwrap(),
+            DataValue::Boolean(true)
+        );
+        assert_eq!(
+            parse_pg_text("123", &DataType::Int32).unwrap(),
+            DataValue::Int32(123)
+        );
+        assert_eq!(
+            parse_pg_text("\\x01ff", &DataType::Binary).unwrap(),
+            DataValue::Binary(vec![1, 255])
+        );
+        assert_eq!(
+            parse_pg_text("2023-01-02 03:04:05.5", &DataType::DateTime).unwrap(),
+            DataValue::DateTime(
+                NaiveDate::from_ymd_opt(2023, 1, 2)
+                    .unwrap()
+                    .and_hms_milli_opt(3, 4, 5, 500)
+                    .unwrap()
+            )
+        );
+        assert_eq!(
+            parse_pg_text("2023-01-02 03:04:05+10", &DataType::DateTimeWithTZ).unwrap(),
+            DataValue::DateTimeWithTZ(DateTimeWithTZ::new(
+                NaiveDate::from_ymd_opt(2023, 1, 1)
+                    .unwrap()
+                    .and_hms_opt(17, 4, 5)
+                    .unwrap(),
+                chrono_tz::UTC
+            ))
+        );
+        parse_pg_text("yes", &DataType::Boolean).unwrap_err();
+        parse_pg_text("\\x0", &DataType::Binary).unwrap_err();
+    }
+}
@@ -1,6 +1,6 @@
 use std::{collections::HashMap, convert::TryInto, str::FromStr, time::Duration};
 
-use ansilo_connectors_base::common::entity::ConnectorEntityConfig;
+use ansilo_connectors_base::common::{entity::ConnectorEntityConfig, tls::TlsVerifyConfig};
 use ansilo_core::{
     config,
     err::{Context, Error, Result},
@@ -30,15 +30,33 @@ pub struct PostgresConnectionConfig {
     pub url: Option<String>,
     /// Connection pool config
     pub pool: Option<PostgresConnectionPoolConfig>,
+    /// Server certificate verification, defaults to the system trust store
+    /// Applies when TLS is used, as per the "sslmode" option
+    pub tls: Option<TlsVerifyConfig>,
+    /// Change data capture config
+    pub cdc: Option<PostgresCdcConfig>,
 }
 
 /// The connection pool config
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct PostgresConnectionPoolConfig {
+    /// Min pool size, these connections are established when the pool is created
+    pub min_size: Option<u16>,
     /// Max pool size
     pub max_size: Option<u16>,
     /// How lont to wait when acquiring a connection
     pub connection_timeout: Option<Duration>,
+    /// How often idle connections are validated to keep them alive
+    pub keepalive_interval: Option<Duration>,
+}
+
+/// The change data capture config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct PostgresCdcConfig {
+    /// The publication the changes are read from, which must include the tables
+    /// of the captured entities. By default, a publication is created for each
+    /// entity with the same name as its replication slot.
+    pub publication: Option<String>,
 }
 
 impl PostgresConnectionConfig {
@@ -1,3 +1,5 @@
+mod cdc;
+pub use cdc::*;
 mod conf;
 use ansilo_connectors_base::{
     common::entity::ConnectorEntityConfig,
@@ -28,6 +30,8 @@ mod result_set;
 pub use result_set::*;
 mod runtime;
 pub use runtime::*;
+mod tls;
+pub use tls::*;
 
 /// The connector for Postgres built on tokio-postgres
 #[derive(Default)]
@@ -5,31 +5,36 @@ use std::{
 
 use ansilo_connectors_base::interface::ConnectionPool;
 use ansilo_core::{auth::AuthContext, err::Result};
-use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
-use native_tls::TlsConnector;
-use postgres_native_tls::MakeTlsConnector;
+use ansilo_logging::{debug, warn};
+use deadpool_postgres::{Manager, ManagerConfig, Object, Pool, RecyclingMethod};
+use tokio::sync::broadcast::{self, Receiver, Sender};
 
-use crate::{runtime, PostgresConnection, PostgresConnectionConfig};
+use crate::{runtime, PostgresConnection, PostgresConnectionConfig, PostgresTlsConnector};
 
 /// Postgres connection pool based on deadpool
 #[derive(Clone)]
 pub struct PostgresConnectionPool {
     pool: Pool,
+    /// Upon drop will shutdown background tasks
+    _terminator: Option<Sender<()>>,
 }
 
 impl PostgresConnectionPool {
     pub fn new(conf: PostgresConnectionConfig) -> Result<Self> {
         let pool_conf = conf.pool.clone().unwrap_or_default();
+        let max_size = pool_conf.max_size.unwrap_or(20);
+        let min_size = pool_conf.min_size.unwrap_or(0).min(max_size) as usize;
+        let tls = PostgresTlsConnector::new(conf.tls.clone().unwrap_or_default())?;
 
         let pool = Pool::builder(Manager::from_config(
             conf.try_into()?,
-            MakeTlsConnector::new(TlsConnector::new()?),
+            tls,
             ManagerConfig {
                 recycling_method: RecyclingMethod::Fast,
             },
         ))
         .runtime(deadpool_postgres::Runtime::Tokio1)
-        .max_size(pool_conf.max_size.unwrap_or(20) as _)
+        .max_size(max_size as _)
         .create_timeout(Some(
             pool_conf
                 .connection_timeout
@@ -37,7 +42,75 @@ impl PostgresConnectionPool {
         ))
         .build()?;
 
-        Ok(Self { pool })
+        runtime().block_on(Self::warm_up(&pool, min_size));
+
+        let terminator = pool_conf.keepalive_interval.map(|interval| {
+            let (terminator, receiver) = broadcast::channel(1);
+            Self::keep_alive(pool.clone(), min_size, interval, receiver);
+            terminator
+        });
+
+        Ok(Self {
+            pool,
+            _terminator: terminator,
+        })
+    }
+
+    /// Establishes connections until the pool contains at least `min_size` connections.
+    /// Failures are logged rather than returned as the pool remains usable,
+    /// connections will be established on demand once the server is reachable.
+    async fn warm_up(pool: &Pool, min_size: usize) {
+        let missing = min_size.saturating_sub(pool.status().size);
+        let mut cons = Vec::with_capacity(missing);
+
+        for _ in 0..missing {
+            match pool.get().await {
+                Ok(con) => cons.push(con),
+                Err(err) => {
+                    warn!(
+                        "Failed to establish postgres connection during warm up: {:?}",
+                        err
+                    );
+                    break;
+                }
+            }
+        }
+
+        if !cons.is_empty() {
+            debug!("Established {} postgres connections", cons.len());
+        }
+    }
+
+    /// Periodically validates the idle connections in the pool so they are not
+    /// closed by the remote server or intermediate firewalls while unused.
+    fn keep_alive(pool: Pool, min_size: usize, interval: Duration, mut terminator: Receiver<()>) {
+        runtime().spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(interval) => {}
+                    _ = terminator.recv() => return,
+                }
+
+                let mut cons = vec![];
+                for _ in 0..pool.status().available.max(0) {
+                    let con = match pool.get().await {
+                        Ok(con) => con,
+                        Err(_) => break,
+                    };
+
+                    if let Err(err) = con.simple_query("").await {
+                        debug!("Discarding broken postgres connection: {:?}", err);
+                        let _ = Object::take(con);
+                        continue;
+                    }
+
+                    cons.push(con);
+                }
+                drop(cons);
+
+                Self::warm_up(&pool, min_size).await;
+            }
+        });
     }
 }
 
@@ -39,6 +39,11 @@ impl PostgresQuery {
             params,
         }
     }
+
+    /// Gets a key which identifies the query and its parameters
+    pub fn cache_key(&self) -> String {
+        format!("{}\n{:?}", self.sql, self.params)
+    }
 }
 
 /// Postgres prepared query
@@ -48,6 +48,10 @@ impl<T: DerefMut<Target = Client>> QueryCompiler for PostgresQueryCompiler<T> {
             params.into_iter().map(|p| QueryParam::dynamic(p)).collect(),
         ))
     }
+
+    fn query_key(query: &Self::TQuery) -> Option<String> {
+        Some(query.cache_key())
+    }
 }
 
 impl<T: DerefMut<Target = Client>> PostgresQueryCompiler<T> {
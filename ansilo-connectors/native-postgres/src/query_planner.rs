@@ -39,67 +39,16 @@ impl<T: DerefMut<Target = Client>> QueryPlanner for PostgresQueryPlanner<T> {
         connection: &mut Self::TConnection,
         entity: &EntitySource<PostgresEntitySourceConfig>,
     ) -> Result<OperationCost> {
-        let mut query = connection.prepare(PostgresQuery::new(
-            format!(
-                r#"EXPLAIN (FORMAT JSON) SELECT * FROM {}"#,
-                PostgresQueryCompiler::<T>::compile_source_identifier(&entity.source)?
+        Self::explain_cost(
+            connection,
+            PostgresQuery::new(
+                format!(
+                    "SELECT * FROM {}",
+                    PostgresQueryCompiler::<T>::compile_source_identifier(&entity.source)?
+                ),
+                vec![],
             ),
-            vec![],
-        ))?;
-
-        let mut result_set = query.execute_query()?.reader()?;
-        let value = result_set
-            .read_data_value()?
-            .context("Unexpected empty result set")?;
-
-        let plan = match value.clone() {
-            DataValue::JSON(plan) => plan,
-            _ => bail!("Unexpected data value returned: {:?}", value),
-        };
-
-        let plan: serde_json::Value = serde_json::from_str(&plan)?;
-        let plan = plan
-            .as_array()
-            .context("Expected array")?
-            .get(0)
-            .context("Expected not empty")?
-            .as_object()
-            .context("Expected object")?
-            .get("Plan")
-            .context("Expected Plan key")?
-            .as_object()
-            .context("Expected object")?;
-
-        let num_rows = plan
-            .get("Plan Rows")
-            .context("Expected Plan Rows key")?
-            .as_u64()
-            .context("Expected row count integer")?;
-
-        let width = plan
-            .get("Plan Width")
-            .context("Expected Plan Width key")?
-            .as_u64()
-            .context("Expected width integer")?;
-
-        let startup_cost = plan
-            .get("Startup Cost")
-            .context("Expected Startup Cost key")?
-            .as_f64()
-            .context("Expected startup cost float64")?;
-
-        let total_cost = plan
-            .get("Total Cost")
-            .context("Expected Total Cost key")?
-            .as_f64()
-            .context("Expected total cost float64")?;
-
-        Ok(OperationCost::new(
-            Some(num_rows as _),
-            Some(width as _),
-            Some(startup_cost),
-            Some(total_cost),
-        ))
+        )
     }
 
     fn get_row_id_exprs(
@@ -272,6 +221,73 @@ impl<T: DerefMut<Target = Client>> QueryPlanner for PostgresQueryPlanner<T> {
 }
 
 impl<T: DerefMut<Target = Client>> PostgresQueryPlanner<T> {
+    /// Gets the cost estimate of the query from the plan chosen by postgres.
+    ///
+    /// The query must not have any dynamic parameters.
+    pub fn explain_cost(
+        connection: &mut PostgresConnection<T>,
+        query: PostgresQuery,
+    ) -> Result<OperationCost> {
+        let mut query = connection.prepare(PostgresQuery::new(
+            format!("EXPLAIN (FORMAT JSON) {}", query.sql),
+            query.params,
+        ))?;
+
+        let mut result_set = query.execute_query()?.reader()?;
+        let value = result_set
+            .read_data_value()?
+            .context("Unexpected empty result set")?;
+
+        let plan = match value.clone() {
+            DataValue::JSON(plan) => plan,
+            _ => bail!("Unexpected data value returned: {:?}", value),
+        };
+
+        let plan: serde_json::Value = serde_json::from_str(&plan)?;
+        let plan = plan
+            .as_array()
+            .context("Expected array")?
+            .get(0)
+            .context("Expected not empty")?
+            .as_object()
+            .context("Expected object")?
+            .get("Plan")
+            .context("Expected Plan key")?
+            .as_object()
+            .context("Expected object")?;
+
+        let num_rows = plan
+            .get("Plan Rows")
+            .context("Expected Plan Rows key")?
+            .as_u64()
+            .context("Expected row count integer")?;
+
+        let width = plan
+            .get("Plan Width")
+            .context("Expected Plan Width key")?
+            .as_u64()
+            .context("Expected width integer")?;
+
+        let startup_cost = plan
+            .get("Startup Cost")
+            .context("Expected Startup Cost key")?
+            .as_f64()
+            .context("Expected startup cost float64")?;
+
+        let total_cost = plan
+            .get("Total Cost")
+            .context("Expected Total Cost key")?
+            .as_f64()
+            .context("Expected total cost float64")?;
+
+        Ok(OperationCost::new(
+            Some(num_rows as _),
+            Some(width as _),
+            Some(startup_cost),
+            Some(total_cost),
+        ))
+    }
+
     fn select_add_col(
         select: &mut sql::Select,
         expr: sql::Expr,
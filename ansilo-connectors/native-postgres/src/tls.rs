@@ -0,0 +1,220 @@
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context as TaskContext, Poll},
+};
+
+use ansilo_connectors_base::common::tls::TlsVerifyConfig;
+use ansilo_core::err::{Context, Result};
+use native_tls::{Certificate, Identity};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_postgres::tls::{ChannelBinding, MakeTlsConnect, TlsConnect};
+
+type BoxError = Box<dyn std::error::Error + Sync + Send>;
+
+/// Establishes TLS connections to postgres, verifying the server
+/// certificate as per the configured options.
+///
+/// This mirrors postgres-native-tls with the addition of
+/// checking the certificate against a pinned fingerprint.
+#[derive(Clone)]
+pub struct PostgresTlsConnector {
+    connector: native_tls::TlsConnector,
+    verify: TlsVerifyConfig,
+}
+
+impl PostgresTlsConnector {
+    pub fn new(verify: TlsVerifyConfig) -> Result<Self> {
+        Self::build(verify, None, true)
+    }
+
+    /// Creates a connector which verifies the certificate chain of the server
+    /// but not its hostname, as per the "verify-ca" sslmode of libpq
+    pub fn without_hostname_verification(verify: TlsVerifyConfig) -> Result<Self> {
+        Self::build(verify, None, false)
+    }
+
+    /// Creates a connector which also presents a client certificate,
+    /// for servers which require mutual TLS
+    pub fn with_identity(verify: TlsVerifyConfig, cert_pem: &[u8], key_pem: &[u8]) -> Result<Self> {
+        let identity = Identity::from_pkcs8(cert_pem, key_pem)
+            .context("Failed to parse TLS client certificate and key")?;
+
+        Self::build(verify, Some(identity), true)
+    }
+
+    fn build(
+        verify: TlsVerifyConfig,
+        identity: Option<Identity>,
+        verify_hostname: bool,
+    ) -> Result<Self> {
+        verify.validate()?;
+
+        let mut builder = native_tls::TlsConnector::builder();
+
+        match &verify {
+            TlsVerifyConfig::System => {}
+            TlsVerifyConfig::CaBundle { .. } => {
+                builder.disable_built_in_roots(true);
+
+                for cert in verify.read_ca_bundle()? {
+                    builder.add_root_certificate(
+                        Certificate::from_pem(&cert).context("Failed to parse CA certificate")?,
+                    );
+                }
+            }
+            // The certificate chain is not verified, instead the leaf
+            // certificate is checked against the fingerprint after the handshake
+            TlsVerifyConfig::Fingerprint { .. } | TlsVerifyConfig::Disabled => {
+                builder.danger_accept_invalid_certs(true);
+                builder.danger_accept_invalid_hostnames(true);
+            }
+        }
+
+        if !verify_hostname {
+            builder.danger_accept_invalid_hostnames(true);
+        }
+
+        if let Some(identity) = identity {
+            builder.identity(identity);
+        }
+
+        Ok(Self {
+            connector: builder
+                .build()
+                .context("Failed to initialise tls connector")?,
+            verify,
+        })
+    }
+}
+
+impl<S> MakeTlsConnect<S> for PostgresTlsConnector
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Stream = PostgresTlsStream<S>;
+    type TlsConnect = PostgresTlsConnect;
+    type Error = BoxError;
+
+    fn make_tls_connect(&mut self, domain: &str) -> Result<Self::TlsConnect, Self::Error> {
+        self.verify.warn_if_disabled(domain);
+
+        Ok(PostgresTlsConnect {
+            connector: self.connector.clone().into(),
+            verify: self.verify.clone(),
+            domain: domain.to_string(),
+        })
+    }
+}
+
+/// Establishes a single TLS connection
+pub struct PostgresTlsConnect {
+    connector: tokio_native_tls::TlsConnector,
+    verify: TlsVerifyConfig,
+    domain: String,
+}
+
+impl<S> TlsConnect<S> for PostgresTlsConnect
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Stream = PostgresTlsStream<S>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Stream, Self::Error>> + Send>>;
+
+    fn connect(self, stream: S) -> Self::Future {
+        Box::pin(async move {
+            let stream = self.connector.connect(&self.domain, stream).await?;
+
+            if let TlsVerifyConfig::Fingerprint { .. } = &self.verify {
+                let cert = stream
+                    .get_ref()
+                    .peer_certificate()?
+                    .ok_or("Server did not present a certificate")?;
+
+                self.verify.verify_fingerprint(&cert.to_der()?)?;
+            }
+
+            Ok(PostgresTlsStream(stream))
+        })
+    }
+}
+
+/// A TLS stream to postgres
+pub struct PostgresTlsStream<S>(tokio_native_tls::TlsStream<S>);
+
+impl<S> AsyncRead for PostgresTlsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl<S> AsyncWrite for PostgresTlsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+impl<S> tokio_postgres::tls::TlsStream for PostgresTlsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn channel_binding(&self) -> ChannelBinding {
+        match self.0.get_ref().tls_server_end_point().ok().flatten() {
+            Some(buf) => ChannelBinding::tls_server_end_point(buf),
+            None => ChannelBinding::none(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_postgres_tls_connector_new() {
+        PostgresTlsConnector::new(TlsVerifyConfig::System).unwrap();
+        PostgresTlsConnector::new(TlsVerifyConfig::Disabled).unwrap();
+        PostgresTlsConnector::without_hostname_verification(TlsVerifyConfig::System).unwrap();
+        PostgresTlsConnector::new(TlsVerifyConfig::Fingerprint {
+            fingerprint: "invalid".into(),
+        })
+        .err()
+        .unwrap();
+        PostgresTlsConnector::new(TlsVerifyConfig::CaBundle {
+            ca_bundle: "/non/existant/ca.pem".into(),
+        })
+        .err()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_postgres_tls_connector_with_identity_invalid() {
+        PostgresTlsConnector::with_identity(TlsVerifyConfig::System, b"invalid", b"invalid")
+            .err()
+            .unwrap();
+    }
+}
@@ -35,6 +35,11 @@ impl SqliteQuery {
             params,
         }
     }
+
+    /// Gets a key which identifies the query and its parameters
+    pub fn cache_key(&self) -> String {
+        format!("{}\n{:?}", self.sql, self.params)
+    }
 }
 
 /// Sqlite prepared query
@@ -1,10 +1,16 @@
+use std::{fs, path::PathBuf};
+
+use ansilo_connectors_base::common::tls::TlsVerifyConfig;
+use ansilo_connectors_native_postgres::PostgresTlsConnector;
 use ansilo_core::{
     config,
     err::{Context, Result},
 };
-use reqwest::Url;
+use reqwest::{blocking::Client, Certificate, Identity, Url};
 use serde::{Deserialize, Serialize};
 
+use crate::sync::PeerSyncConfig;
+
 /// The connection config
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PeerConfig {
@@ -17,6 +23,45 @@ pub struct PeerConfig {
     /// Option to explicitly define the password
     /// Otherwise, passthrough authentication will be used
     pub password: Option<String>,
+    /// Server certificate verification, defaults to the system trust store
+    pub tls: Option<TlsVerifyConfig>,
+    /// If set, the entities of the peer are periodically re-imported
+    /// to keep them in sync with the peer's catalog
+    #[serde(default)]
+    pub sync: Option<PeerSyncConfig>,
+    /// The certificate this node presents to the peer, for peers
+    /// which require mutual TLS
+    #[serde(default)]
+    pub identity: Option<PeerIdentityConfig>,
+}
+
+/// The client certificate identifying this node to the peer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerIdentityConfig {
+    /// The path of the pem-encoded certificate file
+    pub certificate: PathBuf,
+    /// The path of the pem-encoded pkcs8 private key file
+    pub private_key: PathBuf,
+}
+
+impl PeerIdentityConfig {
+    /// Reads the pem-encoded certificate and private key
+    pub fn read(&self) -> Result<(Vec<u8>, Vec<u8>)> {
+        let cert = fs::read(&self.certificate).with_context(|| {
+            format!(
+                "Failed to read TLS client certificate {}",
+                self.certificate.display()
+            )
+        })?;
+        let key = fs::read(&self.private_key).with_context(|| {
+            format!(
+                "Failed to read TLS client private key {}",
+                self.private_key.display()
+            )
+        })?;
+
+        Ok((cert, key))
+    }
 }
 
 impl PeerConfig {
@@ -24,4 +69,62 @@ impl PeerConfig {
         config::from_value::<Self>(options)
             .context("Failed to parse connection configuration options")
     }
+
+    /// Gets the server certificate verification options
+    pub fn tls(&self) -> TlsVerifyConfig {
+        self.tls.clone().unwrap_or_default()
+    }
+
+    /// Creates a connector for postgres connections to the peer
+    pub fn tls_connector(&self) -> Result<PostgresTlsConnector> {
+        match self.identity.as_ref() {
+            Some(identity) => {
+                let (cert, key) = identity.read()?;
+                PostgresTlsConnector::with_identity(self.tls(), &cert, &key)
+            }
+            None => PostgresTlsConnector::new(self.tls()),
+        }
+    }
+
+    /// Creates a http client for requests to the peer, verifying the
+    /// server certificate as per the tls options and presenting the
+    /// client certificate, if configured.
+    /// Pinned fingerprints must be checked against the response using
+    /// `TlsVerifyConfig::verify_fingerprint`.
+    pub fn http_client(&self) -> Result<Client> {
+        let tls = self.tls();
+        tls.validate()?;
+        tls.warn_if_disabled(self.url.as_str());
+
+        let mut builder = Client::builder();
+
+        match &tls {
+            TlsVerifyConfig::System => {}
+            TlsVerifyConfig::CaBundle { .. } => {
+                builder = builder.tls_built_in_root_certs(false);
+
+                for cert in tls.read_ca_bundle()? {
+                    builder = builder.add_root_certificate(
+                        Certificate::from_pem(&cert).context("Failed to parse CA certificate")?,
+                    );
+                }
+            }
+            TlsVerifyConfig::Fingerprint { .. } => {
+                builder = builder.danger_accept_invalid_certs(true).tls_info(true);
+            }
+            TlsVerifyConfig::Disabled => {
+                builder = builder.danger_accept_invalid_certs(true);
+            }
+        }
+
+        if let Some(identity) = self.identity.as_ref() {
+            let (cert, key) = identity.read()?;
+            builder = builder.identity(
+                Identity::from_pkcs8_pem(&cert, &key)
+                    .context("Failed to parse TLS client certificate and key")?,
+            );
+        }
+
+        builder.build().context("Failed to initialise http client")
+    }
 }
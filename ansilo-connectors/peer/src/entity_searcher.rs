@@ -5,7 +5,11 @@ use ansilo_core::{
     web::catalog::{Catalog, CatalogEntitySource},
 };
 
-use ansilo_connectors_base::interface::{EntityDiscoverOptions, EntitySearcher};
+use ansilo_connectors_base::{
+    common::tls::TlsVerifyConfig,
+    interface::{EntityDiscoverOptions, EntitySearcher},
+};
+use reqwest::tls::TlsInfo;
 
 use crate::{conf::PeerConfig, PostgresConnection, PostgresTableOptions};
 
@@ -42,8 +46,25 @@ impl PeerEntitySearcher {
         let mut url = conf.url.clone();
         url.set_path("/api/v1/catalog");
 
-        let catalog = reqwest::blocking::get(url.clone())
-            .context("Failed to retrieve schema from peer")?
+        let res = conf
+            .http_client()?
+            .get(url.clone())
+            .send()
+            .context("Failed to retrieve schema from peer")?;
+
+        if let tls @ TlsVerifyConfig::Fingerprint { .. } = conf.tls() {
+            let cert = res
+                .extensions()
+                .get::<TlsInfo>()
+                .and_then(|i| i.peer_certificate())
+                .context(
+                    "Peer did not present a certificate to verify against the pinned fingerprint",
+                )?;
+
+            tls.verify_fingerprint(cert)?;
+        }
+
+        let catalog = res
             .error_for_status()
             .context("Error response returned from peer")?
             .json::<Catalog>()
@@ -53,7 +74,7 @@ impl PeerEntitySearcher {
             .entities
             .into_iter()
             .map(|e| {
-                EntityConfig::new(
+                let mut entity = EntityConfig::new(
                     e.id,
                     e.name,
                     e.description,
@@ -71,7 +92,9 @@ impl PeerEntitySearcher {
                         ),
                     ))
                     .unwrap(),
-                )
+                );
+                entity.classifications = e.classifications;
+                entity
             })
             .collect())
     }
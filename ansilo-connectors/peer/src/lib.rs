@@ -10,10 +10,13 @@ use ansilo_core::{
 use conf::PeerConfig;
 use entity_searcher::PeerEntitySearcher;
 use pool::PeerConnectionUnpool;
+use query_planner::PeerQueryPlanner;
 
 pub mod conf;
 pub mod entity_searcher;
 pub mod pool;
+pub mod query_planner;
+pub mod sync;
 
 /// The connector for peering with other ansilo nodes
 #[derive(Default)]
@@ -26,7 +29,7 @@ impl Connector for PeerConnector {
     type TEntitySearcher = PostgresEntitySearcher<UnpooledClient>;
     type TEntityValidator = PostgresEntityValidator<UnpooledClient>;
     type TEntitySourceConfig = PostgresEntitySourceConfig;
-    type TQueryPlanner = PostgresQueryPlanner<UnpooledClient>;
+    type TQueryPlanner = PeerQueryPlanner;
     type TQueryCompiler = PostgresQueryCompiler<UnpooledClient>;
     type TQueryHandle = PostgresPreparedQuery<UnpooledClient>;
     type TQuery = PostgresQuery;
@@ -6,8 +6,6 @@ use ansilo_core::{
     config::NodeConfig,
     err::{Context, Result},
 };
-use native_tls::TlsConnector;
-use postgres_native_tls::MakeTlsConnector;
 
 use crate::{conf::PeerConfig, PostgresConnection};
 
@@ -26,6 +24,12 @@ impl PeerConnectionUnpool {
             conf,
         }
     }
+
+    /// Whether credentials are configured, rather than passed through from the
+    /// authenticated user, so connections can be made outside of a session
+    pub fn has_credentials(&self) -> bool {
+        self.conf.username.is_some() && self.conf.password.is_some()
+    }
 }
 
 impl ConnectionPool for PeerConnectionUnpool {
@@ -63,9 +67,7 @@ impl ConnectionPool for PeerConnectionUnpool {
         config.application_name(&format!("ansilo-{}", ansilo_version()));
 
         let (client, con) = postgres_connector_runtime()
-            .block_on(config.connect(MakeTlsConnector::new(
-                TlsConnector::new().context("Failed to initialise tls connector")?,
-            )))
+            .block_on(config.connect(self.conf.tls_connector()?))
             .context("Failed to connect to peer")?;
 
         postgres_connector_runtime().spawn(con);
@@ -0,0 +1,264 @@
+use ansilo_connectors_base::{
+    common::{entity::EntitySource, query::QueryParam},
+    interface::{
+        BulkInsertQueryOperation, DeleteQueryOperation, InsertQueryOperation, OperationCost,
+        QueryCompiler, QueryOperationResult, QueryPlanner, SelectQueryOperation,
+        UpdateQueryOperation,
+    },
+};
+use ansilo_connectors_native_postgres::{
+    PostgresConnection, PostgresConnectorEntityConfig, PostgresEntitySourceConfig, PostgresQuery,
+    PostgresQueryCompiler, PostgresQueryPlanner, UnpooledClient,
+};
+use ansilo_core::{data::DataType, err::Result, sqlil as sql};
+use ansilo_logging::debug;
+
+type Connection = PostgresConnection<UnpooledClient>;
+type Inner = PostgresQueryPlanner<UnpooledClient>;
+
+/// Query planner for peer nodes.
+///
+/// Queries are planned as per postgres, with the addition that once a select
+/// joins or groups the peer's entities the row estimate is retrieved from the peer.
+/// Left to the local estimates, the cost of a join or aggregate performed by the
+/// peer is often overestimated, causing the entities to be retrieved table-at-a-time
+/// and processed locally. With the peer's estimate, the whole subplan is pushed down
+/// to the peer, which in turn may push it down further to its data sources.
+pub struct PeerQueryPlanner;
+
+impl QueryPlanner for PeerQueryPlanner {
+    type TConnection = Connection;
+    type TQuery = PostgresQuery;
+    type TEntitySourceConfig = PostgresEntitySourceConfig;
+
+    fn estimate_size(
+        connection: &mut Connection,
+        entity: &EntitySource<PostgresEntitySourceConfig>,
+    ) -> Result<OperationCost> {
+        Inner::estimate_size(connection, entity)
+    }
+
+    fn get_row_id_exprs(
+        connection: &mut Connection,
+        conf: &PostgresConnectorEntityConfig,
+        entity: &EntitySource<PostgresEntitySourceConfig>,
+        source: &sql::EntitySource,
+    ) -> Result<Vec<(sql::Expr, DataType)>> {
+        Inner::get_row_id_exprs(connection, conf, entity, source)
+    }
+
+    fn create_base_select(
+        connection: &mut Connection,
+        conf: &PostgresConnectorEntityConfig,
+        entity: &EntitySource<PostgresEntitySourceConfig>,
+        source: &sql::EntitySource,
+    ) -> Result<(OperationCost, sql::Select)> {
+        Inner::create_base_select(connection, conf, entity, source)
+    }
+
+    fn create_base_insert(
+        connection: &mut Connection,
+        conf: &PostgresConnectorEntityConfig,
+        entity: &EntitySource<PostgresEntitySourceConfig>,
+        source: &sql::EntitySource,
+    ) -> Result<(OperationCost, sql::Insert)> {
+        Inner::create_base_insert(connection, conf, entity, source)
+    }
+
+    fn create_base_bulk_insert(
+        connection: &mut Connection,
+        conf: &PostgresConnectorEntityConfig,
+        entity: &EntitySource<PostgresEntitySourceConfig>,
+        source: &sql::EntitySource,
+    ) -> Result<(OperationCost, sql::BulkInsert)> {
+        Inner::create_base_bulk_insert(connection, conf, entity, source)
+    }
+
+    fn create_base_update(
+        connection: &mut Connection,
+        conf: &PostgresConnectorEntityConfig,
+        entity: &EntitySource<PostgresEntitySourceConfig>,
+        source: &sql::EntitySource,
+    ) -> Result<(OperationCost, sql::Update)> {
+        Inner::create_base_update(connection, conf, entity, source)
+    }
+
+    fn create_base_delete(
+        connection: &mut Connection,
+        conf: &PostgresConnectorEntityConfig,
+        entity: &EntitySource<PostgresEntitySourceConfig>,
+        source: &sql::EntitySource,
+    ) -> Result<(OperationCost, sql::Delete)> {
+        Inner::create_base_delete(connection, conf, entity, source)
+    }
+
+    fn apply_select_operation(
+        connection: &mut Connection,
+        conf: &PostgresConnectorEntityConfig,
+        select: &mut sql::Select,
+        op: SelectQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        let estimate = requires_remote_estimate(&op);
+
+        match Inner::apply_select_operation(connection, conf, select, op)? {
+            QueryOperationResult::Ok(cost) if estimate && is_subplan(select) => Ok(
+                QueryOperationResult::Ok(remote_estimate(connection, conf, select).unwrap_or(cost)),
+            ),
+            res => Ok(res),
+        }
+    }
+
+    fn get_insert_max_bulk_size(
+        connection: &mut Connection,
+        conf: &PostgresConnectorEntityConfig,
+        insert: &sql::Insert,
+    ) -> Result<u32> {
+        Inner::get_insert_max_bulk_size(connection, conf, insert)
+    }
+
+    fn apply_insert_operation(
+        connection: &mut Connection,
+        conf: &PostgresConnectorEntityConfig,
+        insert: &mut sql::Insert,
+        op: InsertQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        Inner::apply_insert_operation(connection, conf, insert, op)
+    }
+
+    fn apply_bulk_insert_operation(
+        connection: &mut Connection,
+        conf: &PostgresConnectorEntityConfig,
+        insert: &mut sql::BulkInsert,
+        op: BulkInsertQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        Inner::apply_bulk_insert_operation(connection, conf, insert, op)
+    }
+
+    fn apply_update_operation(
+        connection: &mut Connection,
+        conf: &PostgresConnectorEntityConfig,
+        update: &mut sql::Update,
+        op: UpdateQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        Inner::apply_update_operation(connection, conf, update, op)
+    }
+
+    fn apply_delete_operation(
+        connection: &mut Connection,
+        conf: &PostgresConnectorEntityConfig,
+        delete: &mut sql::Delete,
+        op: DeleteQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        Inner::apply_delete_operation(connection, conf, delete, op)
+    }
+
+    fn explain_query(
+        connection: &mut Connection,
+        conf: &PostgresConnectorEntityConfig,
+        query: &sql::Query,
+        verbose: bool,
+    ) -> Result<serde_json::Value> {
+        Inner::explain_query(connection, conf, query, verbose)
+    }
+}
+
+/// Whether the operation changes the rows returned by the select.
+///
+/// Conditions are applied to a join after the join itself, so the
+/// estimate is retrieved again to account for them.
+fn requires_remote_estimate(op: &SelectQueryOperation) -> bool {
+    matches!(
+        op,
+        SelectQueryOperation::AddJoin(_)
+            | SelectQueryOperation::AddWhere(_)
+            | SelectQueryOperation::AddGroupBy(_)
+    )
+}
+
+/// Whether the select performs a join or aggregation on the peer
+fn is_subplan(select: &sql::Select) -> bool {
+    !select.joins.is_empty() || !select.group_bys.is_empty()
+}
+
+/// Retrieves the estimate of the select from the peer's query planner.
+///
+/// Selects with dynamic parameters cannot be planned ahead of execution,
+/// in which case the local estimate is used.
+fn remote_estimate(
+    connection: &mut Connection,
+    conf: &PostgresConnectorEntityConfig,
+    select: &sql::Select,
+) -> Option<OperationCost> {
+    let query = match PostgresQueryCompiler::<UnpooledClient>::compile_query(
+        connection,
+        conf,
+        sql::Query::Select(select.clone()),
+    ) {
+        Ok(query) => query,
+        Err(err) => {
+            debug!("Failed to compile query for remote estimate: {:?}", err);
+            return None;
+        }
+    };
+
+    if query
+        .params
+        .iter()
+        .any(|p| matches!(p, QueryParam::Dynamic(_)))
+    {
+        return None;
+    }
+
+    match Inner::explain_cost(connection, query) {
+        Ok(cost) => Some(cost),
+        Err(err) => {
+            debug!("Failed to retrieve remote estimate from peer: {:?}", err);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_select() -> sql::Select {
+        sql::Select::new(sql::source("people", "p"))
+    }
+
+    #[test]
+    fn test_requires_remote_estimate() {
+        assert!(requires_remote_estimate(&SelectQueryOperation::AddWhere(
+            sql::Expr::attr("p", "active")
+        )));
+        assert!(requires_remote_estimate(&SelectQueryOperation::AddGroupBy(
+            sql::Expr::attr("p", "name")
+        )));
+        assert!(!requires_remote_estimate(
+            &SelectQueryOperation::SetRowLimit(10)
+        ));
+        assert!(!requires_remote_estimate(&SelectQueryOperation::AddColumn(
+            ("name".into(), sql::Expr::attr("p", "name"))
+        )));
+    }
+
+    #[test]
+    fn test_is_subplan() {
+        let mut select = mock_select();
+        assert!(!is_subplan(&select));
+
+        select.r#where.push(sql::Expr::attr("p", "active"));
+        assert!(!is_subplan(&select));
+
+        select.group_bys.push(sql::Expr::attr("p", "name"));
+        assert!(is_subplan(&select));
+
+        let mut select = mock_select();
+        select.joins.push(sql::Join::new(
+            sql::JoinType::Inner,
+            sql::source("orders", "o"),
+            vec![sql::Expr::attr("o", "person_id")],
+        ));
+        assert!(is_subplan(&select));
+    }
+}
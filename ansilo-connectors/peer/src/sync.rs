@@ -0,0 +1,188 @@
+use std::{fmt, time::Duration};
+
+use ansilo_core::config::EntityConfig;
+use serde::{Deserialize, Serialize};
+
+/// The default interval between syncs of the peer's catalog
+pub const DEFAULT_SYNC_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Options for keeping the entities imported from a peer in sync with its catalog
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerSyncConfig {
+    /// The schema the entities of the peer are imported into
+    pub schema: String,
+    /// Prefix applied to the names of the imported tables
+    #[serde(default)]
+    pub table_prefix: Option<String>,
+    /// The interval between syncs in seconds, defaults to 5 minutes
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+}
+
+impl PeerSyncConfig {
+    /// Gets the interval between syncs
+    pub fn interval(&self) -> Duration {
+        self.interval_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_SYNC_INTERVAL)
+    }
+
+    /// Gets the name of the table the entity is imported as
+    pub fn table_name(&self, entity_id: &str) -> String {
+        format!(
+            "{}{entity_id}",
+            self.table_prefix.as_deref().unwrap_or_default()
+        )
+    }
+}
+
+/// The differences between the entities previously imported from a peer
+/// and the entities currently exposed in its catalog
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CatalogChanges {
+    /// Entities which are new in the peer's catalog
+    pub added: Vec<EntityConfig>,
+    /// The ids of the entities no longer in the peer's catalog
+    pub removed: Vec<String>,
+    /// Entities whose definition has changed
+    pub changed: Vec<EntityConfig>,
+}
+
+impl CatalogChanges {
+    /// Compares the previously imported entities to the current entities of the peer
+    pub fn diff(previous: &[EntityConfig], current: &[EntityConfig]) -> Self {
+        let mut changes = Self::default();
+
+        for entity in current.iter() {
+            match previous.iter().find(|e| e.id == entity.id) {
+                None => changes.added.push(entity.clone()),
+                Some(prev) if prev != entity => changes.changed.push(entity.clone()),
+                Some(_) => {}
+            }
+        }
+
+        changes.removed = previous
+            .iter()
+            .filter(|e| !current.iter().any(|c| c.id == e.id))
+            .map(|e| e.id.clone())
+            .collect();
+
+        changes
+    }
+
+    /// Whether the peer's catalog is unchanged
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// The ids of the entities whose tables need to be dropped
+    pub fn to_drop(&self) -> impl Iterator<Item = &str> {
+        self.removed
+            .iter()
+            .map(|id| id.as_str())
+            .chain(self.changed.iter().map(|e| e.id.as_str()))
+    }
+
+    /// The ids of the entities which need to be imported
+    pub fn to_import(&self) -> impl Iterator<Item = &str> {
+        self.added
+            .iter()
+            .chain(self.changed.iter())
+            .map(|e| e.id.as_str())
+    }
+}
+
+impl fmt::Display for CatalogChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} removed, {} changed",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::{
+        config::{EntityAttributeConfig, EntitySourceConfig},
+        data::DataType,
+    };
+
+    use super::*;
+
+    fn mock_entity(id: &str, attrs: Vec<&str>) -> EntityConfig {
+        EntityConfig::minimal(
+            id,
+            attrs
+                .into_iter()
+                .map(|a| EntityAttributeConfig::minimal(a, DataType::Int32))
+                .collect(),
+            EntitySourceConfig::minimal("peer"),
+        )
+    }
+
+    #[test]
+    fn test_sync_config_table_name() {
+        let mut conf = PeerSyncConfig {
+            schema: "peer".into(),
+            table_prefix: None,
+            interval_secs: None,
+        };
+
+        assert_eq!(conf.table_name("customers"), "customers");
+        assert_eq!(conf.interval(), DEFAULT_SYNC_INTERVAL);
+
+        conf.table_prefix = Some("sales_".into());
+        conf.interval_secs = Some(60);
+
+        assert_eq!(conf.table_name("customers"), "sales_customers");
+        assert_eq!(conf.interval(), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_catalog_changes_unchanged() {
+        let entities = vec![mock_entity("a", vec!["id"]), mock_entity("b", vec!["id"])];
+
+        let changes = CatalogChanges::diff(&entities, &entities);
+
+        assert!(changes.is_empty());
+        assert_eq!(changes.to_string(), "0 added, 0 removed, 0 changed");
+    }
+
+    #[test]
+    fn test_catalog_changes_diff() {
+        let previous = vec![
+            mock_entity("unchanged", vec!["id"]),
+            mock_entity("removed", vec!["id"]),
+            mock_entity("changed", vec!["id"]),
+        ];
+        let current = vec![
+            mock_entity("unchanged", vec!["id"]),
+            mock_entity("changed", vec!["id", "name"]),
+            mock_entity("added", vec!["id"]),
+        ];
+
+        let changes = CatalogChanges::diff(&previous, &current);
+
+        assert_eq!(
+            changes,
+            CatalogChanges {
+                added: vec![mock_entity("added", vec!["id"])],
+                removed: vec!["removed".into()],
+                changed: vec![mock_entity("changed", vec!["id", "name"])],
+            }
+        );
+        assert_eq!(
+            changes.to_drop().collect::<Vec<_>>(),
+            vec!["removed", "changed"]
+        );
+        assert_eq!(
+            changes.to_import().collect::<Vec<_>>(),
+            vec!["added", "changed"]
+        );
+        assert_eq!(changes.to_string(), "1 added, 1 removed, 1 changed");
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// The sources defining the types passed between ansilo and its plugins
+const TYPE_SOURCES: [&str; 3] = ["../../ansilo-core/src", "../base/src", "src/api.rs"];
+
+fn main() {
+    // Plugins must be compiled with the same rustc as the host as the
+    // layout of the trait objects passed between them is not stable
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .unwrap_or_default();
+
+    println!(
+        "cargo:rustc-env=ANSILO_PLUGIN_RUSTC_VERSION={}",
+        version.trim()
+    );
+
+    // Plugins must also be compiled against the same definitions of the types
+    // passed across the library boundary, so the sources defining them are hashed
+    let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let mut files = vec![];
+    for src in TYPE_SOURCES {
+        let path = root.join(src);
+        println!("cargo:rerun-if-changed={}", path.display());
+        collect_files(&path, &mut files);
+    }
+    files.sort();
+
+    let mut hash = Fnv1a::default();
+    for file in files {
+        hash.write(
+            file.strip_prefix(&root)
+                .unwrap_or(&file)
+                .to_string_lossy()
+                .as_bytes(),
+        );
+        hash.write(&fs::read(&file).unwrap());
+    }
+
+    println!("cargo:rustc-env=ANSILO_PLUGIN_TYPES_HASH={:016x}", hash.0);
+    println!("cargo:rerun-if-changed=build.rs");
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        for entry in fs::read_dir(path).unwrap() {
+            collect_files(&entry.unwrap().path(), files);
+        }
+    } else if path.extension().and_then(|e| e.to_str()) == Some("rs") {
+        files.push(path.to_path_buf());
+    }
+}
+
+/// A hash which is stable across builds, unlike the std hasher
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
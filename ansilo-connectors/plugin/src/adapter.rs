@@ -0,0 +1,363 @@
+use std::marker::PhantomData;
+
+use ansilo_connectors_base::{
+    common::entity::{ConnectorEntityConfig, EntitySource},
+    interface::{
+        BulkInsertQueryOperation, Connection, ConnectionPool, Connector, DeleteQueryOperation,
+        EntityDiscoverOptions, EntitySearcher, EntityValidator, InsertQueryOperation, LoggedQuery,
+        OperationCost, QueryCompiler, QueryHandle, QueryInputStructure, QueryOperationResult,
+        QueryPlanner, ResultSet, RowStructure, SelectQueryOperation, TransactionManager,
+        UpdateQueryOperation,
+    },
+};
+use ansilo_core::{
+    auth::AuthContext,
+    config::{self, EntityConfig, NodeConfig},
+    data::DataType,
+    err::{Context, Error, Result},
+    sqlil as sql,
+};
+
+use crate::{
+    DynConnection, DynConnectionPool, DynConnector, DynQueryHandle, DynResultSet,
+    PluginEntityConfig, PluginEntitySource, PluginQuery,
+};
+
+/// Exposes a [`Connector`] through the object-safe plugin interface.
+///
+/// This is used within plugin libraries, see [`export_connector`](crate::export_connector),
+/// so third-party connectors are implemented in the same way as the built-in connectors.
+pub struct ConnectorAdapter<T>(PhantomData<fn() -> T>);
+
+impl<T> ConnectorAdapter<T> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T> Default for ConnectorAdapter<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct PoolAdapter<T: Connector>(T::TConnectionPool);
+
+struct ConnectionAdapter<T: Connector>(T::TConnection);
+
+struct QueryHandleAdapter<T: Connector>(T::TQueryHandle);
+
+struct ResultSetAdapter<T: Connector>(T::TResultSet);
+
+impl<T> DynConnector for ConnectorAdapter<T>
+where
+    T: Connector + 'static,
+    T::TConnection: 'static,
+    T::TQuery: 'static,
+    T::TQueryHandle: 'static,
+    T::TResultSet: 'static,
+{
+    fn r#type(&self) -> &'static str {
+        T::TYPE
+    }
+
+    fn validate_options(&self, options: config::Value) -> Result<()> {
+        T::parse_options(options).map(|_| ())
+    }
+
+    fn validate_entity_source_options(&self, options: config::Value) -> Result<()> {
+        T::parse_entity_source_options(options).map(|_| ())
+    }
+
+    fn create_connection_pool(
+        &self,
+        options: config::Value,
+        nc: &NodeConfig,
+        entities: &PluginEntityConfig,
+    ) -> Result<Box<dyn DynConnectionPool>> {
+        let options = T::parse_options(options)?;
+        let pool = T::create_connection_pool(options, nc, &entities_of::<T>(entities)?)?;
+
+        Ok(Box::new(PoolAdapter::<T>(pool)))
+    }
+}
+
+impl<T> DynConnectionPool for PoolAdapter<T>
+where
+    T: Connector + 'static,
+    T::TConnection: 'static,
+    T::TQuery: 'static,
+    T::TQueryHandle: 'static,
+    T::TResultSet: 'static,
+{
+    fn acquire(&mut self, auth: Option<&AuthContext>) -> Result<Box<dyn DynConnection>> {
+        Ok(Box::new(ConnectionAdapter::<T>(self.0.acquire(auth)?)))
+    }
+
+    fn clone_box(&self) -> Box<dyn DynConnectionPool> {
+        Box::new(PoolAdapter::<T>(self.0.clone()))
+    }
+}
+
+impl<T> ConnectionAdapter<T>
+where
+    T: Connector,
+{
+    fn transaction_manager(&mut self) -> Result<&mut T::TTransactionManager> {
+        self.0
+            .transaction_manager()
+            .context("Transactions are not supported by this connector")
+    }
+}
+
+impl<T> DynConnection for ConnectionAdapter<T>
+where
+    T: Connector + 'static,
+    T::TQuery: 'static,
+    T::TQueryHandle: 'static,
+    T::TResultSet: 'static,
+{
+    fn prepare(&mut self, query: PluginQuery) -> Result<Box<dyn DynQueryHandle>> {
+        let query = query
+            .inner
+            .downcast::<T::TQuery>()
+            .map_err(|_| Error::msg("The query was not compiled by this connector"))?;
+
+        Ok(Box::new(QueryHandleAdapter::<T>(self.0.prepare(*query)?)))
+    }
+
+    fn supports_transactions(&mut self) -> bool {
+        self.0.transaction_manager().is_some()
+    }
+
+    fn is_in_transaction(&mut self) -> Result<bool> {
+        self.transaction_manager()?.is_in_transaction()
+    }
+
+    fn begin_transaction(&mut self) -> Result<()> {
+        self.transaction_manager()?.begin_transaction()
+    }
+
+    fn rollback_transaction(&mut self) -> Result<()> {
+        self.transaction_manager()?.rollback_transaction()
+    }
+
+    fn commit_transaction(&mut self) -> Result<()> {
+        self.transaction_manager()?.commit_transaction()
+    }
+
+    fn discover(
+        &mut self,
+        nc: &NodeConfig,
+        opts: EntityDiscoverOptions,
+    ) -> Result<Vec<EntityConfig>> {
+        T::TEntitySearcher::discover(&mut self.0, nc, opts)
+    }
+
+    fn validate(&mut self, entity: &EntityConfig, nc: &NodeConfig) -> Result<()> {
+        T::TEntityValidator::validate(&mut self.0, entity, nc).map(|_| ())
+    }
+
+    fn estimate_size(&mut self, entity: &PluginEntitySource) -> Result<OperationCost> {
+        T::TQueryPlanner::estimate_size(&mut self.0, &entity_of::<T>(entity)?)
+    }
+
+    fn get_row_id_exprs(
+        &mut self,
+        conf: &PluginEntityConfig,
+        entity: &PluginEntitySource,
+        source: &sql::EntitySource,
+    ) -> Result<Vec<(sql::Expr, DataType)>> {
+        T::TQueryPlanner::get_row_id_exprs(
+            &mut self.0,
+            &entities_of::<T>(conf)?,
+            &entity_of::<T>(entity)?,
+            source,
+        )
+    }
+
+    fn create_base_query(
+        &mut self,
+        conf: &PluginEntityConfig,
+        entity: &PluginEntitySource,
+        source: &sql::EntitySource,
+        r#type: sql::QueryType,
+    ) -> Result<(OperationCost, sql::Query)> {
+        T::TQueryPlanner::create_base_query(
+            &mut self.0,
+            &entities_of::<T>(conf)?,
+            &entity_of::<T>(entity)?,
+            source,
+            r#type,
+        )
+    }
+
+    fn apply_select_operation(
+        &mut self,
+        conf: &PluginEntityConfig,
+        select: &mut sql::Select,
+        op: SelectQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        T::TQueryPlanner::apply_select_operation(&mut self.0, &entities_of::<T>(conf)?, select, op)
+    }
+
+    fn get_insert_max_bulk_size(
+        &mut self,
+        conf: &PluginEntityConfig,
+        insert: &sql::Insert,
+    ) -> Result<u32> {
+        T::TQueryPlanner::get_insert_max_bulk_size(&mut self.0, &entities_of::<T>(conf)?, insert)
+    }
+
+    fn apply_insert_operation(
+        &mut self,
+        conf: &PluginEntityConfig,
+        insert: &mut sql::Insert,
+        op: InsertQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        T::TQueryPlanner::apply_insert_operation(&mut self.0, &entities_of::<T>(conf)?, insert, op)
+    }
+
+    fn apply_bulk_insert_operation(
+        &mut self,
+        conf: &PluginEntityConfig,
+        insert: &mut sql::BulkInsert,
+        op: BulkInsertQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        T::TQueryPlanner::apply_bulk_insert_operation(
+            &mut self.0,
+            &entities_of::<T>(conf)?,
+            insert,
+            op,
+        )
+    }
+
+    fn apply_update_operation(
+        &mut self,
+        conf: &PluginEntityConfig,
+        update: &mut sql::Update,
+        op: UpdateQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        T::TQueryPlanner::apply_update_operation(&mut self.0, &entities_of::<T>(conf)?, update, op)
+    }
+
+    fn apply_delete_operation(
+        &mut self,
+        conf: &PluginEntityConfig,
+        delete: &mut sql::Delete,
+        op: DeleteQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        T::TQueryPlanner::apply_delete_operation(&mut self.0, &entities_of::<T>(conf)?, delete, op)
+    }
+
+    fn explain_query(
+        &mut self,
+        conf: &PluginEntityConfig,
+        query: &sql::Query,
+        verbose: bool,
+    ) -> Result<serde_json::Value> {
+        T::TQueryPlanner::explain_query(&mut self.0, &entities_of::<T>(conf)?, query, verbose)
+    }
+
+    fn compile_query(
+        &mut self,
+        conf: &PluginEntityConfig,
+        query: sql::Query,
+    ) -> Result<PluginQuery> {
+        let query = T::TQueryCompiler::compile_query(&mut self.0, &entities_of::<T>(conf)?, query)?;
+
+        Ok(PluginQuery {
+            key: T::TQueryCompiler::query_key(&query),
+            inner: Box::new(query),
+        })
+    }
+
+    fn query_from_string(
+        &mut self,
+        query: String,
+        params: Vec<sql::Parameter>,
+    ) -> Result<PluginQuery> {
+        let query = T::TQueryCompiler::query_from_string(&mut self.0, query, params)?;
+
+        Ok(PluginQuery {
+            key: T::TQueryCompiler::query_key(&query),
+            inner: Box::new(query),
+        })
+    }
+}
+
+impl<T> DynQueryHandle for QueryHandleAdapter<T>
+where
+    T: Connector + 'static,
+    T::TResultSet: 'static,
+{
+    fn get_structure(&self) -> Result<QueryInputStructure> {
+        self.0.get_structure()
+    }
+
+    fn supports_batching(&self) -> bool {
+        self.0.supports_batching()
+    }
+
+    fn write(&mut self, buff: &[u8]) -> Result<usize> {
+        self.0.write(buff)
+    }
+
+    fn restart(&mut self) -> Result<()> {
+        self.0.restart()
+    }
+
+    fn execute_query(&mut self) -> Result<Box<dyn DynResultSet>> {
+        Ok(Box::new(ResultSetAdapter::<T>(self.0.execute_query()?)))
+    }
+
+    fn execute_modify(&mut self) -> Result<Option<u64>> {
+        self.0.execute_modify()
+    }
+
+    fn add_to_batch(&mut self) -> Result<()> {
+        self.0.add_to_batch()
+    }
+
+    fn logged(&self) -> Result<LoggedQuery> {
+        self.0.logged()
+    }
+}
+
+impl<T> DynResultSet for ResultSetAdapter<T>
+where
+    T: Connector,
+{
+    fn get_structure(&self) -> Result<RowStructure> {
+        self.0.get_structure()
+    }
+
+    fn read(&mut self, buff: &mut [u8]) -> Result<usize> {
+        self.0.read(buff)
+    }
+}
+
+/// Parses the source options of the entity using the connector of the plugin.
+///
+/// The entities are parsed on each call as they are owned by ansilo and
+/// may be replaced while the connections of the plugin are open.
+fn entity_of<T: Connector>(
+    entity: &PluginEntitySource,
+) -> Result<EntitySource<T::TEntitySourceConfig>> {
+    Ok(EntitySource::new(
+        entity.conf.clone(),
+        T::parse_entity_source_options(entity.source.0.clone())?,
+    ))
+}
+
+fn entities_of<T: Connector>(
+    conf: &PluginEntityConfig,
+) -> Result<ConnectorEntityConfig<T::TEntitySourceConfig>> {
+    let mut entities = ConnectorEntityConfig::new();
+
+    for entity in conf.entities() {
+        entities.add(entity_of::<T>(entity)?);
+    }
+
+    Ok(entities)
+}
@@ -0,0 +1,231 @@
+use std::any::Any;
+
+use ansilo_connectors_base::{
+    common::entity::{ConnectorEntityConfig, EntitySource},
+    interface::{
+        BulkInsertQueryOperation, DeleteQueryOperation, EntityDiscoverOptions,
+        InsertQueryOperation, LoggedQuery, OperationCost, QueryInputStructure,
+        QueryOperationResult, RowStructure, SelectQueryOperation, UpdateQueryOperation,
+    },
+};
+use ansilo_core::{
+    auth::AuthContext,
+    config::{self, EntityConfig, NodeConfig},
+    data::DataType,
+    err::Result,
+    sqlil as sql,
+};
+
+/// The version of the plugin interface.
+///
+/// This is incremented on any change to the traits in this module,
+/// plugins built against another version are rejected when loaded.
+pub const PLUGIN_API_VERSION: u32 = 2;
+
+/// The version of rustc which compiled this crate.
+///
+/// The trait objects passed between ansilo and its plugins do not have a stable
+/// layout so plugins must be compiled with the same version of rustc.
+pub const RUSTC_VERSION: &str = env!("ANSILO_PLUGIN_RUSTC_VERSION");
+
+/// A hash of the sources of ansilo-core, ansilo-connectors-base and this module.
+///
+/// Types such as [`NodeConfig`], [`sql::Query`] and the errors of these crates are
+/// passed between ansilo and its plugins, so plugins must be compiled against the
+/// same definitions of these types.
+pub const TYPES_HASH: &str = env!("ANSILO_PLUGIN_TYPES_HASH");
+
+/// The name of the symbol of the [`PluginDeclaration`] exported by each plugin
+pub const PLUGIN_DECLARATION_SYMBOL: &[u8] = b"ANSILO_PLUGIN_DECLARATION\0";
+
+/// The declaration exported by a plugin library, see [`export_connector`](crate::export_connector).
+///
+/// The versions are laid out first so they can be checked before
+/// the registration function is called.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PluginDeclaration {
+    pub api_version: u32,
+    pub rustc_version: &'static str,
+    pub types_hash: &'static str,
+    pub register: fn(&mut dyn PluginRegistrar),
+}
+
+/// Receives the connectors provided by a plugin
+pub trait PluginRegistrar {
+    fn register_connector(&mut self, connector: Box<dyn DynConnector>);
+}
+
+/// The entity source options of plugin connectors.
+///
+/// The options are passed to the plugin as-is and parsed by the plugin's connector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginEntitySourceConfig(pub config::Value);
+
+/// The entity configs of the data source of a plugin connector
+pub type PluginEntityConfig = ConnectorEntityConfig<PluginEntitySourceConfig>;
+
+/// A single entity of the data source of a plugin connector
+pub type PluginEntitySource = EntitySource<PluginEntitySourceConfig>;
+
+/// A query compiled by a plugin connector
+pub struct PluginQuery {
+    /// The connector-specific query
+    pub inner: Box<dyn Any>,
+    /// The key of the query, see [`QueryCompiler::query_key`](ansilo_connectors_base::interface::QueryCompiler::query_key)
+    pub key: Option<String>,
+}
+
+/// An object-safe equivalent of the [`Connector`](ansilo_connectors_base::interface::Connector) trait
+pub trait DynConnector: Send + Sync {
+    /// The type of the connector, used as the type of its data sources
+    fn r#type(&self) -> &'static str;
+
+    /// Checks the data source options can be parsed by the connector
+    fn validate_options(&self, options: config::Value) -> Result<()>;
+
+    /// Checks the entity source options can be parsed by the connector
+    fn validate_entity_source_options(&self, options: config::Value) -> Result<()>;
+
+    /// Creates a connection pool to the data source
+    fn create_connection_pool(
+        &self,
+        options: config::Value,
+        nc: &NodeConfig,
+        entities: &PluginEntityConfig,
+    ) -> Result<Box<dyn DynConnectionPool>>;
+}
+
+/// An object-safe equivalent of the [`ConnectionPool`](ansilo_connectors_base::interface::ConnectionPool) trait
+pub trait DynConnectionPool: Send + Sync {
+    fn acquire(&mut self, auth: Option<&AuthContext>) -> Result<Box<dyn DynConnection>>;
+
+    fn clone_box(&self) -> Box<dyn DynConnectionPool>;
+}
+
+/// An object-safe equivalent of a connection along with the entity searcher,
+/// validator, query planner and compiler of the connector which operate on it
+pub trait DynConnection {
+    fn prepare(&mut self, query: PluginQuery) -> Result<Box<dyn DynQueryHandle>>;
+
+    fn supports_transactions(&mut self) -> bool;
+
+    fn is_in_transaction(&mut self) -> Result<bool>;
+
+    fn begin_transaction(&mut self) -> Result<()>;
+
+    fn rollback_transaction(&mut self) -> Result<()>;
+
+    fn commit_transaction(&mut self) -> Result<()>;
+
+    fn discover(
+        &mut self,
+        nc: &NodeConfig,
+        opts: EntityDiscoverOptions,
+    ) -> Result<Vec<EntityConfig>>;
+
+    fn validate(&mut self, entity: &EntityConfig, nc: &NodeConfig) -> Result<()>;
+
+    fn estimate_size(&mut self, entity: &PluginEntitySource) -> Result<OperationCost>;
+
+    fn get_row_id_exprs(
+        &mut self,
+        conf: &PluginEntityConfig,
+        entity: &PluginEntitySource,
+        source: &sql::EntitySource,
+    ) -> Result<Vec<(sql::Expr, DataType)>>;
+
+    fn create_base_query(
+        &mut self,
+        conf: &PluginEntityConfig,
+        entity: &PluginEntitySource,
+        source: &sql::EntitySource,
+        r#type: sql::QueryType,
+    ) -> Result<(OperationCost, sql::Query)>;
+
+    fn apply_select_operation(
+        &mut self,
+        conf: &PluginEntityConfig,
+        select: &mut sql::Select,
+        op: SelectQueryOperation,
+    ) -> Result<QueryOperationResult>;
+
+    fn get_insert_max_bulk_size(
+        &mut self,
+        conf: &PluginEntityConfig,
+        insert: &sql::Insert,
+    ) -> Result<u32>;
+
+    fn apply_insert_operation(
+        &mut self,
+        conf: &PluginEntityConfig,
+        insert: &mut sql::Insert,
+        op: InsertQueryOperation,
+    ) -> Result<QueryOperationResult>;
+
+    fn apply_bulk_insert_operation(
+        &mut self,
+        conf: &PluginEntityConfig,
+        insert: &mut sql::BulkInsert,
+        op: BulkInsertQueryOperation,
+    ) -> Result<QueryOperationResult>;
+
+    fn apply_update_operation(
+        &mut self,
+        conf: &PluginEntityConfig,
+        update: &mut sql::Update,
+        op: UpdateQueryOperation,
+    ) -> Result<QueryOperationResult>;
+
+    fn apply_delete_operation(
+        &mut self,
+        conf: &PluginEntityConfig,
+        delete: &mut sql::Delete,
+        op: DeleteQueryOperation,
+    ) -> Result<QueryOperationResult>;
+
+    fn explain_query(
+        &mut self,
+        conf: &PluginEntityConfig,
+        query: &sql::Query,
+        verbose: bool,
+    ) -> Result<serde_json::Value>;
+
+    fn compile_query(
+        &mut self,
+        conf: &PluginEntityConfig,
+        query: sql::Query,
+    ) -> Result<PluginQuery>;
+
+    fn query_from_string(
+        &mut self,
+        query: String,
+        params: Vec<sql::Parameter>,
+    ) -> Result<PluginQuery>;
+}
+
+/// An object-safe equivalent of the [`QueryHandle`](ansilo_connectors_base::interface::QueryHandle) trait
+pub trait DynQueryHandle {
+    fn get_structure(&self) -> Result<QueryInputStructure>;
+
+    fn supports_batching(&self) -> bool;
+
+    fn write(&mut self, buff: &[u8]) -> Result<usize>;
+
+    fn restart(&mut self) -> Result<()>;
+
+    fn execute_query(&mut self) -> Result<Box<dyn DynResultSet>>;
+
+    fn execute_modify(&mut self) -> Result<Option<u64>>;
+
+    fn add_to_batch(&mut self) -> Result<()>;
+
+    fn logged(&self) -> Result<LoggedQuery>;
+}
+
+/// An object-safe equivalent of the [`ResultSet`](ansilo_connectors_base::interface::ResultSet) trait
+pub trait DynResultSet {
+    fn get_structure(&self) -> Result<RowStructure>;
+
+    fn read(&mut self, buff: &mut [u8]) -> Result<usize>;
+}
@@ -0,0 +1,127 @@
+use ansilo_connectors_base::interface::{Connection, ConnectionPool, TransactionManager};
+use ansilo_core::{
+    auth::AuthContext,
+    err::{bail, Result},
+};
+
+use crate::{
+    ConnectorPlugin, DynConnection, DynConnectionPool, PluginPanic, PluginQuery, PluginQueryHandle,
+};
+
+/// The connection pool of a data source of a plugin connector
+pub struct PluginConnectionPool {
+    plugin: &'static ConnectorPlugin,
+    inner: Box<dyn DynConnectionPool>,
+}
+
+impl PluginConnectionPool {
+    pub(crate) fn new(plugin: &'static ConnectorPlugin, inner: Box<dyn DynConnectionPool>) -> Self {
+        Self { plugin, inner }
+    }
+
+    /// Gets the plugin which provides the connector
+    pub fn plugin(&self) -> &'static ConnectorPlugin {
+        self.plugin
+    }
+}
+
+impl Clone for PluginConnectionPool {
+    fn clone(&self) -> Self {
+        Self {
+            plugin: self.plugin,
+            inner: self.inner.clone_box(),
+        }
+    }
+}
+
+impl ConnectionPool for PluginConnectionPool {
+    type TConnection = PluginConnection;
+
+    fn acquire(&mut self, auth: Option<&AuthContext>) -> Result<PluginConnection> {
+        let inner = &mut self.inner;
+        let inner = self.plugin.guard(|| inner.acquire(auth))?;
+
+        Ok(PluginConnection {
+            plugin: self.plugin,
+            inner,
+            poisoned: false,
+        })
+    }
+}
+
+/// A connection to a data source of a plugin connector
+pub struct PluginConnection {
+    plugin: &'static ConnectorPlugin,
+    inner: Box<dyn DynConnection>,
+    /// Whether the plugin panicked while using this connection
+    poisoned: bool,
+}
+
+impl PluginConnection {
+    /// Calls into the connection of the plugin.
+    ///
+    /// Once the plugin has panicked the state of the connection is unknown,
+    /// so it is not used again and any further calls return an error.
+    pub(crate) fn call<R>(
+        &mut self,
+        f: impl FnOnce(&mut dyn DynConnection) -> Result<R>,
+    ) -> Result<R> {
+        if self.poisoned {
+            bail!(
+                "Connection of plugin connector '{}' cannot be used after a panic",
+                self.plugin.r#type()
+            );
+        }
+
+        let inner = &mut self.inner;
+        let res = self.plugin.guard(|| f(inner.as_mut()));
+
+        if matches!(&res, Err(err) if err.is::<PluginPanic>()) {
+            self.poisoned = true;
+        }
+
+        res
+    }
+}
+
+impl Connection for PluginConnection {
+    type TQuery = PluginQuery;
+    type TQueryHandle = PluginQueryHandle;
+    type TTransactionManager = PluginConnection;
+
+    fn prepare(&mut self, query: PluginQuery) -> Result<PluginQueryHandle> {
+        let plugin = self.plugin;
+        let inner = self.call(|c| c.prepare(query))?;
+
+        Ok(PluginQueryHandle::new(plugin, inner))
+    }
+
+    fn transaction_manager(&mut self) -> Option<&mut Self::TTransactionManager> {
+        if self
+            .call(|c| Ok(c.supports_transactions()))
+            .unwrap_or(false)
+        {
+            Some(self)
+        } else {
+            None
+        }
+    }
+}
+
+impl TransactionManager for PluginConnection {
+    fn is_in_transaction(&mut self) -> Result<bool> {
+        self.call(|c| c.is_in_transaction())
+    }
+
+    fn begin_transaction(&mut self) -> Result<()> {
+        self.call(|c| c.begin_transaction())
+    }
+
+    fn rollback_transaction(&mut self) -> Result<()> {
+        self.call(|c| c.rollback_transaction())
+    }
+
+    fn commit_transaction(&mut self) -> Result<()> {
+        self.call(|c| c.commit_transaction())
+    }
+}
@@ -0,0 +1,23 @@
+use ansilo_core::{
+    config::{EntityConfig, NodeConfig},
+    err::Result,
+};
+
+use ansilo_connectors_base::interface::{EntityDiscoverOptions, EntitySearcher};
+
+use crate::{PluginConnection, PluginEntitySourceConfig};
+
+pub struct PluginEntitySearcher {}
+
+impl EntitySearcher for PluginEntitySearcher {
+    type TConnection = PluginConnection;
+    type TEntitySourceConfig = PluginEntitySourceConfig;
+
+    fn discover(
+        connection: &mut PluginConnection,
+        nc: &NodeConfig,
+        opts: EntityDiscoverOptions,
+    ) -> Result<Vec<EntityConfig>> {
+        connection.call(|c| c.discover(nc, opts))
+    }
+}
@@ -0,0 +1,28 @@
+use ansilo_core::{
+    config::{EntityConfig, NodeConfig},
+    err::Result,
+};
+
+use ansilo_connectors_base::{common::entity::EntitySource, interface::EntityValidator};
+
+use crate::{PluginConnection, PluginEntitySourceConfig};
+
+pub struct PluginEntityValidator {}
+
+impl EntityValidator for PluginEntityValidator {
+    type TConnection = PluginConnection;
+    type TEntitySourceConfig = PluginEntitySourceConfig;
+
+    fn validate(
+        connection: &mut PluginConnection,
+        entity: &EntityConfig,
+        nc: &NodeConfig,
+    ) -> Result<EntitySource<PluginEntitySourceConfig>> {
+        connection.call(|c| c.validate(entity, nc))?;
+
+        Ok(EntitySource::new(
+            entity.clone(),
+            PluginEntitySourceConfig(entity.source.options.clone()),
+        ))
+    }
+}
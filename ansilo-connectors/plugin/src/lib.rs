@@ -0,0 +1,207 @@
+//! Connectors provided by plugins.
+//!
+//! Plugins are dynamic libraries which are loaded from the plugins directory
+//! at startup, allowing third-party connectors to be used without recompiling ansilo.
+//! A plugin implements the [`Connector`] trait as the built-in connectors do
+//! and exports it using the [`export_connector`] macro.
+//!
+//! The interface between ansilo and its plugins is made up of the object-safe
+//! traits in [`api`], which are versioned by [`PLUGIN_API_VERSION`].
+//! Each call into a plugin is guarded so that a panic is returned as a
+//! [`PluginPanic`] error rather than unwinding into ansilo.
+
+use ansilo_connectors_base::interface::Connector;
+use ansilo_core::{
+    config::{self, NodeConfig},
+    err::{bail, Result},
+};
+
+pub mod api;
+pub use api::*;
+mod adapter;
+pub use adapter::*;
+mod registry;
+pub use registry::{all, find, register, ConnectorPlugin, PluginPanic};
+mod loader;
+pub use loader::*;
+mod connection;
+pub use connection::*;
+mod query;
+pub use query::*;
+mod entity_searcher;
+pub use entity_searcher::*;
+mod entity_validator;
+pub use entity_validator::*;
+mod query_planner;
+pub use query_planner::*;
+mod query_compiler;
+pub use query_compiler::*;
+
+/// The connector for data sources of connectors provided by plugins
+#[derive(Default)]
+pub struct PluginConnector;
+
+/// The options of a data source of a plugin connector
+#[derive(Debug, Clone)]
+pub struct PluginConnectionConfig {
+    /// The plugin which provides the connector
+    pub plugin: &'static ConnectorPlugin,
+    /// The options of the data source, parsed by the plugin's connector
+    pub options: config::Value,
+}
+
+impl Connector for PluginConnector {
+    type TConnectionPool = PluginConnectionPool;
+    type TConnection = PluginConnection;
+    type TConnectionConfig = PluginConnectionConfig;
+    type TEntitySearcher = PluginEntitySearcher;
+    type TEntityValidator = PluginEntityValidator;
+    type TEntitySourceConfig = PluginEntitySourceConfig;
+    type TQueryPlanner = PluginQueryPlanner;
+    type TQueryCompiler = PluginQueryCompiler;
+    type TQueryHandle = PluginQueryHandle;
+    type TQuery = PluginQuery;
+    type TResultSet = PluginResultSet;
+    type TTransactionManager = PluginConnection;
+
+    const TYPE: &'static str = "plugin";
+
+    fn parse_options(_options: config::Value) -> Result<Self::TConnectionConfig> {
+        bail!("The options of plugin connectors must be parsed by their plugin")
+    }
+
+    fn parse_entity_source_options(options: config::Value) -> Result<Self::TEntitySourceConfig> {
+        Ok(PluginEntitySourceConfig(options))
+    }
+
+    fn create_connection_pool(
+        options: PluginConnectionConfig,
+        nc: &NodeConfig,
+        entities: &PluginEntityConfig,
+    ) -> Result<PluginConnectionPool> {
+        let plugin = options.plugin;
+        let inner = plugin.guard(|| {
+            plugin
+                .connector
+                .create_connection_pool(options.options, nc, entities)
+        })?;
+
+        Ok(PluginConnectionPool::new(plugin, inner))
+    }
+}
+
+/// Exports the supplied connector from a plugin library.
+///
+/// ```ignore
+/// ansilo_connectors_plugin::export_connector!(MyConnector);
+/// ```
+#[macro_export]
+macro_rules! export_connector {
+    ($($connector:ty),+ $(,)?) => {
+        #[no_mangle]
+        pub static ANSILO_PLUGIN_DECLARATION: $crate::PluginDeclaration = $crate::PluginDeclaration {
+            api_version: $crate::PLUGIN_API_VERSION,
+            rustc_version: $crate::RUSTC_VERSION,
+            types_hash: $crate::TYPES_HASH,
+            register: __ansilo_plugin_register,
+        };
+
+        fn __ansilo_plugin_register(registrar: &mut dyn $crate::PluginRegistrar) {
+            $(
+                registrar.register_connector(::std::boxed::Box::new(
+                    $crate::ConnectorAdapter::<$connector>::new(),
+                ));
+            )+
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_connectors_base::{
+        common::entity::EntitySource,
+        interface::{
+            Connection, ConnectionPool, EntityDiscoverOptions, EntitySearcher, EntityValidator,
+            QueryCompiler, QueryHandle, QueryPlanner, ResultSet,
+        },
+    };
+    use ansilo_connectors_memory::MemoryConnector;
+    use ansilo_core::{
+        config::{EntityAttributeConfig, EntityConfig, EntitySourceConfig},
+        data::{DataType, DataValue},
+        sqlil as sql,
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn mock_entities() -> PluginEntityConfig {
+        let mut entities = PluginEntityConfig::new();
+        entities.add(EntitySource::new(
+            EntityConfig::minimal(
+                "dummy",
+                vec![EntityAttributeConfig::minimal("x", DataType::Int64)],
+                EntitySourceConfig::minimal("memory"),
+            ),
+            PluginEntitySourceConfig(serde_yaml::from_str("{}").unwrap()),
+        ));
+
+        entities
+    }
+
+    #[test]
+    fn test_plugin_connector_with_memory_connector() {
+        let plugin = register(Box::new(ConnectorAdapter::<MemoryConnector>::new()), None).unwrap();
+
+        assert_eq!(plugin.r#type(), MemoryConnector::TYPE);
+        assert_eq!(find(MemoryConnector::TYPE), Some(plugin));
+
+        let nc = NodeConfig::default();
+        let entities = mock_entities();
+        let options = plugin
+            .parse_options(serde_yaml::from_str("dummy: [[1], [2]]").unwrap())
+            .unwrap();
+        let mut pool = PluginConnector::create_connection_pool(options, &nc, &entities).unwrap();
+        let mut con = pool.acquire(None).unwrap();
+
+        let discovered =
+            PluginEntitySearcher::discover(&mut con, &nc, EntityDiscoverOptions::default())
+                .unwrap();
+        assert_eq!(discovered.len(), 1);
+        assert_eq!(discovered[0].id, "dummy");
+
+        let entity = PluginEntityValidator::validate(&mut con, &discovered[0], &nc).unwrap();
+
+        let (_, mut select) = PluginQueryPlanner::create_base_select(
+            &mut con,
+            &entities,
+            &entity,
+            &sql::source("dummy", "d"),
+        )
+        .unwrap();
+        select.cols.push(("x".into(), sql::Expr::attr("d", "x")));
+
+        let query = PluginQueryCompiler::compile_query(&mut con, &entities, select.into()).unwrap();
+        assert!(PluginQueryCompiler::query_key(&query).is_some());
+
+        let mut handle = con.prepare(query).unwrap();
+        let rows = handle
+            .execute_query()
+            .unwrap()
+            .reader()
+            .unwrap()
+            .iter_row_vecs()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(
+            rows,
+            vec![vec![DataValue::Int64(1)], vec![DataValue::Int64(2)]]
+        );
+    }
+
+    #[test]
+    fn test_plugin_connector_parse_options_is_unsupported() {
+        PluginConnector::parse_options(config::Value::Null).unwrap_err();
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    fs, mem,
+    path::{Path, PathBuf},
+};
+
+use ansilo_core::err::{bail, ensure, Context, Error, Result};
+use ansilo_logging::{info, warn};
+use libloading::Library;
+
+use crate::{
+    registry::{self, catch_panic},
+    ConnectorPlugin, DynConnector, PluginDeclaration, PluginRegistrar, PLUGIN_API_VERSION,
+    PLUGIN_DECLARATION_SYMBOL, RUSTC_VERSION, TYPES_HASH,
+};
+
+/// Collects the connectors registered by a plugin library
+#[derive(Default)]
+struct Registrar {
+    connectors: Vec<Box<dyn DynConnector>>,
+}
+
+impl PluginRegistrar for Registrar {
+    fn register_connector(&mut self, connector: Box<dyn DynConnector>) {
+        self.connectors.push(connector);
+    }
+}
+
+/// Loads the plugin libraries from the supplied directory.
+///
+/// Libraries which fail to load are logged and skipped so a single
+/// faulty plugin does not prevent ansilo from starting.
+pub fn load_dir(dir: &Path) -> Result<Vec<&'static ConnectorPlugin>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut paths = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read plugins directory {}", dir.display()))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()
+        .with_context(|| format!("Failed to read plugins directory {}", dir.display()))?;
+
+    paths.retain(|p| {
+        p.is_file()
+            && p.extension().and_then(|e| e.to_str()) == Some(std::env::consts::DLL_EXTENSION)
+    });
+    paths.sort();
+
+    let mut plugins = vec![];
+
+    for path in paths {
+        match load(&path) {
+            Ok(loaded) => plugins.extend(loaded),
+            Err(err) => warn!("Failed to load plugin {}: {:?}", path.display(), err),
+        }
+    }
+
+    Ok(plugins)
+}
+
+/// Loads the plugin library at the supplied path and registers its connectors.
+///
+/// Libraries are never unloaded once loaded.
+pub fn load(path: &Path) -> Result<Vec<&'static ConnectorPlugin>> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("Failed to find plugin {}", path.display()))?;
+
+    if registry::is_loaded(&path) {
+        return Ok(vec![]);
+    }
+
+    let library = unsafe { Library::new(&path) }.context("Failed to load library")?;
+
+    let decl = unsafe {
+        *library
+            .get::<*const PluginDeclaration>(PLUGIN_DECLARATION_SYMBOL)
+            .context("Library is not an ansilo plugin, the plugin declaration was not found")?
+    };
+
+    // The api version is checked before the rest of the declaration
+    // is read as its layout may differ between versions
+    let api_version = unsafe { std::ptr::addr_of!((*decl).api_version).read() };
+    ensure!(
+        api_version == PLUGIN_API_VERSION,
+        "Plugin was built against plugin API version {} but version {} is required",
+        api_version,
+        PLUGIN_API_VERSION
+    );
+
+    let decl = unsafe { decl.read() };
+    ensure!(
+        decl.rustc_version == RUSTC_VERSION,
+        "Plugin was compiled with '{}' but '{}' is required",
+        decl.rustc_version,
+        RUSTC_VERSION
+    );
+    ensure!(
+        decl.types_hash == TYPES_HASH,
+        "Plugin was built against different versions of ansilo-core and ansilo-connectors-base (types hash {} but {} is required)",
+        decl.types_hash,
+        TYPES_HASH
+    );
+
+    let mut registrar = Registrar::default();
+    catch_panic(|| (decl.register)(&mut registrar))
+        .map_err(|msg| Error::msg(format!("Plugin panicked during registration: {msg}")))?;
+
+    if registrar.connectors.is_empty() {
+        bail!("Plugin did not register any connectors");
+    }
+
+    // The connectors of the plugin are used for the life of the process
+    mem::forget(library);
+
+    let mut plugins = vec![];
+
+    for connector in registrar.connectors {
+        match registry::register(connector, Some(&path)) {
+            Ok(plugin) => plugins.push(plugin),
+            Err(err) => warn!(
+                "Failed to register connector from {}: {:?}",
+                path.display(),
+                err
+            ),
+        }
+    }
+
+    info!(
+        "Loaded plugin {} with {} connector(s)",
+        path.display(),
+        plugins.len()
+    );
+
+    Ok(plugins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plugin_load_dir_missing() {
+        assert!(load_dir(Path::new("/this/does/not/exist"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_plugin_load_dir_skips_invalid_libraries() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir
+            .path()
+            .join(format!("invalid.{}", std::env::consts::DLL_EXTENSION));
+        fs::write(&lib, "not a library").unwrap();
+        fs::write(dir.path().join("readme.txt"), "not a plugin").unwrap();
+
+        assert!(load_dir(dir.path()).unwrap().is_empty());
+        load(&lib).unwrap_err();
+    }
+}
@@ -0,0 +1,83 @@
+use ansilo_connectors_base::interface::{
+    LoggedQuery, QueryHandle, QueryInputStructure, ResultSet, RowStructure,
+};
+use ansilo_core::err::Result;
+
+use crate::{ConnectorPlugin, DynQueryHandle, DynResultSet};
+
+/// A query prepared by a plugin connector
+pub struct PluginQueryHandle {
+    plugin: &'static ConnectorPlugin,
+    inner: Box<dyn DynQueryHandle>,
+}
+
+impl PluginQueryHandle {
+    pub(crate) fn new(plugin: &'static ConnectorPlugin, inner: Box<dyn DynQueryHandle>) -> Self {
+        Self { plugin, inner }
+    }
+}
+
+impl QueryHandle for PluginQueryHandle {
+    type TResultSet = PluginResultSet;
+
+    fn get_structure(&self) -> Result<QueryInputStructure> {
+        self.plugin.guard(|| self.inner.get_structure())
+    }
+
+    fn supports_batching(&self) -> bool {
+        self.plugin
+            .guard(|| Ok(self.inner.supports_batching()))
+            .unwrap_or(false)
+    }
+
+    fn write(&mut self, buff: &[u8]) -> Result<usize> {
+        let inner = &mut self.inner;
+        self.plugin.guard(|| inner.write(buff))
+    }
+
+    fn restart(&mut self) -> Result<()> {
+        let inner = &mut self.inner;
+        self.plugin.guard(|| inner.restart())
+    }
+
+    fn execute_query(&mut self) -> Result<PluginResultSet> {
+        let inner = &mut self.inner;
+        let inner = self.plugin.guard(|| inner.execute_query())?;
+
+        Ok(PluginResultSet {
+            plugin: self.plugin,
+            inner,
+        })
+    }
+
+    fn execute_modify(&mut self) -> Result<Option<u64>> {
+        let inner = &mut self.inner;
+        self.plugin.guard(|| inner.execute_modify())
+    }
+
+    fn add_to_batch(&mut self) -> Result<()> {
+        let inner = &mut self.inner;
+        self.plugin.guard(|| inner.add_to_batch())
+    }
+
+    fn logged(&self) -> Result<LoggedQuery> {
+        self.plugin.guard(|| self.inner.logged())
+    }
+}
+
+/// The results of a query executed by a plugin connector
+pub struct PluginResultSet {
+    plugin: &'static ConnectorPlugin,
+    inner: Box<dyn DynResultSet>,
+}
+
+impl ResultSet for PluginResultSet {
+    fn get_structure(&self) -> Result<RowStructure> {
+        self.plugin.guard(|| self.inner.get_structure())
+    }
+
+    fn read(&mut self, buff: &mut [u8]) -> Result<usize> {
+        let inner = &mut self.inner;
+        self.plugin.guard(|| inner.read(buff))
+    }
+}
@@ -0,0 +1,33 @@
+use ansilo_core::{err::Result, sqlil as sql};
+
+use ansilo_connectors_base::interface::QueryCompiler;
+
+use crate::{PluginConnection, PluginEntityConfig, PluginEntitySourceConfig, PluginQuery};
+
+pub struct PluginQueryCompiler;
+
+impl QueryCompiler for PluginQueryCompiler {
+    type TConnection = PluginConnection;
+    type TQuery = PluginQuery;
+    type TEntitySourceConfig = PluginEntitySourceConfig;
+
+    fn compile_query(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        query: sql::Query,
+    ) -> Result<PluginQuery> {
+        connection.call(|c| c.compile_query(conf, query))
+    }
+
+    fn query_from_string(
+        connection: &mut PluginConnection,
+        query: String,
+        params: Vec<sql::Parameter>,
+    ) -> Result<PluginQuery> {
+        connection.call(|c| c.query_from_string(query, params))
+    }
+
+    fn query_key(query: &PluginQuery) -> Option<String> {
+        query.key.clone()
+    }
+}
@@ -0,0 +1,177 @@
+use ansilo_core::{
+    data::DataType,
+    err::{Error, Result},
+    sqlil as sql,
+};
+
+use ansilo_connectors_base::interface::{
+    BulkInsertQueryOperation, DeleteQueryOperation, InsertQueryOperation, OperationCost,
+    QueryOperationResult, QueryPlanner, SelectQueryOperation, UpdateQueryOperation,
+};
+
+use crate::{
+    PluginConnection, PluginEntityConfig, PluginEntitySource, PluginEntitySourceConfig, PluginQuery,
+};
+
+pub struct PluginQueryPlanner {}
+
+impl QueryPlanner for PluginQueryPlanner {
+    type TConnection = PluginConnection;
+    type TQuery = PluginQuery;
+    type TEntitySourceConfig = PluginEntitySourceConfig;
+
+    fn estimate_size(
+        connection: &mut PluginConnection,
+        entity: &PluginEntitySource,
+    ) -> Result<OperationCost> {
+        connection.call(|c| c.estimate_size(entity))
+    }
+
+    fn get_row_id_exprs(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        entity: &PluginEntitySource,
+        source: &sql::EntitySource,
+    ) -> Result<Vec<(sql::Expr, DataType)>> {
+        connection.call(|c| c.get_row_id_exprs(conf, entity, source))
+    }
+
+    fn create_base_query(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        entity: &PluginEntitySource,
+        source: &sql::EntitySource,
+        r#type: sql::QueryType,
+    ) -> Result<(OperationCost, sql::Query)> {
+        connection.call(|c| c.create_base_query(conf, entity, source, r#type))
+    }
+
+    fn create_base_select(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        entity: &PluginEntitySource,
+        source: &sql::EntitySource,
+    ) -> Result<(OperationCost, sql::Select)> {
+        let (cost, query) =
+            Self::create_base_query(connection, conf, entity, source, sql::QueryType::Select)?;
+
+        Ok((cost, query.into_select().map_err(unexpected_query)?))
+    }
+
+    fn create_base_insert(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        entity: &PluginEntitySource,
+        source: &sql::EntitySource,
+    ) -> Result<(OperationCost, sql::Insert)> {
+        let (cost, query) =
+            Self::create_base_query(connection, conf, entity, source, sql::QueryType::Insert)?;
+
+        Ok((cost, query.into_insert().map_err(unexpected_query)?))
+    }
+
+    fn create_base_bulk_insert(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        entity: &PluginEntitySource,
+        source: &sql::EntitySource,
+    ) -> Result<(OperationCost, sql::BulkInsert)> {
+        let (cost, query) =
+            Self::create_base_query(connection, conf, entity, source, sql::QueryType::BulkInsert)?;
+
+        Ok((cost, query.into_bulk_insert().map_err(unexpected_query)?))
+    }
+
+    fn create_base_update(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        entity: &PluginEntitySource,
+        source: &sql::EntitySource,
+    ) -> Result<(OperationCost, sql::Update)> {
+        let (cost, query) =
+            Self::create_base_query(connection, conf, entity, source, sql::QueryType::Update)?;
+
+        Ok((cost, query.into_update().map_err(unexpected_query)?))
+    }
+
+    fn create_base_delete(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        entity: &PluginEntitySource,
+        source: &sql::EntitySource,
+    ) -> Result<(OperationCost, sql::Delete)> {
+        let (cost, query) =
+            Self::create_base_query(connection, conf, entity, source, sql::QueryType::Delete)?;
+
+        Ok((cost, query.into_delete().map_err(unexpected_query)?))
+    }
+
+    fn apply_select_operation(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        select: &mut sql::Select,
+        op: SelectQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        connection.call(|c| c.apply_select_operation(conf, select, op))
+    }
+
+    fn get_insert_max_bulk_size(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        insert: &sql::Insert,
+    ) -> Result<u32> {
+        connection.call(|c| c.get_insert_max_bulk_size(conf, insert))
+    }
+
+    fn apply_insert_operation(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        insert: &mut sql::Insert,
+        op: InsertQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        connection.call(|c| c.apply_insert_operation(conf, insert, op))
+    }
+
+    fn apply_bulk_insert_operation(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        insert: &mut sql::BulkInsert,
+        op: BulkInsertQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        connection.call(|c| c.apply_bulk_insert_operation(conf, insert, op))
+    }
+
+    fn apply_update_operation(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        update: &mut sql::Update,
+        op: UpdateQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        connection.call(|c| c.apply_update_operation(conf, update, op))
+    }
+
+    fn apply_delete_operation(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        delete: &mut sql::Delete,
+        op: DeleteQueryOperation,
+    ) -> Result<QueryOperationResult> {
+        connection.call(|c| c.apply_delete_operation(conf, delete, op))
+    }
+
+    fn explain_query(
+        connection: &mut PluginConnection,
+        conf: &PluginEntityConfig,
+        query: &sql::Query,
+        verbose: bool,
+    ) -> Result<serde_json::Value> {
+        connection.call(|c| c.explain_query(conf, query, verbose))
+    }
+}
+
+fn unexpected_query(query: sql::Query) -> Error {
+    Error::msg(format!(
+        "Plugin connector returned unexpected base query: {:?}",
+        query.r#type()
+    ))
+}
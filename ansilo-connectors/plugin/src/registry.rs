@@ -0,0 +1,281 @@
+use std::{
+    any::Any,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+};
+
+use ansilo_core::{
+    config,
+    err::{bail, Error, Result},
+};
+use ansilo_logging::{error, info};
+
+use crate::{DynConnector, PluginConnectionConfig, PluginEntitySourceConfig};
+
+lazy_static::lazy_static! {
+    /// The connectors registered by the loaded plugins
+    static ref PLUGINS: RwLock<Vec<&'static ConnectorPlugin>> = RwLock::new(vec![]);
+}
+
+/// A connector provided by a plugin.
+///
+/// Plugins are registered for the life of the process as the
+/// connections and queries of their connectors may be in use at any time.
+pub struct ConnectorPlugin {
+    /// The type of the data sources of the connector
+    r#type: &'static str,
+    /// The path of the library which provided the connector, if loaded from a library
+    path: Option<PathBuf>,
+    /// The connector implemented by the plugin
+    pub(crate) connector: Box<dyn DynConnector>,
+    /// The number of panics caught from the plugin
+    panics: AtomicU64,
+}
+
+/// The error returned when a call into a plugin panics.
+///
+/// The panic is isolated to the call so it does not bring down the rest of ansilo.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginPanic {
+    /// The type of the connector which panicked
+    pub connector: String,
+    /// The panic message
+    pub message: String,
+}
+
+impl fmt::Display for PluginPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Plugin connector '{}' panicked: {}",
+            self.connector, self.message
+        )
+    }
+}
+
+impl std::error::Error for PluginPanic {}
+
+impl ConnectorPlugin {
+    /// Gets the type of the data sources of the connector
+    pub fn r#type(&self) -> &'static str {
+        self.r#type
+    }
+
+    /// Gets the path of the library which provided the connector
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Gets the number of panics caught from the plugin
+    pub fn panics(&self) -> u64 {
+        self.panics.load(Ordering::Relaxed)
+    }
+
+    /// Parses the data source options using the connector of the plugin
+    pub fn parse_options(&'static self, options: config::Value) -> Result<PluginConnectionConfig> {
+        self.guard(|| self.connector.validate_options(options.clone()))?;
+
+        Ok(PluginConnectionConfig {
+            plugin: self,
+            options,
+        })
+    }
+
+    /// Parses the entity source options using the connector of the plugin
+    pub fn parse_entity_source_options(
+        &self,
+        options: config::Value,
+    ) -> Result<PluginEntitySourceConfig> {
+        self.guard(|| {
+            self.connector
+                .validate_entity_source_options(options.clone())
+        })?;
+
+        Ok(PluginEntitySourceConfig(options))
+    }
+
+    /// Calls into the plugin, returning a [`PluginPanic`] error if the plugin panics
+    pub(crate) fn guard<R>(&self, f: impl FnOnce() -> Result<R>) -> Result<R> {
+        match catch_panic(f) {
+            Ok(res) => res,
+            Err(message) => {
+                self.panics.fetch_add(1, Ordering::Relaxed);
+                error!("Plugin connector '{}' panicked: {}", self.r#type, message);
+
+                Err(PluginPanic {
+                    connector: self.r#type.into(),
+                    message,
+                }
+                .into())
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ConnectorPlugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectorPlugin")
+            .field("type", &self.r#type)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl PartialEq for ConnectorPlugin {
+    fn eq(&self, other: &Self) -> bool {
+        self.r#type == other.r#type
+    }
+}
+
+/// Registers the connector so data sources of its type can be configured.
+///
+/// Returns an error if a connector of the same type has already been registered.
+pub fn register(
+    connector: Box<dyn DynConnector>,
+    path: Option<&Path>,
+) -> Result<&'static ConnectorPlugin> {
+    let r#type = catch_panic(|| connector.r#type())
+        .map_err(|msg| Error::msg(format!("Plugin connector panicked: {msg}")))?;
+
+    let mut plugins = PLUGINS
+        .write()
+        .map_err(|_| Error::msg("Failed to lock plugin registry"))?;
+
+    if plugins.iter().any(|p| p.r#type == r#type) {
+        bail!(
+            "A plugin connector of type '{}' is already registered",
+            r#type
+        );
+    }
+
+    let plugin: &'static ConnectorPlugin = Box::leak(Box::new(ConnectorPlugin {
+        r#type,
+        path: path.map(|p| p.to_path_buf()),
+        connector,
+        panics: AtomicU64::new(0),
+    }));
+    plugins.push(plugin);
+
+    info!("Registered plugin connector '{}'", r#type);
+
+    Ok(plugin)
+}
+
+/// Finds the registered connector of the supplied type
+pub fn find(r#type: &str) -> Option<&'static ConnectorPlugin> {
+    all().into_iter().find(|p| p.r#type == r#type)
+}
+
+/// Returns all the registered connectors
+pub fn all() -> Vec<&'static ConnectorPlugin> {
+    PLUGINS.read().map(|p| p.clone()).unwrap_or_default()
+}
+
+/// Whether a connector has been registered from the library at the supplied path
+pub(crate) fn is_loaded(path: &Path) -> bool {
+    all().iter().any(|p| p.path() == Some(path))
+}
+
+/// Runs the supplied function, returning the panic message if it panics
+pub(crate) fn catch_panic<R>(f: impl FnOnce() -> R) -> Result<R, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| panic_message(&*payload))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::config::NodeConfig;
+
+    use crate::{DynConnectionPool, PluginEntityConfig};
+
+    use super::*;
+
+    struct PanickingConnector(&'static str);
+
+    impl DynConnector for PanickingConnector {
+        fn r#type(&self) -> &'static str {
+            self.0
+        }
+
+        fn validate_options(&self, _options: config::Value) -> Result<()> {
+            panic!("invalid options")
+        }
+
+        fn validate_entity_source_options(&self, _options: config::Value) -> Result<()> {
+            Ok(())
+        }
+
+        fn create_connection_pool(
+            &self,
+            _options: config::Value,
+            _nc: &NodeConfig,
+            _entities: &PluginEntityConfig,
+        ) -> Result<Box<dyn DynConnectionPool>> {
+            panic!("failed to connect")
+        }
+    }
+
+    #[test]
+    fn test_plugin_registry_register_duplicate_type() {
+        register(Box::new(PanickingConnector("test.duplicate")), None).unwrap();
+        register(Box::new(PanickingConnector("test.duplicate")), None).unwrap_err();
+
+        assert_eq!(
+            all()
+                .iter()
+                .filter(|p| p.r#type() == "test.duplicate")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_plugin_registry_find_unknown_type() {
+        assert_eq!(find("test.unknown"), None);
+    }
+
+    #[test]
+    fn test_plugin_panic_is_isolated() {
+        let plugin = register(Box::new(PanickingConnector("test.panic")), None).unwrap();
+
+        let err = plugin.parse_options(config::Value::Null).unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<PluginPanic>(),
+            Some(&PluginPanic {
+                connector: "test.panic".into(),
+                message: "invalid options".into()
+            })
+        );
+        assert_eq!(plugin.panics(), 1);
+
+        plugin
+            .parse_entity_source_options(config::Value::Null)
+            .unwrap();
+        assert_eq!(plugin.panics(), 1);
+    }
+
+    #[test]
+    fn test_plugin_panic_message() {
+        assert_eq!(catch_panic::<()>(|| panic!("static")), Err("static".into()));
+        assert_eq!(
+            catch_panic::<()>(|| panic!("formatted {}", 1)),
+            Err("formatted 1".into())
+        );
+        assert_eq!(catch_panic(|| 1), Ok(1));
+    }
+}
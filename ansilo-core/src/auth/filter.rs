@@ -0,0 +1,319 @@
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    data::{DataType, DataValue},
+    sqlil,
+};
+
+use super::{AuthContext, ProviderAuthContext};
+
+/// A row filter restricting the rows of an entity which are visible to a user.
+///
+/// Row filters are declared in the form `<attribute> <operator> <value>` where
+/// the value is either a literal or a reference to the authentication context,
+/// eg `region = ${auth.claims.region}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowFilter {
+    /// The attribute id of the entity which is filtered
+    pub attribute: String,
+    /// The comparison operator
+    pub op: sqlil::BinaryOpType,
+    /// The value the attribute is compared against
+    pub value: RowFilterValue,
+}
+
+/// The value of a row filter
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowFilterValue {
+    /// A literal value
+    Literal(String),
+    /// The username of the authenticated user: `${auth.username}`
+    Username,
+    /// The authentication provider: `${auth.provider}`
+    Provider,
+    /// The id of the service user: `${auth.service_user_id}`
+    ServiceUserId,
+    /// A claim of the JWT or custom provider context: `${auth.claims.<claim>}`
+    Claim(String),
+}
+
+/// The supported comparison operators, longest first so they are matched greedily
+const OPERATORS: [(&str, sqlil::BinaryOpType); 7] = [
+    ("!=", sqlil::BinaryOpType::NotEqual),
+    ("<>", sqlil::BinaryOpType::NotEqual),
+    (">=", sqlil::BinaryOpType::GreaterThanOrEqual),
+    ("<=", sqlil::BinaryOpType::LessThanOrEqual),
+    ("=", sqlil::BinaryOpType::Equal),
+    (">", sqlil::BinaryOpType::GreaterThan),
+    ("<", sqlil::BinaryOpType::LessThan),
+];
+
+impl RowFilter {
+    /// Parses the supplied row filter
+    pub fn parse(filter: &str) -> Result<Self> {
+        let filter = filter.trim();
+        let attr_len = filter
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(filter.len());
+        let (attribute, rest) = filter.split_at(attr_len);
+
+        if attribute.is_empty() {
+            bail!("Row filter '{filter}' must start with an attribute id");
+        }
+
+        let rest = rest.trim_start();
+        let (op, value) = match OPERATORS.iter().find(|(op, _)| rest.starts_with(op)) {
+            Some((op, r#type)) => (*r#type, rest[op.len()..].trim()),
+            None => bail!(
+                "Row filter '{filter}' must compare the attribute using one of =, !=, <>, >, >=, <, <="
+            ),
+        };
+
+        if value.is_empty() {
+            bail!("Row filter '{filter}' must compare the attribute to a value");
+        }
+
+        Ok(Self {
+            attribute: attribute.to_string(),
+            op,
+            value: RowFilterValue::parse(value)?,
+        })
+    }
+
+    /// Converts the row filter into a condition on the supplied entity alias,
+    /// with the value bound from the supplied authentication context and
+    /// coerced into the type of the attribute.
+    pub fn to_expr(
+        &self,
+        alias: &str,
+        r#type: &DataType,
+        auth: Option<&AuthContext>,
+    ) -> Result<sqlil::Expr> {
+        let value = self
+            .value
+            .resolve(auth)?
+            .try_coerce_into(r#type)
+            .with_context(|| {
+                format!(
+                    "Failed to convert row filter value for attribute '{}' to {:?}",
+                    self.attribute, r#type
+                )
+            })?;
+
+        Ok(sqlil::Expr::BinaryOp(sqlil::BinaryOp::new(
+            sqlil::Expr::attr(alias, &self.attribute),
+            self.op,
+            sqlil::Expr::constant(value),
+        )))
+    }
+}
+
+impl RowFilterValue {
+    fn parse(value: &str) -> Result<Self> {
+        let path = match value.strip_prefix("${").and_then(|v| v.strip_suffix('}')) {
+            Some(path) => path.trim(),
+            None => {
+                let literal = value
+                    .strip_prefix('\'')
+                    .and_then(|v| v.strip_suffix('\''))
+                    .unwrap_or(value);
+
+                return Ok(Self::Literal(literal.to_string()));
+            }
+        };
+
+        Ok(match path {
+            "auth.username" => Self::Username,
+            "auth.provider" => Self::Provider,
+            "auth.service_user_id" => Self::ServiceUserId,
+            _ => match path.strip_prefix("auth.claims.") {
+                Some(claim) if !claim.is_empty() => Self::Claim(claim.to_string()),
+                _ => bail!(
+                    "Unknown row filter value '{value}', expecting one of ${{auth.username}}, ${{auth.provider}}, ${{auth.service_user_id}} or ${{auth.claims.<claim>}}"
+                ),
+            },
+        })
+    }
+
+    /// Resolves the value from the authentication context.
+    /// If the value cannot be resolved we fail rather than exposing unfiltered rows.
+    fn resolve(&self, auth: Option<&AuthContext>) -> Result<DataValue> {
+        if let Self::Literal(literal) = self {
+            return Ok(DataValue::Utf8String(literal.clone()));
+        }
+
+        let auth = auth.context("Row filter requires an authenticated user")?;
+
+        Ok(match self {
+            Self::Literal(_) => unreachable!(),
+            Self::Username => DataValue::Utf8String(auth.username.clone()),
+            Self::Provider => DataValue::Utf8String(auth.provider.clone()),
+            Self::ServiceUserId => match auth.service_user_id.as_ref() {
+                Some(id) => DataValue::Utf8String(id.clone()),
+                None => bail!("Row filter requires a service user"),
+            },
+            Self::Claim(claim) => {
+                let value = match &auth.more {
+                    ProviderAuthContext::Jwt(jwt) => jwt.claims.get(claim),
+                    ProviderAuthContext::Custom(custom) => custom.data.get(claim),
+                    _ => None,
+                };
+
+                match value {
+                    Some(serde_json::Value::String(s)) => DataValue::Utf8String(s.clone()),
+                    Some(serde_json::Value::Bool(b)) => DataValue::Boolean(*b),
+                    Some(serde_json::Value::Number(n)) => match n.as_i64() {
+                        Some(n) => DataValue::Int64(n),
+                        None => DataValue::Float64(n.as_f64().unwrap_or(f64::NAN)),
+                    },
+                    Some(_) => bail!("Claim '{claim}' used in row filter must be a scalar value"),
+                    None => bail!("Claim '{claim}' used in row filter is not present"),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use crate::auth::{JwtAuthContext, PasswordAuthContext};
+
+    use super::*;
+
+    fn jwt_auth(claims: serde_json::Value) -> AuthContext {
+        AuthContext::new(
+            "user",
+            "jwt",
+            None,
+            ProviderAuthContext::Jwt(JwtAuthContext {
+                raw_token: "".into(),
+                header: json!({}),
+                claims: serde_json::from_value::<HashMap<_, _>>(claims).unwrap(),
+            }),
+        )
+    }
+
+    #[test]
+    fn test_row_filter_parse() {
+        assert_eq!(
+            RowFilter::parse("region = ${auth.claims.region}").unwrap(),
+            RowFilter {
+                attribute: "region".into(),
+                op: sqlil::BinaryOpType::Equal,
+                value: RowFilterValue::Claim("region".into())
+            }
+        );
+        assert_eq!(
+            RowFilter::parse("owner=${auth.username}").unwrap(),
+            RowFilter {
+                attribute: "owner".into(),
+                op: sqlil::BinaryOpType::Equal,
+                value: RowFilterValue::Username
+            }
+        );
+        assert_eq!(
+            RowFilter::parse("level <= 'secret'").unwrap(),
+            RowFilter {
+                attribute: "level".into(),
+                op: sqlil::BinaryOpType::LessThanOrEqual,
+                value: RowFilterValue::Literal("secret".into())
+            }
+        );
+        assert_eq!(
+            RowFilter::parse("status <> 1").unwrap(),
+            RowFilter {
+                attribute: "status".into(),
+                op: sqlil::BinaryOpType::NotEqual,
+                value: RowFilterValue::Literal("1".into())
+            }
+        );
+    }
+
+    #[test]
+    fn test_row_filter_parse_invalid() {
+        RowFilter::parse("").unwrap_err();
+        RowFilter::parse("= 1").unwrap_err();
+        RowFilter::parse("region").unwrap_err();
+        RowFilter::parse("region ~ 1").unwrap_err();
+        RowFilter::parse("region = ").unwrap_err();
+        RowFilter::parse("region = ${auth.password}").unwrap_err();
+        RowFilter::parse("region = ${auth.claims.}").unwrap_err();
+    }
+
+    #[test]
+    fn test_row_filter_to_expr() {
+        let auth = jwt_auth(json!({"region": "eu", "tenant": 5}));
+
+        assert_eq!(
+            RowFilter::parse("region = ${auth.claims.region}")
+                .unwrap()
+                .to_expr("t", &DataType::rust_string(), Some(&auth))
+                .unwrap(),
+            sqlil::Expr::BinaryOp(sqlil::BinaryOp::new(
+                sqlil::Expr::attr("t", "region"),
+                sqlil::BinaryOpType::Equal,
+                sqlil::Expr::constant(DataValue::from("eu"))
+            ))
+        );
+        assert_eq!(
+            RowFilter::parse("tenant_id = ${auth.claims.tenant}")
+                .unwrap()
+                .to_expr("t", &DataType::Int32, Some(&auth))
+                .unwrap(),
+            sqlil::Expr::BinaryOp(sqlil::BinaryOp::new(
+                sqlil::Expr::attr("t", "tenant_id"),
+                sqlil::BinaryOpType::Equal,
+                sqlil::Expr::constant(DataValue::Int32(5))
+            ))
+        );
+        assert_eq!(
+            RowFilter::parse("owner = ${auth.username}")
+                .unwrap()
+                .to_expr("t", &DataType::rust_string(), Some(&auth))
+                .unwrap(),
+            sqlil::Expr::BinaryOp(sqlil::BinaryOp::new(
+                sqlil::Expr::attr("t", "owner"),
+                sqlil::BinaryOpType::Equal,
+                sqlil::Expr::constant(DataValue::from("user"))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_row_filter_to_expr_denies_unresolved_values() {
+        let jwt = jwt_auth(json!({"regions": ["eu"]}));
+        let password = AuthContext::new(
+            "user",
+            "password",
+            None,
+            ProviderAuthContext::Password(PasswordAuthContext::default()),
+        );
+        let filter = RowFilter::parse("region = ${auth.claims.region}").unwrap();
+
+        filter
+            .to_expr("t", &DataType::rust_string(), None)
+            .unwrap_err();
+        filter
+            .to_expr("t", &DataType::rust_string(), Some(&jwt))
+            .unwrap_err();
+        filter
+            .to_expr("t", &DataType::rust_string(), Some(&password))
+            .unwrap_err();
+        RowFilter::parse("region = ${auth.claims.regions}")
+            .unwrap()
+            .to_expr("t", &DataType::rust_string(), Some(&jwt))
+            .unwrap_err();
+        RowFilter::parse("id = ${auth.service_user_id}")
+            .unwrap()
+            .to_expr("t", &DataType::rust_string(), Some(&jwt))
+            .unwrap_err();
+        RowFilter::parse("tenant_id = abc")
+            .unwrap()
+            .to_expr("t", &DataType::Int32, Some(&jwt))
+            .unwrap_err();
+    }
+}
@@ -1,2 +1,4 @@
 mod ctx;
 pub use ctx::*;
+mod filter;
+pub use filter::*;
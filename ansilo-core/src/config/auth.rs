@@ -1,8 +1,11 @@
-use std::{collections::HashMap, fmt::Debug};
+use std::{collections::HashMap, fmt::Debug, time::Duration};
 
+use bincode::{Decode, Encode};
 use enum_as_inner::EnumAsInner;
 use serde::{Deserialize, Serialize};
 
+use super::{AttributeMaskConfig, FetchSizeConfig};
+
 /// Authentication options for the node
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Default)]
 pub struct AuthConfig {
@@ -14,6 +17,134 @@ pub struct AuthConfig {
     /// List of service users
     #[serde(default)]
     pub service_users: Vec<ServiceUserConfig>,
+    /// Rules restricting the queries of users
+    #[serde(default)]
+    pub rules: Vec<QueryRuleConfig>,
+    /// Limits on the resources consumed by users
+    #[serde(default)]
+    pub limits: Vec<UserLimitConfig>,
+    /// Masks applied to the attributes with a data classification
+    #[serde(default)]
+    pub masks: Vec<ClassificationMaskConfig>,
+    /// Grants of access to data sources and entities
+    #[serde(default)]
+    pub grants: Vec<GrantConfig>,
+    /// Assigns users to workload classes which are prioritised when querying data sources
+    #[serde(default)]
+    pub workloads: Vec<WorkloadConfig>,
+}
+
+impl AuthConfig {
+    /// Gets the roles assigned to the user with the supplied username
+    pub fn roles(&self, username: &str) -> &[String] {
+        self.users
+            .iter()
+            .find(|u| u.username == username)
+            .map(|u| u.roles.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Gets the query rules which apply to the user with the supplied username
+    pub fn rules_for<'a>(
+        &'a self,
+        username: &'a str,
+    ) -> impl Iterator<Item = &'a QueryRuleConfig> + 'a {
+        let roles = self.roles(username);
+
+        self.rules
+            .iter()
+            .filter(move |r| r.applies_to.matches(username, roles))
+    }
+
+    /// Gets the grants which apply to the user with the supplied username
+    pub fn grants_for<'a>(
+        &'a self,
+        username: &'a str,
+    ) -> impl Iterator<Item = &'a GrantConfig> + 'a {
+        let roles = self.roles(username);
+
+        self.grants
+            .iter()
+            .filter(move |g| g.applies_to.matches(username, roles))
+    }
+
+    /// Whether the user is permitted to execute remote queries on the data source.
+    /// Without any grants, access is only controlled by the EXECUTE privilege on the
+    /// remote_query and remote_execute functions.
+    /// Once any grant is configured, the user requires a grant with `remote_queries`
+    /// on the data source, which must also grant `write` if the query may modify data.
+    pub fn can_remote_query(&self, username: &str, data_source: &str, write: bool) -> bool {
+        self.grants.is_empty()
+            || self.grants_for(username).any(|g| {
+                g.remote_queries
+                    && (g.write || !write)
+                    && g.sources.iter().any(|s| s == data_source)
+            })
+    }
+
+    /// Gets the resource limits of the user with the supplied username.
+    /// If multiple limits apply to the user, including those of their
+    /// workload class, the lowest of each is used.
+    pub fn limits(&self, username: &str) -> UserLimits {
+        let roles = self.roles(username);
+        let workload = self.workload(username).map(|w| UserLimitConfig {
+            applies_to: UserMatchConfig::default(),
+            max_result_rows: w.max_result_rows,
+            max_statement_runtime: w.max_statement_runtime,
+            max_concurrent_statements: w.max_concurrent_statements,
+        });
+
+        self.limits
+            .iter()
+            .filter(|l| l.applies_to.matches(username, roles))
+            .chain(workload.as_ref())
+            .fold(UserLimits::default(), |limits, l| UserLimits {
+                max_result_rows: lowest(limits.max_result_rows, l.max_result_rows),
+                max_statement_runtime: lowest(
+                    limits.max_statement_runtime,
+                    l.max_statement_runtime.map(Duration::from_secs),
+                ),
+                max_concurrent_statements: lowest(
+                    limits.max_concurrent_statements,
+                    l.max_concurrent_statements,
+                ),
+            })
+    }
+
+    /// Gets the workload assigned to the user with the supplied username.
+    /// If multiple workloads match the user the first is used.
+    pub fn workload(&self, username: &str) -> Option<&WorkloadConfig> {
+        let roles = self.roles(username);
+
+        self.workloads
+            .iter()
+            .find(|w| w.applies_to.matches(username, roles))
+    }
+
+    /// Gets the workload class of the user with the supplied username
+    pub fn workload_class(&self, username: &str) -> WorkloadClass {
+        self.workload(username).map(|w| w.class).unwrap_or_default()
+    }
+
+    /// Gets the first mask which targets any of the supplied data classifications
+    pub fn classification_mask<'a>(
+        &'a self,
+        classifications: impl IntoIterator<Item = &'a String>,
+    ) -> Option<&'a AttributeMaskConfig> {
+        let classifications = classifications.into_iter().collect::<Vec<_>>();
+
+        self.masks
+            .iter()
+            .find(|m| classifications.contains(&&m.classification))
+            .map(|m| &m.mask)
+    }
+}
+
+fn lowest<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
 }
 
 /// Defines an auth provider, used to authenticate tokens
@@ -105,11 +236,158 @@ pub struct UserConfig {
     pub description: Option<String>,
     /// The provider used to authenticate this user
     pub provider: Option<String>,
+    /// The roles assigned to the user, used to target access rules
+    #[serde(default)]
+    pub roles: Vec<String>,
     /// Authenticate type specific options
     #[serde(flatten)]
     pub r#type: UserTypeOptions,
 }
 
+/// Matches users by their username or assigned roles
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Encode, Decode, Default)]
+pub struct UserMatchConfig {
+    /// The usernames which are matched
+    #[serde(default)]
+    pub users: Vec<String>,
+    /// The roles which are matched
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl UserMatchConfig {
+    /// Whether the user with the supplied username and roles is matched
+    pub fn matches(&self, username: &str, roles: &[String]) -> bool {
+        self.users.iter().any(|u| u == username) || self.roles.iter().any(|r| roles.contains(r))
+    }
+}
+
+/// Restricts the queries which can be performed by the matched users
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Default)]
+pub struct QueryRuleConfig {
+    /// The users and roles the rule applies to
+    #[serde(flatten)]
+    pub applies_to: UserMatchConfig,
+    /// Denies queries which insert, update or delete data
+    #[serde(default)]
+    pub deny_writes: bool,
+    /// Denies queries which reference entities from more than one data source
+    #[serde(default)]
+    pub deny_cross_source_joins: bool,
+    /// The ids of the entities which can only be queried with a WHERE clause
+    #[serde(default)]
+    pub require_where: Vec<String>,
+}
+
+/// Grants the matched users access to data sources or entities
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Default)]
+pub struct GrantConfig {
+    /// The users and roles which are granted access
+    #[serde(flatten)]
+    pub applies_to: UserMatchConfig,
+    /// The ids of the data sources, granting access to all of their entities
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// The ids of the entities
+    #[serde(default)]
+    pub entities: Vec<String>,
+    /// Grants insert, update and delete access in addition to select
+    #[serde(default)]
+    pub write: bool,
+    /// Grants access to remote_query (and remote_execute if writes are granted)
+    /// on the data sources
+    #[serde(default)]
+    pub remote_queries: bool,
+}
+
+/// Masks the values of all attributes with a data classification
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ClassificationMaskConfig {
+    /// The data classification targeted by the mask, eg `pii`
+    pub classification: String,
+    /// How the values are masked and which users can read the unmasked values
+    #[serde(flatten)]
+    pub mask: AttributeMaskConfig,
+}
+
+/// Limits the resources which can be consumed by the matched users
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Default)]
+pub struct UserLimitConfig {
+    /// The users and roles the limits apply to
+    #[serde(flatten)]
+    pub applies_to: UserMatchConfig,
+    /// The maximum number of rows returned by each query on a data source
+    pub max_result_rows: Option<u64>,
+    /// The number of seconds after which a statement is cancelled
+    pub max_statement_runtime: Option<u64>,
+    /// The maximum number of statements executing concurrently,
+    /// further statements wait until a running statement completes
+    pub max_concurrent_statements: Option<u32>,
+}
+
+/// The resource limits of a user
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct UserLimits {
+    /// The maximum number of rows returned by each query on a data source
+    pub max_result_rows: Option<u64>,
+    /// The maximum runtime of each statement
+    pub max_statement_runtime: Option<Duration>,
+    /// The maximum number of statements executing concurrently
+    pub max_concurrent_statements: Option<u32>,
+}
+
+/// Assigns the matched users to a workload class
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Default)]
+pub struct WorkloadConfig {
+    /// The users and roles assigned to the class
+    #[serde(flatten)]
+    pub applies_to: UserMatchConfig,
+    /// The workload class of the users
+    #[serde(default)]
+    pub class: WorkloadClass,
+    /// The maximum number of sessions of the class concurrently scanning each data source
+    pub max_concurrent_scans: Option<u32>,
+    /// Bounds on the number of rows transferred in each batch, overriding those of the data sources
+    pub fetch_size: Option<FetchSizeConfig>,
+    /// The maximum number of rows returned by each query of a user on a data source
+    pub max_result_rows: Option<u64>,
+    /// The number of seconds after which a statement of a user is cancelled
+    pub max_statement_runtime: Option<u64>,
+    /// The maximum number of statements of a user executing concurrently
+    pub max_concurrent_statements: Option<u32>,
+}
+
+impl WorkloadConfig {
+    /// Gets the maximum number of sessions of the class concurrently scanning each data source
+    pub fn max_concurrent_scans(&self) -> Option<usize> {
+        self.max_concurrent_scans.map(|max| max.max(1) as _)
+    }
+}
+
+/// The classes of workloads, in order of priority.
+/// Queued interactive queries are admitted before batch queries.
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Serialize, Deserialize, Default,
+)]
+pub enum WorkloadClass {
+    /// Queries issued by users waiting on the results, such as dashboards and ad-hoc analysis
+    #[serde(rename = "interactive")]
+    #[default]
+    Interactive,
+    /// Long running queries which are not latency sensitive, such as scheduled extracts
+    #[serde(rename = "batch")]
+    Batch,
+}
+
+impl WorkloadClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorkloadClass::Interactive => "interactive",
+            WorkloadClass::Batch => "batch",
+        }
+    }
+}
+
 /// Type-specific authentication options for this user
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, EnumAsInner)]
 #[serde(untagged)]
@@ -201,12 +479,20 @@ pub enum ServiceUserPasswordMethod {
 }
 
 /// Constant service user password
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
 pub struct ConstantServiceUserPassword {
     /// The password
     pub password: String,
 }
 
+impl Debug for ConstantServiceUserPassword {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConstantServiceUserPassword")
+            .field("password", &"***REDACTED***")
+            .finish()
+    }
+}
+
 /// Retrieve the password from a shell script
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ShellServiceUserPassword {
@@ -235,3 +521,215 @@ impl ServiceUserConfig {
         self.id.as_ref().unwrap_or(&self.username)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_config_limits_uses_lowest_matching() {
+        let conf = AuthConfig {
+            users: vec![UserConfig {
+                username: "mary".into(),
+                description: None,
+                provider: None,
+                roles: vec!["analyst".into()],
+                r#type: UserTypeOptions::Password(PasswordUserConfig {
+                    password: "pass".into(),
+                }),
+            }],
+            limits: vec![
+                UserLimitConfig {
+                    applies_to: UserMatchConfig {
+                        users: vec![],
+                        roles: vec!["analyst".into()],
+                    },
+                    max_result_rows: Some(1000),
+                    max_statement_runtime: Some(60),
+                    max_concurrent_statements: None,
+                },
+                UserLimitConfig {
+                    applies_to: UserMatchConfig {
+                        users: vec!["mary".into()],
+                        roles: vec![],
+                    },
+                    max_result_rows: Some(100),
+                    max_statement_runtime: None,
+                    max_concurrent_statements: Some(2),
+                },
+            ],
+            ..AuthConfig::default()
+        };
+
+        assert_eq!(
+            conf.limits("mary"),
+            UserLimits {
+                max_result_rows: Some(100),
+                max_statement_runtime: Some(Duration::from_secs(60)),
+                max_concurrent_statements: Some(2),
+            }
+        );
+        assert_eq!(conf.limits("john"), UserLimits::default());
+    }
+
+    #[test]
+    fn test_auth_config_workloads() {
+        let conf = AuthConfig {
+            users: vec![UserConfig {
+                username: "etl".into(),
+                description: None,
+                provider: None,
+                roles: vec!["loader".into()],
+                r#type: UserTypeOptions::Password(PasswordUserConfig {
+                    password: "pass".into(),
+                }),
+            }],
+            limits: vec![UserLimitConfig {
+                applies_to: UserMatchConfig {
+                    users: vec!["etl".into()],
+                    roles: vec![],
+                },
+                max_result_rows: Some(100),
+                max_statement_runtime: Some(60),
+                max_concurrent_statements: None,
+            }],
+            workloads: vec![
+                WorkloadConfig {
+                    applies_to: UserMatchConfig {
+                        users: vec![],
+                        roles: vec!["loader".into()],
+                    },
+                    class: WorkloadClass::Batch,
+                    max_statement_runtime: Some(3600),
+                    max_concurrent_statements: Some(2),
+                    ..WorkloadConfig::default()
+                },
+                WorkloadConfig {
+                    applies_to: UserMatchConfig {
+                        users: vec!["etl".into()],
+                        roles: vec![],
+                    },
+                    class: WorkloadClass::Interactive,
+                    ..WorkloadConfig::default()
+                },
+            ],
+            ..AuthConfig::default()
+        };
+
+        assert_eq!(conf.workload_class("etl"), WorkloadClass::Batch);
+        assert_eq!(conf.workload_class("mary"), WorkloadClass::Interactive);
+        assert_eq!(
+            conf.limits("etl"),
+            UserLimits {
+                max_result_rows: Some(100),
+                max_statement_runtime: Some(Duration::from_secs(60)),
+                max_concurrent_statements: Some(2),
+            }
+        );
+    }
+
+    #[test]
+    fn test_auth_config_can_remote_query() {
+        let mut conf = AuthConfig {
+            users: vec![UserConfig {
+                username: "mary".into(),
+                description: None,
+                provider: None,
+                roles: vec!["analyst".into()],
+                r#type: UserTypeOptions::Password(PasswordUserConfig {
+                    password: "pass".into(),
+                }),
+            }],
+            ..AuthConfig::default()
+        };
+
+        // Without grants access is controlled by postgres privileges
+        assert!(conf.can_remote_query("mary", "mysql", false));
+        assert!(conf.can_remote_query("mary", "mysql", true));
+
+        conf.grants = vec![
+            GrantConfig {
+                applies_to: UserMatchConfig {
+                    users: vec![],
+                    roles: vec!["analyst".into()],
+                },
+                sources: vec!["mysql".into()],
+                remote_queries: true,
+                ..GrantConfig::default()
+            },
+            GrantConfig {
+                applies_to: UserMatchConfig {
+                    users: vec!["mary".into()],
+                    roles: vec![],
+                },
+                sources: vec!["oracle".into()],
+                ..GrantConfig::default()
+            },
+            GrantConfig {
+                applies_to: UserMatchConfig {
+                    users: vec!["john".into()],
+                    roles: vec![],
+                },
+                sources: vec!["postgres".into()],
+                write: true,
+                remote_queries: true,
+                ..GrantConfig::default()
+            },
+        ];
+
+        assert!(conf.can_remote_query("mary", "mysql", false));
+        assert!(!conf.can_remote_query("mary", "oracle", false));
+        assert!(!conf.can_remote_query("john", "mysql", false));
+        assert!(conf.can_remote_query("john", "postgres", true));
+    }
+
+    #[test]
+    fn test_auth_config_can_remote_query_denies_other_sources() {
+        let conf = AuthConfig {
+            grants: vec![
+                GrantConfig {
+                    applies_to: UserMatchConfig {
+                        users: vec!["mary".into()],
+                        roles: vec![],
+                    },
+                    sources: vec!["mysql".into()],
+                    remote_queries: true,
+                    ..GrantConfig::default()
+                },
+                GrantConfig {
+                    applies_to: UserMatchConfig {
+                        users: vec!["john".into()],
+                        roles: vec![],
+                    },
+                    entities: vec!["customers".into()],
+                    ..GrantConfig::default()
+                },
+            ],
+            ..AuthConfig::default()
+        };
+
+        // Sources not referenced by any grant are denied once grants are configured
+        assert!(!conf.can_remote_query("mary", "sqlite", false));
+        assert!(!conf.can_remote_query("john", "sqlite", false));
+        assert!(!conf.can_remote_query("john", "mysql", false));
+    }
+
+    #[test]
+    fn test_auth_config_can_remote_query_read_only() {
+        let conf = AuthConfig {
+            grants: vec![GrantConfig {
+                applies_to: UserMatchConfig {
+                    users: vec!["mary".into()],
+                    roles: vec![],
+                },
+                sources: vec!["mysql".into()],
+                remote_queries: true,
+                ..GrantConfig::default()
+            }],
+            ..AuthConfig::default()
+        };
+
+        assert!(conf.can_remote_query("mary", "mysql", false));
+        assert!(!conf.can_remote_query("mary", "mysql", true));
+    }
+}
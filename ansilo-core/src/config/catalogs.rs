@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+use super::UserMatchConfig;
+
+/// The schema the entities of a catalog are imported into by default
+const DEFAULT_CATALOG_SCHEMA: &str = "public";
+
+/// A catalog is a separate database within the node, which is only accessible to
+/// its users and contains the entities of its data sources.
+/// Clients select the catalog using its name as the database name when connecting.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct CatalogConfig {
+    /// The name of the catalog, used as the name of its database
+    pub name: String,
+    /// The description of the catalog
+    pub description: Option<String>,
+    /// The users and roles permitted to connect to the catalog
+    #[serde(flatten)]
+    pub applies_to: UserMatchConfig,
+    /// The IDs of the data sources of which the entities are imported into the catalog
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// The schema the entities are imported into, defaults to "public"
+    pub schema: Option<String>,
+}
+
+impl CatalogConfig {
+    /// Gets the schema the entities are imported into
+    pub fn schema(&self) -> &str {
+        self.schema.as_deref().unwrap_or(DEFAULT_CATALOG_SCHEMA)
+    }
+}
@@ -0,0 +1,107 @@
+use std::{path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+/// The default interval between heartbeats of each member
+const DEFAULT_HEARTBEAT_INTERVAL_SECS: u64 = 5;
+
+/// The default number of heartbeat intervals before a member is considered gone
+const DEFAULT_MEMBER_TTL_INTERVALS: u64 = 3;
+
+/// Configuration options for running the node as a member of a cluster.
+///
+/// Each member shares the same configuration with `node_id` identifying the
+/// local node, typically supplied from an environment variable.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ClusterConfig {
+    /// The unique id of this node within the cluster
+    pub node_id: String,
+    /// The url at which the other members can reach this node's http api
+    pub url: String,
+    /// Where the shared coordination state of the cluster is stored
+    pub store: ClusterStoreConfig,
+    /// The number of seconds between heartbeats of this node
+    pub heartbeat_interval_secs: Option<u64>,
+    /// The number of seconds without a heartbeat before a member is considered gone
+    pub member_ttl_secs: Option<u64>,
+}
+
+/// The backend storing the shared coordination state
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClusterStoreConfig {
+    #[serde(rename = "postgres")]
+    Postgres(PostgresClusterStoreConfig),
+    #[serde(rename = "etcd")]
+    Etcd(EtcdClusterStoreConfig),
+}
+
+/// Stores the coordination state in a postgres database reachable by every member
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PostgresClusterStoreConfig {
+    /// The connection string of the database, eg "host=db.internal user=ansilo password=..."
+    pub url: String,
+    /// The schema the coordination tables are created in, defaults to "ansilo_cluster"
+    pub schema: Option<String>,
+    /// The PEM bundle of CA certificates used to verify the certificate of the database,
+    /// defaults to the system trust store
+    pub ca_bundle: Option<PathBuf>,
+}
+
+/// Stores the coordination state in an etcd cluster using its http api
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct EtcdClusterStoreConfig {
+    /// The urls of the etcd members, eg "http://etcd-1.internal:2379"
+    pub endpoints: Vec<String>,
+    /// The prefix of the keys, defaults to "/ansilo/{name}" using the name of the node
+    pub prefix: Option<String>,
+}
+
+impl ClusterConfig {
+    /// Gets the interval between heartbeats of this node
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.heartbeat_interval_secs
+                .unwrap_or(DEFAULT_HEARTBEAT_INTERVAL_SECS)
+                .max(1),
+        )
+    }
+
+    /// Gets the duration without a heartbeat before a member is considered gone.
+    ///
+    /// This is also the duration after which leadership of a failed member expires.
+    pub fn member_ttl(&self) -> Duration {
+        self.member_ttl_secs
+            .map(Duration::from_secs)
+            .unwrap_or(self.heartbeat_interval() * DEFAULT_MEMBER_TTL_INTERVALS as u32)
+            .max(self.heartbeat_interval() * 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cluster_config_intervals() {
+        let mut conf = ClusterConfig {
+            node_id: "a".into(),
+            url: "https://a.internal:65432".into(),
+            store: ClusterStoreConfig::Etcd(EtcdClusterStoreConfig {
+                endpoints: vec!["http://etcd.internal:2379".into()],
+                prefix: None,
+            }),
+            heartbeat_interval_secs: None,
+            member_ttl_secs: None,
+        };
+
+        assert_eq!(conf.heartbeat_interval(), Duration::from_secs(5));
+        assert_eq!(conf.member_ttl(), Duration::from_secs(15));
+
+        // The ttl must allow for at least one missed heartbeat
+        conf.heartbeat_interval_secs = Some(10);
+        conf.member_ttl_secs = Some(5);
+
+        assert_eq!(conf.member_ttl(), Duration::from_secs(20));
+    }
+}
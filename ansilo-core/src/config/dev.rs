@@ -0,0 +1,38 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Configuration options which only apply when running in development mode
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DevConfig {
+    /// Seed data loaded when the instance starts
+    #[serde(default)]
+    pub fixtures: Vec<FixtureConfig>,
+    /// Additional files or directories which are watched for changes, such as
+    /// included config files. This can contain wildcards for globbing.
+    #[serde(default)]
+    pub watch: Vec<PathBuf>,
+}
+
+/// A set of seed data loaded into postgres or a memory data source.
+///
+/// Either `sql` or `csv` must be defined. A csv file is loaded into the
+/// postgres `table` or otherwise into the `entity` of the memory `data_source`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FixtureConfig {
+    /// The sql scripts to run. This can contain wildcards for globbing.
+    #[serde(default)]
+    pub sql: Option<PathBuf>,
+    /// The csv file to load, of which the first row contains the column names
+    #[serde(default)]
+    pub csv: Option<PathBuf>,
+    /// The postgres table to load the csv file into, eg "public.people"
+    #[serde(default)]
+    pub table: Option<String>,
+    /// The id of the memory data source to load the csv file into
+    #[serde(default)]
+    pub data_source: Option<String>,
+    /// The id of the entity to load the csv file into
+    #[serde(default)]
+    pub entity: Option<String>,
+}
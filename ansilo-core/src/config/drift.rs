@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// The default interval between checks of the entities for drift
+const DEFAULT_INTERVAL_SECS: u64 = 3600;
+
+/// Configuration options for detecting drift between the configured
+/// entities and the current schema of their data sources.
+///
+/// The entities are periodically re-validated against their data source
+/// and any added, removed or retyped attributes are reported.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Default)]
+pub struct DriftConfig {
+    /// The number of seconds between checks, defaults to an hour
+    pub interval_secs: Option<u64>,
+}
+
+impl DriftConfig {
+    /// Gets the interval between checks of the entities
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs.unwrap_or(DEFAULT_INTERVAL_SECS).max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drift_config_interval() {
+        assert_eq!(DriftConfig::default().interval(), Duration::from_secs(3600));
+        assert_eq!(
+            DriftConfig {
+                interval_secs: Some(60)
+            }
+            .interval(),
+            Duration::from_secs(60)
+        );
+    }
+}
@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Configuration options for encrypting the locally persisted data of the node at rest
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    /// The URL from which the base64-encoded 256-bit encryption key is retrieved,
+    /// eg "vault://..." or "env://ANSILO_ENCRYPTION_KEY".
+    /// See `ansilo_util_url::get` for the supported protocols.
+    pub key: String,
+    /// The directory storing the encrypted files of the data directory,
+    /// defaults to the data directory with the `.encrypted` suffix
+    #[serde(default)]
+    pub volume_dir: Option<PathBuf>,
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use anyhow::{Context, Result};
 use bincode::{Decode, Encode};
@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::data::DataType;
 
+use super::UserMatchConfig;
+
 /// An entity is a typed and documented dataset to be exposed by this ansilo node
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Encode, Decode)]
 pub struct EntityConfig {
@@ -18,6 +20,9 @@ pub struct EntityConfig {
     /// The tags attached to the entity for categorisation
     #[serde(default)]
     pub tags: Vec<TagValueConfig>,
+    /// The data classifications of the entity, eg `pii` or `confidential`
+    #[serde(default)]
+    pub classifications: Vec<String>,
     /// The list of attributes exposed by this entity
     pub attributes: Vec<EntityAttributeConfig>,
     /// The list of constraints (fk or unique) on this entity
@@ -25,6 +30,19 @@ pub struct EntityConfig {
     pub constraints: Vec<EntityConstraintConfig>,
     /// The source-specific config for reading or writing to this entity
     pub source: EntitySourceConfig,
+    /// If set, the entity is served from a local snapshot of the remote data
+    #[serde(default)]
+    pub materialize: Option<EntityMaterializeConfig>,
+    /// If set, the results of scans of the entity are cached locally
+    #[serde(default)]
+    pub cache: Option<EntityCacheConfig>,
+    /// How drift between the entity and the schema of its data source is handled
+    #[serde(default)]
+    pub on_drift: EntityDriftAction,
+    /// Filters appended to every query against the entity to restrict the
+    /// visible rows based on the authenticated user, eg `region = ${auth.claims.region}`
+    #[serde(default)]
+    pub row_filters: Vec<String>,
 }
 
 impl EntityConfig {
@@ -42,9 +60,14 @@ impl EntityConfig {
             name,
             description,
             tags,
+            classifications: vec![],
             attributes,
             constraints,
             source,
+            materialize: None,
+            cache: None,
+            on_drift: EntityDriftAction::default(),
+            row_filters: vec![],
         }
     }
 
@@ -60,9 +83,14 @@ impl EntityConfig {
             name: None,
             description: None,
             tags: vec![],
+            classifications: vec![],
             attributes: attrs,
             constraints: vec![],
             source,
+            materialize: None,
+            cache: None,
+            on_drift: EntityDriftAction::default(),
+            row_filters: vec![],
         }
     }
 
@@ -99,6 +127,12 @@ pub struct EntityAttributeConfig {
     /// Whether the attribute is nullable
     #[serde(default)]
     pub nullable: bool,
+    /// If set, the values of the attribute are masked for unauthorised users
+    #[serde(default)]
+    pub mask: Option<AttributeMaskConfig>,
+    /// The data classifications of the attribute, eg `pii` or `confidential`
+    #[serde(default)]
+    pub classifications: Vec<String>,
 }
 
 impl EntityAttributeConfig {
@@ -115,6 +149,8 @@ impl EntityAttributeConfig {
             r#type,
             primary_key,
             nullable,
+            mask: None,
+            classifications: vec![],
         }
     }
 
@@ -125,6 +161,8 @@ impl EntityAttributeConfig {
             r#type,
             primary_key: false,
             nullable: false,
+            mask: None,
+            classifications: vec![],
         }
     }
 
@@ -135,10 +173,43 @@ impl EntityAttributeConfig {
             r#type,
             primary_key: false,
             nullable: true,
+            mask: None,
+            classifications: vec![],
         }
     }
 }
 
+/// Defines how the values of an attribute are masked
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Encode, Decode)]
+pub struct AttributeMaskConfig {
+    /// How the values are masked
+    #[serde(flatten)]
+    pub r#type: AttributeMaskType,
+    /// The users which can read the unmasked values
+    #[serde(default)]
+    pub unmasked_for: UserMatchConfig,
+}
+
+/// The masking applied to the values of an attribute
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Encode, Decode)]
+#[serde(tag = "type")]
+pub enum AttributeMaskType {
+    /// Replaces the values with null
+    #[serde(rename = "null")]
+    Null,
+    /// Replaces the values with their hex-encoded SHA-256 hash,
+    /// so masked values can still be compared and joined
+    #[serde(rename = "hash")]
+    Hash,
+    /// Replaces all but the last characters of the values with '*'
+    #[serde(rename = "partial")]
+    Partial {
+        /// The number of trailing characters which are left visible
+        #[serde(default)]
+        visible: u32,
+    },
+}
+
 /// A constraint on the entity
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Encode, Decode)]
 #[serde(tag = "type")]
@@ -165,6 +236,75 @@ pub struct UniqueConstraintConfig {
     pub attributes: Vec<String>,
 }
 
+/// Defines how an entity is materialized into a local postgres table
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Encode, Decode)]
+pub struct EntityMaterializeConfig {
+    /// The cron expression defining when the snapshot is refreshed,
+    /// not required when the changes are captured from the data source
+    #[serde(default)]
+    pub refresh: String,
+    /// How the snapshot is refreshed
+    #[serde(default)]
+    pub mode: MaterializeMode,
+    /// The attribute used to find new or updated rows in incremental mode,
+    /// typically an auto-incrementing id or a last modified timestamp
+    pub watermark: Option<String>,
+}
+
+/// Defines how the results of scans of an entity are cached
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Encode, Decode)]
+pub struct EntityCacheConfig {
+    /// The number of seconds the results of a scan are served from the cache
+    pub ttl: u64,
+}
+
+impl EntityCacheConfig {
+    /// Gets the duration the results of a scan are served from the cache
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl)
+    }
+}
+
+/// How drift between an entity and the schema of its data source is handled
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, Encode, Decode)]
+pub enum EntityDriftAction {
+    /// The drift is reported but the entity is left unchanged
+    #[serde(rename = "report")]
+    Report,
+    /// Compatible changes, such as added or retyped attributes, are applied
+    /// to the entity and its foreign tables automatically
+    #[serde(rename = "regenerate")]
+    Regenerate,
+}
+
+impl Default for EntityDriftAction {
+    fn default() -> Self {
+        Self::Report
+    }
+}
+
+/// The refresh strategy of a materialized entity
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, Encode, Decode)]
+pub enum MaterializeMode {
+    /// Replaces the snapshot with all rows from the remote entity
+    #[serde(rename = "full")]
+    Full,
+    /// Upserts rows where the watermark attribute exceeds
+    /// the highest value in the snapshot
+    #[serde(rename = "incremental")]
+    Incremental,
+    /// Applies the changes captured from the data source's
+    /// change stream as they occur
+    #[serde(rename = "cdc")]
+    Cdc,
+}
+
+impl Default for MaterializeMode {
+    fn default() -> Self {
+        Self::Full
+    }
+}
+
 /// Defines the config used to read and write the entity
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct EntitySourceConfig {
@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// The default interval between checks of the other node's health
+const DEFAULT_CHECK_INTERVAL_SECS: u64 = 5;
+
+/// The default number of consecutive failed checks before failing over
+const DEFAULT_FAILOVER_THRESHOLD: u32 = 3;
+
+/// Configuration options for running the node as one of a high-availability pair.
+///
+/// Both nodes share the same configuration with `node_id` identifying the
+/// local node, typically supplied from an environment variable.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct HaConfig {
+    /// The id of this node, must match one of the `nodes`
+    pub node_id: String,
+    /// The nodes of the pair, the first node is preferred as the primary
+    pub nodes: Vec<HaNodeConfig>,
+    /// The password of the user the standby replicates from the primary as
+    pub replication_password: String,
+    /// The number of seconds between checks of the other node's health
+    pub check_interval_secs: Option<u64>,
+    /// The number of consecutive failed checks of the primary before the standby takes over
+    pub failover_threshold: Option<u32>,
+}
+
+/// A node of a high-availability pair
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct HaNodeConfig {
+    /// The id of the node
+    pub id: String,
+    /// The hostname or ip address on which the node accepts replication connections
+    pub host: String,
+    /// The url of the node's http api, eg "https://node-a.internal:65432"
+    pub url: String,
+}
+
+/// The role of a node in a high-availability pair
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HaRole {
+    /// Serves reads and writes, replicating to the standby
+    Primary,
+    /// Serves reads while replicating from the primary, ready to take over
+    Standby,
+}
+
+impl HaConfig {
+    /// Gets the config of this node
+    pub fn this_node(&self) -> Option<&HaNodeConfig> {
+        self.nodes.iter().find(|n| n.id == self.node_id)
+    }
+
+    /// Gets the config of the other node of the pair
+    pub fn peer(&self) -> Option<&HaNodeConfig> {
+        self.nodes.iter().find(|n| n.id != self.node_id)
+    }
+
+    /// Whether this node is preferred as the primary when both nodes start at once
+    pub fn is_preferred_primary(&self) -> bool {
+        self.nodes.first().map(|n| n.id == self.node_id) == Some(true)
+    }
+
+    /// Gets the interval between checks of the other node's health
+    pub fn check_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.check_interval_secs
+                .unwrap_or(DEFAULT_CHECK_INTERVAL_SECS),
+        )
+    }
+
+    /// Gets the number of consecutive failed checks before failing over
+    pub fn failover_threshold(&self) -> u32 {
+        self.failover_threshold
+            .unwrap_or(DEFAULT_FAILOVER_THRESHOLD)
+            .max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_node(id: &str) -> HaNodeConfig {
+        HaNodeConfig {
+            id: id.into(),
+            host: format!("{id}.internal"),
+            url: format!("https://{id}.internal:65432"),
+        }
+    }
+
+    #[test]
+    fn test_ha_config_nodes() {
+        let conf = HaConfig {
+            node_id: "b".into(),
+            nodes: vec![mock_node("a"), mock_node("b")],
+            replication_password: "pass".into(),
+            check_interval_secs: None,
+            failover_threshold: Some(0),
+        };
+
+        assert_eq!(conf.this_node(), Some(&mock_node("b")));
+        assert_eq!(conf.peer(), Some(&mock_node("a")));
+        assert!(!conf.is_preferred_primary());
+        assert_eq!(conf.check_interval(), Duration::from_secs(5));
+        assert_eq!(conf.failover_threshold(), 1);
+    }
+}
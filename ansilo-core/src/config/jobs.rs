@@ -1,4 +1,9 @@
-use serde::{Serialize, Deserialize};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+/// The default number of seconds a script may run before it is killed
+const DEFAULT_SCRIPT_TIMEOUT_SECS: u64 = 3600;
 
 /// A job is a pre-defined query which can be triggered repeatedly
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -13,12 +18,117 @@ pub struct JobConfig {
     /// If not provided it will be executed as ansilo_admin
     pub service_user: Option<String>,
     /// The query/queries that are executed by the job
+    #[serde(default)]
     pub sql: String,
+    /// The name of the catalog the sql is executed in, defaults to the main database
+    #[serde(default)]
+    pub catalog: Option<String>,
+    /// If set, the job re-imports the entities of a peer rather than executing sql
+    #[serde(default)]
+    pub peer_import: Option<PeerImportJobConfig>,
+    /// Further steps which are executed in order after the sql
+    #[serde(default)]
+    pub steps: Vec<JobStepConfig>,
     /// The trigger conditions for the job
     #[serde(default)]
     pub triggers: Vec<JobTriggerConfig>,
 }
 
+impl JobConfig {
+    /// Whether the job is a built-in job, which is run by the node rather than executing sql
+    pub fn is_builtin(&self) -> bool {
+        self.peer_import.is_some()
+    }
+}
+
+/// A built-in job which re-runs the discovery of a peer's entities and
+/// regenerates the foreign tables of those which were added, removed or changed
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PeerImportJobConfig {
+    /// The ID of the peer data source
+    pub data_source: String,
+    /// The schema the entities of the peer are imported into
+    pub schema: String,
+    /// Prefix applied to the names of the imported tables
+    #[serde(default)]
+    pub table_prefix: Option<String>,
+}
+
+/// A step of a job
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JobStepConfig {
+    Sql(SqlJobStepConfig),
+    Script(ScriptJobStepConfig),
+}
+
+/// A step which executes sql in the same way as the sql of the job
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct SqlJobStepConfig {
+    /// The query/queries that are executed by the step
+    pub sql: String,
+}
+
+/// A step which runs an external command
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ScriptJobStepConfig {
+    pub script: ScriptConfig,
+}
+
+/// An external command run by a job.
+///
+/// The command does not inherit the environment of the node, it only receives
+/// the configured variables along with the details required to connect to the node.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ScriptConfig {
+    /// The path of the executable
+    pub command: String,
+    /// The arguments passed to the executable
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// The working directory of the command
+    /// If not provided a temporary directory is created and removed once the command exits
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
+    /// Additional environment variables set for the command
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// The number of seconds before the command is killed, defaults to an hour
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// The operating system user the command is run as, which requires the node to run as root.
+    /// If not provided the command is run as the same user as the node
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Limits on the resources consumed by the command
+    #[serde(default)]
+    pub limits: ScriptLimitsConfig,
+}
+
+/// Limits on the resources consumed by a script, applied to its processes as rlimits
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Default)]
+pub struct ScriptLimitsConfig {
+    /// The maximum virtual memory of each process, in megabytes
+    pub max_memory_mb: Option<u64>,
+    /// The maximum cpu time of each process, in seconds
+    pub max_cpu_secs: Option<u64>,
+    /// The maximum size of the files written by each process, in megabytes
+    pub max_file_size_mb: Option<u64>,
+    /// The maximum number of processes of the user the command is run as
+    pub max_processes: Option<u64>,
+}
+
+impl ScriptConfig {
+    /// Gets the time the command may run before it is killed
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.timeout_secs
+                .unwrap_or(DEFAULT_SCRIPT_TIMEOUT_SECS)
+                .max(1),
+        )
+    }
+}
+
 /// A trigger condition for a job
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -30,6 +140,66 @@ pub enum JobTriggerConfig {
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct CronTriggerConfig {
     /// The cron expression
-    pub cron: String
+    pub cron: String,
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_job_steps() {
+        let steps: Vec<JobStepConfig> = serde_yaml::from_str(
+            "
+- sql: SELECT 1
+- script:
+    command: /usr/bin/curl
+    args: [-X, POST, https://example.com]
+    env:
+      KEY: value
+    timeout_secs: 60
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            steps,
+            vec![
+                JobStepConfig::Sql(SqlJobStepConfig {
+                    sql: "SELECT 1".into()
+                }),
+                JobStepConfig::Script(ScriptJobStepConfig {
+                    script: ScriptConfig {
+                        command: "/usr/bin/curl".into(),
+                        args: vec!["-X".into(), "POST".into(), "https://example.com".into()],
+                        working_dir: None,
+                        env: [("KEY".to_string(), "value".to_string())]
+                            .into_iter()
+                            .collect(),
+                        timeout_secs: Some(60),
+                        user: None,
+                        limits: ScriptLimitsConfig::default(),
+                    }
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_script_timeout() {
+        let mut conf = ScriptConfig {
+            command: "true".into(),
+            args: vec![],
+            working_dir: None,
+            env: HashMap::new(),
+            timeout_secs: None,
+            user: None,
+            limits: ScriptLimitsConfig::default(),
+        };
+
+        assert_eq!(conf.timeout(), Duration::from_secs(3600));
+
+        conf.timeout_secs = Some(0);
+        assert_eq!(conf.timeout(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,15 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Configuration options for logging
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// The log filter, using the same syntax as the RUST_LOG env var
+    /// eg "info" or "warn,ansilo_pg=debug".
+    /// If not specified, the filter is read from RUST_LOG and otherwise defaults to "info".
+    pub level: Option<String>,
+    /// The file which logs are written to when running as a daemon.
+    /// If not specified, defaults to /var/log/ansilo/ansilo.log.
+    pub file: Option<PathBuf>,
+}
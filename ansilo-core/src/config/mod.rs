@@ -15,6 +15,8 @@ mod entities;
 pub use entities::*;
 mod jobs;
 pub use jobs::*;
+mod syncs;
+pub use syncs::*;
 mod util;
 pub use util::*;
 mod postgres;
@@ -23,6 +25,22 @@ mod secrets;
 pub use secrets::*;
 mod resources;
 pub use resources::*;
+mod logging;
+pub use logging::*;
+mod encryption;
+pub use encryption::*;
+mod ha;
+pub use ha::*;
+mod cluster;
+pub use cluster::*;
+mod dev;
+pub use dev::*;
+mod publish;
+pub use publish::*;
+mod catalogs;
+pub use catalogs::*;
+mod drift;
+pub use drift::*;
 
 // TODO: consider ansilo versioning
 
@@ -51,6 +69,33 @@ pub struct NodeConfig {
     /// List of jobs run by the node
     #[serde(default)]
     pub jobs: Vec<JobConfig>,
+    /// List of syncs which copy rows between entities
+    #[serde(default)]
+    pub syncs: Vec<SyncConfig>,
+    /// List of catalogs, each a separate database serving a subset of the data sources
+    #[serde(default)]
+    pub catalogs: Vec<CatalogConfig>,
     /// Postgres configuration options
     pub postgres: Option<PostgresConfig>,
+    /// Logging options
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// If set, the data directory and logs are encrypted at rest
+    #[serde(default)]
+    pub encryption: Option<EncryptionConfig>,
+    /// If set, the node runs as one of a high-availability pair
+    #[serde(default)]
+    pub ha: Option<HaConfig>,
+    /// If set, the node runs as a member of a cluster
+    #[serde(default)]
+    pub cluster: Option<ClusterConfig>,
+    /// If set, the changes to the configured tables are published to kafka
+    #[serde(default)]
+    pub publish: Option<PublishConfig>,
+    /// If set, the entities are periodically checked for drift from their data sources
+    #[serde(default)]
+    pub drift: Option<DriftConfig>,
+    /// Development mode options
+    #[serde(default)]
+    pub dev: DevConfig,
 }
@@ -22,6 +22,34 @@ pub struct TlsConfig {
     pub certificate: PathBuf,
     /// The path of the pem-encoded private key file
     pub private_key: PathBuf,
+    /// If set, clients such as peer nodes authenticate using certificates
+    #[serde(default)]
+    pub client_auth: Option<TlsClientAuthConfig>,
+}
+
+/// Options for verifying the certificates presented by clients, so connections
+/// from peer nodes are mutually authenticated
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct TlsClientAuthConfig {
+    /// The path of the pem-encoded CA certificates which sign the client certificates
+    pub ca_bundle: PathBuf,
+    /// Whether clients must present a certificate
+    #[serde(default)]
+    pub mode: TlsClientAuthMode,
+    /// The names of the peer nodes permitted to connect, which must match
+    /// a DNS subject alternative name of the client certificate
+    pub allowed_names: Vec<String>,
+}
+
+/// Whether clients must present a certificate
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsClientAuthMode {
+    /// Connections without a valid client certificate are rejected
+    #[default]
+    Required,
+    /// Clients may connect without a certificate, but any certificate presented must be valid
+    Optional,
 }
 
 fn port_from_num_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
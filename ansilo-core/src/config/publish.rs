@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// The default interval between checks for unpublished changes
+const DEFAULT_POLL_INTERVAL_MS: u64 = 1000;
+
+/// The default number of changes sent to kafka in a single request
+const DEFAULT_BATCH_SIZE: u32 = 500;
+
+/// Configuration options for publishing the changes made to local tables
+/// to downstream event consumers.
+///
+/// The changes are published using the Debezium event format so existing
+/// consumers of Debezium topics can consume them unchanged.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PublishConfig {
+    /// The kafka cluster the changes are published to
+    pub kafka: KafkaPublishConfig,
+    /// The tables of which the changes are published
+    #[serde(default)]
+    pub tables: Vec<PublishTableConfig>,
+    /// The number of milliseconds between checks for unpublished changes
+    pub poll_interval_ms: Option<u64>,
+    /// The maximum number of changes sent to kafka in a single request
+    pub batch_size: Option<u32>,
+}
+
+/// The kafka cluster the changes are published to
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct KafkaPublishConfig {
+    /// The addresses of the kafka brokers, eg "kafka-1.internal:9092"
+    pub brokers: Vec<String>,
+    /// The client id reported to the brokers, defaults to "ansilo"
+    pub client_id: Option<String>,
+}
+
+/// A local table of which the changes are published
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PublishTableConfig {
+    /// The table, optionally qualified by its schema, eg "public.orders"
+    pub table: String,
+    /// The topic the changes are published to, defaults to "{name}.{schema}.{table}"
+    /// using the name of the node
+    pub topic: Option<String>,
+    /// The columns of the message key, defaults to the primary key of the table
+    #[serde(default)]
+    pub key: Vec<String>,
+}
+
+impl PublishConfig {
+    /// Gets the interval between checks for unpublished changes
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms.unwrap_or(DEFAULT_POLL_INTERVAL_MS))
+    }
+
+    /// Gets the maximum number of changes sent to kafka in a single request
+    pub fn batch_size(&self) -> u32 {
+        self.batch_size.unwrap_or(DEFAULT_BATCH_SIZE)
+    }
+}
+
+impl PublishTableConfig {
+    /// Gets the schema and name of the table, defaulting to the "public" schema
+    pub fn schema_and_name(&self) -> (&str, &str) {
+        self.table
+            .split_once('.')
+            .unwrap_or(("public", &self.table))
+    }
+
+    /// Gets the topic the changes are published to
+    pub fn topic(&self, node_name: &str) -> String {
+        match self.topic.as_ref() {
+            Some(topic) => topic.clone(),
+            None => {
+                let (schema, table) = self.schema_and_name();
+                format!("{node_name}.{schema}.{table}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_table_schema_and_name() {
+        let table = |t: &str| PublishTableConfig {
+            table: t.into(),
+            topic: None,
+            key: vec![],
+        };
+
+        assert_eq!(table("orders").schema_and_name(), ("public", "orders"));
+        assert_eq!(table("sales.orders").schema_and_name(), ("sales", "orders"));
+    }
+
+    #[test]
+    fn test_publish_table_topic() {
+        let mut table = PublishTableConfig {
+            table: "sales.orders".into(),
+            topic: None,
+            key: vec![],
+        };
+
+        assert_eq!(table.topic("node"), "node.sales.orders");
+
+        table.topic = Some("orders".into());
+        assert_eq!(table.topic("node"), "orders");
+    }
+}
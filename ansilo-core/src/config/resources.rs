@@ -18,6 +18,9 @@ pub struct ResourceConfig {
     pub memory: Option<u32>,
     /// Maximum connections to postgres
     pub connections: Option<u32>,
+    /// Minimum connections to postgres, per app user, which are
+    /// established at startup and kept open while idle
+    pub min_connections: Option<u32>,
 }
 
 impl ResourceConfig {
@@ -31,6 +34,11 @@ impl ResourceConfig {
         self.connections.unwrap_or(DEFAULT_CONNECTIONS)
     }
 
+    /// Gets the minimum number of connections to postgres, per app user
+    pub fn min_connections(&self) -> u32 {
+        self.min_connections.unwrap_or(0).min(self.connections())
+    }
+
     /// Gets the memory allocated to the jvm in megabytes
     pub fn jvm_memory_mb(&self) -> u32 {
         self.total_memory() / 3
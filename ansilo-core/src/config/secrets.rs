@@ -1,3 +1,5 @@
+use std::fmt::Debug;
+
 use serde::{Deserialize, Serialize};
 
 /// Configuration for connecting to HashiCorp Vault
@@ -34,28 +36,66 @@ pub enum VaultAuthMethod {
     UsernamePassword(VaultUserPasswordAuth),
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct VaultTokenAuth {
     pub token: String,
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct VaultAppRoleAuth {
     pub mount: String,
     pub role_id: String,
     pub secret_id: String,
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct VaultKubernetesAuth {
     pub mount: String,
     pub role: String,
     pub jwt: String,
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct VaultUserPasswordAuth {
     pub mount: String,
     pub username: String,
     pub password: String,
 }
+
+impl Debug for VaultTokenAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VaultTokenAuth")
+            .field("token", &"***REDACTED***")
+            .finish()
+    }
+}
+
+impl Debug for VaultAppRoleAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VaultAppRoleAuth")
+            .field("mount", &self.mount)
+            .field("role_id", &self.role_id)
+            .field("secret_id", &"***REDACTED***")
+            .finish()
+    }
+}
+
+impl Debug for VaultKubernetesAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VaultKubernetesAuth")
+            .field("mount", &self.mount)
+            .field("role", &self.role)
+            .field("jwt", &"***REDACTED***")
+            .finish()
+    }
+}
+
+impl Debug for VaultUserPasswordAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VaultUserPasswordAuth")
+            .field("mount", &self.mount)
+            .field("username", &self.username)
+            .field("password", &"***REDACTED***")
+            .finish()
+    }
+}
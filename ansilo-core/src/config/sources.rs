@@ -1,5 +1,24 @@
+use std::{str::FromStr, time::Duration};
+
+use bincode::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 
+use crate::err::{bail, Error, Result};
+
+/// The default time in seconds for which remote metadata is cached
+const DEFAULT_METADATA_CACHE_TTL: u64 = 60;
+/// The default number of prepared queries cached per connection
+const DEFAULT_PREPARED_QUERY_CACHE_SIZE: u32 = 100;
+/// The default time in seconds for which an idle connection
+/// with cached prepared queries is kept for reuse
+const DEFAULT_PREPARED_QUERY_IDLE_TIMEOUT: u64 = 60;
+/// The default time in seconds between collecting statistics of each entity
+const DEFAULT_STATISTICS_INTERVAL: u64 = 3600;
+/// The default number of rows sampled from each entity when collecting statistics
+const DEFAULT_STATISTICS_SAMPLE_ROWS: u32 = 10_000;
+/// The default time in seconds a query waits to be admitted before failing
+const DEFAULT_ADMISSION_QUEUE_TIMEOUT: u64 = 30;
+
 /// Defines a data source
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct DataSourceConfig {
@@ -13,4 +32,215 @@ pub struct DataSourceConfig {
     pub r#type: String,
     /// The type specific connection options for the data source
     pub options: serde_yaml::Value,
+    /// The number of seconds for which remote metadata, such as the structure
+    /// and size estimates of entities, is cached. Set to 0 to disable caching.
+    pub metadata_cache_ttl: Option<u64>,
+    /// Bounds on the number of rows transferred from the data source in each batch
+    pub fetch_size: Option<FetchSizeConfig>,
+    /// The encoding used to transfer query results from the data source
+    #[serde(default)]
+    pub transfer_encoding: TransferEncoding,
+    /// The compression applied to large query results transferred from the data source
+    #[serde(default)]
+    pub transfer_compression: TransferCompression,
+    /// Limits on the prepared queries which are cached for reuse across sessions
+    pub prepared_query_cache: Option<PreparedQueryCacheConfig>,
+    /// Options for collecting statistics of the entities in the data source.
+    /// Statistics are only collected if this is set.
+    pub statistics: Option<StatisticsConfig>,
+    /// Limits on the sessions concurrently scanning the data source
+    pub admission: Option<AdmissionConfig>,
+}
+
+/// Bounds on the number of rows transferred in each batch when reading query results.
+/// Within these bounds the batch size adapts to the observed row width and latency.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct FetchSizeConfig {
+    /// The minimum number of rows per batch
+    pub min_rows: Option<u32>,
+    /// The maximum number of rows per batch
+    pub max_rows: Option<u32>,
+}
+
+/// Limits on the prepared queries cached for each connection to the data source.
+/// Connections with cached queries are kept open after the session ends so they
+/// can be reused by subsequent sessions.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct PreparedQueryCacheConfig {
+    /// The maximum number of prepared queries cached per connection.
+    /// Set to 0 to disable caching.
+    pub max_queries: Option<u32>,
+    /// The number of seconds for which an idle connection is kept for reuse
+    pub idle_timeout: Option<u64>,
+}
+
+/// Options for the background job which samples the entities of the data source
+/// to estimate their row counts, column cardinalities and data volumes.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct StatisticsConfig {
+    /// The number of seconds between collecting the statistics of each entity
+    pub interval: Option<u64>,
+    /// The maximum number of rows sampled from each entity
+    pub sample_rows: Option<u32>,
+}
+
+/// Limits on the number of sessions which concurrently execute scans against
+/// the data source. Sessions which exceed the limit are queued until a running
+/// session completes its scans.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct AdmissionConfig {
+    /// The maximum number of sessions concurrently scanning the data source
+    pub max_concurrent_scans: Option<u32>,
+    /// The number of seconds a queued session waits before the query fails
+    pub queue_timeout: Option<u64>,
+}
+
+impl DataSourceConfig {
+    /// Gets the time for which remote metadata is cached
+    pub fn metadata_cache_ttl(&self) -> Duration {
+        Duration::from_secs(
+            self.metadata_cache_ttl
+                .unwrap_or(DEFAULT_METADATA_CACHE_TTL),
+        )
+    }
+
+    /// Gets the maximum number of prepared queries cached per connection
+    pub fn prepared_query_cache_size(&self) -> usize {
+        self.prepared_query_cache
+            .as_ref()
+            .and_then(|c| c.max_queries)
+            .unwrap_or(DEFAULT_PREPARED_QUERY_CACHE_SIZE) as _
+    }
+
+    /// Gets the time for which an idle connection with cached prepared queries is kept
+    pub fn prepared_query_idle_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.prepared_query_cache
+                .as_ref()
+                .and_then(|c| c.idle_timeout)
+                .unwrap_or(DEFAULT_PREPARED_QUERY_IDLE_TIMEOUT),
+        )
+    }
+
+    /// Gets the time between collecting statistics of each entity,
+    /// returns `None` if statistics are not collected
+    pub fn statistics_interval(&self) -> Option<Duration> {
+        self.statistics
+            .as_ref()
+            .map(|c| Duration::from_secs(c.interval.unwrap_or(DEFAULT_STATISTICS_INTERVAL)))
+    }
+
+    /// Gets the maximum number of sessions concurrently scanning the data source,
+    /// returns `None` if the scans are not limited
+    pub fn max_concurrent_scans(&self) -> Option<usize> {
+        self.admission
+            .as_ref()
+            .and_then(|c| c.max_concurrent_scans)
+            .map(|max| max.max(1) as _)
+    }
+
+    /// Gets the time a queued session waits to start scanning the data source
+    pub fn admission_queue_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.admission
+                .as_ref()
+                .and_then(|c| c.queue_timeout)
+                .unwrap_or(DEFAULT_ADMISSION_QUEUE_TIMEOUT),
+        )
+    }
+
+    /// Gets the maximum number of rows sampled from each entity when collecting statistics
+    pub fn statistics_sample_rows(&self) -> u32 {
+        self.statistics
+            .as_ref()
+            .and_then(|c| c.sample_rows)
+            .unwrap_or(DEFAULT_STATISTICS_SAMPLE_ROWS)
+            .max(1)
+    }
+}
+
+/// The encoding used to transfer query results from the data source to postgres
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum TransferEncoding {
+    /// Uses the columnar encoding for scans which are expected to return many rows
+    #[serde(rename = "auto")]
+    Auto,
+    /// Transfers results row-by-row
+    #[serde(rename = "row")]
+    Row,
+    /// Transfers results in columnar arrow batches
+    #[serde(rename = "columnar")]
+    Columnar,
+}
+
+impl Default for TransferEncoding {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+impl TransferEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferEncoding::Auto => "auto",
+            TransferEncoding::Row => "row",
+            TransferEncoding::Columnar => "columnar",
+        }
+    }
+}
+
+impl FromStr for TransferEncoding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "auto" => TransferEncoding::Auto,
+            "row" => TransferEncoding::Row,
+            "columnar" => TransferEncoding::Columnar,
+            _ => bail!("Unknown transfer encoding '{}'", s),
+        })
+    }
+}
+
+/// The compression applied to query results transferred from the data source to postgres
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, Encode, Decode)]
+pub enum TransferCompression {
+    /// Results are not compressed
+    #[serde(rename = "none")]
+    None,
+    /// Results are compressed using LZ4, which is fast with a moderate ratio
+    #[serde(rename = "lz4")]
+    Lz4,
+    /// Results are compressed using zstd, which achieves a higher ratio
+    #[serde(rename = "zstd")]
+    Zstd,
+}
+
+impl Default for TransferCompression {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl TransferCompression {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferCompression::None => "none",
+            TransferCompression::Lz4 => "lz4",
+            TransferCompression::Zstd => "zstd",
+        }
+    }
+}
+
+impl FromStr for TransferCompression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "none" => TransferCompression::None,
+            "lz4" => TransferCompression::Lz4,
+            "zstd" => TransferCompression::Zstd,
+            _ => bail!("Unknown transfer compression '{}'", s),
+        })
+    }
 }
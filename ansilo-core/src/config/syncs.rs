@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+/// A sync copies the rows of a source entity into a target entity on a schedule
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct SyncConfig {
+    /// The ID of the sync
+    pub id: String,
+    /// The ID of the entity the rows are read from
+    pub source: String,
+    /// The ID of the entity the rows are written to
+    pub target: String,
+    /// The cron expression defining when the sync runs
+    pub schedule: String,
+    /// The source attribute copied into each target attribute, defaults to
+    /// the attributes of the target which have a source attribute of the same id
+    #[serde(default)]
+    pub columns: Vec<SyncColumnConfig>,
+    /// The source attribute used to find the rows added or updated since the
+    /// previous run, if not set every row is copied on each run
+    pub watermark: Option<String>,
+    /// How rows which already exist in the target are handled
+    #[serde(default)]
+    pub conflict: SyncConflictStrategy,
+    /// The target attributes identifying existing rows, defaults to the primary key of the target
+    #[serde(default)]
+    pub key: Vec<String>,
+}
+
+/// Maps a source attribute to a target attribute
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct SyncColumnConfig {
+    /// The ID of the source attribute
+    pub source: String,
+    /// The ID of the target attribute
+    pub target: String,
+}
+
+/// How rows which already exist in the target are handled
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum SyncConflictStrategy {
+    /// Inserts every copied row
+    #[serde(rename = "append")]
+    Append,
+    /// Updates existing rows with the same key and inserts the remaining rows
+    #[serde(rename = "upsert")]
+    Upsert,
+    /// Inserts the rows which do not exist, leaving existing rows unchanged
+    #[serde(rename = "skip")]
+    Skip,
+    /// Deletes all rows from the target before inserting the copied rows
+    #[serde(rename = "replace")]
+    Replace,
+}
+
+impl Default for SyncConflictStrategy {
+    fn default() -> Self {
+        Self::Append
+    }
+}
+
+impl SyncConflictStrategy {
+    /// Whether existing rows are identified by the key of the sync
+    pub fn requires_key(&self) -> bool {
+        matches!(self, Self::Upsert | Self::Skip)
+    }
+}
@@ -20,6 +20,8 @@ pub struct CatalogEntity {
     pub name: Option<String>,
     pub description: Option<String>,
     pub tags: Vec<TagValueConfig>,
+    #[serde(default)]
+    pub classifications: Vec<String>,
     pub attributes: Vec<CatalogEntityAttribue>,
     pub constraints: Vec<EntityConstraintConfig>,
     pub source: CatalogEntitySource,
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+/// Model for exposing the membership of the cluster
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterStatus {
+    /// The id of this node
+    pub node_id: String,
+    /// The id of the current leader, if one has been elected
+    pub leader: Option<String>,
+    /// The live members of the cluster
+    pub members: Vec<ClusterMember>,
+}
+
+/// A live member of the cluster
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterMember {
+    pub node_id: String,
+    /// The url of the member's http api
+    pub url: String,
+    /// When the last heartbeat of the member was received, in unix timestamp millis
+    pub last_seen: u64,
+}
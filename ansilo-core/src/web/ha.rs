@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::HaRole;
+
+/// Model for exposing the role of this node in a high-availability pair
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HaStatus {
+    pub node_id: String,
+    pub role: HaRole,
+    /// When this node last became the primary, in unix timestamp millis
+    pub primary_since: Option<u64>,
+}
@@ -1,4 +1,7 @@
 pub mod catalog;
 pub mod auth;
 pub mod query;
-pub mod node;
\ No newline at end of file
+pub mod node;
+pub mod ha;
+pub mod cluster;
+pub mod query_log;
\ No newline at end of file
@@ -5,3 +5,54 @@ pub struct NodeInfo {
     pub name: String,
     pub version: String,
 }
+
+/// The connections of the pools to postgres held by the node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodePools {
+    pub pools: Vec<PoolStatus>,
+}
+
+/// The utilisation of a connection pool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct PoolStatus {
+    /// The name of the pool
+    pub name: String,
+    /// The number of open connections
+    pub size: usize,
+    /// The number of open connections which are idle
+    pub available: usize,
+    /// The number of requests waiting for a connection
+    pub waiting: usize,
+    /// The maximum number of connections
+    pub max_size: usize,
+}
+
+impl PoolStatus {
+    pub fn new(name: impl Into<String>, size: usize, available: isize, max_size: usize) -> Self {
+        // A negative number of available connections is the number of waiters
+        Self {
+            name: name.into(),
+            size,
+            available: available.max(0) as usize,
+            waiting: (-available).max(0) as usize,
+            max_size,
+        }
+    }
+
+    /// Combines the utilisation of the supplied pools
+    pub fn sum(name: impl Into<String>, pools: impl IntoIterator<Item = PoolStatus>) -> Self {
+        pools.into_iter().fold(
+            Self {
+                name: name.into(),
+                ..Default::default()
+            },
+            |acc, p| Self {
+                size: acc.size + p.size,
+                available: acc.available + p.available,
+                waiting: acc.waiting + p.waiting,
+                max_size: acc.max_size + p.max_size,
+                ..acc
+            },
+        )
+    }
+}
@@ -0,0 +1,26 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// A query sent to a data source, as recorded in the remote query log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteQuery {
+    /// Sequential id of the recorded query
+    pub id: u64,
+    /// The id of the data source the query was sent to
+    pub data_source: String,
+    /// The pid of the postgres backend of the session which issued the query
+    pub session_id: Option<u32>,
+    /// The user authenticated by the session
+    pub username: Option<String>,
+    /// The query, as formatted by the connector
+    pub query: String,
+    /// The query parameters, with their values redacted
+    pub params: Vec<String>,
+    /// Additional details reported by the connector, such as the affected row count
+    pub other: HashMap<String, String>,
+    /// When the query was sent, in unix timestamp millis
+    pub started_at: u64,
+    /// How long the query took to execute, in milliseconds
+    pub duration_ms: Option<f64>,
+}
@@ -47,6 +47,7 @@ pub fn init_mongodb(containers: &ContainerInstances, path: PathBuf) -> MongodbCo
             containers.get("mongo").unwrap().ip
         ),
         disable_transactions: false,
+        tls: None,
     };
 
     let connection = MongodbConnector::connect(config).unwrap();
@@ -30,6 +30,7 @@ pub fn run_instance_args(
             .map(|(k, v)| (k.into(), v.into()))
             .collect(),
         force_build: true,
+        plugins_dir: None,
     });
 
     let client = connect(port);
@@ -43,6 +44,7 @@ pub fn run_instance_without_connect(config_path: PathBuf) -> (Ansilo, u16) {
         config: Some(config_path),
         config_args: vec![],
         force_build: true,
+        plugins_dir: None,
     })
 }
 
@@ -53,7 +55,7 @@ pub fn run_instance_without_connect_args(mut args: Args) -> (Ansilo, u16) {
         format!("/tmp/ansilo-e2e/{}", Uuid::new_v4()),
     ));
     let instance =
-        Ansilo::start(Command::Run(args), Some(RemoteQueryLog::store_in_memory())).unwrap();
+        Ansilo::start(Command::Run(args.into()), Some(RemoteQueryLog::store_in_memory())).unwrap();
 
     let port = loop {
         let addrs = instance.subsystems().unwrap().proxy().addrs().unwrap();
@@ -2,4 +2,5 @@ pub mod t001_password;
 pub mod t002_jwt;
 pub mod t003_jwt_rls;
 pub mod t004_jwt_callbacks;
-pub mod t005_custom;
\ No newline at end of file
+pub mod t005_custom;
+pub mod t006_query_rules;
\ No newline at end of file
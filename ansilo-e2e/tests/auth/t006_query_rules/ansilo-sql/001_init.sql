@@ -0,0 +1,5 @@
+IMPORT FOREIGN SCHEMA "%"
+FROM SERVER memory
+INTO public;
+
+GRANT SELECT ON people TO analyst;
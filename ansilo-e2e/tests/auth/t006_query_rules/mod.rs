@@ -0,0 +1,48 @@
+use ansilo_e2e::current_dir;
+use pretty_assertions::assert_eq;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test_require_where_denies_unconditioned_scan() {
+    ansilo_logging::init_for_tests();
+    let (_instance, port) =
+        ansilo_e2e::util::main::run_instance_without_connect(current_dir!().join("config.yml"));
+
+    let mut client =
+        ansilo_e2e::util::main::connect_opts("analyst", "password123", port, |_| ()).unwrap();
+
+    let res = client.query("SELECT * FROM people", &[]);
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "db error: ERROR: User 'analyst' is required to query entity 'people' with a WHERE clause by the query rules"
+    );
+
+    // A join condition alone does not restrict the scan
+    let res = client.query(
+        "SELECT * FROM people p1 INNER JOIN people p2 ON p1.id = p2.id",
+        &[],
+    );
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "db error: ERROR: User 'analyst' is required to query entity 'people' with a WHERE clause by the query rules"
+    );
+}
+
+#[test]
+#[serial]
+fn test_require_where_allows_conditioned_scan() {
+    ansilo_logging::init_for_tests();
+    let (_instance, port) =
+        ansilo_e2e::util::main::run_instance_without_connect(current_dir!().join("config.yml"));
+
+    let mut client =
+        ansilo_e2e::util::main::connect_opts("analyst", "password123", port, |_| ()).unwrap();
+
+    let rows = client
+        .query("SELECT name FROM people WHERE id = 1", &[])
+        .unwrap();
+
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].get::<_, String>(0), "John".to_string());
+}
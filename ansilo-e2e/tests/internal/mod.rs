@@ -2,3 +2,4 @@ pub mod t001_select_job;
 pub mod t002_select_job_triggers;
 pub mod t003_service_users;
 pub mod t004_select_job_whole_row_json;
+pub mod t005_classifications;
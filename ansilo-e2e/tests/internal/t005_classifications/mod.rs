@@ -0,0 +1,38 @@
+use ansilo_e2e::current_dir;
+use pretty_assertions::assert_eq;
+use serial_test::serial;
+
+#[test]
+#[serial]
+fn test() {
+    ansilo_logging::init_for_tests();
+    let (_instance, mut client) =
+        ansilo_e2e::util::main::run_instance(current_dir!().join("config.yml"));
+
+    let rows = client
+        .query(r#"SELECT * FROM ansilo_catalog.classifications"#, &[])
+        .unwrap();
+
+    let rows: Vec<_> = rows
+        .into_iter()
+        .map(|r| {
+            (
+                r.get::<_, Option<String>>("entity_id"),
+                r.get::<_, Option<String>>("attribute_id"),
+                r.get::<_, Option<String>>("classification"),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        rows,
+        vec![
+            (Some("people".into()), None, Some("confidential".into())),
+            (
+                Some("people".into()),
+                Some("name".into()),
+                Some("pii".into())
+            ),
+        ]
+    );
+}
@@ -32,6 +32,7 @@ fn test() {
             name: None,
             description: None,
             tags: vec![],
+            classifications: vec![],
             attributes: vec![
                 CatalogEntityAttribue {
                     attribute: EntityAttributeConfig {
@@ -40,6 +41,8 @@ fn test() {
                         r#type: DataType::Utf8String(StringOptions::default()),
                         primary_key: false,
                         nullable: true,
+                        mask: None,
+                        classifications: vec![],
                     },
                 },
                 CatalogEntityAttribue {
@@ -49,6 +52,8 @@ fn test() {
                         r#type: DataType::Int32,
                         primary_key: false,
                         nullable: true,
+                        mask: None,
+                        classifications: vec![],
                     },
                 },
             ],
@@ -38,6 +38,7 @@ fn test() {
             name: None,
             description: None,
             tags: vec![],
+            classifications: vec![],
             attributes: vec![
                 CatalogEntityAttribue {
                     attribute: EntityAttributeConfig {
@@ -46,6 +47,8 @@ fn test() {
                         r#type: DataType::Utf8String(StringOptions::default()),
                         primary_key: false,
                         nullable: true,
+                        mask: None,
+                        classifications: vec![],
                     },
                 },
                 CatalogEntityAttribue {
@@ -55,6 +58,8 @@ fn test() {
                         r#type: DataType::Int32,
                         primary_key: false,
                         nullable: true,
+                        mask: None,
+                        classifications: vec![],
                     },
                 },
             ],
@@ -28,6 +28,7 @@ fn test() {
             name: None,
             description: Some("This is the list of people".into()),
             tags: vec![],
+            classifications: vec![],
             attributes: vec![
                 CatalogEntityAttribue {
                     attribute: EntityAttributeConfig {
@@ -36,6 +37,8 @@ fn test() {
                         r#type: DataType::Utf8String(StringOptions::default()),
                         primary_key: false,
                         nullable: true,
+                        mask: None,
+                        classifications: vec![],
                     },
                 },
                 CatalogEntityAttribue {
@@ -45,6 +48,8 @@ fn test() {
                         r#type: DataType::Int32,
                         primary_key: false,
                         nullable: true,
+                        mask: None,
+                        classifications: vec![],
                     },
                 },
                 CatalogEntityAttribue {
@@ -54,6 +59,8 @@ fn test() {
                         r#type: DataType::Boolean,
                         primary_key: false,
                         nullable: true,
+                        mask: None,
+                        classifications: vec![],
                     },
                 },
             ],
@@ -1,54 +1,166 @@
+use std::sync::Arc;
+
 use ansilo_core::{
-    config::JobConfig,
+    config::{JobConfig, JobStepConfig, ScriptConfig},
     err::{Context, Result},
 };
-use ansilo_logging::{info, warn};
+use ansilo_logging::{debug, info, warn};
 use ansilo_pg::handler::PostgresConnectionHandler;
 
+use crate::script::{self, NodeAddress};
+
+/// Determines whether a triggered job should run on this node
+pub type JobGuard = Arc<dyn Fn(&JobConfig) -> bool + Send + Sync>;
+
+/// Runs the built-in jobs, which are performed by the node rather than executing sql.
+///
+/// The handler is called on a blocking thread.
+pub type BuiltinJobHandler = Arc<dyn Fn(&JobConfig) -> Result<()> + Send + Sync>;
+
 /// A scheduled job
 #[derive(Clone)]
 pub struct Job {
     /// The job config
-    conf: &'static JobConfig,
+    conf: Arc<JobConfig>,
     /// The postgres connection handler
     pg: PostgresConnectionHandler,
+    /// If set, the job is skipped when triggered unless the guard passes
+    guard: Option<JobGuard>,
+    /// Runs the job if it is a built-in job
+    builtin: Option<BuiltinJobHandler>,
+    /// The address of the node passed to script steps
+    address: Option<NodeAddress>,
 }
 
 impl Job {
-    pub fn new(conf: &'static JobConfig, pg: PostgresConnectionHandler) -> Self {
-        Self { conf, pg }
+    pub fn new(conf: Arc<JobConfig>, pg: PostgresConnectionHandler) -> Self {
+        Self {
+            conf,
+            pg,
+            guard: None,
+            builtin: None,
+            address: None,
+        }
+    }
+
+    /// Only runs the job when triggered if the guard passes
+    pub fn with_guard(mut self, guard: JobGuard) -> Self {
+        self.guard = Some(guard);
+        self
+    }
+
+    /// Runs the job using the handler if it is a built-in job
+    pub fn with_builtin_handler(mut self, handler: BuiltinJobHandler) -> Self {
+        self.builtin = Some(handler);
+        self
+    }
+
+    /// Passes the address of the node to script steps so they can connect back to it
+    pub fn with_node_address(mut self, address: NodeAddress) -> Self {
+        self.address = Some(address);
+        self
     }
 
     /// Run the job
     pub async fn run(&self) -> Result<()> {
         info!("Starting job '{}'", self.conf.id);
 
+        if self.conf.is_builtin() {
+            self.run_builtin().await?;
+        } else {
+            if !self.conf.sql.trim().is_empty() {
+                self.run_sql(&self.conf.sql).await?;
+            }
+
+            for (idx, step) in self.conf.steps.iter().enumerate() {
+                let res = match step {
+                    JobStepConfig::Sql(step) => self.run_sql(&step.sql).await,
+                    JobStepConfig::Script(step) => self.run_script(&step.script).await,
+                };
+
+                res.with_context(|| format!("Failed to run step {} of job", idx + 1))?;
+            }
+        }
+
+        info!("Completed job '{}'", self.conf.id);
+
+        Ok(())
+    }
+
+    async fn run_builtin(&self) -> Result<()> {
+        let handler = self
+            .builtin
+            .clone()
+            .context("No handler is registered for built-in jobs")?;
+        let conf = Arc::clone(&self.conf);
+
+        tokio::task::spawn_blocking(move || handler(&conf))
+            .await
+            .context("Failed to run built-in job")?
+    }
+
+    async fn run_sql(&self, sql: &str) -> Result<()> {
         // Acquire a connection to postgres and execute the queries
+        let catalog = self.conf.catalog.as_deref();
         let res = if let Some(svc_user) = self.conf.service_user.as_ref() {
             let con = self
                 .pg
-                .authenticate_as_service_user(svc_user.clone())
+                .authenticate_as_service_user_in(svc_user.clone(), catalog)
                 .await?;
 
-            con.batch_execute(&self.conf.sql).await
+            con.batch_execute(sql).await
         } else {
-            let con = self.pg.pool().admin().await?;
+            let con = match catalog {
+                Some(catalog) => self.pg.pool().catalog_admin(catalog).await?,
+                None => self.pg.pool().admin().await?,
+            };
 
-            con.batch_execute(&self.conf.sql).await
+            con.batch_execute(sql).await
         };
 
         res.context("Failed to execute sql")?;
 
-        info!("Completed job '{}'", self.conf.id);
-
         Ok(())
     }
 
+    async fn run_script(&self, conf: &ScriptConfig) -> Result<()> {
+        let mut vars = vec![("ANSILO_JOB_ID".to_string(), self.conf.id.clone())];
+
+        if let Some(address) = self.address.as_ref() {
+            vars.push(("PGHOST".into(), address.host.clone()));
+            vars.push(("PGPORT".into(), address.port.to_string()));
+            vars.push((
+                "PGDATABASE".into(),
+                self.conf
+                    .catalog
+                    .clone()
+                    .unwrap_or_else(|| "postgres".into()),
+            ));
+        }
+
+        // Scripts connect as the service user of the job, the credentials
+        // of the admin user are never passed to scripts
+        if let Some(svc_user) = self.conf.service_user.as_ref() {
+            let creds = self.pg.get_service_user_creds(svc_user).await?;
+            vars.push(("PGUSER".into(), creds.username));
+            vars.push(("PGPASSWORD".into(), creds.password));
+        }
+
+        script::run(conf, vars).await
+    }
+
     pub(crate) fn to_scheduler_job(self, cron: &str) -> Result<tokio_cron_scheduler::Job> {
         let job = tokio_cron_scheduler::Job::new_cron_job_async(cron, move |_, _| {
             let job = self.clone();
 
             Box::pin(async move {
+                if let Some(guard) = job.guard.as_ref() {
+                    if !guard(&job.conf) {
+                        debug!("Skipping job '{}' on this node", job.conf.id);
+                        return;
+                    }
+                }
+
                 if let Err(err) = job.run().await {
                     warn!("Error while executing job '{}': {:?}", job.conf.id, err)
                 }
@@ -61,10 +173,16 @@ impl Job {
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicU32, Ordering},
+    };
+
     use ansilo_auth::Authenticator;
     use ansilo_core::config::{
-        AuthConfig, ConstantServiceUserPassword, PasswordUserConfig, ServiceUserConfig,
-        ServiceUserPasswordMethod, UserConfig, UserTypeOptions,
+        AuthConfig, ConstantServiceUserPassword, PasswordUserConfig, PeerImportJobConfig,
+        ScriptJobStepConfig, ScriptLimitsConfig, ServiceUserConfig, ServiceUserPasswordMethod,
+        SqlJobStepConfig, UserConfig, UserTypeOptions,
     };
     use ansilo_pg::{
         connection::PostgresConnection, handler::test::init_pg_handler, PostgresInstance,
@@ -85,6 +203,7 @@ mod tests {
                 username: user.into(),
                 description: None,
                 provider: None,
+                roles: vec![],
                 r#type: UserTypeOptions::Password(PasswordUserConfig {
                     password: pass.into(),
                 }),
@@ -97,24 +216,28 @@ mod tests {
                     password: pass.into(),
                 }),
             )],
+            rules: vec![],
+            limits: vec![],
+            masks: vec![],
+            grants: vec![],
+            workloads: vec![],
         }));
 
         Authenticator::init(conf).unwrap()
     }
 
-    pub fn mock_job(
-        pg: PostgresConnectionHandler,
-        sql: &str,
-        service_user: Option<String>,
-    ) -> Job {
-        let conf = Box::leak(Box::new(JobConfig {
+    pub fn mock_job(pg: PostgresConnectionHandler, sql: &str, service_user: Option<String>) -> Job {
+        let conf = Arc::new(JobConfig {
             id: "test".into(),
             name: None,
             description: None,
             service_user,
             sql: sql.into(),
+            catalog: None,
+            peer_import: None,
+            steps: vec![],
             triggers: vec![],
-        }));
+        });
 
         Job::new(conf, pg)
     }
@@ -158,10 +281,12 @@ mod tests {
 
         query(&mut instance)
             .await
-            .batch_execute("
+            .batch_execute(
+                "
                 CREATE TABLE job AS SELECT 0 as runs, '' as usr;
                 GRANT SELECT, INSERT, UPDATE, DELETE ON job TO svc;
-            ")
+            ",
+            )
             .await
             .unwrap();
 
@@ -195,4 +320,155 @@ mod tests {
         dbg!(err.to_string());
         assert!(err.to_string().contains("Failed to execute sql"))
     }
+
+    fn mock_steps_job(
+        pg: PostgresConnectionHandler,
+        steps: Vec<JobStepConfig>,
+        service_user: Option<String>,
+    ) -> Job {
+        let conf = Arc::new(JobConfig {
+            id: "test".into(),
+            name: None,
+            description: None,
+            service_user,
+            sql: "".into(),
+            catalog: None,
+            peer_import: None,
+            steps,
+            triggers: vec![],
+        });
+
+        Job::new(conf, pg)
+    }
+
+    fn mock_script_step(script: &str) -> JobStepConfig {
+        JobStepConfig::Script(ScriptJobStepConfig {
+            script: ScriptConfig {
+                command: "/bin/sh".into(),
+                args: vec!["-c".into(), script.into()],
+                working_dir: None,
+                env: HashMap::new(),
+                timeout_secs: None,
+                user: None,
+                limits: ScriptLimitsConfig::default(),
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn test_job_run_steps() {
+        ansilo_logging::init_for_tests();
+        let (mut instance, pg) =
+            init_pg_handler("job-run-steps", mock_auth_svc_user("svc", "pass")).await;
+
+        query(&mut instance)
+            .await
+            .batch_execute(
+                "
+                CREATE TABLE job AS SELECT 0 as runs;
+                GRANT SELECT, INSERT, UPDATE, DELETE ON job TO svc;
+            ",
+            )
+            .await
+            .unwrap();
+
+        let job = mock_steps_job(
+            pg,
+            vec![
+                JobStepConfig::Sql(SqlJobStepConfig {
+                    sql: "UPDATE job SET runs = runs + 1".into(),
+                }),
+                mock_script_step(
+                    r#"test "$ANSILO_JOB_ID" = test && test "$PGUSER" = svc && test "$PGPASSWORD" = pass"#,
+                ),
+                JobStepConfig::Sql(SqlJobStepConfig {
+                    sql: "UPDATE job SET runs = runs + 1".into(),
+                }),
+            ],
+            Some("svc".into()),
+        );
+
+        job.run().await.unwrap();
+
+        let row = query(&mut instance)
+            .await
+            .query_one("SELECT * FROM job", &[])
+            .await
+            .unwrap();
+
+        assert_eq!(row.get::<_, i32>("runs"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_job_run_steps_error() {
+        ansilo_logging::init_for_tests();
+        let (_instance, pg) = init_pg_handler("job-run-steps-error", mock_auth_empty()).await;
+
+        let job = mock_steps_job(
+            pg,
+            vec![
+                mock_script_step("exit 0"),
+                mock_script_step("echo failed >&2; exit 1"),
+            ],
+            None,
+        );
+
+        let err = job.run().await.unwrap_err();
+
+        assert!(err.to_string().contains("Failed to run step 2"));
+        assert!(format!("{:?}", err).contains("failed"));
+    }
+
+    fn mock_builtin_job(pg: PostgresConnectionHandler) -> Job {
+        let conf = Arc::new(JobConfig {
+            id: "test".into(),
+            name: None,
+            description: None,
+            service_user: None,
+            sql: "".into(),
+            catalog: None,
+            peer_import: Some(PeerImportJobConfig {
+                data_source: "peer".into(),
+                schema: "peer".into(),
+                table_prefix: None,
+            }),
+            steps: vec![],
+            triggers: vec![],
+        });
+
+        Job::new(conf, pg)
+    }
+
+    #[tokio::test]
+    async fn test_job_run_builtin() {
+        ansilo_logging::init_for_tests();
+        let (_instance, pg) = init_pg_handler("job-run-builtin", mock_auth_empty()).await;
+
+        let runs = Arc::new(AtomicU32::new(0));
+        let job = mock_builtin_job(pg).with_builtin_handler({
+            let runs = runs.clone();
+            Arc::new(move |conf: &JobConfig| {
+                assert_eq!(conf.peer_import.as_ref().unwrap().data_source, "peer");
+                runs.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+        });
+
+        job.run().await.unwrap();
+
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_job_run_builtin_without_handler() {
+        ansilo_logging::init_for_tests();
+        let (_instance, pg) =
+            init_pg_handler("job-run-builtin-no-handler", mock_auth_empty()).await;
+
+        let job = mock_builtin_job(pg);
+
+        let err = job.run().await.unwrap_err();
+
+        assert!(err.to_string().contains("No handler is registered"))
+    }
 }
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use ansilo_core::{
     config::{JobConfig, JobTriggerConfig},
     err::{Context, Result},
@@ -6,9 +8,13 @@ use ansilo_logging::{error, info, warn};
 use ansilo_pg::handler::PostgresConnectionHandler;
 use tokio::runtime::Handle;
 
-use crate::job::Job;
+use crate::{
+    job::{BuiltinJobHandler, Job, JobGuard},
+    script::NodeAddress,
+};
 
 pub mod job;
+pub mod script;
 
 /// The entrypoint to the job scheduler subsystem
 pub struct JobScheduler {
@@ -21,34 +27,67 @@ pub struct JobScheduler {
 /// Inner state for async methods
 struct Inner {
     /// The list of configured jobs
-    jobs: &'static Vec<JobConfig>,
+    jobs: Vec<Arc<JobConfig>>,
     /// The postgres connection handler
     pg: PostgresConnectionHandler,
     /// The inner scheduler instance
     scheduler: Option<tokio_cron_scheduler::JobScheduler>,
+    /// If set, determines whether each triggered job runs on this node
+    guard: Option<JobGuard>,
+    /// If set, runs the built-in jobs
+    builtin: Option<BuiltinJobHandler>,
+    /// If set, the address of the node passed to script steps
+    address: Option<NodeAddress>,
 }
 
 impl JobScheduler {
-    pub fn new(
-        jobs: &'static Vec<JobConfig>,
-        runtime: Handle,
-        pg: PostgresConnectionHandler,
-    ) -> Self {
+    pub fn new(jobs: Vec<JobConfig>, runtime: Handle, pg: PostgresConnectionHandler) -> Self {
         Self {
             runtime,
             inner: Inner {
-                jobs,
+                jobs: jobs.into_iter().map(Arc::new).collect(),
                 pg,
                 scheduler: None,
+                guard: None,
+                builtin: None,
+                address: None,
             },
         }
     }
 
+    /// Only runs the triggered jobs which pass the guard, eg
+    /// to run a job on a single member of a cluster
+    pub fn with_guard(mut self, guard: JobGuard) -> Self {
+        self.inner.guard = Some(guard);
+        self
+    }
+
+    /// Runs the built-in jobs using the handler
+    pub fn with_builtin_handler(mut self, handler: BuiltinJobHandler) -> Self {
+        self.inner.builtin = Some(handler);
+        self
+    }
+
+    /// Passes the address of the node to script steps so they can connect back to it
+    pub fn with_node_address(mut self, address: NodeAddress) -> Self {
+        self.inner.address = Some(address);
+        self
+    }
+
     /// Start the job scheduler
     pub fn start(&mut self) -> Result<()> {
         self.runtime.block_on(self.inner.start())
     }
 
+    /// Replaces the scheduled jobs with the supplied jobs.
+    ///
+    /// The running scheduler is shutdown and restarted with the new job list.
+    pub fn reload(&mut self, jobs: Vec<JobConfig>) -> Result<()> {
+        self.terminate_mut()?;
+        self.inner.jobs = jobs.into_iter().map(Arc::new).collect();
+        self.start()
+    }
+
     /// Checks whether the scheduler is healthy
     pub fn healthy(&self) -> bool {
         match &self.inner.scheduler {
@@ -88,9 +127,18 @@ impl Inner {
 
                 info!("Installing job '{}' for schedule {}", job.id, cron);
 
-                scheduler
-                    .add(Job::new(job, self.pg.clone()).to_scheduler_job(&cron)?)
-                    .await?;
+                let mut scheduled = Job::new(Arc::clone(job), self.pg.clone());
+                if let Some(guard) = self.guard.as_ref() {
+                    scheduled = scheduled.with_guard(guard.clone());
+                }
+                if let Some(handler) = self.builtin.as_ref() {
+                    scheduled = scheduled.with_builtin_handler(handler.clone());
+                }
+                if let Some(address) = self.address.as_ref() {
+                    scheduled = scheduled.with_node_address(address.clone());
+                }
+
+                scheduler.add(scheduled.to_scheduler_job(&cron)?).await?;
             }
         }
 
@@ -149,14 +197,27 @@ mod tests {
         ansilo_logging::init_for_tests();
         let (_instance, pg) = init_pg_handler("job-scheduler-empty", mock_auth_empty()).await;
 
-        let mut scheduler = JobScheduler::new(
-            Box::leak(Box::new(vec![])),
-            tokio::runtime::Handle::current(),
-            pg,
-        );
+        let mut scheduler = JobScheduler::new(vec![], tokio::runtime::Handle::current(), pg);
+
+        tokio::task::spawn_blocking(move || {
+            scheduler.start().unwrap();
+            scheduler.terminate().unwrap();
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_job_scheduler_reload() {
+        ansilo_logging::init_for_tests();
+        let (_instance, pg) = init_pg_handler("job-scheduler-reload", mock_auth_empty()).await;
+
+        let mut scheduler = JobScheduler::new(vec![], tokio::runtime::Handle::current(), pg);
 
         tokio::task::spawn_blocking(move || {
             scheduler.start().unwrap();
+            scheduler.reload(vec![]).unwrap();
+            assert!(scheduler.healthy());
             scheduler.terminate().unwrap();
         })
         .await
@@ -177,16 +238,19 @@ mod tests {
 
         // Increment the counter every second
         let mut scheduler = JobScheduler::new(
-            Box::leak(Box::new(vec![JobConfig {
+            vec![JobConfig {
                 id: "test".into(),
                 name: None,
                 description: None,
                 service_user: None,
                 sql: "UPDATE job SET runs = runs + 1".into(),
+                catalog: None,
+                peer_import: None,
+                steps: vec![],
                 triggers: vec![JobTriggerConfig::Cron(CronTriggerConfig {
                     cron: "* * * * * *".into(),
                 })],
-            }])),
+            }],
             tokio::runtime::Handle::current(),
             pg,
         );
@@ -0,0 +1,271 @@
+use std::{io, process::Stdio};
+
+use ansilo_core::{
+    config::{ScriptConfig, ScriptLimitsConfig},
+    err::{bail, Context, Result},
+};
+use ansilo_logging::debug;
+use nix::{
+    sys::{
+        resource::{setrlimit, Resource},
+        signal::{killpg, Signal},
+    },
+    unistd::{chown, setpgid, Pid, User},
+};
+use tokio::process::Command;
+
+/// The address of the node, passed to scripts so they can connect back to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeAddress {
+    pub host: String,
+    pub port: u16,
+}
+
+/// Runs the command of a script step.
+///
+/// The command does not inherit the environment of the node, it receives only
+/// `PATH`, the configured variables and the supplied variables.
+/// If configured, the command is run as a separate user and its processes are
+/// constrained by resource limits. The command is run in its own process group
+/// which is killed once the command exits or exceeds its timeout, so any processes
+/// it started in the background do not outlive it.
+pub(crate) async fn run(conf: &ScriptConfig, vars: Vec<(String, String)>) -> Result<()> {
+    // The temporary directory is removed once dropped
+    let temp_dir;
+    let working_dir = match conf.working_dir.as_ref() {
+        Some(dir) => dir.as_path(),
+        None => {
+            temp_dir = tempfile::tempdir().context("Failed to create working directory")?;
+            temp_dir.path()
+        }
+    };
+
+    let mut cmd = Command::new(&conf.command);
+    cmd.args(&conf.args)
+        .current_dir(working_dir)
+        .env_clear()
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    if let Some(path) = std::env::var_os("PATH") {
+        cmd.env("PATH", path);
+    }
+    cmd.env("HOME", working_dir);
+    cmd.envs(&conf.env);
+    cmd.envs(vars);
+
+    if let Some(name) = conf.user.as_ref() {
+        let user = User::from_name(name)
+            .with_context(|| format!("Failed to look up user '{}'", name))?
+            .with_context(|| format!("User '{}' does not exist", name))?;
+
+        // The temporary working directory must be writable by the user
+        if conf.working_dir.is_none() {
+            chown(working_dir, Some(user.uid), Some(user.gid)).with_context(|| {
+                format!("Failed to change owner of working directory to '{}'", name)
+            })?;
+        }
+
+        cmd.uid(user.uid.as_raw()).gid(user.gid.as_raw());
+    }
+
+    let limits = rlimits(&conf.limits);
+    // SAFETY: the closure runs in the forked child before exec and
+    // only calls setpgid and setrlimit, which are async-signal-safe
+    unsafe {
+        cmd.pre_exec(move || {
+            setpgid(Pid::from_raw(0), Pid::from_raw(0)).map_err(io::Error::from)?;
+
+            for (resource, limit) in limits.iter() {
+                setrlimit(*resource, Some(*limit), Some(*limit)).map_err(io::Error::from)?;
+            }
+
+            Ok(())
+        });
+    }
+
+    debug!("Running command '{}'", conf.command);
+    let child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to start command '{}'", conf.command))?;
+
+    // The process group is killed once dropped, including on timeout
+    let _group = child.id().map(|pid| ProcessGroup(Pid::from_raw(pid as _)));
+
+    let output = match tokio::time::timeout(conf.timeout(), child.wait_with_output()).await {
+        Ok(output) => output.context("Failed to wait for command")?,
+        Err(_) => bail!(
+            "Command '{}' timed out after {}s",
+            conf.command,
+            conf.timeout().as_secs()
+        ),
+    };
+
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        debug!("[{}] {}", conf.command, line);
+    }
+
+    if !output.status.success() {
+        bail!(
+            "Command '{}' failed with {}: {}",
+            conf.command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+/// The process group of a running command, which is killed once dropped
+struct ProcessGroup(Pid);
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        // The group will not exist if all of its processes have exited
+        let _ = killpg(self.0, Signal::SIGKILL);
+    }
+}
+
+/// Maps the configured limits to the rlimits applied to the command
+fn rlimits(conf: &ScriptLimitsConfig) -> Vec<(Resource, u64)> {
+    const MB: u64 = 1024 * 1024;
+
+    [
+        (Resource::RLIMIT_AS, conf.max_memory_mb.map(|l| l * MB)),
+        (Resource::RLIMIT_CPU, conf.max_cpu_secs),
+        (
+            Resource::RLIMIT_FSIZE,
+            conf.max_file_size_mb.map(|l| l * MB),
+        ),
+        (Resource::RLIMIT_NPROC, conf.max_processes),
+    ]
+    .into_iter()
+    .filter_map(|(resource, limit)| limit.map(|l| (resource, l)))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn mock_script(script: &str, timeout_secs: Option<u64>) -> ScriptConfig {
+        ScriptConfig {
+            command: "/bin/sh".into(),
+            args: vec!["-c".into(), script.into()],
+            working_dir: None,
+            env: [("KEY".to_string(), "value".to_string())]
+                .into_iter()
+                .collect::<HashMap<_, _>>(),
+            timeout_secs,
+            user: None,
+            limits: ScriptLimitsConfig::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_script_run_success() {
+        let conf = mock_script(
+            r#"test "$KEY" = value && test "$INJECTED" = 1 && test "$HOME" = "$(pwd)""#,
+            None,
+        );
+
+        run(&conf, vec![("INJECTED".into(), "1".into())])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_script_run_failure() {
+        let conf = mock_script("echo oops >&2; exit 3", None);
+
+        let err = run(&conf, vec![]).await.unwrap_err();
+
+        assert!(err.to_string().contains("oops"));
+    }
+
+    #[tokio::test]
+    async fn test_script_run_timeout() {
+        let conf = mock_script("sleep 10", Some(1));
+
+        let err = run(&conf, vec![]).await.unwrap_err();
+
+        assert!(err.to_string().contains("timed out"));
+    }
+
+    #[tokio::test]
+    async fn test_script_run_timeout_kills_background_processes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut conf = mock_script("sleep 30 & echo $! > pid; sleep 10", Some(1));
+        conf.working_dir = Some(dir.path().to_path_buf());
+
+        run(&conf, vec![]).await.unwrap_err();
+
+        let pid = std::fs::read_to_string(dir.path().join("pid")).unwrap();
+        let pid = Pid::from_raw(pid.trim().parse().unwrap());
+
+        // The killed process may briefly remain until it is reaped
+        for _ in 0..50 {
+            if nix::sys::signal::kill(pid, None).is_err() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+
+        panic!("Background process of the script is still running");
+    }
+
+    #[tokio::test]
+    async fn test_script_run_invalid_command() {
+        let mut conf = mock_script("", None);
+        conf.command = "/non/existent".into();
+
+        let err = run(&conf, vec![]).await.unwrap_err();
+
+        assert!(err.to_string().contains("Failed to start command"));
+    }
+
+    #[tokio::test]
+    async fn test_script_run_limits() {
+        let mut conf = mock_script(
+            r#"test "$(ulimit -t)" = 5 && test "$(ulimit -v)" = 524288"#,
+            None,
+        );
+        conf.limits = ScriptLimitsConfig {
+            max_memory_mb: Some(512),
+            max_cpu_secs: Some(5),
+            ..Default::default()
+        };
+
+        run(&conf, vec![]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_script_run_unknown_user() {
+        let mut conf = mock_script("true", None);
+        conf.user = Some("ansilo-non-existent-user".into());
+
+        let err = run(&conf, vec![]).await.unwrap_err();
+
+        assert!(err.to_string().contains("does not exist"));
+    }
+
+    #[test]
+    fn test_script_rlimits() {
+        assert_eq!(rlimits(&ScriptLimitsConfig::default()), vec![]);
+        assert_eq!(
+            rlimits(&ScriptLimitsConfig {
+                max_memory_mb: Some(2),
+                max_processes: Some(10),
+                ..Default::default()
+            }),
+            vec![
+                (Resource::RLIMIT_AS, 2 * 1024 * 1024),
+                (Resource::RLIMIT_NPROC, 10)
+            ]
+        );
+    }
+}
@@ -1,21 +1,88 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    RwLock,
+};
 
-use ansilo_core::err::Result;
+use ansilo_core::err::{Context, Error, Result};
 pub use env_logger::{init, init_from_env};
 pub use log::*;
+use once_cell::sync::OnceCell;
 
 pub mod limiting;
 
 static TEST_MODE: AtomicBool = AtomicBool::new(false);
 
+/// The global logger, of which the filter can be changed at runtime
+static LOGGER: OnceCell<ReloadableLogger> = OnceCell::new();
+
+/// Wraps an env_logger instance so it can be swapped out
+/// when the log filter is changed
+struct ReloadableLogger {
+    inner: RwLock<env_logger::Logger>,
+}
+
 /// Configures the logger
 pub fn init_logging() -> Result<()> {
-    env_logger::init_from_env(
+    let inner = env_logger::Builder::from_env(
         env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
-    );
+    )
+    .build();
+    let max_level = inner.filter();
+
+    let logger = LOGGER.get_or_init(|| ReloadableLogger {
+        inner: RwLock::new(inner),
+    });
+
+    log::set_logger(logger).context("Failed to set logger")?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// Updates the filter of the global logger at runtime.
+///
+/// The filter uses the same syntax as the RUST_LOG env var,
+/// if none is supplied we revert to the filter defined by the env.
+pub fn set_filter(filter: Option<&str>) -> Result<()> {
+    let logger = LOGGER.get().context("Logging has not been initialised")?;
+
+    let inner = match filter {
+        Some(filter) => env_logger::Builder::new().parse_filters(filter).build(),
+        None => env_logger::Builder::from_env(
+            env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
+        )
+        .build(),
+    };
+
+    log::set_max_level(inner.filter());
+    *logger
+        .inner
+        .write()
+        .map_err(|_| Error::msg("Failed to lock logger"))? = inner;
+
     Ok(())
 }
 
+impl Log for ReloadableLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        match self.inner.read() {
+            Ok(inner) => inner.enabled(metadata),
+            Err(_) => false,
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if let Ok(inner) = self.inner.read() {
+            inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(inner) = self.inner.read() {
+            inner.flush();
+        }
+    }
+}
+
 /// Logging init function for tests
 pub fn init_for_tests() {
     TEST_MODE.store(true, Ordering::Relaxed);
@@ -37,7 +104,7 @@ pub fn test_mode() -> bool {
 mod tests {
     use ansilo_core::config::NodeConfig;
 
-    use crate::init_logging;
+    use super::*;
 
     #[test]
     fn test_init_logging() {
@@ -46,5 +113,11 @@ mod tests {
         let res = init_logging();
 
         assert!(res.is_ok());
+
+        set_filter(Some("warn")).unwrap();
+        assert_eq!(log::max_level(), LevelFilter::Warn);
+
+        set_filter(Some("warn,ansilo=trace")).unwrap();
+        assert_eq!(log::max_level(), LevelFilter::Trace);
     }
 }
@@ -1,8 +1,16 @@
 use std::path::PathBuf;
 
-use ansilo_core::err::{Error, Result};
+use ansilo_core::{
+    config::NodeConfig,
+    err::{Error, Result},
+};
 use clap::Parser;
 
+use crate::{backup::BackupMethod, data::DataFileFormat};
+
+/// The default path of the file which logs are written to when running as a daemon
+const DEFAULT_LOG_FILE: &str = "/var/log/ansilo/ansilo.log";
+
 /// Arguments for running the Ansilo main program
 #[derive(Parser, Debug, Clone)]
 #[clap(author, version, about, long_about = None)]
@@ -11,13 +19,36 @@ pub enum Command {
     ///
     /// If the databasde has not been initialized it will be initialised
     /// with the current configuration.
-    Run(Args),
+    Run(RunArgs),
     /// Runs in development mode with hot-reload enabled
     Dev(Args),
     /// Initializes postgres so it can be booted rapidly.
     Build(Args),
-    /// Prints the config, after evaluating all expressions, to stdout
+    /// Prints the config, after evaluating all expressions, to stdout.
+    /// Secrets are masked so the output can be safely shared.
     DumpConfig(Args),
+    /// Runs a query against the running instance and exports the results to a file
+    Export(ExportArgs),
+    /// Bulk imports a file into a table of the running instance
+    Import(ImportArgs),
+    /// Prints a summary of the health of the running instance.
+    ///
+    /// Exits with a non-zero status code if the instance is unhealthy.
+    Status(StatusArgs),
+    /// Backs up the configuration, build info, postgres data and logs
+    /// of the running instance into a single archive.
+    ///
+    /// The archive is encrypted if encryption is configured.
+    Backup(BackupArgs),
+    /// Restores a node from an archive created by the backup command.
+    ///
+    /// The node must be stopped as the data directory is replaced.
+    Restore(RestoreArgs),
+    /// Encrypts a secret read from stdin using the config key, printing
+    /// an `!encrypted` value which can be safely included in the config.
+    ///
+    /// The key is read from the ANSILO_CONFIG_KEY or ANSILO_CONFIG_KEY_URL env var.
+    EncryptSecret(Args),
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -36,15 +67,154 @@ pub struct Args {
     /// Whether to force a build of the postgres database
     #[clap(short, long, value_parser)]
     pub force_build: bool,
+
+    /// The directory from which connector plugins are loaded.
+    /// If not specified, no plugins are loaded
+    #[clap(long, value_parser)]
+    pub plugins_dir: Option<PathBuf>,
+}
+
+/// Arguments for running the instance
+#[derive(Parser, Debug, Clone)]
+pub struct RunArgs {
+    #[clap(flatten)]
+    pub args: Args,
+
+    /// Whether to fork and detach from the terminal, running as a background daemon
+    #[clap(long, value_parser)]
+    pub daemon: bool,
+
+    /// The path of the pid file written when running as a daemon
+    #[clap(long, value_parser)]
+    pub pid_file: Option<PathBuf>,
+
+    /// The path of the file which logs are written to when running as a daemon.
+    /// Overrides the log file in the node config.
+    #[clap(long, value_parser)]
+    pub log_file: Option<PathBuf>,
+
+    /// The user to switch to after binding the listening port when started as root
+    #[clap(long, value_parser)]
+    pub run_as_user: Option<String>,
+
+    /// The group to switch to after binding the listening port when started as root.
+    /// Defaults to the primary group of the user.
+    #[clap(long, value_parser)]
+    pub run_as_group: Option<String>,
+}
+
+/// Arguments for printing the status of the running instance
+#[derive(Parser, Debug, Clone)]
+pub struct StatusArgs {
+    #[clap(flatten)]
+    pub args: Args,
+
+    /// The hostname which the certificate of the instance was issued to,
+    /// used to verify the certificate when TLS is enabled.
+    /// Defaults to the name of the node
+    #[clap(long, value_parser)]
+    pub host: Option<String>,
+}
+
+/// Arguments for exporting query results to a file
+#[derive(Parser, Debug, Clone)]
+pub struct ExportArgs {
+    #[clap(flatten)]
+    pub args: Args,
+
+    /// The sql query of which the results are exported
+    #[clap(short, long, value_parser)]
+    pub query: String,
+
+    /// The path of the file to write the results to
+    #[clap(short, long, value_parser)]
+    pub output: PathBuf,
+
+    /// The format of the output file.
+    /// If not specified, this is inferred from the file extension.
+    #[clap(long, value_enum)]
+    pub format: Option<DataFileFormat>,
+}
+
+/// Arguments for importing a file into a table
+#[derive(Parser, Debug, Clone)]
+pub struct ImportArgs {
+    #[clap(flatten)]
+    pub args: Args,
+
+    /// The path of the file to import
+    #[clap(short, long, value_parser)]
+    pub input: PathBuf,
+
+    /// The (foreign or local) table to import the rows into.
+    /// This uses sql syntax, so quote identifiers which are not lower case, eg '"MySchema"."People"'
+    #[clap(short, long, value_parser)]
+    pub table: String,
+
+    /// The format of the input file.
+    /// If not specified, this is inferred from the file extension.
+    #[clap(long, value_enum)]
+    pub format: Option<DataFileFormat>,
+
+    /// The number of rows sent to postgres per batch
+    #[clap(long, value_parser, default_value_t = 1000)]
+    pub batch_size: usize,
+}
+
+/// Arguments for backing up the instance
+#[derive(Parser, Debug, Clone)]
+pub struct BackupArgs {
+    #[clap(flatten)]
+    pub args: Args,
+
+    /// The path to write the archive to, or an s3:// url to upload the archive to
+    #[clap(short, long, value_parser)]
+    pub output: String,
+
+    /// How the postgres data is captured
+    #[clap(long, value_enum, default_value_t = BackupMethod::Basebackup)]
+    pub method: BackupMethod,
+
+    /// The path of the log file of the node, it is included along with its rotated files.
+    /// Overrides the log file in the node config.
+    #[clap(long, value_parser)]
+    pub log_file: Option<PathBuf>,
+}
+
+/// Arguments for restoring the instance from a backup
+#[derive(Parser, Debug, Clone)]
+pub struct RestoreArgs {
+    #[clap(flatten)]
+    pub args: Args,
+
+    /// The path of the archive, or an s3:// url to download the archive from
+    #[clap(short, long, value_parser)]
+    pub input: String,
+
+    /// Whether to keep the existing configuration rather than
+    /// restoring the configuration in the archive
+    #[clap(long, value_parser)]
+    pub skip_config: bool,
+
+    /// The path of the log file of the node, the logs in the archive are restored alongside it.
+    /// Overrides the log file in the node config.
+    #[clap(long, value_parser)]
+    pub log_file: Option<PathBuf>,
 }
 
 impl Command {
     pub(crate) fn args(&self) -> &Args {
         match self {
-            Command::Run(args) => args,
+            Command::Run(run) => &run.args,
             Command::Build(args) => args,
             Command::Dev(args) => args,
             Command::DumpConfig(args) => args,
+            Command::Export(export) => &export.args,
+            Command::Import(import) => &import.args,
+            Command::Status(status) => &status.args,
+            Command::Backup(backup) => &backup.args,
+            Command::Restore(restore) => &restore.args,
+            Command::EncryptSecret(args) => args,
         }
     }
 
@@ -82,6 +252,50 @@ impl Command {
     }
 }
 
+impl RunArgs {
+    pub(crate) fn pid_file(&self) -> PathBuf {
+        self.pid_file
+            .clone()
+            .unwrap_or("/var/run/ansilo/ansilo.pid".into())
+    }
+
+    pub(crate) fn log_file(&self, conf: &NodeConfig) -> PathBuf {
+        log_file(self.log_file.as_ref(), conf)
+    }
+}
+
+impl BackupArgs {
+    pub(crate) fn log_file(&self, conf: &NodeConfig) -> PathBuf {
+        log_file(self.log_file.as_ref(), conf)
+    }
+}
+
+impl RestoreArgs {
+    pub(crate) fn log_file(&self, conf: &NodeConfig) -> PathBuf {
+        log_file(self.log_file.as_ref(), conf)
+    }
+}
+
+/// Gets the log file from the node config, unless overridden by the --log-file argument
+fn log_file(arg: Option<&PathBuf>, conf: &NodeConfig) -> PathBuf {
+    arg.or(conf.logging.file.as_ref())
+        .cloned()
+        .unwrap_or(DEFAULT_LOG_FILE.into())
+}
+
+impl From<Args> for RunArgs {
+    fn from(args: Args) -> Self {
+        Self {
+            args,
+            daemon: false,
+            pid_file: None,
+            log_file: None,
+            run_as_user: None,
+            run_as_group: None,
+        }
+    }
+}
+
 impl Args {
     pub(crate) fn config(&self) -> std::path::PathBuf {
         self.config
@@ -0,0 +1,1030 @@
+use std::{
+    ffi::OsStr,
+    fs::{self, File, Permissions},
+    os::unix::prelude::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use ansilo_core::{
+    build::ansilo_version,
+    err::{bail, ensure, Context, Result},
+};
+use ansilo_logging::{info, warn};
+use ansilo_pg::{
+    conf::PostgresConf, proc::ChildProc, PostgresInstance, PG_DATABASE, PG_PORT, PG_SUPER_USER,
+};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use nix::{
+    fcntl::{renameat2, RenameFlags},
+    sys::signal::Signal,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    args::{BackupArgs, RestoreArgs},
+    build::BuildInfo,
+    conf::{init_conf, AppConf},
+    encryption::{self, EncryptedVolume, EncryptionKey},
+};
+
+/// The file within the archive describing its contents
+const MANIFEST_FILE: &str = "manifest.json";
+/// The directory within the archive containing the configuration directory
+const CONFIG_DIR: &str = "config";
+/// The file within the archive containing the build info
+const BUILD_INFO_FILE: &str = "build-info.json";
+/// The directory within the archive containing the postgres backup
+const POSTGRES_DIR: &str = "postgres";
+/// The directory within the archive containing the log files
+const LOGS_DIR: &str = "logs";
+/// The tar of the data directory produced by pg_basebackup
+const BASE_BACKUP_FILE: &str = "base.tar";
+/// The sql script produced by pg_dumpall
+const DUMP_FILE: &str = "dump.sql";
+
+/// How the postgres data is captured in a backup
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupMethod {
+    /// A physical copy of the data directory using pg_basebackup.
+    /// This can only be restored to a node running the same postgres version.
+    Basebackup,
+    /// A logical sql dump of all databases using pg_dumpall
+    Dump,
+}
+
+/// Describes the contents of a backup archive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    /// The version of ansilo which created the backup
+    pub version: String,
+    /// When the backup was created
+    pub created_at: DateTime<Utc>,
+    /// The name of the node which was backed up
+    pub node: String,
+    /// The file name of the main configuration file
+    pub config_file: String,
+    /// How the postgres data was captured
+    pub method: BackupMethod,
+    /// Whether the archive contains the build info of the node
+    pub build_info: bool,
+    /// The names of the log files in the archive
+    pub logs: Vec<String>,
+}
+
+/// Captures the configuration, build info, postgres data and logs of
+/// the running instance into a single archive.
+///
+/// If encryption is configured the archive is encrypted using the key of the node.
+pub fn backup(conf: &AppConf, args: &BackupArgs) -> Result<()> {
+    let key = conf
+        .node
+        .encryption
+        .as_ref()
+        .map(EncryptionKey::fetch)
+        .transpose()?;
+
+    let staging = tempfile::tempdir().context("Failed to create staging directory")?;
+    let pg_dir = staging.path().join(POSTGRES_DIR);
+    fs::create_dir_all(&pg_dir).context("Failed to create staging directory")?;
+
+    match args.method {
+        BackupMethod::Basebackup => base_backup(conf, &pg_dir)?,
+        BackupMethod::Dump => dump(conf, &pg_dir)?,
+    }
+
+    let upload = is_url(&args.output);
+    let archive = if upload {
+        staging.path().join("backup.tar.gz")
+    } else {
+        PathBuf::from(&args.output)
+    };
+
+    info!("Writing archive...");
+    write_archive(conf, args, &pg_dir, &archive)?;
+
+    if let Some(key) = key.as_ref() {
+        info!("Encrypting archive...");
+        key.seal_file(&archive)
+            .context("Failed to encrypt archive")?;
+    }
+
+    if upload {
+        info!("Uploading archive to {}...", args.output);
+        ansilo_util_url::upload(args.output.clone(), &archive)?;
+    }
+
+    info!("Backed up node '{}' to {}", conf.node.name, args.output);
+    Ok(())
+}
+
+/// Restores the configuration, build info, postgres data and logs
+/// of a node from an archive created by [`backup`].
+///
+/// The node must be stopped as the data directory is replaced. The archive is
+/// fully unpacked and validated, and the new configuration and data directories
+/// are prepared alongside the existing ones, before they are swapped in.
+pub fn restore(args: &RestoreArgs) -> Result<()> {
+    let config_path = args.args.config();
+
+    // The existing configuration is used to check the node is stopped and to retrieve
+    // the key of encrypted archives, although it may be missing or invalid on a new node
+    let existing = if config_path.exists() {
+        match init_conf(&config_path, &args.args) {
+            Ok(conf) => Some(conf),
+            Err(err) => {
+                warn!("Failed to load the existing configuration: {:?}", err);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    if let Some(conf) = existing.as_ref() {
+        ensure_stopped(conf)?;
+    }
+
+    let staging = tempfile::tempdir().context("Failed to create staging directory")?;
+
+    // The archive is copied to the staging directory so it can be decrypted in place
+    let archive = staging.path().join("backup.tar.gz");
+    if is_url(&args.input) {
+        info!("Downloading archive from {}...", args.input);
+        ansilo_util_url::download(args.input.clone(), &archive)?;
+    } else {
+        fs::copy(&args.input, &archive)
+            .with_context(|| format!("Failed to read archive {}", args.input))?;
+    }
+
+    let sealed = encryption::is_sealed(&File::open(&archive).context("Failed to open archive")?)?;
+    if sealed {
+        // The configuration in the archive is encrypted so the
+        // key is retrieved using the existing configuration
+        let encryption = match existing.as_ref().map(|c| c.node.encryption.as_ref()) {
+            Some(Some(encryption)) => encryption,
+            Some(None) => bail!("The archive is encrypted but encryption is not configured"),
+            None => bail!(
+                "The archive is encrypted, the existing configuration is required to decrypt it"
+            ),
+        };
+
+        info!("Decrypting archive...");
+        EncryptionKey::fetch(encryption)?
+            .unseal_file(&archive)
+            .context("Failed to decrypt archive")?;
+    }
+
+    let contents = staging.path().join("archive");
+    unpack_archive(&archive, &contents)?;
+    let manifest = read_manifest(&contents)?;
+    validate_archive(&contents, &manifest, args.skip_config)?;
+    info!(
+        "Restoring node '{}' backed up at {} (version {})",
+        manifest.node,
+        manifest.created_at.to_rfc3339(),
+        manifest.version
+    );
+
+    // The new configuration directory is staged alongside the existing one
+    let config_dir = absolute_dir(config_dir(&config_path))?;
+    let staged_config = if args.skip_config {
+        info!("Skipping restore of configuration");
+        None
+    } else {
+        ensure!(
+            config_path.file_name().and_then(|n| n.to_str()) == Some(manifest.config_file.as_str()),
+            "The archive contains the configuration file '{}', restore using --config {}",
+            manifest.config_file,
+            config_dir.join(&manifest.config_file).display()
+        );
+
+        let staged = StagedDir::new(&config_dir)?;
+        copy_dir(&contents.join(CONFIG_DIR), staged.path())?;
+        Some(staged)
+    };
+
+    // We are happy to let the app-wide config leak for the rest of the program
+    let conf: &'static _ = Box::leak(Box::new(match staged_config.as_ref() {
+        Some(staged) => init_conf(&staged.path().join(&manifest.config_file), &args.args)
+            .context("Failed to load the configuration from the archive")?,
+        None => init_conf(&config_path, &args.args)?,
+    }));
+    ensure_stopped(conf)?;
+
+    // The new data directory is staged alongside the existing one, unless it resides
+    // within the configuration directory, in which case it is swapped in along with it
+    // (which is also the case if it is relative to the staged configuration file)
+    let in_staged_config = |dir: &Path| {
+        staged_config.as_ref().and_then(|staged| {
+            dir.strip_prefix(&config_dir)
+                .or_else(|_| dir.strip_prefix(staged.path()))
+                .ok()
+                .map(|relative| staged.path().join(relative))
+        })
+    };
+    let data_dir = absolute_dir(&conf.pg.data_dir)?;
+    let (staged_data, data_path) = match in_staged_config(&data_dir) {
+        Some(path) => (None, path),
+        None => {
+            let staged = StagedDir::new(&data_dir)?;
+            let path = staged.path().to_path_buf();
+            (Some(staged), path)
+        }
+    };
+
+    // The data of encrypted nodes is restored into a new encrypted volume, which is
+    // staged in the same way and mounted at the staged data directory, so the
+    // restored files are only stored on disk encrypted
+    let key = conf
+        .node
+        .encryption
+        .as_ref()
+        .map(EncryptionKey::fetch)
+        .transpose()?;
+    let (staged_volume, mounted) = match (key.as_ref(), conf.node.encryption.as_ref()) {
+        (Some(key), Some(encryption)) => {
+            let volume_dir =
+                absolute_dir(EncryptedVolume::for_data_dir(encryption, &data_dir).volume_dir())?;
+            let (staged, volume_path) = match in_staged_config(&volume_dir) {
+                Some(path) => (None, path),
+                None => {
+                    let staged = StagedDir::new(&volume_dir)?;
+                    let path = staged.path().to_path_buf();
+                    (Some((staged, volume_dir)), path)
+                }
+            };
+
+            let volume = EncryptedVolume::new(volume_path, &data_path);
+            volume
+                .mount(key)
+                .context("Failed to mount encrypted data directory")?;
+            (staged, Some(MountedVolume(Some(volume))))
+        }
+        _ => (None, None),
+    };
+
+    let pg_dir = contents.join(POSTGRES_DIR);
+    match manifest.method {
+        BackupMethod::Basebackup => restore_base_backup(&pg_dir, &data_path)?,
+        BackupMethod::Dump => restore_dump(conf, &pg_dir, &data_path)?,
+    }
+
+    // The mount point cannot be moved so the volume is unmounted before it is swapped in
+    if let Some(mounted) = mounted {
+        mounted.unmount()?;
+    }
+
+    // Everything is in place so the existing directories are swapped out
+    let mut swaps = vec![];
+    if let Some(staged) = staged_config.as_ref() {
+        swaps.push(("configuration", staged, config_dir.as_path()));
+    }
+    if let Some((staged, volume_dir)) = staged_volume.as_ref() {
+        swaps.push(("encrypted volume", staged, volume_dir.as_path()));
+    }
+    if let Some(staged) = staged_data.as_ref() {
+        swaps.push(("data directory", staged, data_dir.as_path()));
+    }
+    swap_all(&swaps)?;
+
+    // The configuration is reloaded from its final location as
+    // paths within it may be relative to the configuration directory
+    let reloaded = match staged_config {
+        Some(_) => Some(init_conf(&config_path, &args.args)?),
+        None => None,
+    };
+    let conf = reloaded.as_ref().unwrap_or(conf);
+
+    // Without the build info the data directory would be rebuilt on startup
+    let build_info: BuildInfo = if manifest.build_info {
+        let json = fs::read(contents.join(BUILD_INFO_FILE)).context("Failed to read build info")?;
+        serde_json::from_slice(&json).context("Failed to deserialize build info")?
+    } else {
+        BuildInfo::new()
+    };
+    build_info.store(conf)?;
+
+    let logs = restore_logs(
+        &contents.join(LOGS_DIR),
+        &manifest.logs,
+        &args.log_file(&conf.node),
+    )?;
+    if let Some(key) = key.as_ref() {
+        for log in logs.iter() {
+            key.seal_file(log)
+                .with_context(|| format!("Failed to encrypt log file {}", log.display()))?;
+        }
+    }
+
+    // Dropping the staged directories removes the replaced configuration and data
+    drop(staged_data);
+    drop(staged_config);
+
+    info!("Restored node '{}' from {}", manifest.node, args.input);
+    Ok(())
+}
+
+/// Captures a physical copy of the data directory of the running instance,
+/// including the WAL required to restore it
+fn base_backup(conf: &AppConf, dir: &Path) -> Result<()> {
+    info!("Running pg_basebackup...");
+    let mut cmd = Command::new(conf.pg.install_dir.join("bin/pg_basebackup"));
+    cmd.arg("-D")
+        .arg(dir)
+        .arg("-h")
+        .arg(&conf.pg.socket_dir_path)
+        .args(["-p", &PG_PORT.to_string()])
+        .args(["-U", PG_SUPER_USER])
+        .args(["-F", "tar", "-X", "fetch"])
+        .arg("--checkpoint=fast")
+        .arg("--no-password");
+
+    run("[pg_basebackup]", cmd).context("Failed to back up postgres, is the node running?")
+}
+
+/// Captures a logical sql dump of all databases of the running instance
+fn dump(conf: &AppConf, dir: &Path) -> Result<()> {
+    info!("Running pg_dumpall...");
+    let mut cmd = Command::new(conf.pg.install_dir.join("bin/pg_dumpall"));
+    cmd.arg("-h")
+        .arg(&conf.pg.socket_dir_path)
+        .args(["-p", &PG_PORT.to_string()])
+        .args(["-U", PG_SUPER_USER])
+        .arg("-f")
+        .arg(dir.join(DUMP_FILE))
+        .arg("--no-password");
+
+    run("[pg_dumpall]", cmd).context("Failed to back up postgres, is the node running?")
+}
+
+/// Unpacks the data directory captured by pg_basebackup into the supplied directory
+fn restore_base_backup(dir: &Path, data_dir: &Path) -> Result<()> {
+    info!("Unpacking base backup...");
+    fs::create_dir_all(data_dir).context("Failed to create data directory")?;
+    fs::set_permissions(data_dir, Permissions::from_mode(0o700))
+        .context("Failed to set data directory permissions")?;
+
+    let base = File::open(dir.join(BASE_BACKUP_FILE)).context("Failed to open base backup")?;
+    tar::Archive::new(base)
+        .unpack(data_dir)
+        .context("Failed to unpack base backup")?;
+
+    ensure!(
+        data_dir.join("PG_VERSION").exists(),
+        "The base backup does not contain a postgres data directory"
+    );
+
+    Ok(())
+}
+
+/// Initialises a new data directory in the supplied directory and replays the sql dump against it
+fn restore_dump(conf: &'static AppConf, dir: &Path, data_dir: &Path) -> Result<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to create tokio runtime")?;
+
+    let pg: &'static _ = Box::leak(Box::new(PostgresConf {
+        data_dir: data_dir.to_path_buf(),
+        ..conf.pg.clone()
+    }));
+    let postgres = runtime.block_on(PostgresInstance::configure(pg))?;
+
+    info!("Running psql...");
+    let mut cmd = Command::new(pg.install_dir.join("bin/psql"));
+    cmd.arg("-h")
+        .arg(&pg.socket_dir_path)
+        .args(["-p", &PG_PORT.to_string()])
+        .args(["-U", PG_SUPER_USER])
+        .args(["-d", PG_DATABASE])
+        .arg("-q")
+        .arg("-f")
+        .arg(dir.join(DUMP_FILE))
+        .arg("--no-password");
+
+    let res = run("[psql]", cmd).context("Failed to restore sql dump");
+    postgres.terminate()?;
+
+    res
+}
+
+/// Ensures postgres is not running from the data directory of the node
+fn ensure_stopped(conf: &AppConf) -> Result<()> {
+    ensure!(
+        !conf.pg.data_dir.join("postmaster.pid").exists(),
+        "Postgres is running from {}, stop the node before restoring",
+        conf.pg.data_dir.display()
+    );
+
+    Ok(())
+}
+
+/// Runs the command to completion, streaming its output to the logs
+fn run(name: &'static str, cmd: Command) -> Result<()> {
+    let status = ChildProc::new(name, Signal::SIGINT, Duration::from_secs(3), cmd)?.wait()?;
+    ensure!(status.success(), "{} exited with {}", name, status);
+
+    Ok(())
+}
+
+/// Writes the archive containing the postgres backup in `pg_dir`
+/// along with the configuration, build info and logs of the node
+fn write_archive(conf: &AppConf, args: &BackupArgs, pg_dir: &Path, path: &Path) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create archive {}", path.display()))?;
+    let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    // The data directory, encrypted volume and archive are excluded
+    // in case they reside within the config directory
+    let volume = conf
+        .node
+        .encryption
+        .as_ref()
+        .map(|e| EncryptedVolume::for_data_dir(e, &conf.pg.data_dir));
+    let exclude = [conf.pg.data_dir.as_path(), path]
+        .into_iter()
+        .chain(volume.as_ref().map(|v| v.volume_dir()))
+        .filter_map(|p| fs::canonicalize(p).ok())
+        .collect::<Vec<_>>();
+    let config_dir =
+        fs::canonicalize(config_dir(&conf.path)).context("Failed to read config directory")?;
+    append_dir(&mut tar, &config_dir, Path::new(CONFIG_DIR), &exclude)?;
+
+    let build_info = BuildInfo::fetch(conf)?;
+    if let Some(build_info) = build_info.as_ref() {
+        let json = serde_json::to_vec(build_info).context("Failed to serialize build info")?;
+        append_bytes(&mut tar, Path::new(BUILD_INFO_FILE), &json)?;
+    }
+
+    append_dir(&mut tar, pg_dir, Path::new(POSTGRES_DIR), &[])?;
+
+    let logs = log_files(&args.log_file(&conf.node))?;
+    for log in logs.iter() {
+        tar.append_path_with_name(log, Path::new(LOGS_DIR).join(log.file_name().unwrap()))
+            .with_context(|| format!("Failed to archive log file {}", log.display()))?;
+    }
+
+    let manifest = BackupManifest {
+        version: ansilo_version(),
+        created_at: Utc::now(),
+        node: conf.node.name.clone(),
+        config_file: conf
+            .path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .context("Invalid config file path")?
+            .into(),
+        method: args.method,
+        build_info: build_info.is_some(),
+        logs: logs
+            .iter()
+            .filter_map(|l| l.file_name()?.to_str().map(String::from))
+            .collect(),
+    };
+    let json = serde_json::to_vec_pretty(&manifest).context("Failed to serialize manifest")?;
+    append_bytes(&mut tar, Path::new(MANIFEST_FILE), &json)?;
+
+    tar.into_inner()
+        .and_then(|gz| gz.finish())
+        .context("Failed to write archive")?;
+
+    Ok(())
+}
+
+/// Unpacks the archive into the supplied directory
+fn unpack_archive(path: &Path, dir: &Path) -> Result<()> {
+    let file = File::open(path).context("Failed to open archive")?;
+
+    tar::Archive::new(GzDecoder::new(file))
+        .unpack(dir)
+        .context("Failed to unpack archive, is it a valid backup?")
+}
+
+/// Reads the manifest from the unpacked archive
+fn read_manifest(dir: &Path) -> Result<BackupManifest> {
+    let json = fs::read(dir.join(MANIFEST_FILE)).context("Failed to read backup manifest")?;
+
+    serde_json::from_slice(&json).context("Failed to deserialize backup manifest")
+}
+
+/// Ensures the unpacked archive contains everything described by its manifest
+fn validate_archive(dir: &Path, manifest: &BackupManifest, skip_config: bool) -> Result<()> {
+    let pg_file = match manifest.method {
+        BackupMethod::Basebackup => BASE_BACKUP_FILE,
+        BackupMethod::Dump => DUMP_FILE,
+    };
+    ensure!(
+        dir.join(POSTGRES_DIR).join(pg_file).is_file(),
+        "The archive does not contain the postgres backup '{}'",
+        pg_file
+    );
+
+    if manifest.build_info {
+        ensure!(
+            dir.join(BUILD_INFO_FILE).is_file(),
+            "The archive does not contain the build info"
+        );
+    }
+
+    if !skip_config {
+        ensure!(
+            dir.join(CONFIG_DIR).join(&manifest.config_file).is_file(),
+            "The archive does not contain the configuration file '{}'",
+            manifest.config_file
+        );
+    }
+
+    for log in manifest.logs.iter() {
+        // Log names are joined onto the log directory so must not contain a path
+        ensure!(
+            Path::new(log).file_name() == Some(OsStr::new(log)),
+            "Invalid log file name '{}' in backup manifest",
+            log
+        );
+        ensure!(
+            dir.join(LOGS_DIR).join(log).is_file(),
+            "The archive does not contain the log file '{}'",
+            log
+        );
+    }
+
+    Ok(())
+}
+
+/// Appends the file with the supplied contents to the archive
+fn append_bytes<W: std::io::Write>(
+    tar: &mut tar::Builder<W>,
+    name: &Path,
+    data: &[u8],
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(Utc::now().timestamp() as u64);
+    header.set_cksum();
+
+    tar.append_data(&mut header, name, data)
+        .with_context(|| format!("Failed to archive {}", name.display()))
+}
+
+/// Appends the files within the directory to the archive under the supplied
+/// name, skipping any paths within the excluded paths
+fn append_dir<W: std::io::Write>(
+    tar: &mut tar::Builder<W>,
+    dir: &Path,
+    name: &Path,
+    exclude: &[PathBuf],
+) -> Result<()> {
+    tar.append_dir(name, dir)
+        .with_context(|| format!("Failed to archive {}", dir.display()))?;
+
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let entry = entry.context("Failed to read directory entry")?;
+        let path = entry.path();
+
+        if exclude.iter().any(|e| path.starts_with(e)) {
+            continue;
+        }
+
+        let entry_name = name.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            append_dir(tar, &path, &entry_name, exclude)?;
+        } else {
+            tar.append_path_with_name(&path, &entry_name)
+                .with_context(|| format!("Failed to archive {}", path.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies the contents of the directory, overwriting any existing files
+fn copy_dir(src: &Path, dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+
+    for entry in fs::read_dir(src).with_context(|| format!("Failed to read {}", src.display()))? {
+        let entry = entry.context("Failed to read directory entry")?;
+        let dest = dest.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &dest)?;
+        } else {
+            fs::copy(entry.path(), &dest)
+                .with_context(|| format!("Failed to write {}", dest.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the log file along with its rotated files
+fn log_files(log_file: &Path) -> Result<Vec<PathBuf>> {
+    let (dir, name) = match (log_file.parent(), log_file.file_name()) {
+        (Some(dir), Some(name)) => (dir, name.to_string_lossy()),
+        _ => return Ok(vec![]),
+    };
+
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut files = vec![];
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let entry = entry.context("Failed to read directory entry")?;
+
+        if entry.file_type()?.is_file() && entry.file_name().to_string_lossy().starts_with(&*name) {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// Restores the log files alongside the log file of the node.
+///
+/// Existing log files are left untouched so the history of the node is not lost.
+/// Returns the paths of the restored log files.
+fn restore_logs(dir: &Path, logs: &[String], log_file: &Path) -> Result<Vec<PathBuf>> {
+    let dest = match log_file.parent() {
+        Some(dest) if !logs.is_empty() => dest,
+        _ => return Ok(vec![]),
+    };
+    let mut restored = vec![];
+
+    fs::create_dir_all(dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+
+    for log in logs.iter() {
+        let name = match Path::new(log).file_name() {
+            Some(name) if name == OsStr::new(log) => name,
+            _ => bail!("Invalid log file name '{}'", log),
+        };
+        let path = dest.join(name);
+
+        if path.exists() {
+            warn!(
+                "Skipping restore of log file {}, it already exists",
+                path.display()
+            );
+            continue;
+        }
+
+        fs::copy(dir.join(log), &path)
+            .with_context(|| format!("Failed to restore log file {}", path.display()))?;
+        restored.push(path);
+    }
+
+    Ok(restored)
+}
+
+/// A directory prepared alongside the directory it replaces.
+///
+/// Once swapped in it contains the replaced directory.
+/// Either way it is removed when dropped.
+struct StagedDir(PathBuf);
+
+impl StagedDir {
+    /// Creates the staged directory alongside the supplied directory
+    fn new(dir: &Path) -> Result<Self> {
+        let name = dir
+            .file_name()
+            .with_context(|| format!("Invalid directory {}", dir.display()))?;
+        let path = dir.with_file_name(format!(".{}.restore", name.to_string_lossy()));
+
+        // Clear out any leftovers of an earlier failed restore
+        if path.exists() {
+            fs::remove_dir_all(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+        fs::create_dir_all(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+
+        Ok(Self(path))
+    }
+
+    fn path(&self) -> &Path {
+        self.0.as_path()
+    }
+
+    /// Atomically exchanges the staged directory with the supplied directory
+    fn swap(&self, dir: &Path) -> Result<()> {
+        let res = if dir.exists() {
+            renameat2(None, &self.0, None, dir, RenameFlags::RENAME_EXCHANGE)
+                .map_err(std::io::Error::from)
+        } else {
+            fs::rename(&self.0, dir)
+        };
+
+        res.with_context(|| format!("Failed to move {} into place", dir.display()))
+    }
+}
+
+impl Drop for StagedDir {
+    fn drop(&mut self) {
+        if self.0.exists() {
+            if let Err(err) = fs::remove_dir_all(&self.0) {
+                warn!("Failed to remove {}: {:?}", self.0.display(), err);
+            }
+        }
+    }
+}
+
+/// Swaps each staged directory into place. If any fail, the directories which
+/// were already swapped are swapped back so the existing directories are restored.
+fn swap_all(swaps: &[(&str, &StagedDir, &Path)]) -> Result<()> {
+    for (idx, (name, staged, dir)) in swaps.iter().enumerate() {
+        info!("Restoring {} to {}...", name, dir.display());
+
+        if let Err(err) = staged.swap(dir) {
+            for (name, staged, dir) in swaps[..idx].iter().rev() {
+                staged
+                    .swap(dir)
+                    .with_context(|| format!("Failed to revert restore of {name}"))?;
+            }
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+/// An encrypted volume which is unmounted when dropped,
+/// so it is not left mounted if the restore fails
+struct MountedVolume(Option<EncryptedVolume>);
+
+impl MountedVolume {
+    fn unmount(mut self) -> Result<()> {
+        match self.0.take() {
+            Some(volume) => volume.unmount(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for MountedVolume {
+    fn drop(&mut self) {
+        if let Some(volume) = self.0.take() {
+            if let Err(err) = volume.unmount() {
+                warn!("Failed to unmount encrypted volume: {:?}", err);
+            }
+        }
+    }
+}
+
+/// Returns the absolute path of the directory, resolving symlinks if it exists
+fn absolute_dir(dir: &Path) -> Result<PathBuf> {
+    if dir.exists() {
+        fs::canonicalize(dir).with_context(|| format!("Failed to resolve {}", dir.display()))
+    } else if dir.is_absolute() {
+        Ok(dir.to_path_buf())
+    } else {
+        Ok(std::env::current_dir()
+            .context("Failed to get current directory")?
+            .join(dir))
+    }
+}
+
+/// Returns the directory containing the config file
+fn config_dir(config_path: &Path) -> &Path {
+    match config_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Whether the supplied backup location is a url rather than a local path
+fn is_url(location: &str) -> bool {
+    location.contains("://")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join("ansilo-backup-tests").join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_backup_manifest_serde() {
+        let manifest = BackupManifest {
+            version: "1.0.0".into(),
+            created_at: Utc::now(),
+            node: "node".into(),
+            config_file: "ansilo.yml".into(),
+            method: BackupMethod::Basebackup,
+            build_info: true,
+            logs: vec!["ansilo.log".into()],
+        };
+
+        let json = serde_json::to_string(&manifest).unwrap();
+
+        assert!(json.contains(r#""method":"basebackup""#));
+        assert_eq!(
+            serde_json::from_str::<BackupManifest>(&json).unwrap(),
+            manifest
+        );
+    }
+
+    #[test]
+    fn test_backup_archive_dir_excludes_paths() {
+        let dir = test_dir("archive-dir");
+        fs::create_dir_all(dir.join("src/sql")).unwrap();
+        fs::create_dir_all(dir.join("src/data")).unwrap();
+        fs::write(dir.join("src/ansilo.yml"), "name: node").unwrap();
+        fs::write(dir.join("src/sql/1.sql"), "SELECT 1").unwrap();
+        fs::write(dir.join("src/data/PG_VERSION"), "15").unwrap();
+
+        let path = dir.join("archive.tar.gz");
+        let mut tar = tar::Builder::new(GzEncoder::new(
+            File::create(&path).unwrap(),
+            Compression::default(),
+        ));
+        append_dir(
+            &mut tar,
+            &dir.join("src"),
+            Path::new(CONFIG_DIR),
+            &[dir.join("src/data")],
+        )
+        .unwrap();
+        append_bytes(&mut tar, Path::new(MANIFEST_FILE), b"{}").unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        unpack_archive(&path, &dir.join("out")).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("out/config/ansilo.yml")).unwrap(),
+            "name: node"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("out/config/sql/1.sql")).unwrap(),
+            "SELECT 1"
+        );
+        assert!(!dir.join("out/config/data").exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("out/manifest.json")).unwrap(),
+            "{}"
+        );
+    }
+
+    #[test]
+    fn test_backup_copy_dir() {
+        let dir = test_dir("copy-dir");
+        fs::create_dir_all(dir.join("src/sql")).unwrap();
+        fs::write(dir.join("src/ansilo.yml"), "new").unwrap();
+        fs::write(dir.join("src/sql/1.sql"), "SELECT 1").unwrap();
+        fs::create_dir_all(dir.join("dest")).unwrap();
+        fs::write(dir.join("dest/ansilo.yml"), "old").unwrap();
+        fs::write(dir.join("dest/other.yml"), "other").unwrap();
+
+        copy_dir(&dir.join("src"), &dir.join("dest")).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("dest/ansilo.yml")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("dest/sql/1.sql")).unwrap(),
+            "SELECT 1"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("dest/other.yml")).unwrap(),
+            "other"
+        );
+    }
+
+    #[test]
+    fn test_backup_log_files_and_restore() {
+        let dir = test_dir("logs");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/ansilo.log"), "current").unwrap();
+        fs::write(dir.join("src/ansilo.log.1"), "rotated").unwrap();
+        fs::write(dir.join("src/other.log"), "other").unwrap();
+
+        let logs = log_files(&dir.join("src/ansilo.log")).unwrap();
+
+        assert_eq!(
+            logs,
+            vec![dir.join("src/ansilo.log"), dir.join("src/ansilo.log.1")]
+        );
+
+        fs::create_dir_all(dir.join("dest")).unwrap();
+        fs::write(dir.join("dest/ansilo.log"), "existing").unwrap();
+
+        let restored = restore_logs(
+            &dir.join("src"),
+            &["ansilo.log".into(), "ansilo.log.1".into()],
+            &dir.join("dest/ansilo.log"),
+        )
+        .unwrap();
+
+        assert_eq!(restored, vec![dir.join("dest/ansilo.log.1")]);
+
+        assert_eq!(
+            fs::read_to_string(dir.join("dest/ansilo.log")).unwrap(),
+            "existing"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("dest/ansilo.log.1")).unwrap(),
+            "rotated"
+        );
+    }
+
+    #[test]
+    fn test_backup_restore_logs_rejects_paths() {
+        let dir = test_dir("logs-paths");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/ansilo.log"), "current").unwrap();
+
+        for log in ["../ansilo.log", "/etc/passwd", "..", "logs/ansilo.log"] {
+            restore_logs(
+                &dir.join("src"),
+                &[log.into()],
+                &dir.join("dest/ansilo.log"),
+            )
+            .unwrap_err();
+        }
+
+        assert!(!dir.join("ansilo.log").exists());
+    }
+
+    #[test]
+    fn test_backup_validate_archive() {
+        let dir = test_dir("validate");
+        fs::create_dir_all(dir.join("config")).unwrap();
+        fs::create_dir_all(dir.join("postgres")).unwrap();
+        fs::create_dir_all(dir.join("logs")).unwrap();
+        fs::write(dir.join("config/ansilo.yml"), "name: node").unwrap();
+        fs::write(dir.join("logs/ansilo.log"), "log").unwrap();
+
+        let mut manifest = BackupManifest {
+            version: "1.0.0".into(),
+            created_at: Utc::now(),
+            node: "node".into(),
+            config_file: "ansilo.yml".into(),
+            method: BackupMethod::Basebackup,
+            build_info: false,
+            logs: vec!["ansilo.log".into()],
+        };
+
+        // Missing base backup
+        validate_archive(&dir, &manifest, false).unwrap_err();
+
+        fs::write(dir.join("postgres/base.tar"), "").unwrap();
+        validate_archive(&dir, &manifest, false).unwrap();
+
+        manifest.logs = vec!["../../etc/ansilo.log".into()];
+        validate_archive(&dir, &manifest, false).unwrap_err();
+
+        manifest.logs = vec![];
+        manifest.config_file = "other.yml".into();
+        validate_archive(&dir, &manifest, false).unwrap_err();
+        validate_archive(&dir, &manifest, true).unwrap();
+    }
+
+    #[test]
+    fn test_backup_staged_dir_swap() {
+        let dir = test_dir("staged");
+        fs::create_dir_all(dir.join("data")).unwrap();
+        fs::write(dir.join("data/PG_VERSION"), "old").unwrap();
+
+        let staged = StagedDir::new(&dir.join("data")).unwrap();
+        assert_eq!(staged.path(), dir.join(".data.restore"));
+        fs::write(staged.path().join("PG_VERSION"), "new").unwrap();
+
+        staged.swap(&dir.join("data")).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("data/PG_VERSION")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join(".data.restore/PG_VERSION")).unwrap(),
+            "old"
+        );
+
+        drop(staged);
+        assert!(!dir.join(".data.restore").exists());
+
+        // Swapping into a missing directory moves the staged directory into place
+        let staged = StagedDir::new(&dir.join("new")).unwrap();
+        staged.swap(&dir.join("new")).unwrap();
+        drop(staged);
+        assert!(dir.join("new").is_dir());
+    }
+
+    #[test]
+    fn test_backup_config_dir_and_is_url() {
+        assert_eq!(config_dir(Path::new("/app/ansilo.yml")), Path::new("/app"));
+        assert_eq!(config_dir(Path::new("ansilo.yml")), Path::new("."));
+        assert!(is_url("s3://bucket/backup.tar.gz"));
+        assert!(!is_url("/backups/backup.tar.gz"));
+    }
+}
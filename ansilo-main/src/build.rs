@@ -1,5 +1,7 @@
 use std::{
+    collections::hash_map::DefaultHasher,
     fs,
+    hash::{Hash, Hasher},
     path::PathBuf,
     time::{self, UNIX_EPOCH},
 };
@@ -7,21 +9,23 @@ use std::{
 use ansilo_auth::Authenticator;
 use ansilo_core::{
     build::ansilo_version,
-    config::BuildStageMode,
+    config::{BuildStageConfig, BuildStageMode},
     err::{Context, Result},
 };
-use ansilo_logging::info;
+use ansilo_logging::{debug, info};
 use ansilo_pg::{handler::PostgresConnectionHandler, PostgresInstance};
+use ansilo_util_pg::query::pg_str_literal;
 use ansilo_web::VersionInfo;
 use chrono::TimeZone;
 use serde::{Deserialize, Serialize};
 
-use crate::conf::*;
+use crate::{conf::*, grants};
 
 /// Initialises the postgres database
 pub async fn build(
     conf: &'static AppConf,
     auth: Authenticator,
+    tracker: Option<&mut BuildTracker>,
 ) -> Result<(PostgresInstance, BuildInfo)> {
     info!("Building database (mode: buildtime)...");
 
@@ -32,7 +36,9 @@ pub async fn build(
 
     let handler = PostgresConnectionHandler::new(auth, postgres.connections().clone());
 
-    run_build_stages(conf, BuildStageMode::Build, &handler).await?;
+    run_build_stages(conf, BuildStageMode::Build, &handler, tracker).await?;
+
+    grants::apply(&conf.node, &handler).await?;
 
     let build_info = BuildInfo::new();
     build_info.store(conf)?;
@@ -43,22 +49,107 @@ pub async fn build(
 
 /// Runs the runtime build scripts
 pub async fn runtime_build(
-    conf: &'static AppConf,
+    conf: &AppConf,
     handler: &PostgresConnectionHandler,
+    tracker: Option<&mut BuildTracker>,
 ) -> Result<()> {
     info!("Building database (mode: runtime)...");
 
-    run_build_stages(conf, BuildStageMode::Runtime, handler).await?;
+    run_build_stages(conf, BuildStageMode::Runtime, handler, tracker).await?;
+
+    // Runtime build scripts may import further foreign tables
+    grants::apply(&conf.node, handler).await?;
 
     info!("Runtime build complete...");
 
     Ok(())
 }
 
+/// Re-runs the build stages in place following a change in dev mode.
+///
+/// The stages are re-run from the first stage of which the scripts have changed,
+/// or which imported a foreign table of one of the changed `entities`.
+/// The objects created by that stage and the stages following it are dropped
+/// before they are re-run, while the objects of the prior stages are left in place.
+pub async fn rebuild(
+    conf: &AppConf,
+    handler: &PostgresConnectionHandler,
+    tracker: &mut BuildTracker,
+    entities: &[String],
+) -> Result<()> {
+    let stages = [BuildStageMode::Build, BuildStageMode::Runtime]
+        .into_iter()
+        .flat_map(|mode| {
+            conf.node
+                .build
+                .stages
+                .iter()
+                .filter(move |s| s.mode == mode)
+        })
+        .collect::<Vec<_>>();
+
+    let admin_con = handler
+        .pool()
+        .admin()
+        .await
+        .context("Failed to connect to postgres")?;
+
+    let mut from = stages.len().min(tracker.stages.len());
+
+    for (idx, stage) in stages.iter().enumerate().take(from) {
+        if tracker.stages[idx].hash != stage_hash(stage, &stage_scripts(stage)?) {
+            from = idx;
+            break;
+        }
+    }
+
+    if !entities.is_empty() {
+        let tables = admin_con
+            .query(&entity_tables_sql(entities), &[])
+            .await
+            .context("Failed to query foreign tables of entities")?
+            .into_iter()
+            .map(|row| BuildObject::new("FOREIGN TABLE", row.get::<_, String>(0)))
+            .collect::<Vec<_>>();
+
+        if let Some(idx) = tracker.stages[..from]
+            .iter()
+            .position(|s| s.objects.iter().any(|o| tables.contains(o)))
+        {
+            from = idx;
+        }
+    }
+
+    if from == stages.len() && from == tracker.stages.len() {
+        info!("Build stages are up to date");
+    } else {
+        // Drop the objects in reverse order of their creation
+        for stage in tracker.stages.drain(from..).rev() {
+            for object in stage.objects.iter().rev() {
+                debug!("Dropping {} {}", object.kind, object.name);
+                admin_con
+                    .batch_execute(&object.drop_sql())
+                    .await
+                    .with_context(|| format!("Failed to drop {} {}", object.kind, object.name))?;
+            }
+        }
+
+        run_stages(&stages, from, handler, Some(tracker)).await?;
+    }
+
+    // Re-imported foreign tables must be granted to users again
+    grants::apply(&conf.node, handler).await?;
+
+    info!("Rebuild complete...");
+
+    Ok(())
+}
+
 async fn run_build_stages(
     conf: &AppConf,
     mode: BuildStageMode,
     handler: &PostgresConnectionHandler,
+    tracker: Option<&mut BuildTracker>,
 ) -> Result<()> {
     let stages = conf
         .node
@@ -68,7 +159,17 @@ async fn run_build_stages(
         .filter(|s| s.mode == mode)
         .collect::<Vec<_>>();
 
-    if stages.is_empty() {
+    run_stages(&stages, 0, handler, tracker).await
+}
+
+/// Runs the stages starting from the supplied index
+async fn run_stages(
+    stages: &[&BuildStageConfig],
+    from: usize,
+    handler: &PostgresConnectionHandler,
+    mut tracker: Option<&mut BuildTracker>,
+) -> Result<()> {
+    if stages.len() <= from {
         return Ok(());
     }
 
@@ -79,7 +180,7 @@ async fn run_build_stages(
         .await
         .context("Failed to connect to postgres")?;
 
-    Ok(for (idx, stage) in stages.iter().enumerate() {
+    Ok(for (idx, stage) in stages.iter().enumerate().skip(from) {
         info!(
             "Running build stage {}...",
             stage.name.as_ref().unwrap_or(&(idx + 1).to_string())
@@ -101,24 +202,176 @@ async fn run_build_stages(
         // Get a reference to the appropriate connection for this stage
         let con = service_user_con.as_ref().unwrap_or(&admin_con);
 
-        let init_sql_path = stage.sql.clone();
-        info!("Running scripts {}", init_sql_path.display());
+        info!("Running scripts {}", stage.sql.display());
+        let scripts = stage_scripts(stage)?;
 
-        for script in glob::glob(init_sql_path.to_str().context("Invalid init sql path")?)
-            .context("Failed to glob init sql path")?
-        {
-            let script = script.context("Failed to read sql file")?;
+        let before = match tracker {
+            Some(_) => BuildObject::query(&admin_con).await?,
+            None => vec![],
+        };
 
+        for (script, sql) in scripts.iter() {
             info!("Running {}", script.display());
-            let sql = fs::read_to_string(&script)
-                .with_context(|| format!("Failed to read sql file: {}", script.display()))?;
-            con.batch_execute(&sql)
+            con.batch_execute(sql)
                 .await
                 .with_context(|| format!("Failed to execute sql script: {}", script.display()))?;
         }
+
+        if let Some(tracker) = tracker.as_mut() {
+            let objects = BuildObject::query(&admin_con)
+                .await?
+                .into_iter()
+                .filter(|o| !before.contains(o))
+                .collect();
+
+            tracker.stages.push(TrackedStage {
+                hash: stage_hash(stage, &scripts),
+                objects,
+            });
+        }
     })
 }
 
+/// Reads the sql scripts of the stage
+fn stage_scripts(stage: &BuildStageConfig) -> Result<Vec<(PathBuf, String)>> {
+    glob::glob(stage.sql.to_str().context("Invalid init sql path")?)
+        .context("Failed to glob init sql path")?
+        .map(|script| {
+            let script = script.context("Failed to read sql file")?;
+            let sql = fs::read_to_string(&script)
+                .with_context(|| format!("Failed to read sql file: {}", script.display()))?;
+
+            Ok((script, sql))
+        })
+        .collect()
+}
+
+/// Hashes the stage config along with the contents of its scripts
+fn stage_hash(stage: &BuildStageConfig, scripts: &[(PathBuf, String)]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    stage.service_user.hash(&mut hasher);
+    stage.sql.hash(&mut hasher);
+    scripts.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the sql which retrieves the qualified names of the foreign tables of the entities
+fn entity_tables_sql(entities: &[String]) -> String {
+    let entities = entities
+        .iter()
+        .map(|e| pg_str_literal(e))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        r#"
+        SELECT quote_ident(n.nspname) || '.' || quote_ident(c.relname)
+        FROM pg_foreign_table ft
+        JOIN pg_class c ON c.oid = ft.ftrelid
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE EXISTS (
+            SELECT FROM pg_options_to_table(ft.ftoptions) o
+            WHERE o.option_name = 'entity_id'
+            AND o.option_value = ANY(ARRAY[{entities}]::text[])
+        )
+    "#
+    )
+}
+
+/// The schema objects created by each build stage.
+///
+/// These are tracked in dev mode so the stages can be re-run in place
+/// when their scripts or entities change, see [`rebuild`].
+#[derive(Debug, Default)]
+pub struct BuildTracker {
+    /// The stages which have been run, in order
+    stages: Vec<TrackedStage>,
+}
+
+#[derive(Debug)]
+struct TrackedStage {
+    /// The hash of the stage config and its scripts when it was run
+    hash: u64,
+    /// The objects created by the stage
+    objects: Vec<BuildObject>,
+}
+
+/// A schema object created by a build stage
+#[derive(Debug, Clone, PartialEq)]
+struct BuildObject {
+    /// The kind of object, as per its DROP statement, eg "VIEW"
+    kind: String,
+    /// The qualified name of the object
+    name: String,
+}
+
+impl BuildObject {
+    fn new(kind: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            name: name.into(),
+        }
+    }
+
+    /// Retrieves the objects which exist outside of the system schemas
+    async fn query(con: &tokio_postgres::Client) -> Result<Vec<Self>> {
+        Ok(con
+            .query(OBJECTS_SQL, &[])
+            .await
+            .context("Failed to query schema objects")?
+            .into_iter()
+            .map(|row| Self::new(row.get::<_, String>(0), row.get::<_, String>(1)))
+            .collect())
+    }
+
+    fn drop_sql(&self) -> String {
+        format!("DROP {} IF EXISTS {} CASCADE;", self.kind, self.name)
+    }
+}
+
+/// Lists the schemas, relations, routines and types outside of the system schemas
+const OBJECTS_SQL: &str = r#"
+    WITH ns AS (
+        SELECT oid, quote_ident(nspname) AS name
+        FROM pg_namespace
+        WHERE nspname NOT IN ('pg_catalog', 'information_schema')
+        AND nspname NOT LIKE 'pg\_toast%'
+        AND nspname NOT LIKE 'pg\_temp%'
+    )
+    SELECT 'SCHEMA', ns.name FROM ns
+    UNION ALL
+    SELECT
+        CASE c.relkind
+            WHEN 'v' THEN 'VIEW'
+            WHEN 'm' THEN 'MATERIALIZED VIEW'
+            WHEN 'f' THEN 'FOREIGN TABLE'
+            WHEN 'S' THEN 'SEQUENCE'
+            ELSE 'TABLE'
+        END,
+        ns.name || '.' || quote_ident(c.relname)
+    FROM pg_class c
+    JOIN ns ON ns.oid = c.relnamespace
+    WHERE c.relkind IN ('r', 'p', 'v', 'm', 'f', 'S')
+    UNION ALL
+    SELECT
+        CASE p.prokind
+            WHEN 'p' THEN 'PROCEDURE'
+            WHEN 'a' THEN 'AGGREGATE'
+            ELSE 'FUNCTION'
+        END,
+        ns.name || '.' || quote_ident(p.proname) || '(' || pg_get_function_identity_arguments(p.oid) || ')'
+    FROM pg_proc p
+    JOIN ns ON ns.oid = p.pronamespace
+    UNION ALL
+    SELECT
+        CASE t.typtype WHEN 'd' THEN 'DOMAIN' ELSE 'TYPE' END,
+        ns.name || '.' || quote_ident(t.typname)
+    FROM pg_type t
+    JOIN ns ON ns.oid = t.typnamespace
+    LEFT JOIN pg_class c ON c.oid = t.typrelid
+    WHERE t.typtype IN ('d', 'e', 'r') OR c.relkind = 'c'
+"#;
+
 /// Captures information about the build
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BuildInfo {
@@ -184,3 +437,46 @@ impl Into<VersionInfo> for &BuildInfo {
         VersionInfo::new(ansilo_version(), self.built_at())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_stage(sql: &str) -> BuildStageConfig {
+        BuildStageConfig {
+            name: None,
+            service_user: None,
+            sql: sql.into(),
+            mode: BuildStageMode::Build,
+        }
+    }
+
+    #[test]
+    fn test_stage_hash() {
+        let stage = mock_stage("/sql/*.sql");
+        let scripts = vec![("/sql/1.sql".into(), "CREATE VIEW a AS SELECT 1".to_string())];
+        let changed = vec![("/sql/1.sql".into(), "CREATE VIEW a AS SELECT 2".to_string())];
+
+        assert_eq!(stage_hash(&stage, &scripts), stage_hash(&stage, &scripts));
+        assert_ne!(stage_hash(&stage, &scripts), stage_hash(&stage, &changed));
+        assert_ne!(
+            stage_hash(&stage, &scripts),
+            stage_hash(&mock_stage("/other/*.sql"), &scripts)
+        );
+    }
+
+    #[test]
+    fn test_build_object_drop_sql() {
+        assert_eq!(
+            BuildObject::new("FOREIGN TABLE", "public.\"people\"").drop_sql(),
+            "DROP FOREIGN TABLE IF EXISTS public.\"people\" CASCADE;"
+        );
+    }
+
+    #[test]
+    fn test_entity_tables_sql_quotes_entities() {
+        let sql = entity_tables_sql(&["people".into(), "o'brien".into()]);
+
+        assert!(sql.contains("ANY(ARRAY[E'people', E'o''brien']::text[])"));
+    }
+}
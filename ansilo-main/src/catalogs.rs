@@ -0,0 +1,209 @@
+use ansilo_core::config::{CatalogConfig, NodeConfig};
+use ansilo_pg::{conf::PostgresCatalogConf, PG_ADMIN_USER};
+use ansilo_util_pg::query::{pg_quote_identifier, pg_str_literal};
+
+use crate::conf::create_server_sql;
+
+/// Gets the postgres configuration of the databases of each catalog
+pub(crate) fn pg_catalogs(node: &NodeConfig) -> Vec<PostgresCatalogConf> {
+    node.catalogs
+        .iter()
+        .map(|catalog| PostgresCatalogConf {
+            name: catalog.name.clone(),
+            users: users(node, catalog),
+            init_db_sql: init_sql(node, catalog),
+        })
+        .collect()
+}
+
+/// Returns the usernames of the users permitted to connect to the catalog
+pub(crate) fn users(node: &NodeConfig, catalog: &CatalogConfig) -> Vec<String> {
+    node.auth
+        .users
+        .iter()
+        .filter(|u| catalog.applies_to.matches(&u.username, &u.roles))
+        .map(|u| u.username.clone())
+        .collect()
+}
+
+/// Creates the servers of the data sources of the catalog and imports the
+/// foreign tables of their entities into the schema of the catalog.
+///
+/// This is run within the database of the catalog when it is initialised,
+/// catalogs referencing unknown data sources are reported during validation.
+pub(crate) fn init_sql(node: &NodeConfig, catalog: &CatalogConfig) -> Vec<String> {
+    let schema = pg_quote_identifier(catalog.schema());
+    let mut sql = vec![];
+
+    if let Some(description) = catalog.description.as_ref() {
+        sql.push(format!(
+            "COMMENT ON DATABASE {} IS {};",
+            pg_quote_identifier(&catalog.name),
+            pg_str_literal(description)
+        ));
+    }
+
+    if catalog.schema() != "public" {
+        sql.push(format!(
+            "CREATE SCHEMA {schema}; GRANT ALL ON SCHEMA {schema} TO {PG_ADMIN_USER} WITH GRANT OPTION;"
+        ));
+    }
+
+    let sources = node
+        .sources
+        .iter()
+        .filter(|s| catalog.sources.contains(&s.id));
+
+    for source in sources {
+        sql.push(create_server_sql(source));
+
+        let server = pg_quote_identifier(&source.id);
+        let entities = node
+            .entities
+            .iter()
+            .filter(|e| e.source.data_source == source.id)
+            .map(|e| pg_quote_identifier(&e.id));
+
+        for id in entities {
+            sql.push(format!(
+                r#"
+                IMPORT FOREIGN SCHEMA {id} LIMIT TO ({id})
+                FROM SERVER {server}
+                INTO {schema};
+            "#
+            ));
+        }
+    }
+
+    // The users of the catalog can read and write the entities of the catalog
+    let grantees = [PG_ADMIN_USER.to_string()]
+        .into_iter()
+        .chain(users(node, catalog).iter().map(|u| pg_quote_identifier(u)))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    sql.push(format!(
+        r#"
+        GRANT USAGE ON SCHEMA {schema} TO {grantees};
+        GRANT SELECT, INSERT, UPDATE, DELETE ON ALL TABLES IN SCHEMA {schema} TO {grantees};
+    "#
+    ));
+
+    sql
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::config::{
+        AuthConfig, DataSourceConfig, EntityConfig, EntitySourceConfig, PasswordUserConfig,
+        UserConfig, UserMatchConfig, UserTypeOptions, Value,
+    };
+
+    use super::*;
+
+    fn mock_user(username: &str, roles: Vec<&str>) -> UserConfig {
+        UserConfig {
+            username: username.into(),
+            description: None,
+            provider: None,
+            roles: roles.into_iter().map(|r| r.into()).collect(),
+            r#type: UserTypeOptions::Password(PasswordUserConfig {
+                password: "pass".into(),
+            }),
+        }
+    }
+
+    fn mock_source(id: &str) -> DataSourceConfig {
+        DataSourceConfig {
+            id: id.into(),
+            name: None,
+            r#type: "memory".into(),
+            options: Value::Null,
+            metadata_cache_ttl: None,
+            fetch_size: None,
+            transfer_encoding: Default::default(),
+            transfer_compression: Default::default(),
+            prepared_query_cache: None,
+            statistics: None,
+            admission: None,
+        }
+    }
+
+    fn mock_catalog(schema: Option<&str>) -> CatalogConfig {
+        CatalogConfig {
+            name: "sales".into(),
+            description: None,
+            applies_to: UserMatchConfig {
+                users: vec!["bob".into()],
+                roles: vec!["sales".into()],
+            },
+            sources: vec!["crm".into()],
+            schema: schema.map(|s| s.into()),
+        }
+    }
+
+    fn mock_node(catalog: CatalogConfig) -> NodeConfig {
+        NodeConfig {
+            auth: AuthConfig {
+                users: vec![
+                    mock_user("mary", vec!["sales"]),
+                    mock_user("john", vec!["finance"]),
+                    mock_user("bob", vec![]),
+                ],
+                ..AuthConfig::default()
+            },
+            sources: vec![mock_source("crm"), mock_source("ledger")],
+            entities: vec![
+                EntityConfig::minimal("customers", vec![], EntitySourceConfig::minimal("crm")),
+                EntityConfig::minimal("invoices", vec![], EntitySourceConfig::minimal("ledger")),
+            ],
+            catalogs: vec![catalog],
+            ..NodeConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_catalog_users() {
+        let node = mock_node(mock_catalog(None));
+
+        assert_eq!(
+            users(&node, &node.catalogs[0]),
+            vec!["mary".to_string(), "bob".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_catalog_init_sql_imports_entities_of_sources() {
+        let node = mock_node(mock_catalog(None));
+        let sql = init_sql(&node, &node.catalogs[0]).join("\n");
+
+        assert!(sql.contains("CREATE SERVER \"crm\""));
+        assert!(!sql.contains("CREATE SERVER \"ledger\""));
+        assert!(sql.contains("IMPORT FOREIGN SCHEMA \"customers\" LIMIT TO (\"customers\")"));
+        assert!(!sql.contains("\"invoices\""));
+        assert!(!sql.contains("CREATE SCHEMA"));
+        assert!(sql.contains("GRANT USAGE ON SCHEMA \"public\" TO ansiloadmin, \"mary\", \"bob\";"));
+    }
+
+    #[test]
+    fn test_catalog_init_sql_custom_schema() {
+        let node = mock_node(mock_catalog(Some("crm")));
+        let sql = init_sql(&node, &node.catalogs[0]).join("\n");
+
+        assert!(sql.contains("CREATE SCHEMA \"crm\";"));
+        assert!(sql.contains("INTO \"crm\";"));
+    }
+
+    #[test]
+    fn test_pg_catalogs() {
+        let node = mock_node(mock_catalog(None));
+        let catalogs = pg_catalogs(&node);
+
+        assert_eq!(catalogs.len(), 1);
+        assert_eq!(catalogs[0].name, "sales");
+        assert_eq!(
+            catalogs[0].users,
+            vec!["mary".to_string(), "bob".to_string()]
+        );
+    }
+}
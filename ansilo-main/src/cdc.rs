@@ -0,0 +1,445 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use ansilo_connectors_all::Connectors;
+use ansilo_connectors_base::interface::RowChange;
+use ansilo_connectors_native_postgres::{to_pg, to_pg_type};
+use ansilo_core::{
+    config::{
+        DataSourceConfig, EntityAttributeConfig, EntityConfig, EntityMaterializeConfig,
+        MaterializeMode, NodeConfig,
+    },
+    data::DataValue,
+    err::{bail, Context, Error, Result},
+};
+use ansilo_logging::{debug, info, warn};
+use ansilo_pg::handler::PostgresConnectionHandler;
+use ansilo_util_pg::query::pg_quote_identifier;
+use tokio::runtime::Handle;
+use tokio_postgres::types::ToSql;
+
+use crate::materialize::{self, refresh_sql, snapshot_table};
+
+/// The maximum number of changes applied in a single transaction
+const MAX_BATCH_SIZE: usize = 1000;
+
+/// How long to wait before reading the stream again once all changes have been applied
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long to wait before reopening a change stream which failed
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Keeps the snapshots of the entities materialized in cdc mode up to date
+/// by applying the changes captured from their data sources.
+///
+/// Each entity is captured on its own thread. The change stream is opened
+/// before the snapshot is populated with the current rows of the entity,
+/// so no changes are missed, and the changes are then applied as they are read.
+pub struct ChangeCapture {
+    /// Used to apply the changes
+    handler: PostgresConnectionHandler,
+    /// The runtime to apply the changes on
+    runtime: Handle,
+    /// Dropping these senders signals the capture threads to stop
+    stop: Mutex<Vec<Sender<()>>>,
+}
+
+impl ChangeCapture {
+    pub fn new(handler: PostgresConnectionHandler, runtime: Handle) -> Self {
+        Self {
+            handler,
+            runtime,
+            stop: Mutex::new(vec![]),
+        }
+    }
+
+    /// Starts capturing the changes to the entities of the supplied node
+    pub fn start(&self, node: &Arc<NodeConfig>) -> Result<()> {
+        for (entity, conf) in materialize::materialized(node) {
+            if conf.mode != MaterializeMode::Cdc {
+                continue;
+            }
+
+            let source = node
+                .sources
+                .iter()
+                .find(|s| s.id == entity.source.data_source)
+                .with_context(|| format!("Unknown data source '{}'", entity.source.data_source))?;
+
+            self.spawn(
+                Arc::clone(node),
+                source.clone(),
+                entity.clone(),
+                conf.clone(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Restarts capturing using the updated config
+    pub fn reload(&self, node: &Arc<NodeConfig>) -> Result<()> {
+        self.terminate()?;
+        self.start(node)
+    }
+
+    /// Stops capturing the changes to all entities
+    pub fn terminate(&self) -> Result<()> {
+        self.stop
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock change capture threads"))?
+            .clear();
+
+        Ok(())
+    }
+
+    fn spawn(
+        &self,
+        node: Arc<NodeConfig>,
+        source: DataSourceConfig,
+        entity: EntityConfig,
+        conf: EntityMaterializeConfig,
+    ) -> Result<()> {
+        let (tx, rx) = mpsc::channel::<()>();
+        let handler = self.handler.clone();
+        let runtime = self.runtime.clone();
+
+        thread::Builder::new()
+            .name(format!("ansilo-cdc-{}", entity.id))
+            .spawn(move || loop {
+                match Self::capture(&node, &source, &entity, &conf, &handler, &runtime, &rx) {
+                    Ok(()) => break,
+                    Err(err) => warn!(
+                        "Failed to capture changes to entity '{}': {:?}",
+                        entity.id, err
+                    ),
+                }
+
+                match rx.recv_timeout(RETRY_INTERVAL) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            })
+            .context("Failed to spawn change capture thread")?;
+
+        self.stop
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock change capture threads"))?
+            .push(tx);
+
+        Ok(())
+    }
+
+    /// Populates the snapshot of the entity and applies the changes
+    /// read from the change stream until signalled to stop
+    fn capture(
+        node: &NodeConfig,
+        source: &DataSourceConfig,
+        entity: &EntityConfig,
+        conf: &EntityMaterializeConfig,
+        handler: &PostgresConnectionHandler,
+        runtime: &Handle,
+        stop: &Receiver<()>,
+    ) -> Result<()> {
+        let options = Connectors::from_type(&source.r#type)
+            .with_context(|| format!("Unknown data source type '{}'", source.r#type))?
+            .parse_options(source.options.clone())
+            .with_context(|| format!("Failed to parse options of data source '{}'", source.id))?;
+
+        let mut stream = options.open_change_stream(entity, &consumer_id(node, entity))?;
+
+        info!("Materializing entity {}...", entity.id);
+        runtime.block_on(async {
+            let con = handler
+                .pool()
+                .admin()
+                .await
+                .context("Failed to connect to postgres")?;
+
+            con.batch_execute(&refresh_sql(entity, conf)?)
+                .await
+                .with_context(|| format!("Failed to materialize entity '{}'", entity.id))
+        })?;
+
+        info!("Capturing changes to entity {}", entity.id);
+
+        loop {
+            let batch = stream.read(MAX_BATCH_SIZE)?;
+
+            let position = match batch.position {
+                Some(position) => position,
+                None => match stop.recv_timeout(POLL_INTERVAL) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => return Ok(()),
+                },
+            };
+
+            if !batch.changes.is_empty() {
+                debug!(
+                    "Applying {} changes to entity {}",
+                    batch.changes.len(),
+                    entity.id
+                );
+                runtime.block_on(Self::apply(entity, &batch.changes, handler))?;
+            }
+
+            stream.ack(&position)?;
+
+            match stop.try_recv() {
+                Err(TryRecvError::Empty) => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Applies the changes to the snapshot in a single transaction
+    async fn apply(
+        entity: &EntityConfig,
+        changes: &[RowChange],
+        handler: &PostgresConnectionHandler,
+    ) -> Result<()> {
+        let mut con = handler
+            .pool()
+            .admin()
+            .await
+            .context("Failed to connect to postgres")?;
+        let tx = con
+            .transaction()
+            .await
+            .context("Failed to begin transaction")?;
+
+        for change in changes {
+            let (sql, values) = change_sql(entity, change)?;
+            let types = values
+                .iter()
+                .map(|(attr, _)| to_pg_type(&attr.r#type))
+                .collect::<Vec<_>>();
+            let params = values
+                .into_iter()
+                .zip(types.iter())
+                .map(|((_, val), r#type)| to_pg(val, r#type))
+                .collect::<Result<Vec<_>>>()?;
+            let params = params
+                .iter()
+                .map(|p| p.as_ref() as &(dyn ToSql + Sync))
+                .collect::<Vec<_>>();
+
+            let statement = tx
+                .prepare_typed(&sql, &types)
+                .await
+                .context("Failed to prepare change")?;
+            tx.execute(&statement, &params)
+                .await
+                .with_context(|| format!("Failed to apply change to entity '{}'", entity.id))?;
+        }
+
+        tx.commit().await.context("Failed to commit changes")?;
+
+        Ok(())
+    }
+}
+
+impl Drop for ChangeCapture {
+    fn drop(&mut self) {
+        let _ = self.terminate();
+    }
+}
+
+/// Identifies the reader of the entity's changes.
+///
+/// Each member of a cluster stores its own snapshots and so reads
+/// the changes independently.
+fn consumer_id(node: &NodeConfig, entity: &EntityConfig) -> String {
+    let node_id = node.cluster.as_ref().map_or(&node.name, |c| &c.node_id);
+
+    format!("ansilo_{node_id}_{}", entity.id)
+}
+
+/// Returns the sql, along with its parameters, which applies the change to the snapshot
+fn change_sql<'a>(
+    entity: &'a EntityConfig,
+    change: &RowChange,
+) -> Result<(String, Vec<(&'a EntityAttributeConfig, DataValue)>)> {
+    let snapshot = snapshot_table(entity);
+
+    let values = |values: &[(String, DataValue)]| {
+        values
+            .iter()
+            .map(|(id, val)| {
+                let attr = entity
+                    .attributes
+                    .iter()
+                    .find(|a| &a.id == id)
+                    .with_context(|| format!("Unknown attribute '{id}'"))?;
+
+                Ok((attr, val.clone()))
+            })
+            .collect::<Result<Vec<_>>>()
+    };
+
+    Ok(match change {
+        RowChange::Upsert(row) => {
+            let row = values(row)?;
+            let cols = row
+                .iter()
+                .map(|(a, _)| pg_quote_identifier(&a.id))
+                .collect::<Vec<_>>();
+            let params = (1..=row.len()).map(|i| format!("${i}")).collect::<Vec<_>>();
+            let pks = entity
+                .primary_keys()
+                .iter()
+                .map(|a| pg_quote_identifier(&a.id))
+                .collect::<Vec<_>>();
+            let updates = row
+                .iter()
+                .filter(|(a, _)| !a.primary_key)
+                .map(|(a, _)| {
+                    let col = pg_quote_identifier(&a.id);
+                    format!("{col} = EXCLUDED.{col}")
+                })
+                .collect::<Vec<_>>();
+
+            let on_conflict = if updates.is_empty() {
+                "DO NOTHING".into()
+            } else {
+                format!("DO UPDATE SET {}", updates.join(", "))
+            };
+
+            (
+                format!(
+                    "INSERT INTO {snapshot} ({}) VALUES ({}) ON CONFLICT ({}) {on_conflict}",
+                    cols.join(", "),
+                    params.join(", "),
+                    pks.join(", ")
+                ),
+                row,
+            )
+        }
+        RowChange::Delete(key) => {
+            let key = values(key)?;
+
+            if let Some(pk) = entity
+                .primary_keys()
+                .iter()
+                .find(|pk| !key.iter().any(|(a, _)| a.id == pk.id))
+            {
+                bail!("Deleted row is missing primary key '{}'", pk.id);
+            }
+
+            let conditions = key
+                .iter()
+                .enumerate()
+                .map(|(i, (a, _))| format!("{} = ${}", pg_quote_identifier(&a.id), i + 1))
+                .collect::<Vec<_>>();
+
+            (
+                format!("DELETE FROM {snapshot} WHERE {}", conditions.join(" AND ")),
+                key,
+            )
+        }
+        RowChange::Truncate => (format!("DELETE FROM {snapshot}"), vec![]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::{config::EntitySourceConfig, data::DataType};
+
+    use super::*;
+
+    fn mock_entity() -> EntityConfig {
+        let mut entity = EntityConfig::minimal(
+            "orders",
+            vec![
+                EntityAttributeConfig::new("id".into(), None, DataType::Int64, true, false),
+                EntityAttributeConfig::minimal("status", DataType::rust_string()),
+            ],
+            EntitySourceConfig::minimal("postgres"),
+        );
+
+        entity.materialize = Some(EntityMaterializeConfig {
+            refresh: "".into(),
+            mode: MaterializeMode::Cdc,
+            watermark: None,
+        });
+
+        entity
+    }
+
+    fn sql(change: RowChange) -> Result<(String, Vec<DataValue>)> {
+        let entity = mock_entity();
+        let (sql, values) = change_sql(&entity, &change)?;
+
+        Ok((sql, values.into_iter().map(|(_, v)| v).collect()))
+    }
+
+    #[test]
+    fn test_consumer_id() {
+        let node = NodeConfig {
+            name: "sales".into(),
+            ..Default::default()
+        };
+
+        assert_eq!(consumer_id(&node, &mock_entity()), "ansilo_sales_orders");
+    }
+
+    #[test]
+    fn test_change_sql_upsert() {
+        assert_eq!(
+            sql(RowChange::Upsert(vec![
+                ("id".into(), DataValue::Int64(1)),
+                ("status".into(), DataValue::Utf8String("new".into())),
+            ]))
+            .unwrap(),
+            (
+                r#"INSERT INTO ansilo_snapshots."orders" ("id", "status") VALUES ($1, $2) ON CONFLICT ("id") DO UPDATE SET "status" = EXCLUDED."status""#.into(),
+                vec![DataValue::Int64(1), DataValue::Utf8String("new".into())]
+            )
+        );
+    }
+
+    #[test]
+    fn test_change_sql_upsert_key_only() {
+        assert_eq!(
+            sql(RowChange::Upsert(vec![("id".into(), DataValue::Int64(1))]))
+                .unwrap()
+                .0,
+            r#"INSERT INTO ansilo_snapshots."orders" ("id") VALUES ($1) ON CONFLICT ("id") DO NOTHING"#
+        );
+    }
+
+    #[test]
+    fn test_change_sql_upsert_unknown_attribute() {
+        sql(RowChange::Upsert(vec![("email".into(), DataValue::Null)])).unwrap_err();
+    }
+
+    #[test]
+    fn test_change_sql_delete() {
+        assert_eq!(
+            sql(RowChange::Delete(vec![("id".into(), DataValue::Int64(1))])).unwrap(),
+            (
+                r#"DELETE FROM ansilo_snapshots."orders" WHERE "id" = $1"#.into(),
+                vec![DataValue::Int64(1)]
+            )
+        );
+    }
+
+    #[test]
+    fn test_change_sql_delete_without_key() {
+        sql(RowChange::Delete(vec![])).unwrap_err();
+    }
+
+    #[test]
+    fn test_change_sql_truncate() {
+        assert_eq!(
+            sql(RowChange::Truncate).unwrap(),
+            (r#"DELETE FROM ansilo_snapshots."orders""#.into(), vec![])
+        );
+    }
+}
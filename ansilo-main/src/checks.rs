@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+use ansilo_core::err::{ensure, Context, Result};
+use ansilo_util_health::{CheckOutcome, HealthCheck};
+use nix::sys::statvfs::statvfs;
+
+/// Checks the filesystem containing the path has sufficient free space.
+///
+/// The check is degraded when less than twice the minimum is free.
+pub struct DiskSpaceCheck {
+    /// The name of the check
+    name: String,
+    /// A path on the filesystem to check
+    path: PathBuf,
+    /// The minimum ratio of free space, between 0 and 1
+    min_free: f64,
+}
+
+impl DiskSpaceCheck {
+    pub fn new(name: impl Into<String>, path: impl Into<PathBuf>, min_free: f64) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+            min_free,
+        }
+    }
+}
+
+impl HealthCheck for DiskSpaceCheck {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn check(&self) -> Result<CheckOutcome> {
+        let stat = statvfs(&self.path)
+            .with_context(|| format!("Failed to stat filesystem of {}", self.path.display()))?;
+
+        let total = stat.blocks() as f64;
+        let free = stat.blocks_available() as f64;
+        let ratio = if total > 0.0 { free / total } else { 0.0 };
+
+        ensure!(
+            ratio >= self.min_free,
+            "Only {:.1}% of disk space is free on the filesystem of {}",
+            ratio * 100.0,
+            self.path.display()
+        );
+
+        if ratio < self.min_free * 2.0 {
+            return Ok(CheckOutcome::Degraded(format!(
+                "{:.1}% of disk space is free on the filesystem of {}",
+                ratio * 100.0,
+                self.path.display()
+            )));
+        }
+
+        Ok(CheckOutcome::Healthy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_space_check() {
+        assert_eq!(
+            DiskSpaceCheck::new("Disk", "/tmp", 0.0).check().unwrap(),
+            CheckOutcome::Healthy
+        );
+        DiskSpaceCheck::new("Disk", "/tmp", 1.01)
+            .check()
+            .unwrap_err();
+        DiskSpaceCheck::new("Disk", "/this/does/not/exist", 0.0)
+            .check()
+            .unwrap_err();
+    }
+}
@@ -1,26 +1,36 @@
 use std::{
-    env, io,
-    net::{IpAddr, Ipv4Addr},
+    env,
+    io::{self, Read},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     path::{Path, PathBuf},
     process::{Command, Stdio},
+    sync::Arc,
 };
 
-use ansilo_config::loader::ConfigLoader;
+use ansilo_config::{
+    loader::ConfigLoader, processor::encrypted::ConfigKey, validate::ConfigValidator,
+};
+use ansilo_connectors_all::Connectors;
 use ansilo_core::{
-    config::NodeConfig,
-    err::{Context, Result},
+    config::{
+        DataSourceConfig, NodeConfig, TlsClientAuthMode, TransferCompression, TransferEncoding,
+    },
+    err::{ensure, Context, Result},
 };
-use ansilo_logging::{debug, info};
-use ansilo_pg::{conf::PostgresConf, PG_ADMIN_USER};
+use ansilo_jobs::script::NodeAddress;
+use ansilo_logging::{debug, info, warn};
+use ansilo_pg::{conf::PostgresConf, replication::ReplicationConf, PG_ADMIN_USER};
 use ansilo_proxy::conf::{HandlerConf, ProxyConf, TlsConf};
 use ansilo_util_pg::query::{pg_quote_identifier, pg_str_literal};
 
-use crate::args::Args;
+use crate::{args::Args, catalogs, drift, materialize, syncs};
 
 /// Container for the application config
+#[derive(Clone)]
 pub struct AppConf {
-    /// Node configuration from main config file
-    pub node: NodeConfig,
+    /// Node configuration from main config file, shared with the
+    /// subsystems which are reloaded when it changes
+    pub node: Arc<NodeConfig>,
     /// Path to config file
     pub path: PathBuf,
     /// Postgres configuration
@@ -46,23 +56,68 @@ pub fn init_conf(config_path: &Path, args: &Args) -> Result<AppConf> {
         load_dotenv(&path)?;
     }
 
+    // Plugins are loaded after privileges are dropped, so when plugins are
+    // enabled the types of the data sources are checked once they are loaded
+    let validator = match args.plugins_dir {
+        Some(_) => ConfigValidator::new(),
+        None => ConfigValidator::new().with_source_types(Connectors::types()),
+    };
     let node: NodeConfig = config_loader
-        .load(&config_path, args.config_args.iter().cloned().collect())
+        .load_and_validate(
+            &config_path,
+            args.config_args.iter().cloned().collect(),
+            &validator,
+        )
         .context("Failed to load configuration")?;
 
     let pg = pg_conf(&node);
 
     Ok(AppConf {
-        node,
+        node: Arc::new(node),
         path: config_path.into(),
         pg,
     })
 }
 
+/// Loads the connector plugins from the plugins directory, if supplied,
+/// and checks the types of the data sources are supported.
+///
+/// Plugins run with the privileges of the process so this must only
+/// be called once privileges have been dropped.
+pub fn load_plugins(conf: &AppConf, args: &Args) -> Result<()> {
+    let dir = match args.plugins_dir.as_ref() {
+        Some(dir) => dir,
+        None => return Ok(()),
+    };
+
+    debug!("Loading plugins from {}", dir.display());
+
+    for plugin in ansilo_connectors_plugin::load_dir(dir)? {
+        if Connectors::from_type(plugin.r#type()) != Some(Connectors::Plugin(plugin)) {
+            warn!(
+                "Plugin connector '{}' is shadowed by the built-in connector of the same type",
+                plugin.r#type()
+            );
+        }
+    }
+
+    for source in conf.node.sources.iter() {
+        ensure!(
+            Connectors::from_type(&source.r#type).is_some(),
+            "Unknown type '{}' of data source '{}', is the plugin providing it installed?",
+            source.r#type,
+            source.id
+        );
+    }
+
+    Ok(())
+}
+
 /// Dumps the processed configuration to stdout
 pub fn dump_conf(config_path: &Path, args: &Args) -> Result<()> {
     info!("Loading configuration...");
-    let config_loader = ConfigLoader::new();
+    // Secret references are not resolved so they are never printed
+    let config_loader = ConfigLoader::new().redacted();
 
     let processed = config_loader
         .load_as_string(&config_path, args.config_args.iter().cloned().collect())
@@ -72,8 +127,23 @@ pub fn dump_conf(config_path: &Path, args: &Args) -> Result<()> {
     Ok(())
 }
 
+/// Encrypts the secret read from stdin using the config key, printing
+/// the `!encrypted` value to be included in the configuration
+pub fn encrypt_secret() -> Result<()> {
+    let key = ConfigKey::from_env()?;
+
+    let mut secret = String::new();
+    io::stdin()
+        .read_to_string(&mut secret)
+        .context("Failed to read secret from stdin")?;
+    let secret = secret.strip_suffix('\n').unwrap_or(&secret);
+
+    println!("!encrypted {}", key.encrypt(secret)?);
+    Ok(())
+}
+
 /// Gets the postgres configuration for this instance
-fn pg_conf(node: &NodeConfig) -> PostgresConf {
+pub(crate) fn pg_conf(node: &NodeConfig) -> PostgresConf {
     let pg_conf = node.postgres.clone().unwrap_or_default();
 
     PostgresConf {
@@ -107,6 +177,17 @@ fn pg_conf(node: &NodeConfig) -> PostgresConf {
             .collect::<Vec<_>>(),
         //
         init_db_sql: create_db_init_sql(node),
+        //
+        catalogs: catalogs::pg_catalogs(node),
+        //
+        replication: node.ha.as_ref().and_then(|ha| {
+            Some(ReplicationConf {
+                listen_address: ha.this_node()?.host.clone(),
+                peer_host: ha.peer()?.host.clone(),
+                password: ha.replication_password.clone(),
+                application_name: ha.node_id.clone(),
+            })
+        }),
     }
 }
 
@@ -139,21 +220,7 @@ fn create_db_init_sql(node: &NodeConfig) -> Vec<String> {
         //
         node.sources
             .iter()
-            .map(|source| {
-                let name = pg_quote_identifier(&source.id);
-                let id = pg_str_literal(&source.id);
-                format!(
-                    r#"
-                CREATE SERVER {name}
-                FOREIGN DATA WRAPPER ansilo_fdw
-                OPTIONS (
-                    data_source {id}
-                );
-                
-                GRANT ALL ON FOREIGN SERVER {name} TO {PG_ADMIN_USER} WITH GRANT OPTION;
-            "#
-                )
-            })
+            .map(create_server_sql)
             .collect::<Vec<_>>(),
         //
         // Add descriptions of users
@@ -198,6 +265,10 @@ fn create_db_init_sql(node: &NodeConfig) -> Vec<String> {
             )
         ],
         //
+        // Create the table recording the drift of entities from their data sources
+        //
+        vec![drift::init_sql()],
+        //
         // Grant app users read access to the catalog by default
         //
         node.auth.users.iter()
@@ -208,28 +279,118 @@ fn create_db_init_sql(node: &NodeConfig) -> Vec<String> {
                     GRANT SELECT ON ALL TABLES IN SCHEMA ansilo_catalog TO {username};
                 "#)
             })
-            .collect::<Vec<_>>()
+            .collect::<Vec<_>>(),
+        //
+        // Create the snapshot tables of materialized entities
+        //
+        materialize::init_sql(node),
+        //
+        // Import the foreign tables used by the syncs
+        //
+        syncs::init_sql(node),
     ]
     .concat()
 }
 
-/// Initialises the proxy configuration
-pub fn init_proxy_conf(conf: &AppConf, handlers: HandlerConf) -> ProxyConf {
-    let networking = conf.node.networking.clone();
+/// Returns the CREATE SERVER statement for the data source, granting the
+/// admin user access to the server
+pub(crate) fn create_server_sql(source: &DataSourceConfig) -> String {
+    let name = pg_quote_identifier(&source.id);
+    let mut options = vec![format!("data_source {}", pg_str_literal(&source.id))];
 
+    if let Some(fetch_size) = source.fetch_size.as_ref() {
+        if let Some(min_rows) = fetch_size.min_rows {
+            options.push(format!("fetch_min_rows '{min_rows}'"));
+        }
+        if let Some(max_rows) = fetch_size.max_rows {
+            options.push(format!("fetch_max_rows '{max_rows}'"));
+        }
+    }
+
+    if source.transfer_encoding != TransferEncoding::default() {
+        options.push(format!(
+            "transfer_encoding '{}'",
+            source.transfer_encoding.as_str()
+        ));
+    }
+
+    if source.transfer_compression != TransferCompression::default() {
+        options.push(format!(
+            "transfer_compression '{}'",
+            source.transfer_compression.as_str()
+        ));
+    }
+
+    let options = options.join(",\n            ");
+    format!(
+        r#"
+        CREATE SERVER {name}
+        FOREIGN DATA WRAPPER ansilo_fdw
+        OPTIONS (
+            {options}
+        );
+
+        GRANT ALL ON FOREIGN SERVER {name} TO {PG_ADMIN_USER} WITH GRANT OPTION;
+    "#
+    )
+}
+
+/// Initialises the proxy configuration
+pub fn init_proxy_conf(conf: &AppConf, tls: Option<TlsConf>, handlers: HandlerConf) -> ProxyConf {
     ProxyConf {
-        addrs: vec![(
-            networking
-                .bind
-                .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
-            networking.port,
-        )
-            .into()],
-        tls: networking.tls.map(|i| {
-            TlsConf::new(&i.private_key, &i.certificate)
-                .context("Failed to parse TLS configuration options")
-                .unwrap()
-        }),
+        addrs: proxy_addrs(conf),
+        tls,
         handlers,
     }
 }
+
+/// Gets the socket addresses the proxy server listens on
+pub fn proxy_addrs(conf: &AppConf) -> Vec<SocketAddr> {
+    let networking = &conf.node.networking;
+
+    vec![(
+        networking
+            .bind
+            .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
+        networking.port,
+    )
+        .into()]
+}
+
+/// The address which scripts run by jobs use to connect back to the node
+pub fn script_address(conf: &AppConf) -> NodeAddress {
+    let networking = &conf.node.networking;
+
+    // Scripts run on the same host so connect over the loopback
+    // interface unless the node is bound to a specific address
+    let host = match networking.bind {
+        Some(ip) if !ip.is_unspecified() => ip,
+        Some(IpAddr::V6(_)) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        _ => IpAddr::V4(Ipv4Addr::LOCALHOST),
+    };
+
+    NodeAddress {
+        host: host.to_string(),
+        port: networking.port,
+    }
+}
+
+/// Loads the TLS certificate and private key, if configured
+pub fn init_tls_conf(conf: &AppConf) -> Result<Option<TlsConf>> {
+    conf.node
+        .networking
+        .tls
+        .as_ref()
+        .map(|i| match i.client_auth.as_ref() {
+            Some(auth) => TlsConf::with_client_auth(
+                &i.private_key,
+                &i.certificate,
+                &auth.ca_bundle,
+                &auth.allowed_names,
+                auth.mode == TlsClientAuthMode::Required,
+            ),
+            None => TlsConf::new(&i.private_key, &i.certificate),
+        })
+        .map(|tls| tls.context("Failed to parse TLS configuration options"))
+        .transpose()
+}
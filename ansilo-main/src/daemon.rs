@@ -0,0 +1,221 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
+};
+
+use ansilo_core::err::{Context, Result};
+use ansilo_logging::{info, warn};
+use nix::unistd::{dup2, fork, getpid, setsid, ForkResult};
+use once_cell::sync::OnceCell;
+
+use crate::{
+    args::RunArgs,
+    encryption::{self, EncryptionKey},
+};
+
+/// The log file of the daemon, retained so it can be encrypted on shutdown
+static LOG_FILE: OnceCell<File> = OnceCell::new();
+/// The file capturing the output of the daemon until the log file is opened
+static STARTUP_LOG_FILE: OnceCell<PathBuf> = OnceCell::new();
+
+/// Forks the current process into a background daemon.
+///
+/// The parent processes exit once the daemon has been detached from
+/// the controlling terminal. In the daemon process, stdin is redirected
+/// to /dev/null and stdout/stderr (and hence our logs) are redirected
+/// to the startup log. Finally, the pid of the daemon is written to the pid file.
+///
+/// The log file is read from the node config, which may spawn threads to load
+/// so must happen after forking. Until then the output is captured in the startup
+/// log alongside the pid file, which is moved into the log file by [`attach_log_file`].
+///
+/// This must be called before any threads are spawned.
+pub fn daemonize(args: &RunArgs) -> Result<()> {
+    let pid_file = args.pid_file();
+    let startup_log_file = startup_log_path(&pid_file);
+
+    // Open the files before forking so any errors are
+    // reported to the terminal which started the process
+    if let Some(dir) = startup_log_file.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+    }
+    let output = File::create(&startup_log_file).with_context(|| {
+        format!(
+            "Failed to open startup log file {}",
+            startup_log_file.display()
+        )
+    })?;
+    let null = File::open("/dev/null").context("Failed to open /dev/null")?;
+
+    info!(
+        "Starting daemon (pid file: {}, startup log file: {})",
+        pid_file.display(),
+        startup_log_file.display()
+    );
+
+    // SAFETY: we have not spawned any threads at this point
+    if let ForkResult::Parent { .. } = unsafe { fork() }.context("Failed to fork process")? {
+        std::process::exit(0);
+    }
+
+    setsid().context("Failed to create new session")?;
+
+    // Fork again so the daemon is not a session leader and can never
+    // reacquire a controlling terminal
+    if let ForkResult::Parent { .. } = unsafe { fork() }.context("Failed to fork process")? {
+        std::process::exit(0);
+    }
+
+    dup2(null.as_raw_fd(), 0).context("Failed to redirect stdin")?;
+    dup2(output.as_raw_fd(), 1).context("Failed to redirect stdout")?;
+    dup2(output.as_raw_fd(), 2).context("Failed to redirect stderr")?;
+
+    write_pid_file(&pid_file)?;
+    info!("Daemon started with pid {}", getpid());
+
+    let _ = STARTUP_LOG_FILE.set(startup_log_file);
+
+    Ok(())
+}
+
+/// Redirects the output of the daemon to the log file, appending
+/// the output captured in the startup log since it was forked.
+///
+/// If the log file is encrypted it is decrypted using the supplied key so it
+/// can be appended to, and is encrypted again by [`seal_log_file`] on shutdown.
+///
+/// This has no effect if the process was not daemonized.
+pub fn attach_log_file(log_file: &Path, key: Option<&EncryptionKey>) -> Result<()> {
+    let startup_log_file = match STARTUP_LOG_FILE.get() {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+
+    if let Some(dir) = log_file.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create log directory {}", dir.display()))?;
+    }
+    let log = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(log_file)
+        .with_context(|| format!("Failed to open log file {}", log_file.display()))?;
+
+    if encryption::is_sealed(&log)? {
+        let key = key.context("The log file is encrypted but encryption is not configured")?;
+
+        info!("Decrypting log file...");
+        key.unseal_open_file(&log)
+            .with_context(|| format!("Failed to decrypt log file {}", log_file.display()))?;
+    }
+
+    let mut startup = File::open(startup_log_file).with_context(|| {
+        format!(
+            "Failed to open startup log file {}",
+            startup_log_file.display()
+        )
+    })?;
+    io::copy(&mut startup, &mut &log).context("Failed to append startup log to log file")?;
+    dup2(log.as_raw_fd(), 1).context("Failed to redirect stdout")?;
+    dup2(log.as_raw_fd(), 2).context("Failed to redirect stderr")?;
+    info!("Logging to {}", log_file.display());
+
+    if let Err(err) = fs::remove_file(startup_log_file) {
+        warn!(
+            "Failed to remove startup log file {}: {:?}",
+            startup_log_file.display(),
+            err
+        );
+    }
+
+    let _ = LOG_FILE.set(log);
+
+    Ok(())
+}
+
+/// Encrypts the log file of the daemon.
+///
+/// As the log file cannot be appended to once encrypted, any further
+/// output of the process is discarded.
+pub fn seal_log_file(key: &EncryptionKey) -> Result<()> {
+    let log = match LOG_FILE.get() {
+        Some(log) => log,
+        None => return Ok(()),
+    };
+
+    info!("Encrypting log file...");
+    let null = File::options()
+        .write(true)
+        .open("/dev/null")
+        .context("Failed to open /dev/null")?;
+    dup2(null.as_raw_fd(), 1).context("Failed to redirect stdout")?;
+    dup2(null.as_raw_fd(), 2).context("Failed to redirect stderr")?;
+
+    key.seal_open_file(log)
+        .context("Failed to encrypt log file")?;
+
+    Ok(())
+}
+
+/// Removes the pid file written by the daemon
+pub fn remove_pid_file(args: &RunArgs) {
+    let pid_file = args.pid_file();
+
+    if let Err(err) = fs::remove_file(&pid_file) {
+        warn!(
+            "Failed to remove pid file {}: {:?}",
+            pid_file.display(),
+            err
+        );
+    }
+}
+
+/// The startup log is written alongside the pid file
+fn startup_log_path(pid_file: &Path) -> PathBuf {
+    let name = pid_file
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    pid_file.with_file_name(format!("{name}.startup.log"))
+}
+
+fn write_pid_file(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create pid file directory {}", dir.display()))?;
+    }
+
+    fs::write(path, format!("{}\n", getpid()))
+        .with_context(|| format!("Failed to write pid file {}", path.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_startup_log_path() {
+        assert_eq!(
+            startup_log_path(Path::new("/var/run/ansilo/ansilo.pid")),
+            PathBuf::from("/var/run/ansilo/ansilo.pid.startup.log")
+        );
+    }
+
+    #[test]
+    fn test_write_pid_file() {
+        let path = std::env::temp_dir().join("ansilo-main-test/daemon.pid");
+        write_pid_file(&path).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{}\n", std::process::id())
+        );
+    }
+}
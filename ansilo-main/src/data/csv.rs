@@ -0,0 +1,232 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Write},
+    path::Path,
+};
+
+use ansilo_connectors_file_base::{FileStructure, FileWriter};
+use ansilo_core::{
+    data::DataValue,
+    err::{Context, Result},
+};
+
+use super::to_string;
+
+/// Writes rows to a csv file, including a header row of the column names
+pub(super) struct CsvWriter {
+    inner: csv::Writer<File>,
+}
+
+impl CsvWriter {
+    pub(super) fn new(structure: &FileStructure, path: &Path) -> Result<Self> {
+        let mut inner = csv::Writer::from_path(path)
+            .with_context(|| format!("Failed to open file {}", path.display()))?;
+
+        inner
+            .write_record(structure.cols.iter().map(|c| c.name.as_str()))
+            .context("Failed to write csv header")?;
+
+        Ok(Self { inner })
+    }
+}
+
+impl FileWriter for CsvWriter {
+    fn write_row(&mut self, row: Vec<DataValue>) -> Result<()> {
+        let row = row
+            .into_iter()
+            .map(|d| Ok(to_string(d)?.unwrap_or_default()))
+            .collect::<Result<Vec<_>>>()?;
+
+        self.inner
+            .write_record(row)
+            .context("Failed to write csv record")?;
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush().context("Failed to flush csv file")?;
+        Ok(())
+    }
+}
+
+/// Reads a csv file in batches of whole records.
+///
+/// The records are parsed only to find where each batch ends, the raw bytes
+/// of the file are passed to COPY ... FROM STDIN so values are interpreted by
+/// postgres exactly as they are written, eg unquoted empty values as nulls.
+pub(super) struct CsvBatchReader {
+    /// Parses the records of the file
+    records: csv::Reader<File>,
+    /// Reads the raw bytes of the file
+    raw: BufReader<File>,
+    /// The byte offset up to which the file has been read
+    offset: u64,
+    record: csv::ByteRecord,
+}
+
+impl CsvBatchReader {
+    pub(super) fn new(path: &Path) -> Result<Self> {
+        let open =
+            || File::open(path).with_context(|| format!("Failed to open file {}", path.display()));
+
+        Ok(Self {
+            records: csv::Reader::from_reader(open()?),
+            raw: BufReader::new(open()?),
+            offset: 0,
+            record: csv::ByteRecord::new(),
+        })
+    }
+
+    /// Reads the column names from the header row of the csv file
+    pub(super) fn headers(&mut self) -> Result<Vec<String>> {
+        Ok(self
+            .records
+            .headers()
+            .context("Failed to read csv header")?
+            .iter()
+            .map(|h| h.to_string())
+            .collect())
+    }
+
+    /// Reads the raw bytes of up to `batch_size` records, the first batch
+    /// also contains the header row.
+    ///
+    /// Returns None once the end of the file is reached.
+    pub(super) fn read_batch(&mut self, batch_size: usize) -> Result<Option<Vec<u8>>> {
+        let mut rows = 0;
+
+        while rows < batch_size
+            && self
+                .records
+                .read_byte_record(&mut self.record)
+                .context("Failed to read csv record")?
+        {
+            rows += 1;
+        }
+
+        let mut batch = vec![];
+
+        if rows < batch_size {
+            // Reached the end of the file so take any remaining bytes
+            self.raw
+                .read_to_end(&mut batch)
+                .context("Failed to read csv file")?;
+        } else {
+            let end = self.records.position().byte();
+            (&mut self.raw)
+                .take(end - self.offset)
+                .read_to_end(&mut batch)
+                .context("Failed to read csv file")?;
+            self.offset = end;
+        }
+
+        Ok(if batch.is_empty() { None } else { Some(batch) })
+    }
+}
+
+/// Appends the row to the buffer in the csv format expected by COPY ... FROM STDIN.
+///
+/// Nulls are written as unquoted empty values while all other values are quoted
+/// so empty strings are preserved.
+pub(super) fn copy_csv_record(buf: &mut Vec<u8>, row: Vec<DataValue>) -> Result<()> {
+    for (idx, val) in row.into_iter().enumerate() {
+        if idx > 0 {
+            buf.push(b',');
+        }
+
+        if let Some(val) = to_string(val)? {
+            buf.push(b'"');
+            buf.write_all(val.replace('"', "\"\"").as_bytes())?;
+            buf.push(b'"');
+        }
+    }
+
+    buf.push(b'\n');
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_connectors_file_base::FileColumn;
+    use ansilo_core::data::DataType;
+
+    use super::*;
+
+    #[test]
+    fn test_copy_csv_record() {
+        let mut buf = vec![];
+
+        copy_csv_record(
+            &mut buf,
+            vec![
+                DataValue::Int32(1),
+                DataValue::Null,
+                DataValue::Utf8String("".into()),
+                DataValue::Utf8String("a \"quoted\", value".into()),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "\"1\",,\"\",\"a \"\"quoted\"\", value\"\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_writer_and_batch_reader() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.csv");
+        let structure = FileStructure::new(
+            vec![
+                FileColumn::new("id".into(), DataType::Int32, false, None),
+                FileColumn::new("name".into(), DataType::rust_string(), true, None),
+            ],
+            None,
+        );
+
+        let mut writer = CsvWriter::new(&structure, &path).unwrap();
+        writer
+            .write_row(vec![DataValue::Int32(1), DataValue::Utf8String("a".into())])
+            .unwrap();
+        writer
+            .write_row(vec![DataValue::Int32(2), DataValue::Null])
+            .unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "id,name\n1,a\n2,\n"
+        );
+
+        let mut reader = CsvBatchReader::new(&path).unwrap();
+        assert_eq!(
+            reader.headers().unwrap(),
+            vec!["id".to_string(), "name".to_string()]
+        );
+        assert_eq!(
+            reader.read_batch(1).unwrap(),
+            Some(b"id,name\n1,a\n".to_vec())
+        );
+        assert_eq!(reader.read_batch(1).unwrap(), Some(b"2,\n".to_vec()));
+        assert_eq!(reader.read_batch(1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_csv_batch_reader_quoted_newlines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("import.csv");
+        std::fs::write(&path, "id,name\n1,\"multi\nline\"\n2,\"\"\n3,c").unwrap();
+
+        let mut reader = CsvBatchReader::new(&path).unwrap();
+        reader.headers().unwrap();
+
+        assert_eq!(
+            reader.read_batch(2).unwrap(),
+            Some(b"id,name\n1,\"multi\nline\"\n2,\"\"\n".to_vec())
+        );
+        assert_eq!(reader.read_batch(2).unwrap(), Some(b"3,c".to_vec()));
+        assert_eq!(reader.read_batch(2).unwrap(), None);
+    }
+}
@@ -0,0 +1,349 @@
+use std::{path::Path, time::Duration};
+
+use ansilo_connectors_base::interface::ResultSet;
+use ansilo_connectors_file_avro::{AvroConfig, AvroIO};
+use ansilo_connectors_file_base::{FileColumn, FileIO, FileReader, FileStructure, FileWriter};
+use ansilo_connectors_native_postgres::{
+    postgres_connector_runtime, PooledClient, PostgresConnection,
+};
+use ansilo_core::{
+    data::{DataType, DataValue},
+    err::{bail, Context, Result},
+};
+use ansilo_logging::info;
+use ansilo_pg::{connection::PostgresConnectionPool, PG_ADMIN_USER, PG_DATABASE};
+use ansilo_util_pg::query::pg_quote_identifier;
+use clap::ValueEnum;
+use futures_util::SinkExt;
+
+use crate::{
+    args::{ExportArgs, ImportArgs},
+    conf::AppConf,
+};
+
+use self::{
+    csv::{copy_csv_record, CsvBatchReader, CsvWriter},
+    parquet::{ParquetReader, ParquetWriter},
+};
+
+mod csv;
+mod parquet;
+
+/// The file formats supported for exporting and importing data
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum DataFileFormat {
+    Csv,
+    Parquet,
+    Avro,
+}
+
+impl DataFileFormat {
+    /// Infers the file format from the extension of the supplied path
+    pub fn infer(path: &Path) -> Result<Self> {
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+
+        Ok(match ext.as_deref() {
+            Some("csv") => Self::Csv,
+            Some("parquet") => Self::Parquet,
+            Some("avro") => Self::Avro,
+            _ => bail!(
+                "Could not infer file format from path {}, please specify the --format option",
+                path.display()
+            ),
+        })
+    }
+}
+
+/// Runs the supplied query against the running instance and writes
+/// the results to the output file.
+pub fn export(conf: &AppConf, args: &ExportArgs) -> Result<()> {
+    let format = match args.format {
+        Some(f) => f,
+        None => DataFileFormat::infer(&args.output)?,
+    };
+
+    let mut con = connect(conf)?;
+
+    info!("Executing export query...");
+    let results = con
+        .execute(args.query.clone(), vec![])
+        .context("Failed to execute export query")?;
+
+    let structure = FileStructure::new(
+        results
+            .get_structure()?
+            .cols
+            .into_iter()
+            .map(|(name, r#type)| FileColumn::new(name, r#type, true, None))
+            .collect(),
+        None,
+    );
+
+    let mut writer = ExportWriter::new(format, &structure, &args.output)?;
+    let mut reader = results.reader()?;
+    let mut rows = 0u64;
+
+    for row in reader.iter_row_vecs() {
+        writer.write_row(row?)?;
+        rows += 1;
+    }
+
+    writer.finish()?;
+
+    info!("Exported {rows} rows to {}", args.output.display());
+    Ok(())
+}
+
+/// Bulk imports the supplied file into the target table of the running instance.
+///
+/// Rows are streamed to postgres in batches using COPY ... FROM STDIN, when the target is a
+/// foreign table postgres will pass the rows through to the connector's bulk insert path.
+pub fn import(conf: &AppConf, args: &ImportArgs) -> Result<()> {
+    let format = match args.format {
+        Some(f) => f,
+        None => DataFileFormat::infer(&args.input)?,
+    };
+    let batch_size = args.batch_size.max(1);
+
+    let con = connect(conf)?;
+    let table = resolve_table(&con, &args.table)?;
+
+    info!("Importing {} into {}...", args.input.display(), table);
+    let rows = match format {
+        DataFileFormat::Csv => {
+            let mut reader = CsvBatchReader::new(&args.input)?;
+            let cols = reader.headers()?;
+
+            copy_in(&con, &copy_sql(&table, &cols, true), || {
+                reader.read_batch(batch_size)
+            })?
+        }
+        DataFileFormat::Avro => {
+            let avro_conf = avro_conf(&args.input);
+            let structure = AvroIO::get_structure(&avro_conf, &args.input)?;
+            let mut reader = AvroIO::reader(&avro_conf, &structure, &args.input)?;
+            let cols = structure
+                .cols
+                .iter()
+                .map(|c| c.name.clone())
+                .collect::<Vec<_>>();
+
+            copy_in(&con, &copy_sql(&table, &cols, false), || {
+                read_batch(batch_size, || reader.read_row())
+            })?
+        }
+        DataFileFormat::Parquet => {
+            let mut reader = ParquetReader::new(&args.input)?;
+            let cols = reader.cols().to_vec();
+
+            copy_in(&con, &copy_sql(&table, &cols, false), || {
+                read_batch(batch_size, || reader.read_row())
+            })?
+        }
+    };
+
+    info!("Imported {rows} rows into {}", table);
+    Ok(())
+}
+
+/// Streams the batches to postgres using COPY ... FROM STDIN until none remain,
+/// returning the number of copied rows
+fn copy_in(
+    con: &PostgresConnection<PooledClient>,
+    sql: &str,
+    mut next_batch: impl FnMut() -> Result<Option<Vec<u8>>>,
+) -> Result<u64> {
+    postgres_connector_runtime().block_on(async {
+        let client = con.client_async().await;
+        let sink = client.copy_in(sql).await.context("Failed to start COPY")?;
+        futures_util::pin_mut!(sink);
+
+        while let Some(batch) = next_batch()? {
+            sink.send(bytes::Bytes::from(batch)).await?;
+        }
+
+        sink.finish().await.context("Failed to complete COPY")
+    })
+}
+
+/// Reads up to `batch_size` rows, encoded in the csv format expected by COPY ... FROM STDIN.
+///
+/// Returns None once there are no rows remaining.
+fn read_batch(
+    batch_size: usize,
+    mut read_row: impl FnMut() -> Result<Option<Vec<DataValue>>>,
+) -> Result<Option<Vec<u8>>> {
+    let mut batch = vec![];
+    let mut rows = 0;
+
+    while rows < batch_size {
+        match read_row()? {
+            Some(row) => copy_csv_record(&mut batch, row)?,
+            None => break,
+        }
+
+        rows += 1;
+    }
+
+    Ok(if rows > 0 { Some(batch) } else { None })
+}
+
+/// Resolves the supplied table name to its quoted, and qualified if required, form.
+///
+/// The name is parsed by postgres so it follows the usual rules, unquoted identifiers are
+/// folded to lower case while quoted identifiers may contain any character.
+fn resolve_table(con: &PostgresConnection<PooledClient>, table: &str) -> Result<String> {
+    postgres_connector_runtime().block_on(async {
+        let client = con.client_async().await;
+        let row = client
+            .query_one("SELECT $1::text::regclass::text", &[&table])
+            .await
+            .with_context(|| format!("Failed to find table {table}"))?;
+
+        Ok(row.get(0))
+    })
+}
+
+/// Connects to the running instance as the admin user
+fn connect(conf: &AppConf) -> Result<PostgresConnection<PooledClient>> {
+    let pool = PostgresConnectionPool::new(
+        &conf.pg,
+        PG_ADMIN_USER,
+        PG_DATABASE,
+        1,
+        Duration::from_secs(10),
+    )?;
+
+    let client = postgres_connector_runtime()
+        .block_on(pool.acquire())
+        .context("Failed to connect to postgres, is the instance running?")?;
+
+    Ok(PostgresConnection::new(PooledClient(client)))
+}
+
+/// Returns the COPY ... FROM STDIN statement for the supplied table, which must be
+/// quoted already, and column names
+pub(crate) fn copy_sql(table: &str, cols: &[String], header: bool) -> String {
+    let cols = cols
+        .iter()
+        .map(|c| pg_quote_identifier(c))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!("COPY {table} ({cols}) FROM STDIN WITH (FORMAT csv, HEADER {header})")
+}
+
+fn avro_conf(path: &Path) -> AvroConfig {
+    AvroConfig::new(path.parent().unwrap_or(Path::new("/")).to_path_buf())
+}
+
+/// Writes exported rows in the requested file format
+enum ExportWriter {
+    Csv(CsvWriter),
+    Parquet(ParquetWriter),
+    Avro(<AvroIO as FileIO>::Writer),
+}
+
+impl ExportWriter {
+    fn new(format: DataFileFormat, structure: &FileStructure, path: &Path) -> Result<Self> {
+        Ok(match format {
+            DataFileFormat::Csv => Self::Csv(CsvWriter::new(structure, path)?),
+            DataFileFormat::Parquet => Self::Parquet(ParquetWriter::new(structure, path)?),
+            DataFileFormat::Avro => {
+                let conf = avro_conf(path);
+                AvroIO::truncate(&conf, structure, path)?;
+                Self::Avro(AvroIO::writer(&conf, structure, path)?)
+            }
+        })
+    }
+
+    fn write_row(&mut self, row: Vec<DataValue>) -> Result<()> {
+        match self {
+            ExportWriter::Csv(w) => w.write_row(row),
+            ExportWriter::Parquet(w) => w.write_row(row),
+            ExportWriter::Avro(w) => w.write_row(row),
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            ExportWriter::Csv(mut w) => w.flush(),
+            ExportWriter::Parquet(w) => w.close(),
+            ExportWriter::Avro(mut w) => w.flush(),
+        }
+    }
+}
+
+/// Converts the supplied value to its string representation, or None if null
+pub(crate) fn to_string(data: DataValue) -> Result<Option<String>> {
+    Ok(match data {
+        DataValue::Null => None,
+        DataValue::Binary(data) => Some(format!("\\x{}", hex::encode(data))),
+        data => match data.try_coerce_into(&DataType::rust_string())? {
+            DataValue::Utf8String(s) => Some(s),
+            DataValue::Null => None,
+            other => bail!("Unexpected value after coercion to string: {:?}", other),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_file_format_infer() {
+        assert_eq!(
+            DataFileFormat::infer(Path::new("/a/b.csv")).unwrap(),
+            DataFileFormat::Csv
+        );
+        assert_eq!(
+            DataFileFormat::infer(Path::new("b.PARQUET")).unwrap(),
+            DataFileFormat::Parquet
+        );
+        assert_eq!(
+            DataFileFormat::infer(Path::new("b.avro")).unwrap(),
+            DataFileFormat::Avro
+        );
+        DataFileFormat::infer(Path::new("b")).unwrap_err();
+        DataFileFormat::infer(Path::new("b.txt")).unwrap_err();
+    }
+
+    #[test]
+    fn test_copy_sql() {
+        assert_eq!(
+            copy_sql("people", &["id".into(), "name".into()], true),
+            r#"COPY people ("id", "name") FROM STDIN WITH (FORMAT csv, HEADER true)"#
+        );
+        assert_eq!(
+            copy_sql(r#""My.Schema"."Pe""ople""#, &["a.b".into()], false),
+            r#"COPY "My.Schema"."Pe""ople" ("a.b") FROM STDIN WITH (FORMAT csv, HEADER false)"#
+        );
+    }
+
+    #[test]
+    fn test_read_batch() {
+        let mut rows = (1..=5).map(|i| vec![DataValue::Int32(i)]);
+        let mut next = || read_batch(2, || Ok(rows.next()));
+
+        assert_eq!(next().unwrap(), Some(b"\"1\"\n\"2\"\n".to_vec()));
+        assert_eq!(next().unwrap(), Some(b"\"3\"\n\"4\"\n".to_vec()));
+        assert_eq!(next().unwrap(), Some(b"\"5\"\n".to_vec()));
+        assert_eq!(next().unwrap(), None);
+    }
+
+    #[test]
+    fn test_to_string() {
+        assert_eq!(to_string(DataValue::Null).unwrap(), None);
+        assert_eq!(
+            to_string(DataValue::Int32(123)).unwrap(),
+            Some("123".into())
+        );
+        assert_eq!(
+            to_string(DataValue::Binary(vec![0xAB, 0xCD])).unwrap(),
+            Some("\\xabcd".into())
+        );
+    }
+}
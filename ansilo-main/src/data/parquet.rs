@@ -0,0 +1,378 @@
+use std::{fs::File, path::Path, sync::Arc};
+
+use ansilo_connectors_file_base::FileStructure;
+use ansilo_core::{
+    data::{DataType, DataValue},
+    err::{bail, ensure, Context, Result},
+};
+use arrow::{
+    array::{
+        Array, ArrayRef, BinaryArray, BinaryBuilder, BooleanBuilder, Float64Builder, Int64Builder,
+        LargeBinaryArray, StringBuilder, UInt64Builder,
+    },
+    datatypes::{DataType as ArrowType, Field, Schema},
+    record_batch::RecordBatch,
+    util::display::array_value_to_string,
+};
+use parquet::arrow::{
+    arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder},
+    ArrowWriter,
+};
+
+use super::to_string;
+
+/// The number of rows buffered into each parquet row group
+const ROW_GROUP_SIZE: usize = 10_000;
+
+/// Writes rows to a parquet file.
+///
+/// Rows are buffered into arrow column builders and flushed as a
+/// record batch once the row group size is reached.
+pub(super) struct ParquetWriter {
+    inner: ArrowWriter<File>,
+    schema: Arc<Schema>,
+    cols: Vec<ColumnBuilder>,
+    rows: usize,
+}
+
+/// Buffers the values of a single column
+enum ColumnBuilder {
+    Boolean(BooleanBuilder),
+    Int64(Int64Builder),
+    UInt64(UInt64Builder),
+    Float64(Float64Builder),
+    Binary(BinaryBuilder),
+    Utf8(StringBuilder),
+}
+
+impl ParquetWriter {
+    pub(super) fn new(structure: &FileStructure, path: &Path) -> Result<Self> {
+        let schema = Arc::new(Schema::new(
+            structure
+                .cols
+                .iter()
+                .map(|c| Field::new(&c.name, to_arrow_type(&c.r#type), true))
+                .collect(),
+        ));
+
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create file {}", path.display()))?;
+        let inner = ArrowWriter::try_new(file, Arc::clone(&schema), None)
+            .context("Failed to initialise parquet writer")?;
+
+        let mut writer = Self {
+            inner,
+            schema,
+            cols: vec![],
+            rows: 0,
+        };
+        writer.reset();
+
+        Ok(writer)
+    }
+
+    /// Appends the row to the current row group
+    pub(super) fn write_row(&mut self, row: Vec<DataValue>) -> Result<()> {
+        ensure!(
+            row.len() == self.cols.len(),
+            "Unexpected parquet row length"
+        );
+
+        for (col, val) in self.cols.iter_mut().zip(row.into_iter()) {
+            col.append(val)?;
+        }
+
+        self.rows += 1;
+
+        if self.rows >= ROW_GROUP_SIZE {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the buffered rows to the file
+    pub(super) fn flush(&mut self) -> Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+
+        let arrays = self.cols.iter_mut().map(|c| c.finish()).collect::<Vec<_>>();
+
+        let batch = RecordBatch::try_new(Arc::clone(&self.schema), arrays)
+            .context("Failed to create record batch")?;
+
+        self.inner
+            .write(&batch)
+            .context("Failed to write parquet row group")?;
+        self.reset();
+
+        Ok(())
+    }
+
+    /// Flushes any remaining rows and writes the file footer
+    pub(super) fn close(mut self) -> Result<()> {
+        self.flush()?;
+        self.inner
+            .close()
+            .context("Failed to write parquet file footer")?;
+
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.cols = self
+            .schema
+            .fields()
+            .iter()
+            .map(|f| ColumnBuilder::new(f.data_type()))
+            .collect();
+        self.rows = 0;
+    }
+}
+
+impl ColumnBuilder {
+    fn new(r#type: &ArrowType) -> Self {
+        match r#type {
+            ArrowType::Boolean => Self::Boolean(BooleanBuilder::new()),
+            ArrowType::Int64 => Self::Int64(Int64Builder::new()),
+            ArrowType::UInt64 => Self::UInt64(UInt64Builder::new()),
+            ArrowType::Float64 => Self::Float64(Float64Builder::new()),
+            ArrowType::Binary => Self::Binary(BinaryBuilder::new()),
+            _ => Self::Utf8(StringBuilder::new()),
+        }
+    }
+
+    fn append(&mut self, val: DataValue) -> Result<()> {
+        if val.is_null() {
+            match self {
+                Self::Boolean(b) => b.append_null(),
+                Self::Int64(b) => b.append_null(),
+                Self::UInt64(b) => b.append_null(),
+                Self::Float64(b) => b.append_null(),
+                Self::Binary(b) => b.append_null(),
+                Self::Utf8(b) => b.append_null(),
+            }
+
+            return Ok(());
+        }
+
+        match self {
+            Self::Boolean(b) => match val.try_coerce_into(&DataType::Boolean)? {
+                DataValue::Boolean(v) => b.append_value(v),
+                v => bail!("Unexpected value for boolean column: {:?}", v),
+            },
+            Self::Int64(b) => match val.try_coerce_into(&DataType::Int64)? {
+                DataValue::Int64(v) => b.append_value(v),
+                v => bail!("Unexpected value for int64 column: {:?}", v),
+            },
+            Self::UInt64(b) => match val.try_coerce_into(&DataType::UInt64)? {
+                DataValue::UInt64(v) => b.append_value(v),
+                v => bail!("Unexpected value for uint64 column: {:?}", v),
+            },
+            Self::Float64(b) => match val.try_coerce_into(&DataType::Float64)? {
+                DataValue::Float64(v) => b.append_value(v),
+                v => bail!("Unexpected value for float64 column: {:?}", v),
+            },
+            Self::Binary(b) => match val {
+                DataValue::Binary(v) => b.append_value(v),
+                v => bail!("Unexpected value for binary column: {:?}", v),
+            },
+            Self::Utf8(b) => match to_string(val)? {
+                Some(v) => b.append_value(v),
+                None => b.append_null(),
+            },
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::Boolean(b) => Arc::new(b.finish()),
+            Self::Int64(b) => Arc::new(b.finish()),
+            Self::UInt64(b) => Arc::new(b.finish()),
+            Self::Float64(b) => Arc::new(b.finish()),
+            Self::Binary(b) => Arc::new(b.finish()),
+            Self::Utf8(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+/// Reads rows from a parquet file, one record batch at a time
+pub(super) struct ParquetReader {
+    inner: ParquetRecordBatchReader,
+    cols: Vec<String>,
+    batch: Option<RecordBatch>,
+    row: usize,
+}
+
+impl ParquetReader {
+    pub(super) fn new(path: &Path) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open file {}", path.display()))?;
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+            .context("Failed to read parquet file metadata")?;
+
+        let cols = builder
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        let inner = builder
+            .with_batch_size(ROW_GROUP_SIZE)
+            .build()
+            .context("Failed to initialise parquet reader")?;
+
+        Ok(Self {
+            inner,
+            cols,
+            batch: None,
+            row: 0,
+        })
+    }
+
+    /// Gets the column names of the file
+    pub(super) fn cols(&self) -> &[String] {
+        &self.cols
+    }
+
+    /// Reads the next row, returning None once all rows have been read
+    pub(super) fn read_row(&mut self) -> Result<Option<Vec<DataValue>>> {
+        loop {
+            if let Some(batch) = self.batch.as_ref() {
+                if self.row < batch.num_rows() {
+                    let row = batch
+                        .columns()
+                        .iter()
+                        .map(|c| from_arrow_value(c, self.row))
+                        .collect::<Result<Vec<_>>>()?;
+                    self.row += 1;
+
+                    return Ok(Some(row));
+                }
+            }
+
+            match self.inner.next() {
+                Some(batch) => {
+                    self.batch = Some(batch.context("Failed to read parquet record batch")?);
+                    self.row = 0;
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Reads the value of the column at the supplied row.
+/// Values other than binary are read as their string representation.
+fn from_arrow_value(col: &ArrayRef, row: usize) -> Result<DataValue> {
+    if col.is_null(row) {
+        return Ok(DataValue::Null);
+    }
+
+    Ok(match col.data_type() {
+        ArrowType::Binary => DataValue::Binary(
+            col.as_any()
+                .downcast_ref::<BinaryArray>()
+                .context("Unexpected binary array type")?
+                .value(row)
+                .to_vec(),
+        ),
+        ArrowType::LargeBinary => DataValue::Binary(
+            col.as_any()
+                .downcast_ref::<LargeBinaryArray>()
+                .context("Unexpected binary array type")?
+                .value(row)
+                .to_vec(),
+        ),
+        _ => DataValue::Utf8String(
+            array_value_to_string(col, row).context("Failed to read parquet value")?,
+        ),
+    })
+}
+
+/// Maps our data types to the closest arrow type.
+/// Types without a natural equivalent are written as strings.
+fn to_arrow_type(r#type: &DataType) -> ArrowType {
+    match r#type {
+        DataType::Boolean => ArrowType::Boolean,
+        DataType::Int8
+        | DataType::UInt8
+        | DataType::Int16
+        | DataType::UInt16
+        | DataType::Int32
+        | DataType::UInt32
+        | DataType::Int64 => ArrowType::Int64,
+        DataType::UInt64 => ArrowType::UInt64,
+        DataType::Float32 | DataType::Float64 => ArrowType::Float64,
+        DataType::Binary => ArrowType::Binary,
+        _ => ArrowType::Utf8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_connectors_file_base::FileColumn;
+
+    use super::*;
+
+    #[test]
+    fn test_to_arrow_type() {
+        assert_eq!(to_arrow_type(&DataType::Int16), ArrowType::Int64);
+        assert_eq!(to_arrow_type(&DataType::Float32), ArrowType::Float64);
+        assert_eq!(to_arrow_type(&DataType::rust_string()), ArrowType::Utf8);
+        assert_eq!(to_arrow_type(&DataType::Uuid), ArrowType::Utf8);
+    }
+
+    #[test]
+    fn test_parquet_writer_and_reader() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("import.parquet");
+        let structure = FileStructure::new(
+            vec![
+                FileColumn::new("id".into(), DataType::Int32, false, None),
+                FileColumn::new("name".into(), DataType::rust_string(), true, None),
+                FileColumn::new("data".into(), DataType::Binary, true, None),
+            ],
+            None,
+        );
+
+        let mut writer = ParquetWriter::new(&structure, &path).unwrap();
+        writer
+            .write_row(vec![
+                DataValue::Int32(1),
+                DataValue::Utf8String("a".into()),
+                DataValue::Binary(vec![1, 2]),
+            ])
+            .unwrap();
+        writer
+            .write_row(vec![DataValue::Int32(2), DataValue::Null, DataValue::Null])
+            .unwrap();
+        writer.close().unwrap();
+
+        let mut reader = ParquetReader::new(&path).unwrap();
+        assert_eq!(
+            reader.cols(),
+            &["id".to_string(), "name".to_string(), "data".to_string()]
+        );
+        assert_eq!(
+            reader.read_row().unwrap(),
+            Some(vec![
+                DataValue::Utf8String("1".into()),
+                DataValue::Utf8String("a".into()),
+                DataValue::Binary(vec![1, 2]),
+            ])
+        );
+        assert_eq!(
+            reader.read_row().unwrap(),
+            Some(vec![
+                DataValue::Utf8String("2".into()),
+                DataValue::Null,
+                DataValue::Null,
+            ])
+        );
+        assert_eq!(reader.read_row().unwrap(), None);
+    }
+}
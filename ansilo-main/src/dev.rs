@@ -1,11 +1,11 @@
 use std::{
     ffi::CString,
-    path::Path,
+    path::{Path, PathBuf},
     sync::mpsc::{self, channel},
     time::Duration,
 };
 
-use ansilo_core::err::Context;
+use ansilo_core::{config::NodeConfig, err::Context};
 use ansilo_logging::{info, trace, warn};
 use nix::sys::signal;
 use notify::{watcher, RecursiveMode, Watcher};
@@ -13,7 +13,11 @@ use notify::{watcher, RecursiveMode, Watcher};
 use crate::conf::AppConf;
 
 /// We support a fast-reload mode for development using `ansilo dev`.
-/// We will trigger a term signal when configuration files are updated.
+/// We will trigger a hang-up signal when configuration files are updated,
+/// upon which the changes are applied in place or the process is restarted.
+///
+/// The paths in `dev.watch` are only known once the config is loaded,
+/// these are watched alongside the sql files by [`signal_on_sql_update`].
 pub fn signal_on_config_update(path: &Path) {
     let (tx, rx) = channel();
 
@@ -34,30 +38,44 @@ pub fn signal_on_sql_update(conf: &AppConf) {
 
     let mut watcher = watcher(tx, Duration::from_secs(10)).unwrap();
 
-    // Watch for changes on sql files
-    for stage in conf.node.build.stages.iter() {
-        // Watch on the parent dir to enable new files when using glob "/a/b/c/*.sql" etc
-        let mut path = stage.sql.as_path();
-        while path.file_name().is_some()
-            && path.file_name().unwrap().to_string_lossy().contains("*")
-        {
-            path = if let Some(p) = path.parent() {
-                p
-            } else {
-                break;
-            };
-        }
-
+    // Watch for changes on sql files and the additional watched paths
+    for path in watched_paths(&conf.node) {
         trace!("Watching on changes for {}", path.display());
         watcher
-            .watch(path, RecursiveMode::Recursive)
-            .context(path.clone().to_string_lossy().to_string())
+            .watch(&path, RecursiveMode::Recursive)
+            .context(path.to_string_lossy().to_string())
             .unwrap();
     }
 
     terminate_on_event(rx)
 }
 
+/// Gets the paths which are watched for changes, being the sql files
+/// of the build stages and any additional paths listed in `dev.watch`
+pub fn watched_paths(node: &NodeConfig) -> Vec<PathBuf> {
+    node.build
+        .stages
+        .iter()
+        .map(|stage| stage.sql.as_path())
+        .chain(node.dev.watch.iter().map(|p| p.as_path()))
+        .map(|path| {
+            // Watch on the parent dir to enable new files when using glob "/a/b/c/*.sql" etc
+            let mut path = path;
+            while path.file_name().is_some()
+                && path.file_name().unwrap().to_string_lossy().contains("*")
+            {
+                path = if let Some(p) = path.parent() {
+                    p
+                } else {
+                    break;
+                };
+            }
+
+            path.to_path_buf()
+        })
+        .collect()
+}
+
 fn terminate_on_event(rx: mpsc::Receiver<notify::DebouncedEvent>) -> ! {
     loop {
         match rx.recv() {
@@ -93,3 +111,38 @@ pub fn restart() {
     )
     .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::config::{BuildConfig, BuildStageConfig, DevConfig};
+
+    use super::*;
+
+    #[test]
+    fn test_watched_paths() {
+        let node = NodeConfig {
+            build: BuildConfig {
+                stages: vec![BuildStageConfig {
+                    name: None,
+                    service_user: None,
+                    sql: "/app/sql/*.sql".into(),
+                    mode: Default::default(),
+                }],
+            },
+            dev: DevConfig {
+                watch: vec!["/app/config/*.yml".into(), "/app/entities".into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(
+            watched_paths(&node),
+            vec![
+                PathBuf::from("/app/sql"),
+                PathBuf::from("/app/config"),
+                PathBuf::from("/app/entities")
+            ]
+        );
+    }
+}
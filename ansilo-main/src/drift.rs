@@ -0,0 +1,963 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex, RwLock,
+    },
+    thread,
+};
+
+use ansilo_connectors_all::{ConnectionPools, Connectors};
+use ansilo_core::{
+    config::{EntityConfig, EntityDriftAction, NodeConfig},
+    data::DataType,
+    err::{Context, Error, Result},
+};
+use ansilo_logging::{debug, info, warn};
+use ansilo_pg::{handler::PostgresConnectionHandler, types::to_pg_type_name, PG_ADMIN_USER};
+use ansilo_util_health::Health;
+use ansilo_util_pg::query::{pg_quote_identifier, pg_str_literal};
+use tokio::runtime::Handle;
+
+/// The table recording the drift of each entity
+const DRIFT_TABLE: &str = "ansilo_catalog.entity_drift";
+
+/// The table recording the changes applied to regenerated entities
+const AUDIT_TABLE: &str = "ansilo_catalog.entity_drift_audit";
+
+/// The pools of each data source, keyed by the data source id
+type SharedPools = Arc<RwLock<HashMap<String, ConnectionPools>>>;
+
+/// Applies the node config containing the regenerated entities
+/// of the supplied data source to the running instance
+pub type RegenerateHandler = Arc<dyn Fn(&Arc<NodeConfig>, &str) -> Result<()> + Send + Sync>;
+
+/// The kind of difference between an entity and its schema in the data source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriftKind {
+    /// The attribute exists in the data source but is not configured
+    Added,
+    /// The configured attribute no longer exists in the data source
+    Removed,
+    /// The type of the attribute in the data source differs from the configured type
+    Retyped,
+    /// The entity no longer exists in the data source
+    Missing,
+}
+
+impl DriftKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DriftKind::Added => "added",
+            DriftKind::Removed => "removed",
+            DriftKind::Retyped => "retyped",
+            DriftKind::Missing => "missing",
+        }
+    }
+}
+
+/// A difference between the configured entity and its schema in the data source
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityDrift {
+    pub kind: DriftKind,
+    /// The id of the attribute, if the drift is of a single attribute
+    pub attribute: Option<String>,
+    /// The configured type of the attribute
+    pub expected: Option<DataType>,
+    /// The type of the attribute in the data source
+    pub actual: Option<DataType>,
+}
+
+impl EntityDrift {
+    /// Describes the drift for the audit record, eg `retyped id Int32 -> Int64`
+    fn describe(&self) -> String {
+        let mut desc = self.kind.as_str().to_string();
+
+        if let Some(attribute) = self.attribute.as_ref() {
+            desc.push_str(&format!(" {attribute}"));
+        }
+
+        match (self.expected.as_ref(), self.actual.as_ref()) {
+            (Some(expected), Some(actual)) => desc.push_str(&format!(" {expected} -> {actual}")),
+            (None, Some(actual)) => desc.push_str(&format!(" {actual}")),
+            _ => {}
+        }
+
+        desc
+    }
+}
+
+/// The result of checking an entity against its data source
+struct EntityCheck<'a> {
+    entity: &'a EntityConfig,
+    /// The entity as described by the data source, if it still exists
+    remote: Option<EntityConfig>,
+    drift: Vec<EntityDrift>,
+}
+
+/// Periodically re-validates each entity against its data source and records
+/// the attributes which have been added, removed or retyped in the data source
+/// since the entity was configured.
+///
+/// The drift found by the latest check is stored in the `ansilo_catalog.entity_drift`
+/// table and the instance is reported as degraded while any entity has drifted.
+///
+/// Entities configured with `on_drift: regenerate` are updated in place when the
+/// drift is compatible, see [`is_compatible`]. Their foreign tables are altered to
+/// match and the applied changes are recorded in `ansilo_catalog.entity_drift_audit`.
+pub struct DriftDetector {
+    /// Used to record the drift
+    handler: PostgresConnectionHandler,
+    /// The runtime to query postgres on
+    runtime: Handle,
+    /// The current pool of each data source
+    pools: SharedPools,
+    /// The health state to update
+    health: Health,
+    /// Applies regenerated entities to the running instance
+    regenerate: Option<RegenerateHandler>,
+    /// Dropping these senders signals the detection thread to stop
+    stop: Mutex<Vec<Sender<()>>>,
+}
+
+impl DriftDetector {
+    /// The name of the drift detection in the health state
+    pub const SUBSYSTEM: &'static str = "Schema Drift";
+
+    pub fn new(
+        handler: PostgresConnectionHandler,
+        runtime: Handle,
+        pools: HashMap<String, ConnectionPools>,
+        health: Health,
+    ) -> Self {
+        Self {
+            handler,
+            runtime,
+            pools: Arc::new(RwLock::new(pools)),
+            health,
+            regenerate: None,
+            stop: Mutex::new(vec![]),
+        }
+    }
+
+    /// Sets the handler which applies regenerated entities to the running instance.
+    ///
+    /// Without a handler entities are never regenerated and their drift is only reported.
+    pub fn with_regenerate_handler(mut self, handler: RegenerateHandler) -> Self {
+        self.regenerate = Some(handler);
+        self
+    }
+
+    /// Starts checking the entities of the supplied node for drift,
+    /// if drift detection is enabled
+    pub fn start(&self, node: &Arc<NodeConfig>) -> Result<()> {
+        let conf = match node.drift.as_ref() {
+            Some(conf) => conf,
+            None => return self.health.remove(Self::SUBSYSTEM),
+        };
+
+        let (tx, rx) = mpsc::channel::<()>();
+        let handler = self.handler.clone();
+        let runtime = self.runtime.clone();
+        let pools = Arc::clone(&self.pools);
+        let health = self.health.clone();
+        let regenerate = self.regenerate.clone();
+        let interval = conf.interval();
+        let node = Arc::clone(node);
+
+        thread::Builder::new()
+            .name("ansilo-drift".into())
+            .spawn(move || {
+                // Updated as entities are regenerated so their drift is not re-applied
+                let mut node = node;
+
+                loop {
+                    if let Err(err) = Self::run(
+                        &mut node,
+                        &pools,
+                        &handler,
+                        &runtime,
+                        &health,
+                        regenerate.as_ref(),
+                    ) {
+                        warn!("Failed to check entities for drift: {:?}", err);
+                    }
+
+                    match rx.recv_timeout(interval) {
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        _ => break,
+                    }
+                }
+            })
+            .context("Failed to spawn drift detection thread")?;
+
+        self.stop
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock drift detection threads"))?
+            .push(tx);
+
+        Ok(())
+    }
+
+    /// Restarts drift detection using the updated config
+    pub fn reload(&self, node: &Arc<NodeConfig>) -> Result<()> {
+        self.terminate()?;
+        self.start(node)
+    }
+
+    /// Replaces the pool used to check the entities of the data source
+    pub fn replace_pool(&self, data_source_id: &str, pool: ConnectionPools) -> Result<()> {
+        self.pools
+            .write()
+            .map_err(|_| Error::msg("Failed to lock drift detection pools"))?
+            .insert(data_source_id.into(), pool);
+
+        Ok(())
+    }
+
+    /// Stops drift detection
+    pub fn terminate(&self) -> Result<()> {
+        self.stop
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock drift detection threads"))?
+            .clear();
+
+        Ok(())
+    }
+
+    /// Checks each entity for drift, regenerating the entities configured to do so,
+    /// recording the remaining drift and updating the health state
+    fn run(
+        node: &mut Arc<NodeConfig>,
+        pools: &SharedPools,
+        handler: &PostgresConnectionHandler,
+        runtime: &Handle,
+        health: &Health,
+        regenerate: Option<&RegenerateHandler>,
+    ) -> Result<()> {
+        let checked = Arc::clone(node);
+        let mut results = Self::check(&checked, pools)?;
+
+        if let Some(regenerate) = regenerate {
+            for check in results.iter_mut() {
+                let remote = match check.remote.as_ref() {
+                    Some(remote)
+                        if check.entity.on_drift == EntityDriftAction::Regenerate
+                            && is_compatible(&check.drift) =>
+                    {
+                        remote
+                    }
+                    _ => continue,
+                };
+
+                match Self::regenerate(node, check, remote, handler, runtime, regenerate) {
+                    Ok(updated) => {
+                        *node = updated;
+                        check.drift.clear();
+                    }
+                    Err(err) => warn!(
+                        "Failed to regenerate entity '{}': {:?}",
+                        check.entity.id, err
+                    ),
+                }
+            }
+        }
+
+        let sql = record_sql(node.as_ref(), &results);
+        runtime
+            .block_on(async {
+                let con = handler.pool().admin().await?;
+                con.batch_execute(&sql).await?;
+                Result::<()>::Ok(())
+            })
+            .context("Failed to record entity drift")?;
+
+        let drifted = results
+            .iter()
+            .filter(|check| !check.drift.is_empty())
+            .map(|check| check.entity.id.as_str())
+            .collect::<Vec<_>>();
+
+        if drifted.is_empty() {
+            health.update(Self::SUBSYSTEM, true)?;
+        } else {
+            health.update_degraded(
+                Self::SUBSYSTEM,
+                format!(
+                    "Entities have drifted from their data source: {}",
+                    drifted.join(", ")
+                ),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Compares each entity against its schema in the data source.
+    ///
+    /// Entities which could not be checked, such as those of an unreachable
+    /// data source, are excluded from the results.
+    fn check<'a>(node: &'a NodeConfig, pools: &SharedPools) -> Result<Vec<EntityCheck<'a>>> {
+        let pools = pools
+            .read()
+            .map_err(|_| Error::msg("Failed to lock drift detection pools"))?
+            .clone();
+        let mut results = vec![];
+
+        for entity in node.entities.iter() {
+            match Self::check_entity(node, &pools, entity) {
+                Ok(Some(remote)) => {
+                    let drift = compare(entity, remote.as_ref());
+
+                    for d in drift.iter() {
+                        info!(
+                            "Entity '{}' has drifted from its data source: {} {}",
+                            entity.id,
+                            d.attribute.as_deref().unwrap_or("entity"),
+                            d.kind.as_str()
+                        );
+                    }
+
+                    results.push(EntityCheck {
+                        entity,
+                        remote,
+                        drift,
+                    })
+                }
+                Ok(None) => {}
+                Err(err) => warn!(
+                    "Failed to check entity '{}' for drift: {:?}",
+                    entity.id, err
+                ),
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Returns the entity as described by its data source, or None if it cannot be checked
+    fn check_entity(
+        node: &NodeConfig,
+        pools: &HashMap<String, ConnectionPools>,
+        entity: &EntityConfig,
+    ) -> Result<Option<Option<EntityConfig>>> {
+        let data_source_id = &entity.source.data_source;
+        let mut pool = match pools.get(data_source_id) {
+            Some(pool) if pool.supports_ping() => pool.clone(),
+            _ => return Ok(None),
+        };
+        let source = node
+            .sources
+            .iter()
+            .find(|s| &s.id == data_source_id)
+            .with_context(|| format!("Unknown data source '{data_source_id}'"))?;
+        let connector = Connectors::from_type(&source.r#type)
+            .with_context(|| format!("Unknown connector type: {}", source.r#type))?;
+
+        debug!("Checking entity '{}' for drift", entity.id);
+        let remote = connector.describe_entity(&mut pool, node, entity)?;
+
+        Ok(Some(remote))
+    }
+
+    /// Applies the drift of the entity to its config and foreign tables, returning
+    /// the node config containing the regenerated entity
+    fn regenerate(
+        node: &NodeConfig,
+        check: &EntityCheck,
+        remote: &EntityConfig,
+        handler: &PostgresConnectionHandler,
+        runtime: &Handle,
+        on_regenerate: &RegenerateHandler,
+    ) -> Result<Arc<NodeConfig>> {
+        let entity = regenerate_entity(check.entity, remote);
+        let data_source_id = &entity.source.data_source;
+
+        info!(
+            "Regenerating entity '{}' from its data source: {}",
+            entity.id,
+            describe(&check.drift)
+        );
+
+        // The foreign tables are altered in place, rather than re-imported,
+        // so any grants and dependent objects are retained
+        runtime.block_on(async {
+            let mut cons = vec![handler.pool().admin().await?];
+
+            for catalog in node
+                .catalogs
+                .iter()
+                .filter(|c| c.sources.contains(data_source_id))
+            {
+                cons.push(handler.pool().catalog_admin(&catalog.name).await?);
+            }
+
+            for con in cons.iter() {
+                let tables = con
+                    .query(&entity_tables_sql(&entity), &[])
+                    .await
+                    .context("Failed to query foreign tables of entity")?;
+
+                for row in tables.iter() {
+                    let sql = alter_sql(row.get(0), row.get(1), &entity, &check.drift)?;
+                    con.batch_execute(&sql).await.with_context(|| {
+                        format!("Failed to alter foreign table {}", row.get::<_, String>(0))
+                    })?;
+                }
+            }
+
+            Result::<()>::Ok(())
+        })?;
+
+        let mut updated = node.clone();
+        updated.entities = node
+            .entities
+            .iter()
+            .map(|e| {
+                if e.id == entity.id {
+                    entity.clone()
+                } else {
+                    e.clone()
+                }
+            })
+            .collect();
+        let updated = Arc::new(updated);
+
+        on_regenerate(&updated, data_source_id)?;
+
+        // The entity has been applied so a failure to audit must not cause it to be re-applied
+        let sql = audit_sql(&entity, &check.drift)?;
+        if let Err(err) = runtime.block_on(async {
+            let con = handler.pool().admin().await?;
+            con.batch_execute(&sql).await?;
+            Result::<()>::Ok(())
+        }) {
+            warn!(
+                "Failed to record regenerated entity '{}': {:?}",
+                entity.id, err
+            );
+        }
+
+        Ok(updated)
+    }
+}
+
+impl Drop for DriftDetector {
+    fn drop(&mut self) {
+        let _ = self.terminate();
+    }
+}
+
+/// Compares the configured entity to its schema in the data source
+pub(crate) fn compare(entity: &EntityConfig, remote: Option<&EntityConfig>) -> Vec<EntityDrift> {
+    let remote = match remote {
+        Some(remote) => remote,
+        None => {
+            return vec![EntityDrift {
+                kind: DriftKind::Missing,
+                attribute: None,
+                expected: None,
+                actual: None,
+            }]
+        }
+    };
+
+    let mut drift = vec![];
+
+    for attr in entity.attributes.iter() {
+        match remote.attributes.iter().find(|a| a.id == attr.id) {
+            Some(actual) if actual.r#type != attr.r#type => drift.push(EntityDrift {
+                kind: DriftKind::Retyped,
+                attribute: Some(attr.id.clone()),
+                expected: Some(attr.r#type.clone()),
+                actual: Some(actual.r#type.clone()),
+            }),
+            Some(_) => {}
+            None => drift.push(EntityDrift {
+                kind: DriftKind::Removed,
+                attribute: Some(attr.id.clone()),
+                expected: Some(attr.r#type.clone()),
+                actual: None,
+            }),
+        }
+    }
+
+    for actual in remote.attributes.iter() {
+        if !entity.attributes.iter().any(|a| a.id == actual.id) {
+            drift.push(EntityDrift {
+                kind: DriftKind::Added,
+                attribute: Some(actual.id.clone()),
+                expected: None,
+                actual: Some(actual.r#type.clone()),
+            });
+        }
+    }
+
+    drift
+}
+
+/// Whether the drift can be applied to the entity without breaking existing queries,
+/// that is only attributes have been added or retyped
+pub(crate) fn is_compatible(drift: &[EntityDrift]) -> bool {
+    !drift.is_empty()
+        && drift
+            .iter()
+            .all(|d| matches!(d.kind, DriftKind::Added | DriftKind::Retyped))
+}
+
+/// Returns the entity updated with the attributes described by its data source.
+///
+/// The types of the existing attributes are updated in place and the
+/// added attributes are appended, retaining the order of the configured attributes.
+pub(crate) fn regenerate_entity(entity: &EntityConfig, remote: &EntityConfig) -> EntityConfig {
+    let mut regenerated = entity.clone();
+
+    for attr in regenerated.attributes.iter_mut() {
+        if let Some(actual) = remote.attributes.iter().find(|a| a.id == attr.id) {
+            attr.r#type = actual.r#type.clone();
+        }
+    }
+
+    for actual in remote.attributes.iter() {
+        if !entity.attributes.iter().any(|a| a.id == actual.id) {
+            regenerated.attributes.push(actual.clone());
+        }
+    }
+
+    regenerated
+}
+
+/// Describes the drift for logging and auditing, eg `added phone Utf8String, ...`
+fn describe(drift: &[EntityDrift]) -> String {
+    drift
+        .iter()
+        .map(|d| d.describe())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Returns the sql which retrieves the qualified names of the foreign tables of the
+/// entity along with whether the table stores the config of the entity
+fn entity_tables_sql(entity: &EntityConfig) -> String {
+    let entity_id = pg_str_literal(&entity.id);
+    let data_source_id = pg_str_literal(&entity.source.data_source);
+
+    format!(
+        r#"
+        SELECT
+            quote_ident(n.nspname) || '.' || quote_ident(c.relname),
+            EXISTS (
+                SELECT FROM pg_options_to_table(ft.ftoptions) o
+                WHERE o.option_name = '__config'
+            )
+        FROM pg_foreign_table ft
+        JOIN pg_class c ON c.oid = ft.ftrelid
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        JOIN pg_foreign_server s ON s.oid = ft.ftserver
+        WHERE s.srvname = {data_source_id}
+        AND EXISTS (
+            SELECT FROM pg_options_to_table(ft.ftoptions) o
+            WHERE o.option_name = 'entity_id'
+            AND o.option_value = {entity_id}
+        )
+    "#
+    )
+}
+
+/// Returns the sql which alters the foreign table to match the regenerated entity
+fn alter_sql(
+    table: &str,
+    has_config: bool,
+    entity: &EntityConfig,
+    drift: &[EntityDrift],
+) -> Result<String> {
+    let mut actions = vec![];
+
+    for d in drift.iter() {
+        let attr = match d.attribute.as_ref() {
+            Some(id) => entity
+                .attributes
+                .iter()
+                .find(|a| &a.id == id)
+                .with_context(|| format!("Unknown attribute '{id}'"))?,
+            None => continue,
+        };
+        let col = pg_quote_identifier(&attr.id);
+        let r#type = to_pg_type_name(&attr.r#type)?;
+
+        match d.kind {
+            DriftKind::Added => {
+                let mut action = format!("ADD COLUMN IF NOT EXISTS {col} {type}");
+
+                if attr.primary_key {
+                    action.push_str(" OPTIONS (primary_key 'true')");
+                }
+
+                if !attr.nullable {
+                    action.push_str(" NOT NULL");
+                }
+
+                actions.push(action);
+            }
+            DriftKind::Retyped => actions.push(format!("ALTER COLUMN {col} TYPE {type}")),
+            _ => {}
+        }
+    }
+
+    // The entity is registered from the stored config when it is unknown to the fdw
+    if has_config {
+        let config = serde_yaml::to_string(entity).context("Failed to serialise entity config")?;
+        actions.push(format!(
+            "OPTIONS (SET __config {})",
+            pg_str_literal(&config)
+        ));
+    }
+
+    Ok(format!(
+        "ALTER FOREIGN TABLE {table} {};",
+        actions.join(", ")
+    ))
+}
+
+/// Returns the sql which records the changes applied to the regenerated entity
+fn audit_sql(entity: &EntityConfig, drift: &[EntityDrift]) -> Result<String> {
+    let config = serde_yaml::to_string(entity).context("Failed to serialise entity config")?;
+
+    Ok(format!(
+        "INSERT INTO {AUDIT_TABLE} (entity_id, data_source_id, changes, config) VALUES ({}, {}, {}, {});",
+        pg_str_literal(&entity.id),
+        pg_str_literal(&entity.source.data_source),
+        pg_str_literal(&describe(drift)),
+        pg_str_literal(&config)
+    ))
+}
+
+/// Returns the sql which creates the tables recording the drift of each entity
+/// and the changes applied to regenerated entities
+pub(crate) fn init_sql() -> String {
+    format!(
+        r#"
+        CREATE TABLE {DRIFT_TABLE} (
+            entity_id TEXT NOT NULL,
+            data_source_id TEXT NOT NULL,
+            attribute_id TEXT,
+            kind TEXT NOT NULL,
+            expected_type TEXT,
+            actual_type TEXT,
+            detected_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        );
+
+        COMMENT ON TABLE {DRIFT_TABLE} IS 'The differences between the configured entities and their data sources';
+        GRANT SELECT ON {DRIFT_TABLE} TO {PG_ADMIN_USER} WITH GRANT OPTION;
+        GRANT INSERT, DELETE ON {DRIFT_TABLE} TO {PG_ADMIN_USER};
+
+        CREATE TABLE {AUDIT_TABLE} (
+            entity_id TEXT NOT NULL,
+            data_source_id TEXT NOT NULL,
+            changes TEXT NOT NULL,
+            config TEXT NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        );
+
+        COMMENT ON TABLE {AUDIT_TABLE} IS 'The changes applied to entities regenerated from their data sources';
+        GRANT SELECT ON {AUDIT_TABLE} TO {PG_ADMIN_USER} WITH GRANT OPTION;
+        GRANT INSERT ON {AUDIT_TABLE} TO {PG_ADMIN_USER};
+    "#
+    )
+}
+
+/// Returns the sql which replaces the recorded drift of the checked entities,
+/// removing the drift of entities which are no longer configured
+fn record_sql(node: &NodeConfig, results: &[EntityCheck]) -> String {
+    let literal = |s: Option<String>| s.map_or("NULL".into(), |s| pg_str_literal(&s));
+    let configured = node
+        .entities
+        .iter()
+        .map(|e| pg_str_literal(&e.id))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut sql = vec![if configured.is_empty() {
+        format!("DELETE FROM {DRIFT_TABLE};")
+    } else {
+        format!("DELETE FROM {DRIFT_TABLE} WHERE entity_id NOT IN ({configured});")
+    }];
+
+    for EntityCheck { entity, drift, .. } in results.iter() {
+        let entity_id = pg_str_literal(&entity.id);
+        sql.push(format!(
+            "DELETE FROM {DRIFT_TABLE} WHERE entity_id = {entity_id};"
+        ));
+
+        if drift.is_empty() {
+            continue;
+        }
+
+        let data_source_id = pg_str_literal(&entity.source.data_source);
+        let rows = drift
+            .iter()
+            .map(|d| {
+                format!(
+                    "({entity_id}, {data_source_id}, {}, {}, {}, {})",
+                    literal(d.attribute.clone()),
+                    pg_str_literal(d.kind.as_str()),
+                    literal(d.expected.as_ref().map(|t| t.to_string())),
+                    literal(d.actual.as_ref().map(|t| t.to_string())),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        sql.push(format!(
+            "INSERT INTO {DRIFT_TABLE} (entity_id, data_source_id, attribute_id, kind, expected_type, actual_type) VALUES {rows};"
+        ));
+    }
+
+    sql.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::config::{DataSourceConfig, EntityAttributeConfig, EntitySourceConfig, Value};
+
+    use super::*;
+
+    fn mock_entity(attributes: Vec<(&str, DataType)>) -> EntityConfig {
+        EntityConfig::minimal(
+            "customers",
+            attributes
+                .into_iter()
+                .map(|(id, r#type)| EntityAttributeConfig::minimal(id, r#type))
+                .collect(),
+            EntitySourceConfig::minimal("crm"),
+        )
+    }
+
+    #[test]
+    fn test_compare_no_drift() {
+        let entity = mock_entity(vec![
+            ("id", DataType::Int32),
+            ("name", DataType::rust_string()),
+        ]);
+
+        assert_eq!(compare(&entity, Some(&entity.clone())), vec![]);
+    }
+
+    #[test]
+    fn test_compare_missing_entity() {
+        let entity = mock_entity(vec![("id", DataType::Int32)]);
+
+        assert_eq!(
+            compare(&entity, None),
+            vec![EntityDrift {
+                kind: DriftKind::Missing,
+                attribute: None,
+                expected: None,
+                actual: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_compare_attribute_drift() {
+        let entity = mock_entity(vec![
+            ("id", DataType::Int32),
+            ("name", DataType::rust_string()),
+            ("email", DataType::rust_string()),
+        ]);
+        let remote = mock_entity(vec![
+            ("id", DataType::Int64),
+            ("name", DataType::rust_string()),
+            ("phone", DataType::rust_string()),
+        ]);
+
+        assert_eq!(
+            compare(&entity, Some(&remote)),
+            vec![
+                EntityDrift {
+                    kind: DriftKind::Retyped,
+                    attribute: Some("id".into()),
+                    expected: Some(DataType::Int32),
+                    actual: Some(DataType::Int64),
+                },
+                EntityDrift {
+                    kind: DriftKind::Removed,
+                    attribute: Some("email".into()),
+                    expected: Some(DataType::rust_string()),
+                    actual: None,
+                },
+                EntityDrift {
+                    kind: DriftKind::Added,
+                    attribute: Some("phone".into()),
+                    expected: None,
+                    actual: Some(DataType::rust_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_compatible() {
+        let entity = mock_entity(vec![
+            ("id", DataType::Int32),
+            ("email", DataType::rust_string()),
+        ]);
+
+        assert!(!is_compatible(&[]));
+        assert!(!is_compatible(&compare(&entity, None)));
+        assert!(is_compatible(&compare(
+            &entity,
+            Some(&mock_entity(vec![
+                ("id", DataType::Int64),
+                ("email", DataType::rust_string()),
+                ("phone", DataType::rust_string()),
+            ]))
+        )));
+        assert!(!is_compatible(&compare(
+            &entity,
+            Some(&mock_entity(vec![
+                ("id", DataType::Int64),
+                ("phone", DataType::rust_string()),
+            ]))
+        )));
+    }
+
+    #[test]
+    fn test_regenerate_entity() {
+        let mut entity = mock_entity(vec![
+            ("id", DataType::Int32),
+            ("name", DataType::rust_string()),
+        ]);
+        entity.attributes[1].description = Some("The full name".into());
+        let remote = mock_entity(vec![
+            ("phone", DataType::rust_string()),
+            ("name", DataType::Binary),
+            ("id", DataType::Int32),
+        ]);
+
+        let regenerated = regenerate_entity(&entity, &remote);
+
+        assert_eq!(
+            regenerated
+                .attributes
+                .iter()
+                .map(|a| (a.id.as_str(), a.r#type.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("id", DataType::Int32),
+                ("name", DataType::Binary),
+                ("phone", DataType::rust_string()),
+            ]
+        );
+        assert_eq!(
+            regenerated.attributes[1].description,
+            Some("The full name".into())
+        );
+        assert_eq!(compare(&regenerated, Some(&remote)), vec![]);
+    }
+
+    #[test]
+    fn test_alter_sql() {
+        let entity = mock_entity(vec![("id", DataType::Int32)]);
+        let remote = mock_entity(vec![("id", DataType::Int64), ("age", DataType::Int16)]);
+        let drift = compare(&entity, Some(&remote));
+        let regenerated = regenerate_entity(&entity, &remote);
+
+        assert_eq!(
+            alter_sql("public.\"customers\"", false, &regenerated, &drift).unwrap(),
+            "ALTER FOREIGN TABLE public.\"customers\" ALTER COLUMN \"id\" TYPE BIGINT, ADD COLUMN IF NOT EXISTS \"age\" SMALLINT NOT NULL;"
+        );
+
+        let sql = alter_sql("public.\"customers\"", true, &regenerated, &drift).unwrap();
+        assert!(sql.contains(", OPTIONS (SET __config '"));
+        assert!(sql.contains("id: age"));
+    }
+
+    #[test]
+    fn test_audit_sql() {
+        let entity = mock_entity(vec![("id", DataType::Int32)]);
+        let remote = mock_entity(vec![("id", DataType::Int64), ("age", DataType::Int16)]);
+        let drift = compare(&entity, Some(&remote));
+
+        let sql = audit_sql(&regenerate_entity(&entity, &remote), &drift).unwrap();
+
+        assert!(sql.starts_with(
+            "INSERT INTO ansilo_catalog.entity_drift_audit (entity_id, data_source_id, changes, config) VALUES ('customers', 'crm', 'retyped id Int32 -> Int64, added age Int16', "
+        ));
+    }
+
+    #[test]
+    fn test_record_sql() {
+        let entity = mock_entity(vec![("id", DataType::Int32)]);
+        let node = NodeConfig {
+            entities: vec![entity.clone()],
+            ..NodeConfig::default()
+        };
+        let drift = compare(&entity, Some(&mock_entity(vec![("id", DataType::Int64)])));
+
+        let sql = record_sql(
+            &node,
+            &[EntityCheck {
+                entity: &entity,
+                remote: None,
+                drift,
+            }],
+        );
+
+        assert!(sql.contains(
+            "DELETE FROM ansilo_catalog.entity_drift WHERE entity_id NOT IN ('customers');"
+        ));
+        assert!(
+            sql.contains("DELETE FROM ansilo_catalog.entity_drift WHERE entity_id = 'customers';")
+        );
+        assert!(sql.contains("VALUES ('customers', 'crm', 'id', 'retyped', 'Int32', 'Int64');"));
+
+        let sql = record_sql(
+            &node,
+            &[EntityCheck {
+                entity: &entity,
+                remote: None,
+                drift: vec![],
+            }],
+        );
+        assert!(!sql.contains("INSERT"));
+
+        let sql = record_sql(&NodeConfig::default(), &[]);
+        assert_eq!(sql, "DELETE FROM ansilo_catalog.entity_drift;");
+    }
+
+    #[test]
+    fn test_check_memory_entity_has_no_drift() {
+        let entity = mock_entity(vec![("id", DataType::Int32)]);
+        let node = NodeConfig {
+            sources: vec![DataSourceConfig {
+                id: "crm".into(),
+                name: None,
+                r#type: "test.memory".into(),
+                options: Value::Null,
+                metadata_cache_ttl: None,
+                fetch_size: None,
+                transfer_encoding: Default::default(),
+                transfer_compression: Default::default(),
+                prepared_query_cache: None,
+                statistics: None,
+                admission: None,
+            }],
+            entities: vec![entity.clone()],
+            ..NodeConfig::default()
+        };
+        let connector = Connectors::Memory;
+        let options = connector.parse_options(Value::Null).unwrap();
+        let (pool, _) = connector
+            .create_connection_pool(&node, "crm", options)
+            .unwrap();
+        let pools = Arc::new(RwLock::new(HashMap::from([("crm".to_string(), pool)])));
+
+        let results = DriftDetector::check(&node, &pools).unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].entity.id, "customers");
+        assert!(results[0].remote.is_some());
+        assert_eq!(results[0].drift, vec![]);
+    }
+}
@@ -0,0 +1,619 @@
+use std::{
+    env,
+    ffi::OsStr,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, OsRng, Payload},
+    AeadCore, Aes256Gcm, Nonce,
+};
+use ansilo_core::{
+    config::EncryptionConfig,
+    err::{ensure, Context, Error, Result},
+};
+use ansilo_logging::{info, warn};
+
+/// The header written at the start of every encrypted file
+const MAGIC: &[u8] = b"ANSILO-ENC\x01";
+
+/// The size of the plaintext chunks which are encrypted independently,
+/// so large files can be encrypted without reading them into memory
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The length of the AES-GCM nonce prepended to each chunk
+const NONCE_LEN: usize = 12;
+
+/// The length of the AES-GCM authentication tag appended to each chunk
+const TAG_LEN: usize = 16;
+
+/// The suffix of the temporary files written while a file is rewritten
+const TMP_SUFFIX: &str = ".ansilo-tmp";
+
+/// The suffix of the volume directory which stores the encrypted files of the data directory
+const VOLUME_SUFFIX: &str = ".encrypted";
+
+/// The config file written by gocryptfs when a volume is initialised
+const VOLUME_CONF_FILE: &str = "gocryptfs.conf";
+
+/// A key used to encrypt the locally persisted data of the node at rest.
+///
+/// Files are encrypted with AES-256-GCM in chunks, each with a random nonce.
+/// The index of each chunk, and whether it is the last, is authenticated so
+/// chunks cannot be reordered or the file truncated without detection.
+#[derive(Clone)]
+pub struct EncryptionKey {
+    cipher: Aes256Gcm,
+    /// The base64-encoded key, used as the password of the encrypted volume
+    password: String,
+}
+
+impl EncryptionKey {
+    /// Creates a new key from the supplied 256-bit key material
+    pub fn new(key: &[u8]) -> Result<Self> {
+        ensure!(
+            key.len() == 32,
+            "Encryption key must be 256 bits, found {} bits",
+            key.len() * 8
+        );
+
+        Ok(Self {
+            cipher: Aes256Gcm::new_from_slice(key)
+                .map_err(|_| Error::msg("Invalid encryption key"))?,
+            password: base64::encode(key),
+        })
+    }
+
+    /// Retrieves the base64-encoded key from the configured URL
+    pub fn fetch(conf: &EncryptionConfig) -> Result<Self> {
+        let key =
+            ansilo_util_url::get(conf.key.clone()).context("Failed to retrieve encryption key")?;
+        let key = base64::decode(String::from_utf8_lossy(&key).trim())
+            .context("Failed to decode encryption key, expected base64")?;
+
+        Self::new(&key)
+    }
+
+    /// Encrypts the file, returning false if it is already encrypted
+    pub fn seal_file(&self, path: &Path) -> Result<bool> {
+        if is_sealed(&File::open(path)?)? {
+            return Ok(false);
+        }
+
+        Self::rewrite(path, |input, output| self.seal(input, output))
+            .with_context(|| format!("Failed to encrypt file {}", path.display()))?;
+
+        Ok(true)
+    }
+
+    /// Decrypts the file, returning false if it is not encrypted
+    pub fn unseal_file(&self, path: &Path) -> Result<bool> {
+        if !is_sealed(&File::open(path)?)? {
+            return Ok(false);
+        }
+
+        Self::rewrite(path, |input, output| self.unseal(input, output))
+            .with_context(|| format!("Failed to decrypt file {}", path.display()))?;
+
+        Ok(true)
+    }
+
+    /// Encrypts the already opened file in place.
+    ///
+    /// Unlike [`Self::seal_file`] the file is not replaced, so this can be used on
+    /// files which are held open. The contents are buffered in memory.
+    pub fn seal_open_file(&self, mut file: &File) -> Result<bool> {
+        if is_sealed(file)? {
+            return Ok(false);
+        }
+
+        let mut sealed = vec![];
+        file.seek(SeekFrom::Start(0))?;
+        self.seal(&mut BufReader::new(file), &mut sealed)?;
+        Self::replace_contents(file, &sealed)?;
+
+        Ok(true)
+    }
+
+    /// Decrypts the already opened file in place
+    pub fn unseal_open_file(&self, mut file: &File) -> Result<bool> {
+        if !is_sealed(file)? {
+            return Ok(false);
+        }
+
+        let mut unsealed = vec![];
+        file.seek(SeekFrom::Start(0))?;
+        self.unseal(&mut BufReader::new(file), &mut unsealed)?;
+        Self::replace_contents(file, &unsealed)?;
+
+        Ok(true)
+    }
+
+    /// Encrypts the input, writing the header and encrypted chunks to the output
+    fn seal(&self, input: &mut dyn BufRead, output: &mut dyn Write) -> Result<()> {
+        output.write_all(MAGIC)?;
+
+        let mut chunk = read_chunk(input)?;
+        let mut idx = 0u64;
+
+        loop {
+            let next = if chunk.len() == CHUNK_SIZE {
+                read_chunk(input)?
+            } else {
+                vec![]
+            };
+            let last = next.is_empty();
+
+            let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+            let encrypted = self
+                .cipher
+                .encrypt(
+                    &nonce,
+                    Payload {
+                        msg: &chunk,
+                        aad: &chunk_aad(idx, last),
+                    },
+                )
+                .map_err(|_| Error::msg("Failed to encrypt data"))?;
+
+            output.write_all(&nonce)?;
+            output.write_all(&(encrypted.len() as u32).to_be_bytes())?;
+            output.write_all(&encrypted)?;
+
+            if last {
+                return Ok(());
+            }
+
+            chunk = next;
+            idx += 1;
+        }
+    }
+
+    /// Decrypts the input, which must start with the header, writing the plaintext to the output
+    fn unseal(&self, input: &mut dyn BufRead, output: &mut dyn Write) -> Result<()> {
+        let mut magic = [0u8; MAGIC.len()];
+        input.read_exact(&mut magic)?;
+        ensure!(magic == MAGIC, "Data is not encrypted");
+
+        let mut idx = 0u64;
+
+        loop {
+            let mut nonce = [0u8; NONCE_LEN];
+            let mut len = [0u8; 4];
+            input
+                .read_exact(&mut nonce)
+                .and_then(|_| input.read_exact(&mut len))
+                .context("Encrypted data is truncated")?;
+
+            let len = u32::from_be_bytes(len) as usize;
+            ensure!(len <= CHUNK_SIZE + TAG_LEN, "Encrypted data is corrupt");
+
+            let mut encrypted = vec![0u8; len];
+            input
+                .read_exact(&mut encrypted)
+                .context("Encrypted data is truncated")?;
+            let last = input.fill_buf()?.is_empty();
+
+            let chunk = self
+                .cipher
+                .decrypt(
+                    Nonce::from_slice(&nonce),
+                    Payload {
+                        msg: &encrypted,
+                        aad: &chunk_aad(idx, last),
+                    },
+                )
+                .map_err(|_| {
+                    Error::msg("Failed to decrypt data, the encryption key may be incorrect")
+                })?;
+            output.write_all(&chunk)?;
+
+            if last {
+                return Ok(());
+            }
+
+            idx += 1;
+        }
+    }
+
+    /// Rewrites the file via a temporary file, which replaces the original once
+    /// it is fully written so the file is never left partially rewritten
+    fn rewrite(
+        path: &Path,
+        cb: impl FnOnce(&mut dyn BufRead, &mut dyn Write) -> Result<()>,
+    ) -> Result<()> {
+        let tmp = tmp_path(path);
+        let mut input = BufReader::new(File::open(path)?);
+        let output = File::create(&tmp)?;
+        output.set_permissions(fs::metadata(path)?.permissions())?;
+
+        let res = (|| {
+            let mut output = BufWriter::new(output);
+            cb(&mut input, &mut output)?;
+            output
+                .into_inner()
+                .map_err(|e| e.into_error())?
+                .sync_all()?;
+            fs::rename(&tmp, path)?;
+            Ok(())
+        })();
+
+        if res.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+
+        res
+    }
+
+    fn replace_contents(mut file: &File, data: &[u8]) -> Result<()> {
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(data)?;
+        file.sync_all()?;
+
+        Ok(())
+    }
+}
+
+/// A directory of which the files are only ever stored on disk encrypted.
+///
+/// The encrypted files are stored in the volume directory, which is mounted
+/// at the mount directory using gocryptfs. Files are encrypted and decrypted
+/// by the filesystem as they are written and read, so their unencrypted
+/// contents are never written to disk. If the node stops without unmounting
+/// the volume, such as after a crash, the mount directory is left empty.
+pub struct EncryptedVolume {
+    /// The directory containing the encrypted files
+    volume_dir: PathBuf,
+    /// The directory at which the decrypted files are mounted
+    mount_dir: PathBuf,
+}
+
+impl EncryptedVolume {
+    pub fn new(volume_dir: impl Into<PathBuf>, mount_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            volume_dir: volume_dir.into(),
+            mount_dir: mount_dir.into(),
+        }
+    }
+
+    /// Gets the volume which is mounted at the data directory.
+    ///
+    /// Unless configured, the volume directory is stored alongside the data directory.
+    pub fn for_data_dir(conf: &EncryptionConfig, data_dir: &Path) -> Self {
+        let volume_dir = conf.volume_dir.clone().unwrap_or_else(|| {
+            let mut name = data_dir.file_name().unwrap_or_default().to_os_string();
+            name.push(VOLUME_SUFFIX);
+            data_dir.with_file_name(name)
+        });
+
+        Self::new(volume_dir, data_dir)
+    }
+
+    /// The directory containing the encrypted files
+    pub fn volume_dir(&self) -> &Path {
+        self.volume_dir.as_path()
+    }
+
+    /// Mounts the volume, initialising it using the key if it does not exist.
+    ///
+    /// A volume left mounted by an unclean shutdown is unmounted first.
+    /// Mounting fails if files have been written to the mount directory
+    /// while the volume was not mounted, as they are stored unencrypted.
+    pub fn mount(&self, key: &EncryptionKey) -> Result<()> {
+        if is_mount_point(&self.mount_dir)? {
+            warn!(
+                "Unmounting the encrypted volume left mounted at {}",
+                self.mount_dir.display()
+            );
+            self.unmount()?;
+        }
+
+        if self.mount_dir.exists() {
+            let unencrypted = fs::read_dir(&self.mount_dir)
+                .with_context(|| format!("Failed to read {}", self.mount_dir.display()))?
+                .next()
+                .is_some();
+
+            ensure!(
+                !unencrypted,
+                "The directory {} contains unencrypted files and cannot be used as the mount point \
+                of the encrypted volume {}. To encrypt the data of an existing node, back up the \
+                node and restore it once encryption is configured",
+                self.mount_dir.display(),
+                self.volume_dir.display()
+            );
+        }
+
+        if !self.volume_dir.join(VOLUME_CONF_FILE).exists() {
+            info!(
+                "Initialising encrypted volume {}...",
+                self.volume_dir.display()
+            );
+            fs::create_dir_all(&self.volume_dir)
+                .with_context(|| format!("Failed to create {}", self.volume_dir.display()))?;
+            gocryptfs(
+                key,
+                [
+                    OsStr::new("-q"),
+                    OsStr::new("-init"),
+                    self.volume_dir.as_os_str(),
+                ],
+            )
+            .context("Failed to initialise encrypted volume")?;
+        }
+
+        fs::create_dir_all(&self.mount_dir)
+            .with_context(|| format!("Failed to create {}", self.mount_dir.display()))?;
+        gocryptfs(
+            key,
+            [
+                OsStr::new("-q"),
+                self.volume_dir.as_os_str(),
+                self.mount_dir.as_os_str(),
+            ],
+        )
+        .context("Failed to mount encrypted volume, the encryption key may be incorrect")
+    }
+
+    /// Unmounts the volume, which must no longer be in use
+    pub fn unmount(&self) -> Result<()> {
+        let output = Command::new("fusermount")
+            .arg("-u")
+            .arg(&self.mount_dir)
+            .stdin(Stdio::null())
+            .output()
+            .context("Failed to run fusermount")?;
+
+        ensure!(
+            output.status.success(),
+            "Failed to unmount encrypted volume at {}: {}",
+            self.mount_dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+
+        Ok(())
+    }
+}
+
+/// Runs gocryptfs, passing the key as the password of the volume on stdin.
+///
+/// When mounting, gocryptfs continues in the background once the volume is mounted,
+/// so its output is not captured as the background process would hold it open.
+fn gocryptfs<'a>(key: &EncryptionKey, args: impl IntoIterator<Item = &'a OsStr>) -> Result<()> {
+    let mut child = Command::new("gocryptfs")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .context("Failed to run gocryptfs, is it installed?")?;
+
+    child
+        .stdin
+        .take()
+        .context("Failed to open stdin of gocryptfs")?
+        .write_all(format!("{}\n", key.password).as_bytes())
+        .context("Failed to write password to gocryptfs")?;
+
+    let status = child.wait().context("Failed to wait for gocryptfs")?;
+    ensure!(status.success(), "gocryptfs exited with {}", status);
+
+    Ok(())
+}
+
+/// Checks whether a filesystem is mounted at the directory
+fn is_mount_point(dir: &Path) -> Result<bool> {
+    let dir = if dir.is_absolute() {
+        dir.to_path_buf()
+    } else {
+        env::current_dir()
+            .context("Failed to get current directory")?
+            .join(dir)
+    };
+
+    // The mount point itself is not resolved as it may be the
+    // stale mount of a volume of which the process has exited
+    let dir = match (dir.parent(), dir.file_name()) {
+        (Some(parent), Some(name)) if parent.exists() => fs::canonicalize(parent)
+            .with_context(|| format!("Failed to resolve {}", parent.display()))?
+            .join(name),
+        _ => return Ok(false),
+    };
+
+    let mounts =
+        fs::read_to_string("/proc/self/mountinfo").context("Failed to read mounted filesystems")?;
+
+    Ok(mounts
+        .lines()
+        .filter_map(|line| line.split(' ').nth(4))
+        .any(|mount| Path::new(&unescape_mount_path(mount)) == dir))
+}
+
+/// Decodes the octal escapes of the whitespace and backslashes in a path listed in mountinfo
+fn unescape_mount_path(path: &str) -> String {
+    let mut unescaped = String::with_capacity(path.len());
+    let mut rest = path;
+
+    while let Some(idx) = rest.find('\\') {
+        unescaped.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        match rest.get(1..4).and_then(|o| u8::from_str_radix(o, 8).ok()) {
+            Some(c) => {
+                unescaped.push(c as char);
+                rest = &rest[4..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Checks whether the file starts with the encryption header
+pub(crate) fn is_sealed(mut file: &File) -> Result<bool> {
+    let mut header = vec![];
+    file.seek(SeekFrom::Start(0))?;
+    file.take(MAGIC.len() as u64).read_to_end(&mut header)?;
+
+    Ok(header == MAGIC)
+}
+
+fn read_chunk(input: &mut dyn BufRead) -> Result<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    input.take(CHUNK_SIZE as u64).read_to_end(&mut chunk)?;
+
+    Ok(chunk)
+}
+
+fn chunk_aad(idx: u64, last: bool) -> [u8; 9] {
+    let mut aad = [0u8; 9];
+    aad[..8].copy_from_slice(&idx.to_be_bytes());
+    aad[8] = last as u8;
+    aad
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(TMP_SUFFIX);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_key() -> EncryptionKey {
+        EncryptionKey::new(&[7u8; 32]).unwrap()
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ansilo-main-test/encryption/{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_encryption_key_invalid_length() {
+        assert_eq!(
+            EncryptionKey::new(&[0u8; 16]).err().unwrap().to_string(),
+            "Encryption key must be 256 bits, found 128 bits"
+        );
+    }
+
+    #[test]
+    fn test_encryption_seal_unseal_multiple_chunks() {
+        let key = mock_key();
+        let data = (0..(CHUNK_SIZE * 2 + 10))
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+
+        let mut sealed = vec![];
+        key.seal(&mut data.as_slice(), &mut sealed).unwrap();
+        assert!(sealed.starts_with(MAGIC));
+        assert!(!sealed.windows(64).any(|w| w == &data[..64]));
+
+        let mut unsealed = vec![];
+        key.unseal(&mut sealed.as_slice(), &mut unsealed).unwrap();
+        assert_eq!(unsealed, data);
+    }
+
+    #[test]
+    fn test_encryption_unseal_detects_truncation_and_wrong_key() {
+        let key = mock_key();
+        let data = vec![1u8; CHUNK_SIZE + 1];
+
+        let mut sealed = vec![];
+        key.seal(&mut data.as_slice(), &mut sealed).unwrap();
+
+        // Remove the last chunk
+        let truncated = &sealed[..(MAGIC.len() + NONCE_LEN + 4 + CHUNK_SIZE + TAG_LEN)];
+        key.unseal(&mut &truncated[..], &mut vec![]).unwrap_err();
+
+        EncryptionKey::new(&[8u8; 32])
+            .unwrap()
+            .unseal(&mut sealed.as_slice(), &mut vec![])
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_encrypted_volume_for_data_dir() {
+        let conf = EncryptionConfig {
+            key: "env://KEY".into(),
+            volume_dir: None,
+        };
+
+        let volume = EncryptedVolume::for_data_dir(&conf, Path::new("/var/run/ansilo/data/"));
+        assert_eq!(
+            volume.volume_dir(),
+            Path::new("/var/run/ansilo/data.encrypted")
+        );
+
+        let conf = EncryptionConfig {
+            volume_dir: Some("/mnt/encrypted".into()),
+            ..conf
+        };
+        let volume = EncryptedVolume::for_data_dir(&conf, Path::new("/var/run/ansilo/data"));
+        assert_eq!(volume.volume_dir(), Path::new("/mnt/encrypted"));
+    }
+
+    #[test]
+    fn test_encrypted_volume_mount_refuses_unencrypted_files() {
+        let dir = test_dir("volume-unencrypted");
+        fs::create_dir_all(dir.join("data")).unwrap();
+        fs::write(dir.join("data/PG_VERSION"), "15\n").unwrap();
+
+        let volume = EncryptedVolume::new(dir.join("data.encrypted"), dir.join("data"));
+        let err = volume.mount(&mock_key()).unwrap_err();
+
+        assert!(err.to_string().contains("contains unencrypted files"));
+        assert!(!dir.join("data.encrypted").exists());
+    }
+
+    #[test]
+    fn test_encrypted_volume_is_mount_point() {
+        assert!(is_mount_point(Path::new("/proc")).unwrap());
+        assert!(!is_mount_point(&test_dir("volume-mount-point")).unwrap());
+        assert!(!is_mount_point(Path::new("/does/not/exist")).unwrap());
+    }
+
+    #[test]
+    fn test_unescape_mount_path() {
+        assert_eq!(unescape_mount_path("/var/run/ansilo"), "/var/run/ansilo");
+        assert_eq!(unescape_mount_path("/mnt/my\\040dir"), "/mnt/my dir");
+        assert_eq!(unescape_mount_path("/mnt/a\\134b"), "/mnt/a\\b");
+        assert_eq!(unescape_mount_path("/mnt/a\\b"), "/mnt/a\\b");
+    }
+
+    #[test]
+    fn test_encryption_seal_unseal_open_file() {
+        let key = mock_key();
+        let path = test_dir("open-file").join("ansilo.log");
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"log line\n").unwrap();
+
+        assert!(key.seal_open_file(&file).unwrap());
+        assert!(fs::read(&path).unwrap().starts_with(MAGIC));
+
+        assert!(key.unseal_open_file(&file).unwrap());
+        file.write_all(b"another line\n").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "log line\nanother line\n"
+        );
+    }
+}
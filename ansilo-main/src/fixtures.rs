@@ -0,0 +1,266 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use ansilo_connectors_all::{ConnectionPools, ConnectorEntityConfigs};
+use ansilo_core::{
+    config::{EntityConfig, NodeConfig},
+    data::{DataType, DataValue},
+    err::{bail, Context, Result},
+};
+use ansilo_logging::info;
+use ansilo_pg::handler::PostgresConnectionHandler;
+use futures_util::SinkExt;
+
+use crate::data::copy_sql;
+
+/// Loads the csv fixtures of the memory data sources into their connection pools.
+///
+/// The data is shared by all connections of the pool. Loading a fixture
+/// replaces any rows of the entity configured in the data source options.
+pub(crate) fn load_memory(
+    node: &NodeConfig,
+    pools: &HashMap<String, (ConnectionPools, ConnectorEntityConfigs)>,
+) -> Result<()> {
+    for fixture in node.dev.fixtures.iter() {
+        let (path, source, entity) = match (
+            fixture.csv.as_ref(),
+            fixture.data_source.as_ref(),
+            fixture.entity.as_ref(),
+        ) {
+            (Some(path), Some(source), Some(entity)) => (path, source, entity),
+            _ => continue,
+        };
+
+        let pool = match pools.get(source) {
+            Some((ConnectionPools::Memory(pool), _)) => pool,
+            _ => bail!("Data source '{source}' is not a memory data source"),
+        };
+        let entity = node
+            .entities
+            .iter()
+            .find(|e| &e.id == entity && &e.source.data_source == source)
+            .with_context(|| {
+                format!("Failed to find entity '{entity}' of data source '{source}'")
+            })?;
+
+        info!("Loading fixture {}...", path.display());
+        let rows = read_rows(path, entity)?;
+        info!("Loaded {} rows into {source}.{}", rows.len(), entity.id);
+
+        pool.conf().set_data(&entity.id, rows);
+    }
+
+    Ok(())
+}
+
+/// Loads the sql and csv fixtures into postgres as the admin user
+pub(crate) async fn load(node: &NodeConfig, handler: &PostgresConnectionHandler) -> Result<()> {
+    let fixtures = node
+        .dev
+        .fixtures
+        .iter()
+        .filter(|f| f.sql.is_some() || f.table.is_some())
+        .collect::<Vec<_>>();
+
+    if fixtures.is_empty() {
+        return Ok(());
+    }
+
+    let con = handler
+        .pool()
+        .admin()
+        .await
+        .context("Failed to connect to postgres")?;
+
+    for fixture in fixtures {
+        match (
+            fixture.sql.as_ref(),
+            fixture.csv.as_ref(),
+            fixture.table.as_ref(),
+        ) {
+            (Some(sql), _, _) => {
+                for script in glob::glob(sql.to_str().context("Invalid fixture sql path")?)
+                    .context("Failed to glob fixture sql path")?
+                {
+                    let script = script.context("Failed to read sql file")?;
+
+                    info!("Loading fixture {}...", script.display());
+                    let sql = fs::read_to_string(&script).with_context(|| {
+                        format!("Failed to read sql file: {}", script.display())
+                    })?;
+                    con.batch_execute(&sql).await.with_context(|| {
+                        format!("Failed to execute fixture: {}", script.display())
+                    })?;
+                }
+            }
+            (None, Some(path), Some(table)) => {
+                info!("Loading fixture {}...", path.display());
+                let cols = read_headers(path)?;
+                let data = fs::read(path)
+                    .with_context(|| format!("Failed to read file {}", path.display()))?;
+
+                let sink = con
+                    .copy_in(&copy_sql(table, &cols, true))
+                    .await
+                    .context("Failed to start COPY")?;
+                futures_util::pin_mut!(sink);
+
+                sink.send(bytes::Bytes::from(data)).await?;
+                let rows = sink.finish().await.context("Failed to complete COPY")?;
+
+                info!("Loaded {rows} rows into {table}");
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the column names from the header row of the csv file
+fn read_headers(path: &Path) -> Result<Vec<String>> {
+    let mut reader = csv::Reader::from_path(path)
+        .with_context(|| format!("Failed to open file {}", path.display()))?;
+
+    Ok(reader
+        .headers()
+        .context("Failed to read csv header")?
+        .iter()
+        .map(|h| h.to_string())
+        .collect())
+}
+
+/// Reads the rows of the csv file, ordered by the attributes of the entity.
+///
+/// Columns are matched to attributes by name, any attributes without
+/// a column and empty values of nullable attributes are null.
+fn read_rows(path: &Path, entity: &EntityConfig) -> Result<Vec<Vec<DataValue>>> {
+    let mut reader = csv::Reader::from_path(path)
+        .with_context(|| format!("Failed to open file {}", path.display()))?;
+    let headers = reader
+        .headers()
+        .context("Failed to read csv header")?
+        .clone();
+
+    if let Some(col) = headers
+        .iter()
+        .find(|h| !entity.attributes.iter().any(|a| a.id == **h))
+    {
+        bail!(
+            "Column '{col}' in {} is not an attribute of entity '{}'",
+            path.display(),
+            entity.id
+        );
+    }
+
+    let cols = entity
+        .attributes
+        .iter()
+        .map(|a| headers.iter().position(|h| h == a.id))
+        .collect::<Vec<_>>();
+
+    reader
+        .records()
+        .enumerate()
+        .map(|(idx, record)| {
+            let record = record
+                .with_context(|| format!("Failed to read row {} of {}", idx + 1, path.display()))?;
+
+            entity
+                .attributes
+                .iter()
+                .zip(cols.iter())
+                .map(|(attr, col)| match col.and_then(|c| record.get(c)) {
+                    None => Ok(DataValue::Null),
+                    Some("") if attr.nullable => Ok(DataValue::Null),
+                    Some(val) => parse_value(val, &attr.r#type).with_context(|| {
+                        format!(
+                            "Failed to parse value '{val}' of attribute '{}' on row {} of {}",
+                            attr.id,
+                            idx + 1,
+                            path.display()
+                        )
+                    }),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn parse_value(val: &str, r#type: &DataType) -> Result<DataValue> {
+    // Booleans are commonly written as true/false in csv files
+    let val = match r#type {
+        DataType::Boolean if val.eq_ignore_ascii_case("true") => "1",
+        DataType::Boolean if val.eq_ignore_ascii_case("false") => "0",
+        _ => val,
+    };
+
+    DataValue::Utf8String(val.into()).try_coerce_into(r#type)
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::config::{EntityAttributeConfig, EntitySourceConfig};
+
+    use super::*;
+
+    fn mock_entity() -> EntityConfig {
+        EntityConfig::minimal(
+            "people",
+            vec![
+                EntityAttributeConfig::new("id".into(), None, DataType::Int32, true, false),
+                EntityAttributeConfig::nullable("name", DataType::rust_string()),
+                EntityAttributeConfig::nullable("active", DataType::Boolean),
+            ],
+            EntitySourceConfig::minimal("memory"),
+        )
+    }
+
+    #[test]
+    fn test_read_rows() {
+        let path = std::env::temp_dir().join("ansilo-main-test-fixture.csv");
+        fs::write(&path, "name,id,active\nJohn,1,true\n,2,0\n").unwrap();
+
+        assert_eq!(
+            read_rows(&path, &mock_entity()).unwrap(),
+            vec![
+                vec![
+                    DataValue::Int32(1),
+                    DataValue::Utf8String("John".into()),
+                    DataValue::Boolean(true)
+                ],
+                vec![
+                    DataValue::Int32(2),
+                    DataValue::Null,
+                    DataValue::Boolean(false)
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_rows_missing_column() {
+        let path = std::env::temp_dir().join("ansilo-main-test-fixture-missing.csv");
+        fs::write(&path, "id\n1\n").unwrap();
+
+        assert_eq!(
+            read_rows(&path, &mock_entity()).unwrap(),
+            vec![vec![DataValue::Int32(1), DataValue::Null, DataValue::Null]]
+        );
+    }
+
+    #[test]
+    fn test_read_rows_unknown_column() {
+        let path = std::env::temp_dir().join("ansilo-main-test-fixture-unknown.csv");
+        fs::write(&path, "id,email\n1,a@b.com\n").unwrap();
+
+        read_rows(&path, &mock_entity()).unwrap_err();
+    }
+
+    #[test]
+    fn test_read_rows_invalid_value() {
+        let path = std::env::temp_dir().join("ansilo-main-test-fixture-invalid.csv");
+        fs::write(&path, "id\nabc\n").unwrap();
+
+        read_rows(&path, &mock_entity()).unwrap_err();
+    }
+}
@@ -0,0 +1,216 @@
+use ansilo_core::{
+    config::{GrantConfig, NodeConfig},
+    err::{Context, Result},
+};
+use ansilo_logging::info;
+use ansilo_pg::handler::PostgresConnectionHandler;
+use ansilo_util_pg::query::{pg_quote_identifier, pg_str_literal};
+
+/// Schemas containing foreign tables which are never granted to users
+const EXCLUDED_SCHEMAS: [&str; 2] = ["ansilo_catalog", "ansilo_materialize"];
+
+/// Returns the usernames of the users matched by the grant
+fn grantees<'a>(node: &'a NodeConfig, grant: &'a GrantConfig) -> impl Iterator<Item = &'a str> {
+    node.auth
+        .users
+        .iter()
+        .filter(|u| grant.applies_to.matches(&u.username, &u.roles))
+        .map(|u| u.username.as_str())
+}
+
+fn pg_text_array(values: &[String]) -> String {
+    format!(
+        "ARRAY[{}]::text[]",
+        values
+            .iter()
+            .map(|v| pg_str_literal(v))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Returns the sql which grants the users access to the foreign tables of the
+/// data sources and entities in the configured grants.
+///
+/// As the foreign tables are imported by the build scripts, the grants are
+/// applied to the foreign tables which exist once the scripts have run.
+pub(crate) fn grant_sql(node: &NodeConfig) -> Vec<String> {
+    let excluded = EXCLUDED_SCHEMAS
+        .iter()
+        .map(|s| pg_str_literal(s))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    node.auth
+        .grants
+        .iter()
+        .flat_map(|grant| {
+            let privileges = if grant.write {
+                "SELECT, INSERT, UPDATE, DELETE"
+            } else {
+                "SELECT"
+            };
+            let sources = pg_text_array(&grant.sources);
+            let entities = pg_text_array(&grant.entities);
+            let excluded = excluded.clone();
+
+            grantees(node, grant).map(move |username| {
+                let user = pg_quote_identifier(username);
+                let user_literal = pg_str_literal(username);
+
+                let mut sql = format!(
+                    r#"
+                DO $$
+                DECLARE t record;
+                BEGIN
+                    FOR t IN
+                        SELECT c.oid::regclass AS tab, n.nspname AS schema
+                        FROM pg_foreign_table ft
+                        JOIN pg_class c ON c.oid = ft.ftrelid
+                        JOIN pg_namespace n ON n.oid = c.relnamespace
+                        JOIN pg_foreign_server s ON s.oid = ft.ftserver
+                        WHERE n.nspname NOT IN ({excluded})
+                        AND (
+                            s.srvname = ANY({sources})
+                            OR EXISTS (
+                                SELECT FROM pg_options_to_table(ft.ftoptions) o
+                                WHERE o.option_name = 'entity_id'
+                                AND o.option_value = ANY({entities})
+                            )
+                        )
+                    LOOP
+                        EXECUTE format('GRANT USAGE ON SCHEMA %I TO %I', t.schema, {user_literal});
+                        EXECUTE format('GRANT {privileges} ON %s TO %I', t.tab, {user_literal});
+                    END LOOP;
+                END $$;
+            "#
+                );
+
+                if grant.remote_queries && !grant.sources.is_empty() {
+                    sql.push_str(&format!(
+                        r#"
+                GRANT EXECUTE ON FUNCTION remote_query(text, text), remote_query(text, text, variadic "any") TO {user};
+            "#
+                    ));
+
+                    if grant.write {
+                        sql.push_str(&format!(
+                            r#"
+                GRANT EXECUTE ON FUNCTION remote_execute(text, text), remote_execute(text, text, variadic "any") TO {user};
+            "#
+                        ));
+                    }
+                }
+
+                sql
+            })
+        })
+        .collect()
+}
+
+/// Applies the configured grants to the foreign tables created by the build
+pub async fn apply(node: &NodeConfig, handler: &PostgresConnectionHandler) -> Result<()> {
+    let sql = grant_sql(node);
+
+    if sql.is_empty() {
+        return Ok(());
+    }
+
+    info!("Applying {} grant(s)...", node.auth.grants.len());
+
+    let con = handler
+        .pool()
+        .admin()
+        .await
+        .context("Failed to connect to postgres")?;
+
+    for sql in sql {
+        con.batch_execute(&sql)
+            .await
+            .context("Failed to apply grants")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::config::{
+        AuthConfig, PasswordUserConfig, UserConfig, UserMatchConfig, UserTypeOptions,
+    };
+
+    use super::*;
+
+    fn mock_user(username: &str, roles: Vec<&str>) -> UserConfig {
+        UserConfig {
+            username: username.into(),
+            description: None,
+            provider: None,
+            roles: roles.into_iter().map(|r| r.into()).collect(),
+            r#type: UserTypeOptions::Password(PasswordUserConfig {
+                password: "pass".into(),
+            }),
+        }
+    }
+
+    fn mock_node(grants: Vec<GrantConfig>) -> NodeConfig {
+        NodeConfig {
+            auth: AuthConfig {
+                users: vec![
+                    mock_user("mary", vec!["analyst"]),
+                    mock_user("john", vec!["analyst"]),
+                    mock_user("bob", vec![]),
+                ],
+                grants,
+                ..AuthConfig::default()
+            },
+            ..NodeConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_grant_sql_no_grants() {
+        assert!(grant_sql(&mock_node(vec![])).is_empty());
+    }
+
+    #[test]
+    fn test_grant_sql_select() {
+        let sql = grant_sql(&mock_node(vec![GrantConfig {
+            applies_to: UserMatchConfig {
+                users: vec![],
+                roles: vec!["analyst".into()],
+            },
+            sources: vec!["mysql".into()],
+            entities: vec!["customers".into()],
+            ..GrantConfig::default()
+        }]));
+
+        assert_eq!(sql.len(), 2);
+        assert!(sql[0].contains("t.tab, E'mary'"));
+        assert!(sql[1].contains("t.tab, E'john'"));
+        assert!(sql[0].contains("'GRANT SELECT ON %s TO %I'"));
+        assert!(sql[0].contains("s.srvname = ANY(ARRAY[E'mysql']::text[])"));
+        assert!(sql[0].contains("o.option_value = ANY(ARRAY[E'customers']::text[])"));
+        assert!(!sql[0].contains("remote_query"));
+    }
+
+    #[test]
+    fn test_grant_sql_write_and_remote_queries() {
+        let sql = grant_sql(&mock_node(vec![GrantConfig {
+            applies_to: UserMatchConfig {
+                users: vec!["bob".into()],
+                roles: vec![],
+            },
+            sources: vec!["mysql".into()],
+            write: true,
+            remote_queries: true,
+            ..GrantConfig::default()
+        }]));
+
+        assert_eq!(sql.len(), 1);
+        assert!(sql[0].contains("'GRANT SELECT, INSERT, UPDATE, DELETE ON %s TO %I'"));
+        assert!(sql[0].contains("o.option_value = ANY(ARRAY[]::text[])"));
+        assert!(sql[0].contains(r#"GRANT EXECUTE ON FUNCTION remote_query(text, text), remote_query(text, text, variadic "any") TO "bob";"#));
+        assert!(sql[0].contains(r#"GRANT EXECUTE ON FUNCTION remote_execute(text, text), remote_execute(text, text, variadic "any") TO "bob";"#));
+    }
+}
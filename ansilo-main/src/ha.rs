@@ -0,0 +1,278 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use ansilo_core::{
+    config::{HaConfig, HaNodeConfig, HaRole},
+    err::{bail, Context, Error, Result},
+    web::ha::HaStatus,
+};
+use ansilo_logging::{debug, error, info, warn};
+use ansilo_pg::{
+    conf::PostgresConf,
+    replication::{self, HaState},
+};
+use reqwest::{blocking::Client, StatusCode};
+
+/// Coordinates the role of this node in a high-availability pair.
+///
+/// The standby checks the health of the primary and promotes its postgres
+/// instance once the primary fails `failover_threshold` consecutive checks.
+/// The role of each node is exposed at `/api/ha` so load balancers or
+/// health-checked DNS records can direct clients to the primary.
+pub struct HaCoordinator {
+    /// The current role of this node
+    state: HaState,
+    /// Set if this node has stepped down from being the primary
+    stepped_down: Arc<AtomicBool>,
+    /// Dropping this sender signals the coordinator thread to stop
+    stop: Mutex<Option<Sender<()>>>,
+}
+
+impl HaCoordinator {
+    /// Determines the role this node starts as, before postgres is started.
+    ///
+    /// If the other node is already the primary this node starts as the standby.
+    /// When both nodes start at once, the node which is not preferred as the
+    /// primary waits for the other node to take the primary role.
+    pub fn startup_role(ha: &HaConfig) -> Result<HaRole> {
+        let peer = ha.peer().context("Failed to find other node of the pair")?;
+        let client = Self::client(ha)?;
+        let attempts = if ha.is_preferred_primary() {
+            1
+        } else {
+            ha.failover_threshold()
+        };
+
+        for attempt in 1..=attempts {
+            match Self::peer_status(&client, peer) {
+                Ok(status) if status.role == HaRole::Primary => return Ok(HaRole::Standby),
+                Ok(_) => return Ok(HaRole::Primary),
+                Err(err) => debug!("Failed to check status of node '{}': {:?}", peer.id, err),
+            }
+
+            if attempt < attempts {
+                info!("Waiting for node '{}' to start as the primary...", peer.id);
+                thread::sleep(ha.check_interval());
+            }
+        }
+
+        Ok(HaRole::Primary)
+    }
+
+    /// Starts checking the health of the other node of the pair
+    pub fn start(ha: &'static HaConfig, pg: &'static PostgresConf, state: HaState) -> Result<Self> {
+        let peer = ha.peer().context("Failed to find other node of the pair")?;
+        let client = Self::client(ha)?;
+        let stepped_down = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel::<()>();
+
+        {
+            let state = state.clone();
+            let stepped_down = Arc::clone(&stepped_down);
+            let mut failures = 0;
+
+            thread::Builder::new()
+                .name("ansilo-ha".into())
+                .spawn(move || loop {
+                    match rx.recv_timeout(ha.check_interval()) {
+                        Err(RecvTimeoutError::Timeout) => {}
+                        _ => break,
+                    }
+
+                    let res = Self::peer_status(&client, peer);
+
+                    match (state.role(), res) {
+                        (HaRole::Standby, Ok(status)) if status.role == HaRole::Primary => {
+                            failures = 0;
+                        }
+                        (HaRole::Standby, res) => {
+                            failures += 1;
+                            warn!(
+                                "Primary node '{}' failed health check ({failures}/{}): {}",
+                                peer.id,
+                                ha.failover_threshold(),
+                                match res {
+                                    Ok(_) => "node is not the primary".into(),
+                                    Err(err) => format!("{:?}", err),
+                                }
+                            );
+
+                            if failures >= ha.failover_threshold() {
+                                if let Err(err) = Self::failover(pg, &state) {
+                                    error!("Failed to fail over to this node: {:?}", err);
+                                }
+                            }
+                        }
+                        (HaRole::Primary, Ok(status)) if status.role == HaRole::Primary => {
+                            if Self::should_step_down(ha, &state.status(), &status) {
+                                error!(
+                                    "Both nodes are running as the primary, stepping down as node '{}' became the primary more recently. This node must be restarted to rejoin as the standby.",
+                                    peer.id
+                                );
+                                state.set_role(HaRole::Standby);
+                                stepped_down.store(true, Ordering::SeqCst);
+                            }
+                        }
+                        (HaRole::Primary, _) => {}
+                    }
+                })
+                .context("Failed to spawn ha thread")?;
+        }
+
+        Ok(Self {
+            state,
+            stepped_down,
+            stop: Mutex::new(Some(tx)),
+        })
+    }
+
+    /// Gets the current role of this node
+    pub fn state(&self) -> &HaState {
+        &self.state
+    }
+
+    /// Whether this node is healthy, a node which stepped down from
+    /// the primary role must be restarted to rejoin as the standby
+    pub fn healthy(&self) -> bool {
+        !self.stepped_down.load(Ordering::SeqCst)
+    }
+
+    /// Stops checking the health of the other node
+    pub fn terminate(&self) -> Result<()> {
+        self.stop
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock ha thread"))?
+            .take();
+
+        Ok(())
+    }
+
+    /// Promotes this node to the primary.
+    ///
+    /// Once promoted, SIGUSR2 is raised so the subsystems which only run
+    /// on the primary are started on the main thread.
+    fn failover(pg: &PostgresConf, state: &HaState) -> Result<()> {
+        warn!("Failing over to this node...");
+        replication::promote(pg)?;
+        state.set_role(HaRole::Primary);
+        info!("This node is now the primary");
+
+        nix::sys::signal::kill(nix::unistd::getpid(), nix::sys::signal::SIGUSR2)
+            .context("Failed to signal promotion")?;
+
+        Ok(())
+    }
+
+    /// When both nodes are running as the primary, eg after a network partition,
+    /// the node which became the primary least recently steps down
+    fn should_step_down(ha: &HaConfig, this: &HaStatus, peer: &HaStatus) -> bool {
+        match (this.primary_since, peer.primary_since) {
+            (Some(this), Some(peer)) if this != peer => this < peer,
+            _ => !ha.is_preferred_primary(),
+        }
+    }
+
+    fn client(ha: &HaConfig) -> Result<Client> {
+        Client::builder()
+            .timeout(ha.check_interval())
+            .build()
+            .context("Failed to initialise http client")
+    }
+
+    /// Retrieves the status of the other node of the pair
+    fn peer_status(client: &Client, peer: &HaNodeConfig) -> Result<HaStatus> {
+        let res = client
+            .get(format!("{}/api/ha", peer.url.trim_end_matches('/')))
+            .send()
+            .with_context(|| format!("Failed to connect to node '{}'", peer.id))?;
+
+        // The standby responds with 503 so clients are directed to the primary
+        match res.status() {
+            StatusCode::OK | StatusCode::SERVICE_UNAVAILABLE => {}
+            status => bail!("Unexpected response from node '{}': {status}", peer.id),
+        }
+
+        res.json::<HaStatus>()
+            .with_context(|| format!("Failed to parse status of node '{}'", peer.id))
+    }
+}
+
+impl Drop for HaCoordinator {
+    fn drop(&mut self) {
+        let _ = self.terminate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_config(node_id: &str) -> HaConfig {
+        HaConfig {
+            node_id: node_id.into(),
+            nodes: ["a", "b"]
+                .into_iter()
+                .map(|id| HaNodeConfig {
+                    id: id.into(),
+                    host: format!("{id}.internal"),
+                    url: format!("https://{id}.internal:65432"),
+                })
+                .collect(),
+            replication_password: "pass".into(),
+            check_interval_secs: Some(1),
+            failover_threshold: Some(1),
+        }
+    }
+
+    fn mock_status(node_id: &str, primary_since: Option<u64>) -> HaStatus {
+        HaStatus {
+            node_id: node_id.into(),
+            role: HaRole::Primary,
+            primary_since,
+        }
+    }
+
+    #[test]
+    fn test_should_step_down_least_recent_primary() {
+        let ha = mock_config("a");
+
+        assert!(HaCoordinator::should_step_down(
+            &ha,
+            &mock_status("a", Some(100)),
+            &mock_status("b", Some(200))
+        ));
+        assert!(!HaCoordinator::should_step_down(
+            &ha,
+            &mock_status("a", Some(200)),
+            &mock_status("b", Some(100))
+        ));
+    }
+
+    #[test]
+    fn test_should_step_down_tie_prefers_first_node() {
+        assert!(!HaCoordinator::should_step_down(
+            &mock_config("a"),
+            &mock_status("a", Some(100)),
+            &mock_status("b", Some(100))
+        ));
+        assert!(HaCoordinator::should_step_down(
+            &mock_config("b"),
+            &mock_status("b", None),
+            &mock_status("a", Some(100))
+        ));
+    }
+
+    #[test]
+    fn test_startup_role_peer_unreachable() {
+        let mut ha = mock_config("a");
+        ha.nodes[1].url = "http://127.0.0.1:1".into();
+
+        assert_eq!(HaCoordinator::startup_role(&ha).unwrap(), HaRole::Primary);
+    }
+}
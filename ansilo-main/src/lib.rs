@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     os::raw::c_int,
     panic,
     sync::{
@@ -10,43 +10,92 @@ use std::{
     time::Duration,
 };
 
-use crate::{args::Command, build::BuildInfo};
+use crate::{args::Command, build::BuildInfo, reload::ReloadPlan};
 use ansilo_auth::Authenticator;
+use ansilo_cluster::Cluster;
+use ansilo_config::diagnostic;
 use ansilo_connectors_all::{
     ConnectionPools, ConnectorEntityConfigs, Connectors, InternalConnection,
 };
-use ansilo_core::err::{Context, Result};
+use ansilo_core::{
+    config::{DataSourceConfig, JobConfig, NodeConfig},
+    err::{Context, Result},
+};
 use ansilo_jobs::JobScheduler;
 use ansilo_logging::{error, info, trace, warn};
-use ansilo_pg::{fdw::server::FdwServer, handler::PostgresConnectionHandler, PostgresInstance};
+use ansilo_pg::{
+    fdw::server::FdwServer,
+    handler::PostgresConnectionHandler,
+    replication::{self, HaState},
+    PostgresInstance,
+};
 use ansilo_proxy::{conf::HandlerConf, server::ProxyServer};
 use ansilo_util_health::Health;
 use ansilo_web::{Http1ConnectionHandler, Http2ConnectionHandler, HttpApi, HttpApiState};
 use clap::Parser;
 use signal_hook::{
-    consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM, SIGUSR1},
+    consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM, SIGUSR1, SIGUSR2},
     iterator::Signals,
 };
 
 pub mod args;
+pub mod backup;
 pub mod build;
+pub mod catalogs;
+pub mod cdc;
+pub mod checks;
 pub mod conf;
+pub mod daemon;
+pub mod data;
 pub mod dev;
+pub mod drift;
+pub mod encryption;
+mod fixtures;
+pub mod grants;
+pub mod ha;
+pub mod materialize;
+pub mod peer_sync;
+pub mod privileges;
+pub mod probe;
+pub mod publish;
+mod reload;
+pub mod status;
+pub mod syncs;
+pub mod systemd;
 
 pub use ansilo_pg::fdw::log::RemoteQueryLog;
 
 use build::*;
+use cdc::ChangeCapture;
 use conf::*;
+use drift::DriftDetector;
+use encryption::{EncryptedVolume, EncryptionKey};
+use ha::HaCoordinator;
+use peer_sync::PeerCatalogSync;
+use probe::DataSourceProbes;
+use publish::ChangePublisher;
 use tokio::runtime::Runtime;
 
+/// The interval at which the health of each subsystem is checked
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The number of consecutive failed health checks before a subsystem is marked unhealthy
+const HEALTH_FAILURE_THRESHOLD: u32 = 3;
+
+/// The number of consecutive successful health checks before a subsystem is marked healthy again
+const HEALTH_SUCCESS_THRESHOLD: u32 = 2;
+
+/// The minimum ratio of free disk space on the data directory before it is reported as unhealthy
+const MIN_FREE_DISK_SPACE: f64 = 0.05;
+
 /// This struct represents a running instance of ansilo and its subsystems.
 ///
 /// This is the entrypoint to build, start and manage the instance.
 pub struct Ansilo {
     /// The command used to start the instance
     command: Command,
-    /// The configuration used
-    conf: &'static AppConf,
+    /// The configuration used, replaced when it is reloaded
+    conf: Arc<AppConf>,
     /// Running subsystems
     subsystems: Option<Subsystems>,
     /// Remote query log
@@ -55,6 +104,12 @@ pub struct Ansilo {
     health: Health,
     /// Whether the instance has been terminated
     term: Arc<AtomicBool>,
+    /// The key used to encrypt the log file at rest
+    key: Option<EncryptionKey>,
+    /// The encrypted volume mounted at the data directory
+    volume: Option<EncryptedVolume>,
+    /// The objects created by the build stages, tracked in dev mode
+    tracker: Option<BuildTracker>,
 }
 
 pub struct Subsystems {
@@ -72,6 +127,22 @@ pub struct Subsystems {
     http: HttpApi,
     /// The job scheduler
     scheduler: JobScheduler,
+    /// The data source health probes
+    probes: DataSourceProbes,
+    /// Checks the entities for drift from their data sources
+    drift: DriftDetector,
+    /// Keeps the entities imported from peers in sync
+    peer_sync: PeerCatalogSync,
+    /// Applies the changes captured from data sources to materialized entities
+    change_capture: ChangeCapture,
+    /// Publishes the changes to local tables to kafka
+    publisher: ChangePublisher,
+    /// Handler for connections to postgres
+    pg_handler: PostgresConnectionHandler,
+    /// Coordinates the role of this node, if running as one of a high-availability pair
+    ha: Option<HaCoordinator>,
+    /// Membership of the cluster, if running as a member of a cluster
+    cluster: Option<Cluster>,
 }
 
 impl Ansilo {
@@ -79,6 +150,7 @@ impl Ansilo {
     /// Here, we start the initial launch sequence.
     pub fn main() {
         ansilo_logging::init_logging().unwrap();
+        diagnostic::install_report_hook();
         info!("Hi, thanks for using Ansilo!");
 
         let cmd = Command::parse();
@@ -104,6 +176,11 @@ impl Ansilo {
         let args = command.args();
         let log = log.unwrap_or_default();
 
+        if let Command::EncryptSecret(_) = &command {
+            encrypt_secret()?;
+            std::process::exit(0);
+        }
+
         // Load configuration
         let config_path = args.config();
 
@@ -119,9 +196,91 @@ impl Ansilo {
             std::process::exit(0);
         }
 
+        // The node is stopped during a restore so the config may not yet exist
+        if let Command::Restore(args) = &command {
+            backup::restore(args)?;
+            std::process::exit(0);
+        }
+
+        // Detach from the terminal before any threads are spawned, which
+        // includes those of the http client used to fetch remote config
+        if let Command::Run(args) = &command {
+            if args.daemon {
+                daemon::daemonize(args)?;
+            }
+        }
+
         // We are happy to let the app-wide config leak for the rest of the program
         let conf: &'static _ = Box::leak(Box::new(init_conf(&config_path, &args)?));
 
+        if let Some(level) = conf.node.logging.level.as_deref() {
+            if let Err(err) = ansilo_logging::set_filter(Some(level)) {
+                warn!("Failed to set log filter: {:?}", err);
+            }
+        }
+
+        if let Command::Export(args) = &command {
+            data::export(conf, args)?;
+            std::process::exit(0);
+        }
+
+        if let Command::Import(args) = &command {
+            data::import(conf, args)?;
+            std::process::exit(0);
+        }
+
+        if let Command::Backup(args) = &command {
+            backup::backup(conf, args)?;
+            std::process::exit(0);
+        }
+
+        if let Command::Status(args) = &command {
+            let healthy = status::status(conf, args)?;
+            std::process::exit(if healthy { 0 } else { 1 });
+        }
+
+        // Fetching the key may spawn threads so it is done after forking
+        let key = conf
+            .node
+            .encryption
+            .as_ref()
+            .map(EncryptionKey::fetch)
+            .transpose()?;
+        if let Command::Run(args) = &command {
+            daemon::attach_log_file(&args.log_file(&conf.node), key.as_ref())?;
+        }
+
+        // Bind the listening port and load the TLS certificate before dropping
+        // privileges so privileged ports and root-owned certs can be used
+        let proxy_sockets = if command.is_build() {
+            vec![]
+        } else {
+            ProxyServer::bind(&proxy_addrs(conf)).context("Failed to bind proxy server")?
+        };
+        let tls = init_tls_conf(conf)?;
+
+        if let Command::Run(args) = &command {
+            privileges::drop_privileges(args.run_as_user.as_deref(), args.run_as_group.as_deref())?;
+        }
+
+        // Plugins are only loaded once privileges have been dropped
+        load_plugins(conf, args)?;
+
+        // The data directory is mounted from the encrypted volume, so its files are only
+        // stored on disk encrypted. This is done after dropping privileges so the volume
+        // is mounted by, and its files are owned by, the postgres user
+        let volume = match (key.as_ref(), conf.node.encryption.as_ref()) {
+            (Some(key), Some(encryption)) => {
+                let volume = EncryptedVolume::for_data_dir(encryption, &conf.pg.data_dir);
+                info!("Mounting encrypted data directory...");
+                volume
+                    .mount(key)
+                    .context("Failed to mount encrypted data directory")?;
+                Some(volume)
+            }
+            _ => None,
+        };
+
         if command.is_dev() {
             thread::spawn(|| {
                 dev::signal_on_sql_update(conf);
@@ -137,6 +296,14 @@ impl Ansilo {
 
         let pools = Self::init_connectors(conf)?;
 
+        if command.is_dev() {
+            fixtures::load_memory(&conf.node, &pools)?;
+        }
+        let probe_pools = pools
+            .iter()
+            .map(|(id, (pool, _))| (id.clone(), pool.clone()))
+            .collect::<HashMap<_, _>>();
+
         info!("Starting fdw listener...");
         let fdw = FdwServer::start(
             &conf.node,
@@ -146,52 +313,148 @@ impl Ansilo {
         )
         .context("Failed to start fdw server")?;
 
+        // Scans of the materialized entities are served from their snapshots
+        if let Some(snapshots) = materialize::snapshots(&conf.node, &conf.pg)? {
+            fdw.replace_snapshots(snapshots)?;
+        }
+
         info!("Starting authenticator...");
         let authenticator = Authenticator::init(&conf.node.auth)?;
 
-        let (mut postgres, build_info) = if let (Command::Run(_), false, Some(build_info)) =
+        // Determine the role of this node before postgres is started
+        let ha = match (&command, conf.node.ha.as_ref()) {
+            (Command::Run(_), Some(ha)) => {
+                let role = HaCoordinator::startup_role(ha)?;
+                info!("Starting as the {role:?} of the high-availability pair");
+                Some(HaState::new(ha.node_id.clone(), role))
+            }
+            _ => None,
+        };
+        let standby = ha.as_ref().map(|ha| !ha.is_primary()) == Some(true);
+
+        // In dev mode the objects created by the build stages are tracked
+        // so changes can be applied in place
+        let mut tracker = command.is_dev().then(BuildTracker::default);
+
+        let (mut postgres, build_info) = if standby {
+            if !replication::is_standby(&conf.pg) {
+                replication::clone_from_primary(&conf.pg)?;
+            }
+
+            info!("Starting postgres as standby...");
+            let pg = runtime.block_on(PostgresInstance::start(&conf.pg))?;
+            let build_info = match BuildInfo::fetch(conf)? {
+                Some(build_info) => build_info,
+                None => {
+                    let build_info = BuildInfo::new();
+                    build_info.store(conf)?;
+                    build_info
+                }
+            };
+            (pg, build_info)
+        } else if let (Command::Run(_), false, Some(build_info)) =
             (&command, args.force_build, BuildInfo::fetch(conf)?)
         {
             info!("Build occurred at {}", build_info.built_at().to_rfc3339());
             info!("Starting postgres...");
             let pg = runtime.block_on(PostgresInstance::start(&conf.pg))?;
+
+            // The previous primary is unavailable so this standby takes over
+            if ha.is_some() && replication::is_standby(&conf.pg) {
+                replication::promote(&conf.pg)?;
+            }
+
             (pg, build_info)
         } else {
-            runtime.block_on(build(conf, authenticator.clone()))?
+            runtime.block_on(build(conf, authenticator.clone(), tracker.as_mut()))?
         };
 
-        let health = Health::new();
+        let health =
+            Health::new().with_thresholds(HEALTH_FAILURE_THRESHOLD, HEALTH_SUCCESS_THRESHOLD);
+        health.register(checks::DiskSpaceCheck::new(
+            "Disk",
+            conf.pg.data_dir.clone(),
+            MIN_FREE_DISK_SPACE,
+        ))?;
         let term = Arc::new(AtomicBool::new(false));
 
         if command.is_build() {
+            if let Some(volume) = volume.as_ref() {
+                postgres.terminate()?;
+                volume.unmount()?;
+            }
+
             info!("Build complete...");
             return Ok(Self {
                 command,
-                conf,
+                conf: Arc::new(conf.clone()),
                 subsystems: None,
                 log,
                 health,
                 term,
+                key,
+                volume: None,
+                tracker,
             });
         }
 
         let pg_con_handler =
             PostgresConnectionHandler::new(authenticator.clone(), postgres.connections().clone());
 
-        runtime.block_on(runtime_build(conf, &pg_con_handler))?;
+        // The standby is read-only until it is promoted
+        if !standby {
+            runtime.block_on(runtime_build(conf, &pg_con_handler, tracker.as_mut()))?;
+
+            if command.is_dev() {
+                runtime.block_on(fixtures::load(&conf.node, &pg_con_handler))?;
+            }
+        }
+
+        let cluster = match conf.node.cluster.as_ref() {
+            Some(cluster) => {
+                info!("Joining cluster as node '{}'...", cluster.node_id);
+                let store = ansilo_cluster::store::connect(
+                    cluster,
+                    &conf.node.name,
+                    runtime.handle().clone(),
+                )
+                .context("Failed to initialise cluster store")?;
+                let cache = fdw.metadata_cache().clone();
+
+                Some(
+                    Cluster::start(cluster, store, move |source| {
+                        if let Err(err) = cache.invalidate(source) {
+                            warn!("Failed to invalidate metadata cache: {:?}", err);
+                        }
+                    })
+                    .context("Failed to join cluster")?,
+                )
+            }
+            None => None,
+        };
 
         info!("Starting http api...");
-        let http = runtime.block_on(HttpApi::start(HttpApiState::new(
+        let mut http_state = HttpApiState::new(
             &conf.node,
             postgres.connections().clone(),
             pg_con_handler.clone(),
             health.clone(),
+            fdw.metadata_cache().clone(),
+            log.clone(),
             (&build_info).into(),
-        )))?;
+        );
+        if let Some(ha) = ha.as_ref() {
+            http_state = http_state.with_ha(ha.clone());
+        }
+        if let Some(cluster) = cluster.as_ref() {
+            http_state = http_state.with_cluster(cluster.state().clone());
+        }
+        let http = runtime.block_on(HttpApi::start(http_state))?;
 
         info!("Starting proxy server...");
         let proxy_conf = Box::leak(Box::new(init_proxy_conf(
             conf,
+            tls,
             HandlerConf::new(
                 pg_con_handler.clone(),
                 Http2ConnectionHandler::new(http.handler()),
@@ -201,17 +464,100 @@ impl Ansilo {
 
         let mut proxy = ProxyServer::new(proxy_conf);
         runtime
-            .block_on(proxy.start())
+            .block_on(proxy.start_with(proxy_sockets))
             .context("Failed to start proxy server")?;
 
+        let peer_sync = PeerCatalogSync::new(
+            pg_con_handler.clone(),
+            runtime.handle().clone(),
+            fdw.metadata_cache().clone(),
+        );
+        let change_capture = ChangeCapture::new(pg_con_handler.clone(), runtime.handle().clone());
+        let publisher = ChangePublisher::new(pg_con_handler.clone(), runtime.handle().clone());
+
         info!("Staring job scheduler...");
+        let jobs = materialize::jobs(&conf.node)?;
         let mut scheduler =
-            JobScheduler::new(&conf.node.jobs, runtime.handle().clone(), pg_con_handler);
-        scheduler.start().context("Failed to start job scheduler")?;
+            JobScheduler::new(jobs, runtime.handle().clone(), pg_con_handler.clone())
+                .with_builtin_handler(peer_sync.import_job_handler())
+                .with_node_address(script_address(conf));
+        if let Some(cluster) = cluster.as_ref() {
+            // Snapshots and imported peer entities are local to each member so these
+            // jobs run on every member, while other jobs only run on the leader
+            let state = cluster.state().clone();
+            scheduler = scheduler.with_guard(Arc::new(move |job: &JobConfig| {
+                materialize::is_refresh_job(job) || job.peer_import.is_some() || state.is_leader()
+            }));
+        }
+        if !standby {
+            scheduler.start().context("Failed to start job scheduler")?;
+
+            // Empty snapshots are populated in the background so start up is not blocked
+            let handler = pg_con_handler.clone();
+            runtime.spawn(async move { materialize::populate_empty(&conf.node, &handler).await });
+        }
+
+        info!("Starting data source probes...");
+        let fdw_handle = fdw.handle();
+        let drift = DriftDetector::new(
+            pg_con_handler.clone(),
+            runtime.handle().clone(),
+            probe_pools.clone(),
+            health.clone(),
+        )
+        .with_regenerate_handler(Arc::new(
+            move |node: &Arc<NodeConfig>, data_source_id: &str| {
+                // New fdw connections use the regenerated entities of the data source
+                let source = node
+                    .sources
+                    .iter()
+                    .find(|s| s.id == data_source_id)
+                    .with_context(|| format!("Unknown data source '{data_source_id}'"))?;
+                let (_, entities) = Self::init_connection_pool(node, source)?;
+
+                fdw_handle.replace_entities(data_source_id, entities)?;
+                fdw_handle.replace_config(Arc::clone(node))
+            },
+        ));
+        let probes = DataSourceProbes::start(probe_pools, health.clone(), HEALTH_CHECK_INTERVAL)
+            .context("Failed to start data source probes")?;
+
+        if !standby {
+            info!("Starting peer catalog sync...");
+            peer_sync
+                .start(&conf.node)
+                .context("Failed to start peer catalog sync")?;
+
+            info!("Starting change data capture...");
+            change_capture
+                .start(&conf.node)
+                .context("Failed to start change data capture")?;
+
+            info!("Starting change publication...");
+            publisher
+                .start(&conf.node)
+                .context("Failed to start change publication")?;
+
+            info!("Starting drift detection...");
+            drift
+                .start(&conf.node)
+                .context("Failed to start drift detection")?;
+        }
+
+        let ha = match (ha, conf.node.ha.as_ref()) {
+            (Some(state), Some(ha)) => {
+                info!("Starting high-availability coordinator...");
+                Some(
+                    HaCoordinator::start(ha, &conf.pg, state)
+                        .context("Failed to start high-availability coordinator")?,
+                )
+            }
+            _ => None,
+        };
 
         let instance = Self {
             command,
-            conf,
+            conf: Arc::new(conf.clone()),
             subsystems: Some(Subsystems {
                 runtime,
                 postgres,
@@ -220,15 +566,27 @@ impl Ansilo {
                 authenticator,
                 http,
                 scheduler,
+                probes,
+                drift,
+                peer_sync,
+                change_capture,
+                publisher,
+                pg_handler: pg_con_handler,
+                ha,
+                cluster,
             }),
             log,
             health,
             term,
+            key,
+            volume,
+            tracker,
         };
 
         instance.check_health();
 
         info!("Start up complete...");
+        systemd::notify_ready();
         Ok(instance)
     }
 
@@ -247,18 +605,28 @@ impl Ansilo {
         &self.log
     }
 
+    /// Gets the health state, custom checks can be registered on
+    /// this to be run alongside the built-in subsystems
+    pub fn health(&self) -> &Health {
+        &self.health
+    }
+
     /// Waits for instance to terminate
     pub fn wait(mut self) -> Result<()> {
         if self.command.is_build() {
             return Ok(());
         }
 
-        // Update service health every 30s
+        // Update service health periodically, if the systemd watchdog is enabled
+        // we check at least as often as keep-alives are required
+        let interval = systemd::watchdog_interval()
+            .map_or(HEALTH_CHECK_INTERVAL, |i| i.min(HEALTH_CHECK_INTERVAL));
+
         self.check_health();
         let term = Arc::clone(&self.term);
         thread::spawn(move || {
             while !term.load(Ordering::SeqCst) {
-                thread::sleep(Duration::from_secs(30));
+                thread::sleep(interval);
                 let _ = nix::sys::signal::kill(nix::unistd::getpid(), nix::sys::signal::SIGUSR1);
             }
         });
@@ -271,6 +639,31 @@ impl Ansilo {
                 continue;
             }
 
+            // Raised once this node has been promoted to the primary
+            if sig == SIGUSR2 {
+                if let Err(err) = self.on_promoted() {
+                    error!("Failed to start primary subsystems: {:?}", err);
+                }
+                continue;
+            }
+
+            // Outside of dev mode we reload the config in place
+            if sig == SIGHUP && !self.command.is_dev() {
+                if let Err(err) = self.reload() {
+                    error!("Failed to reload configuration: {:?}", err);
+                }
+                continue;
+            }
+
+            // In dev mode we rebuild in place where possible, otherwise we restart
+            if sig == SIGHUP {
+                match self.dev_reload() {
+                    Ok(true) => continue,
+                    Ok(false) => info!("Changes cannot be applied in place"),
+                    Err(err) => error!("Failed to apply changes in place: {:?}", err),
+                }
+            }
+
             break sig;
         };
 
@@ -292,6 +685,35 @@ impl Ansilo {
         self.term.store(true, Ordering::SeqCst);
 
         info!("Terminating...");
+        systemd::notify_stopping();
+        if let Some(ha) = subsystems.ha.as_ref() {
+            if let Err(err) = ha.terminate() {
+                warn!(
+                    "Failed to terminate high-availability coordinator: {:?}",
+                    err
+                );
+            }
+        }
+        if let Some(cluster) = subsystems.cluster.as_ref() {
+            if let Err(err) = cluster.terminate() {
+                warn!("Failed to leave cluster: {:?}", err);
+            }
+        }
+        if let Err(err) = subsystems.probes.terminate() {
+            warn!("Failed to terminate data source probes: {:?}", err);
+        }
+        if let Err(err) = subsystems.drift.terminate() {
+            warn!("Failed to terminate drift detection: {:?}", err);
+        }
+        if let Err(err) = subsystems.peer_sync.terminate() {
+            warn!("Failed to terminate peer catalog sync: {:?}", err);
+        }
+        if let Err(err) = subsystems.change_capture.terminate() {
+            warn!("Failed to terminate change data capture: {:?}", err);
+        }
+        if let Err(err) = subsystems.publisher.terminate() {
+            warn!("Failed to terminate change publication: {:?}", err);
+        }
         if let Err(err) = subsystems.scheduler.terminate() {
             warn!("Failed to terminate job scheduler: {:?}", err);
         }
@@ -315,6 +737,24 @@ impl Ansilo {
 
         info!("Shutdown sequence complete");
 
+        if let Some(volume) = self.volume.as_ref() {
+            if let Err(err) = volume.unmount() {
+                error!("Failed to unmount encrypted data directory: {:?}", err);
+            }
+        }
+
+        if let Command::Run(args) = &self.command {
+            if args.daemon {
+                daemon::remove_pid_file(args);
+
+                if let Some(key) = self.key.as_ref() {
+                    if let Err(err) = daemon::seal_log_file(key) {
+                        error!("Failed to encrypt log file: {:?}", err);
+                    }
+                }
+            }
+        }
+
         // If we are running in dev-mode, restart the process
         if self.command.is_dev() && sig == Some(SIGHUP) {
             dev::restart();
@@ -323,6 +763,259 @@ impl Ansilo {
         Ok(())
     }
 
+    /// Starts the subsystems which only run on the primary
+    /// once this node has been promoted from the standby
+    fn on_promoted(&mut self) -> Result<()> {
+        let conf = Arc::clone(&self.conf);
+        let subsystems = match self.subsystems.as_mut() {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+
+        info!("Starting primary subsystems...");
+        subsystems
+            .runtime
+            .block_on(runtime_build(&conf, &subsystems.pg_handler, None))?;
+        subsystems
+            .scheduler
+            .start()
+            .context("Failed to start job scheduler")?;
+        let handler = subsystems.pg_handler.clone();
+        let node = Arc::clone(&conf.node);
+        subsystems
+            .runtime
+            .spawn(async move { materialize::populate_empty(&node, &handler).await });
+        subsystems
+            .peer_sync
+            .start(&conf.node)
+            .context("Failed to start peer catalog sync")?;
+        subsystems
+            .change_capture
+            .start(&conf.node)
+            .context("Failed to start change data capture")?;
+        subsystems
+            .publisher
+            .start(&conf.node)
+            .context("Failed to start change publication")?;
+        subsystems
+            .drift
+            .start(&conf.node)
+            .context("Failed to start drift detection")?;
+
+        Ok(())
+    }
+
+    /// Reloads the configuration file and applies any changes which
+    /// can be made without restarting the instance.
+    ///
+    /// Changes which require a restart or rebuild are logged but not applied.
+    fn reload(&mut self) -> Result<()> {
+        let subsystems = match self.subsystems.as_mut() {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+
+        info!("Reloading configuration...");
+        let new = init_conf(&self.conf.path, self.command.args())?;
+        load_plugins(&new, self.command.args())?;
+        let plan = ReloadPlan::new(&self.conf.node, &new.node, &self.conf.pg.app_users);
+
+        if plan.is_empty() {
+            info!("No configuration changes detected");
+            return Ok(());
+        }
+
+        // The reloaded subsystems share the updated config, the previous
+        // config is dropped once the subsystems have released it
+        let node = Arc::new(plan.apply_to(&self.conf.node, &new.node));
+
+        Self::apply_reload(subsystems, &plan, &node)?;
+
+        for change in plan.applied.iter() {
+            info!("Applied config change: {change}");
+        }
+        for change in plan.requires_rebuild.iter() {
+            warn!("Config change not applied: {change}");
+        }
+        for change in plan.requires_restart.iter() {
+            warn!("Config change not applied: {change}");
+        }
+
+        self.conf = Arc::new(AppConf {
+            node,
+            path: self.conf.path.clone(),
+            pg: self.conf.pg.clone(),
+        });
+
+        info!("Reload complete");
+        Ok(())
+    }
+
+    /// Applies changes in dev mode without restarting the instance.
+    ///
+    /// Along with the changes which can be reloaded, changes to the sql scripts,
+    /// entities and grants are applied by rebuilding the affected objects in place
+    /// so client connections are retained.
+    /// Returns `false` if the changes require a restart.
+    fn dev_reload(&mut self) -> Result<bool> {
+        let (subsystems, tracker) = match (self.subsystems.as_mut(), self.tracker.as_mut()) {
+            (Some(s), Some(t)) => (s, t),
+            _ => return Ok(false),
+        };
+
+        info!("Applying changes...");
+        let new = init_conf(&self.conf.path, self.command.args())?;
+        load_plugins(&new, self.command.args())?;
+        let plan = ReloadPlan::new(&self.conf.node, &new.node, &self.conf.pg.app_users);
+
+        if !plan.requires_restart.is_empty() {
+            for change in plan.requires_restart.iter() {
+                info!("Config change requires restart: {change}");
+            }
+            return Ok(false);
+        }
+
+        // The watched paths are fixed when the instance started
+        if dev::watched_paths(&self.conf.node) != dev::watched_paths(&new.node) {
+            info!("Watched paths changed, requires restart");
+            return Ok(false);
+        }
+
+        let node = Arc::new(plan.apply_with_rebuild_to(&self.conf.node, &new.node));
+
+        Self::apply_reload(subsystems, &plan, &node)?;
+
+        // Reload the entity configs of the data sources of the changed entities
+        let sources = [&self.conf.node, &node]
+            .into_iter()
+            .flat_map(|n| n.entities.iter())
+            .filter(|e| plan.entities.contains(&e.id))
+            .map(|e| e.source.data_source.clone())
+            .collect::<HashSet<_>>();
+
+        for id in sources.iter() {
+            let source = match node.sources.iter().find(|s| &s.id == id) {
+                Some(s) => s,
+                None => continue,
+            };
+            let (_, entities) = Self::init_connection_pool(&node, source)?;
+
+            subsystems
+                .fdw
+                .replace_entities(id, entities)
+                .with_context(|| format!("Failed to reload entities of data source '{id}'"))?;
+        }
+
+        subsystems.fdw.replace_config(Arc::clone(&node))?;
+
+        let conf = AppConf {
+            node,
+            path: self.conf.path.clone(),
+            pg: self.conf.pg.clone(),
+        };
+
+        subsystems.runtime.block_on(rebuild(
+            &conf,
+            &subsystems.pg_handler,
+            tracker,
+            &plan.entities,
+        ))?;
+
+        for change in plan.applied.iter().chain(plan.requires_rebuild.iter()) {
+            info!("Applied config change: {change}");
+        }
+
+        self.conf = Arc::new(conf);
+
+        info!("Changes applied");
+        Ok(true)
+    }
+
+    /// Applies the reloadable changes in the plan to the running subsystems
+    fn apply_reload(
+        subsystems: &mut Subsystems,
+        plan: &ReloadPlan,
+        node: &Arc<NodeConfig>,
+    ) -> Result<()> {
+        if plan.logging {
+            ansilo_logging::set_filter(node.logging.level.as_deref())
+                .context("Failed to update log filter")?;
+        }
+
+        if let Some(users) = plan.users.as_ref() {
+            subsystems
+                .authenticator
+                .reload_users(users.clone())
+                .context("Failed to reload users")?;
+        }
+
+        for id in plan.sources.iter() {
+            let source = node
+                .sources
+                .iter()
+                .find(|s| &s.id == id)
+                .context("Failed to find data source")?;
+            let (pool, _) = Self::init_connection_pool(node, source)?;
+
+            subsystems
+                .fdw
+                .replace_pool(id, pool.clone())
+                .with_context(|| format!("Failed to reload data source '{id}'"))?;
+            subsystems
+                .drift
+                .replace_pool(id, pool.clone())
+                .with_context(|| {
+                    format!("Failed to reload drift detection of data source '{id}'")
+                })?;
+            subsystems
+                .probes
+                .replace_pool(id, pool)
+                .with_context(|| format!("Failed to reload probe of data source '{id}'"))?;
+        }
+
+        // New connections read the cache and admission settings from the node config
+        if !plan.source_settings.is_empty() {
+            subsystems
+                .fdw
+                .replace_config(Arc::clone(node))
+                .context("Failed to reload data source settings")?;
+        }
+
+        if !plan.sources.is_empty() && subsystems.is_primary() {
+            subsystems
+                .peer_sync
+                .reload(node)
+                .context("Failed to reload peer catalog sync")?;
+            subsystems
+                .change_capture
+                .reload(node)
+                .context("Failed to reload change data capture")?;
+        }
+
+        if plan.publish && subsystems.is_primary() {
+            subsystems
+                .publisher
+                .reload(node)
+                .context("Failed to reload change publication")?;
+        }
+
+        if plan.drift && subsystems.is_primary() {
+            subsystems
+                .drift
+                .reload(node)
+                .context("Failed to reload drift detection")?;
+        }
+
+        if plan.jobs {
+            subsystems
+                .scheduler
+                .reload(materialize::jobs(node)?)
+                .context("Failed to reload job scheduler")?;
+        }
+
+        Ok(())
+    }
+
     fn init_connectors(
         conf: &'static AppConf,
     ) -> Result<HashMap<String, (ConnectionPools, ConnectorEntityConfigs)>> {
@@ -333,15 +1026,7 @@ impl Ansilo {
             .iter()
             .map(|i| {
                 info!("Initializing connector: {}", i.id);
-                let connector = Connectors::from_type(&i.r#type)
-                    .with_context(|| format!("Unknown connector type: {}", i.r#type))?;
-                let options = connector
-                    .parse_options(i.options.clone())
-                    .context("Failed to parse options")?;
-
-                let pool = connector
-                    .create_connection_pool(&conf.node, &i.id, options)
-                    .context("Failed to create connection pool")?;
+                let pool = Self::init_connection_pool(&conf.node, i)?;
 
                 Ok((i.id.clone(), pool))
             })
@@ -358,6 +1043,21 @@ impl Ansilo {
         Ok(pools)
     }
 
+    fn init_connection_pool(
+        nc: &NodeConfig,
+        source: &DataSourceConfig,
+    ) -> Result<(ConnectionPools, ConnectorEntityConfigs)> {
+        let connector = Connectors::from_type(&source.r#type)
+            .with_context(|| format!("Unknown connector type: {}", source.r#type))?;
+        let options = connector
+            .parse_options(source.options.clone())
+            .context("Failed to parse options")?;
+
+        connector
+            .create_connection_pool(nc, &source.id, options)
+            .context("Failed to create connection pool")
+    }
+
     /// Updates the health of the each subsystem
     fn check_health(&self) {
         if let Some(ref subsystems) = self.subsystems {
@@ -372,14 +1072,32 @@ impl Ansilo {
             let _ = self.health.update("Proxy", subsystems.proxy().healthy());
             let _ = self.health.update("FDW", subsystems.fdw().healthy());
             let _ = self.health.update("HTTP", subsystems.http().healthy());
-            let _ = self
-                .health
-                .update("Scheduler", subsystems.scheduler().healthy());
+            // The scheduler only runs on the primary
+            let _ = self.health.update(
+                "Scheduler",
+                subsystems.scheduler().healthy() || !subsystems.is_primary(),
+            );
+            if let Some(ha) = subsystems.ha() {
+                let _ = self.health.update("HA", ha.healthy());
+            }
+            if let Some(cluster) = subsystems.cluster() {
+                let _ = self.health.update("Cluster", cluster.healthy());
+            }
+
+            if let Err(err) = self.health.run_checks() {
+                warn!("Failed to run health checks: {:?}", err);
+            }
+
+            // Only keep the systemd watchdog alive while we are healthy
+            // so an unhealthy instance is restarted
+            if let Ok(true) = self.health.healthy() {
+                systemd::notify_watchdog();
+            }
         }
     }
 
     fn wait_for_signal() -> Result<i32> {
-        let mut sigs = Signals::new(&[SIGINT, SIGQUIT, SIGTERM, SIGHUP, SIGUSR1])
+        let mut sigs = Signals::new(&[SIGINT, SIGQUIT, SIGTERM, SIGHUP, SIGUSR1, SIGUSR2])
             .context("Failed to attach signal handler")?;
         let sig = sigs.forever().next().unwrap();
 
@@ -390,6 +1108,7 @@ impl Ansilo {
                 SIGQUIT => "SIGQUIT".into(),
                 SIGTERM => "SIGTERM".into(),
                 SIGHUP => "SIGHUP".into(),
+                SIGUSR2 => "SIGUSR2".into(),
                 SIGUSR1 => return Ok(sig),
                 _ => format!("unknown signal {}", sig),
             }
@@ -435,4 +1154,38 @@ impl Subsystems {
     pub fn scheduler(&self) -> &JobScheduler {
         &self.scheduler
     }
+
+    pub fn probes(&self) -> &DataSourceProbes {
+        &self.probes
+    }
+
+    pub fn drift(&self) -> &DriftDetector {
+        &self.drift
+    }
+
+    pub fn peer_sync(&self) -> &PeerCatalogSync {
+        &self.peer_sync
+    }
+
+    pub fn change_capture(&self) -> &ChangeCapture {
+        &self.change_capture
+    }
+
+    pub fn publisher(&self) -> &ChangePublisher {
+        &self.publisher
+    }
+
+    pub fn ha(&self) -> Option<&HaCoordinator> {
+        self.ha.as_ref()
+    }
+
+    pub fn cluster(&self) -> Option<&Cluster> {
+        self.cluster.as_ref()
+    }
+
+    /// Whether this node is the primary, nodes which are not
+    /// part of a high-availability pair are always the primary
+    pub fn is_primary(&self) -> bool {
+        self.ha.as_ref().map_or(true, |ha| ha.state().is_primary())
+    }
 }
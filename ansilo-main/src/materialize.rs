@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+
+use ansilo_connectors_base::common::entity::EntitySource;
+use ansilo_connectors_native_postgres::{
+    PostgresConnectionConfig, PostgresConnectionPool, PostgresEntitySourceConfig,
+    PostgresTableOptions,
+};
+use ansilo_core::{
+    config::{
+        CronTriggerConfig, EntityConfig, EntityMaterializeConfig, JobConfig, JobTriggerConfig,
+        MaterializeMode, NodeConfig,
+    },
+    err::{Context, Result},
+};
+use ansilo_logging::{debug, info, warn};
+use ansilo_pg::{
+    conf::PostgresConf,
+    fdw::snapshot::{SnapshotEntities, Snapshots},
+    handler::PostgresConnectionHandler,
+    PG_ADMIN_USER, PG_DATABASE, PG_PORT,
+};
+use ansilo_util_pg::query::pg_quote_identifier;
+
+use crate::syncs;
+
+/// The schema containing the foreign tables which the snapshots are refreshed from
+const MATERIALIZE_SOURCE_SCHEMA: &str = "ansilo_materialize";
+
+/// The prefix of the ids of the jobs which refresh the snapshots
+const REFRESH_JOB_PREFIX: &str = "materialize:";
+
+/// The schema of the snapshot tables, which are only accessible to the admin user.
+/// Users query the foreign tables of the entities which are served from the snapshots.
+const SNAPSHOT_SCHEMA: &str = "ansilo_snapshots";
+
+/// Returns the entities which are served from a local snapshot
+pub(crate) fn materialized(
+    node: &NodeConfig,
+) -> impl Iterator<Item = (&EntityConfig, &EntityMaterializeConfig)> {
+    node.entities
+        .iter()
+        .filter_map(|e| e.materialize.as_ref().map(|m| (e, m)))
+}
+
+/// Returns the entities of which the snapshot is refreshed on a schedule.
+///
+/// The snapshots of the entities materialized in cdc mode are instead
+/// populated and kept up to date by the change capture.
+fn scheduled(node: &NodeConfig) -> impl Iterator<Item = (&EntityConfig, &EntityMaterializeConfig)> {
+    materialized(node).filter(|(_, m)| m.mode != MaterializeMode::Cdc)
+}
+
+/// The qualified name of the local snapshot table of the entity
+pub fn snapshot_table(entity: &EntityConfig) -> String {
+    format!("{SNAPSHOT_SCHEMA}.{}", pg_quote_identifier(&entity.id))
+}
+
+/// The qualified name of the foreign table the snapshot is refreshed from
+fn source_table(entity: &EntityConfig) -> String {
+    format!(
+        "{MATERIALIZE_SOURCE_SCHEMA}.{}",
+        pg_quote_identifier(&entity.id)
+    )
+}
+
+/// The id of the job which refreshes the snapshot of the entity
+pub fn refresh_job_id(entity: &EntityConfig) -> String {
+    format!("{REFRESH_JOB_PREFIX}{}", entity.id)
+}
+
+/// Whether the job refreshes the snapshot of a materialized entity.
+///
+/// Snapshots are local to each node so these jobs run on every member of a cluster.
+pub fn is_refresh_job(job: &JobConfig) -> bool {
+    job.id.starts_with(REFRESH_JOB_PREFIX)
+}
+
+/// Creates the snapshot table of each materialized entity along with the
+/// foreign table it is refreshed from.
+///
+/// Both are only accessible to the admin user, users query the foreign tables
+/// imported from the data source which are served from the snapshots.
+///
+/// This is run when the database is initialised, the snapshots are
+/// populated afterwards using [`populate_empty`].
+pub(crate) fn init_sql(node: &NodeConfig) -> Vec<String> {
+    let mut sql = materialized(node)
+        .map(|(entity, conf)| {
+            let id = pg_quote_identifier(&entity.id);
+            let server = pg_quote_identifier(&entity.source.data_source);
+            let snapshot = snapshot_table(entity);
+            let source = source_table(entity);
+
+            let pks = entity
+                .primary_keys()
+                .iter()
+                .map(|a| pg_quote_identifier(&a.id))
+                .collect::<Vec<_>>();
+            let pk = if pks.is_empty() {
+                "".into()
+            } else {
+                format!(", PRIMARY KEY ({})", pks.join(", "))
+            };
+
+            let index = match (conf.mode, conf.watermark.as_ref()) {
+                (MaterializeMode::Incremental, Some(watermark)) => format!(
+                    "CREATE INDEX ON {snapshot} ({});",
+                    pg_quote_identifier(watermark)
+                ),
+                _ => "".into(),
+            };
+
+            format!(
+                r#"
+                IMPORT FOREIGN SCHEMA {id} LIMIT TO ({id})
+                FROM SERVER {server}
+                INTO {MATERIALIZE_SOURCE_SCHEMA};
+
+                CREATE TABLE {snapshot} (LIKE {source}{pk});
+                {index}
+            "#
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if sql.is_empty() {
+        return sql;
+    }
+
+    // The admin user runs the refresh jobs and reads the snapshots on behalf of users
+    sql.insert(
+        0,
+        format!("CREATE SCHEMA {MATERIALIZE_SOURCE_SCHEMA}; CREATE SCHEMA {SNAPSHOT_SCHEMA};"),
+    );
+    sql.push(format!(
+        r#"
+        GRANT USAGE ON SCHEMA {MATERIALIZE_SOURCE_SCHEMA} TO {PG_ADMIN_USER};
+        GRANT SELECT ON ALL TABLES IN SCHEMA {MATERIALIZE_SOURCE_SCHEMA} TO {PG_ADMIN_USER};
+        GRANT USAGE ON SCHEMA {SNAPSHOT_SCHEMA} TO {PG_ADMIN_USER};
+        GRANT ALL ON ALL TABLES IN SCHEMA {SNAPSHOT_SCHEMA} TO {PG_ADMIN_USER};
+    "#
+    ));
+
+    sql
+}
+
+/// Returns the sql which refreshes the snapshot of the entity from the remote source.
+///
+/// In full mode the snapshot is replaced in a single transaction so queries
+/// are never served from a partially refreshed snapshot.
+/// In incremental mode rows with a watermark greater than the highest in the
+/// snapshot are inserted, replacing existing rows with the same primary key.
+/// In cdc mode the snapshot is replaced as per full mode, before the captured
+/// changes are applied.
+pub fn refresh_sql(entity: &EntityConfig, conf: &EntityMaterializeConfig) -> Result<String> {
+    let snapshot = snapshot_table(entity);
+    let source = source_table(entity);
+
+    let watermark = match conf.mode {
+        MaterializeMode::Full | MaterializeMode::Cdc => {
+            return Ok(format!(
+                r#"
+                BEGIN;
+                DELETE FROM {snapshot};
+                INSERT INTO {snapshot} SELECT * FROM {source};
+                COMMIT;
+            "#
+            ))
+        }
+        MaterializeMode::Incremental => {
+            pg_quote_identifier(conf.watermark.as_ref().with_context(|| {
+                format!(
+                    "Entity '{}' must define a watermark to be materialized incrementally",
+                    entity.id
+                )
+            })?)
+        }
+    };
+
+    let pks = entity
+        .primary_keys()
+        .iter()
+        .map(|a| pg_quote_identifier(&a.id))
+        .collect::<Vec<_>>();
+    let updates = entity
+        .attributes
+        .iter()
+        .filter(|a| !a.primary_key)
+        .map(|a| {
+            let col = pg_quote_identifier(&a.id);
+            format!("{col} = EXCLUDED.{col}")
+        })
+        .collect::<Vec<_>>();
+
+    let on_conflict = match (pks.is_empty(), updates.is_empty()) {
+        (true, _) => "".into(),
+        (false, true) => format!("ON CONFLICT ({}) DO NOTHING", pks.join(", ")),
+        (false, false) => format!(
+            "ON CONFLICT ({}) DO UPDATE SET {}",
+            pks.join(", "),
+            updates.join(", ")
+        ),
+    };
+
+    Ok(format!(
+        r#"
+        DO $$
+        BEGIN
+            IF EXISTS (SELECT 1 FROM {snapshot}) THEN
+                INSERT INTO {snapshot}
+                SELECT * FROM {source}
+                WHERE {watermark} > (SELECT MAX({watermark}) FROM {snapshot})
+                {on_conflict};
+            ELSE
+                INSERT INTO {snapshot} SELECT * FROM {source};
+            END IF;
+        END $$;
+    "#
+    ))
+}
+
+/// Returns the snapshots which the scans of the materialized entities are served from.
+///
+/// The snapshots are read from the local postgres as the admin user, the access
+/// rules of the user are applied by the fdw as they would be to the data source.
+pub(crate) fn snapshots(node: &NodeConfig, pg: &PostgresConf) -> Result<Option<Snapshots>> {
+    if materialized(node).next().is_none() {
+        return Ok(None);
+    }
+
+    let mut entities = SnapshotEntities::new();
+
+    for (entity, _) in materialized(node) {
+        entities.add(EntitySource::new(
+            entity.clone(),
+            PostgresEntitySourceConfig::Table(PostgresTableOptions::new(
+                Some(SNAPSHOT_SCHEMA.into()),
+                entity.id.clone(),
+                HashMap::new(),
+            )),
+        ));
+    }
+
+    let pool = PostgresConnectionPool::new(PostgresConnectionConfig {
+        host: Some(pg.socket_dir_path.to_string_lossy().into()),
+        port: Some(PG_PORT),
+        user: Some(PG_ADMIN_USER.into()),
+        dbname: Some(PG_DATABASE.into()),
+        ..Default::default()
+    })
+    .context("Failed to create the connection pool of the snapshots")?;
+
+    Ok(Some(Snapshots::new(pool, entities)))
+}
+
+/// Returns the configured jobs along with a job to refresh each materialized entity
+/// and a job to run each sync
+pub fn jobs(node: &NodeConfig) -> Result<Vec<JobConfig>> {
+    let mut jobs = node.jobs.clone();
+    jobs.extend(syncs::jobs(node)?);
+
+    for (entity, conf) in scheduled(node) {
+        jobs.push(JobConfig {
+            id: refresh_job_id(entity),
+            name: Some(format!("Refresh snapshot of {}", entity.id)),
+            description: None,
+            service_user: None,
+            sql: refresh_sql(entity, conf)?,
+            catalog: None,
+            peer_import: None,
+            steps: vec![],
+            triggers: vec![JobTriggerConfig::Cron(CronTriggerConfig {
+                cron: conf.refresh.clone(),
+            })],
+        });
+    }
+
+    Ok(jobs)
+}
+
+/// Populates the snapshots of the entities refreshed on a schedule which are empty,
+/// such as when the node first starts.
+///
+/// Snapshots are retained in the data directory across restarts and are otherwise
+/// only refreshed by their jobs. This is run in the background once the node has
+/// started, failures are logged and the snapshot is populated by its next refresh.
+pub async fn populate_empty(node: &NodeConfig, handler: &PostgresConnectionHandler) {
+    let entities = scheduled(node).collect::<Vec<_>>();
+
+    if entities.is_empty() {
+        return;
+    }
+
+    let con = match handler.pool().admin().await {
+        Ok(con) => con,
+        Err(err) => {
+            warn!(
+                "Failed to connect to postgres to populate snapshots: {:?}",
+                err
+            );
+            return;
+        }
+    };
+
+    for (entity, conf) in entities {
+        let snapshot = snapshot_table(entity);
+
+        let res = async {
+            let empty: bool = con
+                .query_one(
+                    &format!("SELECT NOT EXISTS (SELECT 1 FROM {snapshot})"),
+                    &[],
+                )
+                .await
+                .context("Failed to check snapshot")?
+                .get(0);
+
+            if !empty {
+                debug!("Snapshot of entity {} is already populated", entity.id);
+                return Ok(());
+            }
+
+            info!("Materializing entity {}...", entity.id);
+            con.batch_execute(&refresh_sql(entity, conf)?)
+                .await
+                .context("Failed to refresh snapshot")
+        }
+        .await;
+
+        if let Err(err) = res {
+            warn!("Failed to materialize entity '{}': {:?}", entity.id, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::{
+        config::{EntityAttributeConfig, EntitySourceConfig},
+        data::DataType,
+    };
+
+    use super::*;
+    use crate::conf::pg_conf;
+
+    fn mock_entity(mode: MaterializeMode, watermark: Option<&str>) -> EntityConfig {
+        let mut entity = EntityConfig::minimal(
+            "orders",
+            vec![
+                EntityAttributeConfig::new("id".into(), None, DataType::Int64, true, false),
+                EntityAttributeConfig::minimal("status", DataType::rust_string()),
+                EntityAttributeConfig::minimal("updated_at", DataType::DateTime),
+            ],
+            EntitySourceConfig::minimal("mysql"),
+        );
+
+        entity.materialize = Some(EntityMaterializeConfig {
+            refresh: "0 */15 * * * *".into(),
+            mode,
+            watermark: watermark.map(|w| w.into()),
+        });
+
+        entity
+    }
+
+    fn mock_node(entities: Vec<EntityConfig>) -> NodeConfig {
+        NodeConfig {
+            entities,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_snapshot_table() {
+        let entity = mock_entity(MaterializeMode::Full, None);
+        assert_eq!(snapshot_table(&entity), r#"ansilo_snapshots."orders""#);
+    }
+
+    #[test]
+    fn test_init_sql_no_materialized_entities() {
+        let mut entity = mock_entity(MaterializeMode::Full, None);
+        entity.materialize = None;
+
+        assert!(init_sql(&mock_node(vec![entity])).is_empty());
+    }
+
+    #[test]
+    fn test_init_sql() {
+        let sql = init_sql(&mock_node(vec![mock_entity(
+            MaterializeMode::Incremental,
+            Some("updated_at"),
+        )]))
+        .join("\n");
+
+        assert!(sql.contains("CREATE SCHEMA ansilo_materialize; CREATE SCHEMA ansilo_snapshots;"));
+        assert!(sql.contains(r#"IMPORT FOREIGN SCHEMA "orders" LIMIT TO ("orders")"#));
+        assert!(sql.contains(r#"FROM SERVER "mysql""#));
+        assert!(sql.contains(
+            r#"CREATE TABLE ansilo_snapshots."orders" (LIKE ansilo_materialize."orders", PRIMARY KEY ("id"));"#
+        ));
+        assert!(sql.contains(r#"CREATE INDEX ON ansilo_snapshots."orders" ("updated_at");"#));
+        assert!(!sql.contains("WITH GRANT OPTION"));
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let node = mock_node(vec![mock_entity(MaterializeMode::Full, None)]);
+        assert!(snapshots(&node, &pg_conf(&node)).unwrap().is_some());
+
+        let mut entity = mock_entity(MaterializeMode::Full, None);
+        entity.materialize = None;
+        let node = mock_node(vec![entity]);
+        assert!(snapshots(&node, &pg_conf(&node)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_refresh_sql_full() {
+        let entity = mock_entity(MaterializeMode::Full, None);
+        let sql = refresh_sql(&entity, entity.materialize.as_ref().unwrap()).unwrap();
+
+        assert!(sql.contains(r#"DELETE FROM ansilo_snapshots."orders";"#));
+        assert!(sql.contains(
+            r#"INSERT INTO ansilo_snapshots."orders" SELECT * FROM ansilo_materialize."orders";"#
+        ));
+    }
+
+    #[test]
+    fn test_refresh_sql_incremental() {
+        let entity = mock_entity(MaterializeMode::Incremental, Some("updated_at"));
+        let sql = refresh_sql(&entity, entity.materialize.as_ref().unwrap()).unwrap();
+
+        assert!(sql.contains(
+            r#"WHERE "updated_at" > (SELECT MAX("updated_at") FROM ansilo_snapshots."orders")"#
+        ));
+        assert!(sql.contains(
+            r#"ON CONFLICT ("id") DO UPDATE SET "status" = EXCLUDED."status", "updated_at" = EXCLUDED."updated_at";"#
+        ));
+    }
+
+    #[test]
+    fn test_refresh_sql_incremental_without_watermark() {
+        let entity = mock_entity(MaterializeMode::Incremental, None);
+
+        refresh_sql(&entity, entity.materialize.as_ref().unwrap()).unwrap_err();
+    }
+
+    #[test]
+    fn test_refresh_sql_cdc() {
+        let entity = mock_entity(MaterializeMode::Cdc, None);
+        let sql = refresh_sql(&entity, entity.materialize.as_ref().unwrap()).unwrap();
+
+        assert!(sql.contains(r#"DELETE FROM ansilo_snapshots."orders";"#));
+    }
+
+    #[test]
+    fn test_jobs_excludes_cdc() {
+        let node = mock_node(vec![
+            mock_entity(MaterializeMode::Full, None),
+            mock_entity(MaterializeMode::Cdc, None),
+        ]);
+
+        assert_eq!(jobs(&node).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_jobs() {
+        let node = mock_node(vec![mock_entity(MaterializeMode::Full, None)]);
+        let jobs = jobs(&node).unwrap();
+
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, "materialize:orders");
+        assert!(is_refresh_job(&jobs[0]));
+        assert_eq!(
+            jobs[0].triggers,
+            vec![JobTriggerConfig::Cron(CronTriggerConfig {
+                cron: "0 */15 * * * *".into()
+            })]
+        );
+    }
+}
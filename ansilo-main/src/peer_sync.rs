@@ -0,0 +1,405 @@
+use std::{
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use ansilo_connectors_all::{ConnectionConfigs, Connectors, PeerConnector};
+use ansilo_connectors_base::interface::EntityDiscoverOptions;
+use ansilo_connectors_peer::sync::{CatalogChanges, PeerSyncConfig};
+use ansilo_core::{
+    config::{DataSourceConfig, EntityConfig, JobConfig, NodeConfig, PeerImportJobConfig},
+    err::{bail, Context, Error, Result},
+};
+use ansilo_jobs::job::BuiltinJobHandler;
+use ansilo_logging::{debug, info, warn};
+use ansilo_pg::{fdw::cache::MetadataCache, handler::PostgresConnectionHandler};
+use ansilo_util_pg::query::{pg_quote_identifier, pg_str_literal};
+use tokio::runtime::Handle;
+
+use crate::grants;
+
+/// Periodically pulls the catalog of each peer data source with `sync` enabled
+/// and re-imports the entities which were added, removed or changed since
+/// they were last imported.
+///
+/// Each peer is synced on its own thread so an unreachable peer does not
+/// delay the others.
+///
+/// The same sync is also performed on a schedule by the built-in peer import jobs.
+pub struct PeerCatalogSync {
+    /// Used to run the sync queries
+    handler: PostgresConnectionHandler,
+    /// The runtime to run the sync queries on
+    runtime: Handle,
+    /// Cached metadata of the peers, invalidated before re-importing
+    cache: MetadataCache,
+    /// Dropping these senders signals the sync threads to stop
+    stop: Mutex<Vec<Sender<()>>>,
+    /// The node config of the current sync, used by the peer import jobs
+    node: Arc<Mutex<Option<Arc<NodeConfig>>>>,
+}
+
+impl PeerCatalogSync {
+    pub fn new(handler: PostgresConnectionHandler, runtime: Handle, cache: MetadataCache) -> Self {
+        Self {
+            handler,
+            runtime,
+            cache,
+            stop: Mutex::new(vec![]),
+            node: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Starts syncing the peers of the supplied node
+    pub fn start(&self, node: &Arc<NodeConfig>) -> Result<()> {
+        *self
+            .node
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock peer sync node config"))? =
+            Some(Arc::clone(node));
+
+        for source in node.sources.iter() {
+            if let Some(conf) = Self::sync_config(source)? {
+                self.spawn(Arc::clone(node), source.clone(), conf)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Restarts syncing using the updated config
+    pub fn reload(&self, node: &Arc<NodeConfig>) -> Result<()> {
+        self.terminate()?;
+        self.start(node)
+    }
+
+    /// Stops syncing all peers
+    pub fn terminate(&self) -> Result<()> {
+        self.stop
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock peer sync threads"))?
+            .clear();
+
+        Ok(())
+    }
+
+    /// Creates the handler which runs the built-in peer import jobs
+    pub fn import_job_handler(&self) -> BuiltinJobHandler {
+        let handler = self.handler.clone();
+        let runtime = self.runtime.clone();
+        let cache = self.cache.clone();
+        let node = Arc::clone(&self.node);
+
+        Arc::new(move |job: &JobConfig| {
+            let conf = job
+                .peer_import
+                .as_ref()
+                .context("Job is not a peer import job")?;
+            let node = node
+                .lock()
+                .map_err(|_| Error::msg("Failed to lock peer sync node config"))?
+                .clone()
+                .context("Peer catalog sync has not been started")?;
+
+            Self::import(&node, conf, &handler, &runtime, &cache)
+        })
+    }
+
+    /// Re-imports the entities of the peer as per the job options
+    fn import(
+        node: &NodeConfig,
+        conf: &PeerImportJobConfig,
+        handler: &PostgresConnectionHandler,
+        runtime: &Handle,
+        cache: &MetadataCache,
+    ) -> Result<()> {
+        let source = node
+            .sources
+            .iter()
+            .find(|s| s.id == conf.data_source)
+            .with_context(|| format!("Unknown data source '{}'", conf.data_source))?;
+
+        if Connectors::from_type(&source.r#type) != Some(Connectors::Peer) {
+            bail!("Data source '{}' is not a peer", source.id);
+        }
+
+        let conf = PeerSyncConfig {
+            schema: conf.schema.clone(),
+            table_prefix: conf.table_prefix.clone(),
+            interval_secs: None,
+        };
+
+        Self::sync(node, source, &conf, handler, runtime, cache)
+    }
+
+    /// Gets the sync options of the data source, if it is a peer with sync enabled
+    fn sync_config(source: &DataSourceConfig) -> Result<Option<PeerSyncConfig>> {
+        if Connectors::from_type(&source.r#type) != Some(Connectors::Peer) {
+            return Ok(None);
+        }
+
+        let options = Connectors::Peer
+            .parse_options(source.options.clone())
+            .with_context(|| format!("Failed to parse options of data source '{}'", source.id))?;
+
+        Ok(match options {
+            ConnectionConfigs::Peer(peer) => peer.sync,
+            _ => None,
+        })
+    }
+
+    fn spawn(
+        &self,
+        node: Arc<NodeConfig>,
+        source: DataSourceConfig,
+        conf: PeerSyncConfig,
+    ) -> Result<()> {
+        let (tx, rx) = mpsc::channel::<()>();
+        let handler = self.handler.clone();
+        let runtime = self.runtime.clone();
+        let cache = self.cache.clone();
+        let interval = conf.interval();
+
+        info!(
+            "Syncing catalog of peer '{}' every {}s",
+            source.id,
+            interval.as_secs()
+        );
+
+        thread::Builder::new()
+            .name(format!("ansilo-peer-sync-{}", source.id))
+            .spawn(move || loop {
+                if let Err(err) = Self::sync(&node, &source, &conf, &handler, &runtime, &cache) {
+                    warn!("Failed to sync catalog of peer '{}': {:?}", source.id, err);
+                }
+
+                match rx.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            })
+            .context("Failed to spawn peer sync thread")?;
+
+        self.stop
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock peer sync threads"))?
+            .push(tx);
+
+        Ok(())
+    }
+
+    /// Compares the peer's catalog to the imported entities and
+    /// re-imports the entities which have changed
+    fn sync(
+        node: &NodeConfig,
+        source: &DataSourceConfig,
+        conf: &PeerSyncConfig,
+        handler: &PostgresConnectionHandler,
+        runtime: &Handle,
+        cache: &MetadataCache,
+    ) -> Result<()> {
+        // The catalog is retrieved using a blocking http client so this
+        // must occur outside of the async runtime
+        let mut current =
+            PeerConnector::discover_unauthenticated(source, EntityDiscoverOptions::default())?;
+
+        for entity in current.iter_mut() {
+            entity.source.data_source = source.id.clone();
+        }
+
+        runtime.block_on(async {
+            let con = handler
+                .pool()
+                .admin()
+                .await
+                .context("Failed to connect to postgres")?;
+
+            let previous = Self::imported_entities(&con, source, conf).await?;
+            let changes = CatalogChanges::diff(&previous, &current);
+
+            if changes.is_empty() {
+                debug!("Catalog of peer '{}' is unchanged", source.id);
+                return Ok(());
+            }
+
+            info!("Syncing catalog of peer '{}': {changes}", source.id);
+
+            // Ensure the tables are imported from the current catalog
+            cache.invalidate(Some(&source.id))?;
+
+            con.batch_execute(&sync_sql(&source.id, conf, &changes))
+                .await
+                .context("Failed to re-import entities")?;
+
+            grants::apply(node, handler).await
+        })
+    }
+
+    /// Gets the entity config of each table previously imported from the peer
+    async fn imported_entities(
+        con: &ansilo_pg::connection::PostgresConnection,
+        source: &DataSourceConfig,
+        conf: &PeerSyncConfig,
+    ) -> Result<Vec<EntityConfig>> {
+        let rows = con
+            .query(
+                r#"
+                SELECT o.option_value
+                FROM pg_foreign_table ft
+                JOIN pg_class c ON c.oid = ft.ftrelid
+                JOIN pg_namespace n ON n.oid = c.relnamespace
+                JOIN pg_foreign_server s ON s.oid = ft.ftserver
+                CROSS JOIN pg_options_to_table(ft.ftoptions) o
+                WHERE s.srvname = $1
+                AND n.nspname = $2
+                AND o.option_name = '__config'
+            "#,
+                &[&source.id, &conf.schema],
+            )
+            .await
+            .context("Failed to query imported entities")?;
+
+        rows.into_iter()
+            .map(|row| {
+                let yaml: String = row.get(0);
+
+                serde_yaml::from_str::<EntityConfig>(&yaml)
+                    .context("Failed to parse imported entity config")
+            })
+            .collect()
+    }
+}
+
+impl Drop for PeerCatalogSync {
+    fn drop(&mut self) {
+        let _ = self.terminate();
+    }
+}
+
+/// Returns the sql which drops the tables of the removed and changed entities
+/// and imports the added and changed entities from the peer
+pub(crate) fn sync_sql(
+    data_source_id: &str,
+    conf: &PeerSyncConfig,
+    changes: &CatalogChanges,
+) -> String {
+    let schema = pg_quote_identifier(&conf.schema);
+    let table = |id: &str| pg_quote_identifier(&conf.table_name(id));
+    let mut sql = vec!["BEGIN;".to_string()];
+
+    let drop = changes
+        .to_drop()
+        .map(|id| format!("{schema}.{}", table(id)))
+        .collect::<Vec<_>>();
+
+    if !drop.is_empty() {
+        sql.push(format!("DROP FOREIGN TABLE IF EXISTS {};", drop.join(", ")));
+    }
+
+    let import = changes.to_import().map(table).collect::<Vec<_>>();
+
+    if !import.is_empty() {
+        let options = conf
+            .table_prefix
+            .as_ref()
+            .map(|p| format!(" OPTIONS (table_prefix {})", pg_str_literal(p)))
+            .unwrap_or_default();
+
+        sql.push(format!("CREATE SCHEMA IF NOT EXISTS {schema};"));
+        sql.push(format!(
+            r#"IMPORT FOREIGN SCHEMA "%" LIMIT TO ({}) FROM SERVER {} INTO {schema}{options};"#,
+            import.join(", "),
+            pg_quote_identifier(data_source_id)
+        ));
+    }
+
+    sql.push("COMMIT;".into());
+    sql.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::config::EntitySourceConfig;
+
+    use super::*;
+
+    fn mock_entity(id: &str) -> EntityConfig {
+        EntityConfig::minimal(id, vec![], EntitySourceConfig::minimal("sales"))
+    }
+
+    #[test]
+    fn test_sync_sql() {
+        let conf = PeerSyncConfig {
+            schema: "peer".into(),
+            table_prefix: None,
+            interval_secs: None,
+        };
+        let changes = CatalogChanges {
+            added: vec![mock_entity("orders")],
+            removed: vec!["invoices".into()],
+            changed: vec![mock_entity("customers")],
+        };
+
+        assert_eq!(
+            sync_sql("sales", &conf, &changes),
+            [
+                r#"BEGIN;"#,
+                r#"DROP FOREIGN TABLE IF EXISTS "peer"."invoices", "peer"."customers";"#,
+                r#"CREATE SCHEMA IF NOT EXISTS "peer";"#,
+                r#"IMPORT FOREIGN SCHEMA "%" LIMIT TO ("orders", "customers") FROM SERVER "sales" INTO "peer";"#,
+                r#"COMMIT;"#,
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_sync_sql_removed_only_with_prefix() {
+        let conf = PeerSyncConfig {
+            schema: "peer".into(),
+            table_prefix: Some("sales_".into()),
+            interval_secs: None,
+        };
+        let changes = CatalogChanges {
+            removed: vec!["invoices".into()],
+            ..CatalogChanges::default()
+        };
+
+        assert_eq!(
+            sync_sql("sales", &conf, &changes),
+            [
+                r#"BEGIN;"#,
+                r#"DROP FOREIGN TABLE IF EXISTS "peer"."sales_invoices";"#,
+                r#"COMMIT;"#,
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_sync_sql_added_with_prefix() {
+        let conf = PeerSyncConfig {
+            schema: "peer".into(),
+            table_prefix: Some("sales_".into()),
+            interval_secs: None,
+        };
+        let changes = CatalogChanges {
+            added: vec![mock_entity("orders")],
+            ..CatalogChanges::default()
+        };
+
+        assert_eq!(
+            sync_sql("sales", &conf, &changes),
+            [
+                r#"BEGIN;"#,
+                r#"CREATE SCHEMA IF NOT EXISTS "peer";"#,
+                r#"IMPORT FOREIGN SCHEMA "%" LIMIT TO ("sales_orders") FROM SERVER "sales" INTO "peer" OPTIONS (table_prefix E'sales_');"#,
+                r#"COMMIT;"#,
+            ]
+            .join("\n")
+        );
+    }
+}
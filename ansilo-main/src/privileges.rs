@@ -0,0 +1,97 @@
+use std::env;
+
+use ansilo_core::err::{bail, ensure, Context, Result};
+use ansilo_logging::info;
+use nix::unistd::{setgid, setgroups, setuid, Gid, Group, Uid, User};
+
+/// Drops the root privileges of the current process, switching to the
+/// supplied user and/or group.
+///
+/// If only a user is supplied, the primary group of the user is used.
+/// Supplementary groups are cleared so no privileged groups are retained.
+///
+/// This must be called before starting postgres, which refuses to run as root.
+pub fn drop_privileges(user: Option<&str>, group: Option<&str>) -> Result<()> {
+    if user.is_none() && group.is_none() {
+        return Ok(());
+    }
+
+    let user = user
+        .map(|name| {
+            User::from_name(name)
+                .with_context(|| format!("Failed to look up user '{name}'"))?
+                .with_context(|| format!("User '{name}' does not exist"))
+        })
+        .transpose()?;
+
+    let gid = match group {
+        Some(name) => {
+            Group::from_name(name)
+                .with_context(|| format!("Failed to look up group '{name}'"))?
+                .with_context(|| format!("Group '{name}' does not exist"))?
+                .gid
+        }
+        None => user.as_ref().unwrap().gid,
+    };
+
+    // Nothing to do if we are already running as the requested user and group
+    if user
+        .as_ref()
+        .map(|u| u.uid == Uid::current())
+        .unwrap_or(true)
+        && gid == Gid::current()
+    {
+        return Ok(());
+    }
+
+    if !Uid::effective().is_root() {
+        bail!("Cannot switch to the requested user or group as the process is not running as root");
+    }
+
+    info!(
+        "Dropping root privileges (user: {}, group: {})",
+        user.as_ref().map(|u| u.name.as_str()).unwrap_or("root"),
+        gid
+    );
+
+    // The group must be changed first as we lose the permission to do so after setuid
+    setgroups(&[gid]).context("Failed to set supplementary groups")?;
+    setgid(gid).with_context(|| format!("Failed to set group to {gid}"))?;
+
+    if let Some(user) = user {
+        setuid(user.uid).with_context(|| format!("Failed to set user to '{}'", user.name))?;
+
+        ensure!(
+            setuid(Uid::from_raw(0)).is_err(),
+            "Failed to drop root privileges, process was able to regain root"
+        );
+
+        // Ensure child processes (eg postgres) see a consistent environment
+        env::set_var("USER", &user.name);
+        env::set_var("HOME", &user.dir);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_privileges_noop() {
+        drop_privileges(None, None).unwrap();
+    }
+
+    #[test]
+    fn test_drop_privileges_current_user() {
+        let user = User::from_uid(Uid::current()).unwrap().unwrap();
+
+        drop_privileges(Some(&user.name), None).unwrap();
+    }
+
+    #[test]
+    fn test_drop_privileges_unknown_user() {
+        drop_privileges(Some("ansilo-unknown-user"), None).unwrap_err();
+    }
+}
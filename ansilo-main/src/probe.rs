@@ -0,0 +1,218 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex, RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use ansilo_connectors_all::ConnectionPools;
+use ansilo_core::err::{Context, Error, Result};
+use ansilo_logging::{debug, warn};
+use ansilo_util_health::Health;
+
+/// The pools of each probed data source, keyed by the data source id
+type SharedPools = Arc<RwLock<HashMap<String, ConnectionPools>>>;
+
+/// Periodically pings each data source and records the result,
+/// including its latency and any error, in the health state.
+///
+/// Each data source is probed on its own thread so a slow or
+/// unreachable source does not delay the others.
+pub struct DataSourceProbes {
+    /// The current pool of each data source
+    pools: SharedPools,
+    /// The health state to update
+    health: Health,
+    /// The interval between probes
+    interval: Duration,
+    /// Dropping these senders signals the probe threads to stop
+    stop: Mutex<Vec<Sender<()>>>,
+}
+
+impl DataSourceProbes {
+    /// Starts probing the supplied data sources.
+    ///
+    /// Sources which cannot be pinged outside of a user session are skipped.
+    pub fn start(
+        pools: HashMap<String, ConnectionPools>,
+        health: Health,
+        interval: Duration,
+    ) -> Result<Self> {
+        let probes = Self {
+            pools: Arc::new(RwLock::new(HashMap::new())),
+            health,
+            interval,
+            stop: Mutex::new(vec![]),
+        };
+
+        for (id, pool) in pools.into_iter() {
+            probes.replace_pool(&id, pool)?;
+        }
+
+        Ok(probes)
+    }
+
+    /// The name of the data source in the health state
+    pub fn subsystem(data_source_id: &str) -> String {
+        format!("Source:{data_source_id}")
+    }
+
+    /// Replaces the pool of the data source, starting a probe
+    /// if the data source is not yet being probed.
+    pub fn replace_pool(&self, data_source_id: &str, pool: ConnectionPools) -> Result<()> {
+        if !pool.supports_ping() {
+            return Ok(());
+        }
+
+        let existing = self
+            .pools
+            .write()
+            .map_err(|_| Error::msg("Failed to lock probe pools"))?
+            .insert(data_source_id.into(), pool);
+
+        if existing.is_none() {
+            self.spawn(data_source_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Stops all probes
+    pub fn terminate(&self) -> Result<()> {
+        self.stop
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock probe threads"))?
+            .clear();
+
+        Ok(())
+    }
+
+    fn spawn(&self, data_source_id: &str) -> Result<()> {
+        let (tx, rx) = mpsc::channel::<()>();
+        let id = data_source_id.to_string();
+        let pools = Arc::clone(&self.pools);
+        let health = self.health.clone();
+        let interval = self.interval;
+
+        thread::Builder::new()
+            .name(format!("ansilo-probe-{id}"))
+            .spawn(move || loop {
+                let pool = match pools.read().map(|p| p.get(&id).cloned()) {
+                    Ok(Some(pool)) => pool,
+                    _ => break,
+                };
+
+                let (latency, res) = Self::probe(pool);
+                debug!("Probed data source '{id}' in {}ms", latency.as_millis());
+
+                if let Err(err) = health.update_probe(&Self::subsystem(&id), latency, &res) {
+                    warn!("Failed to update health of data source '{id}': {:?}", err);
+                }
+
+                match rx.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            })
+            .context("Failed to spawn probe thread")?;
+
+        self.stop
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock probe threads"))?
+            .push(tx);
+
+        Ok(())
+    }
+
+    /// Pings the data source, returning the latency and the result
+    fn probe(mut pool: ConnectionPools) -> (Duration, Result<()>) {
+        let start = Instant::now();
+        let res = pool.ping();
+
+        (start.elapsed(), res)
+    }
+}
+
+impl Drop for DataSourceProbes {
+    fn drop(&mut self) {
+        let _ = self.terminate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_connectors_all::{Connectors, InternalConnection};
+    use ansilo_core::config::{NodeConfig, Value};
+
+    use super::*;
+
+    fn memory_pool(nc: &NodeConfig) -> ConnectionPools {
+        let connector = Connectors::Memory;
+        let options = connector.parse_options(Value::Null).unwrap();
+
+        connector
+            .create_connection_pool(nc, "memory", options)
+            .unwrap()
+            .0
+    }
+
+    fn wait_for_probe(health: &Health, id: &str) {
+        for _ in 0..100 {
+            if health
+                .check()
+                .unwrap()
+                .contains_key(&DataSourceProbes::subsystem(id))
+            {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        panic!("Data source '{id}' was not probed");
+    }
+
+    #[test]
+    fn test_probe_data_source() {
+        let nc = NodeConfig::default();
+        let health = Health::new();
+
+        let probes = DataSourceProbes::start(
+            HashMap::from([("memory".to_string(), memory_pool(&nc))]),
+            health.clone(),
+            Duration::from_secs(60),
+        )
+        .unwrap();
+
+        wait_for_probe(&health, "memory");
+
+        let status = health.check().unwrap()["Source:memory"].clone();
+        assert_eq!(status.healthy, true);
+        assert_eq!(status.critical, false);
+        assert!(status.latency_ms.is_some());
+        assert_eq!(status.error, None);
+
+        probes.terminate().unwrap();
+    }
+
+    #[test]
+    fn test_probe_skips_internal() {
+        let nc = Box::leak(Box::new(NodeConfig::default()));
+        let health = Health::new();
+
+        let probes = DataSourceProbes::start(
+            HashMap::from([(
+                "internal".to_string(),
+                ConnectionPools::Internal(InternalConnection(nc)),
+            )]),
+            health.clone(),
+            Duration::from_secs(60),
+        )
+        .unwrap();
+
+        assert!(probes.pools.read().unwrap().is_empty());
+        assert!(health.check().unwrap().is_empty());
+    }
+}
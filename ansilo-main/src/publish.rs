@@ -0,0 +1,519 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use ansilo_core::{
+    config::{NodeConfig, PublishConfig, PublishTableConfig},
+    err::{bail, Context, Error, Result},
+};
+use ansilo_logging::{debug, info, warn};
+use ansilo_pg::{connection::PostgresConnection, handler::PostgresConnectionHandler};
+use ansilo_util_pg::query::{pg_quote_identifier, pg_str_literal};
+use kafka::producer::{Producer, Record, RequiredAcks};
+use tokio::runtime::Handle;
+
+/// The schema containing the outbox of unpublished changes
+const PUBLISH_SCHEMA: &str = "ansilo_publish";
+
+/// The name of the triggers which record the changes to the published tables
+const ROW_TRIGGER: &str = "ansilo_publish";
+const TRUNCATE_TRIGGER: &str = "ansilo_publish_truncate";
+
+/// How long kafka has to acknowledge the published changes
+const ACK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait before reconnecting to kafka after a failure
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Publishes the changes made to the configured local tables to kafka.
+///
+/// The changes are recorded in an outbox table by triggers on the published
+/// tables, in the same transaction as the change itself. The outbox is then
+/// drained by a background thread which sends each change to its topic as a
+/// Debezium change event, so changes are published at least once and only
+/// once their transaction has committed.
+pub struct ChangePublisher {
+    /// Used to install the triggers and read the outbox
+    handler: PostgresConnectionHandler,
+    /// The runtime to query postgres on
+    runtime: Handle,
+    /// Dropping these senders signals the publishing thread to stop
+    stop: Mutex<Vec<Sender<()>>>,
+}
+
+impl ChangePublisher {
+    pub fn new(handler: PostgresConnectionHandler, runtime: Handle) -> Self {
+        Self {
+            handler,
+            runtime,
+            stop: Mutex::new(vec![]),
+        }
+    }
+
+    /// Installs the triggers on the published tables of the supplied node
+    /// and starts publishing their changes
+    pub fn start(&self, node: &NodeConfig) -> Result<()> {
+        self.runtime
+            .block_on(Self::install(node, &self.handler))
+            .context("Failed to install change publication triggers")?;
+
+        let conf = match node.publish.as_ref() {
+            Some(conf) => conf.clone(),
+            None => return Ok(()),
+        };
+
+        let (tx, rx) = mpsc::channel::<()>();
+        let handler = self.handler.clone();
+        let runtime = self.runtime.clone();
+
+        thread::Builder::new()
+            .name("ansilo-publish".into())
+            .spawn(move || loop {
+                match Self::publish(&conf, &handler, &runtime, &rx) {
+                    Ok(()) => break,
+                    Err(err) => warn!("Failed to publish changes: {:?}", err),
+                }
+
+                match rx.recv_timeout(RETRY_INTERVAL) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            })
+            .context("Failed to spawn change publication thread")?;
+
+        self.stop
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock change publication threads"))?
+            .push(tx);
+
+        Ok(())
+    }
+
+    /// Restarts publishing using the updated config
+    pub fn reload(&self, node: &NodeConfig) -> Result<()> {
+        self.terminate()?;
+        self.start(node)
+    }
+
+    /// Stops publishing changes, any unpublished changes remain in the outbox
+    pub fn terminate(&self) -> Result<()> {
+        self.stop
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock change publication threads"))?
+            .clear();
+
+        Ok(())
+    }
+
+    /// Creates the outbox and the triggers on the published tables, removing
+    /// the triggers from any tables which are no longer published
+    async fn install(node: &NodeConfig, handler: &PostgresConnectionHandler) -> Result<()> {
+        let con = handler
+            .pool()
+            .admin()
+            .await
+            .context("Failed to connect to postgres")?;
+
+        let tables = node
+            .publish
+            .as_ref()
+            .map(|p| p.tables.as_slice())
+            .unwrap_or_default();
+
+        let rows = con
+            .query(
+                r#"
+                SELECT DISTINCT n.nspname::text, c.relname::text
+                FROM pg_trigger t
+                JOIN pg_class c ON c.oid = t.tgrelid
+                JOIN pg_namespace n ON n.oid = c.relnamespace
+                WHERE t.tgname = $1
+            "#,
+                &[&ROW_TRIGGER],
+            )
+            .await
+            .context("Failed to query published tables")?;
+
+        for row in rows {
+            let (schema, name): (String, String) = (row.get(0), row.get(1));
+
+            if !tables
+                .iter()
+                .any(|t| t.schema_and_name() == (schema.as_str(), name.as_str()))
+            {
+                info!("Removing change publication from table {schema}.{name}");
+                con.batch_execute(&drop_trigger_sql(&schema, &name))
+                    .await
+                    .with_context(|| format!("Failed to remove triggers from {schema}.{name}"))?;
+            }
+        }
+
+        if tables.is_empty() {
+            return Ok(());
+        }
+
+        con.batch_execute(&outbox_sql(node))
+            .await
+            .context("Failed to create outbox")?;
+
+        for table in tables {
+            let key = if table.key.is_empty() {
+                Self::primary_key(&con, table).await?
+            } else {
+                table.key.clone()
+            };
+
+            con.batch_execute(&trigger_sql(node, table, &key))
+                .await
+                .with_context(|| format!("Failed to install triggers on table {}", table.table))?;
+        }
+
+        Ok(())
+    }
+
+    /// Gets the primary key columns of the table
+    async fn primary_key(
+        con: &PostgresConnection,
+        table: &PublishTableConfig,
+    ) -> Result<Vec<String>> {
+        let (schema, name) = table.schema_and_name();
+        let qualified = format!(
+            "{}.{}",
+            pg_quote_identifier(schema),
+            pg_quote_identifier(name)
+        );
+
+        let rows = con
+            .query(
+                r#"
+                SELECT a.attname::text
+                FROM pg_index i
+                JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+                WHERE i.indrelid = $1::text::regclass
+                AND i.indisprimary
+                ORDER BY array_position(i.indkey::int2[], a.attnum)
+            "#,
+                &[&qualified],
+            )
+            .await
+            .with_context(|| format!("Failed to query primary key of table {}", table.table))?;
+
+        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+    }
+
+    /// Sends the changes in the outbox to kafka until signalled to stop
+    fn publish(
+        conf: &PublishConfig,
+        handler: &PostgresConnectionHandler,
+        runtime: &Handle,
+        stop: &Receiver<()>,
+    ) -> Result<()> {
+        let mut producer = Producer::from_hosts(conf.kafka.brokers.clone())
+            .with_client_id(
+                conf.kafka
+                    .client_id
+                    .clone()
+                    .unwrap_or_else(|| "ansilo".into()),
+            )
+            .with_ack_timeout(ACK_TIMEOUT)
+            .with_required_acks(RequiredAcks::All)
+            .create()
+            .map_err(|err| Error::msg(format!("Failed to connect to kafka: {err}")))?;
+
+        info!("Publishing changes to {} table(s)", conf.tables.len());
+
+        loop {
+            let changes = runtime.block_on(Self::read(conf, handler))?;
+
+            if changes.is_empty() {
+                match stop.recv_timeout(conf.poll_interval()) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => return Ok(()),
+                }
+            }
+
+            debug!("Publishing {} changes", changes.len());
+            let records = changes
+                .iter()
+                .map(|c| Record::from_key_value(&c.topic, c.key.as_bytes(), c.value.as_bytes()))
+                .collect::<Vec<_>>();
+
+            let confirms = producer
+                .send_all(&records)
+                .map_err(|err| Error::msg(format!("Failed to publish changes: {err}")))?;
+
+            for confirm in confirms {
+                for partition in confirm.partition_confirms {
+                    if let Err(code) = partition.offset {
+                        bail!(
+                            "Failed to publish changes to topic '{}': {:?}",
+                            confirm.topic,
+                            code
+                        );
+                    }
+                }
+            }
+
+            let ids = changes.iter().map(|c| c.id).collect::<Vec<_>>();
+            runtime.block_on(Self::remove(&ids, handler))?;
+
+            match stop.try_recv() {
+                Err(TryRecvError::Empty) => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Reads the oldest unpublished changes from the outbox
+    async fn read(
+        conf: &PublishConfig,
+        handler: &PostgresConnectionHandler,
+    ) -> Result<Vec<OutboxChange>> {
+        let con = handler
+            .pool()
+            .admin()
+            .await
+            .context("Failed to connect to postgres")?;
+
+        let rows = con
+            .query(
+                r#"
+                SELECT id, topic, COALESCE(key::text, ''), value::text
+                FROM ansilo_publish.outbox
+                ORDER BY id
+                LIMIT $1
+            "#,
+                &[&(conf.batch_size() as i64)],
+            )
+            .await
+            .context("Failed to read outbox")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| OutboxChange {
+                id: row.get(0),
+                topic: row.get(1),
+                key: row.get(2),
+                value: row.get(3),
+            })
+            .collect())
+    }
+
+    /// Removes the published changes from the outbox
+    async fn remove(ids: &[i64], handler: &PostgresConnectionHandler) -> Result<()> {
+        let con = handler
+            .pool()
+            .admin()
+            .await
+            .context("Failed to connect to postgres")?;
+
+        con.execute(
+            "DELETE FROM ansilo_publish.outbox WHERE id = ANY($1)",
+            &[&ids],
+        )
+        .await
+        .context("Failed to remove published changes from outbox")?;
+
+        Ok(())
+    }
+}
+
+impl Drop for ChangePublisher {
+    fn drop(&mut self) {
+        let _ = self.terminate();
+    }
+}
+
+/// An unpublished change read from the outbox
+struct OutboxChange {
+    id: i64,
+    topic: String,
+    /// The json message key, empty if the table has no key
+    key: String,
+    /// The json change event
+    value: String,
+}
+
+/// Returns the sql which creates the outbox and the trigger function
+/// which records the changes to the published tables as Debezium change events
+fn outbox_sql(node: &NodeConfig) -> String {
+    format!(
+        r#"
+CREATE SCHEMA IF NOT EXISTS {PUBLISH_SCHEMA};
+CREATE TABLE IF NOT EXISTS {PUBLISH_SCHEMA}.outbox (
+    id BIGSERIAL PRIMARY KEY,
+    topic TEXT NOT NULL,
+    key JSONB,
+    value JSONB NOT NULL
+);
+CREATE OR REPLACE FUNCTION {PUBLISH_SCHEMA}.capture() RETURNS TRIGGER
+LANGUAGE plpgsql SECURITY DEFINER SET search_path = pg_catalog, pg_temp AS $$
+DECLARE
+    v_before JSONB;
+    v_after JSONB;
+    v_key JSONB;
+    v_op TEXT;
+    v_col TEXT;
+    v_ts_ms BIGINT := (extract(epoch FROM clock_timestamp()) * 1000)::BIGINT;
+BEGIN
+    IF TG_OP = 'TRUNCATE' THEN
+        v_op := 't';
+    ELSE
+        IF TG_OP IN ('UPDATE', 'DELETE') THEN
+            v_before := to_jsonb(OLD);
+        END IF;
+        IF TG_OP IN ('INSERT', 'UPDATE') THEN
+            v_after := to_jsonb(NEW);
+        END IF;
+        v_op := CASE TG_OP WHEN 'INSERT' THEN 'c' WHEN 'UPDATE' THEN 'u' ELSE 'd' END;
+
+        IF TG_NARGS > 1 THEN
+            v_key := '{{}}'::JSONB;
+            FOREACH v_col IN ARRAY TG_ARGV[1:] LOOP
+                v_key := v_key || jsonb_build_object(v_col, COALESCE(v_after, v_before) -> v_col);
+            END LOOP;
+        END IF;
+    END IF;
+
+    INSERT INTO {PUBLISH_SCHEMA}.outbox (topic, key, value) VALUES (
+        TG_ARGV[0],
+        v_key,
+        jsonb_build_object(
+            'before', v_before,
+            'after', v_after,
+            'source', jsonb_build_object(
+                'version', {version},
+                'connector', 'ansilo',
+                'name', {name},
+                'ts_ms', v_ts_ms,
+                'db', current_database(),
+                'schema', TG_TABLE_SCHEMA,
+                'table', TG_TABLE_NAME,
+                'txId', txid_current()
+            ),
+            'op', v_op,
+            'ts_ms', v_ts_ms
+        )
+    );
+
+    RETURN NULL;
+END
+$$;
+"#,
+        version = pg_str_literal(env!("CARGO_PKG_VERSION")),
+        name = pg_str_literal(&node.name),
+    )
+}
+
+/// Returns the sql which installs the triggers recording the changes to the table
+fn trigger_sql(node: &NodeConfig, table: &PublishTableConfig, key: &[String]) -> String {
+    let (schema, name) = table.schema_and_name();
+    let qualified = format!(
+        "{}.{}",
+        pg_quote_identifier(schema),
+        pg_quote_identifier(name)
+    );
+    let topic = pg_str_literal(&table.topic(&node.name));
+    let args = [topic.clone()]
+        .into_iter()
+        .chain(key.iter().map(|k| pg_str_literal(k)))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "CREATE OR REPLACE TRIGGER {ROW_TRIGGER} AFTER INSERT OR UPDATE OR DELETE ON {qualified} \
+        FOR EACH ROW EXECUTE FUNCTION {PUBLISH_SCHEMA}.capture({args});\n\
+        CREATE OR REPLACE TRIGGER {TRUNCATE_TRIGGER} AFTER TRUNCATE ON {qualified} \
+        FOR EACH STATEMENT EXECUTE FUNCTION {PUBLISH_SCHEMA}.capture({topic});"
+    )
+}
+
+/// Returns the sql which removes the triggers from a table which is no longer published
+fn drop_trigger_sql(schema: &str, name: &str) -> String {
+    let qualified = format!(
+        "{}.{}",
+        pg_quote_identifier(schema),
+        pg_quote_identifier(name)
+    );
+
+    format!(
+        "DROP TRIGGER IF EXISTS {ROW_TRIGGER} ON {qualified};\n\
+        DROP TRIGGER IF EXISTS {TRUNCATE_TRIGGER} ON {qualified};"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::config::KafkaPublishConfig;
+
+    use super::*;
+
+    fn mock_node() -> NodeConfig {
+        NodeConfig {
+            name: "node".into(),
+            publish: Some(PublishConfig {
+                kafka: KafkaPublishConfig {
+                    brokers: vec!["localhost:9092".into()],
+                    client_id: None,
+                },
+                tables: vec![PublishTableConfig {
+                    table: "sales.orders".into(),
+                    topic: None,
+                    key: vec![],
+                }],
+                poll_interval_ms: None,
+                batch_size: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_outbox_sql() {
+        let sql = outbox_sql(&mock_node());
+
+        assert!(sql.contains("CREATE TABLE IF NOT EXISTS ansilo_publish.outbox"));
+        assert!(sql.contains("CREATE OR REPLACE FUNCTION ansilo_publish.capture()"));
+        assert!(sql.contains("'name', E'node',"));
+        assert!(sql.contains("v_key := '{}'::JSONB;"));
+    }
+
+    #[test]
+    fn test_trigger_sql() {
+        let node = mock_node();
+        let table = &node.publish.as_ref().unwrap().tables[0];
+
+        assert_eq!(
+            trigger_sql(&node, table, &["id".into(), "region".into()]),
+            "CREATE OR REPLACE TRIGGER ansilo_publish AFTER INSERT OR UPDATE OR DELETE ON \"sales\".\"orders\" \
+            FOR EACH ROW EXECUTE FUNCTION ansilo_publish.capture(E'node.sales.orders', E'id', E'region');\n\
+            CREATE OR REPLACE TRIGGER ansilo_publish_truncate AFTER TRUNCATE ON \"sales\".\"orders\" \
+            FOR EACH STATEMENT EXECUTE FUNCTION ansilo_publish.capture(E'node.sales.orders');"
+        );
+    }
+
+    #[test]
+    fn test_trigger_sql_without_key() {
+        let mut node = mock_node();
+        let table = &mut node.publish.as_mut().unwrap().tables[0];
+        table.topic = Some("orders".into());
+        let table = table.clone();
+
+        assert!(trigger_sql(&node, &table, &[])
+            .contains("FOR EACH ROW EXECUTE FUNCTION ansilo_publish.capture(E'orders');"));
+    }
+
+    #[test]
+    fn test_drop_trigger_sql() {
+        assert_eq!(
+            drop_trigger_sql("public", "orders"),
+            "DROP TRIGGER IF EXISTS ansilo_publish ON \"public\".\"orders\";\n\
+            DROP TRIGGER IF EXISTS ansilo_publish_truncate ON \"public\".\"orders\";"
+        );
+    }
+}
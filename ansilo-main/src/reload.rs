@@ -0,0 +1,734 @@
+use ansilo_core::config::{NodeConfig, UserConfig};
+
+/// The set of changes between the running configuration and the
+/// updated configuration when reloading a running instance.
+///
+/// Only a subset of the configuration can be applied without a restart,
+/// any other changes are reported so the operator knows to restart or
+/// rebuild the instance. In dev mode, the changes which require a rebuild
+/// are applied by rebuilding the affected objects in place.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ReloadPlan {
+    /// The updated list of users, if changed
+    pub users: Option<Vec<UserConfig>>,
+    /// Whether the jobs have changed
+    pub jobs: bool,
+    /// Whether the log filter has changed
+    pub logging: bool,
+    /// The ids of the data sources of which the connection options have changed
+    pub sources: Vec<String>,
+    /// The ids of the data sources of which the cache or admission settings have changed
+    pub source_settings: Vec<String>,
+    /// Whether the build stages have changed
+    pub build: bool,
+    /// The ids of the entities which have been added, changed or removed
+    pub entities: Vec<String>,
+    /// Whether the grants have changed
+    pub grants: bool,
+    /// Whether the change publication options have changed
+    pub publish: bool,
+    /// Whether the drift detection options have changed
+    pub drift: bool,
+    /// Descriptions of the changes which can be applied
+    pub applied: Vec<String>,
+    /// Descriptions of the changes which require a rebuild
+    pub requires_rebuild: Vec<String>,
+    /// Descriptions of the changes which require a restart
+    pub requires_restart: Vec<String>,
+}
+
+impl ReloadPlan {
+    /// Compares the current and new configuration.
+    ///
+    /// The `pg_users` are the users which have been provisioned as roles
+    /// in postgres, new users cannot be added until the database is rebuilt.
+    pub(crate) fn new(current: &NodeConfig, new: &NodeConfig, pg_users: &[String]) -> Self {
+        let mut plan = Self::default();
+
+        let mut restart_if_changed = |name: &str, changed: bool| {
+            if changed {
+                plan.requires_restart
+                    .push(format!("{name} changed, requires a restart"));
+            }
+        };
+
+        restart_if_changed("Node name", current.name != new.name);
+        restart_if_changed("Node description", current.description != new.description);
+        restart_if_changed("Networking config", current.networking != new.networking);
+        restart_if_changed("Resources config", current.resources != new.resources);
+        restart_if_changed("Postgres config", current.postgres != new.postgres);
+        restart_if_changed("Encryption config", current.encryption != new.encryption);
+        restart_if_changed("HA config", current.ha != new.ha);
+        restart_if_changed("Cluster config", current.cluster != new.cluster);
+        restart_if_changed("Dev config", current.dev != new.dev);
+        restart_if_changed("Log file", current.logging.file != new.logging.file);
+        restart_if_changed(
+            "Auth providers",
+            current.auth.providers != new.auth.providers,
+        );
+        restart_if_changed(
+            "Service users",
+            current.auth.service_users != new.auth.service_users,
+        );
+        // The running sessions and data source connections hold onto the auth config
+        // from startup so the following are only enforced after a restart
+        restart_if_changed("Query rules", current.auth.rules != new.auth.rules);
+        restart_if_changed("User limits", current.auth.limits != new.auth.limits);
+        restart_if_changed("Classification masks", current.auth.masks != new.auth.masks);
+        restart_if_changed(
+            "Workload classes",
+            current.auth.workloads != new.auth.workloads,
+        );
+
+        if current.build != new.build {
+            plan.build = true;
+            plan.requires_rebuild
+                .push("Build stages changed, requires a rebuild".into());
+        }
+
+        // The foreign tables used by the syncs are imported when the database is initialised
+        if current.syncs != new.syncs {
+            plan.requires_restart
+                .push("Syncs changed, requires a rebuild".into());
+        }
+
+        // The databases of the catalogs are created when the database is initialised
+        if current.catalogs != new.catalogs {
+            plan.requires_restart
+                .push("Catalogs changed, requires a rebuild".into());
+        }
+
+        if current.auth.grants != new.auth.grants {
+            plan.grants = true;
+            plan.requires_rebuild
+                .push("Grants changed, requires a rebuild".into());
+        }
+
+        plan.diff_entities(current, new);
+        plan.diff_users(current, new, pg_users);
+        plan.diff_sources(current, new);
+
+        if current.jobs != new.jobs {
+            plan.jobs = true;
+            plan.applied.push(format!(
+                "Reloaded job scheduler with {} job(s)",
+                new.jobs.len()
+            ));
+        }
+
+        if current.publish != new.publish {
+            plan.publish = true;
+            plan.applied.push(format!(
+                "Reloaded change publication for {} table(s)",
+                new.publish.as_ref().map_or(0, |p| p.tables.len())
+            ));
+        }
+
+        if current.drift != new.drift {
+            plan.drift = true;
+            plan.applied.push(match new.drift.as_ref() {
+                Some(_) => "Reloaded drift detection".into(),
+                None => "Disabled drift detection".into(),
+            });
+        }
+
+        if current.logging.level != new.logging.level {
+            plan.logging = true;
+            plan.applied.push(format!(
+                "Updated log filter to '{}'",
+                new.logging.level.as_deref().unwrap_or("<env default>")
+            ));
+        }
+
+        plan
+    }
+
+    /// Returns whether there are no changes between the configurations
+    pub(crate) fn is_empty(&self) -> bool {
+        self.applied.is_empty()
+            && self.requires_rebuild.is_empty()
+            && self.requires_restart.is_empty()
+    }
+
+    /// Applies the reloadable changes to the current configuration, returning
+    /// the updated configuration.
+    pub(crate) fn apply_to(&self, current: &NodeConfig, new: &NodeConfig) -> NodeConfig {
+        let mut conf = current.clone();
+
+        if let Some(users) = self.users.as_ref() {
+            conf.auth.users = users.clone();
+        }
+
+        if self.jobs {
+            conf.jobs = new.jobs.clone();
+        }
+
+        if self.logging {
+            conf.logging.level = new.logging.level.clone();
+        }
+
+        if self.publish {
+            conf.publish = new.publish.clone();
+        }
+
+        if self.drift {
+            conf.drift = new.drift.clone();
+        }
+
+        for id in self.sources.iter() {
+            let source = conf.sources.iter_mut().find(|s| &s.id == id);
+            let updated = new.sources.iter().find(|s| &s.id == id);
+
+            if let (Some(source), Some(updated)) = (source, updated) {
+                source.options = updated.options.clone();
+            }
+        }
+
+        for id in self.source_settings.iter() {
+            let source = conf.sources.iter_mut().find(|s| &s.id == id);
+            let updated = new.sources.iter().find(|s| &s.id == id);
+
+            if let (Some(source), Some(updated)) = (source, updated) {
+                source.metadata_cache_ttl = updated.metadata_cache_ttl;
+                source.prepared_query_cache = updated.prepared_query_cache.clone();
+                source.admission = updated.admission.clone();
+            }
+        }
+
+        conf
+    }
+
+    /// Applies the reloadable changes along with the changes which require a
+    /// rebuild to the current configuration, returning the updated configuration.
+    pub(crate) fn apply_with_rebuild_to(
+        &self,
+        current: &NodeConfig,
+        new: &NodeConfig,
+    ) -> NodeConfig {
+        let mut conf = self.apply_to(current, new);
+
+        if self.build {
+            conf.build = new.build.clone();
+        }
+
+        if !self.entities.is_empty() {
+            conf.entities = new.entities.clone();
+        }
+
+        if self.grants {
+            conf.auth.grants = new.auth.grants.clone();
+        }
+
+        conf
+    }
+
+    fn diff_entities(&mut self, current: &NodeConfig, new: &NodeConfig) {
+        let changed = new
+            .entities
+            .iter()
+            .filter(|e| !current.entities.contains(e));
+        let removed = current
+            .entities
+            .iter()
+            .filter(|e| !new.entities.iter().any(|n| n.id == e.id));
+
+        for entity in changed.chain(removed) {
+            // The snapshot tables of materialized entities are created when the database is initialised
+            let materialized = [current, new]
+                .iter()
+                .flat_map(|c| c.entities.iter())
+                .any(|e| e.id == entity.id && e.materialize.is_some());
+
+            if materialized {
+                self.requires_restart.push(format!(
+                    "Materialized entity '{}' changed, requires a rebuild",
+                    entity.id
+                ));
+            } else if !self.entities.contains(&entity.id) {
+                self.entities.push(entity.id.clone());
+                self.requires_rebuild.push(format!(
+                    "Entity '{}' changed, requires a rebuild",
+                    entity.id
+                ));
+            }
+        }
+    }
+
+    fn diff_users(&mut self, current: &NodeConfig, new: &NodeConfig, pg_users: &[String]) {
+        let current = &current.auth.users;
+        let mut users = vec![];
+
+        for user in new.auth.users.iter() {
+            if !pg_users.contains(&user.username) {
+                self.requires_restart.push(format!(
+                    "User '{}' was added, requires a rebuild",
+                    user.username
+                ));
+                continue;
+            }
+
+            match current.iter().find(|u| u.username == user.username) {
+                Some(existing) if existing == user => {}
+                Some(_) => self
+                    .applied
+                    .push(format!("Updated user '{}'", user.username)),
+                None => self
+                    .applied
+                    .push(format!("Restored user '{}'", user.username)),
+            }
+
+            users.push(user.clone());
+        }
+
+        for user in current.iter() {
+            if !new.auth.users.iter().any(|u| u.username == user.username) {
+                self.applied
+                    .push(format!("Removed user '{}'", user.username));
+            }
+        }
+
+        if &users != current {
+            self.users = Some(users);
+        }
+    }
+
+    fn diff_sources(&mut self, current: &NodeConfig, new: &NodeConfig) {
+        for source in new.sources.iter() {
+            let existing = match current.sources.iter().find(|s| s.id == source.id) {
+                Some(s) => s,
+                None => {
+                    self.requires_restart.push(format!(
+                        "Data source '{}' was added, requires a restart",
+                        source.id
+                    ));
+                    continue;
+                }
+            };
+
+            if existing.r#type != source.r#type || existing.name != source.name {
+                self.requires_restart.push(format!(
+                    "Data source '{}' changed, requires a restart",
+                    source.id
+                ));
+                continue;
+            }
+
+            // The fetch size and transfer options are set on the foreign server
+            // when the database is initialised
+            if existing.fetch_size != source.fetch_size
+                || existing.transfer_encoding != source.transfer_encoding
+                || existing.transfer_compression != source.transfer_compression
+            {
+                self.requires_restart.push(format!(
+                    "Transfer options of data source '{}' changed, requires a rebuild",
+                    source.id
+                ));
+            }
+
+            // The statistics collector is started with the config from startup
+            if existing.statistics != source.statistics {
+                self.requires_restart.push(format!(
+                    "Statistics options of data source '{}' changed, requires a restart",
+                    source.id
+                ));
+            }
+
+            // The remaining settings are read from the node config by new connections
+            if existing.metadata_cache_ttl != source.metadata_cache_ttl
+                || existing.prepared_query_cache != source.prepared_query_cache
+                || existing.admission != source.admission
+            {
+                self.source_settings.push(source.id.clone());
+                self.applied.push(format!(
+                    "Reloaded cache and admission settings for data source '{}'",
+                    source.id
+                ));
+            }
+
+            if existing.options != source.options {
+                self.sources.push(source.id.clone());
+                self.applied.push(format!(
+                    "Reloaded connection options for data source '{}'",
+                    source.id
+                ));
+            }
+        }
+
+        for source in current.sources.iter() {
+            if !new.sources.iter().any(|s| s.id == source.id) {
+                self.requires_restart.push(format!(
+                    "Data source '{}' was removed, requires a restart",
+                    source.id
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::{
+        config::{
+            AdmissionConfig, AttributeMaskConfig, AttributeMaskType, ClassificationMaskConfig,
+            DataSourceConfig, DriftConfig, EntityAttributeConfig, EntityConfig,
+            EntityMaterializeConfig, EntitySourceConfig, FetchSizeConfig, JobConfig,
+            KafkaPublishConfig, LoggingConfig, MaterializeMode, NetworkingConfig,
+            PasswordUserConfig, PublishConfig, PublishTableConfig, StatisticsConfig,
+            UserTypeOptions, Value,
+        },
+        data::DataType,
+    };
+
+    use super::*;
+
+    fn user(username: &str, password: &str) -> UserConfig {
+        UserConfig {
+            username: username.into(),
+            description: None,
+            provider: None,
+            roles: vec![],
+            r#type: UserTypeOptions::Password(PasswordUserConfig {
+                password: password.into(),
+            }),
+        }
+    }
+
+    fn source(id: &str, r#type: &str, options: &str) -> DataSourceConfig {
+        DataSourceConfig {
+            id: id.into(),
+            name: None,
+            r#type: r#type.into(),
+            options: Value::String(options.into()),
+            metadata_cache_ttl: None,
+            fetch_size: None,
+            transfer_encoding: Default::default(),
+            transfer_compression: Default::default(),
+            prepared_query_cache: None,
+            statistics: None,
+            admission: None,
+        }
+    }
+
+    fn entity(id: &str, attrs: &[&str]) -> EntityConfig {
+        EntityConfig::minimal(
+            id,
+            attrs
+                .iter()
+                .map(|a| EntityAttributeConfig::minimal(a, DataType::rust_string()))
+                .collect(),
+            EntitySourceConfig::minimal("postgres"),
+        )
+    }
+
+    #[test]
+    fn test_reload_plan_no_changes() {
+        let conf = NodeConfig::default();
+        let plan = ReloadPlan::new(&conf, &conf, &[]);
+
+        assert!(plan.is_empty());
+        assert_eq!(plan, ReloadPlan::default());
+    }
+
+    #[test]
+    fn test_reload_plan_requires_restart() {
+        let current = NodeConfig::default();
+        let new = NodeConfig {
+            name: "new".into(),
+            networking: NetworkingConfig {
+                port: 1234,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let plan = ReloadPlan::new(&current, &new, &[]);
+
+        assert_eq!(
+            plan.requires_restart,
+            vec![
+                "Node name changed, requires a restart".to_string(),
+                "Networking config changed, requires a restart".to_string(),
+            ]
+        );
+        assert!(plan.applied.is_empty());
+        assert_eq!(plan.apply_to(&current, &new), current);
+    }
+
+    #[test]
+    fn test_reload_plan_auth_policies_require_restart() {
+        let current = NodeConfig::default();
+        let mut new = NodeConfig::default();
+        new.auth.rules = vec![Default::default()];
+        new.auth.limits = vec![Default::default()];
+        new.auth.masks = vec![ClassificationMaskConfig {
+            classification: "pii".into(),
+            mask: AttributeMaskConfig {
+                r#type: AttributeMaskType::Null,
+                unmasked_for: Default::default(),
+            },
+        }];
+        new.auth.workloads = vec![Default::default()];
+
+        let plan = ReloadPlan::new(&current, &new, &[]);
+
+        assert_eq!(
+            plan.requires_restart,
+            vec![
+                "Query rules changed, requires a restart".to_string(),
+                "User limits changed, requires a restart".to_string(),
+                "Classification masks changed, requires a restart".to_string(),
+                "Workload classes changed, requires a restart".to_string(),
+            ]
+        );
+        assert!(plan.applied.is_empty());
+        assert_eq!(plan.apply_to(&current, &new), current);
+    }
+
+    #[test]
+    fn test_reload_plan_users() {
+        let mut current = NodeConfig::default();
+        current.auth.users = vec![user("a", "pass"), user("b", "pass")];
+        let mut new = NodeConfig::default();
+        new.auth.users = vec![user("a", "changed"), user("c", "pass")];
+
+        let plan = ReloadPlan::new(&current, &new, &["a".into(), "b".into()]);
+
+        assert_eq!(plan.users, Some(vec![user("a", "changed")]));
+        assert_eq!(
+            plan.applied,
+            vec![
+                "Updated user 'a'".to_string(),
+                "Removed user 'b'".to_string()
+            ]
+        );
+        assert_eq!(
+            plan.requires_restart,
+            vec!["User 'c' was added, requires a rebuild".to_string()]
+        );
+        assert_eq!(
+            plan.apply_to(&current, &new).auth.users,
+            vec![user("a", "changed")]
+        );
+    }
+
+    #[test]
+    fn test_reload_plan_sources() {
+        let mut current = NodeConfig::default();
+        current.sources = vec![
+            source("a", "native.postgres", "password: old"),
+            source("b", "native.postgres", "password: old"),
+            source("c", "native.postgres", "password: old"),
+        ];
+        let mut new = NodeConfig::default();
+        new.sources = vec![
+            source("a", "native.postgres", "password: new"),
+            source("b", "native.sqlite", "password: old"),
+            source("d", "native.postgres", "password: old"),
+        ];
+
+        let plan = ReloadPlan::new(&current, &new, &[]);
+
+        assert_eq!(plan.sources, vec!["a".to_string()]);
+        assert_eq!(
+            plan.applied,
+            vec!["Reloaded connection options for data source 'a'".to_string()]
+        );
+        assert_eq!(
+            plan.requires_restart,
+            vec![
+                "Data source 'b' changed, requires a restart".to_string(),
+                "Data source 'd' was added, requires a restart".to_string(),
+                "Data source 'c' was removed, requires a restart".to_string(),
+            ]
+        );
+
+        let applied = plan.apply_to(&current, &new);
+        assert_eq!(applied.sources[0], new.sources[0]);
+        assert_eq!(applied.sources[1], current.sources[1]);
+        assert_eq!(applied.sources[2], current.sources[2]);
+    }
+
+    #[test]
+    fn test_reload_plan_source_settings() {
+        let mut current = NodeConfig::default();
+        current.sources = vec![
+            source("a", "native.postgres", "opts"),
+            source("b", "native.postgres", "opts"),
+            source("c", "native.postgres", "opts"),
+        ];
+        let mut new = current.clone();
+        new.sources[0].metadata_cache_ttl = Some(10);
+        new.sources[0].admission = Some(AdmissionConfig {
+            max_concurrent_scans: Some(2),
+            ..Default::default()
+        });
+        new.sources[1].fetch_size = Some(FetchSizeConfig {
+            min_rows: Some(10),
+            max_rows: None,
+        });
+        new.sources[2].statistics = Some(StatisticsConfig::default());
+
+        let plan = ReloadPlan::new(&current, &new, &[]);
+
+        assert_eq!(plan.sources, Vec::<String>::new());
+        assert_eq!(plan.source_settings, vec!["a".to_string()]);
+        assert_eq!(
+            plan.applied,
+            vec!["Reloaded cache and admission settings for data source 'a'".to_string()]
+        );
+        assert_eq!(
+            plan.requires_restart,
+            vec![
+                "Transfer options of data source 'b' changed, requires a rebuild".to_string(),
+                "Statistics options of data source 'c' changed, requires a restart".to_string(),
+            ]
+        );
+
+        let applied = plan.apply_to(&current, &new);
+        assert_eq!(applied.sources[0], new.sources[0]);
+        assert_eq!(applied.sources[1], current.sources[1]);
+        assert_eq!(applied.sources[2], current.sources[2]);
+    }
+
+    #[test]
+    fn test_reload_plan_jobs_and_logging() {
+        let current = NodeConfig::default();
+        let new = NodeConfig {
+            jobs: vec![JobConfig {
+                id: "job".into(),
+                name: None,
+                description: None,
+                service_user: None,
+                sql: "SELECT 1".into(),
+                catalog: None,
+                peer_import: None,
+                steps: vec![],
+                triggers: vec![],
+            }],
+            logging: LoggingConfig {
+                level: Some("debug".into()),
+                file: None,
+            },
+            ..Default::default()
+        };
+
+        let plan = ReloadPlan::new(&current, &new, &[]);
+
+        assert!(plan.jobs);
+        assert!(plan.logging);
+        assert_eq!(
+            plan.applied,
+            vec![
+                "Reloaded job scheduler with 1 job(s)".to_string(),
+                "Updated log filter to 'debug'".to_string()
+            ]
+        );
+
+        let applied = plan.apply_to(&current, &new);
+        assert_eq!(applied.jobs, new.jobs);
+        assert_eq!(applied.logging, new.logging);
+    }
+
+    #[test]
+    fn test_reload_plan_publish() {
+        let current = NodeConfig::default();
+        let new = NodeConfig {
+            publish: Some(PublishConfig {
+                kafka: KafkaPublishConfig {
+                    brokers: vec!["localhost:9092".into()],
+                    client_id: None,
+                },
+                tables: vec![PublishTableConfig {
+                    table: "orders".into(),
+                    topic: None,
+                    key: vec![],
+                }],
+                poll_interval_ms: None,
+                batch_size: None,
+            }),
+            ..Default::default()
+        };
+
+        let plan = ReloadPlan::new(&current, &new, &[]);
+
+        assert!(plan.publish);
+        assert!(plan.requires_restart.is_empty());
+        assert_eq!(
+            plan.applied,
+            vec!["Reloaded change publication for 1 table(s)".to_string()]
+        );
+
+        let applied = plan.apply_to(&current, &new);
+        assert_eq!(applied.publish, new.publish);
+    }
+
+    #[test]
+    fn test_reload_plan_drift() {
+        let current = NodeConfig::default();
+        let new = NodeConfig {
+            drift: Some(DriftConfig {
+                interval_secs: Some(600),
+            }),
+            ..Default::default()
+        };
+
+        let plan = ReloadPlan::new(&current, &new, &[]);
+
+        assert!(plan.drift);
+        assert!(plan.requires_restart.is_empty());
+        assert_eq!(plan.applied, vec!["Reloaded drift detection".to_string()]);
+
+        let applied = plan.apply_to(&current, &new);
+        assert_eq!(applied.drift, new.drift);
+
+        let plan = ReloadPlan::new(&new, &current, &[]);
+        assert_eq!(plan.applied, vec!["Disabled drift detection".to_string()]);
+    }
+
+    #[test]
+    fn test_reload_plan_entities() {
+        let mut materialized = entity("d", &["id"]);
+        materialized.materialize = Some(EntityMaterializeConfig {
+            refresh: "0 * * * * *".into(),
+            mode: MaterializeMode::Full,
+            watermark: None,
+        });
+
+        let mut current = NodeConfig::default();
+        current.entities = vec![
+            entity("a", &["id"]),
+            entity("b", &["id"]),
+            materialized.clone(),
+        ];
+        let mut new = NodeConfig::default();
+        new.entities = vec![
+            entity("a", &["id", "name"]),
+            entity("c", &["id"]),
+            entity("d", &["id", "name"]),
+        ];
+
+        let plan = ReloadPlan::new(&current, &new, &[]);
+
+        assert_eq!(
+            plan.entities,
+            vec!["a".to_string(), "c".to_string(), "b".to_string()]
+        );
+        assert_eq!(
+            plan.requires_rebuild,
+            vec![
+                "Entity 'a' changed, requires a rebuild".to_string(),
+                "Entity 'c' changed, requires a rebuild".to_string(),
+                "Entity 'b' changed, requires a rebuild".to_string(),
+            ]
+        );
+        assert_eq!(
+            plan.requires_restart,
+            vec!["Materialized entity 'd' changed, requires a rebuild".to_string()]
+        );
+        assert_eq!(plan.apply_to(&current, &new), current);
+        assert_eq!(
+            plan.apply_with_rebuild_to(&current, &new).entities,
+            new.entities
+        );
+    }
+}
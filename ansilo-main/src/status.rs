@@ -0,0 +1,450 @@
+use std::{
+    fmt::Write,
+    fs::File,
+    io::BufReader,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::Path,
+    time::Duration,
+};
+
+use ansilo_core::{
+    err::{ensure, Context, Result},
+    web::node::NodePools,
+};
+use ansilo_util_health::HealthState;
+use ansilo_web::{api::healthcheck::HealthCheck, VersionInfo};
+use itertools::Itertools;
+use reqwest::{blocking::Client, Certificate, StatusCode};
+
+use crate::{args::StatusArgs, conf::AppConf};
+
+/// A snapshot of the status of the running instance
+#[derive(Debug, Clone)]
+pub struct NodeStatus {
+    /// The version info of the running instance
+    pub version: VersionInfo,
+    /// Whether the instance reports itself as healthy
+    pub healthy: bool,
+    /// The health of each subsystem
+    pub health: HealthCheck,
+    /// The utilisation of the connection pools
+    pub pools: NodePools,
+}
+
+/// Queries the health api of the running instance and prints a
+/// human-readable summary to stdout.
+///
+/// Returns whether the instance is healthy.
+pub fn status(conf: &AppConf, args: &StatusArgs) -> Result<bool> {
+    let status = fetch_status(conf, args)?;
+
+    print!("{}", format_status(conf, &status));
+
+    Ok(status.healthy)
+}
+
+/// Retrieves the status from the running instance
+fn fetch_status(conf: &AppConf, args: &StatusArgs) -> Result<NodeStatus> {
+    let host = host(conf, args);
+    let base_url = base_url(conf, &host);
+
+    let mut client = Client::builder()
+        .connect_timeout(Duration::from_secs(5))
+        .timeout(Duration::from_secs(10))
+        .user_agent("Ansilo/v1");
+
+    // We trust the certificate configured for the instance and, as we are
+    // connecting to it over the listening address, resolve the hostname
+    // which the certificate was issued to to that address.
+    if let Some(tls) = conf.node.networking.tls.as_ref() {
+        for cert in read_certificates(&tls.certificate)? {
+            client = client.add_root_certificate(cert);
+        }
+
+        client = client.resolve(
+            &host,
+            SocketAddr::new(listen_addr(conf), conf.node.networking.port),
+        );
+    }
+
+    let client = client.build().context("Failed to build http client")?;
+
+    let version = client
+        .get(format!("{base_url}/api/version"))
+        .send()
+        .with_context(|| format!("Failed to connect to {base_url}, is the instance running?"))?
+        .error_for_status()?
+        .json::<VersionInfo>()
+        .context("Failed to parse version response")?;
+
+    let res = client
+        .get(format!("{base_url}/api/health"))
+        .send()
+        .with_context(|| format!("Failed to connect to {base_url}, is the instance running?"))?;
+
+    let healthy = res.status() == StatusCode::OK;
+    let health = res
+        .json::<HealthCheck>()
+        .context("Failed to parse health response")?;
+
+    let pools = client
+        .get(format!("{base_url}/api/v1/node/pools"))
+        .send()
+        .with_context(|| format!("Failed to connect to {base_url}, is the instance running?"))?
+        .error_for_status()?
+        .json::<NodePools>()
+        .context("Failed to parse pools response")?;
+
+    Ok(NodeStatus {
+        version,
+        healthy,
+        health,
+        pools,
+    })
+}
+
+/// Reads the certificates from the supplied pem-encoded file
+fn read_certificates(path: &Path) -> Result<Vec<Certificate>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open TLS certificate {}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .with_context(|| format!("Failed to read TLS certificate {}", path.display()))?;
+
+    ensure!(
+        !certs.is_empty(),
+        "No certificates found in {}",
+        path.display()
+    );
+
+    certs
+        .iter()
+        .map(|der| Certificate::from_der(der).context("Failed to parse TLS certificate"))
+        .collect()
+}
+
+/// Gets the address which the running instance is listening on
+fn listen_addr(conf: &AppConf) -> IpAddr {
+    match conf.node.networking.bind {
+        Some(ip) if !ip.is_unspecified() => ip,
+        _ => IpAddr::V4(Ipv4Addr::LOCALHOST),
+    }
+}
+
+/// Gets the host used to connect to the running instance.
+///
+/// If TLS is enabled this is the hostname which the certificate was issued to,
+/// defaulting to the name of the node, otherwise the listening address.
+fn host(conf: &AppConf, args: &StatusArgs) -> String {
+    if conf.node.networking.tls.is_some() {
+        return args.host.clone().unwrap_or_else(|| conf.node.name.clone());
+    }
+
+    match listen_addr(conf) {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{ip}]"),
+    }
+}
+
+/// Gets the base url of the running instance's http api
+fn base_url(conf: &AppConf, host: &str) -> String {
+    let networking = &conf.node.networking;
+
+    let scheme = if networking.tls.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+
+    format!("{scheme}://{host}:{}", networking.port)
+}
+
+/// Formats the status as a human-readable summary
+fn format_status(conf: &AppConf, status: &NodeStatus) -> String {
+    let mut out = String::new();
+
+    let _ = writeln!(out, "Node:     {}", conf.node.name);
+    let _ = writeln!(out, "Version:  {}", status.version.version);
+    let _ = writeln!(out, "Built at: {}", status.version.built_at.to_rfc3339());
+    let _ = writeln!(
+        out,
+        "Status:   {}",
+        if !status.healthy {
+            HealthState::Unhealthy
+        } else if status
+            .health
+            .subsystems
+            .values()
+            .any(|h| h.status != HealthState::Healthy)
+        {
+            HealthState::Degraded
+        } else {
+            HealthState::Healthy
+        }
+    );
+    let _ = writeln!(out);
+    let _ = writeln!(out, "Subsystems:");
+
+    for (name, health) in status
+        .health
+        .subsystems
+        .iter()
+        .sorted_by_key(|(name, _)| name.as_str())
+    {
+        let _ = writeln!(
+            out,
+            "  {:<16} {:<10} last healthy: {}",
+            name,
+            if health.healthy {
+                health.status
+            } else {
+                HealthState::Unhealthy
+            }
+            .to_string(),
+            health
+                .last_healthy
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_else(|| "never".into())
+        );
+
+        if let Some(latency) = health.latency_ms {
+            let _ = writeln!(out, "  {:<16} latency: {}ms", "", latency);
+        }
+        if let Some(reason) = health.reason.as_ref().or(health.error.as_ref()) {
+            let _ = writeln!(out, "  {:<16} reason: {}", "", reason);
+        }
+    }
+
+    let _ = writeln!(out);
+    let _ = writeln!(out, "Pools:");
+
+    for pool in status.pools.pools.iter() {
+        let _ = writeln!(
+            out,
+            "  {:<16} open: {}/{} idle: {} waiting: {}",
+            pool.name, pool.size, pool.max_size, pool.available, pool.waiting
+        );
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use ansilo_core::{
+        config::{NetworkingConfig, NodeConfig, TlsConfig},
+        data::chrono::{DateTime, Utc},
+        web::node::PoolStatus,
+    };
+
+    use ansilo_util_health::HealthStatus;
+
+    use crate::args::Args;
+
+    use super::*;
+
+    fn mock_conf(networking: NetworkingConfig) -> AppConf {
+        AppConf {
+            node: Arc::new(NodeConfig {
+                name: "test-node".into(),
+                networking,
+                ..Default::default()
+            }),
+            path: "/unused".into(),
+            pg: crate::conf::pg_conf(&NodeConfig::default()),
+        }
+    }
+
+    fn mock_args(host: Option<&str>) -> StatusArgs {
+        StatusArgs {
+            args: Args {
+                config: None,
+                config_args: vec![],
+                force_build: false,
+                plugins_dir: None,
+            },
+            host: host.map(|h| h.into()),
+        }
+    }
+
+    fn url(networking: NetworkingConfig, host: Option<&str>) -> String {
+        let conf = mock_conf(networking);
+        base_url(&conf, &super::host(&conf, &mock_args(host)))
+    }
+
+    #[test]
+    fn test_base_url() {
+        assert_eq!(
+            url(
+                NetworkingConfig {
+                    port: 65432,
+                    bind: None,
+                    tls: None
+                },
+                None
+            ),
+            "http://127.0.0.1:65432"
+        );
+        assert_eq!(
+            url(
+                NetworkingConfig {
+                    port: 1234,
+                    bind: Some("0.0.0.0".parse().unwrap()),
+                    tls: None
+                },
+                None
+            ),
+            "http://127.0.0.1:1234"
+        );
+        assert_eq!(
+            url(
+                NetworkingConfig {
+                    port: 1234,
+                    bind: Some("::1".parse().unwrap()),
+                    tls: None
+                },
+                Some("ignored.test")
+            ),
+            "http://[::1]:1234"
+        );
+    }
+
+    #[test]
+    fn test_base_url_tls() {
+        let networking = NetworkingConfig {
+            port: 1234,
+            bind: Some("10.0.0.1".parse().unwrap()),
+            tls: Some(TlsConfig::default()),
+        };
+
+        assert_eq!(url(networking.clone(), None), "https://test-node:1234");
+        assert_eq!(
+            url(networking.clone(), Some("node.example.com")),
+            "https://node.example.com:1234"
+        );
+        assert_eq!(
+            listen_addr(&mock_conf(networking)),
+            "10.0.0.1".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_certificates() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let path = dir.path().join("missing.pem");
+        read_certificates(&path).unwrap_err();
+
+        let path = dir.path().join("empty.pem");
+        std::fs::write(&path, "").unwrap();
+        read_certificates(&path).unwrap_err();
+    }
+
+    #[test]
+    fn test_format_status() {
+        let conf = mock_conf(NetworkingConfig::default());
+        let status = NodeStatus {
+            version: VersionInfo::new("1.0.0", DateTime::<Utc>::MIN_UTC),
+            healthy: false,
+            health: HealthCheck {
+                subsystems: HashMap::from([
+                    (
+                        "Proxy".to_string(),
+                        HealthStatus {
+                            healthy: true,
+                            status: HealthState::Healthy,
+                            reason: None,
+                            checked: DateTime::<Utc>::MIN_UTC,
+                            last_healthy: Some(DateTime::<Utc>::MIN_UTC),
+                            latency_ms: None,
+                            error: None,
+                            critical: true,
+                            consecutive_successes: 0,
+                            consecutive_failures: 0,
+                        },
+                    ),
+                    (
+                        "FDW".to_string(),
+                        HealthStatus {
+                            healthy: false,
+                            status: HealthState::Unhealthy,
+                            reason: None,
+                            checked: DateTime::<Utc>::MIN_UTC,
+                            last_healthy: None,
+                            latency_ms: None,
+                            error: None,
+                            critical: true,
+                            consecutive_successes: 0,
+                            consecutive_failures: 0,
+                        },
+                    ),
+                    (
+                        "Source:mysql".to_string(),
+                        HealthStatus {
+                            healthy: false,
+                            status: HealthState::Unhealthy,
+                            reason: Some("Connection refused".into()),
+                            checked: DateTime::<Utc>::MIN_UTC,
+                            last_healthy: None,
+                            latency_ms: Some(12),
+                            error: Some("Connection refused".into()),
+                            critical: false,
+                            consecutive_successes: 0,
+                            consecutive_failures: 1,
+                        },
+                    ),
+                ]),
+            },
+            pools: NodePools {
+                pools: vec![
+                    PoolStatus::new("admin", 2, 1, 5),
+                    PoolStatus::new("app", 10, -3, 10),
+                ],
+            },
+        };
+
+        let out = format_status(&conf, &status);
+
+        assert!(out.contains("Node:     test-node"));
+        assert!(out.contains("Status:   unhealthy"));
+        assert!(out.find("FDW").unwrap() < out.find("Proxy").unwrap());
+        assert!(out.contains("last healthy: never"));
+        assert!(out.contains("latency: 12ms"));
+        assert!(out.contains("reason: Connection refused"));
+        assert!(out.contains("admin            open: 2/5 idle: 1 waiting: 0"));
+        assert!(out.contains("app              open: 10/10 idle: 0 waiting: 3"));
+    }
+
+    #[test]
+    fn test_format_status_degraded() {
+        let conf = mock_conf(NetworkingConfig::default());
+        let status = NodeStatus {
+            version: VersionInfo::new("1.0.0", DateTime::<Utc>::MIN_UTC),
+            healthy: true,
+            health: HealthCheck {
+                subsystems: HashMap::from([(
+                    "Disk".to_string(),
+                    HealthStatus {
+                        healthy: true,
+                        status: HealthState::Degraded,
+                        reason: Some("8.0% of disk space is free".into()),
+                        checked: DateTime::<Utc>::MIN_UTC,
+                        last_healthy: Some(DateTime::<Utc>::MIN_UTC),
+                        latency_ms: Some(0),
+                        error: None,
+                        critical: false,
+                        consecutive_successes: 1,
+                        consecutive_failures: 0,
+                    },
+                )]),
+            },
+            pools: NodePools { pools: vec![] },
+        };
+
+        let out = format_status(&conf, &status);
+
+        assert!(out.contains("Status:   degraded"));
+        assert!(out.contains("reason: 8.0% of disk space is free"));
+    }
+}
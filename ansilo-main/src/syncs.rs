@@ -0,0 +1,502 @@
+use ansilo_core::{
+    config::{
+        CronTriggerConfig, EntityConfig, JobConfig, JobTriggerConfig, NodeConfig, SyncConfig,
+        SyncConflictStrategy,
+    },
+    err::{bail, Context, Result},
+};
+use ansilo_pg::PG_ADMIN_USER;
+use ansilo_util_pg::query::pg_quote_identifier;
+use itertools::Itertools;
+
+/// The schema containing the foreign tables which the syncs read from and write to
+const SYNC_SCHEMA: &str = "ansilo_sync";
+
+/// The schema containing the watermark of each incremental sync
+const WATERMARK_SCHEMA: &str = "ansilo_sync_watermarks";
+
+/// The temporary table the rows copied by a sync are staged in
+const STAGING_TABLE: &str = "ansilo_sync_rows";
+
+/// The column of the staging table holding the watermark of each row
+const WATERMARK_COLUMN: &str = "__ansilo_watermark";
+
+/// The prefix of the ids of the jobs which run the syncs
+const SYNC_JOB_PREFIX: &str = "sync:";
+
+/// The id of the job which runs the sync
+pub fn sync_job_id(sync: &SyncConfig) -> String {
+    format!("{SYNC_JOB_PREFIX}{}", sync.id)
+}
+
+/// The qualified name of the foreign table of the entity
+fn entity_table(entity: &EntityConfig) -> String {
+    format!("{SYNC_SCHEMA}.{}", pg_quote_identifier(&entity.id))
+}
+
+/// The qualified name of the table storing the watermark of the sync
+fn watermark_table(sync: &SyncConfig) -> String {
+    format!("{WATERMARK_SCHEMA}.{}", pg_quote_identifier(&sync.id))
+}
+
+fn find_entity<'a>(node: &'a NodeConfig, id: &str) -> Result<&'a EntityConfig> {
+    node.entities
+        .iter()
+        .find(|e| e.id == id)
+        .with_context(|| format!("Unknown entity '{id}'"))
+}
+
+/// Returns the pairs of source and target attributes copied by the sync
+fn columns<'a>(
+    sync: &'a SyncConfig,
+    source: &'a EntityConfig,
+    target: &'a EntityConfig,
+) -> Vec<(&'a str, &'a str)> {
+    if !sync.columns.is_empty() {
+        return sync
+            .columns
+            .iter()
+            .map(|c| (c.source.as_str(), c.target.as_str()))
+            .collect();
+    }
+
+    target
+        .attributes
+        .iter()
+        .filter(|t| source.attributes.iter().any(|s| s.id == t.id))
+        .map(|t| (t.id.as_str(), t.id.as_str()))
+        .collect()
+}
+
+/// Returns the target attributes which identify the existing rows
+fn key<'a>(sync: &'a SyncConfig, target: &'a EntityConfig) -> Vec<&'a str> {
+    if !sync.key.is_empty() {
+        return sync.key.iter().map(|k| k.as_str()).collect();
+    }
+
+    target
+        .primary_keys()
+        .iter()
+        .map(|a| a.id.as_str())
+        .collect()
+}
+
+/// Imports the foreign tables of the entities used by the syncs along
+/// with a table to store the watermark of each incremental sync.
+///
+/// This is run when the database is initialised, syncs referencing unknown
+/// entities are reported when their jobs are generated.
+pub(crate) fn init_sql(node: &NodeConfig) -> Vec<String> {
+    if node.syncs.is_empty() {
+        return vec![];
+    }
+
+    let mut sql = vec![format!(
+        "CREATE SCHEMA {SYNC_SCHEMA}; CREATE SCHEMA {WATERMARK_SCHEMA};"
+    )];
+
+    let entities = node
+        .syncs
+        .iter()
+        .flat_map(|s| [s.source.as_str(), s.target.as_str()])
+        .unique()
+        .filter_map(|id| find_entity(node, id).ok());
+
+    for entity in entities {
+        let id = pg_quote_identifier(&entity.id);
+        let server = pg_quote_identifier(&entity.source.data_source);
+
+        sql.push(format!(
+            r#"
+            IMPORT FOREIGN SCHEMA {id} LIMIT TO ({id})
+            FROM SERVER {server}
+            INTO {SYNC_SCHEMA};
+        "#
+        ));
+    }
+
+    for sync in node.syncs.iter() {
+        let (watermark, source) = match (sync.watermark.as_ref(), find_entity(node, &sync.source)) {
+            (Some(watermark), Ok(source)) => (pg_quote_identifier(watermark), entity_table(source)),
+            _ => continue,
+        };
+
+        // The watermark is stored using the type of the source attribute
+        sql.push(format!(
+            "CREATE TABLE {} AS SELECT {watermark} AS value FROM {source} WITH NO DATA;",
+            watermark_table(sync)
+        ));
+    }
+
+    // The tables are only accessible to the admin user, which runs the sync jobs
+    sql.push(format!(
+        r#"
+        GRANT USAGE ON SCHEMA {SYNC_SCHEMA}, {WATERMARK_SCHEMA} TO {PG_ADMIN_USER};
+        GRANT SELECT, INSERT, UPDATE, DELETE ON ALL TABLES IN SCHEMA {SYNC_SCHEMA}, {WATERMARK_SCHEMA} TO {PG_ADMIN_USER};
+    "#
+    ));
+
+    sql
+}
+
+/// Returns the sql which copies the rows of the source entity into the target entity.
+///
+/// The rows are first staged in a temporary table, from which they are written
+/// to the target using the conflict strategy of the sync. Inserts into the target
+/// are sent to the data source in batches using the connector's bulk insert.
+/// When a watermark is configured only the rows with a watermark greater than the
+/// highest copied by the previous run are read, the watermark is then advanced in
+/// the same transaction as the rows are written.
+pub fn sync_sql(node: &NodeConfig, sync: &SyncConfig) -> Result<String> {
+    let source = find_entity(node, &sync.source)?;
+    let target = find_entity(node, &sync.target)?;
+    let source_table = entity_table(source);
+    let target_table = entity_table(target);
+
+    let columns = columns(sync, source, target);
+    if columns.is_empty() {
+        bail!(
+            "Sync '{}' does not copy any attributes from '{}' to '{}'",
+            sync.id,
+            source.id,
+            target.id
+        );
+    }
+
+    let mut select = columns
+        .iter()
+        .map(|(s, t)| {
+            format!(
+                "src.{} AS {}",
+                pg_quote_identifier(s),
+                pg_quote_identifier(t)
+            )
+        })
+        .collect::<Vec<_>>();
+    let targets = columns
+        .iter()
+        .map(|(_, t)| pg_quote_identifier(t))
+        .collect::<Vec<_>>();
+
+    let stage = match sync.watermark.as_ref() {
+        Some(watermark) => {
+            let watermark = pg_quote_identifier(watermark);
+            let watermarks = watermark_table(sync);
+            select.push(format!("src.{watermark} AS {WATERMARK_COLUMN}"));
+            let select = select.join(", ");
+
+            format!(
+                r#"
+                IF EXISTS (SELECT 1 FROM {watermarks}) THEN
+                    CREATE TEMP TABLE {STAGING_TABLE} ON COMMIT DROP AS
+                    SELECT {select} FROM {source_table} src
+                    WHERE src.{watermark} > (SELECT value FROM {watermarks});
+                ELSE
+                    CREATE TEMP TABLE {STAGING_TABLE} ON COMMIT DROP AS
+                    SELECT {select} FROM {source_table} src;
+                END IF;
+            "#
+            )
+        }
+        None => format!(
+            r#"
+                CREATE TEMP TABLE {STAGING_TABLE} ON COMMIT DROP AS
+                SELECT {} FROM {source_table} src;
+            "#,
+            select.join(", ")
+        ),
+    };
+
+    let insert = format!(
+        "INSERT INTO {target_table} ({}) SELECT {} FROM {STAGING_TABLE} r",
+        targets.join(", "),
+        targets.iter().map(|t| format!("r.{t}")).join(", ")
+    );
+
+    let key = key(sync, target);
+    if sync.conflict.requires_key() && key.is_empty() {
+        bail!(
+            "Sync '{}' must define a key to find the existing rows of '{}'",
+            sync.id,
+            target.id
+        );
+    }
+    let matches = key
+        .iter()
+        .map(|k| {
+            let k = pg_quote_identifier(k);
+            format!("t.{k} = r.{k}")
+        })
+        .join(" AND ");
+
+    let apply = match sync.conflict {
+        SyncConflictStrategy::Append => format!("{insert};"),
+        SyncConflictStrategy::Replace => format!("DELETE FROM {target_table};\n{insert};"),
+        SyncConflictStrategy::Skip => {
+            format!("{insert}\nWHERE NOT EXISTS (SELECT 1 FROM {target_table} t WHERE {matches});")
+        }
+        SyncConflictStrategy::Upsert => {
+            let updates = columns
+                .iter()
+                .filter(|(_, t)| !key.contains(t))
+                .map(|(_, t)| {
+                    let t = pg_quote_identifier(t);
+                    format!("{t} = r.{t}")
+                })
+                .collect::<Vec<_>>();
+            let update = if updates.is_empty() {
+                "".into()
+            } else {
+                format!(
+                    "UPDATE {target_table} t SET {} FROM {STAGING_TABLE} r WHERE {matches};\n",
+                    updates.join(", ")
+                )
+            };
+
+            format!(
+                "{update}{insert}\nWHERE NOT EXISTS (SELECT 1 FROM {target_table} t WHERE {matches});"
+            )
+        }
+    };
+
+    let advance = match sync.watermark.as_ref() {
+        Some(_) => {
+            let watermarks = watermark_table(sync);
+
+            format!(
+                r#"
+                IF EXISTS (SELECT 1 FROM {STAGING_TABLE}) THEN
+                    DELETE FROM {watermarks};
+                    INSERT INTO {watermarks} SELECT MAX({WATERMARK_COLUMN}) FROM {STAGING_TABLE};
+                END IF;
+            "#
+            )
+        }
+        None => "".into(),
+    };
+
+    Ok(format!(
+        r#"
+        DO $$
+        BEGIN
+            {stage}
+            {apply}
+            {advance}
+        END $$;
+    "#
+    ))
+}
+
+/// Returns a job to run each sync on its schedule
+pub fn jobs(node: &NodeConfig) -> Result<Vec<JobConfig>> {
+    node.syncs
+        .iter()
+        .map(|sync| {
+            Ok(JobConfig {
+                id: sync_job_id(sync),
+                name: Some(format!("Sync {} to {}", sync.source, sync.target)),
+                description: None,
+                service_user: None,
+                sql: sync_sql(node, sync)
+                    .with_context(|| format!("Failed to generate sql of sync '{}'", sync.id))?,
+                catalog: None,
+                peer_import: None,
+                steps: vec![],
+                triggers: vec![JobTriggerConfig::Cron(CronTriggerConfig {
+                    cron: sync.schedule.clone(),
+                })],
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::{
+        config::{EntityAttributeConfig, EntitySourceConfig, SyncColumnConfig},
+        data::DataType,
+    };
+
+    use super::*;
+
+    fn mock_node(sync: SyncConfig) -> NodeConfig {
+        NodeConfig {
+            entities: vec![
+                EntityConfig::minimal(
+                    "customers",
+                    vec![
+                        EntityAttributeConfig::new("id".into(), None, DataType::Int32, true, false),
+                        EntityAttributeConfig::minimal("name", DataType::rust_string()),
+                        EntityAttributeConfig::minimal("updated_at", DataType::DateTime),
+                    ],
+                    EntitySourceConfig::minimal("crm"),
+                ),
+                EntityConfig::minimal(
+                    "contacts",
+                    vec![
+                        EntityAttributeConfig::new("id".into(), None, DataType::Int32, true, false),
+                        EntityAttributeConfig::minimal("name", DataType::rust_string()),
+                    ],
+                    EntitySourceConfig::minimal("warehouse"),
+                ),
+            ],
+            syncs: vec![sync],
+            ..Default::default()
+        }
+    }
+
+    fn mock_sync(conflict: SyncConflictStrategy, watermark: Option<&str>) -> SyncConfig {
+        SyncConfig {
+            id: "contacts".into(),
+            source: "customers".into(),
+            target: "contacts".into(),
+            schedule: "0 0 * * * *".into(),
+            columns: vec![],
+            watermark: watermark.map(|w| w.into()),
+            conflict,
+            key: vec![],
+        }
+    }
+
+    /// Normalises the whitespace of the sql for comparison
+    fn normalise(sql: &str) -> String {
+        sql.split_whitespace().join(" ")
+    }
+
+    #[test]
+    fn test_columns_default_to_matching_attributes() {
+        let node = mock_node(mock_sync(SyncConflictStrategy::Append, None));
+        let (source, target) = (&node.entities[0], &node.entities[1]);
+
+        assert_eq!(
+            columns(&node.syncs[0], source, target),
+            vec![("id", "id"), ("name", "name")]
+        );
+
+        let mut sync = node.syncs[0].clone();
+        sync.columns = vec![SyncColumnConfig {
+            source: "name".into(),
+            target: "name".into(),
+        }];
+        assert_eq!(columns(&sync, source, target), vec![("name", "name")]);
+    }
+
+    #[test]
+    fn test_init_sql() {
+        let node = mock_node(mock_sync(SyncConflictStrategy::Upsert, Some("updated_at")));
+        let sql = init_sql(&node);
+
+        assert_eq!(sql.len(), 5);
+        assert_eq!(
+            sql[0],
+            "CREATE SCHEMA ansilo_sync; CREATE SCHEMA ansilo_sync_watermarks;"
+        );
+        assert!(sql[1].contains(r#"IMPORT FOREIGN SCHEMA "customers" LIMIT TO ("customers")"#));
+        assert!(sql[1].contains(r#"FROM SERVER "crm""#));
+        assert!(sql[2].contains(r#"IMPORT FOREIGN SCHEMA "contacts" LIMIT TO ("contacts")"#));
+        assert_eq!(
+            sql[3],
+            r#"CREATE TABLE ansilo_sync_watermarks."contacts" AS SELECT "updated_at" AS value FROM ansilo_sync."customers" WITH NO DATA;"#
+        );
+    }
+
+    #[test]
+    fn test_init_sql_without_syncs() {
+        assert_eq!(
+            init_sql(&NodeConfig::default()).unwrap(),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_sync_sql_append() {
+        let node = mock_node(mock_sync(SyncConflictStrategy::Append, None));
+        let sql = normalise(&sync_sql(&node, &node.syncs[0]).unwrap());
+
+        assert_eq!(
+            sql,
+            normalise(
+                r#"
+                DO $$
+                BEGIN
+                    CREATE TEMP TABLE ansilo_sync_rows ON COMMIT DROP AS
+                    SELECT src."id" AS "id", src."name" AS "name" FROM ansilo_sync."customers" src;
+                    INSERT INTO ansilo_sync."contacts" ("id", "name") SELECT r."id", r."name" FROM ansilo_sync_rows r;
+                END $$;
+            "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_sync_sql_replace() {
+        let node = mock_node(mock_sync(SyncConflictStrategy::Replace, None));
+        let sql = normalise(&sync_sql(&node, &node.syncs[0]).unwrap());
+
+        assert!(sql
+            .contains(r#"DELETE FROM ansilo_sync."contacts"; INSERT INTO ansilo_sync."contacts""#));
+    }
+
+    #[test]
+    fn test_sync_sql_upsert_with_watermark() {
+        let node = mock_node(mock_sync(SyncConflictStrategy::Upsert, Some("updated_at")));
+        let sql = normalise(&sync_sql(&node, &node.syncs[0]).unwrap());
+
+        assert_eq!(
+            sql,
+            normalise(
+                r#"
+                DO $$
+                BEGIN
+                    IF EXISTS (SELECT 1 FROM ansilo_sync_watermarks."contacts") THEN
+                        CREATE TEMP TABLE ansilo_sync_rows ON COMMIT DROP AS
+                        SELECT src."id" AS "id", src."name" AS "name", src."updated_at" AS __ansilo_watermark FROM ansilo_sync."customers" src
+                        WHERE src."updated_at" > (SELECT value FROM ansilo_sync_watermarks."contacts");
+                    ELSE
+                        CREATE TEMP TABLE ansilo_sync_rows ON COMMIT DROP AS
+                        SELECT src."id" AS "id", src."name" AS "name", src."updated_at" AS __ansilo_watermark FROM ansilo_sync."customers" src;
+                    END IF;
+                    UPDATE ansilo_sync."contacts" t SET "name" = r."name" FROM ansilo_sync_rows r WHERE t."id" = r."id";
+                    INSERT INTO ansilo_sync."contacts" ("id", "name") SELECT r."id", r."name" FROM ansilo_sync_rows r
+                    WHERE NOT EXISTS (SELECT 1 FROM ansilo_sync."contacts" t WHERE t."id" = r."id");
+                    IF EXISTS (SELECT 1 FROM ansilo_sync_rows) THEN
+                        DELETE FROM ansilo_sync_watermarks."contacts";
+                        INSERT INTO ansilo_sync_watermarks."contacts" SELECT MAX(__ansilo_watermark) FROM ansilo_sync_rows;
+                    END IF;
+                END $$;
+            "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_sync_sql_skip_requires_key() {
+        let mut node = mock_node(mock_sync(SyncConflictStrategy::Skip, None));
+        node.entities[1].attributes[0].primary_key = false;
+
+        sync_sql(&node, &node.syncs[0]).unwrap_err();
+
+        node.syncs[0].key = vec!["id".into()];
+        let sql = normalise(&sync_sql(&node, &node.syncs[0]).unwrap());
+
+        assert!(sql.contains(
+            r#"WHERE NOT EXISTS (SELECT 1 FROM ansilo_sync."contacts" t WHERE t."id" = r."id");"#
+        ));
+        assert!(!sql.contains("UPDATE"));
+    }
+
+    #[test]
+    fn test_jobs() {
+        let node = mock_node(mock_sync(SyncConflictStrategy::Append, None));
+        let jobs = jobs(&node).unwrap();
+
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, "sync:contacts");
+        assert_eq!(
+            jobs[0].triggers,
+            vec![JobTriggerConfig::Cron(CronTriggerConfig {
+                cron: "0 0 * * * *".into()
+            })]
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use ansilo_logging::{debug, warn};
+use sd_notify::NotifyState;
+
+/// Notifies systemd that start up is complete and the instance
+/// is ready to accept connections.
+///
+/// This, like the other notifications, is a no-op when we are not
+/// running under a `Type=notify` systemd unit.
+pub fn notify_ready() {
+    notify(NotifyState::Ready);
+}
+
+/// Notifies systemd that the instance is shutting down
+pub fn notify_stopping() {
+    notify(NotifyState::Stopping);
+}
+
+/// Sends a watchdog keep-alive to systemd.
+/// This should only be sent while the instance is healthy.
+pub fn notify_watchdog() {
+    notify(NotifyState::Watchdog);
+}
+
+/// Returns the interval at which watchdog keep-alives should be sent,
+/// if the watchdog is enabled for the unit.
+///
+/// As recommended by systemd, this is half of the configured watchdog timeout.
+pub fn watchdog_interval() -> Option<Duration> {
+    let mut usec = 0;
+
+    if sd_notify::watchdog_enabled(false, &mut usec) {
+        Some(Duration::from_micros(usec) / 2)
+    } else {
+        None
+    }
+}
+
+fn notify(state: NotifyState) {
+    debug!("Notifying systemd: {:?}", state);
+
+    if let Err(err) = sd_notify::notify(false, &[state]) {
+        warn!("Failed to notify systemd: {:?}", err);
+    }
+}
@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use ansilo_core::config::ResourceConfig;
 
-use crate::PG_PORT;
+use crate::{replication::ReplicationConf, PG_PORT};
 
 /// Configuration of the postgres installation
 #[derive(Debug, Clone, PartialEq)]
@@ -26,9 +26,29 @@ pub struct PostgresConf {
     /// Additional queries to run on database initialisation
     /// Used to bootstrap any initial configuration
     pub init_db_sql: Vec<String>,
+    /// Additional databases which are created on initialisation
+    pub catalogs: Vec<PostgresCatalogConf>,
+    /// If set, postgres replicates to or from the other node of a high-availability pair
+    pub replication: Option<ReplicationConf>,
+}
+
+/// A separate database within the postgres instance
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostgresCatalogConf {
+    /// The name of the database
+    pub name: String,
+    /// The app users which are permitted to connect to the database
+    pub users: Vec<String>,
+    /// Queries to run in the database on initialisation
+    pub init_db_sql: Vec<String>,
 }
 
 impl PostgresConf {
+    /// Gets the catalog with the supplied database name
+    pub fn catalog(&self, name: &str) -> Option<&PostgresCatalogConf> {
+        self.catalogs.iter().find(|c| c.name == name)
+    }
+
     /// Gets the full path of the postgres unix socket
     pub fn pg_socket_path(&self) -> PathBuf {
         self.socket_dir_path.join(format!(".s.PGSQL.{}", PG_PORT))
@@ -50,6 +70,8 @@ mod tests {
             fdw_socket_path: PathBuf::from("/"),
             app_users: vec![],
             init_db_sql: vec![],
+            catalogs: vec![],
+            replication: None,
         };
 
         assert_eq!(
@@ -1,7 +1,12 @@
 use ansilo_core::err::{Context, Result};
-use ansilo_util_pg::query::pg_quote_identifier;
+use ansilo_util_pg::query::{pg_quote_identifier, pg_str_literal};
 
-use crate::{conf::PostgresConf, connection::PostgresConnection, PG_ADMIN_USER, PG_DATABASE};
+use crate::{
+    conf::{PostgresCatalogConf, PostgresConf},
+    connection::PostgresConnection,
+    replication::PG_REPLICATION_USER,
+    PG_ADMIN_USER, PG_DATABASE,
+};
 
 /// Configures a new postgres database such that is ready for use
 pub(crate) async fn configure(
@@ -9,7 +14,8 @@ pub(crate) async fn configure(
     mut superuser_con: PostgresConnection,
 ) -> Result<()> {
     configure_roles(conf, &mut superuser_con).await?;
-    configure_extension(conf, &mut superuser_con).await?;
+    configure_database(PG_DATABASE, &mut superuser_con).await?;
+    configure_extension(&conf.app_users, &mut superuser_con).await?;
 
     for sql in conf.init_db_sql.iter() {
         superuser_con
@@ -18,6 +24,51 @@ pub(crate) async fn configure(
             .context("Failed run db initialisation sql")?;
     }
 
+    // Create the databases of the catalogs, which are only accessible to their users
+    for catalog in conf.catalogs.iter() {
+        let name = pg_quote_identifier(&catalog.name);
+        let users = [PG_ADMIN_USER.to_string()]
+            .into_iter()
+            .chain(catalog.users.iter().map(|u| pg_quote_identifier(u)))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        // CREATE DATABASE cannot run within a transaction block so is executed on its own
+        superuser_con
+            .batch_execute(&format!("CREATE DATABASE {name};"))
+            .await
+            .with_context(|| format!("Failed to create database of catalog '{}'", catalog.name))?;
+        superuser_con
+            .batch_execute(&format!(
+                r#"
+            REVOKE CONNECT ON DATABASE {name} FROM public;
+            GRANT CONNECT ON DATABASE {name} TO {users};
+            "#
+            ))
+            .await
+            .with_context(|| format!("Failed to grant access to catalog '{}'", catalog.name))?;
+    }
+
+    Ok(())
+}
+
+/// Configures the database of a catalog, using a superuser connection to that database
+pub(crate) async fn configure_catalog(
+    catalog: &PostgresCatalogConf,
+    mut superuser_con: PostgresConnection,
+) -> Result<()> {
+    configure_database(&catalog.name, &mut superuser_con).await?;
+    configure_extension(&catalog.users, &mut superuser_con).await?;
+
+    for sql in catalog.init_db_sql.iter() {
+        superuser_con.batch_execute(sql).await.with_context(|| {
+            format!(
+                "Failed run db initialisation sql of catalog '{}'",
+                catalog.name
+            )
+        })?;
+    }
+
     Ok(())
 }
 
@@ -30,13 +81,8 @@ async fn configure_roles(
         .batch_execute(
             format!(
                 r#"
-            -- Important: remove default CREATE on public schema
-            REVOKE CREATE ON SCHEMA public FROM public;
-
             -- Create admin user
             CREATE USER {PG_ADMIN_USER} PASSWORD NULL;
-            GRANT CREATE ON DATABASE {PG_DATABASE} TO {PG_ADMIN_USER} WITH GRANT OPTION;
-            GRANT ALL ON SCHEMA public TO {PG_ADMIN_USER} WITH GRANT OPTION;
             "#
             )
             .as_str(),
@@ -60,11 +106,50 @@ async fn configure_roles(
             .context("Failed to initialise app user")?;
     }
 
+    // Create the user the standby replicates as
+    if let Some(replication) = conf.replication.as_ref() {
+        let password = pg_str_literal(&replication.password);
+        superuser_con
+            .batch_execute(
+                format!(
+                    r#"
+            CREATE USER {PG_REPLICATION_USER} WITH REPLICATION PASSWORD {password};
+            "#
+                )
+                .as_str(),
+            )
+            .await
+            .context("Failed to initialise replication user")?;
+    }
+
+    Ok(())
+}
+
+/// Grants the admin user ownership of the public schema of the database
+async fn configure_database(database: &str, superuser_con: &mut PostgresConnection) -> Result<()> {
+    let database = pg_quote_identifier(database);
+
+    superuser_con
+        .batch_execute(
+            format!(
+                r#"
+            -- Important: remove default CREATE on public schema
+            REVOKE CREATE ON SCHEMA public FROM public;
+
+            GRANT CREATE ON DATABASE {database} TO {PG_ADMIN_USER} WITH GRANT OPTION;
+            GRANT ALL ON SCHEMA public TO {PG_ADMIN_USER} WITH GRANT OPTION;
+            "#
+            )
+            .as_str(),
+        )
+        .await
+        .context("Failed to initialise database")?;
+
     Ok(())
 }
 
 async fn configure_extension(
-    conf: &PostgresConf,
+    app_users: &[String],
     superuser_con: &mut PostgresConnection,
 ) -> Result<()> {
     superuser_con
@@ -90,7 +175,7 @@ async fn configure_extension(
         .context("Failed to initialise ansilo extension")?;
 
     // Configure user-provided users
-    for user in conf.app_users.iter() {
+    for user in app_users.iter() {
         let user = pg_quote_identifier(user);
         superuser_con
             .batch_execute(
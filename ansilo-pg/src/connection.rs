@@ -1,6 +1,9 @@
 use std::time::Duration;
 
-use ansilo_core::err::{Context, Result};
+use ansilo_core::{
+    err::{Context, Result},
+    web::node::PoolStatus,
+};
 use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
 use tokio_postgres::NoTls;
 
@@ -61,6 +64,12 @@ impl PostgresConnectionPool {
             .await
             .context("Failed to acquire a connection from the connection pool")
     }
+
+    /// Gets the utilisation of the pool
+    pub fn status(&self, name: &str) -> PoolStatus {
+        let status = self.pool.status();
+        PoolStatus::new(name, status.size, status.available, status.max_size)
+    }
 }
 
 #[cfg(test)]
@@ -91,6 +100,8 @@ mod tests {
             fdw_socket_path: PathBuf::from("not-used"),
             app_users: vec![],
             init_db_sql: vec![],
+            catalogs: vec![],
+            replication: None,
         };
         Box::leak(Box::new(conf))
     }
@@ -0,0 +1,424 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use ansilo_core::{
+    config::{NodeConfig, WorkloadClass},
+    err::{bail, Error, Result},
+};
+use ansilo_logging::debug;
+
+/// Limits the number of sessions concurrently executing scans against each
+/// data source, so bursts of queries cannot exhaust the sessions of the remote
+/// database. Sessions which exceed the limit are admitted in the order they
+/// arrived, with interactive sessions admitted ahead of batch sessions, failing
+/// if they are not admitted before the queue timeout.
+#[derive(Clone, Default)]
+pub struct AdmissionControl {
+    /// The scan slots of each limited data source keyed by the data source id
+    sources: Arc<Mutex<HashMap<String, Arc<ScanSlots>>>>,
+}
+
+impl AdmissionControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the scan slots of the data source, returns `None` if neither the scans of
+    /// the data source nor those of any workload class are limited.
+    ///
+    /// If the limits of the data source have changed the slots are replaced, sessions
+    /// holding a slot of the previous limits release it as per usual.
+    pub(crate) fn slots(
+        &self,
+        nc: &NodeConfig,
+        data_source_id: &str,
+    ) -> Result<Option<Arc<ScanSlots>>> {
+        let conf = match nc.sources.iter().find(|s| s.id == data_source_id) {
+            Some(conf) => conf,
+            None => return Ok(None),
+        };
+        let max = conf.max_concurrent_scans();
+        let timeout = conf.admission_queue_timeout();
+
+        let class_limited = nc
+            .auth
+            .workloads
+            .iter()
+            .any(|w| w.max_concurrent_scans.is_some());
+        if max.is_none() && !class_limited {
+            return Ok(None);
+        }
+
+        let mut sources = self
+            .sources
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock admission control"))?;
+
+        let slots = sources
+            .entry(data_source_id.into())
+            .or_insert_with(|| Arc::new(ScanSlots::new(data_source_id, max, timeout)));
+
+        if slots.max != max || slots.timeout != timeout {
+            *slots = Arc::new(ScanSlots::new(data_source_id, max, timeout));
+        }
+
+        Ok(Some(Arc::clone(slots)))
+    }
+}
+
+/// The slots available to sessions scanning a single data source
+pub(crate) struct ScanSlots {
+    /// The id of the data source
+    data_source_id: String,
+    /// The maximum number of sessions concurrently scanning the data source
+    max: Option<usize>,
+    /// How long a session waits to be admitted
+    timeout: Duration,
+    state: Mutex<SlotState>,
+    /// Signalled when a slot is released or a session leaves the queue
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct SlotState {
+    /// The number of sessions of each workload class currently admitted
+    running: HashMap<WorkloadClass, usize>,
+    /// The queued sessions ordered by the priority of their class and then
+    /// the order they arrived, with the limit on the sessions of their class
+    queue: BTreeMap<(WorkloadClass, u64), Option<usize>>,
+    /// The ticket issued to the next queued session
+    next_ticket: u64,
+}
+
+/// A slot held by a session while it is scanning the data source, which is
+/// released when dropped
+pub(crate) struct ScanPermit(Arc<ScanSlots>, WorkloadClass);
+
+impl ScanSlots {
+    fn new(data_source_id: &str, max: Option<usize>, timeout: Duration) -> Self {
+        Self {
+            data_source_id: data_source_id.into(),
+            max,
+            timeout,
+            state: Mutex::new(SlotState::default()),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Waits for a slot to become available, failing if the queue timeout elapses first.
+    /// Queued sessions of a higher priority class are admitted first, unless their
+    /// class has reached the supplied limit on its concurrent sessions.
+    pub(crate) fn acquire(
+        self: &Arc<Self>,
+        class: WorkloadClass,
+        class_max: Option<usize>,
+    ) -> Result<ScanPermit> {
+        let mut state = self.lock()?;
+
+        let key = (class, state.next_ticket);
+        state.next_ticket += 1;
+        state.queue.insert(key, class_max);
+
+        let deadline = Instant::now() + self.timeout;
+        let mut logged = false;
+
+        loop {
+            if state.next_admitted(self.max) == Some(key) {
+                state.queue.remove(&key);
+                *state.running.entry(class).or_default() += 1;
+                // The next session in the queue may also be admitted
+                self.changed.notify_all();
+                return Ok(ScanPermit(Arc::clone(self), class));
+            }
+
+            if !logged {
+                debug!(
+                    "Queueing {} query on {}, {} sessions are scanning and {} are queued",
+                    class.as_str(),
+                    self.data_source_id,
+                    state.running.values().sum::<usize>(),
+                    state.queue.len()
+                );
+                logged = true;
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                state.queue.remove(&key);
+                self.changed.notify_all();
+                bail!(
+                    "Timed out after {}s waiting to query data source '{}', the maximum number of concurrent scans was reached",
+                    self.timeout.as_secs(),
+                    self.data_source_id
+                );
+            }
+
+            state = self
+                .changed
+                .wait_timeout(state, remaining)
+                .map_err(|_| Error::msg("Failed to lock scan slots"))?
+                .0;
+        }
+    }
+
+    fn release(&self, class: WorkloadClass) {
+        if let Ok(mut state) = self.state.lock() {
+            if let Some(running) = state.running.get_mut(&class) {
+                *running = running.saturating_sub(1);
+            }
+            self.changed.notify_all();
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<SlotState>> {
+        self.state
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock scan slots"))
+    }
+}
+
+impl SlotState {
+    /// Gets the queued session which is admitted next, if a slot is available
+    fn next_admitted(&self, max: Option<usize>) -> Option<(WorkloadClass, u64)> {
+        let running = |class: &WorkloadClass| self.running.get(class).copied().unwrap_or(0);
+
+        if let Some(max) = max {
+            if self.running.values().sum::<usize>() >= max {
+                return None;
+            }
+        }
+
+        self.queue
+            .iter()
+            .find(|((class, _), class_max)| class_max.map_or(true, |m| running(class) < m))
+            .map(|(key, _)| *key)
+    }
+}
+
+impl Drop for ScanPermit {
+    fn drop(&mut self) {
+        self.0.release(self.1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread};
+
+    use ansilo_core::config::{AdmissionConfig, DataSourceConfig, Value, WorkloadConfig};
+
+    use super::*;
+
+    fn mock_node(max_concurrent_scans: Option<u32>, queue_timeout: Option<u64>) -> NodeConfig {
+        let source = DataSourceConfig {
+            id: "mock".into(),
+            name: None,
+            r#type: "test.memory".into(),
+            options: Value::Null,
+            metadata_cache_ttl: None,
+            fetch_size: None,
+            transfer_encoding: Default::default(),
+            transfer_compression: Default::default(),
+            prepared_query_cache: None,
+            statistics: None,
+            admission: Some(AdmissionConfig {
+                max_concurrent_scans,
+                queue_timeout,
+            }),
+        };
+
+        NodeConfig {
+            sources: vec![source],
+            ..Default::default()
+        }
+    }
+
+    fn acquire(slots: &Arc<ScanSlots>) -> Result<ScanPermit> {
+        slots.acquire(WorkloadClass::Interactive, None)
+    }
+
+    fn running(slots: &ScanSlots) -> usize {
+        slots.lock().unwrap().running.values().sum()
+    }
+
+    #[test]
+    fn test_admission_control_unlimited() {
+        let admission = AdmissionControl::new();
+
+        assert!(admission
+            .slots(&mock_node(None, None), "mock")
+            .unwrap()
+            .is_none());
+        assert!(admission
+            .slots(&mock_node(Some(1), None), "unknown")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_admission_control_class_limited() {
+        let admission = AdmissionControl::new();
+        let mut nc = mock_node(None, None);
+        nc.auth.workloads.push(WorkloadConfig {
+            class: WorkloadClass::Batch,
+            max_concurrent_scans: Some(2),
+            ..WorkloadConfig::default()
+        });
+
+        let slots = admission.slots(&nc, "mock").unwrap().unwrap();
+        assert_eq!(slots.max, None);
+    }
+
+    #[test]
+    fn test_admission_control_shares_slots() {
+        let admission = AdmissionControl::new();
+        let nc = mock_node(Some(2), None);
+
+        let a = admission.slots(&nc, "mock").unwrap().unwrap();
+        let b = admission.slots(&nc, "mock").unwrap().unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+
+        // Changing the limits replaces the slots
+        let c = admission
+            .slots(&mock_node(Some(3), None), "mock")
+            .unwrap()
+            .unwrap();
+        assert!(!Arc::ptr_eq(&a, &c));
+        assert_eq!(c.max, Some(3));
+    }
+
+    #[test]
+    fn test_scan_slots_acquire_and_release() {
+        let slots = Arc::new(ScanSlots::new("mock", Some(2), Duration::from_secs(1)));
+
+        let a = acquire(&slots).unwrap();
+        let b = acquire(&slots).unwrap();
+        assert_eq!(running(&slots), 2);
+
+        drop(a);
+        assert_eq!(running(&slots), 1);
+
+        drop(b);
+        assert_eq!(running(&slots), 0);
+    }
+
+    #[test]
+    fn test_scan_slots_timeout() {
+        let slots = Arc::new(ScanSlots::new("mock", Some(1), Duration::from_millis(10)));
+
+        let _permit = acquire(&slots).unwrap();
+
+        assert_eq!(
+            acquire(&slots).err().unwrap().to_string(),
+            "Timed out after 0s waiting to query data source 'mock', the maximum number of concurrent scans was reached"
+        );
+        assert!(slots.lock().unwrap().queue.is_empty());
+    }
+
+    #[test]
+    fn test_scan_slots_class_limit() {
+        let slots = Arc::new(ScanSlots::new("mock", None, Duration::from_millis(10)));
+
+        let _batch = slots.acquire(WorkloadClass::Batch, Some(1)).unwrap();
+        slots.acquire(WorkloadClass::Batch, Some(1)).unwrap_err();
+
+        // Other classes are not affected by the limit
+        let _interactive = acquire(&slots).unwrap();
+        assert_eq!(running(&slots), 2);
+    }
+
+    #[test]
+    fn test_scan_slots_queued_until_released() {
+        let slots = Arc::new(ScanSlots::new("mock", Some(1), Duration::from_secs(5)));
+        let permit = acquire(&slots).unwrap();
+        let (tx, rx) = mpsc::channel();
+
+        let thread = thread::spawn({
+            let slots = Arc::clone(&slots);
+            move || {
+                let _permit = acquire(&slots).unwrap();
+                tx.send(()).unwrap();
+            }
+        });
+
+        rx.recv_timeout(Duration::from_millis(50)).unwrap_err();
+
+        drop(permit);
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        thread.join().unwrap();
+
+        assert_eq!(running(&slots), 0);
+    }
+
+    fn queue_sessions(
+        slots: &Arc<ScanSlots>,
+        classes: Vec<WorkloadClass>,
+    ) -> (Vec<thread::JoinHandle<()>>, mpsc::Receiver<usize>) {
+        let (tx, rx) = mpsc::channel();
+
+        let threads = classes
+            .into_iter()
+            .enumerate()
+            .map(|(i, class)| {
+                let thread = thread::spawn({
+                    let slots = Arc::clone(slots);
+                    let tx = tx.clone();
+                    move || {
+                        let _permit = slots.acquire(class, None).unwrap();
+                        tx.send(i).unwrap();
+                    }
+                });
+
+                // Wait for the session to be queued before starting the next
+                while slots.lock().unwrap().queue.len() < i + 1 {
+                    thread::yield_now();
+                }
+
+                thread
+            })
+            .collect();
+
+        (threads, rx)
+    }
+
+    #[test]
+    fn test_scan_slots_admitted_in_order() {
+        let slots = Arc::new(ScanSlots::new("mock", Some(1), Duration::from_secs(5)));
+        let permit = acquire(&slots).unwrap();
+
+        let (threads, rx) = queue_sessions(&slots, vec![WorkloadClass::Interactive; 3]);
+
+        drop(permit);
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_scan_slots_admits_interactive_before_batch() {
+        let slots = Arc::new(ScanSlots::new("mock", Some(1), Duration::from_secs(5)));
+        let permit = acquire(&slots).unwrap();
+
+        let (threads, rx) = queue_sessions(
+            &slots,
+            vec![
+                WorkloadClass::Batch,
+                WorkloadClass::Interactive,
+                WorkloadClass::Batch,
+                WorkloadClass::Interactive,
+            ],
+        );
+
+        drop(permit);
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![1, 3, 0, 2]);
+    }
+}
@@ -0,0 +1,393 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use ansilo_connectors_base::interface::{EntityDiscoverOptions, OperationCost};
+use ansilo_core::{
+    auth::AuthContext,
+    config::EntityConfig,
+    err::{bail, Result},
+    sqlil::EntityId,
+};
+use ansilo_logging::debug;
+
+use super::stats::EntityStatistics;
+
+/// Caches metadata retrieved from the remote data sources, such as the
+/// discovered entities, their size estimates and collected statistics.
+///
+/// Looking up this metadata typically requires querying the remote system
+/// catalogs which can be slow, so we avoid doing so on every planning cycle.
+/// Entries expire after the TTL configured on each data source and expired
+/// entries are evicted when read or when new entries are cached. Statistics
+/// do not expire and are only replaced by the statistics collector.
+#[derive(Clone, Default)]
+pub struct MetadataCache {
+    /// The cached metadata keyed by the data source id
+    sources: Arc<Mutex<HashMap<String, SourceMetadata>>>,
+}
+
+/// The cached metadata of a single data source
+#[derive(Default)]
+struct SourceMetadata {
+    /// Discovered entities keyed by the discovery options
+    entities: HashMap<String, Cached<Vec<EntityConfig>>>,
+    /// Size estimates keyed by the entity id
+    sizes: HashMap<String, Cached<OperationCost>>,
+    /// Collected statistics keyed by the entity id
+    statistics: HashMap<String, EntityStatistics>,
+}
+
+struct Cached<T> {
+    value: T,
+    expires_at: Instant,
+}
+
+impl<T> Cached<T> {
+    fn new(value: T, ttl: Duration) -> Self {
+        Self {
+            value,
+            expires_at: Instant::now() + ttl,
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        Instant::now() >= self.expires_at
+    }
+}
+
+impl SourceMetadata {
+    /// Removes the expired entries
+    fn evict_expired(&mut self) {
+        self.entities.retain(|_, e| !e.is_expired());
+        self.sizes.retain(|_, c| !c.is_expired());
+    }
+}
+
+/// Gets the value of the entry, evicting it if it has expired
+fn get_cached<T: Clone>(entries: &mut HashMap<String, Cached<T>>, key: &str) -> Option<T> {
+    match entries.get(key) {
+        Some(c) if c.is_expired() => {
+            entries.remove(key);
+            None
+        }
+        Some(c) => Some(c.value.clone()),
+        None => None,
+    }
+}
+
+impl MetadataCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the cached entities discovered using the supplied options
+    pub(crate) fn get_entities(
+        &self,
+        data_source_id: &str,
+        auth: Option<&AuthContext>,
+        opts: &EntityDiscoverOptions,
+    ) -> Result<Option<Vec<EntityConfig>>> {
+        let key = Self::discover_key(auth, opts);
+
+        Ok(self
+            .lock()?
+            .get_mut(data_source_id)
+            .and_then(|s| get_cached(&mut s.entities, &key)))
+    }
+
+    /// Caches the entities discovered using the supplied options
+    pub(crate) fn put_entities(
+        &self,
+        data_source_id: &str,
+        auth: Option<&AuthContext>,
+        opts: &EntityDiscoverOptions,
+        entities: Vec<EntityConfig>,
+        ttl: Duration,
+    ) -> Result<()> {
+        if ttl.is_zero() {
+            return Ok(());
+        }
+
+        let key = Self::discover_key(auth, opts);
+        let mut sources = self.lock()?;
+        let source = sources.entry(data_source_id.into()).or_default();
+
+        source.evict_expired();
+        source.entities.insert(key, Cached::new(entities, ttl));
+
+        Ok(())
+    }
+
+    /// Gets the cached size estimate of the entity
+    pub(crate) fn get_size(
+        &self,
+        data_source_id: &str,
+        entity: &EntityId,
+    ) -> Result<Option<OperationCost>> {
+        Ok(self
+            .lock()?
+            .get_mut(data_source_id)
+            .and_then(|s| get_cached(&mut s.sizes, &entity.entity_id)))
+    }
+
+    /// Caches the size estimate of the entity
+    pub(crate) fn put_size(
+        &self,
+        data_source_id: &str,
+        entity: &EntityId,
+        cost: OperationCost,
+        ttl: Duration,
+    ) -> Result<()> {
+        if ttl.is_zero() {
+            return Ok(());
+        }
+
+        let mut sources = self.lock()?;
+        let source = sources.entry(data_source_id.into()).or_default();
+
+        source.evict_expired();
+        source
+            .sizes
+            .insert(entity.entity_id.clone(), Cached::new(cost, ttl));
+
+        Ok(())
+    }
+
+    /// Gets the collected statistics of the entity
+    pub(crate) fn get_statistics(
+        &self,
+        data_source_id: &str,
+        entity: &EntityId,
+    ) -> Result<Option<EntityStatistics>> {
+        Ok(self
+            .lock()?
+            .get(data_source_id)
+            .and_then(|s| s.statistics.get(&entity.entity_id))
+            .cloned())
+    }
+
+    /// Stores the collected statistics of the entity
+    pub(crate) fn put_statistics(
+        &self,
+        data_source_id: &str,
+        entity: &EntityId,
+        statistics: EntityStatistics,
+    ) -> Result<()> {
+        self.lock()?
+            .entry(data_source_id.into())
+            .or_default()
+            .statistics
+            .insert(entity.entity_id.clone(), statistics);
+
+        Ok(())
+    }
+
+    /// Removes the cached metadata of the supplied data source,
+    /// or of all data sources if none is supplied.
+    ///
+    /// The collected statistics are retained as collecting them is expensive,
+    /// they are replaced when the entities are next sampled.
+    pub fn invalidate(&self, data_source_id: Option<&str>) -> Result<()> {
+        let mut sources = self.lock()?;
+
+        match data_source_id {
+            Some(id) => {
+                debug!("Invalidating metadata cache of data source '{id}'");
+                if let Some(source) = sources.get_mut(id) {
+                    source.entities.clear();
+                    source.sizes.clear();
+                }
+            }
+            None => {
+                debug!("Invalidating metadata cache of all data sources");
+                for source in sources.values_mut() {
+                    source.entities.clear();
+                    source.sizes.clear();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Discovered entities may depend on the permissions of the user
+    /// so they are cached separately for each user
+    fn discover_key(auth: Option<&AuthContext>, opts: &EntityDiscoverOptions) -> String {
+        let mut other = opts.other.iter().collect::<Vec<_>>();
+        other.sort();
+
+        format!(
+            "{:?}:{:?}:{:?}",
+            auth.map(|a| a.username.as_str()),
+            opts.remote_schema,
+            other
+        )
+    }
+
+    fn lock(&self) -> Result<MutexGuard<HashMap<String, SourceMetadata>>> {
+        match self.sources.lock() {
+            Ok(s) => Ok(s),
+            Err(err) => bail!("Failed to lock metadata cache: {:?}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, thread};
+
+    use ansilo_core::config::EntitySourceConfig;
+
+    use super::*;
+
+    fn mock_entities() -> Vec<EntityConfig> {
+        vec![EntityConfig::minimal(
+            "people",
+            vec![],
+            EntitySourceConfig::minimal("memory"),
+        )]
+    }
+
+    #[test]
+    fn test_metadata_cache_entities() {
+        let cache = MetadataCache::new();
+        let opts = EntityDiscoverOptions::new("db.%", HashMap::new());
+
+        assert_eq!(cache.get_entities("src", None, &opts).unwrap(), None);
+
+        cache
+            .put_entities("src", None, &opts, mock_entities(), Duration::from_secs(60))
+            .unwrap();
+
+        assert_eq!(
+            cache.get_entities("src", None, &opts).unwrap(),
+            Some(mock_entities())
+        );
+        assert_eq!(cache.get_entities("other", None, &opts).unwrap(), None);
+        assert_eq!(
+            cache
+                .get_entities(
+                    "src",
+                    None,
+                    &EntityDiscoverOptions::new("other.%", HashMap::new())
+                )
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_metadata_cache_size() {
+        let cache = MetadataCache::new();
+        let entity = EntityId::new("people");
+        let cost = OperationCost::new(Some(3), None, None, None);
+
+        assert_eq!(cache.get_size("src", &entity).unwrap(), None);
+
+        cache
+            .put_size("src", &entity, cost.clone(), Duration::from_secs(60))
+            .unwrap();
+
+        assert_eq!(cache.get_size("src", &entity).unwrap(), Some(cost));
+    }
+
+    #[test]
+    fn test_metadata_cache_expiry() {
+        let cache = MetadataCache::new();
+        let entity = EntityId::new("people");
+        let cost = OperationCost::new(Some(3), None, None, None);
+
+        cache
+            .put_size("src", &entity, cost, Duration::from_millis(10))
+            .unwrap();
+        thread::sleep(Duration::from_millis(20));
+
+        assert_eq!(cache.get_size("src", &entity).unwrap(), None);
+    }
+
+    #[test]
+    fn test_metadata_cache_disabled() {
+        let cache = MetadataCache::new();
+        let entity = EntityId::new("people");
+
+        cache
+            .put_size("src", &entity, OperationCost::default(), Duration::ZERO)
+            .unwrap();
+
+        assert_eq!(cache.get_size("src", &entity).unwrap(), None);
+    }
+
+    #[test]
+    fn test_metadata_cache_invalidate() {
+        let cache = MetadataCache::new();
+        let entity = EntityId::new("people");
+        let ttl = Duration::from_secs(60);
+
+        cache
+            .put_size("a", &entity, OperationCost::default(), ttl)
+            .unwrap();
+        cache
+            .put_size("b", &entity, OperationCost::default(), ttl)
+            .unwrap();
+
+        cache.invalidate(Some("a")).unwrap();
+        assert_eq!(cache.get_size("a", &entity).unwrap(), None);
+        assert!(cache.get_size("b", &entity).unwrap().is_some());
+
+        cache.invalidate(None).unwrap();
+        assert_eq!(cache.get_size("b", &entity).unwrap(), None);
+    }
+
+    #[test]
+    fn test_metadata_cache_statistics() {
+        let cache = MetadataCache::new();
+        let entity = EntityId::new("people");
+        let stats = EntityStatistics::new(100, 20, HashMap::new());
+
+        assert_eq!(cache.get_statistics("src", &entity).unwrap(), None);
+
+        cache.put_statistics("src", &entity, stats.clone()).unwrap();
+
+        assert_eq!(cache.get_statistics("src", &entity).unwrap(), Some(stats));
+
+        cache.invalidate(Some("src")).unwrap();
+        cache.invalidate(None).unwrap();
+        assert_eq!(cache.get_statistics("src", &entity).unwrap(), Some(stats));
+    }
+
+    #[test]
+    fn test_metadata_cache_evicts_expired() {
+        let cache = MetadataCache::new();
+        let ttl = Duration::from_millis(10);
+
+        cache
+            .put_size("src", &EntityId::new("a"), OperationCost::default(), ttl)
+            .unwrap();
+        cache
+            .put_size("src", &EntityId::new("b"), OperationCost::default(), ttl)
+            .unwrap();
+        thread::sleep(Duration::from_millis(20));
+
+        assert_eq!(cache.get_size("src", &EntityId::new("a")).unwrap(), None);
+        assert_eq!(cache.lock().unwrap()["src"].sizes.len(), 1);
+
+        cache
+            .put_size(
+                "src",
+                &EntityId::new("c"),
+                OperationCost::default(),
+                Duration::from_secs(60),
+            )
+            .unwrap();
+        assert_eq!(
+            cache.lock().unwrap()["src"]
+                .sizes
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["c"]
+        );
+    }
+}
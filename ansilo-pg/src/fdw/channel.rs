@@ -180,7 +180,7 @@ mod tests {
     use nix::libc::close;
 
     use crate::fdw::{
-        proto::{AuthDataSource, ClientQueryMessage, ServerQueryMessage},
+        proto::{AuthDataSource, ClientQueryMessage, ServerQueryMessage, SessionOptions},
         test::create_tmp_ipc_channel,
     };
 
@@ -197,7 +197,7 @@ mod tests {
                         req,
                         ClientMessage::AuthDataSource(AuthDataSource::new(None, "DATA_SOURCE"))
                     );
-                    Ok(Some(ServerMessage::AuthAccepted))
+                    Ok(Some(ServerMessage::AuthAccepted(SessionOptions::default())))
                 })
                 .unwrap();
         });
@@ -209,7 +209,7 @@ mod tests {
             )))
             .unwrap();
 
-        assert_eq!(res, ServerMessage::AuthAccepted);
+        assert_eq!(res, ServerMessage::AuthAccepted(SessionOptions::default()));
         server_thread.join().unwrap();
     }
 
@@ -222,7 +222,7 @@ mod tests {
                 server
                     .recv(|req| {
                         assert_eq!(req, ClientMessage::Close);
-                        Ok(Some(ServerMessage::AuthAccepted))
+                        Ok(Some(ServerMessage::AuthAccepted(SessionOptions::default())))
                     })
                     .unwrap();
             }
@@ -230,7 +230,7 @@ mod tests {
 
         for _ in 1..100 {
             let res = client.send(ClientMessage::Close).unwrap();
-            assert_eq!(res, ServerMessage::AuthAccepted);
+            assert_eq!(res, ServerMessage::AuthAccepted(SessionOptions::default()));
         }
 
         server_thread.join().unwrap();
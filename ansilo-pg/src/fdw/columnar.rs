@@ -0,0 +1,394 @@
+use std::{any::Any, io::Cursor, sync::Arc};
+
+use ansilo_connectors_base::{
+    common::data::{DataReader, DataWriter, ResultSetReader},
+    interface::ResultSet,
+};
+use ansilo_core::{
+    data::{DataType, DataValue},
+    err::{bail, Context, Result},
+};
+use arrow::{
+    array::{
+        Array, ArrayRef, BinaryArray, BinaryBuilder, BooleanArray, BooleanBuilder, Float32Array,
+        Float32Builder, Float64Array, Float64Builder, Int16Array, Int16Builder, Int32Array,
+        Int32Builder, Int64Array, Int64Builder, Int8Array, Int8Builder, StringArray, StringBuilder,
+        UInt16Array, UInt16Builder, UInt32Array, UInt32Builder, UInt64Array, UInt64Builder,
+        UInt8Array, UInt8Builder,
+    },
+    datatypes::{DataType as ArrowType, Field, Schema},
+    ipc::{reader::StreamReader, writer::StreamWriter},
+    record_batch::RecordBatch,
+};
+
+/// Encodes up to the supplied number of rows from the result set as an arrow IPC stream.
+///
+/// Each batch is a self-contained stream so it can be decoded independently
+/// of any previous batches. An empty buffer is returned once the result set
+/// is exhausted.
+pub fn encode_batch<T: ResultSet>(
+    reader: &mut ResultSetReader<T>,
+    max_rows: u32,
+) -> Result<Vec<u8>> {
+    let types = reader.get_structure().types();
+    let mut cols = types.iter().map(ColumnBuilder::new).collect::<Vec<_>>();
+    let mut rows = 0;
+
+    while rows < max_rows {
+        let row = match reader.read_row_vec()? {
+            Some(row) => row,
+            None => break,
+        };
+
+        for (col, val) in cols.iter_mut().zip(row.into_iter()) {
+            col.append(val)?;
+        }
+
+        rows += 1;
+    }
+
+    if rows == 0 {
+        return Ok(vec![]);
+    }
+
+    let schema = Arc::new(schema(&types));
+    let batch = RecordBatch::try_new(
+        Arc::clone(&schema),
+        cols.iter_mut().map(|c| c.finish()).collect(),
+    )
+    .context("Failed to create record batch")?;
+
+    let mut writer =
+        StreamWriter::try_new(vec![], &schema).context("Failed to initialise arrow writer")?;
+    writer
+        .write(&batch)
+        .context("Failed to write record batch")?;
+    writer.finish().context("Failed to finish arrow stream")?;
+
+    writer.into_inner().context("Failed to finish arrow stream")
+}
+
+/// A decoded batch of rows which are read out value-by-value in row-major order,
+/// the same order as our row-based encoding
+pub struct ColumnarBatch {
+    /// The types of each column
+    types: Vec<DataType>,
+    /// The arrays of each column
+    cols: Vec<ArrayRef>,
+    /// The number of rows in the batch
+    rows: usize,
+    /// The index of the next value to read
+    idx: usize,
+}
+
+impl ColumnarBatch {
+    /// Decodes the supplied arrow IPC stream
+    pub fn decode(data: Vec<u8>, types: Vec<DataType>) -> Result<Self> {
+        let mut reader = StreamReader::try_new(Cursor::new(data), None)
+            .context("Failed to read arrow stream")?;
+
+        let batch = match reader.next() {
+            Some(batch) => batch.context("Failed to read record batch")?,
+            None => bail!("Unexpected end of arrow stream"),
+        };
+
+        if batch.num_columns() != types.len() {
+            bail!(
+                "Expected {} columns in record batch, found {}",
+                types.len(),
+                batch.num_columns()
+            );
+        }
+
+        Ok(Self {
+            types,
+            rows: batch.num_rows(),
+            cols: batch.columns().to_vec(),
+            idx: 0,
+        })
+    }
+
+    /// Gets the number of rows in the batch
+    pub fn num_rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Reads the next data value from the batch
+    /// Returns Ok(None) if all values have been read
+    pub fn read_data_value(&mut self) -> Result<Option<DataValue>> {
+        if self.types.is_empty() || self.idx >= self.rows * self.types.len() {
+            return Ok(None);
+        }
+
+        let (row, col) = (self.idx / self.types.len(), self.idx % self.types.len());
+        self.idx += 1;
+
+        read_value(&self.cols[col], &self.types[col], row).map(Some)
+    }
+}
+
+/// Maps our data types to arrow types.
+/// Types without a lossless arrow equivalent are stored as binary
+/// using our row-based encoding.
+fn to_arrow_type(r#type: &DataType) -> ArrowType {
+    match r#type {
+        DataType::Boolean => ArrowType::Boolean,
+        DataType::Int8 => ArrowType::Int8,
+        DataType::UInt8 => ArrowType::UInt8,
+        DataType::Int16 => ArrowType::Int16,
+        DataType::UInt16 => ArrowType::UInt16,
+        DataType::Int32 => ArrowType::Int32,
+        DataType::UInt32 => ArrowType::UInt32,
+        DataType::Int64 => ArrowType::Int64,
+        DataType::UInt64 => ArrowType::UInt64,
+        DataType::Float32 => ArrowType::Float32,
+        DataType::Float64 => ArrowType::Float64,
+        DataType::Utf8String(_) | DataType::JSON => ArrowType::Utf8,
+        _ => ArrowType::Binary,
+    }
+}
+
+fn schema(types: &[DataType]) -> Schema {
+    Schema::new(
+        types
+            .iter()
+            .enumerate()
+            .map(|(idx, t)| Field::new(&format!("c{idx}"), to_arrow_type(t), true))
+            .collect(),
+    )
+}
+
+/// Buffers the values of a single column
+enum ColumnBuilder {
+    Boolean(BooleanBuilder),
+    Int8(Int8Builder),
+    UInt8(UInt8Builder),
+    Int16(Int16Builder),
+    UInt16(UInt16Builder),
+    Int32(Int32Builder),
+    UInt32(UInt32Builder),
+    Int64(Int64Builder),
+    UInt64(UInt64Builder),
+    Float32(Float32Builder),
+    Float64(Float64Builder),
+    Utf8(StringBuilder),
+    Binary(BinaryBuilder),
+    /// Values encoded using our row-based encoding
+    Encoded(BinaryBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(r#type: &DataType) -> Self {
+        match r#type {
+            DataType::Boolean => Self::Boolean(BooleanBuilder::new()),
+            DataType::Int8 => Self::Int8(Int8Builder::new()),
+            DataType::UInt8 => Self::UInt8(UInt8Builder::new()),
+            DataType::Int16 => Self::Int16(Int16Builder::new()),
+            DataType::UInt16 => Self::UInt16(UInt16Builder::new()),
+            DataType::Int32 => Self::Int32(Int32Builder::new()),
+            DataType::UInt32 => Self::UInt32(UInt32Builder::new()),
+            DataType::Int64 => Self::Int64(Int64Builder::new()),
+            DataType::UInt64 => Self::UInt64(UInt64Builder::new()),
+            DataType::Float32 => Self::Float32(Float32Builder::new()),
+            DataType::Float64 => Self::Float64(Float64Builder::new()),
+            DataType::Utf8String(_) | DataType::JSON => Self::Utf8(StringBuilder::new()),
+            DataType::Binary => Self::Binary(BinaryBuilder::new()),
+            _ => Self::Encoded(BinaryBuilder::new()),
+        }
+    }
+
+    fn append(&mut self, val: DataValue) -> Result<()> {
+        if val.is_null() {
+            match self {
+                Self::Boolean(b) => b.append_null(),
+                Self::Int8(b) => b.append_null(),
+                Self::UInt8(b) => b.append_null(),
+                Self::Int16(b) => b.append_null(),
+                Self::UInt16(b) => b.append_null(),
+                Self::Int32(b) => b.append_null(),
+                Self::UInt32(b) => b.append_null(),
+                Self::Int64(b) => b.append_null(),
+                Self::UInt64(b) => b.append_null(),
+                Self::Float32(b) => b.append_null(),
+                Self::Float64(b) => b.append_null(),
+                Self::Utf8(b) => b.append_null(),
+                Self::Binary(b) | Self::Encoded(b) => b.append_null(),
+            }
+
+            return Ok(());
+        }
+
+        match (self, val) {
+            (Self::Boolean(b), DataValue::Boolean(v)) => b.append_value(v),
+            (Self::Int8(b), DataValue::Int8(v)) => b.append_value(v),
+            (Self::UInt8(b), DataValue::UInt8(v)) => b.append_value(v),
+            (Self::Int16(b), DataValue::Int16(v)) => b.append_value(v),
+            (Self::UInt16(b), DataValue::UInt16(v)) => b.append_value(v),
+            (Self::Int32(b), DataValue::Int32(v)) => b.append_value(v),
+            (Self::UInt32(b), DataValue::UInt32(v)) => b.append_value(v),
+            (Self::Int64(b), DataValue::Int64(v)) => b.append_value(v),
+            (Self::UInt64(b), DataValue::UInt64(v)) => b.append_value(v),
+            (Self::Float32(b), DataValue::Float32(v)) => b.append_value(v),
+            (Self::Float64(b), DataValue::Float64(v)) => b.append_value(v),
+            (Self::Utf8(b), DataValue::Utf8String(v) | DataValue::JSON(v)) => b.append_value(v),
+            (Self::Binary(b), DataValue::Binary(v)) => b.append_value(v),
+            (Self::Encoded(b), v) => b.append_value(DataWriter::to_vec_one(v)?),
+            (_, v) => bail!("Unexpected value for arrow column: {:?}", v),
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::Boolean(b) => Arc::new(b.finish()),
+            Self::Int8(b) => Arc::new(b.finish()),
+            Self::UInt8(b) => Arc::new(b.finish()),
+            Self::Int16(b) => Arc::new(b.finish()),
+            Self::UInt16(b) => Arc::new(b.finish()),
+            Self::Int32(b) => Arc::new(b.finish()),
+            Self::UInt32(b) => Arc::new(b.finish()),
+            Self::Int64(b) => Arc::new(b.finish()),
+            Self::UInt64(b) => Arc::new(b.finish()),
+            Self::Float32(b) => Arc::new(b.finish()),
+            Self::Float64(b) => Arc::new(b.finish()),
+            Self::Utf8(b) => Arc::new(b.finish()),
+            Self::Binary(b) | Self::Encoded(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+fn read_value(array: &ArrayRef, r#type: &DataType, row: usize) -> Result<DataValue> {
+    if array.is_null(row) {
+        return Ok(DataValue::Null);
+    }
+
+    Ok(match r#type {
+        DataType::Boolean => DataValue::Boolean(downcast::<BooleanArray>(array)?.value(row)),
+        DataType::Int8 => DataValue::Int8(downcast::<Int8Array>(array)?.value(row)),
+        DataType::UInt8 => DataValue::UInt8(downcast::<UInt8Array>(array)?.value(row)),
+        DataType::Int16 => DataValue::Int16(downcast::<Int16Array>(array)?.value(row)),
+        DataType::UInt16 => DataValue::UInt16(downcast::<UInt16Array>(array)?.value(row)),
+        DataType::Int32 => DataValue::Int32(downcast::<Int32Array>(array)?.value(row)),
+        DataType::UInt32 => DataValue::UInt32(downcast::<UInt32Array>(array)?.value(row)),
+        DataType::Int64 => DataValue::Int64(downcast::<Int64Array>(array)?.value(row)),
+        DataType::UInt64 => DataValue::UInt64(downcast::<UInt64Array>(array)?.value(row)),
+        DataType::Float32 => DataValue::Float32(downcast::<Float32Array>(array)?.value(row)),
+        DataType::Float64 => DataValue::Float64(downcast::<Float64Array>(array)?.value(row)),
+        DataType::Utf8String(_) => {
+            DataValue::Utf8String(downcast::<StringArray>(array)?.value(row).to_string())
+        }
+        DataType::JSON => DataValue::JSON(downcast::<StringArray>(array)?.value(row).to_string()),
+        DataType::Binary => DataValue::Binary(downcast::<BinaryArray>(array)?.value(row).to_vec()),
+        _ => DataReader::read_one(downcast::<BinaryArray>(array)?.value(row).to_vec(), r#type)?,
+    })
+}
+
+fn downcast<T: Any>(array: &ArrayRef) -> Result<&T> {
+    array
+        .as_any()
+        .downcast_ref::<T>()
+        .with_context(|| format!("Unexpected arrow array type: {:?}", array.data_type()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use ansilo_connectors_base::interface::RowStructure;
+    use ansilo_core::data::{chrono::NaiveDate, rust_decimal::Decimal};
+
+    use super::*;
+
+    struct MockResultSet(RowStructure, Cursor<Vec<u8>>);
+
+    impl ResultSet for MockResultSet {
+        fn get_structure(&self) -> Result<RowStructure> {
+            Ok(self.0.clone())
+        }
+
+        fn read(&mut self, buff: &mut [u8]) -> Result<usize> {
+            self.1.read(buff).context("failed to read")
+        }
+    }
+
+    fn mock_reader(
+        cols: Vec<DataType>,
+        rows: Vec<Vec<DataValue>>,
+    ) -> ResultSetReader<MockResultSet> {
+        let structure = RowStructure::new(
+            cols.into_iter()
+                .enumerate()
+                .map(|(idx, t)| (format!("col{idx}"), t))
+                .collect(),
+        );
+        let data = DataWriter::to_vec(rows.into_iter().flatten().collect()).unwrap();
+
+        ResultSetReader::new(MockResultSet(structure, Cursor::new(data))).unwrap()
+    }
+
+    fn read_all(batch: &mut ColumnarBatch) -> Vec<DataValue> {
+        let mut values = vec![];
+
+        while let Some(val) = batch.read_data_value().unwrap() {
+            values.push(val);
+        }
+
+        values
+    }
+
+    #[test]
+    fn test_columnar_encode_decode() {
+        let types = vec![
+            DataType::Int32,
+            DataType::rust_string(),
+            DataType::Decimal(Default::default()),
+            DataType::Date,
+        ];
+        let rows = vec![
+            vec![
+                DataValue::Int32(1),
+                DataValue::Utf8String("abc".into()),
+                DataValue::Decimal(Decimal::new(12345, 2)),
+                DataValue::Date(NaiveDate::from_ymd_opt(2020, 1, 2).unwrap()),
+            ],
+            vec![
+                DataValue::Int32(2),
+                DataValue::Null,
+                DataValue::Null,
+                DataValue::Date(NaiveDate::from_ymd_opt(2021, 3, 4).unwrap()),
+            ],
+        ];
+        let mut reader = mock_reader(types.clone(), rows.clone());
+
+        let data = encode_batch(&mut reader, 10).unwrap();
+        let mut batch = ColumnarBatch::decode(data, types).unwrap();
+
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(
+            read_all(&mut batch),
+            rows.into_iter().flatten().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_columnar_encode_max_rows() {
+        let types = vec![DataType::Int64];
+        let rows = (0..5)
+            .map(|i| vec![DataValue::Int64(i)])
+            .collect::<Vec<_>>();
+        let mut reader = mock_reader(types.clone(), rows);
+
+        let first = ColumnarBatch::decode(encode_batch(&mut reader, 3).unwrap(), types.clone());
+        assert_eq!(first.unwrap().num_rows(), 3);
+
+        let mut second =
+            ColumnarBatch::decode(encode_batch(&mut reader, 3).unwrap(), types).unwrap();
+        assert_eq!(
+            read_all(&mut second),
+            vec![DataValue::Int64(3), DataValue::Int64(4)]
+        );
+
+        assert_eq!(encode_batch(&mut reader, 3).unwrap(), Vec::<u8>::new());
+    }
+}
@@ -0,0 +1,88 @@
+use ansilo_core::{
+    config::TransferCompression,
+    err::{bail, Context, Result},
+};
+
+/// Result batches smaller than this are sent uncompressed as
+/// the overhead of compressing them outweighs the savings
+pub const MIN_COMPRESSED_BYTES: usize = 16 * 1024;
+
+/// The zstd compression level, we favour speed over ratio
+/// as the data is compressed on every transfer
+const ZSTD_LEVEL: i32 = 1;
+
+/// Compresses the supplied result data
+pub fn compress(compression: TransferCompression, data: &[u8]) -> Result<Vec<u8>> {
+    Ok(match compression {
+        TransferCompression::None => data.to_vec(),
+        TransferCompression::Lz4 => lz4_flex::compress_prepend_size(data),
+        TransferCompression::Zstd => {
+            zstd::encode_all(data, ZSTD_LEVEL).context("Failed to compress result data")?
+        }
+    })
+}
+
+/// Decompresses result data compressed using [`compress`]
+pub fn decompress(compression: TransferCompression, data: Vec<u8>) -> Result<Vec<u8>> {
+    Ok(match compression {
+        TransferCompression::None => data,
+        TransferCompression::Lz4 => match lz4_flex::decompress_size_prepended(&data) {
+            Ok(data) => data,
+            Err(err) => bail!("Failed to decompress result data: {}", err),
+        },
+        TransferCompression::Zstd => {
+            zstd::decode_all(data.as_slice()).context("Failed to decompress result data")?
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_data() -> Vec<u8> {
+        (0..MIN_COMPRESSED_BYTES).map(|i| (i % 16) as u8).collect()
+    }
+
+    #[test]
+    fn test_compression_none() {
+        let data = mock_data();
+
+        let compressed = compress(TransferCompression::None, &data).unwrap();
+        assert_eq!(compressed, data);
+        assert_eq!(
+            decompress(TransferCompression::None, compressed).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn test_compression_lz4() {
+        let data = mock_data();
+
+        let compressed = compress(TransferCompression::Lz4, &data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(
+            decompress(TransferCompression::Lz4, compressed).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn test_compression_zstd() {
+        let data = mock_data();
+
+        let compressed = compress(TransferCompression::Zstd, &data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(
+            decompress(TransferCompression::Zstd, compressed).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn test_compression_invalid_data() {
+        decompress(TransferCompression::Lz4, vec![1, 2, 3]).unwrap_err();
+        decompress(TransferCompression::Zstd, vec![1, 2, 3]).unwrap_err();
+    }
+}
@@ -1,23 +1,24 @@
 use std::{
     any::TypeId,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Display,
     io::{Read, Write},
     mem,
-    sync::{RwLock, RwLockReadGuard},
+    sync::{Arc, RwLock, RwLockReadGuard},
+    time::{Duration, Instant},
 };
 
-use ansilo_connectors_all::PeerConnector;
+use ansilo_connectors_all::{PeerConnector, PostgresConnector};
 use ansilo_connectors_base::{
     common::{
-        data::{QueryHandleWrite, ResultSetRead},
+        data::{QueryHandleWrite, ResultSetRead, ResultSetReader},
         entity::{ConnectorEntityConfig, EntitySource, UnknownEntityError},
     },
     interface::*,
 };
 use ansilo_core::{
-    auth::AuthContext,
-    config::{EntityConfig, NodeConfig},
+    auth::{AuthContext, RowFilter},
+    config::{AttributeMaskType, EntityConfig, NodeConfig, TransferCompression, UserLimits},
     data::DataType,
     err::{bail, Context, Result},
     sqlil::{self, EntityId},
@@ -25,9 +26,22 @@ use ansilo_core::{
 use ansilo_logging::{debug, warn};
 
 use super::{
+    admission::{AdmissionControl, ScanPermit},
+    cache::MetadataCache,
     channel::IpcServerChannel,
-    log::RemoteQueryLog,
-    proto::{ClientMessage, ClientQueryMessage, QueryId, ServerMessage, ServerQueryMessage},
+    columnar,
+    compression::{self, MIN_COMPRESSED_BYTES},
+    limit::LimitedResultSet,
+    log::{RemoteQueryExecution, RemoteQueryLog},
+    mask::MaskedResultSet,
+    prepared::PreparedQueryCache,
+    proto::{
+        ClientMessage, ClientQueryMessage, QueryId, QueryRuleCheck, ServerMessage,
+        ServerQueryMessage,
+    },
+    scan_cache::{CachedResultSet, ScanCache, ScanRecording},
+    snapshot::Snapshots,
+    stats::QueryEstimate,
 };
 
 /// A single connection from the FDW
@@ -36,8 +50,10 @@ pub(crate) struct FdwConnection<'a, TConnector: Connector> {
     data_source_id: String,
     /// Authentication context of the client
     auth: Option<AuthContext>,
+    /// The pid of the postgres backend of the current session
+    session_id: Option<u32>,
     /// Global config
-    nc: &'static NodeConfig,
+    nc: Arc<NodeConfig>,
     /// The unix socket the server listens on
     chan: Option<IpcServerChannel>,
     /// Entity config
@@ -48,10 +64,58 @@ pub(crate) struct FdwConnection<'a, TConnector: Connector> {
     connection: FdwConnectionState<TConnector>,
     /// Current query states
     queries: HashMap<QueryId, FdwQueryState<TConnector>>,
+    /// The compression requested for the result data of each query
+    compression: HashMap<QueryId, TransferCompression>,
     /// Current query id counter
     query_id: QueryId,
     /// Remote query log
     log: RemoteQueryLog,
+    /// Cache of remote metadata shared across connections
+    cache: MetadataCache,
+    /// Cache of prepared queries which outlives the session
+    prepared: PreparedQueryCache<TConnector::TQueryHandle>,
+    /// The cache keys and input structure of the current prepared queries
+    prepared_keys: HashMap<QueryId, (String, QueryInputStructure)>,
+    /// Row estimates of the select queries based on the collected statistics
+    estimates: HashMap<QueryId, QueryEstimate>,
+    /// The masks of the output columns of the select queries
+    masks: HashMap<QueryId, Vec<Option<AttributeMaskType>>>,
+    /// The resource limits of the authenticated user
+    limits: UserLimits,
+    /// Limits on the sessions concurrently scanning each data source
+    admission: AdmissionControl,
+    /// The slot held while the session has executed scans against the data source
+    permit: Option<ScanPermit>,
+    /// The select queries which have been executed and not yet discarded
+    scans: HashSet<QueryId>,
+    /// Cache of the results of scans of the cached entities
+    scan_cache: ScanCache,
+    /// The select queries of which the results can be cached
+    cacheable: HashMap<QueryId, CacheableScan>,
+    /// The cached entities written by each modify query
+    writes: HashMap<QueryId, Vec<String>>,
+    /// The cached entities written within the current transaction
+    written: HashSet<String>,
+    /// Whether a transaction has been started on the connection
+    in_transaction: bool,
+    /// The connection to the local snapshots which serves the scans of materialized entities
+    snapshot: Option<Box<FdwConnection<'a, PostgresConnector>>>,
+    /// The ids of the queries served from the snapshots mapped to their ids on the snapshot connection
+    snapshot_queries: HashMap<QueryId, QueryId>,
+}
+
+/// A select query of which the results can be cached
+struct CacheableScan {
+    /// The query, used as the cache key along with the parameters
+    query: String,
+    /// The parameters written to the query
+    params: Vec<u8>,
+    /// Whether the parameters are replaced by the next write, after the query is restarted
+    reset: bool,
+    /// The ids of the entities read by the query
+    entities: Vec<String>,
+    /// The shortest cache ttl of the entities
+    ttl: Duration,
 }
 
 enum FdwConnectionState<TConnector: Connector> {
@@ -59,6 +123,10 @@ enum FdwConnectionState<TConnector: Connector> {
     Connected(TConnector::TConnection),
 }
 
+/// The result set of a query with the access rules and limits of the user applied
+type FdwResultSet<TConnector> =
+    LimitedResultSet<CachedResultSet<MaskedResultSet<<TConnector as Connector>::TResultSet>>>;
+
 enum FdwQueryState<TConnector: Connector> {
     New,
     Planning(sqlil::Query),
@@ -66,7 +134,12 @@ enum FdwQueryState<TConnector: Connector> {
     Prepared(QueryHandleWrite<TConnector::TQueryHandle>),
     ExecutedQuery(
         QueryHandleWrite<TConnector::TQueryHandle>,
-        ResultSetRead<TConnector::TResultSet>,
+        ResultSetRead<FdwResultSet<TConnector>>,
+        LoggedQuery,
+    ),
+    ExecutedColumnar(
+        QueryHandleWrite<TConnector::TQueryHandle>,
+        ResultSetReader<FdwResultSet<TConnector>>,
         LoggedQuery,
     ),
     ExecutedModify(QueryHandleWrite<TConnector::TQueryHandle>, LoggedQuery),
@@ -76,23 +149,158 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
     pub(crate) fn new(
         data_source_id: String,
         auth: Option<AuthContext>,
-        nc: &'static NodeConfig,
+        nc: Arc<NodeConfig>,
         chan: IpcServerChannel,
         entities: &'a RwLock<ConnectorEntityConfig<TConnector::TEntitySourceConfig>>,
         pool: TConnector::TConnectionPool,
         log: RemoteQueryLog,
+        cache: MetadataCache,
+        admission: AdmissionControl,
+        scan_cache: ScanCache,
+    ) -> Self {
+        Self {
+            chan: Some(chan),
+            ..Self::detached(
+                data_source_id,
+                auth,
+                nc,
+                entities,
+                pool,
+                log,
+                cache,
+                admission,
+                scan_cache,
+            )
+        }
+    }
+
+    /// Creates a connection which is not yet bound to a session
+    fn detached(
+        data_source_id: String,
+        auth: Option<AuthContext>,
+        nc: Arc<NodeConfig>,
+        entities: &'a RwLock<ConnectorEntityConfig<TConnector::TEntitySourceConfig>>,
+        pool: TConnector::TConnectionPool,
+        log: RemoteQueryLog,
+        cache: MetadataCache,
+        admission: AdmissionControl,
+        scan_cache: ScanCache,
     ) -> Self {
+        // Prepared queries are only cached for configured data sources
+        let max_prepared_queries = nc
+            .sources
+            .iter()
+            .find(|i| i.id == data_source_id)
+            .map(|i| i.prepared_query_cache_size())
+            .unwrap_or(0);
+        let limits = auth
+            .as_ref()
+            .map(|auth| nc.auth.limits(&auth.username))
+            .unwrap_or_default();
+
         Self {
             data_source_id,
             auth,
+            session_id: None,
             nc,
-            chan: Some(chan),
+            chan: None,
             entities,
             pool,
             connection: FdwConnectionState::New,
             queries: HashMap::new(),
+            compression: HashMap::new(),
             query_id: 0,
             log,
+            cache,
+            prepared: PreparedQueryCache::new(max_prepared_queries),
+            prepared_keys: HashMap::new(),
+            estimates: HashMap::new(),
+            masks: HashMap::new(),
+            limits,
+            admission,
+            permit: None,
+            scans: HashSet::new(),
+            scan_cache,
+            cacheable: HashMap::new(),
+            writes: HashMap::new(),
+            written: HashSet::new(),
+            in_transaction: false,
+            snapshot: None,
+            snapshot_queries: HashMap::new(),
+        }
+    }
+
+    /// Attributes the remote queries to the supplied session
+    pub(crate) fn with_session(mut self, session_id: Option<u32>) -> Self {
+        self.session_id = session_id;
+        self
+    }
+
+    /// Serves the scans of the materialized entities from their local snapshots.
+    ///
+    /// Sessions without an authentication context, such as the jobs which
+    /// refresh the snapshots, continue to read from the data source.
+    pub(crate) fn with_snapshots(mut self, snapshots: Option<&'a Snapshots>) -> Self {
+        let snapshots = match (snapshots, self.auth.as_ref()) {
+            (Some(snapshots), Some(_)) => snapshots,
+            _ => return self,
+        };
+
+        // The snapshot connection acts on behalf of the data source so the same access
+        // rules apply, though its scans are not admitted against the data source's limits
+        let snapshot = FdwConnection::<PostgresConnector>::detached(
+            self.data_source_id.clone(),
+            self.auth.clone(),
+            Arc::clone(&self.nc),
+            &snapshots.entities,
+            snapshots.pool.clone(),
+            self.log.clone(),
+            self.cache.clone(),
+            AdmissionControl::new(),
+            self.scan_cache.clone(),
+        )
+        .with_session(self.session_id);
+
+        self.snapshot = Some(Box::new(snapshot));
+        self
+    }
+
+    /// Resumes processing messages from a new session, reusing
+    /// the existing connection and its cached prepared queries
+    pub(crate) fn resume(&mut self, session_id: Option<u32>, chan: IpcServerChannel) {
+        self.session_id = session_id;
+        self.chan = Some(chan);
+
+        if let Some(snapshot) = self.snapshot.as_mut() {
+            snapshot.session_id = session_id;
+        }
+    }
+
+    /// Releases the queries of the closed session, returning their prepared queries
+    /// to the cache. Returns whether the connection can be reused by another session,
+    /// which requires it to have cached prepared queries and no active transaction.
+    pub(crate) fn release(&mut self) -> bool {
+        for query_id in self.queries.keys().cloned().collect::<Vec<_>>() {
+            let _ = self.discard_query(query_id);
+        }
+
+        if let Some(snapshot) = self.snapshot.as_mut() {
+            snapshot.release();
+        }
+        self.snapshot_queries.clear();
+
+        if self.prepared.len() == 0 {
+            return false;
+        }
+
+        let connection = match &mut self.connection {
+            FdwConnectionState::Connected(c) => c,
+            FdwConnectionState::New => return false,
+        };
+
+        match connection.transaction_manager() {
+            Some(tm) => matches!(tm.is_in_transaction(), Ok(false)),
+            None => true,
         }
     }
 
@@ -126,19 +334,36 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
                 ServerMessage::RegisteredEntity
             }
             ClientMessage::EstimateSize(entity) => {
-                ServerMessage::EstimatedSizeResult(self.estimate_size(&entity)?)
+                ServerMessage::EstimatedSizeResult(match self.snapshot_connection(&entity) {
+                    Some(snapshot) => snapshot.estimate_size(&entity)?,
+                    None => self.estimate_size(&entity)?,
+                })
             }
             ClientMessage::GetRowIds(entity) => {
                 ServerMessage::RowIds(self.get_row_id_exprs(&entity)?)
             }
             ClientMessage::CreateQuery(entity, query_type) => {
-                let (query_id, cost) = self.create_query(&entity, query_type)?;
+                let (query_id, cost) = match query_type {
+                    sqlil::QueryType::Select if self.is_snapshot(&entity.entity) => {
+                        self.create_snapshot_query(&entity)?
+                    }
+                    _ => self.create_query(&entity, query_type)?,
+                };
                 ServerMessage::QueryCreated(query_id, cost)
             }
-            ClientMessage::CreateStringQuery(query, params) => {
-                let (query_id, cost) = self.create_string_query(query, params)?;
+            ClientMessage::CreateStringQuery(query, params, write) => {
+                let (query_id, cost) = self.create_string_query(query, params, write)?;
                 ServerMessage::QueryCreated(query_id, cost)
             }
+            ClientMessage::CheckQueryRules(check) => {
+                self.check_query_rules(&check)?;
+                ServerMessage::QueryRulesPassed
+            }
+            ClientMessage::Query(query_id, message)
+                if self.snapshot_queries.contains_key(&query_id) =>
+            {
+                ServerMessage::Query(self.handle_snapshot_query_message(query_id, message)?)
+            }
             ClientMessage::Query(query_id, message) => {
                 ServerMessage::Query(self.handle_query_message(query_id, message)?)
             }
@@ -195,7 +420,25 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
                 // TODO[low]: remove copy
                 let mut buff = vec![0u8; len as usize];
                 let read = self.read(query_id, &mut buff[..])?;
-                ServerQueryMessage::ReadData(buff[..read].to_vec())
+                buff.truncate(read);
+
+                match self.compress(query_id, &buff)? {
+                    Some((c, data)) => ServerQueryMessage::CompressedReadData(c, data),
+                    None => ServerQueryMessage::ReadData(buff),
+                }
+            }
+            ClientQueryMessage::ReadColumnar(rows) => {
+                let data = self.read_columnar(query_id, rows)?;
+
+                match self.compress(query_id, &data)? {
+                    Some((c, data)) => ServerQueryMessage::CompressedColumnarData(c, data),
+                    None => ServerQueryMessage::ColumnarData(data),
+                }
+            }
+            ClientQueryMessage::SetCompression(compression) => {
+                Self::query(&mut self.queries, query_id)?;
+                self.compression.insert(query_id, compression);
+                ServerQueryMessage::CompressionSet
             }
             ClientQueryMessage::Restart => {
                 self.restart_query(query_id)?;
@@ -206,11 +449,89 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
                 ServerQueryMessage::Duplicated(new_id)
             }
             ClientQueryMessage::Discard => {
-                self.queries
-                    .remove(&query_id)
-                    .context("Invalid query id while discarding")?;
+                self.discard_query(query_id)?;
+                ServerQueryMessage::Discarded
+            }
+        })
+    }
+
+    /// Whether the scans of the entity are served from its local snapshot
+    fn is_snapshot(&self, entity: &EntityId) -> bool {
+        self.snapshot.is_some()
+            && Self::configured_entity(&self.nc, &self.data_source_id, entity)
+                .map_or(false, |e| e.materialize.is_some())
+    }
+
+    /// Gets the snapshot connection if the scans of the entity are served from its snapshot
+    fn snapshot_connection(
+        &mut self,
+        entity: &EntityId,
+    ) -> Option<&mut FdwConnection<'a, PostgresConnector>> {
+        if !self.is_snapshot(entity) {
+            return None;
+        }
+
+        self.snapshot.as_deref_mut()
+    }
+
+    /// Creates a select query against the local snapshot of the entity
+    fn create_snapshot_query(
+        &mut self,
+        source: &sqlil::EntitySource,
+    ) -> Result<(QueryId, OperationCost)> {
+        let snapshot = self
+            .snapshot
+            .as_mut()
+            .context("Snapshots are not available")?;
+        let (snapshot_id, cost) = snapshot.create_query(source, sqlil::QueryType::Select)?;
+
+        let query_id = self.query_id;
+        self.snapshot_queries.insert(query_id, snapshot_id);
+        self.query_id += 1;
+
+        Ok((query_id, cost))
+    }
+
+    /// Forwards the message to the query on the snapshot connection.
+    /// Joins are only pushed down to the snapshots if the joined entity is also materialized.
+    fn handle_snapshot_query_message(
+        &mut self,
+        query_id: QueryId,
+        message: ClientQueryMessage,
+    ) -> Result<ServerQueryMessage> {
+        if let ClientQueryMessage::Apply(QueryOperation::Select(SelectQueryOperation::AddJoin(
+            join,
+        ))) = &message
+        {
+            if !self.is_snapshot(&join.target.entity) {
+                return Ok(ServerQueryMessage::OperationResult(
+                    QueryOperationResult::Unsupported,
+                ));
+            }
+        }
+
+        let snapshot_id = *self
+            .snapshot_queries
+            .get(&query_id)
+            .context("Invalid query id")?;
+        let snapshot = self
+            .snapshot
+            .as_mut()
+            .context("Snapshots are not available")?;
+
+        Ok(match snapshot.handle_query_message(snapshot_id, message)? {
+            ServerQueryMessage::Duplicated(duplicate_id) => {
+                let new_id = self.query_id;
+                self.snapshot_queries.insert(new_id, duplicate_id);
+                self.query_id += 1;
+
+                ServerQueryMessage::Duplicated(new_id)
+            }
+            ServerQueryMessage::Discarded => {
+                self.snapshot_queries.remove(&query_id);
                 ServerQueryMessage::Discarded
             }
+            res => res,
         })
     }
 
@@ -243,7 +564,46 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
         queries.get_mut(&query_id).context("Invalid query id")
     }
 
+    /// The time for which metadata of this data source is cached.
+    /// Caching is disabled for sources which are not configured, eg the internal catalog.
+    fn metadata_cache_ttl(&self) -> Duration {
+        self.nc
+            .sources
+            .iter()
+            .find(|i| i.id == self.data_source_id)
+            .map(|i| i.metadata_cache_ttl())
+            .unwrap_or(Duration::ZERO)
+    }
+
     fn discover_entities(&mut self, opts: EntityDiscoverOptions) -> Result<Vec<EntityConfig>> {
+        if let Some(entities) =
+            self.cache
+                .get_entities(&self.data_source_id, self.auth.as_ref(), &opts)?
+        {
+            debug!(
+                "Using cached entities of data source '{}'",
+                self.data_source_id
+            );
+            return Ok(entities);
+        }
+
+        let entities = self.discover_remote_entities(opts.clone())?;
+
+        self.cache.put_entities(
+            &self.data_source_id,
+            self.auth.as_ref(),
+            &opts,
+            entities.clone(),
+            self.metadata_cache_ttl(),
+        )?;
+
+        Ok(entities)
+    }
+
+    fn discover_remote_entities(
+        &mut self,
+        opts: EntityDiscoverOptions,
+    ) -> Result<Vec<EntityConfig>> {
         // Check if we are trying to discover from a peer node
         // If so we special case and discover entities using the public endpoint.
         let mut entities = if TypeId::of::<TConnector::TEntitySearcher>()
@@ -260,7 +620,7 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
         } else {
             // For regular cases we find entities using the connection to the data source
             self.connect()?;
-            TConnector::TEntitySearcher::discover(self.connection.get()?, self.nc, opts)?
+            TConnector::TEntitySearcher::discover(self.connection.get()?, &self.nc, opts)?
         };
 
         // Avoid having the connectors having to supply the data source
@@ -276,7 +636,7 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
         self.connect()?;
 
         let entity =
-            TConnector::TEntityValidator::validate(self.connection.get()?, &config, self.nc)
+            TConnector::TEntityValidator::validate(self.connection.get()?, &config, &self.nc)
                 .context("Failed to validate entity config")?;
 
         // We only lock in write-mode when we know we will succeed.
@@ -293,12 +653,33 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
     }
 
     fn estimate_size(&mut self, entity: &EntityId) -> Result<OperationCost> {
-        self.connect()?;
-        let entities = Self::entities(self.entities)?;
-        Ok(TConnector::TQueryPlanner::estimate_size(
-            self.connection.get()?,
-            Self::get_entity_config(&*entities, entity)?,
-        )?)
+        let mut cost = match self.cache.get_size(&self.data_source_id, entity)? {
+            Some(cost) => cost,
+            None => {
+                self.connect()?;
+                let entities = Self::entities(self.entities)?;
+                let cost = TConnector::TQueryPlanner::estimate_size(
+                    self.connection.get()?,
+                    Self::get_entity_config(&*entities, entity)?,
+                )?;
+
+                self.cache.put_size(
+                    &self.data_source_id,
+                    entity,
+                    cost.clone(),
+                    self.metadata_cache_ttl(),
+                )?;
+
+                cost
+            }
+        };
+
+        // Fill in what the connector could not estimate from the collected statistics
+        if let Some(statistics) = self.cache.get_statistics(&self.data_source_id, entity)? {
+            cost.default_to(&statistics.cost());
+        }
+
+        Ok(cost)
     }
 
     fn get_row_id_exprs(
@@ -322,21 +703,73 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
         source: &sqlil::EntitySource,
         r#type: sqlil::QueryType,
     ) -> Result<(QueryId, OperationCost)> {
+        let is_select = matches!(r#type, sqlil::QueryType::Select);
+        if !is_select {
+            self.check_write_rules()?;
+        }
+
+        let filters = self.row_filters(source)?;
         self.connect()?;
         let entities = Self::entities(self.entities)?;
-        let (cost, query) = TConnector::TQueryPlanner::create_base_query(
-            self.connection.get()?,
+        let connection = self.connection.get()?;
+        let (mut cost, mut query) = TConnector::TQueryPlanner::create_base_query(
+            connection,
             &*entities,
             Self::get_entity_config(&*entities, &source.entity)?,
             source,
             r#type,
         )?;
 
+        // We have no way to filter the rows locally so if the data source
+        // cannot apply the row filters we deny the query
+        for filter in filters {
+            let res = match &mut query {
+                sqlil::Query::Select(select) => TConnector::TQueryPlanner::apply_select_operation(
+                    connection,
+                    &*entities,
+                    select,
+                    SelectQueryOperation::AddWhere(filter),
+                )?,
+                sqlil::Query::Update(update) => TConnector::TQueryPlanner::apply_update_operation(
+                    connection,
+                    &*entities,
+                    update,
+                    UpdateQueryOperation::AddWhere(filter),
+                )?,
+                sqlil::Query::Delete(delete) => TConnector::TQueryPlanner::apply_delete_operation(
+                    connection,
+                    &*entities,
+                    delete,
+                    DeleteQueryOperation::AddWhere(filter),
+                )?,
+                // Row filters do not restrict the rows which can be inserted
+                sqlil::Query::Insert(_) | sqlil::Query::BulkInsert(_) => break,
+            };
+
+            if let QueryOperationResult::Unsupported = res {
+                bail!(
+                    "Row filters on entity '{}' are not supported by the data source",
+                    source.entity.entity_id
+                );
+            }
+        }
+
         let query_id = self.query_id;
         self.queries
             .insert(query_id, FdwQueryState::Planning(query));
         self.query_id += 1;
 
+        if is_select {
+            if let Some(statistics) = self
+                .cache
+                .get_statistics(&self.data_source_id, &source.entity)?
+            {
+                cost.default_to(&statistics.cost());
+                self.estimates
+                    .insert(query_id, QueryEstimate::new(&source.alias, statistics));
+            }
+        }
+
         Ok((query_id, cost))
     }
 
@@ -344,7 +777,48 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
         &mut self,
         query: String,
         params: Vec<sqlil::Parameter>,
+        write: bool,
     ) -> Result<(QueryId, OperationCost)> {
+        // Raw queries would bypass the row filters and masking
+        if let Some(entity) = self.restricted_entities().next() {
+            bail!(
+                "Queries on data source '{}' are restricted by the access rules on entity '{}'",
+                self.data_source_id,
+                entity.id
+            );
+        }
+
+        if let Some(auth) = self.auth.as_ref() {
+            if !self
+                .nc
+                .auth
+                .can_remote_query(&auth.username, &self.data_source_id, write)
+            {
+                bail!(
+                    "User '{}' is not granted access to execute {} queries on data source '{}'",
+                    auth.username,
+                    if write { "raw write" } else { "raw" },
+                    self.data_source_id
+                );
+            }
+        }
+
+        // Raw queries cannot be checked against the query rules
+        if let Some(auth) = self.auth.as_ref() {
+            if self
+                .nc
+                .auth
+                .rules_for(&auth.username)
+                .any(|r| r.deny_writes || !r.require_where.is_empty())
+            {
+                bail!(
+                    "User '{}' is not permitted to execute raw queries on data source '{}' by the query rules",
+                    auth.username,
+                    self.data_source_id
+                );
+            }
+        }
+
         self.connect()?;
         let query =
             TConnector::TQueryCompiler::query_from_string(self.connection.get()?, query, params)?;
@@ -382,18 +856,140 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
             .context("Current query is not SELECT")?;
         let entities = Self::entities(self.entities)?;
 
-        // Ensure joined entities are present in config
+        // Materialized entities are read from their snapshots so cannot be joined remotely
         if let SelectQueryOperation::AddJoin(join) = &op {
-            Self::get_entity_config(&*entities, &join.target.entity)?;
+            if self.snapshot.is_some()
+                && Self::configured_entity(&self.nc, &self.data_source_id, &join.target.entity)
+                    .map_or(false, |e| e.materialize.is_some())
+            {
+                return Ok(QueryOperationResult::Unsupported);
+            }
+        }
+
+        // Conditions, orderings and groupings on masked attributes are evaluated locally
+        // by postgres on the masked values, otherwise the unmasked values could be
+        // inferred from the results of the query
+        let (exprs, join_target) = match &op {
+            SelectQueryOperation::AddWhere(expr) => (vec![expr], None),
+            SelectQueryOperation::AddGroupBy(expr) => (vec![expr], None),
+            SelectQueryOperation::AddOrderBy(ordering) => (vec![&ordering.expr], None),
+            SelectQueryOperation::AddJoin(join) => {
+                (join.conds.iter().collect(), Some(&join.target))
+            }
+            _ => (vec![], None),
+        };
+        for expr in exprs {
+            let mut attrs = vec![];
+            expr.walk(&mut |e| {
+                if let sqlil::Expr::Attribute(attr) = e {
+                    attrs.push(attr.clone());
+                }
+            });
+
+            for attr in attrs {
+                let entity = match join_target {
+                    Some(target) if target.alias == attr.entity_alias => &target.entity,
+                    _ => select.get_entity(&attr.entity_alias)?,
+                };
+
+                if Self::attribute_mask(
+                    &self.nc,
+                    &self.data_source_id,
+                    self.auth.as_ref(),
+                    entity,
+                    &attr.attribute_id,
+                )
+                .is_some()
+                {
+                    return Ok(QueryOperationResult::Unsupported);
+                }
+            }
         }
 
-        let res = TConnector::TQueryPlanner::apply_select_operation(
+        // Ensure joined entities are present in config
+        let mut op = op;
+        let join_statistics = if let SelectQueryOperation::AddJoin(join) = &mut op {
+            Self::get_entity_config(&*entities, &join.target.entity)?;
+
+            // The row filters of the joined entity are added to the join conditions.
+            // This is not equivalent for right or full joins in which case
+            // we fall back to performing the join locally.
+            let filters = Self::bind_row_filters(
+                &self.nc,
+                &self.data_source_id,
+                self.auth.as_ref(),
+                &join.target,
+            )?;
+            if !filters.is_empty() {
+                if !(join.r#type.is_inner() || join.r#type.is_left()) {
+                    return Ok(QueryOperationResult::Unsupported);
+                }
+
+                join.conds.extend(filters);
+            }
+
+            self.cache
+                .get_statistics(&self.data_source_id, &join.target.entity)?
+        } else {
+            None
+        };
+
+        // Masked attributes can only be selected directly so their values can be masked
+        // in the result set, other expressions are evaluated locally by postgres
+        let column_mask = match &op {
+            SelectQueryOperation::AddColumn((_, expr)) => {
+                let mut attrs = vec![];
+                expr.walk(&mut |e| {
+                    if let sqlil::Expr::Attribute(attr) = e {
+                        attrs.push(attr.clone());
+                    }
+                });
+
+                let mut masks = vec![];
+                for attr in attrs {
+                    if let Some(mask) = Self::attribute_mask(
+                        &self.nc,
+                        &self.data_source_id,
+                        self.auth.as_ref(),
+                        select.get_entity(&attr.entity_alias)?,
+                        &attr.attribute_id,
+                    ) {
+                        masks.push(mask.clone());
+                    }
+                }
+
+                match (expr, masks.pop()) {
+                    (_, None) => Some(None),
+                    (sqlil::Expr::Attribute(_), Some(mask)) => Some(Some(mask)),
+                    _ => return Ok(QueryOperationResult::Unsupported),
+                }
+            }
+            _ => None,
+        };
+
+        let mut res = TConnector::TQueryPlanner::apply_select_operation(
             self.connection.get()?,
             &*entities,
             select,
-            op,
+            op.clone(),
         )?;
 
+        // If the connector cannot estimate the rows we do so from the collected statistics
+        if let QueryOperationResult::Ok(cost) = &mut res {
+            if let Some(estimate) = self.estimates.get_mut(&query_id) {
+                match estimate.apply(&op, join_statistics) {
+                    Some(rows) => cost.rows = cost.rows.or(Some(rows)),
+                    None => {
+                        self.estimates.remove(&query_id);
+                    }
+                }
+            }
+        }
+
+        if let (QueryOperationResult::Ok(_), Some(mask)) = (&res, column_mask) {
+            self.masks.entry(query_id).or_default().push(mask);
+        }
+
         Ok(res)
     }
 
@@ -478,19 +1074,21 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
     }
 
     fn prepare(&mut self, query_id: QueryId) -> Result<QueryInputStructure> {
-        let connection = self.connection.get()?;
-
         let state = mem::replace(
             Self::query(&mut self.queries, query_id)?,
             FdwQueryState::New,
         );
 
         let query = match state {
-            FdwQueryState::Planning(query) => TConnector::TQueryCompiler::compile_query(
-                connection,
-                &*Self::entities(self.entities)?,
-                query.clone(),
-            )?,
+            FdwQueryState::Planning(query) => {
+                self.track_cached_entities(query_id, &query);
+
+                TConnector::TQueryCompiler::compile_query(
+                    self.connection.get()?,
+                    &*Self::entities(self.entities)?,
+                    query.clone(),
+                )?
+            }
             FdwQueryState::Compiled(query) => query,
             _ => bail!(
                 "Expected query to be in planning or compiled state but currest state is '{}'",
@@ -498,9 +1096,27 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
             ),
         };
 
-        let handle = connection.prepare(query)?;
+        // Reuse a previously prepared query if possible
+        let key = TConnector::TQueryCompiler::query_key(&query).filter(|_| self.prepared.enabled());
+        let cached = key.as_ref().and_then(|key| self.prepared.take(key));
+
+        let (handle, structure) = match cached {
+            Some(cached) => {
+                debug!("Reusing prepared query on {}", self.data_source_id);
+                cached
+            }
+            None => {
+                let handle = self.connection.get()?.prepare(query)?;
+                let structure = handle.get_structure()?;
+                (handle, structure)
+            }
+        };
+
+        if let Some(key) = key {
+            self.prepared_keys
+                .insert(query_id, (key, structure.clone()));
+        }
 
-        let structure = handle.get_structure()?;
         *Self::query(&mut self.queries, query_id)? =
             FdwQueryState::Prepared(QueryHandleWrite(handle));
 
@@ -508,6 +1124,15 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
     }
 
     fn write_params(&mut self, query_id: QueryId, data: Vec<u8>) -> Result<()> {
+        if let Some(scan) = self.cacheable.get_mut(&query_id) {
+            if scan.reset {
+                scan.params.clear();
+                scan.reset = false;
+            }
+
+            scan.params.extend_from_slice(&data);
+        }
+
         let handle = Self::query(&mut self.queries, query_id)?.query_handle()?;
 
         handle
@@ -518,15 +1143,47 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
     }
 
     fn execute_query(&mut self, query_id: QueryId) -> Result<RowStructure> {
+        let masks = self.masks.get(&query_id).cloned().unwrap_or_default();
+        let recording = self.scan_recording(query_id, &masks);
+
+        // Repeat scans are served from the cache without querying the data source
+        if let Some(recording) = recording.as_ref() {
+            if let Some((structure, data)) =
+                self.scan_cache.get(&self.data_source_id, &recording.key)?
+            {
+                debug!("Serving cached scan on {}", self.data_source_id);
+                let handle = self.get_prepared_query(query_id)?;
+                let query = handle.0.logged()?;
+                let result_set =
+                    LimitedResultSet::new(CachedResultSet::cached(structure, data), &self.limits)?;
+                let row_structure = result_set.get_structure()?;
+
+                *Self::query(&mut self.queries, query_id)? =
+                    FdwQueryState::ExecutedQuery(handle, ResultSetRead(result_set), query);
+
+                return Ok(row_structure);
+            }
+        }
+
+        self.admit_scan(query_id)?;
         let mut handle = self.get_prepared_query(query_id)?;
 
         debug!("Executing query on {}", self.data_source_id);
-        let result_set = handle.0.execute_query()?;
+        let started = Instant::now();
+        let result_set = LimitedResultSet::new(
+            CachedResultSet::remote(
+                MaskedResultSet::new(handle.0.execute_query()?, masks)?,
+                recording,
+            ),
+            &self.limits,
+        )?;
+        let duration = started.elapsed();
         let row_structure = result_set.get_structure()?;
+        self.invalidate_cached_scans(query_id)?;
 
         debug!("Logging query on {}", self.data_source_id);
         let query = handle.0.logged()?;
-        self.log.record(&self.data_source_id, query.clone())?;
+        self.log_query(query.clone(), Some(duration))?;
 
         *Self::query(&mut self.queries, query_id)? =
             FdwQueryState::ExecutedQuery(handle, ResultSetRead(result_set), query);
@@ -534,18 +1191,147 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
         Ok(row_structure)
     }
 
+    /// Waits for the session to be admitted to scan the data source.
+    /// The slot is held until all of the executed scans of the session have been discarded.
+    fn admit_scan(&mut self, query_id: QueryId) -> Result<()> {
+        if self.permit.is_none() {
+            if let Some(slots) = self.admission.slots(&self.nc, &self.data_source_id)? {
+                let workload = self
+                    .auth
+                    .as_ref()
+                    .and_then(|auth| self.nc.auth.workload(&auth.username));
+                let class = workload.map(|w| w.class).unwrap_or_default();
+                let class_max = workload.and_then(|w| w.max_concurrent_scans());
+
+                self.permit = Some(slots.acquire(class, class_max)?);
+            }
+        }
+
+        self.scans.insert(query_id);
+        Ok(())
+    }
+
+    /// Records the cached entities read or written by the query.
+    /// The results of select queries are only cached if every entity they read is cached.
+    fn track_cached_entities(&mut self, query_id: QueryId, query: &sqlil::Query) {
+        let configs = query
+            .get_entity_sources()
+            .map(|s| Self::configured_entity(&self.nc, &self.data_source_id, &s.entity))
+            .collect::<Vec<_>>();
+        let entities = configs
+            .iter()
+            .flatten()
+            .filter(|e| e.cache.is_some())
+            .map(|e| e.id.clone())
+            .collect::<Vec<_>>();
+
+        if entities.is_empty() {
+            return;
+        }
+
+        if !matches!(query, sqlil::Query::Select(_)) {
+            self.writes.insert(query_id, entities);
+            return;
+        }
+
+        let ttl = configs
+            .iter()
+            .map(|e| e.and_then(|e| e.cache.as_ref()).map(|c| c.ttl()))
+            .collect::<Option<Vec<_>>>()
+            .and_then(|ttls| ttls.into_iter().min());
+
+        if let Some(ttl) = ttl {
+            self.cacheable.insert(
+                query_id,
+                CacheableScan {
+                    query: format!("{:?}", query),
+                    params: vec![],
+                    reset: false,
+                    entities,
+                    ttl,
+                },
+            );
+        }
+    }
+
+    /// Gets the scan to be recorded in the cache, if the results of the query can be cached.
+    ///
+    /// The results are cached per query, parameters and attribute masks, as the
+    /// row filters of the user are included in the query.
+    fn scan_recording(
+        &self,
+        query_id: QueryId,
+        masks: &[Option<AttributeMaskType>],
+    ) -> Option<ScanRecording> {
+        let scan = self.cacheable.get(&query_id)?;
+
+        // Scans within a transaction which has written to the entities may see
+        // uncommitted changes so they are always read from the data source
+        if scan.entities.iter().any(|e| self.written.contains(e)) {
+            return None;
+        }
+
+        // The rows returned may depend on the user, such as through row level
+        // security on the data source, so the cached scans are per user
+        let username = self.auth.as_ref().map(|a| a.username.as_str());
+
+        Some(ScanRecording {
+            cache: self.scan_cache.clone(),
+            data_source_id: self.data_source_id.clone(),
+            key: format!(
+                "{:?}\n{}\n{:?}\n{:?}",
+                username, scan.query, scan.params, masks
+            ),
+            entities: scan.entities.clone(),
+            ttl: scan.ttl,
+        })
+    }
+
+    /// Invalidates the cached scans of the entities written by the query
+    fn invalidate_cached_scans(&mut self, query_id: QueryId) -> Result<()> {
+        let entities = match self.writes.get(&query_id) {
+            Some(entities) => entities,
+            None => return Ok(()),
+        };
+
+        for entity in entities.iter() {
+            self.scan_cache.invalidate(&self.data_source_id, entity)?;
+
+            if self.in_transaction {
+                self.written.insert(entity.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Invalidates the cached scans of the entities written within the completed transaction,
+    /// as other sessions may have cached the rows before the changes were committed
+    fn complete_transaction(&mut self) -> Result<()> {
+        self.in_transaction = false;
+
+        for entity in mem::take(&mut self.written).iter() {
+            self.scan_cache.invalidate(&self.data_source_id, entity)?;
+        }
+
+        Ok(())
+    }
+
     fn execute_modify(&mut self, query_id: QueryId) -> Result<Option<u64>> {
         let mut handle = self.get_prepared_query(query_id)?;
 
         debug!("Executing query on {}", self.data_source_id);
+        let started = Instant::now();
         let affected_rows = handle.0.execute_modify()?;
+        let duration = started.elapsed();
+        self.invalidate_cached_scans(query_id)?;
 
         debug!("Logging query on {}", self.data_source_id);
         let mut query = handle.0.logged()?;
         query
             .other_mut()
             .insert("affected".into(), format!("{:?}", affected_rows));
-        self.log.record(&self.data_source_id, query.clone())?;
+        self.log_query(query.clone(), Some(duration))?;
 
         *Self::query(&mut self.queries, query_id)? = FdwQueryState::ExecutedModify(handle, query);
 
@@ -597,21 +1383,83 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
     fn read(&mut self, query_id: QueryId, buff: &mut [u8]) -> Result<usize> {
         let result_set = Self::query(&mut self.queries, query_id)?.result_set()?;
 
-        let read = result_set
-            .read(buff)
-            .context("Failed to read from result set")?;
+        // Fill the requested batch so the client receives the number of rows
+        // it asked for in a single round-trip, unless the result set is exhausted
+        let mut read = 0;
+        while read < buff.len() {
+            let len = result_set
+                .read(&mut buff[read..])
+                .context("Failed to read from result set")?;
+
+            if len == 0 {
+                break;
+            }
+
+            read += len;
+        }
 
         Ok(read)
     }
 
+    fn read_columnar(&mut self, query_id: QueryId, max_rows: u32) -> Result<Vec<u8>> {
+        let state = Self::query(&mut self.queries, query_id)?;
+
+        // On the first columnar read we start decoding the result set
+        // so the rows can be encoded into arrow batches
+        if let FdwQueryState::ExecutedQuery(_, _, _) = state {
+            *state = match mem::replace(state, FdwQueryState::New) {
+                FdwQueryState::ExecutedQuery(handle, ResultSetRead(result_set), query) => {
+                    FdwQueryState::ExecutedColumnar(
+                        handle,
+                        ResultSetReader::new(result_set)?,
+                        query,
+                    )
+                }
+                _ => unreachable!(),
+            };
+        }
+
+        match state {
+            FdwQueryState::ExecutedColumnar(_, reader, _) => {
+                columnar::encode_batch(reader, max_rows)
+            }
+            _ => bail!("Expecting query state to be 'executed' found {}", state),
+        }
+    }
+
+    /// Compresses the result data if requested for the query and
+    /// the data is large enough to benefit from compression
+    fn compress(
+        &self,
+        query_id: QueryId,
+        data: &[u8],
+    ) -> Result<Option<(TransferCompression, Vec<u8>)>> {
+        let algorithm = match self.compression.get(&query_id) {
+            Some(TransferCompression::None) | None => return Ok(None),
+            Some(c) => *c,
+        };
+
+        if data.len() < MIN_COMPRESSED_BYTES {
+            return Ok(None);
+        }
+
+        Ok(Some((algorithm, compression::compress(algorithm, data)?)))
+    }
+
     fn restart_query(&mut self, query_id: QueryId) -> Result<()> {
         let query = mem::replace(
             Self::query(&mut self.queries, query_id)?,
             FdwQueryState::New,
         );
 
+        // The parameters are replaced if they are written again before the next execution
+        if let Some(scan) = self.cacheable.get_mut(&query_id) {
+            scan.reset = true;
+        }
+
         *Self::query(&mut self.queries, query_id)? = match query {
             FdwQueryState::ExecutedQuery(mut handle, _, _)
+            | FdwQueryState::ExecutedColumnar(mut handle, _, _)
             | FdwQueryState::ExecutedModify(mut handle, _) => {
                 handle.0.restart()?;
                 FdwQueryState::Prepared(handle)
@@ -625,17 +1473,63 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
         Ok(())
     }
 
-    fn explain_query(&mut self, query_id: QueryId, verbose: bool) -> Result<String> {
-        let val = match Self::query(&mut self.queries, query_id)? {
-            // if planning phase, explain the query using the connector
-            FdwQueryState::Planning(q) => TConnector::TQueryPlanner::explain_query(
+    fn discard_query(&mut self, query_id: QueryId) -> Result<()> {
+        let state = self
+            .queries
+            .remove(&query_id)
+            .context("Invalid query id while discarding")?;
+        self.compression.remove(&query_id);
+        self.estimates.remove(&query_id);
+        self.masks.remove(&query_id);
+        self.cacheable.remove(&query_id);
+        self.writes.remove(&query_id);
+
+        // Release the slot once the session has no remaining scans
+        if self.scans.remove(&query_id) && self.scans.is_empty() {
+            self.permit = None;
+        }
+
+        // Return the prepared query to the cache so it can be reused
+        if let Some((key, structure)) = self.prepared_keys.remove(&query_id) {
+            // Any open result set is closed before the query is restarted
+            let mut handle = match state {
+                FdwQueryState::Prepared(handle) => handle.0,
+                FdwQueryState::ExecutedQuery(handle, result_set, _) => {
+                    mem::drop(result_set);
+                    handle.0
+                }
+                FdwQueryState::ExecutedColumnar(handle, reader, _) => {
+                    mem::drop(reader);
+                    handle.0
+                }
+                FdwQueryState::ExecutedModify(handle, _) => handle.0,
+                _ => return Ok(()),
+            };
+
+            if let Err(err) = handle.restart() {
+                warn!("Failed to restart query, not caching: {:?}", err);
+                return Ok(());
+            }
+
+            self.prepared.put(key, handle, structure);
+        }
+
+        Ok(())
+    }
+
+    fn explain_query(&mut self, query_id: QueryId, verbose: bool) -> Result<String> {
+        let val = match Self::query(&mut self.queries, query_id)? {
+            // if planning phase, explain the query using the connector
+            FdwQueryState::Planning(q) => TConnector::TQueryPlanner::explain_query(
                 self.connection.get()?,
                 &*Self::entities(self.entities)?,
                 q,
                 verbose,
             )?,
             // if the query has executed, use the logged query
-            FdwQueryState::ExecutedQuery(_, _, q) | FdwQueryState::ExecutedModify(_, q) => {
+            FdwQueryState::ExecutedQuery(_, _, q)
+            | FdwQueryState::ExecutedColumnar(_, _, q)
+            | FdwQueryState::ExecutedModify(_, q) => {
                 if verbose {
                     serde_json::to_value(q).context("Failed to convert LoggedQuery to JSON")?
                 } else {
@@ -674,11 +1568,19 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
             _ => bail!("Duplicating query is only valid for new or planning states"),
         };
 
-        let query_id = self.query_id;
-        self.queries.insert(query_id, cloned);
+        let new_id = self.query_id;
+        self.queries.insert(new_id, cloned);
         self.query_id += 1;
 
-        Ok(query_id)
+        if let Some(estimate) = self.estimates.get(&query_id).cloned() {
+            self.estimates.insert(new_id, estimate);
+        }
+
+        if let Some(masks) = self.masks.get(&query_id).cloned() {
+            self.masks.insert(new_id, masks);
+        }
+
+        Ok(new_id)
     }
 
     fn with_transaction_manager(
@@ -700,8 +1602,11 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
             Ok(ServerMessage::TransactionBegun)
         })?;
 
-        self.log
-            .record(&self.data_source_id, LoggedQuery::new_query("BEGIN"))?;
+        if matches!(res, ServerMessage::TransactionBegun) {
+            self.in_transaction = true;
+        }
+
+        self.log_query(LoggedQuery::new_query("BEGIN"), None)?;
 
         Ok(res)
     }
@@ -711,9 +1616,9 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
             tm.rollback_transaction()?;
             Ok(ServerMessage::TransactionRolledBack)
         })?;
+        self.complete_transaction()?;
 
-        self.log
-            .record(&self.data_source_id, LoggedQuery::new_query("ROLLBACK"))?;
+        self.log_query(LoggedQuery::new_query("ROLLBACK"), None)?;
 
         Ok(res)
     }
@@ -723,13 +1628,26 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
             tm.commit_transaction()?;
             Ok(ServerMessage::TransactionCommitted)
         })?;
+        self.complete_transaction()?;
 
-        self.log
-            .record(&self.data_source_id, LoggedQuery::new_query("COMMIT"))?;
+        self.log_query(LoggedQuery::new_query("COMMIT"), None)?;
 
         Ok(res)
     }
 
+    /// Records the query in the remote query log, attributed to the current session
+    fn log_query(&self, query: LoggedQuery, duration: Option<Duration>) -> Result<()> {
+        self.log.record_execution(
+            &self.data_source_id,
+            query,
+            RemoteQueryExecution {
+                session_id: self.session_id,
+                username: self.auth.as_ref().map(|a| a.username.clone()),
+                duration,
+            },
+        )
+    }
+
     fn execute_batch(&mut self, reqs: Vec<ClientMessage>) -> Result<ServerMessage> {
         let mut results = Vec::with_capacity(reqs.len());
 
@@ -759,6 +1677,162 @@ impl<'a, TConnector: Connector> FdwConnection<'a, TConnector> {
         }
     }
 
+    /// Checks the query being planned against the query rules of the authenticated user
+    fn check_query_rules(&self, check: &QueryRuleCheck) -> Result<()> {
+        let auth = match self.auth.as_ref() {
+            Some(auth) => auth,
+            None => return Ok(()),
+        };
+
+        for rule in self.nc.auth.rules_for(&auth.username) {
+            if rule.deny_cross_source_joins
+                && check.data_sources.iter().any(|i| *i != self.data_source_id)
+            {
+                bail!(
+                    "User '{}' is not permitted to query across data sources by the query rules, the query references data sources '{}'",
+                    auth.username,
+                    check.data_sources.join("', '")
+                );
+            }
+
+            if !check.has_conditions && rule.require_where.contains(&check.entity.entity_id) {
+                bail!(
+                    "User '{}' is required to query entity '{}' with a WHERE clause by the query rules",
+                    auth.username,
+                    check.entity.entity_id
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fails if the authenticated user is denied from modifying data
+    fn check_write_rules(&self) -> Result<()> {
+        if let Some(auth) = self.auth.as_ref() {
+            if self
+                .nc
+                .auth
+                .rules_for(&auth.username)
+                .any(|r| r.deny_writes)
+            {
+                bail!(
+                    "User '{}' is not permitted to modify data on data source '{}' by the query rules",
+                    auth.username,
+                    self.data_source_id
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gets the row filters of the entity bound to the authenticated user
+    fn row_filters(&self, source: &sqlil::EntitySource) -> Result<Vec<sqlil::Expr>> {
+        Self::bind_row_filters(&self.nc, &self.data_source_id, self.auth.as_ref(), source)
+    }
+
+    fn bind_row_filters(
+        nc: &NodeConfig,
+        data_source_id: &str,
+        auth: Option<&AuthContext>,
+        source: &sqlil::EntitySource,
+    ) -> Result<Vec<sqlil::Expr>> {
+        let entity = match Self::configured_entity(nc, data_source_id, &source.entity) {
+            Some(entity) => entity,
+            None => return Ok(vec![]),
+        };
+
+        entity
+            .row_filters
+            .iter()
+            .map(|filter| {
+                let filter = RowFilter::parse(filter)?;
+                let attr = entity
+                    .attributes
+                    .iter()
+                    .find(|a| a.id == filter.attribute)
+                    .with_context(|| {
+                        format!("Unknown attribute '{}' in row filter", filter.attribute)
+                    })?;
+
+                filter.to_expr(&source.alias, &attr.r#type, auth)
+            })
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Failed to apply row filters on entity '{}'", entity.id))
+    }
+
+    /// Gets the mask applied to the attribute for the authenticated user, if any
+    fn attribute_mask<'b>(
+        nc: &'b NodeConfig,
+        data_source_id: &str,
+        auth: Option<&AuthContext>,
+        entity: &EntityId,
+        attribute_id: &str,
+    ) -> Option<&'b AttributeMaskType> {
+        let entity = Self::configured_entity(nc, data_source_id, entity)?;
+        let attribute = entity.attributes.iter().find(|a| a.id == attribute_id)?;
+
+        // The mask configured on the attribute takes precedence over
+        // any masks targeting its data classifications
+        let mask = attribute.mask.as_ref().or_else(|| {
+            nc.auth.classification_mask(
+                attribute
+                    .classifications
+                    .iter()
+                    .chain(entity.classifications.iter()),
+            )
+        })?;
+
+        let unmasked = match auth {
+            Some(auth) => mask
+                .unmasked_for
+                .matches(&auth.username, nc.auth.roles(&auth.username)),
+            None => false,
+        };
+
+        if unmasked {
+            None
+        } else {
+            Some(&mask.r#type)
+        }
+    }
+
+    /// Gets the entities of the data source which declare row filters
+    /// or attributes which are masked for the authenticated user
+    fn restricted_entities(&self) -> impl Iterator<Item = &EntityConfig> {
+        self.nc
+            .entities
+            .iter()
+            .filter(move |e| e.source.data_source == self.data_source_id)
+            .filter(move |e| {
+                !e.row_filters.is_empty()
+                    || e.attributes.iter().any(|a| {
+                        Self::attribute_mask(
+                            &self.nc,
+                            &self.data_source_id,
+                            self.auth.as_ref(),
+                            &EntityId::new(&e.id),
+                            &a.id,
+                        )
+                        .is_some()
+                    })
+            })
+    }
+
+    /// Gets the entity from the node config.
+    /// Access rules are only read from the node config, not from
+    /// the entities registered by the client.
+    fn configured_entity<'b>(
+        nc: &'b NodeConfig,
+        data_source_id: &str,
+        entity: &EntityId,
+    ) -> Option<&'b EntityConfig> {
+        nc.entities
+            .iter()
+            .find(|e| e.id == entity.entity_id && e.source.data_source == data_source_id)
+    }
+
     fn get_entity_config<'b, 'c>(
         entities: &'b ConnectorEntityConfig<TConnector::TEntitySourceConfig>,
         entity: &'c EntityId,
@@ -784,7 +1858,7 @@ impl<TConnector: Connector> FdwQueryState<TConnector> {
         })
     }
 
-    fn result_set(&mut self) -> Result<&mut ResultSetRead<TConnector::TResultSet>> {
+    fn result_set(&mut self) -> Result<&mut ResultSetRead<FdwResultSet<TConnector>>> {
         Ok(match self {
             FdwQueryState::ExecutedQuery(_, result_set, _) => result_set,
             _ => bail!("Expecting query state to be 'executed' found {}", self),
@@ -800,6 +1874,7 @@ impl<TConnector: Connector> Display for FdwQueryState<TConnector> {
             FdwQueryState::Compiled(_) => "compiled",
             FdwQueryState::Prepared(_) => "prepared",
             FdwQueryState::ExecutedQuery(_, _, _) => "executed-query",
+            FdwQueryState::ExecutedColumnar(_, _, _) => "executed-columnar",
             FdwQueryState::ExecutedModify(_, _) => "executed-modify",
         })
     }
@@ -818,6 +1893,7 @@ impl<TConnector: Connector> FdwConnectionState<TConnector> {
 mod tests {
     use std::{
         io,
+        sync::Arc,
         thread::{self, JoinHandle},
     };
 
@@ -827,14 +1903,23 @@ mod tests {
         MemoryDatabaseConf,
     };
     use ansilo_core::{
-        config::{EntityAttributeConfig, EntityConfig, EntitySourceConfig, NodeConfig},
+        auth::{PasswordAuthContext, ProviderAuthContext},
+        config::{
+            AttributeMaskConfig, AuthConfig, ClassificationMaskConfig, EntityAttributeConfig,
+            EntityCacheConfig, EntityConfig, EntitySourceConfig, GrantConfig, NodeConfig,
+            PasswordUserConfig, QueryRuleConfig, UserConfig, UserMatchConfig, UserTypeOptions,
+        },
         data::{DataType, DataValue},
     };
     use lazy_static::lazy_static;
     use pretty_assertions::assert_eq;
 
     use crate::fdw::{
-        channel::IpcClientChannel, proto::AuthDataSource, test::create_tmp_ipc_channel,
+        channel::IpcClientChannel,
+        columnar::ColumnarBatch,
+        proto::AuthDataSource,
+        stats::{ColumnStatistics, EntityStatistics},
+        test::create_tmp_ipc_channel,
     };
 
     use super::*;
@@ -883,6 +1968,7 @@ mod tests {
         name: &'static str,
         db_conf: MemoryDatabaseConf,
         log: RemoteQueryLog,
+        cache: MetadataCache,
     ) -> (
         JoinHandle<Result<FdwConnection<MemoryConnector>>>,
         IpcClientChannel,
@@ -898,11 +1984,84 @@ mod tests {
             let mut fdw = FdwConnection::<MemoryConnector>::new(
                 "memory".into(),
                 None,
-                &NODE_CONFIG,
+                Arc::new(NODE_CONFIG.clone()),
                 server_chan,
                 entities,
                 pool,
                 log,
+                cache,
+                AdmissionControl::new(),
+                ScanCache::new(),
+            );
+
+            fdw.process()?;
+
+            Ok(fdw)
+        });
+
+        (thread, client_chan)
+    }
+
+    fn create_mock_connection_with_cached_entity(
+        name: &'static str,
+    ) -> (
+        JoinHandle<Result<FdwConnection<MemoryConnector>>>,
+        IpcClientChannel,
+        Arc<MemoryDatabase>,
+    ) {
+        let (entities, pool) = create_memory_connection_pool(MemoryDatabaseConf::default());
+        let data = pool.conf();
+        let (thread, client_chan) = create_mock_connection_with_cached_entity_opts(
+            name,
+            None,
+            entities,
+            pool,
+            ScanCache::new(),
+        );
+
+        (thread, client_chan, data)
+    }
+
+    fn create_mock_connection_with_cached_entity_opts(
+        name: &'static str,
+        auth: Option<AuthContext>,
+        entities: ConnectorEntityConfig<MemoryConnectorEntitySourceConfig>,
+        pool: MemoryConnectionPool,
+        scan_cache: ScanCache,
+    ) -> (
+        JoinHandle<Result<FdwConnection<MemoryConnector>>>,
+        IpcClientChannel,
+    ) {
+        let mut entity = EntityConfig::minimal(
+            "people",
+            vec![
+                EntityAttributeConfig::minimal("first_name", DataType::rust_string()),
+                EntityAttributeConfig::minimal("last_name", DataType::rust_string()),
+            ],
+            EntitySourceConfig::minimal("memory"),
+        );
+        entity.cache = Some(EntityCacheConfig { ttl: 60 });
+        let nc = Arc::new(NodeConfig {
+            entities: vec![entity],
+            ..NodeConfig::default()
+        });
+
+        let (client_chan, server_chan) = create_tmp_ipc_channel(name);
+
+        let thread = thread::spawn(move || {
+            let entities = Box::leak(Box::new(RwLock::new(entities)));
+
+            let mut fdw = FdwConnection::<MemoryConnector>::new(
+                "memory".into(),
+                auth,
+                nc,
+                server_chan,
+                entities,
+                pool,
+                RemoteQueryLog::new(),
+                MetadataCache::new(),
+                AdmissionControl::new(),
+                scan_cache,
             );
 
             fdw.process()?;
@@ -919,7 +2078,65 @@ mod tests {
         JoinHandle<Result<FdwConnection<MemoryConnector>>>,
         IpcClientChannel,
     ) {
-        create_mock_connection_opts(name, MemoryDatabaseConf::default(), RemoteQueryLog::new())
+        create_mock_connection_opts(
+            name,
+            MemoryDatabaseConf::default(),
+            RemoteQueryLog::new(),
+            MetadataCache::new(),
+        )
+    }
+
+    fn create_mock_connection_with_auth(
+        name: &'static str,
+        entity: EntityConfig,
+        username: &str,
+    ) -> (JoinHandle<Result<()>>, IpcClientChannel) {
+        create_mock_connection_with_config(
+            name,
+            NodeConfig {
+                entities: vec![entity],
+                ..NodeConfig::default()
+            },
+            username,
+        )
+    }
+
+    fn create_mock_connection_with_config(
+        name: &'static str,
+        nc: NodeConfig,
+        username: &str,
+    ) -> (JoinHandle<Result<()>>, IpcClientChannel) {
+        let nc = Arc::new(nc);
+        let auth = AuthContext::new(
+            username,
+            "password",
+            None,
+            ProviderAuthContext::Password(PasswordAuthContext::default()),
+        );
+
+        let (entities, pool) = create_memory_connection_pool(MemoryDatabaseConf::default());
+        let (client_chan, server_chan) = create_tmp_ipc_channel(name);
+
+        let thread = thread::spawn(move || {
+            let entities = Box::leak(Box::new(RwLock::new(entities)));
+
+            let mut fdw = FdwConnection::<MemoryConnector>::new(
+                "memory".into(),
+                Some(auth),
+                nc,
+                server_chan,
+                entities,
+                pool,
+                RemoteQueryLog::new(),
+                MetadataCache::new(),
+                AdmissionControl::new(),
+                ScanCache::new(),
+            );
+
+            fdw.process()
+        });
+
+        (thread, client_chan)
     }
 
     #[test]
@@ -939,6 +2156,77 @@ mod tests {
         thread.join().unwrap().unwrap();
     }
 
+    #[test]
+    fn test_fdw_connection_statistics_estimates() {
+        let cache = MetadataCache::new();
+        cache
+            .put_statistics(
+                "memory",
+                &EntityId::new("people"),
+                EntityStatistics::new(
+                    1000,
+                    12,
+                    [("first_name".to_string(), ColumnStatistics::new(100, 0.0))]
+                        .into_iter()
+                        .collect(),
+                ),
+            )
+            .unwrap();
+
+        let (thread, mut client) = create_mock_connection_opts(
+            "connection_statistics_estimates",
+            MemoryDatabaseConf::default(),
+            RemoteQueryLog::new(),
+            cache,
+        );
+
+        // The connector estimate takes precedence
+        let res = client
+            .send(ClientMessage::EstimateSize(sqlil::entity("people")))
+            .unwrap();
+
+        assert_eq!(
+            res,
+            ServerMessage::EstimatedSizeResult(OperationCost::new(Some(3), Some(12), None, None))
+        );
+
+        let res = client
+            .send(ClientMessage::CreateQuery(
+                sqlil::source("people", "people"),
+                sqlil::QueryType::Select,
+            ))
+            .unwrap();
+
+        assert_eq!(
+            res,
+            ServerMessage::QueryCreated(0, OperationCost::new(Some(1000), Some(12), None, None))
+        );
+
+        let res = client
+            .send(ClientMessage::Query(
+                0,
+                ClientQueryMessage::Apply(
+                    SelectQueryOperation::AddWhere(sqlil::Expr::BinaryOp(sqlil::BinaryOp::new(
+                        sqlil::Expr::attr("people", "first_name"),
+                        sqlil::BinaryOpType::Equal,
+                        sqlil::Expr::constant(DataValue::from("Mary")),
+                    )))
+                    .into(),
+                ),
+            ))
+            .unwrap();
+
+        assert_eq!(
+            res,
+            ServerMessage::Query(ServerQueryMessage::OperationResult(
+                QueryOperationResult::Ok(OperationCost::new(Some(10), None, None, None))
+            ))
+        );
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+    }
+
     #[test]
     fn test_fdw_connection_discover_entities() {
         let (thread, mut client) = create_mock_connection("connection_discover_entities");
@@ -979,81 +2267,786 @@ mod tests {
 
         assert_eq!(res, ServerMessage::RegisteredEntity);
 
-        client.close().unwrap();
-        thread.join().unwrap().unwrap();
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_fdw_connection_estimate_size_unknown_entity() {
+        let (thread, mut client) =
+            create_mock_connection("connection_estimate_size_unknown_entity");
+
+        let res = client
+            .send(ClientMessage::EstimateSize(sqlil::entity("unknown")))
+            .unwrap();
+
+        assert_eq!(res, ServerMessage::UnknownEntity(EntityId::new("unknown")));
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_fdw_connection_select_join_unknown_entity() {
+        let (thread, mut client) = create_mock_connection("connection_select_join_unknown_entity");
+
+        let res = client
+            .send(ClientMessage::CreateQuery(
+                sqlil::source("people", "people"),
+                sqlil::QueryType::Select,
+            ))
+            .unwrap();
+
+        assert_eq!(
+            res,
+            ServerMessage::QueryCreated(0, OperationCost::default())
+        );
+
+        let res = client
+            .send(ClientMessage::Query(
+                0,
+                ClientQueryMessage::Apply(
+                    SelectQueryOperation::AddJoin(sqlil::Join::new(
+                        sqlil::JoinType::Inner,
+                        sqlil::source("joined_entity", "a"),
+                        vec![],
+                    ))
+                    .into(),
+                ),
+            ))
+            .unwrap();
+
+        assert_eq!(
+            res,
+            ServerMessage::UnknownEntity(EntityId::new("joined_entity"))
+        );
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_fdw_connection_select() {
+        let (thread, mut client) = create_mock_connection("connection_select");
+
+        let res = client
+            .send(ClientMessage::CreateQuery(
+                sqlil::source("people", "people"),
+                sqlil::QueryType::Select,
+            ))
+            .unwrap();
+
+        assert_eq!(
+            res,
+            ServerMessage::QueryCreated(0, OperationCost::default())
+        );
+
+        let res = client
+            .send(ClientMessage::Query(
+                0,
+                ClientQueryMessage::Apply(
+                    SelectQueryOperation::AddColumn((
+                        "first_name".into(),
+                        sqlil::Expr::attr("people", "first_name"),
+                    ))
+                    .into(),
+                ),
+            ))
+            .unwrap();
+
+        assert_eq!(
+            res,
+            ServerMessage::Query(ServerQueryMessage::OperationResult(
+                QueryOperationResult::Ok(OperationCost::default())
+            ))
+        );
+
+        let res = client
+            .send(ClientMessage::Query(0, ClientQueryMessage::Prepare))
+            .unwrap();
+        assert_eq!(
+            res,
+            ServerMessage::Query(ServerQueryMessage::Prepared(QueryInputStructure::new(
+                vec![]
+            )))
+        );
+
+        let res = client
+            .send(ClientMessage::Query(0, ClientQueryMessage::ExecuteQuery))
+            .unwrap();
+        let row_structure = RowStructure::new(vec![("first_name".into(), DataType::rust_string())]);
+        assert_eq!(
+            res,
+            ServerMessage::Query(ServerQueryMessage::ResultSet(row_structure.clone()))
+        );
+
+        let res = client
+            .send(ClientMessage::Query(0, ClientQueryMessage::Read(1024)))
+            .unwrap();
+        let data = match res {
+            ServerMessage::Query(ServerQueryMessage::ReadData(data)) => data,
+            _ => unreachable!("Unexpected response {:?}", res),
+        };
+
+        let mut result_data = DataReader::new(io::Cursor::new(data), row_structure.types());
+
+        assert_eq!(
+            result_data.read_data_value().unwrap(),
+            Some(DataValue::from("Mary"))
+        );
+        assert_eq!(
+            result_data.read_data_value().unwrap(),
+            Some(DataValue::from("John"))
+        );
+        assert_eq!(
+            result_data.read_data_value().unwrap(),
+            Some(DataValue::from("Gary"))
+        );
+        assert_eq!(result_data.read_data_value().unwrap(), None);
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_fdw_connection_row_filters() {
+        let mut entity = EntityConfig::minimal(
+            "people",
+            vec![
+                EntityAttributeConfig::minimal("first_name", DataType::rust_string()),
+                EntityAttributeConfig::minimal("last_name", DataType::rust_string()),
+            ],
+            EntitySourceConfig::minimal("memory"),
+        );
+        entity.row_filters = vec!["first_name = ${auth.username}".into()];
+
+        let (thread, mut client) =
+            create_mock_connection_with_auth("connection_row_filters", entity, "John");
+
+        // Raw queries would bypass the row filters
+        let res = client
+            .send(ClientMessage::CreateStringQuery(
+                "SELECT 1".into(),
+                vec![],
+                false,
+            ))
+            .unwrap();
+        assert!(matches!(res, ServerMessage::Error(_)));
+
+        let res = client
+            .send(ClientMessage::CreateQuery(
+                sqlil::source("people", "people"),
+                sqlil::QueryType::Select,
+            ))
+            .unwrap();
+        assert!(matches!(res, ServerMessage::QueryCreated(0, _)));
+
+        // Filters on joined entities cannot be applied to right joins
+        let res = client
+            .send(ClientMessage::Query(
+                0,
+                ClientQueryMessage::Apply(
+                    SelectQueryOperation::AddJoin(sqlil::Join::new(
+                        sqlil::JoinType::Right,
+                        sqlil::source("people", "other"),
+                        vec![],
+                    ))
+                    .into(),
+                ),
+            ))
+            .unwrap();
+        assert_eq!(
+            res,
+            ServerMessage::Query(ServerQueryMessage::OperationResult(
+                QueryOperationResult::Unsupported
+            ))
+        );
+
+        client
+            .send(ClientMessage::Query(
+                0,
+                ClientQueryMessage::Apply(
+                    SelectQueryOperation::AddColumn((
+                        "last_name".into(),
+                        sqlil::Expr::attr("people", "last_name"),
+                    ))
+                    .into(),
+                ),
+            ))
+            .unwrap();
+        client
+            .send(ClientMessage::Query(0, ClientQueryMessage::Prepare))
+            .unwrap();
+        client
+            .send(ClientMessage::Query(0, ClientQueryMessage::ExecuteQuery))
+            .unwrap();
+
+        let res = client
+            .send(ClientMessage::Query(0, ClientQueryMessage::Read(1024)))
+            .unwrap();
+        let data = match res {
+            ServerMessage::Query(ServerQueryMessage::ReadData(data)) => data,
+            _ => unreachable!("Unexpected response {:?}", res),
+        };
+
+        let mut result_data = DataReader::new(io::Cursor::new(data), vec![DataType::rust_string()]);
+        assert_eq!(
+            result_data.read_data_value().unwrap(),
+            Some(DataValue::from("Smith"))
+        );
+        assert_eq!(result_data.read_data_value().unwrap(), None);
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_fdw_connection_query_rules() {
+        let nc = NodeConfig {
+            auth: AuthConfig {
+                rules: vec![QueryRuleConfig {
+                    applies_to: UserMatchConfig {
+                        users: vec![],
+                        roles: vec!["analyst".into()],
+                    },
+                    deny_writes: true,
+                    deny_cross_source_joins: true,
+                    require_where: vec!["people".into()],
+                }],
+                users: vec![UserConfig {
+                    username: "analyst".into(),
+                    description: None,
+                    provider: None,
+                    roles: vec!["analyst".into()],
+                    r#type: UserTypeOptions::Password(PasswordUserConfig {
+                        password: "pass".into(),
+                    }),
+                }],
+                ..AuthConfig::default()
+            },
+            ..NodeConfig::default()
+        };
+        let check = |has_conditions: bool, data_sources: Vec<&str>| {
+            ClientMessage::CheckQueryRules(QueryRuleCheck {
+                entity: sqlil::entity("people"),
+                has_conditions,
+                data_sources: data_sources.into_iter().map(|i| i.to_string()).collect(),
+            })
+        };
+
+        let (thread, mut client) =
+            create_mock_connection_with_config("connection_query_rules", nc.clone(), "analyst");
+
+        let res = client.send(check(true, vec!["memory"])).unwrap();
+        assert_eq!(res, ServerMessage::QueryRulesPassed);
+
+        let res = client.send(check(false, vec!["memory"])).unwrap();
+        assert_eq!(
+            res,
+            ServerMessage::Error("User 'analyst' is required to query entity 'people' with a WHERE clause by the query rules".into())
+        );
+
+        let res = client.send(check(true, vec!["memory", "other"])).unwrap();
+        assert!(matches!(res, ServerMessage::Error(_)));
+
+        for r#type in [
+            sqlil::QueryType::Insert,
+            sqlil::QueryType::BulkInsert,
+            sqlil::QueryType::Update,
+            sqlil::QueryType::Delete,
+        ] {
+            let res = client
+                .send(ClientMessage::CreateQuery(
+                    sqlil::source("people", "people"),
+                    r#type,
+                ))
+                .unwrap();
+            assert_eq!(
+                res,
+                ServerMessage::Error(
+                    "User 'analyst' is not permitted to modify data on data source 'memory' by the query rules".into()
+                )
+            );
+        }
+
+        let res = client
+            .send(ClientMessage::CreateStringQuery(
+                "SELECT 1".into(),
+                vec![],
+                false,
+            ))
+            .unwrap();
+        assert!(matches!(res, ServerMessage::Error(_)));
+
+        let res = client
+            .send(ClientMessage::CreateQuery(
+                sqlil::source("people", "people"),
+                sqlil::QueryType::Select,
+            ))
+            .unwrap();
+        assert!(matches!(res, ServerMessage::QueryCreated(_, _)));
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+
+        // Users not matched by the rules are unrestricted
+        let (thread, mut client) =
+            create_mock_connection_with_config("connection_query_rules_other", nc, "other");
+
+        let res = client.send(check(false, vec!["memory", "other"])).unwrap();
+        assert_eq!(res, ServerMessage::QueryRulesPassed);
+
+        let res = client
+            .send(ClientMessage::CreateQuery(
+                sqlil::source("people", "people"),
+                sqlil::QueryType::Insert,
+            ))
+            .unwrap();
+        assert!(matches!(res, ServerMessage::QueryCreated(_, _)));
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_fdw_connection_remote_query_grants() {
+        let nc = NodeConfig {
+            auth: AuthConfig {
+                grants: vec![GrantConfig {
+                    applies_to: UserMatchConfig {
+                        users: vec!["analyst".into()],
+                        roles: vec![],
+                    },
+                    sources: vec!["memory".into()],
+                    remote_queries: true,
+                    ..GrantConfig::default()
+                }],
+                ..AuthConfig::default()
+            },
+            ..NodeConfig::default()
+        };
+
+        // Users without a grant are denied
+        let (thread, mut client) = create_mock_connection_with_config(
+            "connection_remote_query_grants_other",
+            nc.clone(),
+            "other",
+        );
+
+        let res = client
+            .send(ClientMessage::CreateStringQuery(
+                "SELECT 1".into(),
+                vec![],
+                false,
+            ))
+            .unwrap();
+        assert_eq!(
+            res,
+            ServerMessage::Error(
+                "User 'other' is not granted access to execute raw queries on data source 'memory'"
+                    .into()
+            )
+        );
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+
+        // Granted users reach the connector, which does not support raw queries
+        let (thread, mut client) =
+            create_mock_connection_with_config("connection_remote_query_grants", nc, "analyst");
+
+        let res = client
+            .send(ClientMessage::CreateStringQuery(
+                "SELECT 1".into(),
+                vec![],
+                false,
+            ))
+            .unwrap();
+        assert_eq!(res, ServerMessage::Error("Unsupported".into()));
+
+        // Writes require the grant to include write access
+        let res = client
+            .send(ClientMessage::CreateStringQuery(
+                "DELETE FROM people".into(),
+                vec![],
+                true,
+            ))
+            .unwrap();
+        assert_eq!(
+            res,
+            ServerMessage::Error(
+                "User 'analyst' is not granted access to execute raw write queries on data source 'memory'"
+                    .into()
+            )
+        );
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+    }
+
+    fn people_entity() -> EntityConfig {
+        EntityConfig::minimal(
+            "people",
+            vec![
+                EntityAttributeConfig::minimal("first_name", DataType::rust_string()),
+                EntityAttributeConfig::minimal("last_name", DataType::rust_string()),
+            ],
+            EntitySourceConfig::minimal("memory"),
+        )
+    }
+
+    fn select_masked_people(name: &'static str, username: &str) -> Vec<Option<DataValue>> {
+        let mut entity = people_entity();
+        entity.attributes[1].mask = Some(AttributeMaskConfig {
+            r#type: AttributeMaskType::Partial { visible: 2 },
+            unmasked_for: UserMatchConfig {
+                users: vec!["admin".into()],
+                roles: vec![],
+            },
+        });
+
+        select_people(
+            name,
+            NodeConfig {
+                entities: vec![entity],
+                ..NodeConfig::default()
+            },
+            username,
+        )
+    }
+
+    fn select_people(name: &'static str, nc: NodeConfig, username: &str) -> Vec<Option<DataValue>> {
+        let (thread, mut client) = create_mock_connection_with_config(name, nc, username);
+
+        client
+            .send(ClientMessage::CreateQuery(
+                sqlil::source("people", "people"),
+                sqlil::QueryType::Select,
+            ))
+            .unwrap();
+
+        for col in ["first_name", "last_name"] {
+            let res = client
+                .send(ClientMessage::Query(
+                    0,
+                    ClientQueryMessage::Apply(
+                        SelectQueryOperation::AddColumn((
+                            col.into(),
+                            sqlil::Expr::attr("people", col),
+                        ))
+                        .into(),
+                    ),
+                ))
+                .unwrap();
+            assert!(matches!(
+                res,
+                ServerMessage::Query(ServerQueryMessage::OperationResult(
+                    QueryOperationResult::Ok(_)
+                ))
+            ));
+        }
+
+        client
+            .send(ClientMessage::Query(0, ClientQueryMessage::Prepare))
+            .unwrap();
+        client
+            .send(ClientMessage::Query(0, ClientQueryMessage::ExecuteQuery))
+            .unwrap();
+
+        let res = client
+            .send(ClientMessage::Query(0, ClientQueryMessage::Read(1024)))
+            .unwrap();
+        let data = match res {
+            ServerMessage::Query(ServerQueryMessage::ReadData(data)) => data,
+            _ => unreachable!("Unexpected response {:?}", res),
+        };
+
+        let mut result_data = DataReader::new(
+            io::Cursor::new(data),
+            vec![DataType::rust_string(), DataType::rust_string()],
+        );
+        let mut values = vec![];
+        for _ in 0..2 {
+            values.push(result_data.read_data_value().unwrap());
+        }
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+
+        values
+    }
+
+    #[test]
+    fn test_fdw_connection_column_masks() {
+        assert_eq!(
+            select_masked_people("connection_column_masks", "John"),
+            vec![Some(DataValue::from("Mary")), Some(DataValue::from("**ne"))]
+        );
+        assert_eq!(
+            select_masked_people("connection_column_masks_exempt", "admin"),
+            vec![Some(DataValue::from("Mary")), Some(DataValue::from("Jane"))]
+        );
+    }
+
+    fn apply_to_masked_people(
+        name: &'static str,
+        username: &str,
+        op: SelectQueryOperation,
+    ) -> ServerMessage {
+        let mut entity = people_entity();
+        entity.attributes[1].mask = Some(AttributeMaskConfig {
+            r#type: AttributeMaskType::Partial { visible: 2 },
+            unmasked_for: UserMatchConfig {
+                users: vec!["admin".into()],
+                roles: vec![],
+            },
+        });
+        let nc = NodeConfig {
+            entities: vec![entity],
+            ..NodeConfig::default()
+        };
+
+        let (thread, mut client) = create_mock_connection_with_config(name, nc, username);
+
+        client
+            .send(ClientMessage::CreateQuery(
+                sqlil::source("people", "people"),
+                sqlil::QueryType::Select,
+            ))
+            .unwrap();
+
+        let res = client
+            .send(ClientMessage::Query(
+                0,
+                ClientQueryMessage::Apply(op.into()),
+            ))
+            .unwrap();
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+
+        res
+    }
+
+    #[test]
+    fn test_fdw_connection_masked_attributes_not_pushed_down() {
+        let filter = |attr: &str| {
+            SelectQueryOperation::AddWhere(sqlil::Expr::BinaryOp(sqlil::BinaryOp::new(
+                sqlil::Expr::attr("people", attr),
+                sqlil::BinaryOpType::Equal,
+                sqlil::Expr::constant(DataValue::from("Jane")),
+            )))
+        };
+        let unsupported = ServerMessage::Query(ServerQueryMessage::OperationResult(
+            QueryOperationResult::Unsupported,
+        ));
+
+        assert_eq!(
+            apply_to_masked_people("connection_masked_where", "John", filter("last_name")),
+            unsupported
+        );
+        assert_eq!(
+            apply_to_masked_people(
+                "connection_masked_order_by",
+                "John",
+                SelectQueryOperation::AddOrderBy(sqlil::Ordering::asc(sqlil::Expr::attr(
+                    "people",
+                    "last_name"
+                )))
+            ),
+            unsupported
+        );
+        assert_ne!(
+            apply_to_masked_people("connection_unmasked_where", "John", filter("first_name")),
+            unsupported
+        );
+        assert_ne!(
+            apply_to_masked_people(
+                "connection_masked_where_exempt",
+                "admin",
+                filter("last_name")
+            ),
+            unsupported
+        );
+    }
+
+    #[test]
+    fn test_fdw_connection_classification_masks() {
+        let mut entity = people_entity();
+        entity.attributes[0].classifications = vec!["pii".into()];
+        entity.attributes[0].mask = Some(AttributeMaskConfig {
+            r#type: AttributeMaskType::Null,
+            unmasked_for: UserMatchConfig::default(),
+        });
+        entity.attributes[1].classifications = vec!["pii".into()];
+
+        let nc = NodeConfig {
+            entities: vec![entity],
+            auth: AuthConfig {
+                masks: vec![ClassificationMaskConfig {
+                    classification: "pii".into(),
+                    mask: AttributeMaskConfig {
+                        r#type: AttributeMaskType::Partial { visible: 2 },
+                        unmasked_for: UserMatchConfig {
+                            users: vec!["admin".into()],
+                            roles: vec![],
+                        },
+                    },
+                }],
+                ..AuthConfig::default()
+            },
+            ..NodeConfig::default()
+        };
+
+        // The mask on the attribute takes precedence over the classification mask
+        assert_eq!(
+            select_people("connection_classification_masks", nc.clone(), "John"),
+            vec![Some(DataValue::Null), Some(DataValue::from("**ne"))]
+        );
+        assert_eq!(
+            select_people("connection_classification_masks_exempt", nc, "admin"),
+            vec![Some(DataValue::Null), Some(DataValue::from("Jane"))]
+        );
     }
 
     #[test]
-    fn test_fdw_connection_estimate_size_unknown_entity() {
+    fn test_fdw_connection_column_masks_expressions_evaluated_locally() {
+        let mut entity = EntityConfig::minimal(
+            "people",
+            vec![EntityAttributeConfig::minimal(
+                "last_name",
+                DataType::rust_string(),
+            )],
+            EntitySourceConfig::minimal("memory"),
+        );
+        entity.attributes[0].mask = Some(AttributeMaskConfig {
+            r#type: AttributeMaskType::Hash,
+            unmasked_for: UserMatchConfig::default(),
+        });
+
         let (thread, mut client) =
-            create_mock_connection("connection_estimate_size_unknown_entity");
+            create_mock_connection_with_auth("connection_column_masks_exprs", entity, "John");
 
+        // Raw queries would bypass the masking
         let res = client
-            .send(ClientMessage::EstimateSize(sqlil::entity("unknown")))
+            .send(ClientMessage::CreateStringQuery(
+                "SELECT 1".into(),
+                vec![],
+                false,
+            ))
             .unwrap();
+        assert!(matches!(res, ServerMessage::Error(_)));
 
-        assert_eq!(res, ServerMessage::UnknownEntity(EntityId::new("unknown")));
+        client
+            .send(ClientMessage::CreateQuery(
+                sqlil::source("people", "people"),
+                sqlil::QueryType::Select,
+            ))
+            .unwrap();
+
+        let res = client
+            .send(ClientMessage::Query(
+                0,
+                ClientQueryMessage::Apply(
+                    SelectQueryOperation::AddColumn((
+                        "name".into(),
+                        sqlil::Expr::BinaryOp(sqlil::BinaryOp::new(
+                            sqlil::Expr::attr("people", "last_name"),
+                            sqlil::BinaryOpType::Concat,
+                            sqlil::Expr::constant(DataValue::from("!")),
+                        )),
+                    ))
+                    .into(),
+                ),
+            ))
+            .unwrap();
+        assert_eq!(
+            res,
+            ServerMessage::Query(ServerQueryMessage::OperationResult(
+                QueryOperationResult::Unsupported
+            ))
+        );
 
         client.close().unwrap();
         thread.join().unwrap().unwrap();
     }
 
     #[test]
-    fn test_fdw_connection_select_join_unknown_entity() {
-        let (thread, mut client) = create_mock_connection("connection_select_join_unknown_entity");
+    fn test_fdw_connection_select_columnar() {
+        let (thread, mut client) = create_mock_connection("connection_select_columnar");
 
-        let res = client
+        client
             .send(ClientMessage::CreateQuery(
                 sqlil::source("people", "people"),
                 sqlil::QueryType::Select,
             ))
             .unwrap();
-
-        assert_eq!(
-            res,
-            ServerMessage::QueryCreated(0, OperationCost::default())
-        );
-
-        let res = client
+        client
             .send(ClientMessage::Query(
                 0,
                 ClientQueryMessage::Apply(
-                    SelectQueryOperation::AddJoin(sqlil::Join::new(
-                        sqlil::JoinType::Inner,
-                        sqlil::source("joined_entity", "a"),
-                        vec![],
+                    SelectQueryOperation::AddColumn((
+                        "first_name".into(),
+                        sqlil::Expr::attr("people", "first_name"),
                     ))
                     .into(),
                 ),
             ))
             .unwrap();
+        client
+            .send(ClientMessage::Query(0, ClientQueryMessage::Prepare))
+            .unwrap();
+        client
+            .send(ClientMessage::Query(0, ClientQueryMessage::ExecuteQuery))
+            .unwrap();
+
+        let mut read_batch = || {
+            let res = client
+                .send(ClientMessage::Query(0, ClientQueryMessage::ReadColumnar(2)))
+                .unwrap();
+
+            match res {
+                ServerMessage::Query(ServerQueryMessage::ColumnarData(data)) => data,
+                _ => unreachable!("Unexpected response {:?}", res),
+            }
+        };
 
+        let mut batch = ColumnarBatch::decode(read_batch(), vec![DataType::rust_string()]).unwrap();
+        assert_eq!(batch.num_rows(), 2);
         assert_eq!(
-            res,
-            ServerMessage::UnknownEntity(EntityId::new("joined_entity"))
+            batch.read_data_value().unwrap(),
+            Some(DataValue::from("Mary"))
+        );
+        assert_eq!(
+            batch.read_data_value().unwrap(),
+            Some(DataValue::from("John"))
+        );
+        assert_eq!(batch.read_data_value().unwrap(), None);
+
+        let mut batch = ColumnarBatch::decode(read_batch(), vec![DataType::rust_string()]).unwrap();
+        assert_eq!(
+            batch.read_data_value().unwrap(),
+            Some(DataValue::from("Gary"))
         );
+        assert_eq!(batch.read_data_value().unwrap(), None);
+
+        assert_eq!(read_batch(), Vec::<u8>::new());
 
         client.close().unwrap();
         thread.join().unwrap().unwrap();
     }
 
     #[test]
-    fn test_fdw_connection_select() {
-        let (thread, mut client) = create_mock_connection("connection_select");
+    fn test_fdw_connection_select_with_compression() {
+        let (thread, mut client) = create_mock_connection("connection_select_compression");
 
-        let res = client
+        client
             .send(ClientMessage::CreateQuery(
                 sqlil::source("people", "people"),
                 sqlil::QueryType::Select,
             ))
             .unwrap();
-
-        assert_eq!(
-            res,
-            ServerMessage::QueryCreated(0, OperationCost::default())
-        );
-
-        let res = client
+        client
             .send(ClientMessage::Query(
                 0,
                 ClientQueryMessage::Apply(
@@ -1065,33 +3058,25 @@ mod tests {
                 ),
             ))
             .unwrap();
-
-        assert_eq!(
-            res,
-            ServerMessage::Query(ServerQueryMessage::OperationResult(
-                QueryOperationResult::Ok(OperationCost::default())
-            ))
-        );
-
-        let res = client
+        client
             .send(ClientMessage::Query(0, ClientQueryMessage::Prepare))
             .unwrap();
-        assert_eq!(
-            res,
-            ServerMessage::Query(ServerQueryMessage::Prepared(QueryInputStructure::new(
-                vec![]
-            )))
-        );
+        client
+            .send(ClientMessage::Query(0, ClientQueryMessage::ExecuteQuery))
+            .unwrap();
 
         let res = client
-            .send(ClientMessage::Query(0, ClientQueryMessage::ExecuteQuery))
+            .send(ClientMessage::Query(
+                0,
+                ClientQueryMessage::SetCompression(TransferCompression::Lz4),
+            ))
             .unwrap();
-        let row_structure = RowStructure::new(vec![("first_name".into(), DataType::rust_string())]);
         assert_eq!(
             res,
-            ServerMessage::Query(ServerQueryMessage::ResultSet(row_structure.clone()))
+            ServerMessage::Query(ServerQueryMessage::CompressionSet)
         );
 
+        // Small batches are not worth compressing
         let res = client
             .send(ClientMessage::Query(0, ClientQueryMessage::Read(1024)))
             .unwrap();
@@ -1100,21 +3085,11 @@ mod tests {
             _ => unreachable!("Unexpected response {:?}", res),
         };
 
-        let mut result_data = DataReader::new(io::Cursor::new(data), row_structure.types());
-
+        let mut result_data = DataReader::new(io::Cursor::new(data), vec![DataType::rust_string()]);
         assert_eq!(
             result_data.read_data_value().unwrap(),
             Some(DataValue::from("Mary"))
         );
-        assert_eq!(
-            result_data.read_data_value().unwrap(),
-            Some(DataValue::from("John"))
-        );
-        assert_eq!(
-            result_data.read_data_value().unwrap(),
-            Some(DataValue::from("Gary"))
-        );
-        assert_eq!(result_data.read_data_value().unwrap(), None);
 
         client.close().unwrap();
         thread.join().unwrap().unwrap();
@@ -1857,8 +3832,10 @@ mod tests {
             MemoryDatabaseConf {
                 transactions_enabled: false,
                 row_locks_pretend: true,
+                ..Default::default()
             },
             RemoteQueryLog::new(),
+            MetadataCache::new(),
         );
 
         let res = client.send(ClientMessage::BeginTransaction).unwrap();
@@ -1986,6 +3963,7 @@ mod tests {
             "connection_remote_query_log",
             MemoryDatabaseConf::default(),
             log.clone(),
+            MetadataCache::new(),
         );
 
         let res = client
@@ -2407,4 +4385,203 @@ mod tests {
             ]
         );
     }
+
+    fn scan_first_names(client: &mut IpcClientChannel, query_id: QueryId) -> Vec<DataValue> {
+        client
+            .send(ClientMessage::CreateQuery(
+                sqlil::source("people", "people"),
+                sqlil::QueryType::Select,
+            ))
+            .unwrap();
+        client
+            .send(ClientMessage::Query(
+                query_id,
+                ClientQueryMessage::Apply(
+                    SelectQueryOperation::AddColumn((
+                        "first_name".into(),
+                        sqlil::Expr::attr("people", "first_name"),
+                    ))
+                    .into(),
+                ),
+            ))
+            .unwrap();
+        client
+            .send(ClientMessage::Query(query_id, ClientQueryMessage::Prepare))
+            .unwrap();
+        client
+            .send(ClientMessage::Query(
+                query_id,
+                ClientQueryMessage::ExecuteQuery,
+            ))
+            .unwrap();
+
+        let res = client
+            .send(ClientMessage::Query(
+                query_id,
+                ClientQueryMessage::Read(1024),
+            ))
+            .unwrap();
+        let data = match res {
+            ServerMessage::Query(ServerQueryMessage::ReadData(data)) => data,
+            _ => unreachable!("Unexpected response {:?}", res),
+        };
+        client
+            .send(ClientMessage::Query(query_id, ClientQueryMessage::Discard))
+            .unwrap();
+
+        let mut reader = DataReader::new(io::Cursor::new(data), vec![DataType::rust_string()]);
+        let mut values = vec![];
+        while let Some(value) = reader.read_data_value().unwrap() {
+            values.push(value);
+        }
+
+        values
+    }
+
+    #[test]
+    fn test_fdw_connection_cached_scans() {
+        let (thread, mut client, data) =
+            create_mock_connection_with_cached_entity("connection_cached_scans");
+
+        assert_eq!(
+            scan_first_names(&mut client, 0),
+            vec![
+                DataValue::from("Mary"),
+                DataValue::from("John"),
+                DataValue::from("Gary")
+            ]
+        );
+
+        // Changes made outside of ansilo are not visible until the cached scan expires
+        data.set_data(
+            "people",
+            vec![vec![DataValue::from("Bob"), DataValue::from("Jones")]],
+        );
+
+        assert_eq!(
+            scan_first_names(&mut client, 1),
+            vec![
+                DataValue::from("Mary"),
+                DataValue::from("John"),
+                DataValue::from("Gary")
+            ]
+        );
+
+        // Writing to the entity invalidates the cached scans
+        client
+            .send(ClientMessage::CreateQuery(
+                sqlil::source("people", "people"),
+                sqlil::QueryType::Insert,
+            ))
+            .unwrap();
+        for (col, val) in [("first_name", "New"), ("last_name", "Man")] {
+            client
+                .send(ClientMessage::Query(
+                    2,
+                    ClientQueryMessage::Apply(
+                        InsertQueryOperation::AddColumn((
+                            col.into(),
+                            sqlil::Expr::constant(DataValue::from(val)),
+                        ))
+                        .into(),
+                    ),
+                ))
+                .unwrap();
+        }
+        client
+            .send(ClientMessage::Query(2, ClientQueryMessage::Prepare))
+            .unwrap();
+        let res = client
+            .send(ClientMessage::Query(2, ClientQueryMessage::ExecuteModify))
+            .unwrap();
+        assert!(matches!(
+            res,
+            ServerMessage::Query(ServerQueryMessage::AffectedRows(_))
+        ));
+
+        assert_eq!(
+            scan_first_names(&mut client, 3),
+            vec![DataValue::from("Bob"), DataValue::from("New")]
+        );
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_fdw_connection_cached_scans_per_user() {
+        let (entities, pool) = create_memory_connection_pool(MemoryDatabaseConf::default());
+        let data = pool.conf();
+        let scan_cache = ScanCache::new();
+        let auth = |username: &str| {
+            Some(AuthContext::new(
+                username,
+                "password",
+                None,
+                ProviderAuthContext::Password(PasswordAuthContext::default()),
+            ))
+        };
+
+        let (thread, mut client) = create_mock_connection_with_cached_entity_opts(
+            "connection_cached_scans_per_user_1",
+            auth("john"),
+            entities.clone(),
+            pool.clone(),
+            scan_cache.clone(),
+        );
+
+        assert_eq!(
+            scan_first_names(&mut client, 0),
+            vec![
+                DataValue::from("Mary"),
+                DataValue::from("John"),
+                DataValue::from("Gary")
+            ]
+        );
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+
+        data.set_data(
+            "people",
+            vec![vec![DataValue::from("Bob"), DataValue::from("Jones")]],
+        );
+
+        // Scans cached for one user are not served to another
+        let (thread, mut client) = create_mock_connection_with_cached_entity_opts(
+            "connection_cached_scans_per_user_2",
+            auth("mary"),
+            entities.clone(),
+            pool.clone(),
+            scan_cache.clone(),
+        );
+
+        assert_eq!(
+            scan_first_names(&mut client, 0),
+            vec![DataValue::from("Bob")]
+        );
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+
+        let (thread, mut client) = create_mock_connection_with_cached_entity_opts(
+            "connection_cached_scans_per_user_3",
+            auth("john"),
+            entities,
+            pool,
+            scan_cache,
+        );
+
+        assert_eq!(
+            scan_first_names(&mut client, 0),
+            vec![
+                DataValue::from("Mary"),
+                DataValue::from("John"),
+                DataValue::from("Gary")
+            ]
+        );
+
+        client.close().unwrap();
+        thread.join().unwrap().unwrap();
+    }
 }
@@ -0,0 +1,276 @@
+use std::{cmp, time::Duration};
+
+/// The default minimum number of rows transferred per batch
+pub const DEFAULT_MIN_FETCH_ROWS: u32 = 10;
+/// The default maximum number of rows transferred per batch
+pub const DEFAULT_MAX_FETCH_ROWS: u32 = 100_000;
+
+/// The number of rows requested in the first batch
+const INITIAL_FETCH_ROWS: u32 = 100;
+/// The assumed row width before any rows have been read
+const DEFAULT_ROW_WIDTH: u64 = 100;
+/// The smallest batch we will request, in bytes
+const MIN_BATCH_BYTES: u64 = 1024;
+/// The largest batch we will request, in bytes, this bounds
+/// the memory used to buffer wide rows
+const MAX_BATCH_BYTES: u64 = 4 * 1024 * 1024;
+/// Batches returned faster than this are grown
+const FAST_BATCH_LATENCY: Duration = Duration::from_millis(10);
+/// Batches returned slower than this are shrunk
+const SLOW_BATCH_LATENCY: Duration = Duration::from_millis(250);
+
+/// The bounds on the number of rows transferred per batch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FetchSizeBounds {
+    pub min_rows: u32,
+    pub max_rows: u32,
+}
+
+impl FetchSizeBounds {
+    pub fn new(min_rows: Option<u32>, max_rows: Option<u32>) -> Self {
+        let min_rows = min_rows.unwrap_or(DEFAULT_MIN_FETCH_ROWS).max(1);
+        let max_rows = max_rows.unwrap_or(DEFAULT_MAX_FETCH_ROWS).max(min_rows);
+
+        Self { min_rows, max_rows }
+    }
+
+    /// Replaces the supplied bounds, keeping the existing bounds where they are not set
+    pub fn with_overrides(self, min_rows: Option<u32>, max_rows: Option<u32>) -> Self {
+        Self::new(
+            Some(min_rows.unwrap_or(self.min_rows)),
+            Some(max_rows.unwrap_or(self.max_rows)),
+        )
+    }
+}
+
+impl Default for FetchSizeBounds {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+/// Determines the size of each batch of result data transferred over the FDW channel.
+///
+/// The number of rows per batch starts small and is adjusted based on the latency
+/// of each batch: fast batches indicate the round-trip overhead dominates so we
+/// request more rows, slow batches are halved to keep the query responsive.
+/// The rows are converted into a byte count using the observed average row width,
+/// which is capped so that wide rows result in fewer rows per batch.
+#[derive(Debug, Clone)]
+pub struct AdaptiveFetchSize {
+    /// The bounds on the number of rows per batch
+    bounds: FetchSizeBounds,
+    /// The current number of rows per batch
+    rows: u32,
+    /// The row width estimate used before any rows have been read
+    estimated_row_width: u64,
+    /// The total number of bytes received
+    bytes_read: u64,
+    /// The total number of rows read
+    rows_read: u64,
+}
+
+impl AdaptiveFetchSize {
+    pub fn new(bounds: FetchSizeBounds, estimated_row_width: Option<u32>) -> Self {
+        Self {
+            bounds,
+            rows: INITIAL_FETCH_ROWS.clamp(bounds.min_rows, bounds.max_rows),
+            estimated_row_width: estimated_row_width
+                .map(|w| w as u64)
+                .filter(|w| *w > 0)
+                .unwrap_or(DEFAULT_ROW_WIDTH),
+            bytes_read: 0,
+            rows_read: 0,
+        }
+    }
+
+    /// Gets the current number of rows per batch
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// Gets the average row width in bytes
+    pub fn row_width(&self) -> u64 {
+        if self.rows_read == 0 {
+            return self.estimated_row_width;
+        }
+
+        cmp::max(self.bytes_read / self.rows_read, 1)
+    }
+
+    /// Gets the number of bytes to request in the next batch
+    pub fn batch_bytes(&self) -> usize {
+        (self.rows as u64 * self.row_width()).clamp(MIN_BATCH_BYTES, MAX_BATCH_BYTES) as usize
+    }
+
+    /// Gets the number of rows to request in the next batch,
+    /// for transfers which are sized by rows rather than bytes
+    pub fn batch_rows(&self) -> u32 {
+        let max = cmp::max(MAX_BATCH_BYTES / self.row_width(), 1);
+
+        cmp::min(self.rows as u64, max) as u32
+    }
+
+    /// Records a batch of the requested size which returned the
+    /// supplied number of bytes after the supplied latency
+    pub fn observe_batch(&mut self, requested: usize, received: usize, latency: Duration) {
+        self.bytes_read += received as u64;
+        self.adapt(received >= requested, latency);
+    }
+
+    /// Records a batch of the requested number of rows which returned
+    /// the supplied number of rows and bytes after the supplied latency
+    pub fn observe_rows(&mut self, requested: u32, rows: u32, bytes: usize, latency: Duration) {
+        self.bytes_read += bytes as u64;
+        self.rows_read += rows as u64;
+        self.adapt(rows >= requested, latency);
+    }
+
+    /// Records that a row was read from the result set
+    pub fn observe_row(&mut self) {
+        self.rows_read += 1;
+    }
+
+    fn adapt(&mut self, complete: bool, latency: Duration) {
+        // A partial batch means the result set is exhausted so its latency
+        // does not reflect the batch size
+        if !complete {
+            return;
+        }
+
+        if latency < FAST_BATCH_LATENCY {
+            self.rows = self.rows.saturating_mul(2);
+        } else if latency > SLOW_BATCH_LATENCY {
+            self.rows /= 2;
+        }
+
+        self.rows = self.rows.clamp(self.bounds.min_rows, self.bounds.max_rows);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fetch_size_bounds() {
+        assert_eq!(
+            FetchSizeBounds::default(),
+            FetchSizeBounds {
+                min_rows: DEFAULT_MIN_FETCH_ROWS,
+                max_rows: DEFAULT_MAX_FETCH_ROWS
+            }
+        );
+        assert_eq!(
+            FetchSizeBounds::new(Some(0), Some(5)),
+            FetchSizeBounds {
+                min_rows: 1,
+                max_rows: 5
+            }
+        );
+        assert_eq!(
+            FetchSizeBounds::new(Some(50), Some(5)),
+            FetchSizeBounds {
+                min_rows: 50,
+                max_rows: 50
+            }
+        );
+    }
+
+    #[test]
+    fn test_fetch_size_bounds_with_overrides() {
+        let bounds = FetchSizeBounds::new(Some(50), Some(500));
+
+        assert_eq!(bounds.with_overrides(None, None), bounds);
+        assert_eq!(
+            bounds.with_overrides(Some(1000), None),
+            FetchSizeBounds {
+                min_rows: 1000,
+                max_rows: 1000
+            }
+        );
+        assert_eq!(
+            bounds.with_overrides(None, Some(100_000)),
+            FetchSizeBounds {
+                min_rows: 50,
+                max_rows: 100_000
+            }
+        );
+    }
+
+    #[test]
+    fn test_adaptive_fetch_size_initial() {
+        let fetch = AdaptiveFetchSize::new(FetchSizeBounds::default(), Some(50));
+
+        assert_eq!(fetch.rows(), INITIAL_FETCH_ROWS);
+        assert_eq!(fetch.row_width(), 50);
+        assert_eq!(fetch.batch_bytes(), 5000);
+    }
+
+    #[test]
+    fn test_adaptive_fetch_size_grows_when_fast() {
+        let mut fetch = AdaptiveFetchSize::new(FetchSizeBounds::new(None, Some(300)), None);
+
+        fetch.observe_batch(100, 100, Duration::from_millis(1));
+        assert_eq!(fetch.rows(), 200);
+
+        fetch.observe_batch(100, 100, Duration::from_millis(1));
+        assert_eq!(fetch.rows(), 300);
+    }
+
+    #[test]
+    fn test_adaptive_fetch_size_shrinks_when_slow() {
+        let mut fetch = AdaptiveFetchSize::new(FetchSizeBounds::new(Some(30), None), None);
+
+        fetch.observe_batch(100, 100, Duration::from_secs(1));
+        assert_eq!(fetch.rows(), 50);
+
+        fetch.observe_batch(100, 100, Duration::from_secs(1));
+        assert_eq!(fetch.rows(), 30);
+    }
+
+    #[test]
+    fn test_adaptive_fetch_size_ignores_partial_batch() {
+        let mut fetch = AdaptiveFetchSize::new(FetchSizeBounds::default(), None);
+
+        fetch.observe_batch(100, 10, Duration::from_millis(1));
+        assert_eq!(fetch.rows(), INITIAL_FETCH_ROWS);
+    }
+
+    #[test]
+    fn test_adaptive_fetch_size_uses_observed_row_width() {
+        let mut fetch = AdaptiveFetchSize::new(FetchSizeBounds::default(), Some(10));
+
+        fetch.observe_batch(5000, 2000, Duration::from_millis(100));
+        for _ in 0..10 {
+            fetch.observe_row();
+        }
+
+        assert_eq!(fetch.row_width(), 200);
+        assert_eq!(fetch.batch_bytes(), 20_000);
+    }
+
+    #[test]
+    fn test_adaptive_fetch_size_wide_rows_are_capped() {
+        let mut fetch = AdaptiveFetchSize::new(FetchSizeBounds::default(), None);
+
+        fetch.observe_batch(1, 1024 * 1024, Duration::from_millis(100));
+        fetch.observe_row();
+
+        assert_eq!(fetch.batch_bytes(), MAX_BATCH_BYTES as usize);
+    }
+
+    #[test]
+    fn test_adaptive_fetch_size_rows() {
+        let mut fetch = AdaptiveFetchSize::new(FetchSizeBounds::default(), None);
+
+        fetch.observe_rows(100, 100, 10_000, Duration::from_millis(1));
+        assert_eq!(fetch.rows(), 200);
+        assert_eq!(fetch.row_width(), 100);
+        assert_eq!(fetch.batch_rows(), 200);
+
+        fetch.observe_rows(200, 10, 10 * 1024 * 1024, Duration::from_millis(1));
+        assert_eq!(fetch.rows(), 200);
+        assert_eq!(fetch.batch_rows(), 43);
+    }
+}
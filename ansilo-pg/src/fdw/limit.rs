@@ -0,0 +1,224 @@
+use std::{
+    cmp,
+    time::{Duration, Instant},
+};
+
+use ansilo_connectors_base::{
+    common::data::{DataWriter, ResultSetReader},
+    interface::{ResultSet, RowStructure},
+};
+use ansilo_core::{
+    config::UserLimits,
+    err::{bail, Result},
+};
+
+/// Wraps a result set, enforcing the resource limits of the user as the rows are read
+pub(crate) struct LimitedResultSet<T: ResultSet> {
+    state: LimitState<T>,
+    /// The maximum runtime of the query and the time at which it started
+    runtime: Option<(Duration, Instant)>,
+}
+
+enum LimitState<T: ResultSet> {
+    /// The rows are not limited so the data is passed through without decoding
+    Unlimited(T),
+    Limited {
+        reader: ResultSetReader<T>,
+        /// The maximum number of rows which can be read
+        max_rows: u64,
+        /// The number of rows read so far
+        rows: u64,
+        /// Buffer of the row data which has not yet been read
+        writer: DataWriter<Vec<u8>>,
+        /// The position of the unread data in the buffer
+        pos: usize,
+    },
+}
+
+impl<T: ResultSet> LimitedResultSet<T> {
+    pub(crate) fn new(inner: T, limits: &UserLimits) -> Result<Self> {
+        let runtime = limits
+            .max_statement_runtime
+            .map(|runtime| (runtime, Instant::now()));
+
+        let state = match limits.max_result_rows {
+            Some(max_rows) => LimitState::Limited {
+                reader: ResultSetReader::new(inner)?,
+                max_rows,
+                rows: 0,
+                writer: DataWriter::new(vec![], None),
+                pos: 0,
+            },
+            None => LimitState::Unlimited(inner),
+        };
+
+        Ok(Self { state, runtime })
+    }
+}
+
+impl<T: ResultSet> ResultSet for LimitedResultSet<T> {
+    fn get_structure(&self) -> Result<RowStructure> {
+        match &self.state {
+            LimitState::Unlimited(inner) => inner.get_structure(),
+            LimitState::Limited { reader, .. } => Ok(reader.get_structure().clone()),
+        }
+    }
+
+    fn read(&mut self, buff: &mut [u8]) -> Result<usize> {
+        if let Some((runtime, started)) = self.runtime {
+            if started.elapsed() > runtime {
+                bail!(
+                    "Query exceeded the maximum statement runtime of {}s",
+                    runtime.as_secs()
+                );
+            }
+        }
+
+        let (reader, max_rows, rows, writer, pos) = match &mut self.state {
+            LimitState::Unlimited(inner) => return inner.read(buff),
+            LimitState::Limited {
+                reader,
+                max_rows,
+                rows,
+                writer,
+                pos,
+            } => (reader, *max_rows, rows, writer, pos),
+        };
+
+        while writer.inner_mut().len() - *pos < buff.len() {
+            let row = match reader.read_row_vec()? {
+                Some(row) => row,
+                None => break,
+            };
+
+            *rows += 1;
+            if *rows > max_rows {
+                bail!("Query exceeded the maximum of {max_rows} result rows");
+            }
+
+            for value in row.into_iter() {
+                writer.write_data_value(value)?;
+            }
+        }
+
+        let data = writer.inner_mut();
+        let len = cmp::min(buff.len(), data.len() - *pos);
+        buff[..len].copy_from_slice(&data[*pos..(*pos + len)]);
+        *pos += len;
+
+        if *pos == data.len() {
+            data.clear();
+            *pos = 0;
+        }
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::data::{DataType, DataValue};
+
+    use super::*;
+
+    struct MockResultSet(RowStructure, std::io::Cursor<Vec<u8>>);
+
+    impl ResultSet for MockResultSet {
+        fn get_structure(&self) -> Result<RowStructure> {
+            Ok(self.0.clone())
+        }
+
+        fn read(&mut self, buff: &mut [u8]) -> Result<usize> {
+            Ok(std::io::Read::read(&mut self.1, buff)?)
+        }
+    }
+
+    fn mock_result_set(rows: Vec<DataValue>) -> MockResultSet {
+        MockResultSet(
+            RowStructure::new(vec![("id".into(), DataType::Int32)]),
+            std::io::Cursor::new(DataWriter::to_vec(rows).unwrap()),
+        )
+    }
+
+    #[test]
+    fn test_limited_result_set_unlimited() {
+        let result_set = LimitedResultSet::new(
+            mock_result_set(vec![DataValue::Int32(1), DataValue::Int32(2)]),
+            &UserLimits::default(),
+        )
+        .unwrap();
+
+        assert!(matches!(result_set.state, LimitState::Unlimited(_)));
+
+        let mut reader = result_set.reader().unwrap();
+        assert_eq!(
+            reader.read_row_vec().unwrap(),
+            Some(vec![DataValue::Int32(1)])
+        );
+        assert_eq!(
+            reader.read_row_vec().unwrap(),
+            Some(vec![DataValue::Int32(2)])
+        );
+        assert_eq!(reader.read_row_vec().unwrap(), None);
+    }
+
+    #[test]
+    fn test_limited_result_set_max_rows() {
+        let limits = UserLimits {
+            max_result_rows: Some(2),
+            ..UserLimits::default()
+        };
+
+        let mut reader = LimitedResultSet::new(
+            mock_result_set(vec![DataValue::Int32(1), DataValue::Int32(2)]),
+            &limits,
+        )
+        .unwrap()
+        .reader()
+        .unwrap();
+        assert_eq!(
+            reader.read_row_vec().unwrap(),
+            Some(vec![DataValue::Int32(1)])
+        );
+        assert_eq!(
+            reader.read_row_vec().unwrap(),
+            Some(vec![DataValue::Int32(2)])
+        );
+        assert_eq!(reader.read_row_vec().unwrap(), None);
+
+        let mut result_set = LimitedResultSet::new(
+            mock_result_set(vec![
+                DataValue::Int32(1),
+                DataValue::Int32(2),
+                DataValue::Int32(3),
+            ]),
+            &limits,
+        )
+        .unwrap();
+        let mut buff = [0u8; 1024];
+        assert_eq!(
+            result_set.read(&mut buff).unwrap_err().to_string(),
+            "Query exceeded the maximum of 2 result rows"
+        );
+    }
+
+    #[test]
+    fn test_limited_result_set_max_runtime() {
+        let mut result_set = LimitedResultSet::new(
+            mock_result_set(vec![DataValue::Int32(1)]),
+            &UserLimits {
+                max_statement_runtime: Some(Duration::ZERO),
+                ..UserLimits::default()
+            },
+        )
+        .unwrap();
+
+        std::thread::sleep(Duration::from_millis(1));
+
+        let mut buff = [0u8; 1024];
+        assert_eq!(
+            result_set.read(&mut buff).unwrap_err().to_string(),
+            "Query exceeded the maximum statement runtime of 0s"
+        );
+    }
+}
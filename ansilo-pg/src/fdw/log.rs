@@ -1,28 +1,71 @@
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use ansilo_connectors_base::interface::LoggedQuery;
-use ansilo_core::err::{bail, Context, Result};
+use ansilo_core::{
+    err::{bail, Context, Error, Result},
+    web::query_log::RemoteQuery,
+};
 use ansilo_logging::{info, limiting::MaxLogLength};
 
+/// The number of recent queries retained for the query log api
+const MAX_RECENT_QUERIES: usize = 1000;
+
 /// Storage for logging remote queries
 #[derive(Clone)]
 pub struct RemoteQueryLog {
     /// Recorded remote queries
     queries: Option<Arc<Mutex<Vec<(String, LoggedQuery)>>>>,
+    /// The most recent queries with their execution details, oldest first
+    recent: Arc<Mutex<RecentQueries>>,
+}
+
+#[derive(Default)]
+struct RecentQueries {
+    queries: VecDeque<RemoteQuery>,
+    /// The id of the next recorded query
+    next_id: u64,
+}
+
+/// The details of how a remote query was executed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RemoteQueryExecution {
+    /// The pid of the postgres backend of the session which issued the query
+    pub session_id: Option<u32>,
+    /// The user authenticated by the session
+    pub username: Option<String>,
+    /// How long the query took to execute
+    pub duration: Option<Duration>,
 }
 
 impl RemoteQueryLog {
     pub fn new() -> Self {
-        Self { queries: None }
+        Self {
+            queries: None,
+            recent: Arc::new(Mutex::new(RecentQueries::default())),
+        }
     }
 
     pub fn store_in_memory() -> Self {
         Self {
             queries: Some(Arc::new(Mutex::new(vec![]))),
+            ..Self::new()
         }
     }
 
     pub fn record(&self, data_source: &str, query: LoggedQuery) -> Result<()> {
+        self.record_execution(data_source, query, RemoteQueryExecution::default())
+    }
+
+    pub fn record_execution(
+        &self,
+        data_source: &str,
+        query: LoggedQuery,
+        execution: RemoteQueryExecution,
+    ) -> Result<()> {
         info!(
             "Remote query sent to {}: {:?}",
             data_source,
@@ -37,6 +80,8 @@ impl RemoteQueryLog {
             )
         );
 
+        self.push_recent(data_source, &query, execution)?;
+
         if self.queries.is_some() {
             self.lock()?.push((data_source.into(), query));
         }
@@ -54,6 +99,49 @@ impl RemoteQueryLog {
         Ok(queries.clone())
     }
 
+    /// Gets the most recent queries, oldest first, with their parameters redacted
+    pub fn recent(&self) -> Result<Vec<RemoteQuery>> {
+        Ok(self.lock_recent()?.queries.iter().cloned().collect())
+    }
+
+    fn push_recent(
+        &self,
+        data_source: &str,
+        query: &LoggedQuery,
+        execution: RemoteQueryExecution,
+    ) -> Result<()> {
+        let now = SystemTime::now();
+        let started_at = execution
+            .duration
+            .and_then(|d| now.checked_sub(d))
+            .unwrap_or(now);
+
+        let mut recent = self.lock_recent()?;
+        let id = recent.next_id;
+        recent.next_id += 1;
+
+        if recent.queries.len() >= MAX_RECENT_QUERIES {
+            recent.queries.pop_front();
+        }
+
+        recent.queries.push_back(RemoteQuery {
+            id,
+            data_source: data_source.into(),
+            session_id: execution.session_id,
+            username: execution.username,
+            query: query.query().into(),
+            params: query.params().iter().map(|p| redact_param(p)).collect(),
+            other: query.other().clone(),
+            started_at: started_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            duration_ms: execution.duration.map(|d| d.as_secs_f64() * 1000.0),
+        });
+
+        Ok(())
+    }
+
     fn lock(&self) -> Result<MutexGuard<Vec<(String, LoggedQuery)>>> {
         let queries = self
             .queries
@@ -65,6 +153,12 @@ impl RemoteQueryLog {
             Err(err) => bail!("Failed to lock query log: {:?}", err),
         })
     }
+
+    fn lock_recent(&self) -> Result<MutexGuard<RecentQueries>> {
+        self.recent
+            .lock()
+            .map_err(|_| Error::msg("Failed to lock recent queries"))
+    }
 }
 
 impl Default for RemoteQueryLog {
@@ -73,6 +167,45 @@ impl Default for RemoteQueryLog {
     }
 }
 
+/// Redacts the values from a logged query parameter.
+///
+/// Connectors format their parameters differently, the value following
+/// a `value=` marker is redacted while the remaining details, such as the
+/// parameter type, are retained. Parameters without the marker are redacted entirely.
+fn redact_param(param: &str) -> String {
+    const MARKER: &str = "value=";
+    const REDACTED: &str = "<redacted>";
+
+    let start = match param.find(MARKER) {
+        Some(idx) => idx + MARKER.len(),
+        None => return REDACTED.into(),
+    };
+
+    // Find the end of the value, skipping over any nested or quoted delimiters
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut end = param.len();
+
+    for (idx, c) in param[start..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            _ if quoted => {}
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' if depth > 0 => depth -= 1,
+            ' ' | ',' | ')' | ']' | '}' if depth == 0 => {
+                end = start + idx;
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    format!("{}{}{}", &param[..start], REDACTED, &param[end..])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -102,4 +235,67 @@ mod tests {
 
         assert_eq!(log.get_from_memory().unwrap(), vec![]);
     }
+
+    #[test]
+    fn test_remote_query_log_recent() {
+        let log = RemoteQueryLog::new();
+
+        log.record_execution(
+            "abc",
+            LoggedQuery::new("SELECT $1", vec!["value=Int32(1) type=int4".into()], None),
+            RemoteQueryExecution {
+                session_id: Some(123),
+                username: Some("app".into()),
+                duration: Some(Duration::from_millis(5)),
+            },
+        )
+        .unwrap();
+        log.record("abc", LoggedQuery::new_query("COMMIT")).unwrap();
+
+        let recent = log.recent().unwrap();
+
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].id, 0);
+        assert_eq!(recent[0].data_source, "abc");
+        assert_eq!(recent[0].session_id, Some(123));
+        assert_eq!(recent[0].username, Some("app".into()));
+        assert_eq!(recent[0].query, "SELECT $1");
+        assert_eq!(recent[0].params, vec!["value=<redacted> type=int4"]);
+        assert_eq!(recent[0].duration_ms, Some(5.0));
+        assert_eq!(recent[1].id, 1);
+        assert_eq!(recent[1].query, "COMMIT");
+        assert_eq!(recent[1].duration_ms, None);
+    }
+
+    #[test]
+    fn test_remote_query_log_recent_is_bounded() {
+        let log = RemoteQueryLog::new();
+
+        for _ in 0..(MAX_RECENT_QUERIES + 5) {
+            log.record("abc", LoggedQuery::new_query("query")).unwrap();
+        }
+
+        let recent = log.recent().unwrap();
+
+        assert_eq!(recent.len(), MAX_RECENT_QUERIES);
+        assert_eq!(recent[0].id, 5);
+    }
+
+    #[test]
+    fn test_redact_param() {
+        assert_eq!(
+            redact_param("value=Int32(1) type=int4"),
+            "value=<redacted> type=int4"
+        );
+        assert_eq!(
+            redact_param(r#"value=Utf8String("a) b\" c") type=text"#),
+            "value=<redacted> type=text"
+        );
+        assert_eq!(
+            redact_param("LoggedParam [index=1, method=setInt, value=1234]"),
+            "LoggedParam [index=1, method=setInt, value=<redacted>]"
+        );
+        assert_eq!(redact_param("value=Null"), "value=<redacted>");
+        assert_eq!(redact_param("(1, Int32(5))"), "<redacted>");
+    }
 }
@@ -0,0 +1,265 @@
+use std::cmp;
+
+use ansilo_connectors_base::{
+    common::data::{DataWriter, ResultSetReader},
+    interface::{ResultSet, RowStructure},
+};
+use ansilo_core::{config::AttributeMaskType, data::DataValue, err::Result};
+use sha2::{Digest, Sha256};
+
+/// Masks the values of the supplied column
+pub fn mask_value(mask: &AttributeMaskType, value: DataValue) -> DataValue {
+    // Only strings can be hashed or partially masked without changing
+    // the type of the column, other types are masked as null
+    let value = match (mask, value) {
+        (_, DataValue::Null) => return DataValue::Null,
+        (AttributeMaskType::Null, _) => return DataValue::Null,
+        (_, DataValue::Utf8String(value)) => value,
+        _ => return DataValue::Null,
+    };
+
+    DataValue::Utf8String(match mask {
+        AttributeMaskType::Null => unreachable!(),
+        AttributeMaskType::Hash => hex::encode(Sha256::digest(value.as_bytes())),
+        AttributeMaskType::Partial { visible } => {
+            let len = value.chars().count();
+            // Short values are masked entirely so they are not revealed
+            let visible = if len > *visible as usize {
+                *visible as usize
+            } else {
+                0
+            };
+
+            value
+                .chars()
+                .enumerate()
+                .map(|(idx, c)| if idx < len - visible { '*' } else { c })
+                .collect()
+        }
+    })
+}
+
+/// Wraps a result set, masking the values of the masked columns
+/// before they are returned to the client
+pub(crate) struct MaskedResultSet<T: ResultSet> {
+    state: MaskState<T>,
+}
+
+enum MaskState<T: ResultSet> {
+    /// No columns are masked so the data is passed through without decoding
+    Unmasked(T),
+    Masked {
+        reader: ResultSetReader<T>,
+        /// The mask of each column in the result set
+        masks: Vec<Option<AttributeMaskType>>,
+        /// Buffer of the masked row data which has not yet been read
+        writer: DataWriter<Vec<u8>>,
+        /// The position of the unread data in the buffer
+        pos: usize,
+    },
+}
+
+impl<T: ResultSet> MaskedResultSet<T> {
+    pub(crate) fn new(inner: T, masks: Vec<Option<AttributeMaskType>>) -> Result<Self> {
+        if masks.iter().all(|m| m.is_none()) {
+            return Ok(Self {
+                state: MaskState::Unmasked(inner),
+            });
+        }
+
+        let reader = ResultSetReader::new(inner)?;
+        let mut masks = masks;
+        masks.resize(reader.get_structure().cols.len(), None);
+
+        Ok(Self {
+            state: MaskState::Masked {
+                reader,
+                masks,
+                writer: DataWriter::new(vec![], None),
+                pos: 0,
+            },
+        })
+    }
+}
+
+impl<T: ResultSet> ResultSet for MaskedResultSet<T> {
+    fn get_structure(&self) -> Result<RowStructure> {
+        match &self.state {
+            MaskState::Unmasked(inner) => inner.get_structure(),
+            MaskState::Masked { reader, .. } => Ok(reader.get_structure().clone()),
+        }
+    }
+
+    fn read(&mut self, buff: &mut [u8]) -> Result<usize> {
+        let (reader, masks, writer, pos) = match &mut self.state {
+            MaskState::Unmasked(inner) => return inner.read(buff),
+            MaskState::Masked {
+                reader,
+                masks,
+                writer,
+                pos,
+            } => (reader, masks, writer, pos),
+        };
+
+        while writer.inner_mut().len() - *pos < buff.len() {
+            let row = match reader.read_row_vec()? {
+                Some(row) => row,
+                None => break,
+            };
+
+            for (value, mask) in row.into_iter().zip(masks.iter()) {
+                writer.write_data_value(match mask {
+                    Some(mask) => mask_value(mask, value),
+                    None => value,
+                })?;
+            }
+        }
+
+        let data = writer.inner_mut();
+        let len = cmp::min(buff.len(), data.len() - *pos);
+        buff[..len].copy_from_slice(&data[*pos..(*pos + len)]);
+        *pos += len;
+
+        if *pos == data.len() {
+            data.clear();
+            *pos = 0;
+        }
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_connectors_base::common::data::DataReader;
+    use ansilo_core::data::DataType;
+
+    use super::*;
+
+    struct MockResultSet(RowStructure, std::io::Cursor<Vec<u8>>);
+
+    impl ResultSet for MockResultSet {
+        fn get_structure(&self) -> Result<RowStructure> {
+            Ok(self.0.clone())
+        }
+
+        fn read(&mut self, buff: &mut [u8]) -> Result<usize> {
+            Ok(std::io::Read::read(&mut self.1, buff)?)
+        }
+    }
+
+    fn mock_result_set(rows: Vec<DataValue>) -> MockResultSet {
+        MockResultSet(
+            RowStructure::new(vec![
+                ("id".into(), DataType::Int32),
+                ("email".into(), DataType::rust_string()),
+            ]),
+            std::io::Cursor::new(DataWriter::to_vec(rows).unwrap()),
+        )
+    }
+
+    #[test]
+    fn test_mask_value_null() {
+        assert_eq!(
+            mask_value(&AttributeMaskType::Null, DataValue::from("abc")),
+            DataValue::Null
+        );
+        assert_eq!(
+            mask_value(&AttributeMaskType::Null, DataValue::Int32(1)),
+            DataValue::Null
+        );
+    }
+
+    #[test]
+    fn test_mask_value_hash() {
+        assert_eq!(
+            mask_value(&AttributeMaskType::Hash, DataValue::from("abc")),
+            DataValue::from("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(
+            mask_value(&AttributeMaskType::Hash, DataValue::Int32(1)),
+            DataValue::Null
+        );
+        assert_eq!(
+            mask_value(&AttributeMaskType::Hash, DataValue::Null),
+            DataValue::Null
+        );
+    }
+
+    #[test]
+    fn test_mask_value_partial() {
+        let mask = AttributeMaskType::Partial { visible: 4 };
+
+        assert_eq!(
+            mask_value(&mask, DataValue::from("4111111111111234")),
+            DataValue::from("************1234")
+        );
+        assert_eq!(
+            mask_value(&mask, DataValue::from("1234")),
+            DataValue::from("****")
+        );
+        assert_eq!(
+            mask_value(
+                &AttributeMaskType::Partial { visible: 0 },
+                DataValue::from("abc")
+            ),
+            DataValue::from("***")
+        );
+    }
+
+    #[test]
+    fn test_masked_result_set_unmasked() {
+        let result_set = MaskedResultSet::new(
+            mock_result_set(vec![DataValue::Int32(1), DataValue::from("a@b.com")]),
+            vec![None, None],
+        )
+        .unwrap();
+
+        assert!(matches!(result_set.state, MaskState::Unmasked(_)));
+
+        let mut reader = result_set.reader().unwrap();
+        assert_eq!(
+            reader.read_row_vec().unwrap(),
+            Some(vec![DataValue::Int32(1), DataValue::from("a@b.com")])
+        );
+        assert_eq!(reader.read_row_vec().unwrap(), None);
+    }
+
+    #[test]
+    fn test_masked_result_set_masks_columns() {
+        let mut result_set = MaskedResultSet::new(
+            mock_result_set(vec![
+                DataValue::Int32(1),
+                DataValue::from("a@b.com"),
+                DataValue::Int32(2),
+                DataValue::Null,
+            ]),
+            vec![None, Some(AttributeMaskType::Partial { visible: 3 })],
+        )
+        .unwrap();
+
+        // Read in small chunks to exercise the buffering
+        let mut data = vec![];
+        let mut buff = [0u8; 3];
+        loop {
+            let len = result_set.read(&mut buff).unwrap();
+            if len == 0 {
+                break;
+            }
+            data.extend_from_slice(&buff[..len]);
+        }
+
+        let mut reader = DataReader::new(
+            std::io::Cursor::new(data),
+            vec![DataType::Int32, DataType::rust_string()],
+        );
+        assert_eq!(reader.read_data_value().unwrap(), Some(DataValue::Int32(1)));
+        assert_eq!(
+            reader.read_data_value().unwrap(),
+            Some(DataValue::from("****com"))
+        );
+        assert_eq!(reader.read_data_value().unwrap(), Some(DataValue::Int32(2)));
+        assert_eq!(reader.read_data_value().unwrap(), Some(DataValue::Null));
+        assert_eq!(reader.read_data_value().unwrap(), None);
+    }
+}
@@ -9,6 +9,17 @@ pub mod bincode;
 pub mod connection;
 pub mod data;
 pub mod log;
+pub mod cache;
+pub mod fetch;
+pub mod columnar;
+pub mod compression;
+pub mod prepared;
+pub mod stats;
+pub mod mask;
+pub mod limit;
+pub mod admission;
+pub mod scan_cache;
+pub mod snapshot;
 
 #[cfg(test)]
 mod test;
\ No newline at end of file
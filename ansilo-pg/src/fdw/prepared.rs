@@ -0,0 +1,355 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+use ansilo_connectors_base::interface::QueryInputStructure;
+use ansilo_core::err::{bail, Result};
+use ansilo_logging::debug;
+
+use super::{channel::IpcServerChannel, proto::AuthDataSource};
+
+/// Caches prepared queries of a single connection to a data source so they can
+/// be reused by later queries with the same compiled SQL and parameter types.
+///
+/// Once the limit is reached the least recently used query is evicted.
+pub(crate) struct PreparedQueryCache<T> {
+    /// The maximum number of cached queries
+    max_queries: usize,
+    /// The cached queries keyed by the compiled query
+    queries: HashMap<String, CachedQuery<T>>,
+    /// Counter used to track the recency of each query
+    counter: u64,
+}
+
+struct CachedQuery<T> {
+    handle: T,
+    structure: QueryInputStructure,
+    last_used: u64,
+}
+
+impl<T> PreparedQueryCache<T> {
+    pub(crate) fn new(max_queries: usize) -> Self {
+        Self {
+            max_queries,
+            queries: HashMap::new(),
+            counter: 0,
+        }
+    }
+
+    /// Whether caching is enabled
+    pub(crate) fn enabled(&self) -> bool {
+        self.max_queries > 0
+    }
+
+    /// Gets the number of cached queries
+    pub(crate) fn len(&self) -> usize {
+        self.queries.len()
+    }
+
+    /// Removes the prepared query with the supplied key from the cache.
+    /// The query is returned to the cache using [`PreparedQueryCache::put`] once
+    /// it is no longer in use.
+    pub(crate) fn take(&mut self, key: &str) -> Option<(T, QueryInputStructure)> {
+        self.queries
+            .remove(key)
+            .map(|cached| (cached.handle, cached.structure))
+    }
+
+    /// Adds the prepared query to the cache, evicting the
+    /// least recently used query if the cache is full
+    pub(crate) fn put(&mut self, key: String, handle: T, structure: QueryInputStructure) {
+        if !self.enabled() {
+            return;
+        }
+
+        if !self.queries.contains_key(&key) && self.queries.len() >= self.max_queries {
+            let lru = self
+                .queries
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(key, _)| key.clone());
+
+            if let Some(lru) = lru {
+                debug!("Evicting prepared query from cache");
+                self.queries.remove(&lru);
+            }
+        }
+
+        self.counter += 1;
+        self.queries.insert(
+            key,
+            CachedQuery {
+                handle,
+                structure,
+                last_used: self.counter,
+            },
+        );
+    }
+}
+
+/// Tracks the idle FDW connections which are waiting to be reused by a new session.
+///
+/// When a session closes, a connection with cached prepared queries parks its
+/// thread here rather than closing. New sessions which authenticate to the same
+/// data source with the same credentials are handed to a parked connection so
+/// its prepared queries and connector connection are reused.
+#[derive(Clone, Default)]
+pub struct IdleConnections {
+    /// The parked connections keyed by their authentication, without the session
+    idle: Arc<Mutex<HashMap<AuthDataSource, Vec<(u64, mpsc::Sender<IdleSession>)>>>>,
+    /// Counter used to identify each parked connection
+    counter: Arc<AtomicU64>,
+}
+
+/// A new session dispatched to an idle connection, with the id of the session
+type IdleSession = (Option<u32>, IpcServerChannel);
+
+impl IdleConnections {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hands the channel of a new session to an idle connection authenticated
+    /// with the supplied credentials. If there is no idle connection the channel
+    /// is returned so it can be processed by a new connection.
+    pub(crate) fn dispatch(
+        &self,
+        auth: &AuthDataSource,
+        chan: IpcServerChannel,
+    ) -> Result<Option<IpcServerChannel>> {
+        let key = auth.without_session();
+        let mut idle = self.lock()?;
+        let mut session = (auth.session_id, chan);
+
+        while let Some((_, sender)) = idle.get_mut(&key).and_then(|i| i.pop()) {
+            // If the connection has since terminated we try the next one
+            match sender.send(session) {
+                Ok(_) => return Ok(None),
+                Err(mpsc::SendError(returned)) => session = returned,
+            }
+        }
+
+        idle.remove(&key);
+        Ok(Some(session.1))
+    }
+
+    /// Parks the current thread until a new session is dispatched to it,
+    /// returning `None` if the timeout elapses or the idle connections are invalidated.
+    pub(crate) fn park(
+        &self,
+        auth: &AuthDataSource,
+        timeout: Duration,
+    ) -> Result<Option<IdleSession>> {
+        let key = auth.without_session();
+        let id = self.counter.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = mpsc::channel();
+
+        self.lock()?
+            .entry(key.clone())
+            .or_default()
+            .push((id, sender));
+
+        match receiver.recv_timeout(timeout) {
+            Ok(session) => return Ok(Some(session)),
+            Err(RecvTimeoutError::Disconnected) => return Ok(None),
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        // If we are still registered we can safely exit, otherwise a session
+        // has been dispatched to us concurrently and must be processed
+        {
+            let mut idle = self.lock()?;
+            if let Some(parked) = idle.get_mut(&key) {
+                if let Some(idx) = parked.iter().position(|(i, _)| *i == id) {
+                    parked.remove(idx);
+                    return Ok(None);
+                }
+            }
+        }
+
+        Ok(receiver.recv().ok())
+    }
+
+    /// Gets the number of idle connections
+    pub fn len(&self) -> Result<usize> {
+        Ok(self.lock()?.values().map(|i| i.len()).sum())
+    }
+
+    /// Terminates the idle connections of the supplied data source, or all
+    /// data sources if `None` is supplied
+    pub fn invalidate(&self, data_source_id: Option<&str>) -> Result<()> {
+        let mut idle = self.lock()?;
+
+        match data_source_id {
+            Some(id) => idle.retain(|auth, _| auth.data_source_id != id),
+            None => idle.clear(),
+        }
+
+        Ok(())
+    }
+
+    fn lock(
+        &self,
+    ) -> Result<MutexGuard<HashMap<AuthDataSource, Vec<(u64, mpsc::Sender<IdleSession>)>>>> {
+        match self.idle.lock() {
+            Ok(idle) => Ok(idle),
+            Err(_) => bail!("Failed to lock idle connections"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::net::UnixStream, thread};
+
+    use ansilo_core::data::DataType;
+
+    use super::*;
+
+    fn structure() -> QueryInputStructure {
+        QueryInputStructure::new(vec![(1, DataType::Int32)])
+    }
+
+    fn mock_channel() -> IpcServerChannel {
+        let (sock, _) = UnixStream::pair().unwrap();
+        IpcServerChannel::new(sock)
+    }
+
+    #[test]
+    fn test_prepared_query_cache_take_and_put() {
+        let mut cache = PreparedQueryCache::<u32>::new(10);
+
+        assert!(cache.take("query").is_none());
+
+        cache.put("query".into(), 1, structure());
+        assert_eq!(cache.len(), 1);
+
+        assert_eq!(cache.take("query"), Some((1, structure())));
+        assert_eq!(cache.len(), 0);
+        assert!(cache.take("query").is_none());
+    }
+
+    #[test]
+    fn test_prepared_query_cache_evicts_least_recently_used() {
+        let mut cache = PreparedQueryCache::<u32>::new(2);
+
+        cache.put("a".into(), 1, structure());
+        cache.put("b".into(), 2, structure());
+
+        // Using "a" makes "b" the least recently used
+        let (handle, input) = cache.take("a").unwrap();
+        cache.put("a".into(), handle, input);
+
+        cache.put("c".into(), 3, structure());
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.take("b").is_none());
+        assert!(cache.take("a").is_some());
+        assert!(cache.take("c").is_some());
+    }
+
+    #[test]
+    fn test_prepared_query_cache_disabled() {
+        let mut cache = PreparedQueryCache::<u32>::new(0);
+
+        cache.put("query".into(), 1, structure());
+
+        assert!(!cache.enabled());
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_idle_connections_dispatch_without_idle() {
+        let idle = IdleConnections::new();
+        let auth = AuthDataSource::new(None, "source");
+
+        assert!(idle.dispatch(&auth, mock_channel()).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_idle_connections_park_timeout() {
+        let idle = IdleConnections::new();
+        let auth = AuthDataSource::new(None, "source");
+
+        assert!(idle
+            .park(&auth, Duration::from_millis(10))
+            .unwrap()
+            .is_none());
+        assert_eq!(idle.len().unwrap(), 0);
+        assert!(idle.dispatch(&auth, mock_channel()).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_idle_connections_dispatch_to_parked() {
+        let idle = IdleConnections::new();
+        let auth = AuthDataSource::new(None, "source");
+
+        let parked = {
+            let idle = idle.clone();
+            let auth = auth.clone();
+            thread::spawn(move || idle.park(&auth, Duration::from_secs(10)).unwrap())
+        };
+
+        while idle.len().unwrap() == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // Different credentials must not be dispatched to the parked connection
+        let other = AuthDataSource::new(None, "other");
+        assert!(idle.dispatch(&other, mock_channel()).unwrap().is_some());
+
+        assert!(idle.dispatch(&auth, mock_channel()).unwrap().is_none());
+        assert!(parked.join().unwrap().is_some());
+        assert_eq!(idle.len().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_idle_connections_dispatch_new_session() {
+        let idle = IdleConnections::new();
+        let auth = AuthDataSource::new(None, "source").with_session(123);
+
+        let parked = {
+            let idle = idle.clone();
+            let auth = auth.clone();
+            thread::spawn(move || idle.park(&auth, Duration::from_secs(10)).unwrap())
+        };
+
+        while idle.len().unwrap() == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // Sessions with the same credentials share the idle connections
+        let next = AuthDataSource::new(None, "source").with_session(456);
+        assert!(idle.dispatch(&next, mock_channel()).unwrap().is_none());
+
+        let (session, _) = parked.join().unwrap().unwrap();
+        assert_eq!(session, Some(456));
+    }
+
+    #[test]
+    fn test_idle_connections_invalidate() {
+        let idle = IdleConnections::new();
+        let auth = AuthDataSource::new(None, "source");
+
+        let parked = {
+            let idle = idle.clone();
+            let auth = auth.clone();
+            thread::spawn(move || idle.park(&auth, Duration::from_secs(10)).unwrap())
+        };
+
+        while idle.len().unwrap() == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        idle.invalidate(Some("source")).unwrap();
+
+        assert!(parked.join().unwrap().is_none());
+        assert_eq!(idle.len().unwrap(), 0);
+    }
+}
@@ -6,7 +6,7 @@ pub use ansilo_connectors_base::interface::{
 
 use ansilo_core::{
     auth::AuthContext,
-    config::EntityConfig,
+    config::{EntityConfig, TransferCompression},
     data::DataType,
     sqlil::{self, EntityId},
 };
@@ -29,8 +29,11 @@ pub enum ClientMessage {
     GetRowIds(sqlil::EntitySource),
     /// Creates a new query
     CreateQuery(sqlil::EntitySource, sqlil::QueryType),
-    /// Creates a new query from a string
-    CreateStringQuery(String, Vec<sqlil::Parameter>),
+    /// Creates a new query from a string, flagged as to whether it is
+    /// executed as a modification (remote_execute) or not (remote_query)
+    CreateStringQuery(String, Vec<sqlil::Parameter>, bool),
+    /// Checks the query being planned against the query rules of the user
+    CheckQueryRules(QueryRuleCheck),
     /// Performs an action on the the specified query
     Query(QueryId, ClientQueryMessage),
     /// Begins a transaction on the remote connection
@@ -74,6 +77,12 @@ pub enum ClientQueryMessage {
     AddToBatch,
     /// Read up to the supplied number of bytes from result set
     Read(u32),
+    /// Read up to the supplied number of rows from the result set encoded as an arrow IPC stream.
+    /// The result set of a query must be read using either Read or ReadColumnar, not both.
+    ReadColumnar(u32),
+    /// Requests result data of the query to be compressed using the supplied algorithm.
+    /// Only batches above a minimum size are compressed.
+    SetCompression(TransferCompression),
     /// Discard the current result set and ready the query for new params and execution
     Restart,
     /// Copies the state of the query to a new query
@@ -83,7 +92,7 @@ pub enum ClientQueryMessage {
 }
 
 /// Message sent by the client to initialise the connection
-#[derive(Debug, PartialEq, Clone, Encode, Decode)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Encode, Decode)]
 pub struct AuthDataSource {
     /// The authentication context
     ///
@@ -92,6 +101,8 @@ pub struct AuthDataSource {
     context: Option<String>,
     /// The data source id
     pub data_source_id: String,
+    /// The pid of the postgres backend of the session, used to attribute remote queries
+    pub session_id: Option<u32>,
 }
 
 impl AuthDataSource {
@@ -99,6 +110,21 @@ impl AuthDataSource {
         Self {
             context: context.map(|c| serde_json::to_string(&c).unwrap()),
             data_source_id: data_source_id.into(),
+            session_id: None,
+        }
+    }
+
+    pub fn with_session(mut self, session_id: u32) -> Self {
+        self.session_id = Some(session_id);
+        self
+    }
+
+    /// Gets the credentials without the session, identifying the connections
+    /// which may be shared between sessions
+    pub fn without_session(&self) -> Self {
+        Self {
+            session_id: None,
+            ..self.clone()
         }
     }
 
@@ -109,11 +135,31 @@ impl AuthDataSource {
     }
 }
 
+/// The properties of a query being planned which are checked against the query rules
+#[derive(Debug, PartialEq, Clone, Encode, Decode)]
+pub struct QueryRuleCheck {
+    /// The entity being scanned by the query
+    pub entity: EntityId,
+    /// Whether the scan of the entity is restricted by any conditions
+    pub has_conditions: bool,
+    /// The ids of the data sources of all foreign tables referenced by the query
+    pub data_sources: Vec<String>,
+}
+
+/// Options of the authenticated session which override those of the data source
+#[derive(Debug, PartialEq, Clone, Default, Encode, Decode)]
+pub struct SessionOptions {
+    /// The minimum number of rows per batch for the workload class of the user
+    pub fetch_min_rows: Option<u32>,
+    /// The maximum number of rows per batch for the workload class of the user
+    pub fetch_max_rows: Option<u32>,
+}
+
 /// Protocol responses sent by ansilo
 #[derive(Debug, PartialEq, Clone, Encode, Decode)]
 pub enum ServerMessage {
-    /// Token was accepted
-    AuthAccepted,
+    /// Token was accepted, with the options of the authenticated session
+    AuthAccepted(SessionOptions),
     /// Entities discovered from the data source
     DiscoveredEntitiesResult(Vec<EntityConfig>),
     /// The supplied entity was registered
@@ -124,6 +170,8 @@ pub enum ServerMessage {
     RowIds(Vec<(sqlil::Expr, DataType)>),
     /// The base query was created
     QueryCreated(QueryId, OperationCost),
+    /// The query being planned is permitted by the query rules
+    QueryRulesPassed,
     /// The responses from operations on a specific query
     Query(ServerQueryMessage),
     /// Transactions not supported against this data source
@@ -167,6 +215,15 @@ pub enum ServerQueryMessage {
     /// Rows returned by the query
     /// TODO[maybe]: Write this to a shared-memory segment to avoid copying
     ReadData(Vec<u8>),
+    /// Rows returned by the query encoded as an arrow IPC stream,
+    /// empty once all rows have been read
+    ColumnarData(Vec<u8>),
+    /// Rows returned by the query, equivalent to ReadData, compressed using the supplied algorithm
+    CompressedReadData(TransferCompression, Vec<u8>),
+    /// Rows returned by the query, equivalent to ColumnarData, compressed using the supplied algorithm
+    CompressedColumnarData(TransferCompression, Vec<u8>),
+    /// The compression of the result data was set
+    CompressionSet,
     /// Query restarted
     Restarted,
     /// Query duplicated
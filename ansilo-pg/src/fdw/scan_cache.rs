@@ -0,0 +1,346 @@
+use std::{
+    cmp,
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use ansilo_connectors_base::interface::{ResultSet, RowStructure};
+use ansilo_core::err::{bail, Result};
+use ansilo_logging::debug;
+
+/// The maximum size of the result data of a single scan which is cached,
+/// larger results are read from the data source on each scan
+pub const MAX_CACHED_SCAN_BYTES: usize = 16 * 1024 * 1024;
+
+/// Caches the results of scans of the entities which are configured with a cache.
+///
+/// Repeat scans with the same query and parameters are served from the cache
+/// until the entry expires, avoiding the round-trip to the data source.
+/// Entries are invalidated when the entities they read are written through Ansilo.
+#[derive(Clone, Default)]
+pub struct ScanCache {
+    /// The cached scans keyed by the data source id
+    sources: Arc<Mutex<HashMap<String, HashMap<String, CachedScan>>>>,
+}
+
+/// The results of a single scan
+struct CachedScan {
+    /// The ids of the entities read by the scan
+    entities: Vec<String>,
+    /// The structure of the result rows
+    structure: RowStructure,
+    /// The encoded result data
+    data: Arc<Vec<u8>>,
+    expires_at: Instant,
+}
+
+impl ScanCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the results of the scan with the supplied key, if cached and not expired
+    pub(crate) fn get(
+        &self,
+        data_source_id: &str,
+        key: &str,
+    ) -> Result<Option<(RowStructure, Arc<Vec<u8>>)>> {
+        let mut sources = self.lock()?;
+        let scans = match sources.get_mut(data_source_id) {
+            Some(scans) => scans,
+            None => return Ok(None),
+        };
+
+        match scans.get(key) {
+            Some(scan) if Instant::now() < scan.expires_at => {
+                Ok(Some((scan.structure.clone(), Arc::clone(&scan.data))))
+            }
+            Some(_) => {
+                scans.remove(key);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Stores the results of a scan
+    pub(crate) fn put(
+        &self,
+        data_source_id: &str,
+        key: String,
+        entities: Vec<String>,
+        structure: RowStructure,
+        data: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<()> {
+        let mut sources = self.lock()?;
+        let scans = sources.entry(data_source_id.into()).or_default();
+
+        // Remove expired entries as new scans are stored
+        let now = Instant::now();
+        scans.retain(|_, scan| now < scan.expires_at);
+
+        scans.insert(
+            key,
+            CachedScan {
+                entities,
+                structure,
+                data: Arc::new(data),
+                expires_at: now + ttl,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Removes the cached scans which read the supplied entity
+    pub(crate) fn invalidate(&self, data_source_id: &str, entity_id: &str) -> Result<()> {
+        let mut sources = self.lock()?;
+
+        if let Some(scans) = sources.get_mut(data_source_id) {
+            let before = scans.len();
+            scans.retain(|_, scan| !scan.entities.iter().any(|e| e == entity_id));
+
+            if scans.len() != before {
+                debug!(
+                    "Invalidated {} cached scan(s) of entity '{}' on {}",
+                    before - scans.len(),
+                    entity_id,
+                    data_source_id
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<MutexGuard<HashMap<String, HashMap<String, CachedScan>>>> {
+        match self.sources.lock() {
+            Ok(sources) => Ok(sources),
+            Err(_) => bail!("Failed to lock scan cache"),
+        }
+    }
+}
+
+/// A scan which is stored in the cache once its results have been fully read
+pub(crate) struct ScanRecording {
+    pub cache: ScanCache,
+    pub data_source_id: String,
+    pub key: String,
+    pub entities: Vec<String>,
+    pub ttl: Duration,
+}
+
+/// A result set which is either read from the data source, recording the
+/// result data to be cached, or served from a previously cached scan
+pub(crate) enum CachedResultSet<T: ResultSet> {
+    Remote {
+        inner: T,
+        /// The scan and the result data read so far, if it is to be cached
+        recording: Option<(ScanRecording, Vec<u8>)>,
+    },
+    Cached {
+        structure: RowStructure,
+        data: Arc<Vec<u8>>,
+        /// The position of the unread data
+        pos: usize,
+    },
+}
+
+impl<T: ResultSet> CachedResultSet<T> {
+    /// Reads the results from the data source, caching them if a recording is supplied
+    pub(crate) fn remote(inner: T, recording: Option<ScanRecording>) -> Self {
+        Self::Remote {
+            inner,
+            recording: recording.map(|r| (r, vec![])),
+        }
+    }
+
+    /// Serves the results of a cached scan
+    pub(crate) fn cached(structure: RowStructure, data: Arc<Vec<u8>>) -> Self {
+        Self::Cached {
+            structure,
+            data,
+            pos: 0,
+        }
+    }
+}
+
+impl<T: ResultSet> ResultSet for CachedResultSet<T> {
+    fn get_structure(&self) -> Result<RowStructure> {
+        match self {
+            Self::Remote { inner, .. } => inner.get_structure(),
+            Self::Cached { structure, .. } => Ok(structure.clone()),
+        }
+    }
+
+    fn read(&mut self, buff: &mut [u8]) -> Result<usize> {
+        let (inner, recording) = match self {
+            Self::Remote { inner, recording } => (inner, recording),
+            Self::Cached { data, pos, .. } => {
+                let len = cmp::min(buff.len(), data.len() - *pos);
+                buff[..len].copy_from_slice(&data[*pos..(*pos + len)]);
+                *pos += len;
+                return Ok(len);
+            }
+        };
+
+        let len = inner.read(buff)?;
+
+        if len == 0 {
+            // The results have been fully read so the scan is stored in the cache
+            if let Some((scan, data)) = recording.take() {
+                scan.cache.put(
+                    &scan.data_source_id,
+                    scan.key,
+                    scan.entities,
+                    inner.get_structure()?,
+                    data,
+                    scan.ttl,
+                )?;
+            }
+        } else if let Some((_, data)) = recording.as_mut() {
+            data.extend_from_slice(&buff[..len]);
+
+            if data.len() > MAX_CACHED_SCAN_BYTES {
+                debug!("Scan results exceed the maximum cached size, not caching");
+                *recording = None;
+            }
+        }
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_connectors_base::common::data::DataWriter;
+    use ansilo_core::data::{DataType, DataValue};
+
+    use super::*;
+
+    struct MockResultSet(RowStructure, std::io::Cursor<Vec<u8>>);
+
+    impl ResultSet for MockResultSet {
+        fn get_structure(&self) -> Result<RowStructure> {
+            Ok(self.0.clone())
+        }
+
+        fn read(&mut self, buff: &mut [u8]) -> Result<usize> {
+            Ok(std::io::Read::read(&mut self.1, buff)?)
+        }
+    }
+
+    fn mock_result_set(rows: Vec<i32>) -> MockResultSet {
+        MockResultSet(
+            RowStructure::new(vec![("id".into(), DataType::Int32)]),
+            std::io::Cursor::new(
+                DataWriter::to_vec(rows.into_iter().map(DataValue::Int32).collect()).unwrap(),
+            ),
+        )
+    }
+
+    fn recording(cache: &ScanCache, ttl: Duration) -> ScanRecording {
+        ScanRecording {
+            cache: cache.clone(),
+            data_source_id: "source".into(),
+            key: "key".into(),
+            entities: vec!["people".into()],
+            ttl,
+        }
+    }
+
+    fn read_rows<T: ResultSet>(result_set: T) -> Vec<DataValue> {
+        let mut reader = result_set.reader().unwrap();
+        let mut rows = vec![];
+
+        while let Some(row) = reader.read_row_vec().unwrap() {
+            rows.extend(row);
+        }
+
+        rows
+    }
+
+    #[test]
+    fn test_scan_cache_empty() {
+        let cache = ScanCache::new();
+
+        assert!(cache.get("source", "key").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_scan_cache_stores_results_once_fully_read() {
+        let cache = ScanCache::new();
+        let result_set = CachedResultSet::remote(
+            mock_result_set(vec![1, 2, 3]),
+            Some(recording(&cache, Duration::from_secs(60))),
+        );
+
+        assert_eq!(
+            read_rows(result_set),
+            vec![
+                DataValue::Int32(1),
+                DataValue::Int32(2),
+                DataValue::Int32(3)
+            ]
+        );
+
+        let (structure, data) = cache.get("source", "key").unwrap().unwrap();
+        assert_eq!(
+            read_rows(CachedResultSet::<MockResultSet>::cached(structure, data)),
+            vec![
+                DataValue::Int32(1),
+                DataValue::Int32(2),
+                DataValue::Int32(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_cache_partially_read_results_not_stored() {
+        let cache = ScanCache::new();
+        let mut result_set = CachedResultSet::remote(
+            mock_result_set(vec![1, 2, 3]),
+            Some(recording(&cache, Duration::from_secs(60))),
+        );
+
+        let mut buff = [0u8; 2];
+        result_set.read(&mut buff).unwrap();
+        drop(result_set);
+
+        assert!(cache.get("source", "key").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_scan_cache_expiry() {
+        let cache = ScanCache::new();
+        let result_set = CachedResultSet::remote(
+            mock_result_set(vec![1]),
+            Some(recording(&cache, Duration::from_millis(10))),
+        );
+        read_rows(result_set);
+
+        assert!(cache.get("source", "key").unwrap().is_some());
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(cache.get("source", "key").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_scan_cache_invalidate() {
+        let cache = ScanCache::new();
+        read_rows(CachedResultSet::remote(
+            mock_result_set(vec![1]),
+            Some(recording(&cache, Duration::from_secs(60))),
+        ));
+
+        cache.invalidate("source", "other").unwrap();
+        assert!(cache.get("source", "key").unwrap().is_some());
+
+        cache.invalidate("other", "people").unwrap();
+        assert!(cache.get("source", "key").unwrap().is_some());
+
+        cache.invalidate("source", "people").unwrap();
+        assert!(cache.get("source", "key").unwrap().is_none());
+    }
+}
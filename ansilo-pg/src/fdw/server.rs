@@ -14,30 +14,68 @@ use ansilo_connectors_all::*;
 use ansilo_connectors_base::{common::entity::ConnectorEntityConfig, interface::Connector};
 use ansilo_core::{
     config::NodeConfig,
-    err::{bail, Context, Result},
+    err::{bail, Context, Error, Result},
 };
 use ansilo_logging::{error, warn};
 
 use super::{
+    admission::AdmissionControl,
+    cache::MetadataCache,
     channel::IpcServerChannel,
     connection::FdwConnection,
     log::RemoteQueryLog,
-    proto::{AuthDataSource, ClientMessage, ServerMessage},
+    prepared::IdleConnections,
+    proto::{AuthDataSource, ClientMessage, ServerMessage, SessionOptions},
+    scan_cache::ScanCache,
+    snapshot::Snapshots,
+    stats::StatisticsCollector,
 };
 
+/// The connection pools and entity config keyed by their data source id.
+///
+/// We wrap each list of entities in a RW lock as these may be
+/// added to when new entities are registered from a connection.
+/// The map itself is locked so pools can be replaced on config reload.
+pub(crate) type SharedPools =
+    Arc<RwLock<HashMap<String, (ConnectionPools, Arc<RwLockEntityConfigs>)>>>;
+
+/// The node configuration, which is locked so it can be replaced
+/// when entities or access rules are rebuilt in place.
+pub(crate) type SharedNodeConfig = Arc<RwLock<Arc<NodeConfig>>>;
+
+/// The snapshots of the materialized entities, which are locked
+/// so they can be supplied once the server has started.
+pub(crate) type SharedSnapshots = Arc<RwLock<Option<Snapshots>>>;
+
 /// Handles connections back from postgres
 pub struct FdwServer {
-    /// Global node configuration
-    #[allow(unused)]
-    nc: &'static NodeConfig,
+    /// Global node configuration shared with the listener
+    nc: SharedNodeConfig,
     /// The path of the socket which the server is listening on
     path: PathBuf,
+    /// The connection pools shared with the listener
+    pools: SharedPools,
+    /// The cache of remote metadata shared with the listener
+    cache: MetadataCache,
+    /// The idle connections which are waiting to be reused
+    idle: IdleConnections,
+    /// The snapshots of the materialized entities shared with the listener
+    snapshots: SharedSnapshots,
     /// Listener thread
     thread: Option<JoinHandle<()>>,
     /// Whether the server is terminated
     terminated: Arc<AtomicBool>,
 }
 
+/// A cloneable handle to the shared state of the [`FdwServer`]
+#[derive(Clone)]
+pub struct FdwHandle {
+    nc: SharedNodeConfig,
+    pools: SharedPools,
+    cache: MetadataCache,
+    idle: IdleConnections,
+}
+
 impl FdwServer {
     /// Starts a new server instance listening at the specified path
     pub fn start(
@@ -46,11 +84,41 @@ impl FdwServer {
         pools: HashMap<String, (ConnectionPools, ConnectorEntityConfigs)>,
         log: RemoteQueryLog,
     ) -> Result<Self> {
-        let (thread, terminated) = Self::start_listening_thread(nc, path.as_path(), pools, log)?;
+        let pools: SharedPools = Arc::new(RwLock::new(
+            pools
+                .into_iter()
+                .map(|(k, (p, e))| (k, (p, Arc::new(e.into()))))
+                .collect(),
+        ));
+        let cache = MetadataCache::new();
+        let idle = IdleConnections::new();
+        let snapshots: SharedSnapshots = Arc::new(RwLock::new(None));
+        let shared_nc: SharedNodeConfig = Arc::new(RwLock::new(Arc::new(nc.clone())));
+        let (thread, terminated) = Self::start_listening_thread(
+            Arc::clone(&shared_nc),
+            path.as_path(),
+            Arc::clone(&pools),
+            log,
+            cache.clone(),
+            AdmissionControl::new(),
+            ScanCache::new(),
+            idle.clone(),
+            Arc::clone(&snapshots),
+        )?;
+        StatisticsCollector::start(
+            nc,
+            Arc::clone(&pools),
+            cache.clone(),
+            Arc::clone(&terminated),
+        );
 
         Ok(Self {
-            nc,
+            nc: shared_nc,
             path,
+            pools,
+            cache,
+            idle,
+            snapshots,
             thread: Some(thread),
             terminated,
         })
@@ -61,6 +129,72 @@ impl FdwServer {
         self.path.as_path()
     }
 
+    /// Gets the cache of remote metadata
+    pub fn metadata_cache(&self) -> &MetadataCache {
+        &self.cache
+    }
+
+    /// Replaces the connection pool of the supplied data source.
+    ///
+    /// New connections will use the replaced pool while existing
+    /// connections continue to use the previous pool until they are closed.
+    /// Any cached metadata and idle connections of the data source are invalidated.
+    pub fn replace_pool(&self, data_source_id: &str, pool: ConnectionPools) -> Result<()> {
+        let mut pools = self
+            .pools
+            .write()
+            .map_err(|_| Error::msg("Failed to lock connection pools"))?;
+
+        let (current, _) = pools
+            .get_mut(data_source_id)
+            .with_context(|| format!("Failed to find data source with id: {}", data_source_id))?;
+        *current = pool;
+
+        self.cache.invalidate(Some(data_source_id))?;
+        self.idle.invalidate(Some(data_source_id))?;
+
+        Ok(())
+    }
+
+    /// Serves the scans of the materialized entities from the supplied snapshots.
+    /// Idle connections are invalidated so they are not reused.
+    pub fn replace_snapshots(&self, snapshots: Snapshots) -> Result<()> {
+        let mut current = self
+            .snapshots
+            .write()
+            .map_err(|_| Error::msg("Failed to lock snapshots"))?;
+        *current = Some(snapshots);
+
+        self.idle.invalidate(None)?;
+
+        Ok(())
+    }
+
+    /// Replaces the entity configs of the supplied data source, see [`FdwHandle::replace_entities`]
+    pub fn replace_entities(
+        &self,
+        data_source_id: &str,
+        entities: ConnectorEntityConfigs,
+    ) -> Result<()> {
+        self.handle().replace_entities(data_source_id, entities)
+    }
+
+    /// Replaces the node configuration used by new connections, see [`FdwHandle::replace_config`]
+    pub fn replace_config(&self, nc: Arc<NodeConfig>) -> Result<()> {
+        self.handle().replace_config(nc)
+    }
+
+    /// Gets a handle which can replace the entities and configuration of the
+    /// server from other threads
+    pub fn handle(&self) -> FdwHandle {
+        FdwHandle {
+            nc: Arc::clone(&self.nc),
+            pools: Arc::clone(&self.pools),
+            cache: self.cache.clone(),
+            idle: self.idle.clone(),
+        }
+    }
+
     /// Waits for the listener thread complete
     pub fn wait(&mut self) -> Result<()> {
         if let Err(_) = self.thread.take().unwrap().join() {
@@ -90,6 +224,10 @@ impl FdwServer {
 
         self.terminated.store(true, Ordering::SeqCst);
 
+        if let Err(err) = self.idle.invalidate(None) {
+            warn!("Failed to terminate idle connections: {:?}", err);
+        }
+
         // Run a throw-away thread to trigger a bind to the unix socket
         // in order to trigger its shutdown
         {
@@ -108,10 +246,15 @@ impl FdwServer {
     }
 
     fn start_listening_thread(
-        nc: &'static NodeConfig,
+        nc: SharedNodeConfig,
         path: &Path,
-        pools: HashMap<String, (ConnectionPools, ConnectorEntityConfigs)>,
+        pools: SharedPools,
         log: RemoteQueryLog,
+        cache: MetadataCache,
+        admission: AdmissionControl,
+        scan_cache: ScanCache,
+        idle: IdleConnections,
+        snapshots: SharedSnapshots,
     ) -> Result<(JoinHandle<()>, Arc<AtomicBool>)> {
         let terminated = Arc::new(AtomicBool::new(false));
 
@@ -124,7 +267,11 @@ impl FdwServer {
             let terminated = Arc::clone(&terminated);
 
             thread::spawn(move || {
-                let res = FdwListener::bind(nc, listener, pools, terminated, log).listen();
+                let res = FdwListener::bind(
+                    nc, listener, pools, terminated, log, cache, admission, scan_cache, idle,
+                    snapshots,
+                )
+                .listen();
 
                 if let Err(err) = res {
                     error!("FDW listener error: {}", err);
@@ -139,40 +286,52 @@ impl FdwServer {
 /// Handles connections from postgres, serving data from a connector
 pub struct FdwListener {
     /// Global node configuration
-    nc: &'static NodeConfig,
+    nc: SharedNodeConfig,
     /// The unix socket the server listens on
     listener: UnixListener,
     /// The connection pools and entity config keyed by their data source id.
-    ///
-    /// We wrap each list of entities in a RW lock as these may be
-    /// added to when new entities are registered from a connection.
-    pools: Arc<HashMap<String, (ConnectionPools, Arc<RwLockEntityConfigs>)>>,
+    pools: SharedPools,
     /// Whether the server is terminated
     terminated: Arc<AtomicBool>,
     /// Remote query log
     log: RemoteQueryLog,
+    /// Cache of remote metadata
+    cache: MetadataCache,
+    /// Limits on the sessions concurrently scanning each data source
+    admission: AdmissionControl,
+    /// Cache of the results of scans of the cached entities
+    scan_cache: ScanCache,
+    /// The idle connections which are waiting to be reused
+    idle: IdleConnections,
+    /// The snapshots of the materialized entities
+    snapshots: SharedSnapshots,
 }
 
 impl FdwListener {
     /// Starts a server which listens
     pub fn bind(
-        nc: &'static NodeConfig,
+        nc: SharedNodeConfig,
         listener: UnixListener,
-        pools: HashMap<String, (ConnectionPools, ConnectorEntityConfigs)>,
+        pools: SharedPools,
         terminated: Arc<AtomicBool>,
         log: RemoteQueryLog,
+        cache: MetadataCache,
+        admission: AdmissionControl,
+        scan_cache: ScanCache,
+        idle: IdleConnections,
+        snapshots: SharedSnapshots,
     ) -> Self {
         Self {
             nc,
             listener,
-            pools: Arc::new(
-                pools
-                    .into_iter()
-                    .map(|(k, (p, e))| (k, (p, Arc::new(e.into()))))
-                    .collect(),
-            ),
+            pools,
             terminated,
             log,
+            cache,
+            admission,
+            scan_cache,
+            idle,
+            snapshots,
         }
     }
 
@@ -192,13 +351,27 @@ impl FdwListener {
     /// Starts the thread responsible for processing the supplied connection
     fn start(&self, socket: UnixStream) -> Result<()> {
         let pool = Arc::clone(&self.pools);
-        let nc = self.nc;
+        let nc = Arc::clone(
+            &*self
+                .nc
+                .read()
+                .map_err(|_| Error::msg("Failed to lock node config"))?,
+        );
         let log = self.log.clone();
+        let cache = self.cache.clone();
+        let admission = self.admission.clone();
+        let scan_cache = self.scan_cache.clone();
+        let idle = self.idle.clone();
+        let snapshots = self
+            .snapshots
+            .read()
+            .map_err(|_| Error::msg("Failed to lock snapshots"))?
+            .clone();
 
         let _ = thread::spawn(move || {
             let mut chan = IpcServerChannel::new(socket);
 
-            let (auth, pool, entities) = match Self::auth(&mut chan, pool) {
+            let (auth, pool, entities) = match Self::auth(&mut chan, &nc, pool) {
                 Ok(pool) => pool,
                 Err(err) => {
                     warn!("Failed to authenticate client: {:?}", err);
@@ -206,42 +379,98 @@ impl FdwListener {
                 }
             };
 
+            // Hand the session to an idle connection if there is one
+            // so its cached prepared queries can be reused
+            let chan = match idle.dispatch(&auth, chan) {
+                Ok(Some(chan)) => chan,
+                Ok(None) => return,
+                Err(err) => {
+                    warn!("Failed to dispatch to idle connection: {:?}", err);
+                    return;
+                }
+            };
+
             match (pool, &*entities) {
                 (ConnectionPools::Jdbc(pool), RwLockEntityConfigs::OracleJdbc(entities)) => {
-                    Self::process::<OracleJdbcConnector>(auth, nc, chan, pool, entities, log)
+                    Self::process::<OracleJdbcConnector>(
+                        auth, nc, chan, pool, entities, log, cache, admission, scan_cache, idle,
+                        snapshots.as_ref(),
+                    )
                 }
                 (ConnectionPools::Jdbc(pool), RwLockEntityConfigs::MysqlJdbc(entities)) => {
-                    Self::process::<MysqlJdbcConnector>(auth, nc, chan, pool, entities, log)
+                    Self::process::<MysqlJdbcConnector>(
+                        auth, nc, chan, pool, entities, log, cache, admission, scan_cache, idle,
+                        snapshots.as_ref(),
+                    )
                 }
                 (ConnectionPools::Jdbc(pool), RwLockEntityConfigs::TeradataJdbc(entities)) => {
-                    Self::process::<TeradataJdbcConnector>(auth, nc, chan, pool, entities, log)
+                    Self::process::<TeradataJdbcConnector>(
+                        auth, nc, chan, pool, entities, log, cache, admission, scan_cache, idle,
+                        snapshots.as_ref(),
+                    )
                 }
                 (ConnectionPools::Jdbc(pool), RwLockEntityConfigs::MssqlJdbc(entities)) => {
-                    Self::process::<MssqlJdbcConnector>(auth, nc, chan, pool, entities, log)
+                    Self::process::<MssqlJdbcConnector>(
+                        auth, nc, chan, pool, entities, log, cache, admission, scan_cache, idle,
+                        snapshots.as_ref(),
+                    )
                 }
                 (
                     ConnectionPools::NativePostgres(pool),
                     RwLockEntityConfigs::NativePostgres(entities),
-                ) => Self::process::<PostgresConnector>(auth, nc, chan, pool, entities, log),
+                ) => Self::process::<PostgresConnector>(
+                    auth, nc, chan, pool, entities, log, cache, admission, scan_cache, idle,
+                    snapshots.as_ref(),
+                ),
                 (
                     ConnectionPools::NativeSqlite(pool),
                     RwLockEntityConfigs::NativeSqlite(entities),
-                ) => Self::process::<SqliteConnector>(auth, nc, chan, pool, entities, log),
+                ) => Self::process::<SqliteConnector>(
+                    auth, nc, chan, pool, entities, log, cache, admission, scan_cache, idle,
+                    snapshots.as_ref(),
+                ),
                 (
                     ConnectionPools::NativeMongodb(pool),
                     RwLockEntityConfigs::NativeMongodb(entities),
-                ) => Self::process::<MongodbConnector>(auth, nc, chan, pool, entities, log),
+                ) => Self::process::<MongodbConnector>(
+                    auth, nc, chan, pool, entities, log, cache, admission, scan_cache, idle,
+                    snapshots.as_ref(),
+                ),
                 (ConnectionPools::FileAvro(pool), RwLockEntityConfigs::File(entities)) => {
-                    Self::process::<AvroConnector>(auth, nc, chan, pool, entities, log)
+                    Self::process::<AvroConnector>(
+                        auth, nc, chan, pool, entities, log, cache, admission, scan_cache, idle,
+                        snapshots.as_ref(),
+                    )
+                }
+                (ConnectionPools::FileWasm(pool), RwLockEntityConfigs::File(entities)) => {
+                    Self::process::<WasmConnector>(
+                        auth, nc, chan, pool, entities, log, cache, admission, scan_cache, idle,
+                        snapshots.as_ref(),
+                    )
                 }
                 (ConnectionPools::Peer(pool), RwLockEntityConfigs::Peer(entities)) => {
-                    Self::process::<PeerConnector>(auth, nc, chan, pool, entities, log)
+                    Self::process::<PeerConnector>(
+                        auth, nc, chan, pool, entities, log, cache, admission, scan_cache, idle,
+                        snapshots.as_ref(),
+                    )
                 }
                 (ConnectionPools::Internal(pool), RwLockEntityConfigs::Internal(entities)) => {
-                    Self::process::<InternalConnector>(auth, nc, chan, pool, entities, log)
+                    Self::process::<InternalConnector>(
+                        auth, nc, chan, pool, entities, log, cache, admission, scan_cache, idle,
+                        snapshots.as_ref(),
+                    )
                 }
                 (ConnectionPools::Memory(pool), RwLockEntityConfigs::Memory(entities)) => {
-                    Self::process::<MemoryConnector>(auth, nc, chan, pool, entities, log)
+                    Self::process::<MemoryConnector>(
+                        auth, nc, chan, pool, entities, log, cache, admission, scan_cache, idle,
+                        snapshots.as_ref(),
+                    )
+                }
+                (ConnectionPools::Plugin(pool), RwLockEntityConfigs::Plugin(entities)) => {
+                    Self::process::<PluginConnector>(
+                        auth, nc, chan, pool, entities, log, cache, admission, scan_cache, idle,
+                        snapshots.as_ref(),
+                    )
                 }
                 _ => {
                     panic!("Unknown types or mismatch between pool and entities",)
@@ -254,7 +483,8 @@ impl FdwListener {
 
     fn auth(
         chan: &mut IpcServerChannel,
-        pools: Arc<HashMap<String, (ConnectionPools, Arc<RwLockEntityConfigs>)>>,
+        nc: &NodeConfig,
+        pools: SharedPools,
     ) -> Result<(AuthDataSource, ConnectionPools, Arc<RwLockEntityConfigs>)> {
         chan.recv_with_return(|msg| {
             let auth = match msg {
@@ -262,6 +492,10 @@ impl FdwListener {
                 _ => bail!("Received unexpected message from client: {:?}", msg),
             };
 
+            let pools = pools
+                .read()
+                .map_err(|_| Error::msg("Failed to lock connection pools"))?;
+
             let pool = pools
                 .get(&auth.data_source_id)
                 .cloned()
@@ -274,7 +508,7 @@ impl FdwListener {
                 });
 
             let response = match pool {
-                Ok(_) => ServerMessage::AuthAccepted,
+                Ok(_) => ServerMessage::AuthAccepted(Self::session_options(nc, &auth)),
                 Err(_) => ServerMessage::Error("Unknown data source id".to_string()),
             };
 
@@ -282,14 +516,40 @@ impl FdwListener {
         })?
     }
 
+    /// Gets the options of the session, the fetch size of the workload class
+    /// of the user overrides that of the data source
+    fn session_options(nc: &NodeConfig, auth: &AuthDataSource) -> SessionOptions {
+        let fetch_size = auth
+            .context()
+            .and_then(|ctx| nc.auth.workload(&ctx.username))
+            .and_then(|w| w.fetch_size.as_ref());
+
+        SessionOptions {
+            fetch_min_rows: fetch_size.and_then(|f| f.min_rows),
+            fetch_max_rows: fetch_size.and_then(|f| f.max_rows),
+        }
+    }
+
     fn process<TConnector: Connector>(
         auth: AuthDataSource,
-        nc: &'static NodeConfig,
+        nc: Arc<NodeConfig>,
         chan: IpcServerChannel,
         pool: TConnector::TConnectionPool,
         entities: &RwLock<ConnectorEntityConfig<TConnector::TEntitySourceConfig>>,
         log: RemoteQueryLog,
+        cache: MetadataCache,
+        admission: AdmissionControl,
+        scan_cache: ScanCache,
+        idle: IdleConnections,
+        snapshots: Option<&Snapshots>,
     ) {
+        let idle_timeout = nc
+            .sources
+            .iter()
+            .find(|i| i.id == auth.data_source_id)
+            .map(|i| i.prepared_query_idle_timeout())
+            .unwrap_or_default();
+
         let mut fdw_con = FdwConnection::<TConnector>::new(
             auth.data_source_id.clone(),
             auth.context(),
@@ -298,14 +558,78 @@ impl FdwListener {
             entities,
             pool,
             log,
-        );
+            cache,
+            admission,
+            scan_cache,
+        )
+        .with_session(auth.session_id)
+        .with_snapshots(snapshots);
+
+        loop {
+            if let Err(err) = fdw_con.process() {
+                error!("Error while processing FDW connection: {:?}", err);
+                return;
+            }
 
-        if let Err(err) = fdw_con.process() {
-            error!("Error while processing FDW connection: {:?}", err);
+            // Once the session closes we keep the connection open for reuse
+            // by a subsequent session, until the idle timeout elapses
+            if !fdw_con.release() {
+                return;
+            }
+
+            match idle.park(&auth, idle_timeout) {
+                Ok(Some((session_id, chan))) => fdw_con.resume(session_id, chan),
+                Ok(None) => return,
+                Err(err) => {
+                    warn!("Failed to park idle connection: {:?}", err);
+                    return;
+                }
+            }
         }
     }
 }
 
+impl FdwHandle {
+    /// Replaces the entity configs of the supplied data source.
+    ///
+    /// Existing connections continue to use the previous entities until they are closed.
+    /// Any cached metadata and idle connections of the data source are invalidated.
+    pub fn replace_entities(
+        &self,
+        data_source_id: &str,
+        entities: ConnectorEntityConfigs,
+    ) -> Result<()> {
+        let mut pools = self
+            .pools
+            .write()
+            .map_err(|_| Error::msg("Failed to lock connection pools"))?;
+
+        let (_, current) = pools
+            .get_mut(data_source_id)
+            .with_context(|| format!("Failed to find data source with id: {}", data_source_id))?;
+        *current = Arc::new(entities.into());
+
+        self.cache.invalidate(Some(data_source_id))?;
+        self.idle.invalidate(Some(data_source_id))?;
+
+        Ok(())
+    }
+
+    /// Replaces the node configuration used by new connections,
+    /// idle connections are invalidated so they are not reused.
+    pub fn replace_config(&self, nc: Arc<NodeConfig>) -> Result<()> {
+        let mut current = self
+            .nc
+            .write()
+            .map_err(|_| Error::msg("Failed to lock node config"))?;
+        *current = nc;
+
+        self.idle.invalidate(None)?;
+
+        Ok(())
+    }
+}
+
 impl Drop for FdwServer {
     fn drop(&mut self) {
         if let Err(err) = self.terminate_mut() {
@@ -340,6 +664,7 @@ pub enum RwLockEntityConfigs {
     Peer(RwLock<ConnectorEntityConfig<<PeerConnector as Connector>::TEntitySourceConfig>>),
     Internal(RwLock<ConnectorEntityConfig<<InternalConnector as Connector>::TEntitySourceConfig>>),
     Memory(RwLock<ConnectorEntityConfig<<MemoryConnector as Connector>::TEntitySourceConfig>>),
+    Plugin(RwLock<ConnectorEntityConfig<<PluginConnector as Connector>::TEntitySourceConfig>>),
 }
 
 impl From<ConnectorEntityConfigs> for RwLockEntityConfigs {
@@ -358,6 +683,7 @@ impl From<ConnectorEntityConfigs> for RwLockEntityConfigs {
                 Self::Internal(RwLock::new(ConnectorEntityConfig::new()))
             }
             ConnectorEntityConfigs::Memory(e) => Self::Memory(RwLock::new(e)),
+            ConnectorEntityConfigs::Plugin(e) => Self::Plugin(RwLock::new(e)),
         }
     }
 }
@@ -371,7 +697,11 @@ mod tests {
         MemoryConnectionPool, MemoryConnector, MemoryConnectorEntitySourceConfig, MemoryDatabase,
     };
     use ansilo_core::{
-        config::{EntityAttributeConfig, EntityConfig, EntitySourceConfig, NodeConfig},
+        auth::{AuthContext, PasswordAuthContext, ProviderAuthContext},
+        config::{
+            AuthConfig, EntityAttributeConfig, EntityConfig, EntitySourceConfig, FetchSizeConfig,
+            NodeConfig, UserMatchConfig, WorkloadClass, WorkloadConfig,
+        },
         data::{DataType, DataValue},
         sqlil,
     };
@@ -379,7 +709,7 @@ mod tests {
 
     use crate::fdw::{
         channel::IpcClientChannel,
-        proto::{AuthDataSource, ClientMessage, ServerMessage},
+        proto::{AuthDataSource, ClientMessage, ServerMessage, SessionOptions},
     };
 
     use super::*;
@@ -453,7 +783,7 @@ mod tests {
                 data_source_id,
             )))
             .unwrap();
-        assert_eq!(res, ServerMessage::AuthAccepted);
+        assert_eq!(res, ServerMessage::AuthAccepted(SessionOptions::default()));
     }
 
     #[test]
@@ -503,4 +833,53 @@ mod tests {
 
         client.close().unwrap();
     }
+
+    #[test]
+    fn test_fdw_server_session_options() {
+        let nc = NodeConfig {
+            auth: AuthConfig {
+                workloads: vec![WorkloadConfig {
+                    applies_to: UserMatchConfig {
+                        users: vec!["etl".into()],
+                        roles: vec![],
+                    },
+                    class: WorkloadClass::Batch,
+                    fetch_size: Some(FetchSizeConfig {
+                        min_rows: Some(10_000),
+                        max_rows: None,
+                    }),
+                    ..WorkloadConfig::default()
+                }],
+                ..AuthConfig::default()
+            },
+            ..NodeConfig::default()
+        };
+        let auth = |username: &str| {
+            AuthDataSource::new(
+                Some(AuthContext::new(
+                    username,
+                    "password",
+                    None,
+                    ProviderAuthContext::Password(PasswordAuthContext::default()),
+                )),
+                "memory",
+            )
+        };
+
+        assert_eq!(
+            FdwListener::session_options(&nc, &auth("etl")),
+            SessionOptions {
+                fetch_min_rows: Some(10_000),
+                fetch_max_rows: None
+            }
+        );
+        assert_eq!(
+            FdwListener::session_options(&nc, &auth("mary")),
+            SessionOptions::default()
+        );
+        assert_eq!(
+            FdwListener::session_options(&nc, &AuthDataSource::new(None, "memory")),
+            SessionOptions::default()
+        );
+    }
 }
@@ -0,0 +1,33 @@
+use std::sync::{Arc, RwLock};
+
+use ansilo_connectors_all::PostgresConnector;
+use ansilo_connectors_base::{common::entity::ConnectorEntityConfig, interface::Connector};
+
+/// The pool of connections to the local postgres which the snapshots are read from
+pub type SnapshotPool = <PostgresConnector as Connector>::TConnectionPool;
+
+/// The local tables of the snapshots, keyed by the id of the materialized entity
+pub type SnapshotEntities =
+    ConnectorEntityConfig<<PostgresConnector as Connector>::TEntitySourceConfig>;
+
+/// The local snapshots of the materialized entities.
+///
+/// Scans of a materialized entity are served from its snapshot rather than
+/// the data source, through the same connection so the row filters, masks
+/// and query rules of the user apply as they would to the data source.
+#[derive(Clone)]
+pub struct Snapshots {
+    /// The pool of connections to the local postgres
+    pub(crate) pool: SnapshotPool,
+    /// The snapshot tables of the materialized entities
+    pub(crate) entities: Arc<RwLock<SnapshotEntities>>,
+}
+
+impl Snapshots {
+    pub fn new(pool: SnapshotPool, entities: SnapshotEntities) -> Self {
+        Self {
+            pool,
+            entities: Arc::new(RwLock::new(entities)),
+        }
+    }
+}
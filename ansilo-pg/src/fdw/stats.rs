@@ -0,0 +1,835 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use ansilo_connectors_all::*;
+use ansilo_connectors_base::{
+    common::entity::{ConnectorEntityConfig, EntitySource},
+    interface::{
+        Connection, ConnectionPool, Connector, OperationCost, QueryCompiler, QueryHandle,
+        QueryOperationResult, QueryPlanner, ResultSet, SelectQueryOperation,
+    },
+};
+use ansilo_core::{
+    config::{DataSourceConfig, NodeConfig},
+    data::DataValue,
+    err::{bail, Context, Error, Result},
+    sqlil as sql,
+};
+use ansilo_logging::{debug, warn};
+
+use super::{
+    cache::MetadataCache,
+    server::{RwLockEntityConfigs, SharedPools},
+};
+
+/// Default selectivities used when there are no applicable statistics.
+/// Values borrowed from postgres
+/// @see https://doxygen.postgresql.org/selfuncs_8h.html
+const DEFAULT_EQ_SEL: f64 = 0.005;
+const DEFAULT_INEQ_SEL: f64 = 1.0 / 3.0;
+const DEFAULT_MATCH_SEL: f64 = 0.005;
+const DEFAULT_UNK_SEL: f64 = 0.005;
+const DEFAULT_CLAUSE_SEL: f64 = 0.5;
+const DEFAULT_NUM_DISTINCT: f64 = 200.0;
+
+/// How often the collector checks for entities with stale statistics
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// The minimum time before retrying an entity which failed to be sampled
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+/// The alias of the entity in the sampling query
+const SAMPLE_ALIAS: &str = "s";
+
+/// Statistics of an entity collected by sampling its rows from the data source
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityStatistics {
+    /// The estimated number of rows
+    pub rows: u64,
+    /// The average width of each row in bytes
+    pub row_width: u32,
+    /// The statistics of each column keyed by the attribute id
+    pub columns: HashMap<String, ColumnStatistics>,
+    /// When the statistics were collected
+    pub collected_at: Instant,
+}
+
+/// Statistics of a single column of an entity
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStatistics {
+    /// The estimated number of distinct non-null values
+    pub ndistinct: u64,
+    /// The fraction of rows which are null
+    pub null_frac: f64,
+}
+
+impl EntityStatistics {
+    pub fn new(rows: u64, row_width: u32, columns: HashMap<String, ColumnStatistics>) -> Self {
+        Self {
+            rows,
+            row_width,
+            columns,
+            collected_at: Instant::now(),
+        }
+    }
+
+    /// Gets the estimated data volume of the entity in bytes
+    pub fn total_bytes(&self) -> u64 {
+        self.rows.saturating_mul(self.row_width as u64)
+    }
+
+    /// Gets the cost estimate of scanning the entity
+    pub fn cost(&self) -> OperationCost {
+        OperationCost::new(
+            Some(self.rows),
+            Some(self.row_width).filter(|w| *w > 0),
+            None,
+            None,
+        )
+    }
+}
+
+impl ColumnStatistics {
+    pub fn new(ndistinct: u64, null_frac: f64) -> Self {
+        Self {
+            ndistinct,
+            null_frac,
+        }
+    }
+}
+
+/// Collects the statistics of the entity by reading a sample of its rows.
+///
+/// The sample is the first rows returned by the data source rather than
+/// a random sample, in return it can be retrieved using any connector
+/// which supports row limits.
+pub(crate) fn collect<TConnector: Connector>(
+    connection: &mut TConnector::TConnection,
+    entities: &ConnectorEntityConfig<TConnector::TEntitySourceConfig>,
+    entity: &EntitySource<TConnector::TEntitySourceConfig>,
+    sample_rows: u32,
+) -> Result<EntityStatistics> {
+    if entity.conf.attributes.is_empty() {
+        bail!("Entity '{}' has no attributes", entity.conf.id);
+    }
+
+    let source = sql::EntitySource::new(sql::EntityId::new(&entity.conf.id), SAMPLE_ALIAS);
+    let (_, mut select) =
+        TConnector::TQueryPlanner::create_base_select(connection, entities, entity, &source)?;
+
+    let ops = entity
+        .conf
+        .attributes
+        .iter()
+        .map(|a| {
+            SelectQueryOperation::AddColumn((a.id.clone(), sql::Expr::attr(SAMPLE_ALIAS, &a.id)))
+        })
+        .chain([SelectQueryOperation::SetRowLimit(sample_rows as _)]);
+
+    for op in ops {
+        let res = TConnector::TQueryPlanner::apply_select_operation(
+            connection,
+            entities,
+            &mut select,
+            op.clone(),
+        )?;
+
+        if let QueryOperationResult::Unsupported = res {
+            bail!("Failed to sample entity, unsupported operation: {:?}", op);
+        }
+    }
+
+    let query = TConnector::TQueryCompiler::compile_query(connection, entities, select.into())?;
+    let mut handle = connection.prepare(query)?;
+    let mut reader = handle.execute_query()?.reader()?;
+
+    let mut columns = entity
+        .conf
+        .attributes
+        .iter()
+        .map(|a| (a.id.clone(), ColumnSample::default()))
+        .collect::<Vec<_>>();
+    let mut sampled = 0u64;
+    let mut bytes = 0u64;
+
+    while sampled < sample_rows as u64 {
+        let row = match reader.read_row_vec()? {
+            Some(row) => row,
+            None => break,
+        };
+
+        for ((_, column), value) in columns.iter_mut().zip(row.iter()) {
+            bytes += value_width(value);
+            column.add(value);
+        }
+
+        sampled += 1;
+    }
+
+    drop(reader);
+    drop(handle);
+
+    // If the sample did not exhaust the entity we rely on
+    // the connector to estimate the total number of rows
+    let rows = if sampled < sample_rows as u64 {
+        sampled
+    } else {
+        TConnector::TQueryPlanner::estimate_size(connection, entity)?
+            .rows
+            .unwrap_or(sampled)
+            .max(sampled)
+    };
+
+    let row_width = if sampled == 0 { 0 } else { bytes / sampled };
+
+    Ok(EntityStatistics::new(
+        rows,
+        row_width.min(u32::MAX as _) as _,
+        columns
+            .into_iter()
+            .map(|(id, column)| (id, column.statistics(sampled, rows)))
+            .collect(),
+    ))
+}
+
+/// The values of a column read while sampling an entity
+#[derive(Default)]
+struct ColumnSample {
+    /// The number of occurrences of each distinct value, keyed by its hash
+    values: HashMap<u64, u64>,
+    /// The number of null values
+    nulls: u64,
+}
+
+impl ColumnSample {
+    fn add(&mut self, value: &DataValue) {
+        if value.is_null() {
+            self.nulls += 1;
+            return;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        *self.values.entry(hasher.finish()).or_default() += 1;
+    }
+
+    fn statistics(&self, sampled: u64, rows: u64) -> ColumnStatistics {
+        if sampled == 0 {
+            return ColumnStatistics::new(0, 0.0);
+        }
+
+        let null_frac = self.nulls as f64 / sampled as f64;
+        let non_null = sampled - self.nulls;
+        let total_non_null = (rows as f64 * (1.0 - null_frac)).round() as u64;
+        let singletons = self.values.values().filter(|c| **c == 1).count() as u64;
+
+        ColumnStatistics::new(
+            estimate_ndistinct(
+                non_null,
+                self.values.len() as u64,
+                singletons,
+                total_non_null,
+            ),
+            null_frac,
+        )
+    }
+}
+
+/// Estimates the number of distinct values of a column from a sample of `n` values
+/// out of a total of `total` values, where the sample contains `d` distinct values of
+/// which `f1` occurred exactly once.
+///
+/// This uses the Duj1 estimator of Haas and Stokes, as is used by postgres
+/// @see https://doxygen.postgresql.org/analyze_8c.html
+fn estimate_ndistinct(n: u64, d: u64, f1: u64, total: u64) -> u64 {
+    // The whole column was sampled so the count is exact
+    if n == 0 || n >= total {
+        return d;
+    }
+
+    let (n, d, f1, total) = (n as f64, d as f64, f1 as f64, total as f64);
+    let estimate = n * d / (n - f1 + f1 * n / total);
+
+    estimate.round().clamp(d, total) as u64
+}
+
+/// Gets the approximate size of the value in bytes
+fn value_width(value: &DataValue) -> u64 {
+    match value {
+        DataValue::Null => 0,
+        DataValue::Utf8String(s) | DataValue::JSON(s) => s.len() as _,
+        DataValue::Binary(b) => b.len() as _,
+        DataValue::Boolean(_) | DataValue::Int8(_) | DataValue::UInt8(_) => 1,
+        DataValue::Int16(_) | DataValue::UInt16(_) => 2,
+        DataValue::Int32(_) | DataValue::UInt32(_) | DataValue::Float32(_) => 4,
+        DataValue::Date(_) => 4,
+        DataValue::Int64(_) | DataValue::UInt64(_) | DataValue::Float64(_) => 8,
+        DataValue::Time(_) | DataValue::DateTime(_) | DataValue::DateTimeWithTZ(_) => 8,
+        DataValue::Decimal(_) | DataValue::Uuid(_) => 16,
+    }
+}
+
+/// Estimates the number of rows returned by a select query using the
+/// statistics of the entities it reads from.
+///
+/// This is used when the connector is unable to estimate the effect of
+/// conditions, joins and grouping on the number of rows itself.
+#[derive(Debug, Clone)]
+pub(crate) struct QueryEstimate {
+    /// The statistics of each entity in the query keyed by its alias
+    sources: HashMap<String, EntityStatistics>,
+    /// The estimated number of rows before grouping
+    rows: f64,
+    /// The estimated number of groups, if the query is grouped
+    groups: Option<f64>,
+    /// The row limit of the query
+    limit: Option<u64>,
+    /// The row offset of the query
+    offset: u64,
+}
+
+impl QueryEstimate {
+    pub(crate) fn new(alias: impl Into<String>, statistics: EntityStatistics) -> Self {
+        Self {
+            rows: statistics.rows as _,
+            sources: [(alias.into(), statistics)].into_iter().collect(),
+            groups: None,
+            limit: None,
+            offset: 0,
+        }
+    }
+
+    /// Gets the estimated number of rows returned by the query
+    pub(crate) fn rows(&self) -> u64 {
+        let mut rows = match self.groups {
+            Some(groups) => groups.min(self.rows),
+            None => self.rows,
+        };
+
+        rows = (rows - self.offset as f64).max(0.0);
+
+        if let Some(limit) = self.limit {
+            rows = rows.min(limit as _);
+        }
+
+        rows.round().max(1.0) as u64
+    }
+
+    /// Updates the estimate with the supplied operation, returning the new row estimate.
+    /// Joins require the statistics of the joined entity, if these are not supplied
+    /// `None` is returned and the estimate can no longer be used.
+    pub(crate) fn apply(
+        &mut self,
+        op: &SelectQueryOperation,
+        join: Option<EntityStatistics>,
+    ) -> Option<u64> {
+        match op {
+            SelectQueryOperation::AddWhere(cond) => {
+                self.rows *= self.selectivity(cond);
+            }
+            SelectQueryOperation::AddJoin(j) => {
+                let statistics = join?;
+                let left = self.rows;
+                let right = statistics.rows as f64;
+                self.sources.insert(j.target.alias.clone(), statistics);
+
+                let selectivity = j.conds.iter().map(|c| self.selectivity(c)).product::<f64>();
+                let inner = left * right * selectivity;
+
+                self.rows = match j.r#type {
+                    sql::JoinType::Inner => inner,
+                    sql::JoinType::Left => inner.max(left),
+                    sql::JoinType::Right => inner.max(right),
+                    sql::JoinType::Full => inner.max(left).max(right),
+                };
+            }
+            SelectQueryOperation::AddGroupBy(expr) => {
+                let ndistinct = self
+                    .column(expr)
+                    .map(|c| c.ndistinct.max(1) as f64)
+                    .unwrap_or(DEFAULT_NUM_DISTINCT);
+
+                self.groups = Some(self.groups.unwrap_or(1.0) * ndistinct);
+            }
+            SelectQueryOperation::SetRowLimit(limit) => self.limit = Some(*limit),
+            SelectQueryOperation::SetRowOffset(offset) => self.offset = *offset,
+            SelectQueryOperation::AddColumn(_)
+            | SelectQueryOperation::AddOrderBy(_)
+            | SelectQueryOperation::SetRowLockMode(_) => {}
+        }
+
+        Some(self.rows())
+    }
+
+    /// Estimates the fraction of rows which satisfy the condition
+    fn selectivity(&self, cond: &sql::Expr) -> f64 {
+        use sql::{BinaryOpType as B, UnaryOpType as U};
+
+        let selectivity = match cond {
+            sql::Expr::BinaryOp(op) => match op.r#type {
+                B::LogicalAnd => self.selectivity(&op.left) * self.selectivity(&op.right),
+                B::LogicalOr => {
+                    let (left, right) = (self.selectivity(&op.left), self.selectivity(&op.right));
+                    left + right - left * right
+                }
+                B::Equal | B::NullSafeEqual => self.eq_selectivity(&op.left, &op.right),
+                B::NotEqual => 1.0 - self.eq_selectivity(&op.left, &op.right),
+                B::GreaterThan | B::GreaterThanOrEqual | B::LessThan | B::LessThanOrEqual => {
+                    DEFAULT_INEQ_SEL
+                }
+                B::Regexp => DEFAULT_MATCH_SEL,
+                _ => DEFAULT_CLAUSE_SEL,
+            },
+            sql::Expr::UnaryOp(op) => match op.r#type {
+                U::LogicalNot => 1.0 - self.selectivity(&op.expr),
+                U::IsNull => self
+                    .column(&op.expr)
+                    .map(|c| c.null_frac)
+                    .unwrap_or(DEFAULT_UNK_SEL),
+                U::IsNotNull => self
+                    .column(&op.expr)
+                    .map(|c| 1.0 - c.null_frac)
+                    .unwrap_or(1.0 - DEFAULT_UNK_SEL),
+                _ => DEFAULT_CLAUSE_SEL,
+            },
+            _ => DEFAULT_CLAUSE_SEL,
+        };
+
+        selectivity.clamp(0.0, 1.0)
+    }
+
+    /// Estimates the fraction of rows where the expressions are equal
+    fn eq_selectivity(&self, left: &sql::Expr, right: &sql::Expr) -> f64 {
+        match (self.column(left), self.column(right)) {
+            // Joining columns, we assume the values of the column with fewer
+            // distinct values are all present in the other column
+            (Some(l), Some(r)) => {
+                (1.0 - l.null_frac) * (1.0 - r.null_frac)
+                    / l.ndistinct.max(r.ndistinct).max(1) as f64
+            }
+            // Comparing a column to a constant or parameter
+            (Some(c), None) | (None, Some(c)) => (1.0 - c.null_frac) / c.ndistinct.max(1) as f64,
+            (None, None) => DEFAULT_EQ_SEL,
+        }
+    }
+
+    /// Gets the statistics of the column referenced by the expression
+    fn column(&self, expr: &sql::Expr) -> Option<&ColumnStatistics> {
+        match expr {
+            sql::Expr::Attribute(attr) => self
+                .sources
+                .get(&attr.entity_alias)?
+                .columns
+                .get(&attr.attribute_id),
+            sql::Expr::Cast(cast) => self.column(&cast.expr),
+            _ => None,
+        }
+    }
+}
+
+/// Periodically samples the entities of the data sources which have statistics
+/// enabled. The statistics are stored in the metadata cache and used to fill in
+/// the estimates which the connectors cannot provide themselves.
+pub(crate) struct StatisticsCollector {
+    nc: &'static NodeConfig,
+    pools: SharedPools,
+    cache: MetadataCache,
+    terminated: Arc<AtomicBool>,
+    /// When each entity was last sampled, keyed by the data source and entity id
+    attempts: HashMap<(String, String), Instant>,
+}
+
+impl StatisticsCollector {
+    /// Starts the collector thread if any data source has statistics enabled
+    pub(crate) fn start(
+        nc: &'static NodeConfig,
+        pools: SharedPools,
+        cache: MetadataCache,
+        terminated: Arc<AtomicBool>,
+    ) {
+        if !nc.sources.iter().any(|s| s.statistics_interval().is_some()) {
+            return;
+        }
+
+        let mut collector = Self {
+            nc,
+            pools,
+            cache,
+            terminated,
+            attempts: HashMap::new(),
+        };
+
+        thread::spawn(move || {
+            while !collector.terminated.load(Ordering::SeqCst) {
+                collector.collect_all();
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+    }
+
+    fn collect_all(&mut self) {
+        for source in self.nc.sources.iter() {
+            let interval = match source.statistics_interval() {
+                Some(interval) => interval,
+                None => continue,
+            };
+
+            if let Err(err) = self.collect_source(source, interval) {
+                warn!(
+                    "Failed to collect statistics from data source '{}': {:?}",
+                    source.id, err
+                );
+            }
+        }
+    }
+
+    fn collect_source(&mut self, source: &DataSourceConfig, interval: Duration) -> Result<()> {
+        let (pool, entities) = {
+            let pools = self
+                .pools
+                .read()
+                .map_err(|_| Error::msg("Failed to lock connection pools"))?;
+
+            match pools.get(&source.id) {
+                Some((pool, entities)) => (pool.clone(), Arc::clone(entities)),
+                None => return Ok(()),
+            }
+        };
+
+        match (pool, &*entities) {
+            (ConnectionPools::Jdbc(pool), RwLockEntityConfigs::OracleJdbc(entities)) => {
+                self.collect_entities::<OracleJdbcConnector>(source, interval, pool, entities)
+            }
+            (ConnectionPools::Jdbc(pool), RwLockEntityConfigs::MysqlJdbc(entities)) => {
+                self.collect_entities::<MysqlJdbcConnector>(source, interval, pool, entities)
+            }
+            (ConnectionPools::Jdbc(pool), RwLockEntityConfigs::TeradataJdbc(entities)) => {
+                self.collect_entities::<TeradataJdbcConnector>(source, interval, pool, entities)
+            }
+            (ConnectionPools::Jdbc(pool), RwLockEntityConfigs::MssqlJdbc(entities)) => {
+                self.collect_entities::<MssqlJdbcConnector>(source, interval, pool, entities)
+            }
+            (
+                ConnectionPools::NativePostgres(pool),
+                RwLockEntityConfigs::NativePostgres(entities),
+            ) => self.collect_entities::<PostgresConnector>(source, interval, pool, entities),
+            (ConnectionPools::NativeSqlite(pool), RwLockEntityConfigs::NativeSqlite(entities)) => {
+                self.collect_entities::<SqliteConnector>(source, interval, pool, entities)
+            }
+            (
+                ConnectionPools::NativeMongodb(pool),
+                RwLockEntityConfigs::NativeMongodb(entities),
+            ) => self.collect_entities::<MongodbConnector>(source, interval, pool, entities),
+            (ConnectionPools::FileAvro(pool), RwLockEntityConfigs::File(entities)) => {
+                self.collect_entities::<AvroConnector>(source, interval, pool, entities)
+            }
+            (ConnectionPools::FileWasm(pool), RwLockEntityConfigs::File(entities)) => {
+                self.collect_entities::<WasmConnector>(source, interval, pool, entities)
+            }
+            (ConnectionPools::Peer(pool), RwLockEntityConfigs::Peer(entities)) => {
+                self.collect_entities::<PeerConnector>(source, interval, pool, entities)
+            }
+            (ConnectionPools::Internal(pool), RwLockEntityConfigs::Internal(entities)) => {
+                self.collect_entities::<InternalConnector>(source, interval, pool, entities)
+            }
+            (ConnectionPools::Memory(pool), RwLockEntityConfigs::Memory(entities)) => {
+                self.collect_entities::<MemoryConnector>(source, interval, pool, entities)
+            }
+            (ConnectionPools::Plugin(pool), RwLockEntityConfigs::Plugin(entities)) => {
+                self.collect_entities::<PluginConnector>(source, interval, pool, entities)
+            }
+            _ => bail!("Unknown types or mismatch between pool and entities"),
+        }
+    }
+
+    fn collect_entities<TConnector: Connector>(
+        &mut self,
+        source: &DataSourceConfig,
+        interval: Duration,
+        mut pool: TConnector::TConnectionPool,
+        entities: &RwLock<ConnectorEntityConfig<TConnector::TEntitySourceConfig>>,
+    ) -> Result<()> {
+        // We copy the entities so the lock is not held while sampling
+        let entities = match entities.read() {
+            Ok(e) => e.clone(),
+            Err(_) => bail!("Failed to load entities"),
+        };
+
+        let due = entities
+            .entities()
+            .filter(|e| self.is_due(&source.id, &e.conf.id, interval))
+            .collect::<Vec<_>>();
+
+        if due.is_empty() {
+            return Ok(());
+        }
+
+        let mut connection = pool.acquire(None).context("Failed to acquire connection")?;
+
+        for entity in due {
+            if self.terminated.load(Ordering::SeqCst) {
+                break;
+            }
+
+            self.attempts
+                .insert((source.id.clone(), entity.conf.id.clone()), Instant::now());
+
+            let res = collect::<TConnector>(
+                &mut connection,
+                &entities,
+                entity,
+                source.statistics_sample_rows(),
+            );
+
+            match res {
+                Ok(statistics) => {
+                    debug!(
+                        "Collected statistics of entity '{}' from data source '{}': {:?}",
+                        entity.conf.id, source.id, statistics
+                    );
+                    self.cache.put_statistics(
+                        &source.id,
+                        &sql::EntityId::new(&entity.conf.id),
+                        statistics,
+                    )?;
+                }
+                Err(err) => warn!(
+                    "Failed to collect statistics of entity '{}' from data source '{}': {:?}",
+                    entity.conf.id, source.id, err
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the statistics of the entity are missing or stale
+    /// and it has not been attempted recently
+    fn is_due(&self, data_source_id: &str, entity_id: &str, interval: Duration) -> bool {
+        let stale = match self
+            .cache
+            .get_statistics(data_source_id, &sql::EntityId::new(entity_id))
+        {
+            Ok(Some(statistics)) => statistics.collected_at.elapsed() >= interval,
+            Ok(None) => true,
+            Err(_) => false,
+        };
+
+        let attempted = self
+            .attempts
+            .get(&(data_source_id.into(), entity_id.into()))
+            .map(|t| t.elapsed() < interval.min(RETRY_INTERVAL))
+            .unwrap_or(false);
+
+        stale && !attempted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_connectors_memory::{MemoryConnectorEntitySourceConfig, MemoryDatabase};
+    use ansilo_core::{
+        config::{EntityAttributeConfig, EntityConfig, EntitySourceConfig},
+        data::DataType,
+    };
+    use lazy_static::lazy_static;
+
+    use super::*;
+
+    lazy_static! {
+        static ref NODE_CONFIG: NodeConfig = NodeConfig::default();
+    }
+
+    fn mock_statistics(rows: u64, columns: Vec<(&str, u64, f64)>) -> EntityStatistics {
+        EntityStatistics::new(
+            rows,
+            10,
+            columns
+                .into_iter()
+                .map(|(id, nd, nf)| (id.to_string(), ColumnStatistics::new(nd, nf)))
+                .collect(),
+        )
+    }
+
+    fn eq(left: sql::Expr, right: sql::Expr) -> sql::Expr {
+        sql::Expr::BinaryOp(sql::BinaryOp::new(left, sql::BinaryOpType::Equal, right))
+    }
+
+    fn collect_memory(
+        rows: Vec<Vec<DataValue>>,
+        mock_size: Option<u64>,
+        sample_rows: u32,
+    ) -> EntityStatistics {
+        let data = MemoryDatabase::new();
+        let mut entities = ConnectorEntityConfig::new();
+
+        entities.add(EntitySource::new(
+            EntityConfig::minimal(
+                "people",
+                vec![
+                    EntityAttributeConfig::minimal("name", DataType::rust_string()),
+                    EntityAttributeConfig::minimal("age", DataType::UInt32),
+                ],
+                EntitySourceConfig::minimal(""),
+            ),
+            MemoryConnectorEntitySourceConfig::new(
+                mock_size.map(|rows| OperationCost::new(Some(rows), None, None, None)),
+            ),
+        ));
+        data.set_data("people", rows);
+
+        let mut pool =
+            MemoryConnector::create_connection_pool(data, &NODE_CONFIG, &entities).unwrap();
+        let mut connection = pool.acquire(None).unwrap();
+        let entity = entities.get(&sql::EntityId::new("people")).unwrap().clone();
+
+        collect::<MemoryConnector>(&mut connection, &entities, &entity, sample_rows).unwrap()
+    }
+
+    #[test]
+    fn test_estimate_ndistinct() {
+        // Whole column sampled
+        assert_eq!(estimate_ndistinct(100, 10, 0, 100), 10);
+        // All values unique
+        assert_eq!(estimate_ndistinct(100, 100, 100, 10_000), 10_000);
+        // No singletons implies all values have been seen
+        assert_eq!(estimate_ndistinct(100, 10, 0, 10_000), 10);
+        // Empty sample
+        assert_eq!(estimate_ndistinct(0, 0, 0, 10_000), 0);
+    }
+
+    #[test]
+    fn test_value_width() {
+        assert_eq!(value_width(&DataValue::Null), 0);
+        assert_eq!(value_width(&DataValue::from("abc")), 3);
+        assert_eq!(value_width(&DataValue::Int32(1)), 4);
+        assert_eq!(value_width(&DataValue::Float64(1.0)), 8);
+    }
+
+    #[test]
+    fn test_collect_statistics_exhausted_sample() {
+        let stats = collect_memory(
+            vec![
+                vec![DataValue::from("Mary"), DataValue::UInt32(20)],
+                vec![DataValue::from("John"), DataValue::UInt32(20)],
+                vec![DataValue::from("Gary"), DataValue::Null],
+                vec![DataValue::from("Mary"), DataValue::Null],
+            ],
+            None,
+            100,
+        );
+
+        assert_eq!(stats.rows, 4);
+        assert_eq!(stats.row_width, 6);
+        assert_eq!(stats.total_bytes(), 24);
+        assert_eq!(stats.columns["name"], ColumnStatistics::new(3, 0.0));
+        assert_eq!(stats.columns["age"], ColumnStatistics::new(1, 0.5));
+    }
+
+    #[test]
+    fn test_collect_statistics_extrapolates_sample() {
+        let stats = collect_memory(
+            (0..10)
+                .map(|i| vec![DataValue::from("Mary"), DataValue::UInt32(i)])
+                .collect(),
+            Some(1000),
+            5,
+        );
+
+        assert_eq!(stats.rows, 1000);
+        assert_eq!(stats.columns["name"].ndistinct, 1);
+        assert_eq!(stats.columns["age"].ndistinct, 1000);
+    }
+
+    #[test]
+    fn test_query_estimate_where() {
+        let mut estimate = QueryEstimate::new(
+            "p",
+            mock_statistics(1000, vec![("id", 1000, 0.0), ("country", 10, 0.5)]),
+        );
+
+        let rows = estimate.apply(
+            &SelectQueryOperation::AddWhere(eq(
+                sql::Expr::attr("p", "country"),
+                sql::Expr::constant(DataValue::from("AU")),
+            )),
+            None,
+        );
+
+        assert_eq!(rows, Some(50));
+
+        let rows = estimate.apply(
+            &SelectQueryOperation::AddWhere(sql::Expr::UnaryOp(sql::UnaryOp::new(
+                sql::UnaryOpType::LogicalNot,
+                eq(
+                    sql::Expr::attr("p", "id"),
+                    sql::Expr::constant(DataValue::UInt32(1)),
+                ),
+            ))),
+            None,
+        );
+
+        assert_eq!(rows, Some(50));
+    }
+
+    #[test]
+    fn test_query_estimate_join() {
+        let mut estimate = QueryEstimate::new(
+            "o",
+            mock_statistics(10_000, vec![("customer_id", 100, 0.0)]),
+        );
+
+        let join = sql::Join::new(
+            sql::JoinType::Inner,
+            sql::EntitySource::new(sql::EntityId::new("customers"), "c"),
+            vec![eq(
+                sql::Expr::attr("o", "customer_id"),
+                sql::Expr::attr("c", "id"),
+            )],
+        );
+
+        let rows = estimate.apply(
+            &SelectQueryOperation::AddJoin(join.clone()),
+            Some(mock_statistics(200, vec![("id", 200, 0.0)])),
+        );
+
+        assert_eq!(rows, Some(10_000));
+
+        // Without statistics of the joined entity there is no estimate
+        let mut estimate = QueryEstimate::new(
+            "o",
+            mock_statistics(10_000, vec![("customer_id", 100, 0.0)]),
+        );
+        assert_eq!(
+            estimate.apply(&SelectQueryOperation::AddJoin(join), None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_query_estimate_group_by_and_limit() {
+        let mut estimate = QueryEstimate::new(
+            "p",
+            mock_statistics(1000, vec![("country", 10, 0.0), ("gender", 2, 0.0)]),
+        );
+
+        estimate.apply(
+            &SelectQueryOperation::AddGroupBy(sql::Expr::attr("p", "country")),
+            None,
+        );
+        let rows = estimate.apply(
+            &SelectQueryOperation::AddGroupBy(sql::Expr::attr("p", "gender")),
+            None,
+        );
+        assert_eq!(rows, Some(20));
+
+        let rows = estimate.apply(&SelectQueryOperation::SetRowLimit(5), None);
+        assert_eq!(rows, Some(5));
+    }
+}
@@ -250,11 +250,17 @@ mod tests {
                 username: "john".into(),
                 description: None,
                 provider: Some("password".into()),
+                roles: vec![],
                 r#type: UserTypeOptions::Password(PasswordUserConfig {
                     password: "password1".into(),
                 }),
             }],
             service_users: vec![],
+            rules: vec![],
+            limits: vec![],
+            masks: vec![],
+            grants: vec![],
+            workloads: vec![],
         }));
 
         Authenticator::init(conf).unwrap()
@@ -281,6 +287,7 @@ mod tests {
                 username: "mary".into(),
                 description: None,
                 provider: Some("jwt".into()),
+                roles: vec![],
                 r#type: UserTypeOptions::Jwt(JwtUserConfig {
                     claims: vec![(
                         "scope".into(),
@@ -291,6 +298,11 @@ mod tests {
                 }),
             }],
             service_users: vec![],
+            rules: vec![],
+            limits: vec![],
+            masks: vec![],
+            grants: vec![],
+            workloads: vec![],
         }));
 
         (Authenticator::init(conf).unwrap(), encoding_key)
@@ -308,9 +320,15 @@ mod tests {
                 username: "john".into(),
                 description: None,
                 provider: Some("custom".into()),
+                roles: vec![],
                 r#type: UserTypeOptions::Custom(CustomUserConfig { custom: None }),
             }],
             service_users: vec![],
+            rules: vec![],
+            limits: vec![],
+            masks: vec![],
+            grants: vec![],
+            workloads: vec![],
         }));
 
         Authenticator::init(conf).unwrap()
@@ -7,6 +7,7 @@ pub mod test;
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use crate::{
@@ -17,12 +18,15 @@ use crate::{
     proto::{
         be::PostgresBackendMessage,
         common::CancelKey,
-        fe::{PostgresFrontendMessage, PostgresFrontendStartupMessage},
+        fe::{PostgresFrontendMessage, PostgresFrontendMessageTag, PostgresFrontendStartupMessage},
     },
     PostgresConnectionPools,
 };
 use ansilo_auth::Authenticator;
-use ansilo_core::err::{Context, Result};
+use ansilo_core::{
+    config::UserLimits,
+    err::{bail, Context, Result},
+};
 use ansilo_logging::{debug, warn};
 use ansilo_proxy::{handler::ConnectionHandler, stream::IOStream};
 use ansilo_util_pg::query::{pg_quote_identifier, pg_str_literal};
@@ -32,7 +36,7 @@ use rand::distributions::{Alphanumeric, DistString};
 use tokio::{
     io::{AsyncWriteExt, ReadHalf, WriteHalf},
     net::UnixStream,
-    sync::Mutex,
+    sync::{watch, Mutex, OwnedSemaphorePermit, Semaphore},
 };
 
 /// Request handler for postgres-wire-protocol connections
@@ -41,6 +45,8 @@ pub struct PostgresConnectionHandler {
     authenticator: Authenticator,
     pool: PostgresConnectionPools,
     cancel_keys: Arc<Mutex<HashMap<CancelKey, CancelKey>>>,
+    /// Limits the concurrent statements of each user, keyed by username
+    statements: Arc<Mutex<HashMap<String, (u32, Arc<Semaphore>)>>>,
 }
 
 impl PostgresConnectionHandler {
@@ -49,12 +55,28 @@ impl PostgresConnectionHandler {
             authenticator,
             pool,
             cancel_keys: Arc::new(Mutex::new(HashMap::new())),
+            statements: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
     pub fn pool(&self) -> &PostgresConnectionPools {
         &self.pool
     }
+
+    /// Gets the semaphore limiting the concurrent statements of the user.
+    /// If the limit has changed a new semaphore is created.
+    async fn statement_semaphore(&self, username: &str, max: u32) -> Arc<Semaphore> {
+        let mut statements = self.statements.lock().await;
+
+        match statements.get(username) {
+            Some((limit, semaphore)) if *limit == max => Arc::clone(semaphore),
+            _ => {
+                let semaphore = Arc::new(Semaphore::new(max.max(1) as _));
+                statements.insert(username.to_string(), (max, Arc::clone(&semaphore)));
+                semaphore
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -108,6 +130,11 @@ impl PostgresConnectionHandler {
         };
 
         // The key is valid, try cancel the query
+        self.cancel_backend(con_key).await
+    }
+
+    /// Cancels the statement running on the postgres backend with the supplied key
+    async fn cancel_backend(&self, con_key: CancelKey) -> Result<()> {
         let mut con = UnixStream::connect(self.pool.conf().pg_socket_path())
             .await
             .context("Failed to cancel request")?;
@@ -179,7 +206,7 @@ impl<'a> ProxySession<'a> {
         let startup = self.startup.clone();
 
         // Now that we have authenticated, we acquire a connection to postgres
-        self.con = Some(self.handler.pool.app(&auth.username).await?);
+        self.con = Some(self.acquire_connection(&auth.username).await?);
         let mut con = self.con.as_mut().unwrap();
 
         // Set the authentication context with a new reset token
@@ -218,6 +245,26 @@ impl<'a> ProxySession<'a> {
             .await
             .context("Failed to set client connection parameters")?;
 
+        // Apply the resource limits of the user, after the client parameters
+        // so they cannot be overridden on startup
+        let limits = self.handler.authenticator.conf().limits(&auth.username);
+        Self::set_limit_parameters(&mut con, &limits)
+            .await
+            .context("Failed to apply user limits")?;
+
+        let statements = match limits.max_concurrent_statements {
+            Some(max) => Some(self.handler.statement_semaphore(&auth.username, max).await),
+            None => None,
+        };
+
+        // The statement timeout of the session can be changed by the client so the
+        // runtime is also enforced by cancelling statements from the handler
+        let runtime = match (limits.max_statement_runtime, con.backend_key_data()) {
+            (Some(runtime), Some(con_key)) => Some((runtime, con_key.clone())),
+            (Some(_), None) => bail!("Cannot enforce statement runtime without backend key data"),
+            (None, _) => None,
+        };
+
         // We now inform the client that we are ready to accept queries
         PostgresBackendMessage::ReadyForQuery(b'I')
             .write(&mut client)
@@ -233,10 +280,13 @@ impl<'a> ProxySession<'a> {
         let (mut pg_reader, mut pg_writer) = con.split();
 
         match Self::proxy(
+            self.handler,
             &mut client_reader,
             &mut client_writer,
             &mut pg_reader,
             &mut pg_writer,
+            statements,
+            runtime,
         )
         .await
         {
@@ -253,6 +303,34 @@ impl<'a> ProxySession<'a> {
         Ok(())
     }
 
+    /// Acquires a connection to the database requested by the client.
+    ///
+    /// If the database is the name of a catalog the connection is made to the
+    /// database of that catalog, otherwise to the main database.
+    async fn acquire_connection(&self, username: &str) -> Result<AppPostgresConnection> {
+        let pool = &self.handler.pool;
+        let catalog = self
+            .startup
+            .params
+            .get("database")
+            .and_then(|db| pool.conf().catalog(db));
+
+        let catalog = match catalog {
+            Some(catalog) => catalog,
+            None => return pool.app(username).await,
+        };
+
+        if !catalog.users.iter().any(|u| u == username) {
+            bail!(
+                "User '{}' is not permitted to connect to catalog '{}'",
+                username,
+                catalog.name
+            );
+        }
+
+        pool.catalog_app(&catalog.name, username).await
+    }
+
     /// Forwards the session local connection parameters from the client to the server.
     ///
     /// The parameters are reset by "DISCARD ALL" when the connection is recycled.
@@ -313,18 +391,75 @@ impl<'a> ProxySession<'a> {
         Ok(())
     }
 
+    /// Sets the session parameters which enforce the resource limits of the user.
+    ///
+    /// The parameters are reset by "DISCARD ALL" when the connection is recycled.
+    /// The client can still change the statement timeout, so the runtime limit is
+    /// enforced regardless by cancelling statements in [`Self::proxy`].
+    async fn set_limit_parameters(
+        con: &mut AppPostgresConnection,
+        limits: &UserLimits,
+    ) -> Result<()> {
+        if let Some(runtime) = limits.max_statement_runtime {
+            con.execute(format!(
+                "SET SESSION statement_timeout = {}",
+                runtime.as_millis()
+            ))
+            .await?;
+        }
+
+        Ok(())
+    }
+
     /// Perfoms bi-directional proxying of messages between the client (frontend) and the server (backend)
+    ///
+    /// If the concurrent statements of the user are limited, each statement acquires a permit from the
+    /// supplied semaphore before it is forwarded, which is released once postgres is ready for the next query.
+    ///
+    /// If the runtime of statements is limited, statements still running after the runtime are cancelled
+    /// using the supplied backend key.
     async fn proxy(
+        handler: &PostgresConnectionHandler,
         client_reader: &mut ReadHalf<Box<dyn IOStream>>,
         client_writer: &mut WriteHalf<Box<dyn IOStream>>,
         pg_reader: &mut PgReader,
         pg_writer: &mut PgWriter,
+        statements: Option<Arc<Semaphore>>,
+        runtime: Option<(Duration, CancelKey)>,
     ) -> Result<()> {
+        let permit = Arc::new(Mutex::new(None::<OwnedSemaphorePermit>));
+        let input_permit = Arc::clone(&permit);
+
+        // Tracks when the current statement started, if any
+        let (started_tx, mut started_rx) = watch::channel(None::<Instant>);
+        let started_tx = Arc::new(started_tx);
+        let input_started_tx = Arc::clone(&started_tx);
+
         // Task for forwarding messages from the client to postgres
         let input = async move {
             loop {
                 let msg = PostgresFrontendMessage::read(client_reader).await?;
 
+                if Self::starts_statement(&msg) {
+                    input_started_tx.send_if_modified(|started| match started {
+                        Some(_) => false,
+                        None => {
+                            *started = Some(Instant::now());
+                            true
+                        }
+                    });
+                }
+
+                if let Some(statements) = statements.as_ref() {
+                    if Self::starts_statement(&msg) && input_permit.lock().await.is_none() {
+                        let acquired = Arc::clone(statements)
+                            .acquire_owned()
+                            .await
+                            .context("Failed to acquire statement permit")?;
+                        *input_permit.lock().await = Some(acquired);
+                    }
+                }
+
                 // If the client sends a terminate message we dont want
                 // to actually close the connection since then it cannot be
                 // recycled for future sessions.
@@ -344,6 +479,12 @@ impl<'a> ProxySession<'a> {
         let output = async move {
             loop {
                 let msg = pg_reader.receive().await?;
+
+                if let PostgresBackendMessage::ReadyForQuery(_) = msg {
+                    permit.lock().await.take();
+                    started_tx.send_replace(None);
+                }
+
                 msg.write(client_writer).await?;
                 client_writer.flush().await?;
             }
@@ -352,16 +493,62 @@ impl<'a> ProxySession<'a> {
             Result::<()>::Ok(())
         };
 
-        // Perform both tasks concurrently and, importantly,
-        // finish both tasks as soon as either one ends.
+        // Task for cancelling statements which exceed the runtime limit
+        let watchdog = async move {
+            let (runtime, con_key) = match runtime {
+                Some(runtime) => runtime,
+                None => return std::future::pending::<Result<()>>().await,
+            };
+
+            loop {
+                let started = *started_rx.borrow_and_update();
+                let deadline = match started {
+                    Some(started) => tokio::time::Instant::from_std(started + runtime),
+                    None => {
+                        started_rx.changed().await?;
+                        continue;
+                    }
+                };
+
+                tokio::select! {
+                    res = started_rx.changed() => res?,
+                    _ = tokio::time::sleep_until(deadline) => {
+                        debug!("Cancelling statement exceeding runtime of {:?}", runtime);
+                        handler.cancel_backend(con_key.clone()).await?;
+
+                        // Wait for the statement to end before watching the next
+                        while started_rx.borrow_and_update().is_some() {
+                            started_rx.changed().await?;
+                        }
+                    }
+                }
+            }
+        };
+
+        // Perform all tasks concurrently and, importantly,
+        // finish all tasks as soon as any one ends.
         tokio::select! {
             res = input => res?,
             res = output => res?,
+            res = watchdog => res?,
         };
 
         Ok(())
     }
 
+    /// Whether the message starts executing a statement
+    fn starts_statement(msg: &PostgresFrontendMessage) -> bool {
+        match msg {
+            PostgresFrontendMessage::Query(_) => true,
+            PostgresFrontendMessage::Other(msg) => [
+                Some(PostgresFrontendMessageTag::Execute as u8),
+                Some(PostgresFrontendMessageTag::FunctionCall as u8),
+            ]
+            .contains(&msg.tag()),
+            _ => false,
+        }
+    }
+
     /// Generate a random auth reset token.
     /// The reset token cannot be made available to the client,
     /// otherwise they could potentially change their auth context and hence
@@ -521,6 +708,10 @@ mod tests {
 
     use ansilo_core::{
         auth::{AuthContext, PasswordAuthContext, ProviderAuthContext},
+        config::{
+            AuthConfig, PasswordUserConfig, UserConfig, UserLimitConfig, UserMatchConfig,
+            UserTypeOptions,
+        },
         err::Error,
     };
     use tokio_postgres::NoTls;
@@ -825,4 +1016,83 @@ mod tests {
         let cancel_keys = handler.cancel_keys.lock().await;
         assert_eq!(cancel_keys.len(), 0);
     }
+
+    #[tokio::test]
+    async fn test_user_limits() {
+        ansilo_logging::init_for_tests();
+        let conf = Box::leak(Box::new(AuthConfig {
+            users: vec![UserConfig {
+                username: "test_user".into(),
+                description: None,
+                provider: None,
+                roles: vec!["analyst".into()],
+                r#type: UserTypeOptions::Password(PasswordUserConfig {
+                    password: "pass123".into(),
+                }),
+            }],
+            limits: vec![UserLimitConfig {
+                applies_to: UserMatchConfig {
+                    users: vec![],
+                    roles: vec!["analyst".into()],
+                },
+                max_result_rows: None,
+                max_statement_runtime: Some(1),
+                max_concurrent_statements: Some(1),
+            }],
+            ..AuthConfig::default()
+        }));
+        let auth = Authenticator::init(conf).unwrap();
+        let (_pg, handler) = init_pg_handler("user-limits", auth).await;
+
+        let (client, stream) = init_client_stream();
+
+        let fut_client = async move {
+            let (client, con) = tokio_postgres::Config::new()
+                .user("test_user")
+                .password("pass123")
+                .connect_raw(client, NoTls)
+                .await?;
+            tokio::spawn(con);
+
+            let timeout: String = client
+                .query_one("SHOW statement_timeout", &[])
+                .await?
+                .get(0);
+            assert_eq!(timeout, "1s");
+
+            let err = client
+                .batch_execute("SELECT pg_sleep(5)")
+                .await
+                .unwrap_err();
+            assert!(err
+                .as_db_error()
+                .unwrap()
+                .to_string()
+                .contains("canceling statement due to statement timeout"));
+
+            // Lifting the statement timeout does not lift the runtime limit
+            client.batch_execute("SET statement_timeout = 0").await?;
+
+            let err = client
+                .batch_execute("SELECT pg_sleep(5)")
+                .await
+                .unwrap_err();
+            assert!(err
+                .as_db_error()
+                .unwrap()
+                .to_string()
+                .contains("canceling statement due to user request"));
+
+            Result::<_, Error>::Ok(())
+        };
+        let fut_handler = handler.handle(stream);
+
+        tokio::try_join!(fut_client, fut_handler).unwrap();
+
+        // Ensure the statement permit is released with the session
+        let statements = handler.statements.lock().await;
+        let (max, semaphore) = statements.get("test_user").unwrap();
+        assert_eq!(*max, 1);
+        assert_eq!(semaphore.available_permits(), 1);
+    }
 }
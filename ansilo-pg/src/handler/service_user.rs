@@ -1,4 +1,5 @@
 use crate::proto::fe::PostgresFrontendMessage;
+use ansilo_auth::service_user::ServiceUserCredentials;
 use ansilo_core::err::{Context, Result};
 use ansilo_logging::{debug, info, warn};
 use ansilo_proxy::stream::Stream;
@@ -15,19 +16,35 @@ impl PostgresConnectionHandler {
     pub async fn authenticate_as_service_user(
         &self,
         service_user_id: String,
+    ) -> Result<tokio_postgres::Client> {
+        self.authenticate_as_service_user_in(service_user_id, None)
+            .await
+    }
+
+    /// Retrieves the credentials of the service user.
+    /// This could block so the credentials are retrieved on a blocking thread.
+    pub async fn get_service_user_creds(
+        &self,
+        service_user_id: &str,
+    ) -> Result<ServiceUserCredentials> {
+        let authenticator = self.authenticator.clone();
+        let service_user_id = service_user_id.to_string();
+
+        tokio::task::spawn_blocking(move || authenticator.get_service_user_creds(&service_user_id))
+            .await?
+    }
+
+    /// Authenticate to postgres as a service user, connecting to the database
+    /// of the supplied catalog or the main database if none is supplied.
+    pub async fn authenticate_as_service_user_in(
+        &self,
+        service_user_id: String,
+        catalog: Option<&str>,
     ) -> Result<tokio_postgres::Client> {
         debug!("Authenticating as service user '{service_user_id}'");
 
         // Get the credentials for the service user
-        // (this could block)
-        let creds = {
-            let authenticator = self.authenticator.clone();
-            let service_user_id = service_user_id.clone();
-            tokio::task::spawn_blocking(move || {
-                authenticator.get_service_user_creds(&service_user_id)
-            })
-            .await??
-        };
+        let creds = self.get_service_user_creds(&service_user_id).await?;
 
         // Create a unix stream pair
         let (sock_client, mut sock_handler) =
@@ -69,6 +86,9 @@ impl PostgresConnectionHandler {
         config.user(&creds.username);
         config.password(&creds.password);
         config.application_name("ansilo-svc-user");
+        if let Some(catalog) = catalog {
+            config.dbname(catalog);
+        }
 
         // No TLS is required over a local connection
         let (client, con) = config
@@ -99,11 +119,17 @@ mod tests {
                 username: "test_user".into(),
                 description: None,
                 provider: None,
+                roles: vec![],
                 r#type: UserTypeOptions::Password(PasswordUserConfig {
                     password: "pass123".into(),
                 }),
             }],
             service_users: vec![svc_user],
+            rules: vec![],
+            limits: vec![],
+            masks: vec![],
+            grants: vec![],
+            workloads: vec![],
         }));
 
         Authenticator::init(conf).unwrap()
@@ -16,6 +16,7 @@ pub fn mock_password_auth_default() -> Authenticator {
                 username: "test_user".into(),
                 description: None,
                 provider: None,
+                roles: vec![],
                 r#type: UserTypeOptions::Password(PasswordUserConfig {
                     password: "pass123".into(),
                 }),
@@ -24,12 +25,18 @@ pub fn mock_password_auth_default() -> Authenticator {
                 username: "another_user".into(),
                 description: None,
                 provider: None,
+                roles: vec![],
                 r#type: UserTypeOptions::Password(PasswordUserConfig {
                     password: "luna456".into(),
                 }),
             },
         ],
         service_users: vec![],
+        rules: vec![],
+        limits: vec![],
+        masks: vec![],
+        grants: vec![],
+        workloads: vec![],
     }));
 
     Authenticator::init(conf).unwrap()
@@ -53,6 +60,8 @@ pub async fn init_pg(test_name: &'static str, auth: &Authenticator) -> PostgresI
             .map(|i| i.username.clone())
             .collect(),
         init_db_sql: vec![],
+        catalogs: vec![],
+        replication: None,
     }));
 
     PostgresInstance::configure(conf).await.unwrap()
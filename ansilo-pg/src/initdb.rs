@@ -9,7 +9,7 @@ use ansilo_core::err::{Context, Result};
 use ansilo_logging::info;
 use nix::sys::signal::Signal;
 
-use crate::{conf::PostgresConf, proc::ChildProc, PG_SUPER_USER};
+use crate::{conf::PostgresConf, proc::ChildProc, replication, PG_SUPER_USER};
 
 /// initdb creates a new postgres data director
 #[derive(Debug)]
@@ -40,8 +40,20 @@ impl PostgresInitDb {
     /// Clears out the data directory so it can be reset
     pub fn reset(conf: &PostgresConf) -> Result<()> {
         info!("Clearing data dir {}...", conf.data_dir.display());
+        // The contents are removed, rather than the directory itself,
+        // as it may be the mount point of an encrypted volume
         if conf.data_dir.exists() {
-            fs::remove_dir_all(conf.data_dir.as_path()).context("Failed to clear directory")?;
+            for entry in
+                fs::read_dir(conf.data_dir.as_path()).context("Failed to read directory")?
+            {
+                let path = entry.context("Failed to read directory")?.path();
+                let res = if fs::symlink_metadata(&path)?.is_dir() {
+                    fs::remove_dir_all(&path)
+                } else {
+                    fs::remove_file(&path)
+                };
+                res.with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
         }
         fs::create_dir_all(conf.data_dir.as_path()).context("Failed to create directory")?;
         fs::set_permissions(conf.data_dir.as_path(), Permissions::from_mode(0o700))
@@ -68,6 +80,11 @@ impl PostgresInitDb {
                     .context("Failed to set perms on postgres.conf file")?;
             }
 
+            // Only trust local connections once postgres listens for replication
+            if self.conf.replication.is_some() {
+                replication::write_hba_conf(self.conf)?;
+            }
+
             // Default postgres.conf files have "include_dir 'conf.d'"
             // lets make sure it doesn't break our install
             fs::create_dir_all(self.conf.data_dir.join("conf.d"))
@@ -98,6 +115,8 @@ mod tests {
             fdw_socket_path: PathBuf::from("not-used"),
             app_users: vec![],
             init_db_sql: vec![],
+            catalogs: vec![],
+            replication: None,
         };
         Box::leak(Box::new(conf))
     }
@@ -1,9 +1,12 @@
-use std::time::Duration;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use ansilo_core::err::Result;
+use ansilo_core::{
+    err::{Context, Result},
+    web::node::PoolStatus,
+};
 use ansilo_logging::info;
 use conf::PostgresConf;
-use configure::configure;
+use configure::{configure, configure_catalog};
 use connection::{PostgresConnection, PostgresConnectionPool};
 use initdb::PostgresInitDb;
 use low_level::{
@@ -27,7 +30,9 @@ pub mod low_level;
 pub mod manager;
 pub mod proc;
 pub mod proto;
+pub mod replication;
 pub mod server;
+pub mod types;
 
 mod configure;
 #[cfg(any(test, feature = "test"))]
@@ -66,6 +71,17 @@ pub struct PostgresConnectionPools {
     admin: PostgresConnectionPool,
     /// The app user connection pool
     app: MultiUserPostgresConnectionPool,
+    /// The connection pools of each catalog database, keyed by the catalog name
+    catalogs: Arc<HashMap<String, PostgresCatalogPools>>,
+}
+
+/// The connection pools to the database of a catalog
+#[derive(Clone)]
+pub struct PostgresCatalogPools {
+    /// The admin user connection pool
+    admin: PostgresConnectionPool,
+    /// The app user connection pool, containing the users of the catalog
+    app: MultiUserPostgresConnectionPool,
 }
 
 impl PostgresInstance {
@@ -97,6 +113,21 @@ impl PostgresInstance {
         info!("Configuring postgres...");
         configure(conf, superuser_con).await?;
 
+        for catalog in conf.catalogs.iter() {
+            info!("Configuring catalog '{}'...", catalog.name);
+            let superuser_con = PostgresConnectionPool::new(
+                conf,
+                PG_SUPER_USER,
+                &catalog.name,
+                1,
+                connect_timeout,
+            )?
+            .acquire()
+            .await?;
+
+            configure_catalog(catalog, superuser_con).await?;
+        }
+
         Self::connect(conf, server).await
     }
 
@@ -113,6 +144,7 @@ impl PostgresInstance {
                 pg: conf,
                 users: conf.app_users.clone(),
                 database: PG_DATABASE.into(),
+                min_cons_per_user: conf.resources.min_connections() as _,
                 max_cons_per_user: conf.resources.connections() as _,
                 connect_timeout,
             })?;
@@ -120,10 +152,37 @@ impl PostgresInstance {
         // Ensure able to connect to postgres
         let _ = admin_pool.acquire().await?;
 
+        // Establish the app connections upfront to avoid paying
+        // the connection cost on the first queries
+        app_pool.warm_up().await;
+
+        // Connections to the catalog databases are established on demand
+        let mut catalogs = HashMap::new();
+        for catalog in conf.catalogs.iter() {
+            let admin = PostgresConnectionPool::new(
+                conf,
+                PG_ADMIN_USER,
+                &catalog.name,
+                2,
+                connect_timeout,
+            )?;
+            let app =
+                MultiUserPostgresConnectionPool::new(MultiUserPostgresConnectionPoolConfig {
+                    pg: conf,
+                    users: catalog.users.clone(),
+                    database: catalog.name.clone(),
+                    min_cons_per_user: 0,
+                    max_cons_per_user: conf.resources.connections() as _,
+                    connect_timeout,
+                })?;
+
+            catalogs.insert(catalog.name.clone(), PostgresCatalogPools { admin, app });
+        }
+
         Ok(Self {
             conf,
             server,
-            pools: PostgresConnectionPools::new(conf, admin_pool, app_pool),
+            pools: PostgresConnectionPools::new(conf, admin_pool, app_pool).with_catalogs(catalogs),
         })
     }
 
@@ -154,7 +213,18 @@ impl PostgresConnectionPools {
         admin: PostgresConnectionPool,
         app: MultiUserPostgresConnectionPool,
     ) -> Self {
-        Self { conf, admin, app }
+        Self {
+            conf,
+            admin,
+            app,
+            catalogs: Arc::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the connection pools of the catalog databases
+    pub fn with_catalogs(mut self, catalogs: HashMap<String, PostgresCatalogPools>) -> Self {
+        self.catalogs = Arc::new(catalogs);
+        self
     }
 
     /// Gets the pg config
@@ -173,6 +243,43 @@ impl PostgresConnectionPools {
     pub async fn app(&self, username: &str) -> Result<AppPostgresConnection> {
         self.app.acquire(username).await
     }
+
+    /// Gets a connection with admin privileges to the database of the supplied catalog
+    /// IMPORTANT: Only use this connection for trusted queries
+    /// and not queries supplied by the user
+    pub async fn catalog_admin(&self, catalog: &str) -> Result<PostgresConnection> {
+        self.catalog(catalog)?.admin.acquire().await
+    }
+
+    /// Gets a connection to the database of the supplied catalog authenticated as the supplied app user
+    pub async fn catalog_app(
+        &self,
+        catalog: &str,
+        username: &str,
+    ) -> Result<AppPostgresConnection> {
+        self.catalog(catalog)?.app.acquire(username).await
+    }
+
+    /// Gets the utilisation of each of the connection pools
+    pub fn status(&self) -> Vec<PoolStatus> {
+        let mut pools = vec![self.admin.status("admin"), self.app.status("app")];
+
+        let mut catalogs = self.catalogs.iter().collect::<Vec<_>>();
+        catalogs.sort_by_key(|(name, _)| name.as_str());
+
+        for (name, catalog) in catalogs {
+            pools.push(catalog.admin.status(&format!("{name}.admin")));
+            pools.push(catalog.app.status(&format!("{name}.app")));
+        }
+
+        pools
+    }
+
+    fn catalog(&self, catalog: &str) -> Result<&PostgresCatalogPools> {
+        self.catalogs
+            .get(catalog)
+            .with_context(|| format!("Catalog '{}' does not exist", catalog))
+    }
 }
 
 #[cfg(test)]
@@ -195,6 +302,8 @@ mod tests {
             fdw_socket_path: PathBuf::from("not-used"),
             app_users: vec![],
             init_db_sql: vec![],
+            catalogs: vec![],
+            replication: None,
         };
         Box::leak(Box::new(conf))
     }
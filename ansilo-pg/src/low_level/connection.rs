@@ -338,6 +338,8 @@ mod tests {
             fdw_socket_path: PathBuf::from("not-used"),
             app_users: vec![],
             init_db_sql: vec![],
+            catalogs: vec![],
+            replication: None,
         };
         Box::leak(Box::new(conf))
     }
@@ -1,7 +1,10 @@
 use std::{collections::HashMap, time::Duration};
 
 use crate::conf::PostgresConf;
-use ansilo_core::err::{bail, Result};
+use ansilo_core::{
+    err::{bail, Result},
+    web::node::PoolStatus,
+};
 use ansilo_logging::warn;
 use deadpool::managed::Object;
 
@@ -24,6 +27,7 @@ pub struct MultiUserPostgresConnectionPoolConfig {
     pub pg: &'static PostgresConf,
     pub users: Vec<String>,
     pub database: String,
+    pub min_cons_per_user: usize,
     pub max_cons_per_user: usize,
     pub connect_timeout: Duration,
 }
@@ -41,6 +45,7 @@ impl MultiUserPostgresConnectionPool {
                         pg: conf.pg,
                         user: user.into(),
                         database: conf.database.clone(),
+                        min_size: conf.min_cons_per_user,
                         max_size: conf.max_cons_per_user,
                         connect_timeout: conf.connect_timeout,
                     })?,
@@ -51,6 +56,13 @@ impl MultiUserPostgresConnectionPool {
         Ok(Self { pools })
     }
 
+    /// Establishes the minimum number of connections for each user
+    pub async fn warm_up(&self) {
+        for pool in self.pools.values() {
+            pool.warm_up().await;
+        }
+    }
+
     /// Acquires a connection which has been authenticated as the supplied user
     pub async fn acquire(&self, username: &str) -> Result<Object<LlPostgresConnectionManager>> {
         let pool = match self.pools.get(username) {
@@ -69,6 +81,11 @@ impl MultiUserPostgresConnectionPool {
 
         pool.acquire().await
     }
+
+    /// Gets the combined utilisation of the pools of each user
+    pub fn status(&self, name: &str) -> PoolStatus {
+        PoolStatus::sum(name, self.pools.values().map(|p| p.status(name)))
+    }
 }
 
 #[cfg(test)]
@@ -98,6 +115,8 @@ mod tests {
             fdw_socket_path: PathBuf::from("not-used"),
             app_users: vec![],
             init_db_sql: vec![],
+            catalogs: vec![],
+            replication: None,
         };
         Box::leak(Box::new(conf))
     }
@@ -109,6 +128,7 @@ mod tests {
             pg: conf,
             users: vec!["user1".into(), "user2".into()],
             database: "postgres".into(),
+            min_cons_per_user: 0,
             max_cons_per_user: 5,
             connect_timeout: Duration::from_secs(1),
         })
@@ -117,4 +137,29 @@ mod tests {
         assert!(pool.pools.contains_key("user1"));
         assert!(pool.pools.contains_key("user2"));
     }
+
+    #[tokio::test]
+    async fn test_postgres_connection_pool_status() {
+        let conf = test_pg_config("status");
+        let pool = MultiUserPostgresConnectionPool::new(MultiUserPostgresConnectionPoolConfig {
+            pg: conf,
+            users: vec!["user1".into(), "user2".into()],
+            database: "postgres".into(),
+            min_cons_per_user: 0,
+            max_cons_per_user: 5,
+            connect_timeout: Duration::from_secs(1),
+        })
+        .unwrap();
+
+        assert_eq!(
+            pool.status("app"),
+            PoolStatus {
+                name: "app".into(),
+                size: 0,
+                available: 0,
+                waiting: 0,
+                max_size: 10,
+            }
+        );
+    }
 }
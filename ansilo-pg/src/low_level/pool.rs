@@ -1,7 +1,10 @@
 use std::time::Duration;
 
-use ansilo_core::err::{Error, Result};
-use ansilo_logging::{debug, info};
+use ansilo_core::{
+    err::{Error, Result},
+    web::node::PoolStatus,
+};
+use ansilo_logging::{debug, info, warn};
 use deadpool::{
     async_trait,
     managed::{Manager, Object, Pool, RecycleError, RecycleResult},
@@ -20,6 +23,8 @@ pub type AppPostgresConnection = Object<LlPostgresConnectionManager>;
 pub struct LlPostgresConnectionPool {
     /// The inner deadpool pool
     pool: Pool<LlPostgresConnectionManager>,
+    /// The minimum number of connections kept in the pool
+    min_size: usize,
     /// Upon drop will shutdown background tasks
     _terminator: Sender<()>,
 }
@@ -30,6 +35,7 @@ pub struct LlPostgresConnectionPoolConfig {
     pub pg: &'static PostgresConf,
     pub user: String,
     pub database: String,
+    pub min_size: usize,
     pub max_size: usize,
     pub connect_timeout: Duration,
 }
@@ -54,15 +60,20 @@ impl LlPostgresConnectionPool {
             })?;
 
         let (terminator, receiver) = broadcast::channel(1);
-        Self::drop_old_connections(pool.clone(), receiver);
+        Self::drop_old_connections(pool.clone(), conf.min_size, receiver);
 
         Ok(Self {
             pool,
+            min_size: conf.min_size,
             _terminator: terminator,
         })
     }
 
-    fn drop_old_connections(pool: Pool<LlPostgresConnectionManager>, mut terminator: Receiver<()>) {
+    fn drop_old_connections(
+        pool: Pool<LlPostgresConnectionManager>,
+        min_size: usize,
+        mut terminator: Receiver<()>,
+    ) {
         tokio::spawn(async move {
             // TODO[low]: Make max connection age configurable
             let max_age = Duration::from_secs(3600);
@@ -75,10 +86,34 @@ impl LlPostgresConnectionPool {
 
                 debug!("Dropping old postgres connections");
                 pool.retain(|_, metrics| metrics.last_used() < max_age);
+
+                // Replace any dropped connections to keep the pool warm
+                Self::establish(&pool, min_size).await;
             }
         });
     }
 
+    /// Establishes the minimum number of connections in the pool
+    /// so they are ready before the first queries are received
+    pub async fn warm_up(&self) {
+        Self::establish(&self.pool, self.min_size).await
+    }
+
+    async fn establish(pool: &Pool<LlPostgresConnectionManager>, min_size: usize) {
+        let missing = min_size.saturating_sub(pool.status().size);
+        let mut cons = Vec::with_capacity(missing);
+
+        for _ in 0..missing {
+            match pool.get().await {
+                Ok(con) => cons.push(con),
+                Err(err) => {
+                    warn!("Failed to establish postgres connection: {:?}", err);
+                    break;
+                }
+            }
+        }
+    }
+
     /// Aquires a connection from the pool
     pub async fn acquire(&self) -> Result<AppPostgresConnection> {
         self.pool
@@ -86,6 +121,12 @@ impl LlPostgresConnectionPool {
             .await
             .map_err(|e| Error::msg(format!("Failed to acquire connection: {:?}", e)))
     }
+
+    /// Gets the utilisation of the pool
+    pub fn status(&self, name: &str) -> PoolStatus {
+        let status = self.pool.status();
+        PoolStatus::new(name, status.size, status.available, status.max_size)
+    }
 }
 
 #[derive(Debug)]
@@ -156,6 +197,8 @@ mod tests {
             fdw_socket_path: PathBuf::from("not-used"),
             app_users: vec![],
             init_db_sql: vec![],
+            catalogs: vec![],
+            replication: None,
         };
         Box::leak(Box::new(conf))
     }
@@ -167,6 +210,7 @@ mod tests {
             pg: conf,
             user: PG_SUPER_USER.into(),
             database: "postgres".into(),
+            min_size: 0,
             max_size: 5,
             connect_timeout: Duration::from_secs(1),
         })
@@ -183,6 +227,7 @@ mod tests {
             pg: conf,
             user: PG_SUPER_USER.into(),
             database: "postgres".into(),
+            min_size: 0,
             max_size: 5,
             connect_timeout: Duration::from_secs(1),
         })
@@ -205,6 +250,7 @@ mod tests {
             pg: conf,
             user: PG_SUPER_USER.into(),
             database: "postgres".into(),
+            min_size: 0,
             max_size: 5,
             connect_timeout: Duration::from_secs(1),
         })
@@ -213,4 +259,32 @@ mod tests {
         let mut con = pool.acquire().await.unwrap();
         con.execute("SELECT 3 + 4").await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_postgres_connection_pool_warm_up() {
+        ansilo_logging::init_for_tests();
+        let conf = test_pg_config("warm-up");
+        PostgresInitDb::reset(conf).unwrap();
+        PostgresInitDb::run(conf).unwrap().complete().unwrap();
+        let mut _server = PostgresServer::boot(conf).unwrap();
+        thread::spawn(move || _server.wait());
+        thread::sleep(Duration::from_secs(2));
+
+        let pool = LlPostgresConnectionPool::new(LlPostgresConnectionPoolConfig {
+            pg: conf,
+            user: PG_SUPER_USER.into(),
+            database: "postgres".into(),
+            min_size: 3,
+            max_size: 5,
+            connect_timeout: Duration::from_secs(1),
+        })
+        .unwrap();
+
+        assert_eq!(pool.pool.status().size, 0);
+
+        pool.warm_up().await;
+
+        assert_eq!(pool.pool.status().size, 3);
+        assert_eq!(pool.pool.status().available, 3);
+    }
 }
@@ -0,0 +1,292 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    process::Command,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use ansilo_core::{
+    config::HaRole,
+    err::{bail, Context, Result},
+    web::ha::HaStatus,
+};
+use ansilo_logging::{info, warn};
+use nix::sys::signal::Signal;
+
+use crate::{conf::PostgresConf, initdb::PostgresInitDb, proc::ChildProc, PG_PORT};
+
+/// The username of the user the standby replicates from the primary as
+pub const PG_REPLICATION_USER: &str = "ansiloreplicator";
+
+/// The amount of WAL retained for a standby which falls behind the primary
+const WAL_KEEP_SIZE: &str = "1GB";
+
+/// Streaming replication options of the postgres instance
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicationConf {
+    /// The address postgres listens on for replication connections
+    pub listen_address: String,
+    /// The host of the other node's postgres instance
+    pub peer_host: String,
+    /// The password of the replication user
+    pub password: String,
+    /// The name the standby identifies itself as to the primary
+    pub application_name: String,
+}
+
+impl ReplicationConf {
+    /// Gets the postgres settings required to replicate to or from the other node
+    pub(crate) fn settings(&self) -> Vec<String> {
+        vec![
+            format!("listen_addresses={}", self.listen_address),
+            "wal_level=replica".into(),
+            "hot_standby=on".into(),
+            format!("wal_keep_size={WAL_KEEP_SIZE}"),
+        ]
+    }
+
+    /// Gets the connection string used by the standby to connect to the primary
+    pub fn primary_conninfo(&self) -> String {
+        [
+            ("host", self.peer_host.as_str()),
+            ("port", &PG_PORT.to_string()),
+            ("user", PG_REPLICATION_USER),
+            ("password", self.password.as_str()),
+            ("application_name", self.application_name.as_str()),
+        ]
+        .into_iter()
+        .map(|(k, v)| format!("{k}={}", conninfo_value(v)))
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+}
+
+/// Quotes a value of a libpq connection string
+fn conninfo_value(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// The client authentication config of an instance which accepts replication connections.
+///
+/// Connections over the unix socket are trusted as the socket directory is
+/// only accessible to ansilo, while replication connections over tcp
+/// must authenticate as the replication user.
+pub(crate) fn hba_conf() -> String {
+    format!(
+        r#"# Managed by ansilo
+local   all             all                                     trust
+local   replication     all                                     trust
+host    replication     {PG_REPLICATION_USER}        all                     scram-sha-256
+"#
+    )
+}
+
+/// Writes the client authentication config to the data directory
+pub(crate) fn write_hba_conf(conf: &PostgresConf) -> Result<()> {
+    fs::write(conf.data_dir.join("pg_hba.conf"), hba_conf()).context("Failed to write pg_hba.conf")
+}
+
+/// Whether the data directory is that of a standby
+pub fn is_standby(conf: &PostgresConf) -> bool {
+    conf.data_dir.join("standby.signal").exists()
+}
+
+/// Replaces the data directory with a base backup of the primary and
+/// configures the instance to start as a standby which streams changes
+/// from the primary.
+pub fn clone_from_primary(conf: &'static PostgresConf) -> Result<()> {
+    let replication = conf
+        .replication
+        .as_ref()
+        .context("Replication is not configured")?;
+
+    PostgresInitDb::reset(conf)?;
+
+    info!(
+        "Cloning data directory from primary {}...",
+        replication.peer_host
+    );
+    let mut cmd = Command::new(conf.install_dir.join("bin/pg_basebackup"));
+    cmd.arg("-D")
+        .arg(conf.data_dir.as_os_str())
+        .args(["-h", &replication.peer_host])
+        .args(["-p", &PG_PORT.to_string()])
+        .args(["-U", PG_REPLICATION_USER])
+        .args(["-X", "stream"])
+        .arg("--checkpoint=fast")
+        .arg("--no-password")
+        .env("PGPASSWORD", &replication.password);
+
+    let status = ChildProc::new(
+        "[pg_basebackup]",
+        Signal::SIGINT,
+        Duration::from_secs(3),
+        cmd,
+    )?
+    .wait()?;
+
+    if !status.success() {
+        bail!("Failed to clone data directory from primary: {status}");
+    }
+
+    fs::write(conf.data_dir.join("standby.signal"), "")
+        .context("Failed to create standby.signal")?;
+
+    let mut auto_conf = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(conf.data_dir.join("postgresql.auto.conf"))
+        .context("Failed to open postgresql.auto.conf")?;
+    writeln!(
+        auto_conf,
+        "primary_conninfo = '{}'",
+        replication.primary_conninfo().replace('\'', "''")
+    )
+    .context("Failed to write postgresql.auto.conf")?;
+
+    Ok(())
+}
+
+/// Promotes the standby instance to the primary, waiting for the promotion to complete
+pub fn promote(conf: &PostgresConf) -> Result<()> {
+    warn!("Promoting postgres to primary...");
+    let mut cmd = Command::new(conf.install_dir.join("bin/pg_ctl"));
+    cmd.arg("promote")
+        .arg("-D")
+        .arg(conf.data_dir.as_os_str())
+        .arg("-w");
+
+    let status = ChildProc::new("[pg_ctl]", Signal::SIGINT, Duration::from_secs(3), cmd)?.wait()?;
+
+    if !status.success() {
+        bail!("Failed to promote postgres to primary: {status}");
+    }
+
+    Ok(())
+}
+
+/// The current role of the node in a high-availability pair, shared across subsystems
+#[derive(Debug, Clone)]
+pub struct HaState {
+    status: Arc<RwLock<HaStatus>>,
+}
+
+impl HaState {
+    pub fn new(node_id: impl Into<String>, role: HaRole) -> Self {
+        let state = Self {
+            status: Arc::new(RwLock::new(HaStatus {
+                node_id: node_id.into(),
+                role: HaRole::Standby,
+                primary_since: None,
+            })),
+        };
+
+        state.set_role(role);
+        state
+    }
+
+    /// Gets the current status of this node
+    pub fn status(&self) -> HaStatus {
+        self.status
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Gets the current role of this node
+    pub fn role(&self) -> HaRole {
+        self.status().role
+    }
+
+    /// Updates the current role of this node
+    pub fn set_role(&self, role: HaRole) {
+        let mut status = self.status.write().unwrap_or_else(|e| e.into_inner());
+
+        status.primary_since = match role {
+            HaRole::Primary if status.role == HaRole::Primary => status.primary_since,
+            HaRole::Primary => Some(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64,
+            ),
+            HaRole::Standby => None,
+        };
+        status.role = role;
+    }
+
+    /// Whether this node is currently the primary
+    pub fn is_primary(&self) -> bool {
+        self.role() == HaRole::Primary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_conf(password: &str) -> ReplicationConf {
+        ReplicationConf {
+            listen_address: "10.0.0.2".into(),
+            peer_host: "10.0.0.1".into(),
+            password: password.into(),
+            application_name: "node-b".into(),
+        }
+    }
+
+    #[test]
+    fn test_replication_settings() {
+        assert_eq!(
+            mock_conf("pass").settings(),
+            vec![
+                "listen_addresses=10.0.0.2",
+                "wal_level=replica",
+                "hot_standby=on",
+                "wal_keep_size=1GB",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replication_primary_conninfo() {
+        assert_eq!(
+            mock_conf("p'a\\ss word").primary_conninfo(),
+            r#"host='10.0.0.1' port='5432' user='ansiloreplicator' password='p\'a\\ss word' application_name='node-b'"#
+        );
+    }
+
+    #[test]
+    fn test_replication_hba_conf() {
+        let hba = hba_conf();
+
+        assert!(hba.contains("local   all             all"));
+        assert!(hba.contains("local   replication     all"));
+        assert!(hba.contains("host    replication     ansiloreplicator"));
+        assert!(!hba.contains("127.0.0.1"));
+    }
+
+    #[test]
+    fn test_ha_state() {
+        let state = HaState::new("node-a", HaRole::Standby);
+        let shared = state.clone();
+
+        assert_eq!(state.status().node_id, "node-a");
+        assert!(!state.is_primary());
+        assert_eq!(state.status().primary_since, None);
+
+        shared.set_role(HaRole::Primary);
+        let since = state.status().primary_since;
+
+        assert!(state.is_primary());
+        assert!(since.is_some());
+
+        // Remaining the primary does not reset when it became the primary
+        shared.set_role(HaRole::Primary);
+        assert_eq!(state.status().primary_since, since);
+
+        shared.set_role(HaRole::Standby);
+        assert_eq!(state.role(), HaRole::Standby);
+        assert_eq!(state.status().primary_since, None);
+    }
+}
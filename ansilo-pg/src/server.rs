@@ -82,6 +82,14 @@ impl PostgresServer {
         debug!("Setting postgres work_mem={work_mem}MB");
         cmd.args(["-c".into(), format!("work_mem={work_mem}MB")]);
 
+        // Listen for replication connections from the other node of a high-availability pair,
+        // these settings take precedence over the listen_addresses set above
+        if let Some(replication) = conf.replication.as_ref() {
+            for setting in replication.settings() {
+                cmd.args(["-c".into(), setting]);
+            }
+        }
+
         // Start postgres
         let mut proc = ChildProc::new("[postgres]", Signal::SIGINT, Duration::from_secs(3), cmd)
             .context("Failed to start postgres server process")?;
@@ -99,7 +107,10 @@ impl PostgresServer {
     fn wait_for_ready(output: Receiver<String>, ready: Arc<AtomicBool>) {
         thread::spawn(move || {
             while let Ok(log) = output.recv() {
-                if log.contains("ready to accept connections") {
+                // Standby instances only accept read-only connections
+                if log.contains("ready to accept connections")
+                    || log.contains("ready to accept read-only connections")
+                {
                     ready.store(true, Ordering::SeqCst);
                     break;
                 }
@@ -199,6 +210,8 @@ mod tests {
             fdw_socket_path: PathBuf::from("not-used"),
             app_users: vec![],
             init_db_sql: vec![],
+            catalogs: vec![],
+            replication: None,
         };
         Box::leak(Box::new(conf))
     }
@@ -0,0 +1,57 @@
+use ansilo_core::{data::DataType, err::Result};
+
+/// Converts the supplied data type to the matching pg type name for use in DDL
+pub fn to_pg_type_name(r#type: &DataType) -> Result<String> {
+    Ok(match r#type {
+        DataType::Int8 => "SMALLINT".into(),
+        DataType::Int16 => "SMALLINT".into(),
+        DataType::Int32 => "INTEGER".into(),
+        DataType::Int64 => "BIGINT".into(),
+        DataType::UInt8 => "SMALLINT".into(),
+        DataType::UInt16 => "INTEGER".into(),
+        DataType::UInt32 => "BIGINT".into(),
+        DataType::UInt64 => "NUMERIC".into(),
+        DataType::Float32 => "REAL".into(),
+        DataType::Float64 => "DOUBLE PRECISION".into(),
+        DataType::Decimal(_) => "NUMERIC".into(),
+        // Varchar max length is 10485760
+        DataType::Utf8String(opt) if opt.length.is_some() && opt.length.unwrap() <= 10485760 => {
+            format!("VARCHAR({})", opt.length.unwrap())
+        }
+        DataType::Utf8String(_) => "TEXT".into(),
+        //
+        DataType::Binary => "BYTEA".into(),
+        //
+        DataType::Boolean => "BOOLEAN".into(),
+        //
+        DataType::JSON => "JSONB".into(),
+        //
+        DataType::Date => "DATE".into(),
+        DataType::Time => "TIME".into(),
+        DataType::DateTime => "TIMESTAMP".into(),
+        DataType::DateTimeWithTZ => "TIMESTAMPTZ".into(),
+        //
+        DataType::Uuid => "UUID".into(),
+        DataType::Null => "BOOLEAN".into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ansilo_core::data::StringOptions;
+
+    use super::*;
+
+    #[test]
+    fn test_to_pg_type_name() {
+        assert_eq!(to_pg_type_name(&DataType::Int32).unwrap(), "INTEGER");
+        assert_eq!(
+            to_pg_type_name(&DataType::Utf8String(StringOptions::new(Some(255)))).unwrap(),
+            "VARCHAR(255)"
+        );
+        assert_eq!(
+            to_pg_type_name(&DataType::Utf8String(StringOptions::default())).unwrap(),
+            "TEXT"
+        );
+    }
+}
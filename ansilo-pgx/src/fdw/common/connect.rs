@@ -1,18 +1,26 @@
 use std::{
     collections::HashMap,
+    os::raw::c_char,
     os::unix::net::UnixStream,
     sync::{Arc, Mutex, Weak},
 };
 
-use ansilo_core::err::{bail, Context, Result};
+use ansilo_core::{
+    config::{TransferCompression, TransferEncoding},
+    err::{bail, Context, Result},
+};
 use ansilo_pg::fdw::{
     channel::IpcClientChannel,
+    fetch::FetchSizeBounds,
     proto::{AuthDataSource, ClientMessage, ServerMessage},
 };
 
 use lazy_static::lazy_static;
 use pgx::{
-    pg_sys::{DefElem, GetForeignServer, GetForeignTable, Oid},
+    pg_sys::{
+        CommonTableExpr, DefElem, GetForeignServer, GetForeignTable, Oid, PlannerInfo, Query,
+        RangeTblEntry,
+    },
     *,
 };
 
@@ -44,13 +52,28 @@ pub struct FdwIpcConnection {
     pub data_source_id: String,
     /// The IPC client used to communicate with ansilo
     pub client: Mutex<IpcClientChannel>,
+    /// The bounds on the number of rows fetched per batch
+    pub fetch_size: FetchSizeBounds,
+    /// The encoding used to transfer query results
+    pub transfer_encoding: TransferEncoding,
+    /// The compression applied to large query results
+    pub transfer_compression: TransferCompression,
 }
 
 impl FdwIpcConnection {
-    pub fn new(data_source_id: impl Into<String>, client: IpcClientChannel) -> Self {
+    pub fn new(
+        data_source_id: impl Into<String>,
+        client: IpcClientChannel,
+        fetch_size: FetchSizeBounds,
+        transfer_encoding: TransferEncoding,
+        transfer_compression: TransferCompression,
+    ) -> Self {
         let con = Self {
             data_source_id: data_source_id.into(),
             client: Mutex::new(client),
+            fetch_size,
+            transfer_encoding,
+            transfer_compression,
         };
 
         pgx::debug1!("Established ipc connection: {:?}", con);
@@ -190,20 +213,31 @@ unsafe fn get_connection(opts: ServerOptions) -> Result<Arc<FdwIpcConnection>> {
     let mut client = IpcClientChannel::new(sock);
 
     // Try authenticated using the current authentication token
+    // The backend pid identifies the session in the remote query log
     let auth = AuthDataSource::new(
         AuthContextState::get().map(|c| c.context),
         &opts.data_source,
-    );
+    )
+    .with_session(pg_sys::MyProcPid as u32);
     let response = client
         .send(ClientMessage::AuthDataSource(auth.clone()))
         .context("Failed to authenticate")?;
 
-    match response {
-        ServerMessage::AuthAccepted => {}
+    // The workload class of the user may override the fetch size of the data source
+    let fetch_size = match response {
+        ServerMessage::AuthAccepted(session) => opts
+            .fetch_size
+            .with_overrides(session.fetch_min_rows, session.fetch_max_rows),
         _ => bail!("Failed to authenticate: {:?}", response),
-    }
-
-    let con = Arc::new(FdwIpcConnection::new(opts.data_source.clone(), client));
+    };
+
+    let con = Arc::new(FdwIpcConnection::new(
+        opts.data_source.clone(),
+        client,
+        fetch_size,
+        opts.transfer_encoding,
+        opts.transfer_compression,
+    ));
     active.insert(opts.data_source.clone(), Arc::downgrade(&con));
     pgx::debug1!(
         "Successfully connected for data source {}",
@@ -213,6 +247,51 @@ unsafe fn get_connection(opts: ServerOptions) -> Result<Arc<FdwIpcConnection>> {
     Ok(con)
 }
 
+/// Gets the ids of the data sources of the foreign tables referenced by the query being planned,
+/// including those referenced by the parent queries of the current subquery
+pub(crate) unsafe fn get_query_data_sources(root: *mut PlannerInfo) -> Result<Vec<String>> {
+    let mut data_sources = vec![];
+    let mut root = root;
+
+    while !root.is_null() {
+        collect_data_sources((*root).parse, &mut data_sources)?;
+        root = (*root).parent_root;
+    }
+
+    Ok(data_sources)
+}
+
+unsafe fn collect_data_sources(query: *mut Query, data_sources: &mut Vec<String>) -> Result<()> {
+    if query.is_null() {
+        return Ok(());
+    }
+
+    for rte in PgList::<RangeTblEntry>::from_pg((*query).rtable).iter_ptr() {
+        match (*rte).rtekind {
+            pg_sys::RTEKind_RTE_RELATION
+                if (*rte).relkind == pg_sys::RELKIND_FOREIGN_TABLE as c_char =>
+            {
+                let table = GetForeignTable((*rte).relid);
+                let server = GetForeignServer((*table).serverid);
+                let opts = ServerOptions::parse(PgList::<DefElem>::from_pg((*server).options))
+                    .context("Failed to parse server options")?;
+
+                if !data_sources.contains(&opts.data_source) {
+                    data_sources.push(opts.data_source);
+                }
+            }
+            pg_sys::RTEKind_RTE_SUBQUERY => collect_data_sources((*rte).subquery, data_sources)?,
+            _ => {}
+        }
+    }
+
+    for cte in PgList::<CommonTableExpr>::from_pg((*query).cteList).iter_ptr() {
+        collect_data_sources((*cte).ctequery as *mut Query, data_sources)?;
+    }
+
+    Ok(())
+}
+
 /// Clears all current active connections
 pub fn clear_fdw_ipc_connections() {
     let mut active = ACTIVE_CONNECTIONS
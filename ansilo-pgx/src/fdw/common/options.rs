@@ -1,4 +1,8 @@
-use ansilo_core::err::{Context, Result};
+use ansilo_core::{
+    config::{TransferCompression, TransferEncoding},
+    err::{Context, Result},
+};
+use ansilo_pg::fdw::fetch::FetchSizeBounds;
 use cstr::cstr;
 use std::{env, path::PathBuf};
 
@@ -15,12 +19,22 @@ pub struct ServerOptions {
     pub data_source: String,
     /// The path of the socket
     pub socket: PathBuf,
+    /// The bounds on the number of rows fetched per batch
+    pub fetch_size: FetchSizeBounds,
+    /// The encoding used to transfer query results
+    pub transfer_encoding: TransferEncoding,
+    /// The compression applied to large query results
+    pub transfer_compression: TransferCompression,
 }
 
 impl ServerOptions {
     pub unsafe fn parse(opts: PgList<DefElem>) -> Result<Self> {
         let mut data_source = None;
         let mut socket = None;
+        let mut fetch_min_rows = None;
+        let mut fetch_max_rows = None;
+        let mut transfer_encoding = None;
+        let mut transfer_compression = None;
 
         for opt in opts.iter_ptr() {
             if strcmp((*opt).defname, cstr!("data_source").as_ptr()) == 0 {
@@ -30,6 +44,38 @@ impl ServerOptions {
             if strcmp((*opt).defname, cstr!("socket").as_ptr()) == 0 {
                 let _ = socket.insert(def_get_owned_utf8_string(opt)?);
             }
+
+            if strcmp((*opt).defname, cstr!("fetch_min_rows").as_ptr()) == 0 {
+                let _ = fetch_min_rows.insert(
+                    def_get_owned_utf8_string(opt)?
+                        .parse::<u32>()
+                        .context("Server option 'fetch_min_rows' must be an integer")?,
+                );
+            }
+
+            if strcmp((*opt).defname, cstr!("fetch_max_rows").as_ptr()) == 0 {
+                let _ = fetch_max_rows.insert(
+                    def_get_owned_utf8_string(opt)?
+                        .parse::<u32>()
+                        .context("Server option 'fetch_max_rows' must be an integer")?,
+                );
+            }
+
+            if strcmp((*opt).defname, cstr!("transfer_encoding").as_ptr()) == 0 {
+                let _ = transfer_encoding.insert(
+                    def_get_owned_utf8_string(opt)?
+                        .parse::<TransferEncoding>()
+                        .context("Invalid server option 'transfer_encoding'")?,
+                );
+            }
+
+            if strcmp((*opt).defname, cstr!("transfer_compression").as_ptr()) == 0 {
+                let _ = transfer_compression.insert(
+                    def_get_owned_utf8_string(opt)?
+                        .parse::<TransferCompression>()
+                        .context("Invalid server option 'transfer_compression'")?,
+                );
+            }
         }
 
         let data_source =
@@ -44,6 +90,9 @@ impl ServerOptions {
         Ok(Self {
             data_source,
             socket,
+            fetch_size: FetchSizeBounds::new(fetch_min_rows, fetch_max_rows),
+            transfer_encoding: transfer_encoding.unwrap_or_default(),
+            transfer_compression: transfer_compression.unwrap_or_default(),
         })
     }
 }
@@ -112,6 +161,40 @@ mod tests {
 
             assert_eq!(parsed.data_source, "data_source_id");
             assert_eq!(parsed.socket, PathBuf::from("/some/path.sock"));
+            assert_eq!(parsed.fetch_size, FetchSizeBounds::default());
+            assert_eq!(parsed.transfer_encoding, TransferEncoding::Auto);
+            assert_eq!(parsed.transfer_compression, TransferCompression::None);
+        }
+    }
+
+    #[pg_test]
+    fn test_fdw_common_server_options_parse_fetch_size() {
+        unsafe {
+            let mut opts = PgList::<DefElem>::new();
+            opts.push(makeDefElem(
+                cstr!("data_source").as_ptr() as _,
+                makeString(cstr!("data_source_id").as_ptr() as _) as _,
+                0,
+            ));
+            opts.push(makeDefElem(
+                cstr!("socket").as_ptr() as _,
+                makeString(cstr!("/some/path.sock").as_ptr() as _) as _,
+                0,
+            ));
+            opts.push(makeDefElem(
+                cstr!("fetch_min_rows").as_ptr() as _,
+                makeString(cstr!("5").as_ptr() as _) as _,
+                0,
+            ));
+            opts.push(makeDefElem(
+                cstr!("fetch_max_rows").as_ptr() as _,
+                makeString(cstr!("500").as_ptr() as _) as _,
+                0,
+            ));
+
+            let parsed = ServerOptions::parse(opts).unwrap();
+
+            assert_eq!(parsed.fetch_size, FetchSizeBounds::new(Some(5), Some(500)));
         }
     }
 
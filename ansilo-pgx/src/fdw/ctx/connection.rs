@@ -6,7 +6,9 @@ use ansilo_core::{
     err::{anyhow, Context, Error, Result},
     sqlil,
 };
-use ansilo_pg::fdw::proto::{ClientMessage, EntityDiscoverOptions, OperationCost, ServerMessage};
+use ansilo_pg::fdw::proto::{
+    ClientMessage, EntityDiscoverOptions, OperationCost, QueryRuleCheck, ServerMessage,
+};
 use pgx::pg_sys::Oid;
 
 use crate::{
@@ -85,6 +87,29 @@ impl FdwContext {
         Ok(base_cost)
     }
 
+    /// Checks the scan of the entity against the query rules of the current user
+    ///
+    /// If the scan is denied, the error contains the reason from the server as is.
+    pub fn check_query_rules(
+        &mut self,
+        has_conditions: bool,
+        data_sources: Vec<String>,
+    ) -> Result<()> {
+        let res = self
+            .send(ClientMessage::CheckQueryRules(QueryRuleCheck {
+                entity: self.entity.clone(),
+                has_conditions,
+                data_sources,
+            }))
+            .context("Check Query Rules")?;
+
+        match res {
+            ServerMessage::QueryRulesPassed => Ok(()),
+            ServerMessage::Error(message) => Err(anyhow!(message)),
+            _ => Err(unexpected_response(res).context("Check Query Rules")),
+        }
+    }
+
     pub fn get_row_id_exprs(&mut self, alias: &str) -> Result<Vec<(sqlil::Expr, DataType)>> {
         let res = self
             .send(ClientMessage::GetRowIds(sqlil::EntitySource::new(
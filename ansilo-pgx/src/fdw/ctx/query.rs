@@ -1,12 +1,16 @@
-use std::{cmp, collections::HashMap, rc::Rc, sync::Arc};
+use std::{cell::RefCell, cmp, collections::HashMap, rc::Rc, sync::Arc, time::Instant};
 
 use ansilo_core::{
+    config::{TransferCompression, TransferEncoding},
     data::{DataType, DataValue},
     err::{anyhow, Context, Error, Result},
     sqlil,
 };
 use ansilo_pg::fdw::{
+    columnar::ColumnarBatch,
+    compression,
     data::{DataWriter, LoggedQuery, QueryHandle, QueryHandleWriter, ResultSet, ResultSetReader},
+    fetch::AdaptiveFetchSize,
     proto::{
         BulkInsertQueryOperation, ClientMessage, ClientQueryMessage, DeleteQueryOperation,
         InsertQueryOperation, OperationCost, QueryId, QueryInputStructure, QueryOperation,
@@ -24,6 +28,14 @@ use serde::{Deserialize, Serialize};
 
 use crate::{fdw::common::FdwIpcConnection, sqlil::ConversionContext};
 
+/// Scans which are expected to return at least this many rows
+/// transfer their results in columnar batches
+const COLUMNAR_MIN_ROWS: u64 = 10_000;
+
+/// Queries which are expected to return at least this many bytes
+/// request their results to be compressed, if enabled on the data source
+const COMPRESSION_MIN_RESULT_BYTES: u64 = 1024 * 1024;
+
 /// Query-specific state for the FDW used during query planning and execution
 /// Ideally we should have seperate structs for planning/execution.
 pub struct FdwQueryContext {
@@ -35,6 +47,12 @@ pub struct FdwQueryContext {
     query_writer: Option<QueryHandleWriter<FdwQueryHandle>>,
     /// The current result set reader
     result_set: Option<ResultSetReader<FdwResultSet>>,
+    /// The batch size of the current result set
+    fetch_size: Option<Rc<RefCell<AdaptiveFetchSize>>>,
+    /// The number of data values read from the current result set
+    values_read: usize,
+    /// The current result set reader if results are transferred in columnar batches
+    columnar_result_set: Option<FdwColumnarResultSet>,
     /// Whether the query has been executed
     executed: bool,
     /// Max bulk insert size
@@ -76,12 +94,32 @@ pub(crate) struct FdwQueryHandle {
     pub query_input: QueryInputStructure,
 }
 
+/// Reads the result set of a query in columnar batches
+pub(crate) struct FdwColumnarResultSet {
+    /// The connection to ansilo
+    connection: QueryScopedConnection,
+    /// The types of each column in the result set
+    types: Vec<DataType>,
+    /// Determines the number of rows in each batch
+    fetch_size: AdaptiveFetchSize,
+    /// The current batch of rows
+    batch: Option<ColumnarBatch>,
+    /// Whether all rows have been retrieved
+    done: bool,
+}
+
 #[derive(Clone)]
 pub struct FdwResultSet {
     /// The connection to ansilo
     connection: QueryScopedConnection,
     /// The result set output structure
     pub row_structure: RowStructure,
+    /// Determines the size of each batch read from the result set
+    fetch_size: Rc<RefCell<AdaptiveFetchSize>>,
+    /// The current batch of result data
+    batch: Vec<u8>,
+    /// The position of the next unread byte in the current batch
+    pos: usize,
 }
 
 impl FdwQueryContext {
@@ -100,6 +138,9 @@ impl FdwQueryContext {
             q: query,
             query_writer: None,
             result_set: None,
+            fetch_size: None,
+            values_read: 0,
+            columnar_result_set: None,
             executed: false,
             max_bulk_query_size: None,
             supports_batching: None,
@@ -285,7 +326,33 @@ impl FdwQueryContext {
         let result_set = writer.inner_mut().execute_query()?;
         let row_structure = result_set.row_structure.clone();
 
-        self.result_set = Some(ResultSetReader::new(result_set)?);
+        // Seed the batch size using the estimated row width of the entity
+        let fetch_size =
+            AdaptiveFetchSize::new(self.connection.inner().fetch_size, self.base_cost.row_width);
+
+        let algorithm = self.connection.inner().transfer_compression;
+        if algorithm != TransferCompression::None && self.use_compression() {
+            self.connection
+                .send(ClientQueryMessage::SetCompression(algorithm))
+                .and_then(|res| match res {
+                    ServerQueryMessage::CompressionSet => Ok(()),
+                    _ => return Err(unexpected_response(res)),
+                })
+                .context("Failed to set result compression")?;
+        }
+
+        if self.use_columnar() {
+            self.fetch_size = None;
+            self.result_set = None;
+            self.columnar_result_set = Some(FdwColumnarResultSet::new(result_set, fetch_size));
+        } else {
+            result_set.fetch_size.replace(fetch_size);
+            self.fetch_size = Some(Rc::clone(&result_set.fetch_size));
+            self.result_set = Some(ResultSetReader::new(result_set)?);
+            self.columnar_result_set = None;
+        }
+
+        self.values_read = 0;
         self.executed = true;
 
         Ok(row_structure)
@@ -303,11 +370,43 @@ impl FdwQueryContext {
         Ok(affected_rows)
     }
 
+    /// Whether the results of the query are transferred in columnar batches
+    fn use_columnar(&self) -> bool {
+        match self.connection.inner().transfer_encoding {
+            TransferEncoding::Row => false,
+            TransferEncoding::Columnar => true,
+            TransferEncoding::Auto => self.retrieved_rows.unwrap_or(0) >= COLUMNAR_MIN_ROWS,
+        }
+    }
+
+    /// Whether the query is expected to return enough data to benefit from compression
+    fn use_compression(&self) -> bool {
+        let rows = self.retrieved_rows.unwrap_or(0);
+        let row_width = self.base_cost.row_width.unwrap_or(1) as u64;
+
+        rows.saturating_mul(row_width) >= COMPRESSION_MIN_RESULT_BYTES
+    }
+
     /// Reads the next data value from the result set of this query
     pub fn read_result_data(&mut self) -> Result<Option<DataValue>> {
+        if let Some(columnar) = self.columnar_result_set.as_mut() {
+            return columnar.read_data_value();
+        }
+
         let reader = self.result_set.as_mut().context("Query not executed")?;
+        let value = reader.read_data_value()?;
 
-        reader.read_data_value()
+        // Track the number of rows read so the batch size can adapt to the row width
+        let cols = reader.get_structure().cols.len();
+        if let (Some(_), Some(fetch_size)) = (&value, &self.fetch_size) {
+            self.values_read += 1;
+
+            if cols > 0 && self.values_read % cols == 0 {
+                fetch_size.borrow_mut().observe_row();
+            }
+        }
+
+        Ok(value)
     }
 
     /// Returns whether the query has been executed
@@ -479,6 +578,9 @@ impl FdwQueryContext {
             ),
             query_writer: None,
             result_set: None,
+            fetch_size: None,
+            values_read: 0,
+            columnar_result_set: None,
             executed: false,
             should_discard: true,
             max_bulk_query_size: self.max_bulk_query_size,
@@ -565,10 +667,9 @@ impl QueryHandle for FdwQueryHandle {
         self.connection
             .send(ClientQueryMessage::ExecuteQuery)
             .and_then(|res| match res {
-                ServerQueryMessage::ResultSet(row_structure) => Ok(FdwResultSet {
-                    connection: self.connection.clone(),
-                    row_structure,
-                }),
+                ServerQueryMessage::ResultSet(row_structure) => {
+                    Ok(FdwResultSet::new(self.connection.clone(), row_structure))
+                }
                 _ => return Err(unexpected_response(res)),
             })
             .context("Failed to execute query")
@@ -595,17 +696,118 @@ impl ResultSet for FdwResultSet {
     }
 
     fn read(&mut self, buff: &mut [u8]) -> Result<usize> {
-        self.connection
-            .send(ClientQueryMessage::Read(buff.len() as _))
+        if self.pos >= self.batch.len() {
+            self.read_batch()?;
+        }
+
+        let read = cmp::min(buff.len(), self.batch.len() - self.pos);
+        buff[..read].copy_from_slice(&self.batch[self.pos..(self.pos + read)]);
+        self.pos += read;
+
+        Ok(read)
+    }
+}
+
+impl FdwResultSet {
+    pub(crate) fn new(connection: QueryScopedConnection, row_structure: RowStructure) -> Self {
+        let fetch_size = AdaptiveFetchSize::new(connection.inner().fetch_size, None);
+
+        Self {
+            connection,
+            row_structure,
+            fetch_size: Rc::new(RefCell::new(fetch_size)),
+            batch: vec![],
+            pos: 0,
+        }
+    }
+
+    /// Retrieves the next batch of result data, sized according
+    /// to the row width and latency of the previous batches
+    fn read_batch(&mut self) -> Result<()> {
+        let requested = self.fetch_size.borrow().batch_bytes();
+        let start = Instant::now();
+
+        let data = self
+            .connection
+            .send(ClientQueryMessage::Read(requested as _))
             .and_then(|res| match res {
-                ServerQueryMessage::ReadData(data) => {
-                    let read = cmp::min(buff.len(), data.len());
-                    buff[..read].copy_from_slice(&data[..read]);
-                    Ok(read)
+                ServerQueryMessage::ReadData(data) => Ok(data),
+                ServerQueryMessage::CompressedReadData(c, data) => compression::decompress(c, data),
+                _ => return Err(unexpected_response(res)),
+            })
+            .context("Failed to read from result set")?;
+
+        self.fetch_size
+            .borrow_mut()
+            .observe_batch(requested, data.len(), start.elapsed());
+        self.batch = data;
+        self.pos = 0;
+
+        Ok(())
+    }
+}
+
+impl FdwColumnarResultSet {
+    fn new(result_set: FdwResultSet, fetch_size: AdaptiveFetchSize) -> Self {
+        Self {
+            types: result_set.row_structure.types(),
+            connection: result_set.connection,
+            fetch_size,
+            batch: None,
+            done: false,
+        }
+    }
+
+    /// Reads the next data value from the result set
+    fn read_data_value(&mut self) -> Result<Option<DataValue>> {
+        loop {
+            if let Some(batch) = self.batch.as_mut() {
+                if let Some(val) = batch.read_data_value()? {
+                    return Ok(Some(val));
+                }
+            }
+
+            if self.done {
+                return Ok(None);
+            }
+
+            self.read_batch()?;
+        }
+    }
+
+    /// Retrieves the next batch of rows
+    fn read_batch(&mut self) -> Result<()> {
+        let requested = self.fetch_size.batch_rows();
+        let start = Instant::now();
+
+        let data = self
+            .connection
+            .send(ClientQueryMessage::ReadColumnar(requested))
+            .and_then(|res| match res {
+                ServerQueryMessage::ColumnarData(data) => Ok(data),
+                ServerQueryMessage::CompressedColumnarData(c, data) => {
+                    compression::decompress(c, data)
                 }
                 _ => return Err(unexpected_response(res)),
             })
-            .context("Failed to read from result set")
+            .context("Failed to read from result set")?;
+
+        if data.is_empty() {
+            self.batch = None;
+            self.done = true;
+            return Ok(());
+        }
+
+        let bytes = data.len();
+        let batch = ColumnarBatch::decode(data, self.types.clone())?;
+        let rows = batch.num_rows() as u32;
+
+        self.fetch_size
+            .observe_rows(requested, rows, bytes, start.elapsed());
+        self.batch = Some(batch);
+        self.done = rows < requested;
+
+        Ok(())
     }
 }
 
@@ -77,6 +77,20 @@ pub unsafe extern "C" fn get_foreign_rel_size(
 
     let planner = pg_transaction_scoped(PlannerContext::base_rel(root, baserel));
 
+    // Enforce the query rules of the current user before planning the scan.
+    // Only the conditions restricting this relation itself are considered,
+    // a join condition does not count as the relation may still be scanned
+    // in full to perform the join.
+    let baserel_conds = PgList::<RestrictInfo>::from_pg((*baserel).baserestrictinfo);
+    let data_sources = match common::get_query_data_sources(root) {
+        Ok(data_sources) => data_sources,
+        Err(err) => pgx::error!("Failed to get the data sources of the query: {:?}", err),
+    };
+    match ctx.check_query_rules(!baserel_conds.is_empty(), data_sources) {
+        Ok(_) => {}
+        Err(err) => pgx::error!("{}", err),
+    }
+
     let mut base_cost = ctx.estimate_size().unwrap();
 
     // Default row number if not supplied from data source
@@ -91,7 +105,6 @@ pub unsafe extern "C" fn get_foreign_rel_size(
     // Apply base cost defaults
     query.base_cost.default_to(&base_cost);
 
-    let baserel_conds = PgList::<RestrictInfo>::from_pg((*baserel).baserestrictinfo);
     apply_query_conds(
         &ctx,
         &mut query,
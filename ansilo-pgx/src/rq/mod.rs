@@ -45,11 +45,11 @@ mod tests;
 
 // We also will cache prepared remote queries so they can be reused cheaply
 //
-// The cache key structure is (server_name, query_sql, param_types)
+// The cache key structure is (server_name, query_sql, param_types, write)
 lazy_static! {
     static ref PREPARED_QUERIES: Mutex<
         HashMap<
-            (String, String, Vec<DataType>),
+            (String, String, Vec<DataType>, bool),
             (Weak<FdwIpcConnection>, QueryId, QueryInputStructure),
         >,
     > = Mutex::new(HashMap::new());
@@ -118,7 +118,7 @@ fn try_remote_execute(
     query: String,
     params: Vec<DataValue>,
 ) -> Result<Option<i64>> {
-    let mut query = prepare_query(server_name, query, params)?;
+    let mut query = prepare_query(server_name, query, params, true)?;
 
     Ok(query.execute_modify()?.map(|i| i as i64))
 }
@@ -232,7 +232,7 @@ fn try_remote_query(
     query: String,
     params: Vec<DataValue>,
 ) -> Result<ResultSetReader<FdwResultSet>> {
-    let mut query = prepare_query(server_name, query, params)?;
+    let mut query = prepare_query(server_name, query, params, false)?;
 
     let result_set = query.execute_query()?;
     let reader = result_set.reader()?;
@@ -284,10 +284,13 @@ unsafe fn parse_params(fcinfo: FunctionCallInfo, variadic_start: u32) -> Result<
 }
 
 /// Prepares the supplied query, sends all query params and returns the query handle
+///
+/// The write flag determines whether the query requires a grant with write access.
 fn prepare_query(
     server_name: String,
     query: String,
     params: Vec<DataValue>,
+    write: bool,
 ) -> Result<FdwQueryHandle> {
     let param_types = params.iter().map(|p| p.r#type()).collect::<Vec<_>>();
     let param_exprs = param_types
@@ -297,7 +300,12 @@ fn prepare_query(
         .collect::<Vec<_>>();
 
     // Now check if we have a cached prepared query that can be reused
-    let cache_key = (server_name.clone(), query.clone(), param_types.clone());
+    let cache_key = (
+        server_name.clone(),
+        query.clone(),
+        param_types.clone(),
+        write,
+    );
     let entry = {
         PREPARED_QUERIES
             .lock()
@@ -329,7 +337,7 @@ fn prepare_query(
                 unsafe { crate::fdw::common::try_connect_server_by_name(&server_name)?.connection };
 
             let res = con
-                .send(ClientMessage::CreateStringQuery(query, param_exprs))
+                .send(ClientMessage::CreateStringQuery(query, param_exprs, write))
                 .context("Failed to create remote query")?;
 
             let query_id = match res {
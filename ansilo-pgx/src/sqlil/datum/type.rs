@@ -4,6 +4,8 @@ use ansilo_core::{
 };
 use pgx::pg_sys;
 
+pub use ansilo_pg::types::to_pg_type_name;
+
 /// Converts the supplied postgres type oid to the equivalent mapped DataType
 pub fn from_pg_type(type_oid: pg_sys::Oid) -> Result<DataType> {
     match type_oid {
@@ -69,42 +71,6 @@ pub fn into_pg_type(r#type: &DataType) -> Result<pg_sys::Oid> {
     }
 }
 
-/// Converts the supplied data type to the matching pg type name for use in DDL
-pub fn to_pg_type_name(r#type: &DataType) -> Result<String> {
-    Ok(match r#type {
-        DataType::Int8 => "SMALLINT".into(),
-        DataType::Int16 => "SMALLINT".into(),
-        DataType::Int32 => "INTEGER".into(),
-        DataType::Int64 => "BIGINT".into(),
-        DataType::UInt8 => "SMALLINT".into(),
-        DataType::UInt16 => "INTEGER".into(),
-        DataType::UInt32 => "BIGINT".into(),
-        DataType::UInt64 => "NUMERIC".into(),
-        DataType::Float32 => "REAL".into(),
-        DataType::Float64 => "DOUBLE PRECISION".into(),
-        DataType::Decimal(_) => "NUMERIC".into(),
-        // Varchar max length is 10485760
-        DataType::Utf8String(opt) if opt.length.is_some() && opt.length.unwrap() <= 10485760 => {
-            format!("VARCHAR({})", opt.length.unwrap())
-        }
-        DataType::Utf8String(_) => "TEXT".into(),
-        //
-        DataType::Binary => "BYTEA".into(),
-        //
-        DataType::Boolean => "BOOLEAN".into(),
-        //
-        DataType::JSON => "JSONB".into(),
-        //
-        DataType::Date => "DATE".into(),
-        DataType::Time => "TIME".into(),
-        DataType::DateTime => "TIMESTAMP".into(),
-        DataType::DateTimeWithTZ => "TIMESTAMPTZ".into(),
-        //
-        DataType::Uuid => "UUID".into(),
-        DataType::Null => "BOOLEAN".into(),
-    })
-}
-
 #[cfg(test)]
 mod pg_tests {
     use super::*;
@@ -5,8 +5,13 @@ use std::{
     sync::Arc,
 };
 
-use ansilo_core::{data::DataType, err::Result, sqlil};
-use ansilo_pg::fdw::channel::IpcClientChannel;
+use ansilo_core::{
+    config::{TransferCompression, TransferEncoding},
+    data::DataType,
+    err::Result,
+    sqlil,
+};
+use ansilo_pg::fdw::{channel::IpcClientChannel, fetch::FetchSizeBounds};
 use pgx::{
     pg_sys::{self, Node},
     *,
@@ -37,7 +42,13 @@ pub(super) fn convert_simple_expr_with_context(
         let (node, planner) = parse_pg_expr(select, params);
 
         let client = IpcClientChannel::new(UnixStream::from_raw_fd(1234));
-        let con = FdwIpcConnection::new("data_source", client);
+        let con = FdwIpcConnection::new(
+            "data_source",
+            client,
+            FetchSizeBounds::default(),
+            TransferEncoding::default(),
+            TransferCompression::default(),
+        );
 
         let fdw = FdwContext::new(
             Arc::new(con),
@@ -1,9 +1,14 @@
-use std::{fs, net::SocketAddr, path::Path};
+use std::{fs, io, net::SocketAddr, path::Path, sync::Arc, time::SystemTime};
 
-use ansilo_core::err::{Context, Result};
-use tokio_native_tls::{
-    native_tls::{self, Protocol},
-    TlsAcceptor,
+use ansilo_core::err::{bail, Context, Result};
+use tokio_native_tls::native_tls::{self, Protocol};
+use tokio_rustls::rustls::{
+    self,
+    server::{
+        AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, ClientCertVerified,
+        ClientCertVerifier,
+    },
+    Certificate, DistinguishedNames, RootCertStore,
 };
 
 use crate::handler::ConnectionHandler;
@@ -23,15 +28,95 @@ pub struct ProxyConf {
 pub struct TlsConf {
     /// Server cert and key
     pub identity: native_tls::Identity,
+    /// If set, client certificates are verified during the handshake.
+    /// As native-tls does not support verifying client certificates these
+    /// connections are accepted using rustls.
+    pub client_auth: Option<Arc<rustls::ServerConfig>>,
+}
+
+/// Accepts TLS connections
+pub enum TlsAcceptor {
+    Native(tokio_native_tls::TlsAcceptor),
+    ClientAuth(tokio_rustls::TlsAcceptor),
 }
 
 impl TlsConf {
     pub fn new(private_key_path: &Path, certificate_path: &Path) -> Result<Self> {
         Ok(Self {
             identity: Self::server_identity(private_key_path, certificate_path)?,
+            client_auth: None,
         })
     }
 
+    /// Requires clients to authenticate with a certificate signed by one of the
+    /// CA's in the bundle and issued to one of the allowed names.
+    /// If not required, clients may also connect without a certificate.
+    pub fn with_client_auth(
+        private_key_path: &Path,
+        certificate_path: &Path,
+        ca_bundle_path: &Path,
+        allowed_names: &[String],
+        required: bool,
+    ) -> Result<Self> {
+        if allowed_names.is_empty() {
+            bail!("At least one allowed name is required to verify TLS client certificates");
+        }
+
+        for name in allowed_names {
+            webpki::DnsNameRef::try_from_ascii_str(name)
+                .with_context(|| format!("Invalid allowed TLS client name '{name}'"))?;
+        }
+
+        let mut conf = Self::new(private_key_path, certificate_path)?;
+
+        let certs = read_pem(certificate_path, rustls_pemfile::certs)
+            .context("Failed to read TLS certificate")?
+            .into_iter()
+            .map(rustls::Certificate)
+            .collect::<Vec<_>>();
+
+        let key = read_pem(private_key_path, rustls_pemfile::pkcs8_private_keys)
+            .context("Failed to read TLS private key")?
+            .into_iter()
+            .next()
+            .map(rustls::PrivateKey)
+            .context("TLS private key must be pkcs8 encoded")?;
+
+        let mut roots = RootCertStore::empty();
+        for ca in read_pem(ca_bundle_path, rustls_pemfile::certs)
+            .context("Failed to read TLS client CA bundle")?
+        {
+            roots
+                .add(&rustls::Certificate(ca))
+                .context("Failed to parse TLS client CA certificate")?;
+        }
+
+        if roots.is_empty() {
+            bail!(
+                "TLS client CA bundle {} does not contain any certificates",
+                ca_bundle_path.display()
+            );
+        }
+
+        let verifier = Arc::new(AllowedNamesVerifier {
+            inner: if required {
+                AllowAnyAuthenticatedClient::new(roots)
+            } else {
+                AllowAnyAnonymousOrAuthenticatedClient::new(roots)
+            },
+            allowed_names: allowed_names.to_vec(),
+        });
+
+        let server = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(certs, key)
+            .context("Failed to parse TLS cert and key")?;
+
+        conf.client_auth = Some(Arc::new(server));
+        Ok(conf)
+    }
+
     fn server_identity(
         private_key_path: &Path,
         certificate_path: &Path,
@@ -46,14 +131,85 @@ impl TlsConf {
     }
 
     pub fn acceptor(&self) -> Result<TlsAcceptor> {
+        if let Some(server) = self.client_auth.as_ref() {
+            return Ok(TlsAcceptor::ClientAuth(Arc::clone(server).into()));
+        }
+
         native_tls::TlsAcceptor::builder(self.identity.clone())
             .min_protocol_version(Some(Protocol::Tlsv11))
             .build()
-            .map(|a| a.into())
+            .map(|a| TlsAcceptor::Native(a.into()))
             .context("Failed to build TLS acceptor")
     }
 }
 
+/// Verifies the client certificate is signed by a trusted CA and
+/// was issued to one of the allowed names
+struct AllowedNamesVerifier {
+    inner: Arc<dyn ClientCertVerifier>,
+    allowed_names: Vec<String>,
+}
+
+impl ClientCertVerifier for AllowedNamesVerifier {
+    fn offer_client_auth(&self) -> bool {
+        self.inner.offer_client_auth()
+    }
+
+    fn client_auth_mandatory(&self) -> Option<bool> {
+        self.inner.client_auth_mandatory()
+    }
+
+    fn client_auth_root_subjects(&self) -> Option<DistinguishedNames> {
+        self.inner.client_auth_root_subjects()
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        now: SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        let verified = self
+            .inner
+            .verify_client_cert(end_entity, intermediates, now)?;
+
+        verify_allowed_name(end_entity, &self.allowed_names)?;
+
+        Ok(verified)
+    }
+}
+
+/// Checks the certificate has a DNS subject alternative name matching one of the allowed names
+fn verify_allowed_name(cert: &Certificate, allowed_names: &[String]) -> Result<(), rustls::Error> {
+    let cert = webpki::EndEntityCert::try_from(cert.0.as_slice())
+        .map_err(|err| rustls::Error::InvalidCertificateData(err.to_string()))?;
+
+    let allowed = allowed_names.iter().any(|name| {
+        webpki::DnsNameRef::try_from_ascii_str(name)
+            .map(|name| cert.verify_is_valid_for_dns_name(name).is_ok())
+            .unwrap_or(false)
+    });
+
+    if !allowed {
+        return Err(rustls::Error::InvalidCertificateData(
+            "Client certificate was not issued to an allowed name".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn read_pem(
+    path: &Path,
+    parse: fn(&mut dyn io::BufRead) -> io::Result<Vec<Vec<u8>>>,
+) -> Result<Vec<Vec<u8>>> {
+    let file =
+        fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+
+    parse(&mut io::BufReader::new(file))
+        .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
 /// Connection handlers
 pub struct HandlerConf {
     pub(crate) postgres: Box<dyn ConnectionHandler>,
@@ -74,3 +230,88 @@ impl HandlerConf {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_cert(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/mock-certs")
+            .join(name)
+    }
+
+    #[test]
+    fn test_tls_conf_with_client_auth() {
+        let conf = TlsConf::with_client_auth(
+            &mock_cert("mock.test-key.pem"),
+            &mock_cert("mock.test.pem"),
+            &mock_cert("rootCA.pem"),
+            &["peer.test".to_string()],
+            true,
+        )
+        .unwrap();
+
+        assert!(conf.client_auth.is_some());
+        assert!(matches!(
+            conf.acceptor().unwrap(),
+            TlsAcceptor::ClientAuth(_)
+        ));
+    }
+
+    #[test]
+    fn test_tls_conf_with_client_auth_invalid_ca_bundle() {
+        TlsConf::with_client_auth(
+            &mock_cert("mock.test-key.pem"),
+            &mock_cert("mock.test.pem"),
+            &mock_cert("missing.pem"),
+            &["peer.test".to_string()],
+            true,
+        )
+        .unwrap_err();
+
+        // The private key does not contain any certificates
+        TlsConf::with_client_auth(
+            &mock_cert("mock.test-key.pem"),
+            &mock_cert("mock.test.pem"),
+            &mock_cert("mock.test-key.pem"),
+            &["peer.test".to_string()],
+            true,
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn test_tls_conf_with_client_auth_invalid_allowed_names() {
+        TlsConf::with_client_auth(
+            &mock_cert("mock.test-key.pem"),
+            &mock_cert("mock.test.pem"),
+            &mock_cert("rootCA.pem"),
+            &[],
+            true,
+        )
+        .unwrap_err();
+
+        TlsConf::with_client_auth(
+            &mock_cert("mock.test-key.pem"),
+            &mock_cert("mock.test.pem"),
+            &mock_cert("rootCA.pem"),
+            &["invalid name".to_string()],
+            true,
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn test_verify_allowed_name() {
+        let cert = read_pem(&mock_cert("mock.test.pem"), rustls_pemfile::certs)
+            .unwrap()
+            .into_iter()
+            .map(Certificate)
+            .next()
+            .unwrap();
+
+        verify_allowed_name(&cert, &["other.test".into(), "mock.test".into()]).unwrap();
+        verify_allowed_name(&cert, &["other.test".into()]).unwrap_err();
+    }
+}
@@ -2,7 +2,7 @@ use ansilo_core::err::{bail, Result};
 use tokio::io::{AsyncRead, AsyncWrite};
 
 use crate::{
-    conf::ProxyConf,
+    conf::{ProxyConf, TlsAcceptor},
     peekable::Peekable,
     proto::{http1::Http1Protocol, http2::Http2Protocol, postgres::PostgresProtocol, Protocol},
 };
@@ -41,17 +41,29 @@ impl<S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static> Connection<S> {
         }
 
         // Otherwise, for http, we require TLS transport layer
-        let tls = self.conf.tls.as_ref().unwrap().acceptor()?;
-        let mut con = Peekable::new(tls.accept(self.inner).await?);
+        match self.conf.tls.as_ref().unwrap().acceptor()? {
+            TlsAcceptor::Native(tls) => {
+                Self::handle_http(self.conf, Peekable::new(tls.accept(self.inner).await?)).await
+            }
+            TlsAcceptor::ClientAuth(tls) => {
+                Self::handle_http(self.conf, Peekable::new(tls.accept(self.inner).await?)).await
+            }
+        }
+    }
 
+    /// Handle http connection over the TLS transport layer
+    async fn handle_http<T: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static>(
+        conf: &'static ProxyConf,
+        mut con: Peekable<T>,
+    ) -> Result<()> {
         // Now check for http/2, http/1
         // Importantly we check for http/1 first as it has the smaller peek-ahead length
-        let mut http1 = Http1Protocol::new(self.conf);
+        let mut http1 = Http1Protocol::new(conf);
         if let Ok(true) = http1.matches(&mut con).await {
             return http1.handle(con).await;
         }
 
-        let mut http2 = Http2Protocol::new(self.conf);
+        let mut http2 = Http2Protocol::new(conf);
         if let Ok(true) = http2.matches(&mut con).await {
             return http2.handle(con).await;
         }
@@ -92,8 +104,8 @@ mod tests {
     };
 
     use crate::test::{
-        create_socket_pair, mock_config_no_tls, mock_config_tls, mock_tls_connector,
-        MockConnectionHandler,
+        create_socket_pair, mock_config_no_tls, mock_config_tls, mock_config_tls_client_auth,
+        mock_tls_connector, MockConnectionHandler,
     };
 
     use super::*;
@@ -365,4 +377,67 @@ mod tests {
             }
         )
     }
+
+    #[tokio::test]
+    async fn test_connection_with_tls_client_auth_optional_without_client_cert() {
+        let conf = mock_config_tls_client_auth(false);
+        let (client, connection) = mock_connection(conf);
+
+        // Process server-side TLS handshake in server task
+        let server = tokio::spawn(async move {
+            connection.handle().await.unwrap();
+        });
+
+        // Perform TLS-hanshake without a client certificate
+        let mut client_con = mock_tls_connector()
+            .connect("mock.test".try_into().unwrap(), client)
+            .await
+            .unwrap();
+
+        // Send HTTP/1 POST request
+        client_con.write_all(b"POST /abc HTTP/1.1").await.unwrap();
+        client_con.flush().await.unwrap();
+
+        // Wait for server-side to finish processing
+        server.await.unwrap();
+        assert_eq!(
+            ReceivedConnections::from(conf),
+            ReceivedConnections {
+                postgres: 0,
+                http2: 0,
+                http1: 1
+            }
+        )
+    }
+
+    #[tokio::test]
+    async fn test_connection_with_tls_client_auth_required_without_client_cert() {
+        let conf = mock_config_tls_client_auth(true);
+        let (client, connection) = mock_connection(conf);
+
+        // Process server-side TLS handshake in server task
+        let server = tokio::spawn(async move {
+            connection.handle().await.unwrap_err();
+        });
+
+        // The server rejects the handshake as no client certificate is presented
+        if let Ok(mut client_con) = mock_tls_connector()
+            .connect("mock.test".try_into().unwrap(), client)
+            .await
+        {
+            let _ = client_con.write_all(b"POST /abc HTTP/1.1").await;
+            let _ = client_con.flush().await;
+        }
+
+        // Wait for server-side to finish processing
+        server.await.unwrap();
+        assert_eq!(
+            ReceivedConnections::from(conf),
+            ReceivedConnections {
+                postgres: 0,
+                http2: 0,
+                http1: 0
+            }
+        )
+    }
 }
@@ -2,7 +2,11 @@ use ansilo_core::err::{bail, Result};
 use async_trait::async_trait;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use crate::{conf::ProxyConf, peekable::Peekable, stream::Stream};
+use crate::{
+    conf::{ProxyConf, TlsAcceptor},
+    peekable::Peekable,
+    stream::{IOStream, Stream},
+};
 
 use super::Protocol;
 
@@ -82,21 +86,13 @@ impl<S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static> Protocol<S> for
             con.flush().await?;
 
             // Process TLS
-            let con = self
-                .conf
-                .tls
-                .as_ref()
-                .unwrap()
-                .acceptor()?
-                .accept(con)
-                .await?;
+            let con: Box<dyn IOStream> = match self.conf.tls.as_ref().unwrap().acceptor()? {
+                TlsAcceptor::Native(tls) => Box::new(Stream(tls.accept(con).await?)),
+                TlsAcceptor::ClientAuth(tls) => Box::new(Stream(tls.accept(con).await?)),
+            };
 
             // At this point the client should send StartupMessage
-            self.conf
-                .handlers
-                .postgres
-                .handle(Box::new(Stream(con)))
-                .await
+            self.conf.handlers.postgres.handle(con).await
         } else {
             // If TLS is disabled, reply N to SSLRequest, if it was received
             // We peek first as we do not want to accidentally consume StartupMessage
@@ -35,22 +35,32 @@ impl ProxyServer {
 
     /// Starts the proxy server
     pub async fn start(&mut self) -> Result<()> {
-        let listeners = self
-            .conf
-            .addrs
-            .iter()
-            .cloned()
-            .map(|addr| {
-                ProxyListener::start(
+        let sockets = Self::bind(&self.conf.addrs)?;
+
+        self.start_with(sockets).await
+    }
+
+    /// Binds to the supplied addresses without accepting any connections.
+    ///
+    /// This allows privileged ports to be bound before the process drops
+    /// its root privileges, the returned sockets are then passed to [`ProxyServer::start_with`].
+    pub fn bind(addrs: &[SocketAddr]) -> Result<Vec<std::net::TcpListener>> {
+        addrs.iter().cloned().map(ProxyListener::bind).collect()
+    }
+
+    /// Starts the proxy server using the previously bound sockets
+    pub async fn start_with(&mut self, sockets: Vec<std::net::TcpListener>) -> Result<()> {
+        let listeners = sockets
+            .into_iter()
+            .map(|socket| {
+                ProxyListener::new(
                     self.conf,
                     Arc::clone(&self.addrs),
-                    addr,
+                    socket,
                     self.terminator.as_ref().unwrap().0.subscribe(),
                 )
             })
-            .collect::<Vec<_>>();
-
-        let listeners = futures::future::try_join_all(listeners).await?;
+            .collect::<Result<Vec<_>>>()?;
 
         for mut listener in listeners {
             self.listeners.push(tokio::spawn(async move {
@@ -104,12 +114,8 @@ struct ProxyListener {
 }
 
 impl ProxyListener {
-    async fn start(
-        conf: &'static ProxyConf,
-        addrs: Arc<Mutex<Vec<SocketAddr>>>,
-        addr: SocketAddr,
-        terminator: Receiver<()>,
-    ) -> Result<Self> {
+    /// Binds a socket to the supplied address
+    fn bind(addr: SocketAddr) -> Result<std::net::TcpListener> {
         let socket = Socket::new(
             Domain::for_address(addr),
             socket2::Type::STREAM,
@@ -136,14 +142,21 @@ impl ProxyListener {
             .set_nonblocking(true)
             .context("Failed to set socket to non-blocking mode")?;
 
-        let listener = Self {
+        Ok(socket.into())
+    }
+
+    fn new(
+        conf: &'static ProxyConf,
+        addrs: Arc<Mutex<Vec<SocketAddr>>>,
+        socket: std::net::TcpListener,
+        terminator: Receiver<()>,
+    ) -> Result<Self> {
+        Ok(Self {
             conf,
             addrs,
-            listener: Some(TcpListener::from_std(socket.into())?),
+            listener: Some(TcpListener::from_std(socket)?),
             terminator,
-        };
-
-        Ok(listener)
+        })
     }
 
     /// Accepts new connections
@@ -285,4 +298,21 @@ mod tests {
             vec!["127.0.0.1:0".parse().unwrap()]
         )
     }
+
+    #[tokio::test]
+    async fn test_server_bind_then_start_with() {
+        ansilo_logging::init_for_tests();
+        let mut server = create_server(mock_config_no_tls());
+
+        let sockets = ProxyServer::bind(&server.conf.addrs).unwrap();
+
+        // Socket should be bound before the server is started
+        let mut con = TcpStream::connect(server.conf.addrs[0]).unwrap();
+
+        server.start_with(sockets).await.unwrap();
+        assert!(server.healthy());
+
+        con.write_all(&[1]).unwrap();
+        con.flush().unwrap();
+    }
 }
@@ -76,24 +76,36 @@ pub fn mock_config_no_tls_with_port(port: u16) -> &'static ProxyConf {
     Box::leak(Box::new(conf))
 }
 
+const MOCK_KEY: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/src/mock-certs/mock.test-key.pem"
+);
+const MOCK_CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/mock-certs/mock.test.pem");
+const MOCK_CA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/mock-certs/rootCA.pem");
+
 pub fn mock_config_tls() -> &'static ProxyConf {
+    mock_config_with_tls(TlsConf::new(&Path::new(MOCK_KEY), &Path::new(MOCK_CERT)).unwrap())
+}
+
+pub fn mock_config_tls_client_auth(required: bool) -> &'static ProxyConf {
+    mock_config_with_tls(
+        TlsConf::with_client_auth(
+            &Path::new(MOCK_KEY),
+            &Path::new(MOCK_CERT),
+            &Path::new(MOCK_CA),
+            &["mock.test".to_string()],
+            required,
+        )
+        .unwrap(),
+    )
+}
+
+fn mock_config_with_tls(tls: TlsConf) -> &'static ProxyConf {
     let port = PORT.fetch_add(1, Ordering::Relaxed);
 
     let conf = ProxyConf {
         addrs: vec![SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port))],
-        tls: Some(
-            TlsConf::new(
-                &Path::new(concat!(
-                    env!("CARGO_MANIFEST_DIR"),
-                    "/src/mock-certs/mock.test-key.pem"
-                )),
-                &Path::new(concat!(
-                    env!("CARGO_MANIFEST_DIR"),
-                    "/src/mock-certs/mock.test.pem"
-                )),
-            )
-            .unwrap(),
-        ),
+        tls: Some(tls),
         handlers: HandlerConf::new(
             MockConnectionHandler::new(),
             MockConnectionHandler::new(),
@@ -108,11 +120,7 @@ pub fn mock_tls_connector() -> tokio_native_tls::TlsConnector {
     let mut builder = tokio_native_tls::native_tls::TlsConnector::builder();
 
     rustls_pemfile::certs(&mut io::BufReader::new(
-        fs::File::open(Path::new(concat!(
-            env!("CARGO_MANIFEST_DIR"),
-            "/src/mock-certs/rootCA.pem"
-        )))
-        .unwrap(),
+        fs::File::open(Path::new(MOCK_CA)).unwrap(),
     ))
     .unwrap()
     .into_iter()
@@ -1,39 +1,193 @@
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{HashMap, VecDeque},
+    fmt,
     sync::{Arc, RwLock},
+    time::{Duration, Instant},
 };
 
 use ansilo_core::{
-    data::chrono::{DateTime, Utc},
+    data::chrono::{self, DateTime, Utc},
     err::{Error, Result},
 };
 use ansilo_logging::{info, warn};
 use serde::{Deserialize, Serialize};
 
+/// The maximum number of transitions retained per subsystem.
+///
+/// Transitions are otherwise retained for [`HISTORY_RETENTION_HOURS`], this
+/// only bounds the memory used by a subsystem which is flapping rapidly.
+const MAX_HISTORY: usize = 10_000;
+
+/// The number of hours for which transitions are retained
+const HISTORY_RETENTION_HOURS: i64 = 24;
+
 /// Stores the health status of each subsystem
 #[derive(Clone)]
 pub struct Health {
     /// Mapping of the subsytem name to the healthy status
     state: Arc<RwLock<HashMap<String, HealthStatus>>>,
+    /// The recent status transitions of each subsystem
+    history: Arc<RwLock<HashMap<String, RetainedHistory>>>,
+    /// Custom checks which are run on each health check cycle
+    checks: Arc<RwLock<Vec<Arc<dyn HealthCheck>>>>,
+    /// The number of consecutive failed checks before a healthy subsystem is marked unhealthy
+    failure_threshold: u32,
+    /// The number of consecutive successful checks before an unhealthy subsystem is marked healthy
+    success_threshold: u32,
+}
+
+/// The health of a subsystem
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthState {
+    /// The subsystem is functioning normally
+    #[default]
+    Healthy,
+    /// The subsystem is functioning but requires attention, eg a saturated
+    /// pool or a check which has failed fewer times than the failure threshold
+    Degraded,
+    /// The subsystem is not functioning
+    Unhealthy,
+}
+
+impl fmt::Display for HealthState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HealthState::Healthy => "healthy",
+            HealthState::Degraded => "degraded",
+            HealthState::Unhealthy => "unhealthy",
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct HealthStatus {
-    /// Is the system healthy?
+    /// Is the system healthy? This is true unless the status is unhealthy.
     pub healthy: bool,
+    /// The status of the system
+    #[serde(default)]
+    pub status: HealthState,
+    /// Why the system is degraded or unhealthy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
     /// When was it last checked?
     pub checked: DateTime<Utc>,
     /// When was it last healthy?
     pub last_healthy: Option<DateTime<Utc>>,
+    /// How long the last check took, in milliseconds, if measured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    /// The error message of the last check, if it failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Whether the instance is considered unhealthy while this is unhealthy.
+    /// Probes of external systems, such as data sources, are not critical.
+    #[serde(default = "default_critical")]
+    pub critical: bool,
+    /// The number of consecutive successful checks
+    #[serde(default)]
+    pub consecutive_successes: u32,
+    /// The number of consecutive failed checks
+    #[serde(default)]
+    pub consecutive_failures: u32,
+}
+
+fn default_critical() -> bool {
+    true
+}
+
+/// A custom health check, such as the free disk space of the data
+/// directory or the expiry of a certificate.
+///
+/// Registered checks are run on each health check cycle of the instance
+/// and are reported alongside the built-in subsystems.
+pub trait HealthCheck: Send + Sync {
+    /// The name of the check, as reported in the health state
+    fn name(&self) -> String;
+
+    /// Whether the instance is considered unhealthy while this check fails
+    fn critical(&self) -> bool {
+        false
+    }
+
+    /// Runs the check, returning an error describing the failure.
+    ///
+    /// Checks are run sequentially so should complete quickly.
+    fn check(&self) -> Result<CheckOutcome>;
+}
+
+/// The outcome of a health check which did not fail
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckOutcome {
+    Healthy,
+    /// The check passed but reported a condition which requires attention
+    Degraded(String),
+}
+
+/// A change in the health status of a subsystem
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HealthTransition {
+    /// When did the status change?
+    pub at: DateTime<Utc>,
+    /// Is the system healthy after the change?
+    pub healthy: bool,
+    /// The status of the system after the change
+    #[serde(default)]
+    pub status: HealthState,
+    /// The reason for the change, if the system is degraded or unhealthy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The status transitions of a subsystem over the last 24 hours
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SubsystemHistory {
+    /// The transitions, oldest first
+    pub transitions: Vec<HealthTransition>,
+    /// Whether older transitions within the last 24 hours were dropped
+    /// as the subsystem exceeded the maximum number of retained transitions
+    pub truncated: bool,
+}
+
+/// The retained status transitions of a subsystem
+#[derive(Debug, Clone, Default)]
+struct RetainedHistory {
+    /// The transitions, oldest first
+    transitions: VecDeque<HealthTransition>,
+    /// When the latest transition which was dropped due to [`MAX_HISTORY`] occurred
+    truncated_at: Option<DateTime<Utc>>,
+}
+
+impl HealthStatus {
+    /// Returns whether this status affects the health of the instance
+    pub fn ok(&self) -> bool {
+        self.healthy || !self.critical
+    }
 }
 
 impl Health {
     pub fn new() -> Self {
         Self {
             state: Arc::new(RwLock::new(HashMap::new())),
+            history: Arc::new(RwLock::new(HashMap::new())),
+            checks: Arc::new(RwLock::new(vec![])),
+            failure_threshold: 1,
+            success_threshold: 1,
         }
     }
 
+    /// Sets the number of consecutive failed checks required to mark a
+    /// subsystem as unhealthy, and the number of consecutive successful
+    /// checks required to mark it as healthy again.
+    ///
+    /// This stops transient failures from flapping the health status.
+    /// The first check of a subsystem always determines its initial status.
+    pub fn with_thresholds(mut self, failures: u32, successes: u32) -> Self {
+        self.failure_threshold = failures.max(1);
+        self.success_threshold = successes.max(1);
+        self
+    }
+
     /// Returns a copy of the health state
     pub fn check(&self) -> Result<HashMap<String, HealthStatus>> {
         Ok(self
@@ -43,37 +197,265 @@ impl Health {
             .clone())
     }
 
+    /// Returns the status transitions of each subsystem over the last 24 hours, oldest first
+    pub fn history(&self) -> Result<HashMap<String, SubsystemHistory>> {
+        let since = Utc::now() - chrono::Duration::hours(HISTORY_RETENTION_HOURS);
+
+        Ok(self
+            .history
+            .read()
+            .map_err(|_| Error::msg("Failed to lock health history"))?
+            .iter()
+            .map(|(name, history)| {
+                (
+                    name.clone(),
+                    SubsystemHistory {
+                        transitions: history
+                            .transitions
+                            .iter()
+                            .filter(|t| t.at >= since)
+                            .cloned()
+                            .collect(),
+                        truncated: history.truncated_at.map_or(false, |t| t >= since),
+                    },
+                )
+            })
+            .collect())
+    }
+
+    /// Returns whether all critical subsystems are healthy
+    pub fn healthy(&self) -> Result<bool> {
+        Ok(self
+            .state
+            .read()
+            .map_err(|_| Error::msg("Failed to lock health state"))?
+            .values()
+            .all(|s| s.ok()))
+    }
+
     /// Updates the health status of a system
     pub fn update(&self, subsystem: &str, healthy: bool) -> Result<()> {
+        self.update_status(subsystem, healthy, None, None, None, true)
+    }
+
+    /// Marks a system as degraded, it is still considered healthy
+    pub fn update_degraded(&self, subsystem: &str, reason: impl Into<String>) -> Result<()> {
+        self.update_status(subsystem, true, Some(reason.into()), None, None, true)
+    }
+
+    /// Updates the health status of a non-critical system using the
+    /// result of a probe, recording its latency and error message
+    pub fn update_probe(
+        &self,
+        subsystem: &str,
+        latency: Duration,
+        result: &Result<()>,
+    ) -> Result<()> {
+        self.update_status(
+            subsystem,
+            result.is_ok(),
+            None,
+            Some(latency.as_millis() as u64),
+            result.as_ref().err().map(|e| format!("{:#}", e)),
+            false,
+        )
+    }
+
+    /// Registers a custom check to be run on each health check cycle
+    pub fn register(&self, check: impl HealthCheck + 'static) -> Result<()> {
+        self.checks
+            .write()
+            .map_err(|_| Error::msg("Failed to lock health checks"))?
+            .push(Arc::new(check));
+
+        Ok(())
+    }
+
+    /// Runs each of the registered checks and updates their health status
+    pub fn run_checks(&self) -> Result<()> {
+        let checks = self
+            .checks
+            .read()
+            .map_err(|_| Error::msg("Failed to lock health checks"))?
+            .clone();
+
+        for check in checks.iter() {
+            let start = Instant::now();
+            let res = check.check();
+            let latency_ms = Some(start.elapsed().as_millis() as u64);
+
+            match res {
+                Ok(outcome) => self.update_status(
+                    &check.name(),
+                    true,
+                    match outcome {
+                        CheckOutcome::Healthy => None,
+                        CheckOutcome::Degraded(reason) => Some(reason),
+                    },
+                    latency_ms,
+                    None,
+                    check.critical(),
+                )?,
+                Err(err) => self.update_status(
+                    &check.name(),
+                    false,
+                    None,
+                    latency_ms,
+                    Some(format!("{:#}", err)),
+                    check.critical(),
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the health status of a system which no longer exists
+    pub fn remove(&self, subsystem: &str) -> Result<()> {
+        self.state
+            .write()
+            .map_err(|_| Error::msg("Failed to lock health state"))?
+            .remove(subsystem);
+        self.history
+            .write()
+            .map_err(|_| Error::msg("Failed to lock health history"))?
+            .remove(subsystem);
+
+        Ok(())
+    }
+
+    fn update_status(
+        &self,
+        subsystem: &str,
+        passed: bool,
+        degraded: Option<String>,
+        latency_ms: Option<u64>,
+        error: Option<String>,
+        critical: bool,
+    ) -> Result<()> {
         let mut state = self
             .state
             .write()
             .map_err(|_| Error::msg("Failed to lock health state"))?;
 
         let now = Utc::now();
+        let is_new = !state.contains_key(subsystem);
+
+        let s = state
+            .entry(subsystem.into())
+            .or_insert_with(|| HealthStatus {
+                healthy: passed,
+                status: if passed {
+                    HealthState::Healthy
+                } else {
+                    HealthState::Unhealthy
+                },
+                reason: None,
+                checked: now,
+                last_healthy: None,
+                latency_ms: None,
+                error: None,
+                critical,
+                consecutive_successes: 0,
+                consecutive_failures: 0,
+            });
 
-        match state.entry(subsystem.into()) {
-            Entry::Occupied(mut s) => {
-                let s = s.get_mut();
+        if passed {
+            s.consecutive_successes = s.consecutive_successes.saturating_add(1);
+            s.consecutive_failures = 0;
+        } else {
+            s.consecutive_failures = s.consecutive_failures.saturating_add(1);
+            s.consecutive_successes = 0;
+        }
+
+        let healthy = match (s.healthy, passed) {
+            (true, false) => s.consecutive_failures < self.failure_threshold,
+            (false, true) => s.consecutive_successes >= self.success_threshold,
+            (current, _) => current,
+        };
 
-                match (s.healthy, healthy) {
-                    (true, false) => warn!("Subsystem '{subsystem}' changed to unhealthy"),
-                    (false, true) => info!("Subsystem '{subsystem}' changed to healthy"),
-                    _ => {}
-                }
+        // Failures below the threshold are reported as degraded
+        let (status, reason) = match (healthy, passed) {
+            (false, _) => (HealthState::Unhealthy, error.clone()),
+            (true, false) => (
+                HealthState::Degraded,
+                Some(format!(
+                    "Failed check {} of {}{}",
+                    s.consecutive_failures,
+                    self.failure_threshold,
+                    error.as_ref().map(|e| format!(": {e}")).unwrap_or_default()
+                )),
+            ),
+            (true, true) if degraded.is_some() => (HealthState::Degraded, degraded),
+            (true, true) => (HealthState::Healthy, None),
+        };
 
-                s.healthy = healthy;
-                if healthy {
-                    s.last_healthy = Some(now)
-                }
+        let because = reason
+            .as_ref()
+            .map(|r| format!(": {r}"))
+            .unwrap_or_default();
+        match (is_new, s.status, status) {
+            (true, _, HealthState::Healthy) => {}
+            (true, _, status) => warn!("Subsystem '{subsystem}' is {status}{because}"),
+            (false, prev, HealthState::Unhealthy) if prev != HealthState::Unhealthy => warn!(
+                "Subsystem '{subsystem}' changed to unhealthy after {} failed check(s){because}",
+                s.consecutive_failures
+            ),
+            (false, prev, HealthState::Healthy) if prev != HealthState::Healthy => {
+                info!("Subsystem '{subsystem}' changed to healthy")
             }
-            Entry::Vacant(s) => {
-                s.insert(HealthStatus {
-                    healthy,
-                    checked: now,
-                    last_healthy: if healthy { Some(now) } else { None },
-                });
+            (false, prev, HealthState::Degraded) if prev != HealthState::Degraded || !passed => {
+                warn!("Subsystem '{subsystem}' is degraded{because}")
             }
+            _ => {}
+        }
+
+        if is_new || s.status != status {
+            self.record_transition(subsystem, now, status, reason.clone())?;
+        }
+
+        s.healthy = healthy;
+        s.status = status;
+        s.reason = reason;
+        s.checked = now;
+        s.latency_ms = latency_ms;
+        s.error = error;
+        s.critical = critical;
+        if healthy {
+            s.last_healthy = Some(now)
+        }
+
+        Ok(())
+    }
+
+    fn record_transition(
+        &self,
+        subsystem: &str,
+        at: DateTime<Utc>,
+        status: HealthState,
+        error: Option<String>,
+    ) -> Result<()> {
+        let mut history = self
+            .history
+            .write()
+            .map_err(|_| Error::msg("Failed to lock health history"))?;
+        let history = history.entry(subsystem.into()).or_default();
+        let since = at - chrono::Duration::hours(HISTORY_RETENTION_HOURS);
+
+        history.transitions.push_back(HealthTransition {
+            at,
+            healthy: status != HealthState::Unhealthy,
+            status,
+            error,
+        });
+
+        while history.transitions.front().map_or(false, |t| t.at < since) {
+            history.transitions.pop_front();
+        }
+
+        while history.transitions.len() > MAX_HISTORY {
+            let dropped = history.transitions.pop_front().unwrap();
+            history.truncated_at = Some(dropped.at);
         }
 
         Ok(())
@@ -82,6 +464,8 @@ impl Health {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
     use super::*;
 
     #[test]
@@ -89,6 +473,7 @@ mod tests {
         let health = Health::new();
 
         assert_eq!(health.check().unwrap(), HashMap::new());
+        assert_eq!(health.healthy().unwrap(), true);
 
         health.update("sys", true).unwrap();
         health.update("other", false).unwrap();
@@ -100,10 +485,284 @@ mod tests {
         let other = health.check().unwrap().get("other").cloned().unwrap();
         assert_eq!(other.healthy, false);
         assert_eq!(other.last_healthy.is_some(), false);
+        assert_eq!(health.healthy().unwrap(), false);
 
         health.update("other", true).unwrap();
 
         let other = health.check().unwrap().get("other").cloned().unwrap();
         assert_eq!(other.last_healthy.is_some(), true);
+        assert_eq!(health.healthy().unwrap(), true);
+    }
+
+    #[test]
+    fn test_probe() {
+        let health = Health::new();
+
+        health.update("sys", true).unwrap();
+        health
+            .update_probe("source", Duration::from_millis(15), &Ok(()))
+            .unwrap();
+
+        let source = health.check().unwrap().get("source").cloned().unwrap();
+        assert_eq!(source.healthy, true);
+        assert_eq!(source.latency_ms, Some(15));
+        assert_eq!(source.error, None);
+        assert_eq!(source.critical, false);
+
+        health
+            .update_probe(
+                "source",
+                Duration::from_millis(30),
+                &Err(Error::msg("Connection refused")),
+            )
+            .unwrap();
+
+        let source = health.check().unwrap().get("source").cloned().unwrap();
+        assert_eq!(source.healthy, false);
+        assert_eq!(source.latency_ms, Some(30));
+        assert_eq!(source.error, Some("Connection refused".into()));
+        assert_eq!(source.last_healthy.is_some(), true);
+
+        // Failing probes do not affect the health of the instance
+        assert_eq!(health.healthy().unwrap(), true);
+
+        health.remove("source").unwrap();
+        assert_eq!(health.check().unwrap().contains_key("source"), false);
+    }
+
+    #[test]
+    fn test_thresholds() {
+        let health = Health::new().with_thresholds(3, 2);
+        let status = |h: &Health| h.check().unwrap().get("sys").cloned().unwrap();
+
+        health.update("sys", true).unwrap();
+        assert_eq!(status(&health).healthy, true);
+        assert_eq!(status(&health).consecutive_successes, 1);
+
+        // Transient failures are tolerated
+        health.update("sys", false).unwrap();
+        health.update("sys", false).unwrap();
+        assert_eq!(status(&health).healthy, true);
+        assert_eq!(status(&health).consecutive_failures, 2);
+        assert_eq!(status(&health).consecutive_successes, 0);
+
+        health.update("sys", true).unwrap();
+        assert_eq!(status(&health).healthy, true);
+        assert_eq!(status(&health).consecutive_failures, 0);
+
+        for _ in 0..3 {
+            health.update("sys", false).unwrap();
+        }
+        assert_eq!(status(&health).healthy, false);
+        assert_eq!(status(&health).consecutive_failures, 3);
+        assert_eq!(health.healthy().unwrap(), false);
+
+        // Recovery requires consecutive successes
+        health.update("sys", true).unwrap();
+        assert_eq!(status(&health).healthy, false);
+        health.update("sys", false).unwrap();
+        health.update("sys", true).unwrap();
+        assert_eq!(status(&health).healthy, false);
+        health.update("sys", true).unwrap();
+        assert_eq!(status(&health).healthy, true);
+        assert_eq!(status(&health).consecutive_successes, 2);
+    }
+
+    #[test]
+    fn test_history() {
+        let health = Health::new().with_thresholds(2, 1);
+
+        health.update("sys", true).unwrap();
+        health.update("sys", false).unwrap();
+        health.update("sys", true).unwrap();
+        health
+            .update_probe("source", Duration::ZERO, &Err(Error::msg("Timeout")))
+            .unwrap();
+
+        // Failures below the threshold are transitions to degraded
+        let history = health.history().unwrap();
+        assert_eq!(
+            history["sys"]
+                .transitions
+                .iter()
+                .map(|t| t.status)
+                .collect::<Vec<_>>(),
+            vec![
+                HealthState::Healthy,
+                HealthState::Degraded,
+                HealthState::Healthy
+            ]
+        );
+        assert_eq!(history["sys"].transitions[1].healthy, true);
+        assert_eq!(history["sys"].truncated, false);
+        assert_eq!(history["source"].transitions.len(), 1);
+        assert_eq!(
+            history["source"].transitions[0].error,
+            Some("Timeout".into())
+        );
+
+        health.update("sys", false).unwrap();
+        health.update("sys", false).unwrap();
+        health.update("sys", true).unwrap();
+
+        let history = health.history().unwrap();
+        assert_eq!(
+            history["sys"]
+                .transitions
+                .iter()
+                .map(|t| t.healthy)
+                .collect::<Vec<_>>(),
+            vec![true, true, true, true, false, true]
+        );
+        assert!(history["sys"]
+            .transitions
+            .windows(2)
+            .all(|w| w[0].at <= w[1].at));
+
+        health.remove("sys").unwrap();
+        assert_eq!(health.history().unwrap().contains_key("sys"), false);
+    }
+
+    #[test]
+    fn test_history_bounded() {
+        let health = Health::new();
+
+        for i in 0..(MAX_HISTORY * 2) {
+            health.update("sys", i % 2 == 0).unwrap();
+        }
+
+        let history = health.history().unwrap();
+        assert_eq!(history["sys"].transitions.len(), MAX_HISTORY);
+        assert_eq!(history["sys"].truncated, true);
+    }
+
+    struct MockCheck {
+        healthy: Arc<AtomicBool>,
+        critical: bool,
+    }
+
+    impl HealthCheck for MockCheck {
+        fn name(&self) -> String {
+            "Mock".into()
+        }
+
+        fn critical(&self) -> bool {
+            self.critical
+        }
+
+        fn check(&self) -> Result<CheckOutcome> {
+            if self.healthy.load(Ordering::SeqCst) {
+                Ok(CheckOutcome::Healthy)
+            } else {
+                Err(Error::msg("Mock check failed"))
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_check() {
+        let health = Health::new();
+        let healthy = Arc::new(AtomicBool::new(true));
+
+        health
+            .register(MockCheck {
+                healthy: healthy.clone(),
+                critical: true,
+            })
+            .unwrap();
+
+        // Checks are only run on each cycle
+        assert_eq!(health.check().unwrap().contains_key("Mock"), false);
+
+        health.run_checks().unwrap();
+
+        let mock = health.check().unwrap().get("Mock").cloned().unwrap();
+        assert_eq!(mock.healthy, true);
+        assert_eq!(mock.critical, true);
+        assert!(mock.latency_ms.is_some());
+
+        healthy.store(false, Ordering::SeqCst);
+        health.run_checks().unwrap();
+
+        let mock = health.check().unwrap().get("Mock").cloned().unwrap();
+        assert_eq!(mock.healthy, false);
+        assert_eq!(mock.error, Some("Mock check failed".into()));
+        assert_eq!(health.healthy().unwrap(), false);
+    }
+
+    #[test]
+    fn test_custom_check_non_critical() {
+        let health = Health::new();
+
+        health
+            .register(MockCheck {
+                healthy: Arc::new(AtomicBool::new(false)),
+                critical: false,
+            })
+            .unwrap();
+        health.run_checks().unwrap();
+
+        assert_eq!(health.check().unwrap()["Mock"].healthy, false);
+        assert_eq!(health.healthy().unwrap(), true);
+    }
+
+    #[test]
+    fn test_degraded() {
+        let health = Health::new();
+
+        health.update("sys", true).unwrap();
+        health.update_degraded("sys", "Pool 90% saturated").unwrap();
+
+        let sys = health.check().unwrap()["sys"].clone();
+        assert_eq!(sys.status, HealthState::Degraded);
+        assert_eq!(sys.healthy, true);
+        assert_eq!(sys.reason, Some("Pool 90% saturated".into()));
+        assert_eq!(health.healthy().unwrap(), true);
+
+        health.update("sys", true).unwrap();
+
+        let sys = health.check().unwrap()["sys"].clone();
+        assert_eq!(sys.status, HealthState::Healthy);
+        assert_eq!(sys.reason, None);
+
+        health.update("sys", false).unwrap();
+
+        let sys = health.check().unwrap()["sys"].clone();
+        assert_eq!(sys.status, HealthState::Unhealthy);
+        assert_eq!(health.healthy().unwrap(), false);
+    }
+
+    #[test]
+    fn test_degraded_below_failure_threshold() {
+        let health = Health::new().with_thresholds(3, 1);
+
+        health.update("sys", true).unwrap();
+        health
+            .update_probe("sys", Duration::ZERO, &Err(Error::msg("Timeout")))
+            .unwrap();
+
+        let sys = health.check().unwrap()["sys"].clone();
+        assert_eq!(sys.status, HealthState::Degraded);
+        assert_eq!(sys.reason, Some("Failed check 1 of 3: Timeout".into()));
+    }
+
+    #[test]
+    fn test_health_state_serialization() {
+        assert_eq!(
+            serde_json::to_string(&HealthState::Degraded).unwrap(),
+            r#""degraded""#
+        );
+        assert_eq!(HealthState::Unhealthy.to_string(), "unhealthy");
+    }
+
+    #[test]
+    fn test_thresholds_initial_status() {
+        let health = Health::new().with_thresholds(3, 2);
+
+        health.update("sys", false).unwrap();
+
+        let sys = health.check().unwrap().get("sys").cloned().unwrap();
+        assert_eq!(sys.healthy, false);
+        assert_eq!(sys.last_healthy, None);
     }
 }
@@ -0,0 +1,305 @@
+use std::{env, time::Duration};
+
+use ansilo_core::err::{ensure, Context, Result};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{blocking::Client, Url};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::http;
+
+/// The version of the blob storage REST API
+const API_VERSION: &str = "2021-08-06";
+
+/// The oauth2 resource of azure storage
+const STORAGE_RESOURCE: &str = "https://storage.azure.com/";
+
+/// The token endpoint of the instance metadata service, used for managed identities
+const IMDS_TOKEN_URL: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+
+/// Gets the contents of the blob at the supplied azure://account/container/path/to/blob url
+pub(crate) fn get_azure(url: Url) -> Result<Vec<u8>> {
+    let account = url
+        .host_str()
+        .context("Expected storage account in azure:// url")?;
+    let path = url.path();
+    ensure!(
+        path.trim_start_matches('/').contains('/'),
+        "Expected container and blob path in azure:// url"
+    );
+
+    // Allow the endpoint to be overridden, eg for Azurite
+    let endpoint = env::var("AZURE_STORAGE_ENDPOINT")
+        .unwrap_or_else(|_| format!("https://{account}.blob.core.windows.net"));
+
+    let client = http::client()?;
+    let credentials = Credentials::from_env(&client)?;
+
+    get_blob(&client, &endpoint, account, path, &credentials)
+}
+
+/// The credentials used to authenticate with azure storage
+enum Credentials {
+    /// A shared access signature appended to the query string
+    Sas(String),
+    /// The storage account access key
+    SharedKey(String),
+    /// An oauth2 access token
+    Bearer(String),
+    /// Anonymous access, for public containers
+    Anonymous,
+}
+
+impl Credentials {
+    /// Retrieves the credentials using the following chain:
+    ///
+    ///  1. A SAS token in the `AZURE_STORAGE_SAS_TOKEN` env var
+    ///  2. The account key in the `AZURE_STORAGE_KEY` env var
+    ///  3. A service principal using the `AZURE_TENANT_ID`, `AZURE_CLIENT_ID`
+    ///     and `AZURE_CLIENT_SECRET` env vars
+    ///  4. A managed identity using the instance metadata service, when running on azure
+    ///
+    /// If no credentials are found the request is made anonymously.
+    fn from_env(client: &Client) -> Result<Self> {
+        if let Ok(sas) = env::var("AZURE_STORAGE_SAS_TOKEN") {
+            return Ok(Self::Sas(sas.trim_start_matches('?').into()));
+        }
+
+        if let Ok(key) = env::var("AZURE_STORAGE_KEY") {
+            return Ok(Self::SharedKey(key));
+        }
+
+        if let (Ok(tenant), Ok(client_id), Ok(secret)) = (
+            env::var("AZURE_TENANT_ID"),
+            env::var("AZURE_CLIENT_ID"),
+            env::var("AZURE_CLIENT_SECRET"),
+        ) {
+            return Ok(Self::Bearer(client_secret_token(
+                client, &tenant, &client_id, &secret,
+            )?));
+        }
+
+        Ok(match managed_identity_token(client) {
+            Some(token) => Self::Bearer(token),
+            None => Self::Anonymous,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// Retrieves an access token for the service principal
+fn client_secret_token(
+    client: &Client,
+    tenant: &str,
+    client_id: &str,
+    secret: &str,
+) -> Result<String> {
+    let token_url = Url::parse(&format!(
+        "https://login.microsoftonline.com/{tenant}/oauth2/v2.0/token"
+    ))
+    .context("Invalid azure tenant id")?;
+    let scope = format!("{STORAGE_RESOURCE}.default");
+
+    let response = http::send(
+        client.post(token_url.clone()).form(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", client_id),
+            ("client_secret", secret),
+            ("scope", scope.as_str()),
+        ]),
+        &token_url,
+    )
+    .context("Failed to retrieve azure access token")?;
+
+    let response: TokenResponse =
+        serde_json::from_slice(&response).context("Failed to parse azure token response")?;
+
+    Ok(response.access_token)
+}
+
+/// Retrieves an access token for the managed identity, if available
+fn managed_identity_token(client: &Client) -> Option<String> {
+    let mut query = vec![("api-version", "2018-02-01".to_string())];
+    query.push(("resource", STORAGE_RESOURCE.into()));
+    if let Ok(client_id) = env::var("AZURE_CLIENT_ID") {
+        query.push(("client_id", client_id));
+    }
+
+    let response = client
+        .get(IMDS_TOKEN_URL)
+        .query(&query)
+        .header("Metadata", "true")
+        .timeout(Duration::from_secs(2))
+        .send()
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.bytes())
+        .ok()?;
+
+    serde_json::from_slice::<TokenResponse>(&response)
+        .ok()
+        .map(|r| r.access_token)
+}
+
+/// Retrieves the blob using the blob storage REST API
+fn get_blob(
+    client: &Client,
+    endpoint: &str,
+    account: &str,
+    path: &str,
+    credentials: &Credentials,
+) -> Result<Vec<u8>> {
+    let blob_url = Url::parse(&format!("{}{}", endpoint.trim_end_matches('/'), path))
+        .context("Failed to build azure blob url")?;
+
+    let mut request_url = blob_url.clone();
+    if let Credentials::Sas(sas) = credentials {
+        request_url.set_query(Some(sas));
+    }
+
+    let date = Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    let mut request = client
+        .get(request_url)
+        .header("x-ms-date", &date)
+        .header("x-ms-version", API_VERSION);
+
+    request = match credentials {
+        Credentials::SharedKey(key) => request.header(
+            "Authorization",
+            format!(
+                "SharedKey {}:{}",
+                account,
+                shared_key_signature(key, account, path, &date)?
+            ),
+        ),
+        Credentials::Bearer(token) => request.bearer_auth(token),
+        Credentials::Sas(_) | Credentials::Anonymous => request,
+    };
+
+    http::send(request, &blob_url)
+}
+
+/// Signs a GET request for the blob using the storage account key
+/// @see https://learn.microsoft.com/en-us/rest/api/storageservices/authorize-with-shared-key
+fn shared_key_signature(key: &str, account: &str, path: &str, date: &str) -> Result<String> {
+    let string_to_sign = format!(
+        "GET\n{}x-ms-date:{}\nx-ms-version:{}\n/{}{}",
+        "\n".repeat(11),
+        date,
+        API_VERSION,
+        account,
+        path
+    );
+
+    let key = base64::decode(key).context("Failed to decode azure storage account key")?;
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(&key).context("Invalid azure storage account key")?;
+    mac.update(string_to_sign.as_bytes());
+
+    Ok(base64::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use httpmock::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_azure_shared_key_signature() {
+        let key = base64::encode("secret");
+        let date = "Mon, 01 Jan 2024 00:00:00 GMT";
+
+        let sig = shared_key_signature(&key, "account", "/container/blob.txt", date).unwrap();
+
+        // Signature should be deterministic
+        assert_eq!(
+            sig,
+            shared_key_signature(&key, "account", "/container/blob.txt", date).unwrap()
+        );
+        assert_ne!(
+            sig,
+            shared_key_signature(&key, "account", "/container/other.txt", date).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_azure_get_blob_sas() {
+        let server = MockServer::start();
+
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/container/path/blob.txt")
+                .query_param("sig", "abc")
+                .header("x-ms-version", API_VERSION);
+            then.status(200).body("secret");
+        });
+
+        let res = get_blob(
+            &http::client().unwrap(),
+            &server.base_url(),
+            "account",
+            "/container/path/blob.txt",
+            &Credentials::Sas("sv=2021&sig=abc".into()),
+        )
+        .unwrap();
+
+        mock.assert();
+        assert_eq!(res, b"secret".to_vec());
+    }
+
+    #[test]
+    fn test_azure_get_blob_shared_key() {
+        let server = MockServer::start();
+
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/container/blob.txt")
+                .header_exists("authorization")
+                .header_exists("x-ms-date");
+            then.status(200).body("secret");
+        });
+
+        let res = get_blob(
+            &http::client().unwrap(),
+            &server.base_url(),
+            "account",
+            "/container/blob.txt",
+            &Credentials::SharedKey(base64::encode("secret")),
+        )
+        .unwrap();
+
+        mock.assert();
+        assert_eq!(res, b"secret".to_vec());
+    }
+
+    #[test]
+    fn test_azure_get_blob_error_does_not_leak_sas() {
+        let server = MockServer::start();
+
+        server.mock(|when, then| {
+            when.method(GET);
+            then.status(403);
+        });
+
+        let err = get_blob(
+            &http::client().unwrap(),
+            &server.base_url(),
+            "account",
+            "/container/blob.txt",
+            &Credentials::Sas("sig=supersecret".into()),
+        )
+        .unwrap_err();
+
+        assert!(!format!("{:?}", err).contains("supersecret"));
+    }
+
+    #[test]
+    fn test_azure_invalid_url() {
+        get_azure(Url::parse("azure://account/container").unwrap()).unwrap_err();
+    }
+}
@@ -0,0 +1,78 @@
+use ansilo_core::err::{Context, Result};
+use reqwest::Url;
+
+/// Gets the inline contents of the supplied data: url, eg "data:;base64,Zm9vYmFy"
+/// or "data:text/plain,hello%20world"
+pub(crate) fn get_data(url: Url) -> Result<Vec<u8>> {
+    let content = &url.as_str()["data:".len()..];
+    let (meta, data) = content
+        .split_once(',')
+        .context("Expected ',' separating the media type and data in data: url")?;
+
+    if meta.ends_with(";base64") {
+        let data = percent_decode(data)
+            .into_iter()
+            .filter(|b| !b.is_ascii_whitespace())
+            .collect::<Vec<_>>();
+
+        base64::decode(data).context("Failed to decode base64 data in data: url")
+    } else {
+        Ok(percent_decode(data))
+    }
+}
+
+pub(crate) fn percent_decode(data: &str) -> Vec<u8> {
+    let bytes = data.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(url: &str) -> Result<Vec<u8>> {
+        get_data(Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_data_base64() {
+        assert_eq!(get("data:;base64,Zm9vYmFy").unwrap(), b"foobar".to_vec());
+        assert_eq!(
+            get("data:application/x-pem-file;base64,Zm9v\nYmFy").unwrap(),
+            b"foobar".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_data_plain() {
+        assert_eq!(get("data:,hello%20world").unwrap(), b"hello world".to_vec());
+        assert_eq!(get("data:text/plain,100%").unwrap(), b"100%".to_vec());
+    }
+
+    #[test]
+    fn test_data_invalid() {
+        get("data:;base64").unwrap_err();
+        get("data:;base64,!!!").unwrap_err();
+    }
+}
@@ -0,0 +1,22 @@
+use std::env;
+
+use ansilo_core::err::{Context, Error, Result};
+use reqwest::Url;
+
+/// Gets the value of the environment variable from the supplied env://VAR_NAME url
+pub(crate) fn get_env(url: Url) -> Result<Vec<u8>> {
+    let name = url
+        .host_str()
+        .context("Expected variable name in env:// url")?;
+
+    let value =
+        env::var_os(name).with_context(|| format!("Environment variable '{name}' is not set"))?;
+
+    let value = value.into_string().map_err(|_| {
+        Error::msg(format!(
+            "Environment variable '{name}' is not valid unicode"
+        ))
+    })?;
+
+    Ok(value.into_bytes())
+}
@@ -0,0 +1,285 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use ansilo_core::err::{ensure, Context, Result};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::{blocking::Client, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::http;
+
+/// The default Google Cloud Storage endpoint
+const GCS_ENDPOINT: &str = "https://storage.googleapis.com";
+
+/// The default oauth2 token endpoint
+const TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+
+/// The token endpoint of the GCE metadata server
+const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// The oauth2 scope required to read objects
+const READ_ONLY_SCOPE: &str = "https://www.googleapis.com/auth/devstorage.read_only";
+
+/// Gets the contents of the object at the supplied gcs://bucket/path/to/object url
+pub(crate) fn get_gcs(url: Url) -> Result<Vec<u8>> {
+    let bucket = url.host_str().context("Expected bucket in gcs:// url")?;
+    let object = url.path().trim_start_matches('/');
+    ensure!(!object.is_empty(), "Expected object path in gcs:// url");
+
+    let client = http::client()?;
+    let token = access_token(&client)?;
+    let endpoint = env::var("STORAGE_EMULATOR_HOST").unwrap_or_else(|_| GCS_ENDPOINT.into());
+
+    get_object(&client, &endpoint, bucket, object, token.as_deref())
+}
+
+/// Retrieves the object using the GCS JSON API
+fn get_object(
+    client: &Client,
+    endpoint: &str,
+    bucket: &str,
+    object: &str,
+    token: Option<&str>,
+) -> Result<Vec<u8>> {
+    // The object path is already percent-encoded, apart from the path separators
+    let api_url = Url::parse(&format!(
+        "{}/storage/v1/b/{}/o/{}?alt=media",
+        endpoint.trim_end_matches('/'),
+        bucket,
+        object.replace('/', "%2F")
+    ))
+    .context("Failed to build GCS API url")?;
+
+    let mut request = client.get(api_url.clone());
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+
+    http::send(request, &api_url)
+}
+
+/// Retrieves an access token using the application default credentials chain:
+///
+///  1. The `GOOGLE_OAUTH_ACCESS_TOKEN` env var
+///  2. The credentials file at `GOOGLE_APPLICATION_CREDENTIALS`, or the gcloud
+///     application default credentials file, supporting service account keys
+///     and authorized user credentials
+///  3. The GCE metadata server, when running on GCP
+///
+/// If no credentials are found the request is made anonymously.
+fn access_token(client: &Client) -> Result<Option<String>> {
+    if let Ok(token) = env::var("GOOGLE_OAUTH_ACCESS_TOKEN") {
+        return Ok(Some(token));
+    }
+
+    if let Some(path) = credentials_path() {
+        let creds = fs::read(&path)
+            .with_context(|| format!("Failed to read GCP credentials from {}", path.display()))?;
+        let creds: Credentials = serde_json::from_slice(&creds)
+            .with_context(|| format!("Failed to parse GCP credentials from {}", path.display()))?;
+
+        return creds.access_token(client).map(Some);
+    }
+
+    Ok(metadata_access_token(client))
+}
+
+fn credentials_path() -> Option<PathBuf> {
+    if let Ok(path) = env::var("GOOGLE_APPLICATION_CREDENTIALS") {
+        return Some(path.into());
+    }
+
+    let path = PathBuf::from(env::var("HOME").ok()?)
+        .join(".config/gcloud/application_default_credentials.json");
+
+    if path.is_file() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// Retrieves an access token from the GCE metadata server, if available
+fn metadata_access_token(client: &Client) -> Option<String> {
+    let response = client
+        .get(METADATA_TOKEN_URL)
+        .header("Metadata-Flavor", "Google")
+        .timeout(Duration::from_secs(2))
+        .send()
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.bytes())
+        .ok()?;
+
+    serde_json::from_slice::<TokenResponse>(&response)
+        .ok()
+        .map(|r| r.access_token)
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Credentials {
+    ServiceAccount {
+        client_email: String,
+        private_key: String,
+        token_uri: Option<String>,
+    },
+    AuthorizedUser {
+        client_id: String,
+        client_secret: String,
+        refresh_token: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+impl Credentials {
+    fn access_token(&self, client: &Client) -> Result<String> {
+        let (token_uri, form) = match self {
+            Credentials::ServiceAccount {
+                client_email,
+                private_key,
+                token_uri,
+            } => {
+                let token_uri = token_uri.as_deref().unwrap_or(TOKEN_URI);
+                let assertion = service_account_jwt(client_email, private_key, token_uri)?;
+
+                (
+                    token_uri,
+                    vec![
+                        (
+                            "grant_type",
+                            "urn:ietf:params:oauth:grant-type:jwt-bearer".into(),
+                        ),
+                        ("assertion", assertion),
+                    ],
+                )
+            }
+            Credentials::AuthorizedUser {
+                client_id,
+                client_secret,
+                refresh_token,
+            } => (
+                TOKEN_URI,
+                vec![
+                    ("grant_type", "refresh_token".to_string()),
+                    ("client_id", client_id.clone()),
+                    ("client_secret", client_secret.clone()),
+                    ("refresh_token", refresh_token.clone()),
+                ],
+            ),
+        };
+
+        let token_uri = Url::parse(token_uri).context("Invalid GCP token uri")?;
+        let response = http::send(client.post(token_uri.clone()).form(&form), &token_uri)
+            .context("Failed to retrieve GCP access token")?;
+        let response: TokenResponse =
+            serde_json::from_slice(&response).context("Failed to parse GCP token response")?;
+
+        Ok(response.access_token)
+    }
+}
+
+/// Creates a signed JWT used to exchange service account credentials for an access token
+fn service_account_jwt(client_email: &str, private_key: &str, token_uri: &str) -> Result<String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("System time is before unix epoch")?
+        .as_secs();
+
+    let claims = Claims {
+        iss: client_email,
+        scope: READ_ONLY_SCOPE,
+        aud: token_uri,
+        iat: now,
+        exp: now + 3600,
+    };
+
+    let key = EncodingKey::from_rsa_pem(private_key.as_bytes())
+        .context("Failed to parse service account private key")?;
+
+    jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &key)
+        .context("Failed to sign service account JWT")
+}
+
+#[cfg(test)]
+mod tests {
+    use httpmock::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_gcs_get_object() {
+        let server = MockServer::start();
+
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path_contains("/storage/v1/b/my-bucket/o/path")
+                .query_param("alt", "media")
+                .header("authorization", "Bearer tok");
+            then.status(200).body("secret");
+        });
+
+        let res = get_object(
+            &http::client().unwrap(),
+            &server.base_url(),
+            "my-bucket",
+            "path/to/secret.txt",
+            Some("tok"),
+        )
+        .unwrap();
+
+        mock.assert();
+        assert_eq!(res, b"secret".to_vec());
+    }
+
+    #[test]
+    fn test_gcs_get_object_error_status() {
+        let server = MockServer::start();
+
+        server.mock(|when, then| {
+            when.method(GET);
+            then.status(403);
+        });
+
+        get_object(
+            &http::client().unwrap(),
+            &server.base_url(),
+            "my-bucket",
+            "secret.txt",
+            None,
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn test_gcs_authorized_user_credentials() {
+        let creds: Credentials = serde_json::from_str(
+            r#"{"type": "authorized_user", "client_id": "id", "client_secret": "secret", "refresh_token": "refresh"}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(creds, Credentials::AuthorizedUser { .. }));
+    }
+
+    #[test]
+    fn test_gcs_invalid_url() {
+        get_gcs(Url::parse("gcs://bucket").unwrap()).unwrap_err();
+        get_gcs(Url::parse("gcs://bucket/").unwrap()).unwrap_err();
+    }
+}
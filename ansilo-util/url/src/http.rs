@@ -1,23 +1,322 @@
-use std::time::Duration;
+use std::{fs, path::PathBuf, thread, time::Duration};
 
-use ansilo_core::err::{Context, Result};
-use reqwest::Url;
+use ansilo_core::err::{bail, Context, Result};
+use reqwest::{
+    blocking::{Client, RequestBuilder, Response},
+    Certificate, StatusCode, Url,
+};
 
-/// Gets response body from the supplied http(s) url
+use crate::data::percent_decode;
+
+/// Options for fetching http(s) urls.
+///
+/// These can be supplied in the url fragment, which is never sent to the server,
+/// eg "https://host/secret#header.Authorization=Bearer%20abc&timeout=10&retries=3"
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpOptions {
+    /// Additional headers sent with the request
+    pub headers: Vec<(String, String)>,
+    /// The timeout of the request, including reading the response
+    pub timeout: Duration,
+    /// The timeout for establishing a connection
+    pub connect_timeout: Duration,
+    /// The number of times the request is retried on a connection error or
+    /// a server error status (5xx or 429)
+    pub retries: u32,
+    /// Path of a PEM-encoded CA bundle used to verify the server certificate,
+    /// in addition to the system roots
+    pub ca_cert: Option<PathBuf>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            headers: vec![],
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(30),
+            retries: 0,
+            ca_cert: None,
+        }
+    }
+}
+
+impl HttpOptions {
+    /// Parses the options from the fragment of the supplied url
+    pub fn from_fragment(url: &Url) -> Result<Self> {
+        let mut options = Self::default();
+
+        let fragment = match url.fragment() {
+            Some(f) if !f.is_empty() => f,
+            _ => return Ok(options),
+        };
+
+        for pair in fragment.split('&').filter(|p| !p.is_empty()) {
+            let (key, val) = pair.split_once('=').unwrap_or((pair, ""));
+            let key = decode(key)?;
+            let val = decode(val)?;
+
+            match key.as_str() {
+                "timeout" => options.timeout = Duration::from_secs(parse_num(&key, &val)?),
+                "connect_timeout" => {
+                    options.connect_timeout = Duration::from_secs(parse_num(&key, &val)?)
+                }
+                "retries" => options.retries = parse_num(&key, &val)? as u32,
+                "ca_cert" => options.ca_cert = Some(val.into()),
+                _ if key.starts_with("header.") => options
+                    .headers
+                    .push((key["header.".len()..].to_string(), val)),
+                _ => bail!("Unknown http option '{key}' in url fragment"),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+fn decode(s: &str) -> Result<String> {
+    String::from_utf8(percent_decode(&s.replace('+', " "))).context("Invalid utf8 in url fragment")
+}
+
+fn parse_num(key: &str, val: &str) -> Result<u64> {
+    val.parse()
+        .with_context(|| format!("Expected http option '{key}' to be a number, found '{val}'"))
+}
+
+/// Gets response body from the supplied http(s) url using the options
+/// defined in the url fragment
 pub(crate) fn get_http(url: Url) -> Result<Vec<u8>> {
-    let client = reqwest::blocking::Client::builder()
-        .connect_timeout(Duration::from_secs(30))
-        .user_agent("Ansilo/v1")
-        .build()
-        .context("Failed to build http client")?;
+    let options = HttpOptions::from_fragment(&url)?;
+
+    get_http_with_options(url, &options)
+}
+
+/// Gets response body from the supplied http(s) url
+pub(crate) fn get_http_with_options(mut url: Url, options: &HttpOptions) -> Result<Vec<u8>> {
+    url.set_fragment(None);
+    let client = client_with_options(options)?;
+    let mut attempt = 0;
+
+    loop {
+        let mut request = client.get(url.clone()).timeout(options.timeout);
+        for (name, val) in options.headers.iter() {
+            request = request.header(name, val);
+        }
+
+        let response = request.send();
+
+        let retry = match &response {
+            Ok(res) => {
+                res.status().is_server_error() || res.status() == StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(err) => err.is_connect() || err.is_timeout(),
+        };
+
+        if retry && attempt < options.retries {
+            // Exponential backoff, capped at 10s
+            thread::sleep(
+                Duration::from_millis(500 * 2u64.pow(attempt.min(5))).min(Duration::from_secs(10)),
+            );
+            attempt += 1;
+            continue;
+        }
+
+        let response = response
+            .map_err(|e| e.without_url())
+            .with_context(|| format!("Error during request to {}", url))?
+            .error_for_status()
+            .map_err(|e| e.without_url())
+            .with_context(|| format!("Request to {} failed", url))?;
+
+        return Ok(response.bytes()?.to_vec());
+    }
+}
+
+/// Builds the http client used for requests
+pub(crate) fn client() -> Result<Client> {
+    client_with_options(&HttpOptions::default())
+}
+
+fn client_with_options(options: &HttpOptions) -> Result<Client> {
+    let mut builder = reqwest::blocking::Client::builder()
+        .connect_timeout(options.connect_timeout)
+        .user_agent("Ansilo/v1");
+
+    if let Some(path) = options.ca_cert.as_ref() {
+        for cert in read_ca_bundle(path)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    builder.build().context("Failed to build http client")
+}
 
-    let response = client
-        .get(url.clone())
+/// Reads each certificate from the PEM-encoded CA bundle
+fn read_ca_bundle(path: &PathBuf) -> Result<Vec<Certificate>> {
+    const END: &str = "-----END CERTIFICATE-----";
+
+    let pem = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read CA bundle {}", path.display()))?;
+
+    let certs = pem
+        .split_inclusive(END)
+        .filter(|c| c.contains(END))
+        .map(|c| {
+            Certificate::from_pem(c.trim().as_bytes())
+                .with_context(|| format!("Failed to parse certificate in {}", path.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if certs.is_empty() {
+        bail!("No certificates found in CA bundle {}", path.display());
+    }
+
+    Ok(certs)
+}
+
+/// Sends the request and returns the response body, failing on an error status.
+///
+/// The supplied url is used in error messages, the request url is omitted
+/// as it may contain credentials (eg SAS tokens).
+pub(crate) fn send(request: RequestBuilder, url: &Url) -> Result<Vec<u8>> {
+    let response = request
         .timeout(Duration::from_secs(30))
         .send()
+        .map_err(|e| e.without_url())
         .with_context(|| format!("Error during request to {}", url))?;
 
-    let response = response.error_for_status()?;
+    let response = response
+        .error_for_status()
+        .map_err(|e| e.without_url())
+        .with_context(|| format!("Request to {} failed", url))?;
 
     Ok(response.bytes()?.to_vec())
 }
+
+/// Creates a client for transferring large files, which does not time out once connected
+pub(crate) fn transfer_client() -> Result<Client> {
+    reqwest::blocking::Client::builder()
+        .connect_timeout(HttpOptions::default().connect_timeout)
+        .timeout(None)
+        .user_agent("Ansilo/v1")
+        .build()
+        .context("Failed to build http client")
+}
+
+/// Sends the request and returns the response, failing on an error status.
+///
+/// Unlike [`send`] the body is not read so it can be streamed.
+pub(crate) fn send_streaming(request: RequestBuilder, url: &Url) -> Result<Response> {
+    let response = request
+        .send()
+        .map_err(|e| e.without_url())
+        .with_context(|| format!("Error during request to {}", url))?;
+
+    response
+        .error_for_status()
+        .map_err(|e| e.without_url())
+        .with_context(|| format!("Request to {} failed", url))
+}
+
+#[cfg(test)]
+mod tests {
+    use httpmock::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_http_options_from_fragment() {
+        assert_eq!(
+            HttpOptions::from_fragment(&Url::parse("https://host/path").unwrap()).unwrap(),
+            HttpOptions::default()
+        );
+
+        assert_eq!(
+            HttpOptions::from_fragment(
+                &Url::parse("https://host/path#header.Authorization=Bearer%20abc&header.X-Test=a+b&timeout=10&connect_timeout=5&retries=3&ca_cert=/etc/ssl/ca.pem")
+                    .unwrap()
+            )
+            .unwrap(),
+            HttpOptions {
+                headers: vec![
+                    ("Authorization".into(), "Bearer abc".into()),
+                    ("X-Test".into(), "a b".into()),
+                ],
+                timeout: Duration::from_secs(10),
+                connect_timeout: Duration::from_secs(5),
+                retries: 3,
+                ca_cert: Some("/etc/ssl/ca.pem".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_http_options_from_fragment_invalid() {
+        HttpOptions::from_fragment(&Url::parse("https://host/path#unknown=1").unwrap())
+            .unwrap_err();
+        HttpOptions::from_fragment(&Url::parse("https://host/path#timeout=abc").unwrap())
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_http_get_with_headers() {
+        let server = MockServer::start();
+
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/secret")
+                .header("authorization", "Bearer abc");
+            then.status(200).body("secret");
+        });
+
+        let res = get_http(
+            Url::parse(&format!(
+                "{}/secret#header.Authorization=Bearer%20abc",
+                server.base_url()
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+
+        mock.assert();
+        assert_eq!(res, b"secret".to_vec());
+    }
+
+    #[test]
+    fn test_http_get_retries() {
+        let server = MockServer::start();
+
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/unavailable");
+            then.status(503);
+        });
+
+        get_http(Url::parse(&format!("{}/unavailable#retries=2", server.base_url())).unwrap())
+            .unwrap_err();
+
+        mock.assert_hits(3);
+    }
+
+    #[test]
+    fn test_http_get_no_retry_on_client_error() {
+        let server = MockServer::start();
+
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/forbidden");
+            then.status(403);
+        });
+
+        get_http(Url::parse(&format!("{}/forbidden#retries=2", server.base_url())).unwrap())
+            .unwrap_err();
+
+        mock.assert_hits(1);
+    }
+
+    #[test]
+    fn test_http_invalid_ca_bundle() {
+        client_with_options(&HttpOptions {
+            ca_cert: Some("/this/does/not/exist.pem".into()),
+            ..Default::default()
+        })
+        .unwrap_err();
+    }
+}
@@ -1,19 +1,36 @@
+use std::{fs, path::Path};
+
 use ansilo_core::err::{bail, Context, Error, Result};
 use reqwest::Url;
 
+mod azure;
+mod data;
+mod env;
 mod file;
+mod gcs;
 mod http;
+mod s3;
 mod shell;
+mod vault;
+
+pub use http::HttpOptions;
 
 /// Retrieves the contents from the supplied URL.
 ///
-/// We current support http(s):// and file:// protocols
+/// We current support http(s)://, file://, sh://, env://, gcs://, azure://, s3://, vault:// and data: protocols.
+/// Options for http(s) requests can be supplied in the url fragment, see [`HttpOptions`].
 pub fn get(url: impl Into<String>) -> Result<Vec<u8>> {
     let url: String = url.into();
     let url = Url::parse(&url).with_context(|| format!("Failed to parse URL: {}", url))?;
 
     match url.scheme() {
         "http" | "https" => http::get_http(url),
+        "env" => env::get_env(url),
+        "data" => data::get_data(url),
+        "gcs" | "gs" => gcs::get_gcs(url),
+        "azure" => azure::get_azure(url),
+        "s3" => s3::get_s3(url),
+        "vault" => vault::get_vault(url),
         "file" => file::get_file(
             url.to_file_path()
                 .map_err(|_| Error::msg("Failed to get file path from URL"))?,
@@ -37,6 +54,59 @@ pub fn get(url: impl Into<String>) -> Result<Vec<u8>> {
     }
 }
 
+/// Retrieves the contents from the supplied http(s) URL using the supplied options
+pub fn get_with_options(url: impl Into<String>, options: &HttpOptions) -> Result<Vec<u8>> {
+    let url: String = url.into();
+    let url = Url::parse(&url).with_context(|| format!("Failed to parse URL: {}", url))?;
+
+    match url.scheme() {
+        "http" | "https" => http::get_http_with_options(url, options),
+        _ => bail!(
+            "Options are only supported for http(s) urls, found protocol '{}'",
+            url.scheme()
+        ),
+    }
+}
+
+/// Downloads the contents of the supplied URL to the file at `path`.
+///
+/// s3:// urls are streamed to the file, other protocols supported by [`get`] are read into memory first.
+pub fn download(url: impl Into<String>, path: &Path) -> Result<()> {
+    let url: String = url.into();
+    let parsed = Url::parse(&url).with_context(|| format!("Failed to parse URL: {}", url))?;
+
+    match parsed.scheme() {
+        "s3" => s3::download_s3(parsed, path),
+        _ => fs::write(path, get(url)?)
+            .with_context(|| format!("Failed to write file {}", path.display())),
+    }
+}
+
+/// Uploads the file at `path` to the supplied URL.
+///
+/// We currently support the s3:// and file:// protocols.
+pub fn upload(url: impl Into<String>, path: &Path) -> Result<()> {
+    let url: String = url.into();
+    let url = Url::parse(&url).with_context(|| format!("Failed to parse URL: {}", url))?;
+
+    match url.scheme() {
+        "s3" => s3::upload_s3(url, path),
+        "file" => {
+            let dest = url
+                .to_file_path()
+                .map_err(|_| Error::msg("Failed to get file path from URL"))?;
+            fs::copy(path, &dest)
+                .with_context(|| format!("Failed to copy file to {}", dest.display()))?;
+            Ok(())
+        }
+        _ => bail!(
+            "Uploading to URL protocol '{}' is not supported: {}",
+            url.scheme(),
+            url
+        ),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
@@ -114,6 +184,51 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_url_get_env() {
+        std::env::set_var("ANSILO_TEST_URL_ENV_VAR", "secret value");
+        assert_eq!(
+            get("env://ANSILO_TEST_URL_ENV_VAR").unwrap(),
+            b"secret value".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_url_get_env_not_set() {
+        assert_eq!(
+            get("env://ANSILO_TEST_URL_ENV_VAR_NOT_SET")
+                .unwrap_err()
+                .to_string(),
+            "Environment variable 'ANSILO_TEST_URL_ENV_VAR_NOT_SET' is not set"
+        );
+    }
+
+    #[test]
+    fn test_url_download_and_upload_file() {
+        fs::write("/tmp/ansilo-example-upload.txt", "file content").unwrap();
+
+        upload(
+            "file:///tmp/ansilo-example-uploaded.txt",
+            Path::new("/tmp/ansilo-example-upload.txt"),
+        )
+        .unwrap();
+        download(
+            "file:///tmp/ansilo-example-uploaded.txt",
+            Path::new("/tmp/ansilo-example-downloaded.txt"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read("/tmp/ansilo-example-downloaded.txt").unwrap(),
+            b"file content".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_url_upload_unsupported() {
+        upload("https://example.com/file", Path::new("/tmp/file")).unwrap_err();
+    }
+
     #[test]
     fn test_sh_run_bin_true() {
         assert_eq!(get("sh:///bin/true").unwrap(), Vec::<u8>::new());
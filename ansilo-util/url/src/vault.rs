@@ -0,0 +1,286 @@
+use std::{env, fs};
+
+use ansilo_core::err::{bail, ensure, Context, Result};
+use reqwest::{blocking::Client, Url};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::http;
+
+/// The path of the service account token when running in kubernetes
+const K8S_TOKEN_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
+
+/// Gets the secret at the supplied vault://mount/path/to/secret#field url.
+///
+/// Secrets are read from the KV v2 secrets engine. If a field is specified its
+/// value is returned, otherwise all fields of the secret are returned as JSON.
+pub(crate) fn get_vault(url: Url) -> Result<Vec<u8>> {
+    let mount = url.host_str().context("Expected mount in vault:// url")?;
+    let path = url.path().trim_start_matches('/');
+    ensure!(!path.is_empty(), "Expected secret path in vault:// url");
+
+    let settings = VaultSettings::from_env()?;
+    let client = http::client()?;
+
+    let secret = read_secret(&client, &settings, mount, path)?;
+
+    match url.fragment() {
+        Some(field) => match secret.get(field) {
+            Some(Value::String(s)) => Ok(s.as_bytes().to_vec()),
+            Some(val) => Ok(val.to_string().into_bytes()),
+            None => {
+                bail!("Vault secret '{path}' (mount '{mount}') does not contain field '{field}'")
+            }
+        },
+        None => Ok(Value::Object(secret).to_string().into_bytes()),
+    }
+}
+
+/// Connection settings for vault, read from the standard VAULT_* env vars
+struct VaultSettings {
+    /// The address of vault
+    addr: String,
+    /// The vault namespace
+    namespace: Option<String>,
+    /// The method used to authenticate
+    auth: VaultAuth,
+}
+
+enum VaultAuth {
+    Token(String),
+    AppRole {
+        mount: String,
+        role_id: String,
+        secret_id: String,
+    },
+    Kubernetes {
+        mount: String,
+        role: String,
+        jwt: String,
+    },
+}
+
+impl VaultSettings {
+    /// Reads the settings from the environment:
+    ///
+    ///  - `VAULT_ADDR` (required) and `VAULT_NAMESPACE`
+    ///  - `VAULT_TOKEN` for token auth
+    ///  - `VAULT_ROLE_ID` and `VAULT_SECRET_ID` for approle auth
+    ///  - `VAULT_K8S_ROLE` for kubernetes auth, using the pod's service account token
+    ///
+    /// The mount of the auth method can be set using `VAULT_AUTH_MOUNT`.
+    fn from_env() -> Result<Self> {
+        let addr = env::var("VAULT_ADDR").context("VAULT_ADDR must be set to use vault:// urls")?;
+        let namespace = env::var("VAULT_NAMESPACE").ok();
+        let auth_mount = env::var("VAULT_AUTH_MOUNT").ok();
+
+        let auth = if let Ok(token) = env::var("VAULT_TOKEN") {
+            VaultAuth::Token(token)
+        } else if let (Ok(role_id), Ok(secret_id)) =
+            (env::var("VAULT_ROLE_ID"), env::var("VAULT_SECRET_ID"))
+        {
+            VaultAuth::AppRole {
+                mount: auth_mount.unwrap_or_else(|| "approle".into()),
+                role_id,
+                secret_id,
+            }
+        } else if let Ok(role) = env::var("VAULT_K8S_ROLE") {
+            let jwt = fs::read_to_string(K8S_TOKEN_PATH).with_context(|| {
+                format!("Failed to read service account token {K8S_TOKEN_PATH}")
+            })?;
+
+            VaultAuth::Kubernetes {
+                mount: auth_mount.unwrap_or_else(|| "kubernetes".into()),
+                role,
+                jwt: jwt.trim().into(),
+            }
+        } else {
+            bail!("No vault credentials found, set VAULT_TOKEN, VAULT_ROLE_ID/VAULT_SECRET_ID or VAULT_K8S_ROLE");
+        };
+
+        Ok(Self {
+            addr,
+            namespace,
+            auth,
+        })
+    }
+
+    fn url(&self, path: &str) -> Result<Url> {
+        Url::parse(&format!("{}/v1/{}", self.addr.trim_end_matches('/'), path))
+            .with_context(|| format!("Invalid vault address {}", self.addr))
+    }
+}
+
+#[derive(Deserialize)]
+struct LoginResponse {
+    auth: LoginAuth,
+}
+
+#[derive(Deserialize)]
+struct LoginAuth {
+    client_token: String,
+}
+
+#[derive(Deserialize)]
+struct SecretResponse {
+    data: SecretData,
+}
+
+#[derive(Deserialize)]
+struct SecretData {
+    data: Map<String, Value>,
+}
+
+/// Retrieves a vault token using the configured auth method
+fn login(client: &Client, settings: &VaultSettings) -> Result<String> {
+    let (mount, body) = match &settings.auth {
+        VaultAuth::Token(token) => return Ok(token.clone()),
+        VaultAuth::AppRole {
+            mount,
+            role_id,
+            secret_id,
+        } => (mount, json!({ "role_id": role_id, "secret_id": secret_id })),
+        VaultAuth::Kubernetes { mount, role, jwt } => (mount, json!({ "role": role, "jwt": jwt })),
+    };
+
+    let url = settings.url(&format!("auth/{mount}/login"))?;
+    let mut request = client.post(url.clone()).body(body.to_string());
+    if let Some(ns) = settings.namespace.as_ref() {
+        request = request.header("X-Vault-Namespace", ns);
+    }
+
+    let response = http::send(request, &url).context("Failed to authenticate with vault")?;
+    let response: LoginResponse =
+        serde_json::from_slice(&response).context("Failed to parse vault login response")?;
+
+    Ok(response.auth.client_token)
+}
+
+/// Reads the secret from the KV v2 secrets engine
+fn read_secret(
+    client: &Client,
+    settings: &VaultSettings,
+    mount: &str,
+    path: &str,
+) -> Result<Map<String, Value>> {
+    let token = login(client, settings)?;
+
+    let url = settings.url(&format!("{mount}/data/{path}"))?;
+    let mut request = client.get(url.clone()).header("X-Vault-Token", token);
+    if let Some(ns) = settings.namespace.as_ref() {
+        request = request.header("X-Vault-Namespace", ns);
+    }
+
+    let response = http::send(request, &url)
+        .with_context(|| format!("Failed to retrieve vault secret '{path}' (mount '{mount}')"))?;
+    let response: SecretResponse =
+        serde_json::from_slice(&response).context("Failed to parse vault secret response")?;
+
+    Ok(response.data.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use httpmock::prelude::*;
+
+    use super::*;
+
+    fn settings(server: &MockServer, auth: VaultAuth) -> VaultSettings {
+        VaultSettings {
+            addr: server.base_url(),
+            namespace: None,
+            auth,
+        }
+    }
+
+    fn mock_secret<'a>(server: &'a MockServer, token: &str) -> httpmock::Mock<'a> {
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/v1/secret/data/db/creds")
+                .header("x-vault-token", token);
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({
+                    "data": {
+                        "data": {"password": "mysupersecret", "port": 5432},
+                        "metadata": {"version": 1}
+                    }
+                }));
+        })
+    }
+
+    #[test]
+    fn test_vault_read_secret_token() {
+        let server = MockServer::start();
+        let mock = mock_secret(&server, "tok");
+
+        let secret = read_secret(
+            &http::client().unwrap(),
+            &settings(&server, VaultAuth::Token("tok".into())),
+            "secret",
+            "db/creds",
+        )
+        .unwrap();
+
+        mock.assert();
+        assert_eq!(secret.get("password"), Some(&json!("mysupersecret")));
+        assert_eq!(secret.get("port"), Some(&json!(5432)));
+    }
+
+    #[test]
+    fn test_vault_read_secret_approle() {
+        let server = MockServer::start();
+
+        let login = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/auth/approle/login")
+                .json_body(json!({"role_id": "role", "secret_id": "secret"}));
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!({"auth": {"client_token": "approle-tok"}}));
+        });
+        let mock = mock_secret(&server, "approle-tok");
+
+        let secret = read_secret(
+            &http::client().unwrap(),
+            &settings(
+                &server,
+                VaultAuth::AppRole {
+                    mount: "approle".into(),
+                    role_id: "role".into(),
+                    secret_id: "secret".into(),
+                },
+            ),
+            "secret",
+            "db/creds",
+        )
+        .unwrap();
+
+        login.assert();
+        mock.assert();
+        assert_eq!(secret.get("password"), Some(&json!("mysupersecret")));
+    }
+
+    #[test]
+    fn test_vault_read_secret_not_found() {
+        let server = MockServer::start();
+
+        server.mock(|when, then| {
+            when.method(GET);
+            then.status(404);
+        });
+
+        read_secret(
+            &http::client().unwrap(),
+            &settings(&server, VaultAuth::Token("tok".into())),
+            "secret",
+            "missing",
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn test_vault_invalid_url() {
+        get_vault(Url::parse("vault://secret").unwrap()).unwrap_err();
+    }
+}
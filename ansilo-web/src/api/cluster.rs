@@ -0,0 +1,26 @@
+use std::sync::Arc;
+
+use ansilo_core::web::cluster::ClusterStatus;
+use axum::{extract::State, routing, Json, Router};
+use hyper::StatusCode;
+
+use crate::HttpApiState;
+
+/// Returns the live members of the cluster and the current leader
+async fn handler(
+    State(state): State<Arc<HttpApiState>>,
+) -> Result<Json<ClusterStatus>, (StatusCode, &'static str)> {
+    let status = state
+        .cluster()
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            "Clustering is not configured on this node",
+        ))?
+        .status();
+
+    Ok(Json(status))
+}
+
+pub(super) fn router() -> Router<Arc<HttpApiState>> {
+    Router::new().route("/", routing::get(handler))
+}
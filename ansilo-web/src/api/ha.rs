@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use ansilo_core::{config::HaRole, web::ha::HaStatus};
+use axum::{extract::State, routing, Json, Router};
+use hyper::StatusCode;
+
+use crate::HttpApiState;
+
+/// Returns the role of this node in the high-availability pair.
+///
+/// Only the primary responds with a 200 status so load balancers and
+/// health-checked DNS records can direct clients to the primary.
+async fn handler(
+    State(state): State<Arc<HttpApiState>>,
+) -> Result<(StatusCode, Json<HaStatus>), (StatusCode, &'static str)> {
+    let status = state
+        .ha()
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            "High availability is not configured on this node",
+        ))?
+        .status();
+
+    Ok((
+        if status.role == HaRole::Primary {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        },
+        Json(status),
+    ))
+}
+
+pub(super) fn router() -> Router<Arc<HttpApiState>> {
+    Router::new().route("/", routing::get(handler))
+}
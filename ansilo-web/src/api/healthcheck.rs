@@ -1,7 +1,7 @@
 use std::{collections::HashMap, sync::Arc};
 
 use ansilo_logging::warn;
-use ansilo_util_health::HealthStatus;
+use ansilo_util_health::{HealthStatus, SubsystemHistory};
 use axum::{extract::State, routing, Json, Router};
 use hyper::StatusCode;
 use serde::{Deserialize, Serialize};
@@ -13,6 +13,12 @@ pub struct HealthCheck {
     pub subsystems: HashMap<String, HealthStatus>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthHistory {
+    /// The status transitions of each subsystem over the last 24 hours
+    pub subsystems: HashMap<String, SubsystemHistory>,
+}
+
 async fn handler(
     State(state): State<Arc<HttpApiState>>,
 ) -> Result<(StatusCode, Json<HealthCheck>), (StatusCode, &'static str)> {
@@ -24,7 +30,7 @@ async fn handler(
         )
     })?;
 
-    let healthy = subsystems.values().all(|h| h.healthy);
+    let healthy = subsystems.values().all(|h| h.ok());
 
     Ok((
         if healthy {
@@ -36,6 +42,22 @@ async fn handler(
     ))
 }
 
+async fn history(
+    State(state): State<Arc<HttpApiState>>,
+) -> Result<Json<HealthHistory>, (StatusCode, &'static str)> {
+    let subsystems = state.health().history().map_err(|e| {
+        warn!("Failed to get health history: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to get health history",
+        )
+    })?;
+
+    Ok(Json(HealthHistory { subsystems }))
+}
+
 pub(super) fn router() -> Router<Arc<HttpApiState>> {
-    Router::new().route("/", routing::get(handler))
+    Router::new()
+        .route("/", routing::get(handler))
+        .route("/history", routing::get(history))
 }
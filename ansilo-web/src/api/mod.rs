@@ -4,6 +4,8 @@ use axum::Router;
 
 use crate::HttpApiState;
 
+pub mod cluster;
+pub mod ha;
 pub mod healthcheck;
 pub mod v1;
 pub mod version;
@@ -12,5 +14,7 @@ pub(super) fn router(state: Arc<HttpApiState>) -> Router<Arc<HttpApiState>> {
     Router::new()
         .nest("/v1", v1::router(state.clone()))
         .nest("/health", healthcheck::router())
+        .nest("/ha", ha::router())
+        .nest("/cluster", cluster::router())
         .nest("/version", version::router())
 }
@@ -30,6 +30,7 @@ pub(super) fn to_catalog(
         name: e.name,
         description: e.description,
         tags: e.tags,
+        classifications: e.classifications,
         attributes: e
             .attributes
             .into_iter()
@@ -11,7 +11,7 @@ pub mod query;
 
 pub(super) fn router(state: Arc<HttpApiState>) -> Router<Arc<HttpApiState>> {
     Router::new()
-        .nest("/node", node::router())
+        .nest("/node", node::router(state.clone()))
         .nest("/catalog", catalog::router(state.clone()))
         .nest("/auth", auth::router())
         .nest("/query", query::router(state.clone()))
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use ansilo_logging::{error, info};
+use axum::{
+    extract::{Query, State},
+    Extension,
+};
+use hyper::StatusCode;
+use serde::Deserialize;
+
+use crate::{middleware::pg_auth::ClientAuthenticatedPostgresConnection, HttpApiState};
+
+use super::require_admin;
+
+#[derive(Debug, Deserialize)]
+pub(super) struct InvalidateParams {
+    /// The data source to invalidate, if omitted all data sources are invalidated
+    source: Option<String>,
+}
+
+/// Invalidates the cached remote metadata, such as entity structures and size estimates,
+/// so it is retrieved from the data source during the next query.
+/// This is restricted to users which are members of the admin role.
+pub(super) async fn invalidate(
+    State(state): State<Arc<HttpApiState>>,
+    Extension(con): Extension<ClientAuthenticatedPostgresConnection>,
+    Query(params): Query<InvalidateParams>,
+) -> Result<StatusCode, (StatusCode, &'static str)> {
+    require_admin(&con, "cache invalidation").await?;
+
+    if let Some(source) = params.source.as_ref() {
+        if !state.conf().sources.iter().any(|s| &s.id == source) {
+            return Err((StatusCode::NOT_FOUND, "Unknown data source"));
+        }
+    }
+
+    info!(
+        "Metadata cache invalidation of {} requested via http api",
+        params.source.as_deref().unwrap_or("all data sources")
+    );
+    state
+        .metadata_cache()
+        .invalidate(params.source.as_deref())
+        .map_err(|e| {
+            error!("Failed to invalidate metadata cache: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal error")
+        })?;
+
+    // Ensure the other members of the cluster also invalidate their cache
+    if let Some(cluster) = state.cluster() {
+        cluster.invalidate_cache(params.source.as_deref());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,11 +1,59 @@
 use std::sync::Arc;
 
+use ansilo_logging::{error, warn};
+use ansilo_pg::PG_ADMIN_USER;
 use axum::{routing, Router};
+use hyper::StatusCode;
 
-use crate::HttpApiState;
+use crate::{
+    middleware::pg_auth::{self, ClientAuthenticatedPostgresConnection},
+    HttpApiState,
+};
 
+pub mod cache;
 pub mod get;
+pub mod pools;
+pub mod queries;
+pub mod reload;
 
-pub(super) fn router() -> Router<Arc<HttpApiState>> {
-    Router::new().route("/", routing::get(get::handler))
+pub(super) fn router(state: Arc<HttpApiState>) -> Router<Arc<HttpApiState>> {
+    // Only the routes added before the auth layer require authentication
+    Router::new()
+        .route("/reload", routing::post(reload::handler))
+        .route("/cache/invalidate", routing::post(cache::invalidate))
+        .route("/queries", routing::get(queries::handler))
+        .route_layer({
+            axum::middleware::from_fn(move |req, next| pg_auth::auth(req, next, state.clone()))
+        })
+        .route("/", routing::get(get::handler))
+        .route("/pools", routing::get(pools::handler))
+}
+
+/// Checks the authenticated user is a member of the admin role
+pub(super) async fn require_admin(
+    con: &ClientAuthenticatedPostgresConnection,
+    action: &str,
+) -> Result<(), (StatusCode, &'static str)> {
+    let con = con.0.lock().await;
+
+    let is_admin: bool = con
+        .client_async()
+        .await
+        .query_one(
+            "SELECT pg_has_role(current_user, $1, 'MEMBER')",
+            &[&PG_ADMIN_USER],
+        )
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal error")
+        })?
+        .get(0);
+
+    if !is_admin {
+        warn!("Rejected {action} request from non-admin user");
+        return Err((StatusCode::FORBIDDEN, "Forbidden"));
+    }
+
+    Ok(())
 }
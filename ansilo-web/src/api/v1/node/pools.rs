@@ -0,0 +1,16 @@
+use std::sync::Arc;
+
+use ansilo_core::{err::Result, web::node::*};
+use axum::{extract::State, Json};
+use hyper::StatusCode;
+
+use crate::HttpApiState;
+
+// Unauthenticated endpoint to retrieve the utilisation of the postgres connection pools
+pub(super) async fn handler(
+    State(state): State<Arc<HttpApiState>>,
+) -> Result<Json<NodePools>, (StatusCode, &'static str)> {
+    Ok(Json(NodePools {
+        pools: state.pools().status(),
+    }))
+}
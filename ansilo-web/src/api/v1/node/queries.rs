@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use ansilo_core::web::query_log::RemoteQuery;
+use ansilo_logging::error;
+use axum::{
+    extract::{Query, State},
+    Extension, Json,
+};
+use hyper::StatusCode;
+use serde::Deserialize;
+
+use crate::{middleware::pg_auth::ClientAuthenticatedPostgresConnection, HttpApiState};
+
+use super::require_admin;
+
+#[derive(Debug, Default, Deserialize)]
+pub(super) struct QueriesParams {
+    /// Only return queries sent to this data source
+    source: Option<String>,
+    /// Only return queries issued by this session
+    session: Option<u32>,
+    /// Only return queries recorded after the query with this id
+    after: Option<u64>,
+}
+
+/// Returns the most recent queries sent to the data sources, newest first.
+/// The query parameters are redacted, though the queries themselves may contain
+/// sensitive literals so this is restricted to users which are members of the admin role.
+pub(super) async fn handler(
+    State(state): State<Arc<HttpApiState>>,
+    Extension(con): Extension<ClientAuthenticatedPostgresConnection>,
+    Query(params): Query<QueriesParams>,
+) -> Result<Json<Vec<RemoteQuery>>, (StatusCode, &'static str)> {
+    require_admin(&con, "query log").await?;
+
+    let queries = state.query_log().recent().map_err(|e| {
+        error!("Failed to retrieve remote query log: {:?}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Internal error")
+    })?;
+
+    Ok(Json(filter(queries, &params)))
+}
+
+fn filter(queries: Vec<RemoteQuery>, params: &QueriesParams) -> Vec<RemoteQuery> {
+    queries
+        .into_iter()
+        .rev()
+        .filter(|q| params.source.as_ref().map_or(true, |s| &q.data_source == s))
+        .filter(|q| params.session.map_or(true, |s| q.session_id == Some(s)))
+        .filter(|q| params.after.map_or(true, |id| q.id > id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn mock_query(id: u64, data_source: &str, session_id: Option<u32>) -> RemoteQuery {
+        RemoteQuery {
+            id,
+            data_source: data_source.into(),
+            session_id,
+            username: None,
+            query: "SELECT 1".into(),
+            params: vec![],
+            other: HashMap::new(),
+            started_at: 0,
+            duration_ms: None,
+        }
+    }
+
+    fn ids(queries: Vec<RemoteQuery>) -> Vec<u64> {
+        queries.into_iter().map(|q| q.id).collect()
+    }
+
+    #[test]
+    fn test_filter_queries() {
+        let queries = vec![
+            mock_query(0, "a", Some(1)),
+            mock_query(1, "b", Some(1)),
+            mock_query(2, "a", Some(2)),
+            mock_query(3, "a", None),
+        ];
+
+        assert_eq!(
+            ids(filter(queries.clone(), &QueriesParams::default())),
+            vec![3, 2, 1, 0]
+        );
+        assert_eq!(
+            ids(filter(
+                queries.clone(),
+                &QueriesParams {
+                    source: Some("a".into()),
+                    ..Default::default()
+                }
+            )),
+            vec![3, 2, 0]
+        );
+        assert_eq!(
+            ids(filter(
+                queries.clone(),
+                &QueriesParams {
+                    session: Some(1),
+                    ..Default::default()
+                }
+            )),
+            vec![1, 0]
+        );
+        assert_eq!(
+            ids(filter(
+                queries,
+                &QueriesParams {
+                    source: Some("a".into()),
+                    after: Some(0),
+                    ..Default::default()
+                }
+            )),
+            vec![3, 2]
+        );
+    }
+}
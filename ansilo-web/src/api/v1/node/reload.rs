@@ -0,0 +1,30 @@
+use ansilo_logging::{error, info};
+use axum::Extension;
+use hyper::StatusCode;
+use nix::{
+    sys::signal::{kill, SIGHUP},
+    unistd::getpid,
+};
+
+use crate::middleware::pg_auth::ClientAuthenticatedPostgresConnection;
+
+use super::require_admin;
+
+/// Triggers a reload of the node configuration.
+///
+/// The reload is performed by the main process when it receives SIGHUP,
+/// the result of which is written to the logs.
+/// This is restricted to users which are members of the admin role.
+pub(super) async fn handler(
+    Extension(con): Extension<ClientAuthenticatedPostgresConnection>,
+) -> Result<StatusCode, (StatusCode, &'static str)> {
+    require_admin(&con, "config reload").await?;
+
+    info!("Config reload requested via http api");
+    kill(getpid(), SIGHUP).map_err(|e| {
+        error!("Failed to signal config reload: {:?}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Internal error")
+    })?;
+
+    Ok(StatusCode::ACCEPTED)
+}
@@ -228,17 +228,20 @@ mod tests {
     use std::time::Duration;
 
     use ansilo_auth::Authenticator;
+    use ansilo_cluster::ClusterState;
     use ansilo_core::{
-        config::{NodeConfig, ResourceConfig},
+        config::{HaRole, NodeConfig, ResourceConfig},
         data::chrono::{DateTime, Utc},
     };
     use ansilo_pg::{
         conf::PostgresConf,
         connection::PostgresConnectionPool,
+        fdw::{cache::MetadataCache, log::RemoteQueryLog},
         handler::PostgresConnectionHandler,
         low_level::multi_pool::{
             MultiUserPostgresConnectionPool, MultiUserPostgresConnectionPoolConfig,
         },
+        replication::HaState,
         PostgresConnectionPools,
     };
     use ansilo_util_health::Health;
@@ -258,6 +261,8 @@ mod tests {
             fdw_socket_path: "unused".into(),
             app_users: vec![],
             init_db_sql: vec![],
+            catalogs: vec![],
+            replication: None,
         }));
 
         let pools = PostgresConnectionPools::new(
@@ -267,6 +272,7 @@ mod tests {
                 pg,
                 users: vec![],
                 database: "unused".into(),
+                min_cons_per_user: 0,
                 max_cons_per_user: 10,
                 connect_timeout: Duration::from_secs(1),
             })
@@ -279,6 +285,8 @@ mod tests {
             pools.clone(),
             PostgresConnectionHandler::new(authenticator, pools),
             Health::new(),
+            MetadataCache::new(),
+            RemoteQueryLog::new(),
             VersionInfo::new("test", DateTime::<Utc>::MIN_UTC),
         )
     }
@@ -319,6 +327,136 @@ mod tests {
         assert_eq!(&body[..], r#"{"subsystems":{}}"#.as_bytes());
     }
 
+    #[tokio::test]
+    async fn test_health_history() {
+        let router = HttpApi::router(mock_state());
+
+        let res = router
+            .oneshot(
+                Request::builder()
+                    .uri("/api/health/history")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&body[..], r#"{"subsystems":{}}"#.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_ha_not_configured() {
+        let router = HttpApi::router(mock_state());
+
+        let res = router
+            .oneshot(
+                Request::builder()
+                    .uri("/api/ha")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_ha_status() {
+        let ha = HaState::new("node-a", HaRole::Standby);
+        let router = HttpApi::router(mock_state().with_ha(ha.clone()));
+
+        let res = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/ha")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(
+            &body[..],
+            r#"{"node_id":"node-a","role":"standby","primary_since":null}"#.as_bytes()
+        );
+
+        ha.set_role(HaRole::Primary);
+
+        let res = router
+            .oneshot(
+                Request::builder()
+                    .uri("/api/ha")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_cluster_status() {
+        let router = HttpApi::router(mock_state());
+
+        let res = router
+            .oneshot(
+                Request::builder()
+                    .uri("/api/cluster")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let router = HttpApi::router(mock_state().with_cluster(ClusterState::new("node-a")));
+
+        let res = router
+            .oneshot(
+                Request::builder()
+                    .uri("/api/cluster")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(
+            &body[..],
+            r#"{"node_id":"node-a","leader":null,"members":[]}"#.as_bytes()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_log_requires_auth() {
+        let router = HttpApi::router(mock_state());
+
+        let res = router
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v1/node/queries")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
     #[tokio::test]
     async fn test_non_existant_endpoint() {
         let router = HttpApi::router(mock_state());
@@ -1,8 +1,14 @@
+use ansilo_cluster::ClusterState;
 use ansilo_core::{
     config::NodeConfig,
     data::chrono::{DateTime, Utc},
 };
-use ansilo_pg::{handler::PostgresConnectionHandler, PostgresConnectionPools};
+use ansilo_pg::{
+    fdw::{cache::MetadataCache, log::RemoteQueryLog},
+    handler::PostgresConnectionHandler,
+    replication::HaState,
+    PostgresConnectionPools,
+};
 use ansilo_util_health::Health;
 use serde::{Deserialize, Serialize};
 
@@ -17,8 +23,16 @@ pub struct HttpApiState {
     pg_handler: PostgresConnectionHandler,
     /// System health
     health: Health,
+    /// Cache of remote metadata
+    metadata_cache: MetadataCache,
+    /// Log of the queries sent to the data sources
+    query_log: RemoteQueryLog,
     /// Version info
     version_info: VersionInfo,
+    /// The role of the node, if running as one of a high-availability pair
+    ha: Option<HaState>,
+    /// The view of the cluster, if running as a member of a cluster
+    cluster: Option<ClusterState>,
 }
 
 impl HttpApiState {
@@ -27,6 +41,8 @@ impl HttpApiState {
         pools: PostgresConnectionPools,
         pg_handler: PostgresConnectionHandler,
         health: Health,
+        metadata_cache: MetadataCache,
+        query_log: RemoteQueryLog,
         version_info: VersionInfo,
     ) -> Self {
         Self {
@@ -34,10 +50,26 @@ impl HttpApiState {
             pools,
             pg_handler,
             health,
+            metadata_cache,
+            query_log,
             version_info,
+            ha: None,
+            cluster: None,
         }
     }
 
+    /// Exposes the role of the node in a high-availability pair
+    pub fn with_ha(mut self, ha: HaState) -> Self {
+        self.ha = Some(ha);
+        self
+    }
+
+    /// Exposes the membership of the cluster
+    pub fn with_cluster(mut self, cluster: ClusterState) -> Self {
+        self.cluster = Some(cluster);
+        self
+    }
+
     pub fn conf(&self) -> &NodeConfig {
         self.conf
     }
@@ -54,9 +86,25 @@ impl HttpApiState {
         &self.health
     }
 
+    pub fn metadata_cache(&self) -> &MetadataCache {
+        &self.metadata_cache
+    }
+
+    pub fn query_log(&self) -> &RemoteQueryLog {
+        &self.query_log
+    }
+
     pub fn version_info(&self) -> &VersionInfo {
         &self.version_info
     }
+
+    pub fn ha(&self) -> Option<&HaState> {
+        self.ha.as_ref()
+    }
+
+    pub fn cluster(&self) -> Option<&ClusterState> {
+        self.cluster.as_ref()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
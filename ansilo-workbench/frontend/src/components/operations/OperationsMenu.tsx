@@ -42,6 +42,16 @@ export default function OperationsMenu(props: Props) {
             <ListItemText primary="Status" />
           </ListItemButton>
         </Link>
+        <Link href="/operations/remote-queries">
+          <ListItemButton
+            selected={router.asPath.startsWith("/operations/remote-queries")}
+          >
+            <ListItemIcon>
+              <LogsIcon />
+            </ListItemIcon>
+            <ListItemText primary="Remote Queries" />
+          </ListItemButton>
+        </Link>
       </List>
     </Paper>
   );
@@ -0,0 +1,193 @@
+import Paper from "@mui/material/Paper";
+import Box from "@mui/material/Box";
+import Typography from "@mui/material/Typography";
+import Alert from "@mui/material/Alert";
+import TextField from "@mui/material/TextField";
+import FormControlLabel from "@mui/material/FormControlLabel";
+import Switch from "@mui/material/Switch";
+import TableContainer from "@mui/material/TableContainer";
+import Table from "@mui/material/Table";
+import TableHead from "@mui/material/TableHead";
+import TableRow from "@mui/material/TableRow";
+import TableCell from "@mui/material/TableCell";
+import TableBody from "@mui/material/TableBody";
+import _ from "lodash";
+import { useCallback, useEffect, useState } from "react";
+import OperationsMenu from "../OperationsMenu";
+import { Authenticated } from "../../auth/Authenticated";
+import { selectCredentials } from "../../auth/auth.slice";
+import { useAppDispatch, useAppSelector } from "../../../store/hooks";
+import { useInterval } from "../../../util/useInterval";
+import { fetchRemoteQueries, RemoteQuery } from "./remote-queries.api";
+
+const REFRESH_INTERVAL_MS = 2000;
+
+export const RemoteQueries = () => {
+  return (
+    <Box sx={{ flexGrow: "1", display: "flex" }}>
+      <OperationsMenu />
+      <Authenticated>
+        <Box sx={{ flexGrow: 1, display: "flex", padding: 4, minWidth: 0 }}>
+          <Paper
+            sx={{
+              display: "flex",
+              p: 4,
+              flexDirection: "column",
+              flexGrow: 1,
+              minWidth: 0,
+            }}
+            elevation={8}
+          >
+            <RemoteQueriesTable />
+          </Paper>
+        </Box>
+      </Authenticated>
+    </Box>
+  );
+};
+
+const RemoteQueriesTable = () => {
+  const dispatch = useAppDispatch();
+  const creds = useAppSelector(selectCredentials);
+  const [queries, setQueries] = useState<RemoteQuery[]>();
+  const [error, setError] = useState<string>();
+  const [source, setSource] = useState("");
+  const [session, setSession] = useState("");
+  const [live, setLive] = useState(true);
+
+  const refresh = useCallback(async () => {
+    if (!creds) {
+      return;
+    }
+
+    try {
+      setQueries(
+        await fetchRemoteQueries(dispatch, creds, {
+          source: source || undefined,
+          session: session ? parseInt(session) : undefined,
+        })
+      );
+      setError(undefined);
+    } catch (e) {
+      setError(String(e));
+    }
+  }, [dispatch, creds, source, session]);
+
+  useEffect(() => {
+    refresh();
+  }, [refresh]);
+
+  useInterval(() => {
+    if (live) {
+      refresh();
+    }
+  }, REFRESH_INTERVAL_MS);
+
+  return (
+    <>
+      <Typography sx={{ mb: 2 }} variant="h6">
+        Remote Queries
+      </Typography>
+      <Box sx={{ display: "flex", gap: 2, mb: 2, alignItems: "center" }}>
+        <TextField
+          label="Data Source"
+          size="small"
+          value={source}
+          onChange={(e) => setSource(e.target.value)}
+        />
+        <TextField
+          label="Session"
+          size="small"
+          type="number"
+          value={session}
+          onChange={(e) => setSession(e.target.value)}
+        />
+        <FormControlLabel
+          control={
+            <Switch
+              checked={live}
+              onChange={(e) => setLive(e.target.checked)}
+            />
+          }
+          label="Live"
+        />
+      </Box>
+      {error && (
+        <Alert severity="error" sx={{ mb: 2 }}>
+          {error}
+        </Alert>
+      )}
+      {queries && queries.length === 0 && (
+        <Typography variant="body1" color="text.secondary">
+          No remote queries have been recorded
+        </Typography>
+      )}
+      {queries && queries.length > 0 && (
+        <TableContainer sx={{ maxHeight: "70vh" }}>
+          <Table stickyHeader size="small">
+            <TableHead>
+              <TableRow>
+                <TableCell>Time</TableCell>
+                <TableCell>Data Source</TableCell>
+                <TableCell>Session</TableCell>
+                <TableCell>User</TableCell>
+                <TableCell>Duration</TableCell>
+                <TableCell>Query</TableCell>
+                <TableCell>Parameters</TableCell>
+              </TableRow>
+            </TableHead>
+            <TableBody>
+              {queries.map((q) => (
+                <TableRow key={q.id}>
+                  <TableCell>{q.startedAt.toLocaleTimeString()}</TableCell>
+                  <TableCell>{q.dataSource}</TableCell>
+                  <TableCell>
+                    {q.sessionId !== undefined ? (
+                      <a
+                        href="#"
+                        onClick={(e) => {
+                          e.preventDefault();
+                          setSession(String(q.sessionId));
+                        }}
+                      >
+                        {q.sessionId}
+                      </a>
+                    ) : (
+                      "N/A"
+                    )}
+                  </TableCell>
+                  <TableCell>{q.username || "N/A"}</TableCell>
+                  <TableCell>
+                    {q.durationMs !== undefined
+                      ? `${q.durationMs.toFixed(1)}ms`
+                      : "N/A"}
+                  </TableCell>
+                  <TableCell>
+                    <Box
+                      component="pre"
+                      sx={{
+                        m: 0,
+                        whiteSpace: "pre-wrap",
+                        wordBreak: "break-all",
+                      }}
+                    >
+                      {q.query}
+                    </Box>
+                  </TableCell>
+                  <TableCell>
+                    {q.params.map((p, idx) => (
+                      <div key={idx}>{p}</div>
+                    ))}
+                    {_.toPairs(q.other).map(([k, v]) => (
+                      <div key={k}>{`${k}: ${v}`}</div>
+                    ))}
+                  </TableCell>
+                </TableRow>
+              ))}
+            </TableBody>
+          </Table>
+        </TableContainer>
+      )}
+    </>
+  );
+};
@@ -0,0 +1,63 @@
+import qs from "qs";
+import { AppDispatch } from "../../../store/store";
+import { authenticatedFetch } from "../../auth/auth.api";
+import { AuthCredentials } from "../../auth/auth.slice";
+
+export interface RemoteQuery {
+  id: number;
+  dataSource: string;
+  sessionId?: number;
+  username?: string;
+  query: string;
+  params: string[];
+  other: { [key: string]: string };
+  startedAt: Date;
+  durationMs?: number;
+}
+
+export interface RemoteQueryFilters {
+  source?: string;
+  session?: number;
+}
+
+export const fetchRemoteQueries = async (
+  dispatch: AppDispatch,
+  creds: AuthCredentials,
+  filters: RemoteQueryFilters
+): Promise<RemoteQuery[]> => {
+  const res = await authenticatedFetch(
+    dispatch,
+    creds,
+    `/api/v1/node/queries?${qs.stringify(filters, { skipNulls: true })}`,
+    {}
+  );
+
+  if (!res) {
+    throw new Error(`Authentication failure`);
+  }
+
+  if (res.status === 403) {
+    throw new Error(`The query log is only available to admin users`);
+  }
+
+  if (res.status >= 400) {
+    throw new Error(`Unexpected status code from query log: ${res.status}`);
+  }
+
+  const queries = await res.json();
+
+  return queries.map(
+    (q: any) =>
+      ({
+        id: q.id,
+        dataSource: q.data_source,
+        sessionId: q.session_id ?? undefined,
+        username: q.username ?? undefined,
+        query: q.query,
+        params: q.params,
+        other: q.other,
+        startedAt: new Date(q.started_at),
+        durationMs: q.duration_ms ?? undefined,
+      } as RemoteQuery)
+  );
+};
@@ -0,0 +1,14 @@
+import * as React from "react";
+import type { NextPage } from "next";
+import { Template } from "../../../components/template/Template";
+import { RemoteQueries } from "../../../components/operations/remote-queries/RemoteQueries";
+
+const Page: NextPage = () => {
+  return (
+    <Template title="Operations > Remote Queries">
+      <RemoteQueries />
+    </Template>
+  );
+};
+
+export default Page;